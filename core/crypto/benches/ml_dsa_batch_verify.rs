@@ -0,0 +1,102 @@
+//! Benchmarks for parallel ML-DSA batch verification
+//!
+//! Compares serial verification against `BatchVerifier` for a range of batch
+//! sizes and worker thread counts.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use qudag_crypto::ml_dsa::{BatchVerifier, MlDsaKeyPair, MlDsaPublicKey};
+use rand::thread_rng;
+
+const BATCH_SIZES: &[usize] = &[1, 8, 32, 128, 512];
+const THREAD_COUNTS: &[usize] = &[1, 2, 4, 8];
+
+struct Batch {
+    public_key: MlDsaPublicKey,
+    messages: Vec<Vec<u8>>,
+    signatures: Vec<Vec<u8>>,
+}
+
+fn build_batch(size: usize) -> Batch {
+    let mut rng = thread_rng();
+    let keypair = MlDsaKeyPair::generate(&mut rng).expect("key generation failed");
+    let messages: Vec<Vec<u8>> = (0..size)
+        .map(|i| format!("vertex payload {}", i).into_bytes())
+        .collect();
+    let signatures = messages
+        .iter()
+        .map(|m| keypair.sign(m, &mut rng).expect("signing failed"))
+        .collect();
+
+    Batch {
+        public_key: keypair.to_public_key().expect("invalid public key"),
+        messages,
+        signatures,
+    }
+}
+
+fn bench_batch_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ml_dsa_batch_verify");
+
+    for &size in BATCH_SIZES {
+        let batch = build_batch(size);
+        let messages: Vec<&[u8]> = batch.messages.iter().map(Vec::as_slice).collect();
+        let signatures: Vec<&[u8]> = batch.signatures.iter().map(Vec::as_slice).collect();
+        let public_keys = vec![&batch.public_key; size];
+
+        group.throughput(Throughput::Elements(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("serial", size), &size, |b, _| {
+            b.iter(|| {
+                for i in 0..size {
+                    black_box(public_keys[i].verify(messages[i], signatures[i]).is_ok());
+                }
+            })
+        });
+
+        for &threads in THREAD_COUNTS {
+            let verifier = BatchVerifier::new()
+                .with_threads(threads)
+                .with_min_batch_per_thread(1);
+            group.bench_with_input(
+                BenchmarkId::new(format!("parallel_{}_threads", threads), size),
+                &size,
+                |b, _| {
+                    b.iter(|| {
+                        black_box(verifier.verify(
+                            black_box(&messages),
+                            black_box(&signatures),
+                            black_box(&public_keys),
+                        ))
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_early_abort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ml_dsa_batch_verify_early_abort");
+    let size = 128;
+    let mut batch = build_batch(size);
+    batch.signatures[0][10] ^= 0xff;
+
+    let messages: Vec<&[u8]> = batch.messages.iter().map(Vec::as_slice).collect();
+    let signatures: Vec<&[u8]> = batch.signatures.iter().map(Vec::as_slice).collect();
+    let public_keys = vec![&batch.public_key; size];
+
+    for &early_abort in &[false, true] {
+        let verifier = BatchVerifier::new().with_early_abort(early_abort);
+        group.bench_with_input(
+            BenchmarkId::new("early_abort", early_abort),
+            &early_abort,
+            |b, _| b.iter(|| black_box(verifier.verify(&messages, &signatures, &public_keys))),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch_sizes, bench_early_abort);
+criterion_main!(benches);
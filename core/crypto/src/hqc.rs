@@ -58,7 +58,6 @@ pub enum HqcError {
 #[derive(Debug, Clone)]
 pub struct PublicKey {
     inner: Vec<u8>,
-    #[allow(dead_code)]
     params: Parameters,
 }
 
@@ -66,7 +65,6 @@ pub struct PublicKey {
 #[derive(Debug, Clone)]
 pub struct SecretKey {
     inner: Vec<u8>,
-    #[allow(dead_code)]
     params: Parameters,
 }
 
@@ -333,6 +331,11 @@ impl PublicKey {
         self.inner.clone()
     }
 
+    /// Security level this key was created for
+    pub fn security(&self) -> SecurityParameter {
+        self.params.security
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, HqcError> {
         // Default to HQC256 if no other information is available
         let params = Parameters::new(SecurityParameter::Hqc256);
@@ -370,6 +373,11 @@ impl SecretKey {
         self.inner.clone()
     }
 
+    /// Security level this key was created for
+    pub fn security(&self) -> SecurityParameter {
+        self.params.security
+    }
+
     /// Create secret key from bytes with specific security level
     pub fn from_bytes_with_params(
         bytes: &[u8],
@@ -0,0 +1,576 @@
+//! Key rotation and revocation certificates for ML-DSA identity keys.
+//!
+//! A [`KeyRotation`] is signed by the outgoing key and counter-signed by the
+//! incoming key, proving control of both. A [`KeyRevocation`] is signed by
+//! the key it revokes, so it can be generated together with the key and kept
+//! offline until needed.
+//!
+//! Verified certificates are collected in a [`RevocationList`], which
+//! resolvers, handshakes and RPC authentication consult before trusting a key.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use qudag_crypto::key_certificate::{KeyRevocation, RevocationList, RevocationReason};
+//! use qudag_crypto::ml_dsa::MlDsaKeyPair;
+//!
+//! let mut rng = rand::thread_rng();
+//! let keypair = MlDsaKeyPair::generate(&mut rng).unwrap();
+//!
+//! // Pre-sign a revocation and store it offline
+//! let revocation = KeyRevocation::new(&keypair, RevocationReason::KeyCompromise, 0).unwrap();
+//!
+//! let list = RevocationList::new();
+//! assert!(list.check(keypair.public_key()).is_ok());
+//! list.insert(revocation.into()).unwrap();
+//! assert!(list.is_revoked(keypair.public_key()));
+//! ```
+
+use crate::ml_dsa::{MlDsaError, MlDsaKeyPair, MlDsaPublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const ROTATION_DOMAIN: &[u8] = b"QuDAG key rotation v1";
+const ROTATION_COUNTERSIGN_DOMAIN: &[u8] = b"QuDAG key rotation countersignature v1";
+const REVOCATION_DOMAIN: &[u8] = b"QuDAG key revocation v1";
+
+/// Longest rotation chain followed when resolving the current key
+pub const MAX_ROTATION_CHAIN: usize = 32;
+
+/// Identifier of an ML-DSA public key (BLAKE3 of its bytes)
+pub type KeyId = [u8; 32];
+
+/// Compute the identifier of a public key
+pub fn key_id(public_key: &[u8]) -> KeyId {
+    *blake3::hash(public_key).as_bytes()
+}
+
+/// Errors that can occur during certificate operations
+#[derive(Debug, Error)]
+pub enum KeyCertificateError {
+    #[error("Key has been revoked ({reason:?})")]
+    Revoked { reason: RevocationReason },
+    #[error("Key has been rotated to a new key")]
+    Rotated { new_key: Vec<u8> },
+    #[error("Certificate rotates a key to itself")]
+    SelfRotation,
+    #[error("Rotation chain exceeds {MAX_ROTATION_CHAIN} keys or contains a cycle")]
+    RotationChainTooLong,
+    #[error("Conflicting rotation already recorded for this key")]
+    ConflictingRotation,
+    #[error("Certificate storage lock poisoned")]
+    StorageError,
+    #[error("ML-DSA error: {0}")]
+    MlDsaError(#[from] MlDsaError),
+}
+
+/// Why a key was revoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RevocationReason {
+    /// No reason given
+    Unspecified,
+    /// The secret key was or may have been disclosed
+    KeyCompromise,
+    /// The key was replaced and should no longer be used
+    Superseded,
+    /// The identity is no longer in service
+    Retired,
+}
+
+impl RevocationReason {
+    fn code(self) -> u8 {
+        match self {
+            RevocationReason::Unspecified => 0,
+            RevocationReason::KeyCompromise => 1,
+            RevocationReason::Superseded => 2,
+            RevocationReason::Retired => 3,
+        }
+    }
+}
+
+/// Statement by an old key that it has been replaced by a new key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    /// Public key being retired
+    pub old_public_key: Vec<u8>,
+    /// Public key replacing it
+    pub new_public_key: Vec<u8>,
+    /// Issue time (seconds since the Unix epoch)
+    pub issued_at: u64,
+    /// Signature by the old key
+    pub signature: Vec<u8>,
+    /// Counter-signature by the new key, proving possession
+    pub new_key_signature: Vec<u8>,
+}
+
+impl KeyRotation {
+    /// Create a rotation from `old_keypair` to `new_keypair`
+    pub fn new(
+        old_keypair: &MlDsaKeyPair,
+        new_keypair: &MlDsaKeyPair,
+        issued_at: u64,
+    ) -> Result<Self, KeyCertificateError> {
+        if old_keypair.public_key() == new_keypair.public_key() {
+            return Err(KeyCertificateError::SelfRotation);
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut rotation = Self {
+            old_public_key: old_keypair.public_key().to_vec(),
+            new_public_key: new_keypair.public_key().to_vec(),
+            issued_at,
+            signature: Vec::new(),
+            new_key_signature: Vec::new(),
+        };
+        rotation.signature =
+            old_keypair.sign(&rotation.signable_bytes(ROTATION_DOMAIN), &mut rng)?;
+        rotation.new_key_signature = new_keypair.sign(
+            &rotation.signable_bytes(ROTATION_COUNTERSIGN_DOMAIN),
+            &mut rng,
+        )?;
+        Ok(rotation)
+    }
+
+    /// Create a rotation issued now
+    pub fn now(
+        old_keypair: &MlDsaKeyPair,
+        new_keypair: &MlDsaKeyPair,
+    ) -> Result<Self, KeyCertificateError> {
+        Self::new(old_keypair, new_keypair, unix_now())
+    }
+
+    /// Verify both signatures
+    pub fn verify(&self) -> Result<(), KeyCertificateError> {
+        if self.old_public_key == self.new_public_key {
+            return Err(KeyCertificateError::SelfRotation);
+        }
+
+        MlDsaPublicKey::from_bytes(&self.old_public_key)?
+            .verify(&self.signable_bytes(ROTATION_DOMAIN), &self.signature)?;
+        MlDsaPublicKey::from_bytes(&self.new_public_key)?.verify(
+            &self.signable_bytes(ROTATION_COUNTERSIGN_DOMAIN),
+            &self.new_key_signature,
+        )?;
+        Ok(())
+    }
+
+    fn signable_bytes(&self, domain: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            domain.len() + self.old_public_key.len() + self.new_public_key.len() + 16,
+        );
+        bytes.extend_from_slice(domain);
+        bytes.extend_from_slice(&(self.old_public_key.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.old_public_key);
+        bytes.extend_from_slice(&(self.new_public_key.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.new_public_key);
+        bytes.extend_from_slice(&self.issued_at.to_be_bytes());
+        bytes
+    }
+}
+
+/// Statement by a key that it must no longer be trusted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRevocation {
+    /// Public key being revoked
+    pub public_key: Vec<u8>,
+    /// Reason for revocation
+    pub reason: RevocationReason,
+    /// Issue time (seconds since the Unix epoch)
+    pub issued_at: u64,
+    /// Signature by the revoked key
+    pub signature: Vec<u8>,
+}
+
+impl KeyRevocation {
+    /// Create a revocation signed by `keypair`
+    ///
+    /// The certificate does not need to be published right away; it can be
+    /// stored offline and announced if the key is later lost or compromised.
+    pub fn new(
+        keypair: &MlDsaKeyPair,
+        reason: RevocationReason,
+        issued_at: u64,
+    ) -> Result<Self, KeyCertificateError> {
+        let mut revocation = Self {
+            public_key: keypair.public_key().to_vec(),
+            reason,
+            issued_at,
+            signature: Vec::new(),
+        };
+        revocation.signature =
+            keypair.sign(&revocation.signable_bytes(), &mut rand::thread_rng())?;
+        Ok(revocation)
+    }
+
+    /// Verify the self-signature
+    pub fn verify(&self) -> Result<(), KeyCertificateError> {
+        MlDsaPublicKey::from_bytes(&self.public_key)?
+            .verify(&self.signable_bytes(), &self.signature)?;
+        Ok(())
+    }
+
+    fn signable_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(REVOCATION_DOMAIN.len() + self.public_key.len() + 16);
+        bytes.extend_from_slice(REVOCATION_DOMAIN);
+        bytes.extend_from_slice(&(self.public_key.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.public_key);
+        bytes.push(self.reason.code());
+        bytes.extend_from_slice(&self.issued_at.to_be_bytes());
+        bytes
+    }
+}
+
+/// Either kind of key certificate, as gossiped and persisted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyCertificate {
+    Rotation(KeyRotation),
+    Revocation(KeyRevocation),
+}
+
+impl KeyCertificate {
+    /// Verify the certificate's signatures
+    pub fn verify(&self) -> Result<(), KeyCertificateError> {
+        match self {
+            KeyCertificate::Rotation(rotation) => rotation.verify(),
+            KeyCertificate::Revocation(revocation) => revocation.verify(),
+        }
+    }
+
+    /// Identifier of the key the certificate retires
+    pub fn subject(&self) -> KeyId {
+        match self {
+            KeyCertificate::Rotation(rotation) => key_id(&rotation.old_public_key),
+            KeyCertificate::Revocation(revocation) => key_id(&revocation.public_key),
+        }
+    }
+
+    /// Short name of the certificate kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KeyCertificate::Rotation(_) => "rotation",
+            KeyCertificate::Revocation(_) => "revocation",
+        }
+    }
+}
+
+impl From<KeyRotation> for KeyCertificate {
+    fn from(rotation: KeyRotation) -> Self {
+        KeyCertificate::Rotation(rotation)
+    }
+}
+
+impl From<KeyRevocation> for KeyCertificate {
+    fn from(revocation: KeyRevocation) -> Self {
+        KeyCertificate::Revocation(revocation)
+    }
+}
+
+/// Trust status of a key according to a [`RevocationList`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyStatus {
+    /// No certificate retires the key
+    Active,
+    /// The key was replaced by `new_key`
+    Rotated { new_key: Vec<u8> },
+    /// The key was revoked
+    Revoked { reason: RevocationReason },
+}
+
+#[derive(Debug, Default)]
+struct RevocationListInner {
+    revocations: HashMap<KeyId, KeyRevocation>,
+    rotations: HashMap<KeyId, KeyRotation>,
+}
+
+/// Local set of verified rotation and revocation certificates
+///
+/// Cloning is cheap and every clone shares the same underlying list, so one
+/// list can be handed to the resolver, the handshake coordinator and the RPC
+/// server. A revocation always takes precedence over a rotation of the same
+/// key.
+#[derive(Debug, Clone, Default)]
+pub struct RevocationList {
+    inner: Arc<RwLock<RevocationListInner>>,
+}
+
+impl RevocationList {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify and add a certificate
+    ///
+    /// Returns `Ok(true)` if the certificate was new and `Ok(false)` if an
+    /// identical one was already known, so callers know whether to re-gossip.
+    pub fn insert(&self, certificate: KeyCertificate) -> Result<bool, KeyCertificateError> {
+        certificate.verify()?;
+        let subject = certificate.subject();
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|_| KeyCertificateError::StorageError)?;
+
+        match certificate {
+            KeyCertificate::Revocation(revocation) => {
+                if inner.revocations.contains_key(&subject) {
+                    return Ok(false);
+                }
+                inner.revocations.insert(subject, revocation);
+            }
+            KeyCertificate::Rotation(rotation) => {
+                if let Some(existing) = inner.rotations.get(&subject) {
+                    if existing.new_public_key == rotation.new_public_key {
+                        return Ok(false);
+                    }
+                    // Two successors for one key means the key signed both;
+                    // treat it as compromised rather than picking one
+                    return Err(KeyCertificateError::ConflictingRotation);
+                }
+                inner.rotations.insert(subject, rotation);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Add a batch of certificates, skipping invalid ones
+    ///
+    /// Returns the number of certificates that were new.
+    pub fn extend<I: IntoIterator<Item = KeyCertificate>>(&self, certificates: I) -> usize {
+        certificates
+            .into_iter()
+            .filter(|certificate| matches!(self.insert(certificate.clone()), Ok(true)))
+            .count()
+    }
+
+    /// Trust status of a public key
+    pub fn status(&self, public_key: &[u8]) -> KeyStatus {
+        let id = key_id(public_key);
+        let Ok(inner) = self.inner.read() else {
+            // Fail closed if the list is unusable
+            return KeyStatus::Revoked {
+                reason: RevocationReason::Unspecified,
+            };
+        };
+
+        if let Some(revocation) = inner.revocations.get(&id) {
+            return KeyStatus::Revoked {
+                reason: revocation.reason,
+            };
+        }
+        if let Some(rotation) = inner.rotations.get(&id) {
+            return KeyStatus::Rotated {
+                new_key: rotation.new_public_key.clone(),
+            };
+        }
+        KeyStatus::Active
+    }
+
+    /// Fail unless the key is active
+    pub fn check(&self, public_key: &[u8]) -> Result<(), KeyCertificateError> {
+        match self.status(public_key) {
+            KeyStatus::Active => Ok(()),
+            KeyStatus::Rotated { new_key } => Err(KeyCertificateError::Rotated { new_key }),
+            KeyStatus::Revoked { reason } => Err(KeyCertificateError::Revoked { reason }),
+        }
+    }
+
+    /// Whether the key has been revoked
+    pub fn is_revoked(&self, public_key: &[u8]) -> bool {
+        matches!(self.status(public_key), KeyStatus::Revoked { .. })
+    }
+
+    /// Follow rotations from `public_key` to the key currently in use
+    ///
+    /// Fails if any key along the chain has been revoked.
+    pub fn current_key(&self, public_key: &[u8]) -> Result<Vec<u8>, KeyCertificateError> {
+        let mut current = public_key.to_vec();
+        for _ in 0..MAX_ROTATION_CHAIN {
+            match self.status(&current) {
+                KeyStatus::Active => return Ok(current),
+                KeyStatus::Rotated { new_key } => current = new_key,
+                KeyStatus::Revoked { reason } => {
+                    return Err(KeyCertificateError::Revoked { reason })
+                }
+            }
+        }
+        Err(KeyCertificateError::RotationChainTooLong)
+    }
+
+    /// All certificates in the list, for persistence or gossip
+    pub fn certificates(&self) -> Vec<KeyCertificate> {
+        let Ok(inner) = self.inner.read() else {
+            return Vec::new();
+        };
+        inner
+            .revocations
+            .values()
+            .cloned()
+            .map(KeyCertificate::from)
+            .chain(inner.rotations.values().cloned().map(KeyCertificate::from))
+            .collect()
+    }
+
+    /// Number of certificates in the list
+    pub fn len(&self) -> usize {
+        self.inner
+            .read()
+            .map(|inner| inner.revocations.len() + inner.rotations.len())
+            .unwrap_or(0)
+    }
+
+    /// Check if the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair() -> MlDsaKeyPair {
+        MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap()
+    }
+
+    #[test]
+    fn test_rotation_roundtrip() {
+        let old = keypair();
+        let new = keypair();
+        let rotation = KeyRotation::new(&old, &new, 1_700_000_000).unwrap();
+        rotation.verify().unwrap();
+
+        let list = RevocationList::new();
+        assert!(list.insert(rotation.clone().into()).unwrap());
+        assert!(!list.insert(rotation.into()).unwrap());
+
+        assert!(matches!(
+            list.check(old.public_key()),
+            Err(KeyCertificateError::Rotated { .. })
+        ));
+        assert!(list.check(new.public_key()).is_ok());
+        assert_eq!(
+            list.current_key(old.public_key()).unwrap(),
+            new.public_key()
+        );
+    }
+
+    #[test]
+    fn test_tampered_rotation_rejected() {
+        let old = keypair();
+        let new = keypair();
+        let attacker = keypair();
+
+        let mut rotation = KeyRotation::new(&old, &new, 1).unwrap();
+        rotation.new_public_key = attacker.public_key().to_vec();
+        assert!(rotation.verify().is_err());
+
+        let mut rotation = KeyRotation::new(&old, &new, 1).unwrap();
+        rotation.issued_at += 1;
+        assert!(RevocationList::new().insert(rotation.into()).is_err());
+    }
+
+    #[test]
+    fn test_rotation_requires_new_key_countersignature() {
+        let old = keypair();
+        let new = keypair();
+        let victim = keypair();
+
+        // Claiming someone else's key as successor fails without their signature
+        let mut rotation = KeyRotation::new(&old, &new, 1).unwrap();
+        rotation.new_public_key = victim.public_key().to_vec();
+        rotation.signature = old
+            .sign(
+                &rotation.signable_bytes(ROTATION_DOMAIN),
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+        assert!(rotation.verify().is_err());
+    }
+
+    #[test]
+    fn test_presigned_revocation() {
+        let key = keypair();
+        let revocation = KeyRevocation::new(&key, RevocationReason::KeyCompromise, 42).unwrap();
+
+        // Survives being stored and reloaded
+        let stored = serde_json::to_vec(&KeyCertificate::from(revocation)).unwrap();
+        let restored: KeyCertificate = serde_json::from_slice(&stored).unwrap();
+
+        let list = RevocationList::new();
+        assert!(list.insert(restored).unwrap());
+        assert_eq!(
+            list.status(key.public_key()),
+            KeyStatus::Revoked {
+                reason: RevocationReason::KeyCompromise
+            }
+        );
+    }
+
+    #[test]
+    fn test_revocation_reason_is_signed() {
+        let key = keypair();
+        let mut revocation = KeyRevocation::new(&key, RevocationReason::Superseded, 1).unwrap();
+        revocation.reason = RevocationReason::Retired;
+        assert!(revocation.verify().is_err());
+    }
+
+    #[test]
+    fn test_revocation_overrides_rotation() {
+        let old = keypair();
+        let new = keypair();
+        let list = RevocationList::new();
+        list.insert(KeyRotation::new(&old, &new, 1).unwrap().into())
+            .unwrap();
+        list.insert(
+            KeyRevocation::new(&new, RevocationReason::KeyCompromise, 2)
+                .unwrap()
+                .into(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            list.current_key(old.public_key()),
+            Err(KeyCertificateError::Revoked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_rotation_rejected() {
+        let old = keypair();
+        let list = RevocationList::new();
+        list.insert(KeyRotation::new(&old, &keypair(), 1).unwrap().into())
+            .unwrap();
+
+        assert!(matches!(
+            list.insert(KeyRotation::new(&old, &keypair(), 2).unwrap().into()),
+            Err(KeyCertificateError::ConflictingRotation)
+        ));
+    }
+
+    #[test]
+    fn test_clones_share_state() {
+        let key = keypair();
+        let list = RevocationList::new();
+        let shared = list.clone();
+        list.insert(
+            KeyRevocation::new(&key, RevocationReason::Retired, 1)
+                .unwrap()
+                .into(),
+        )
+        .unwrap();
+
+        assert!(shared.is_revoked(key.public_key()));
+        assert_eq!(shared.certificates().len(), 1);
+    }
+}
@@ -0,0 +1,1512 @@
+//! Algorithm-tagged, versioned encodings for public and secret keys.
+//!
+//! Every key type in this crate converts to and from [`EncodedKey`], which can be
+//! written in three interchangeable formats:
+//!
+//! - **Binary**: compact framing starting with `QDKY`, used for storage and the wire
+//! - **Armored**: the binary form in base64 between PEM-style boundary lines
+//! - **JSON**: a JWK-like object with base64url fields
+//!
+//! Secret keys may be protected with a password in any format. The password is
+//! stretched with Argon2id and the secret key sealed with ChaCha20-Poly1305; the
+//! public key stays readable so a protected key file can still be identified.
+//!
+//! ```rust
+//! use qudag_crypto::key_encoding::{EncodedKey, KeyFormat};
+//! use qudag_crypto::ml_dsa::{MlDsaKeyPair, MlDsaPublicKey};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let keypair = MlDsaKeyPair::generate(&mut rand::thread_rng())?;
+//! let armored = EncodedKey::from(&keypair).to_public().encode(KeyFormat::Armored, None)?;
+//! assert!(armored.starts_with(b"-----BEGIN QUDAG ML-DSA-65 PUBLIC KEY-----"));
+//!
+//! let decoded = EncodedKey::decode(&armored, None)?;
+//! let public_key = MlDsaPublicKey::try_from(&decoded)?;
+//! assert_eq!(public_key.as_bytes(), keypair.public_key());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::hqc::{self, Parameters as HqcParameters, SecurityParameter};
+use crate::kem;
+use crate::ml_dsa::{
+    MlDsaError, MlDsaKeyPair, MlDsaPublicKey, ML_DSA_PUBLIC_KEY_SIZE, ML_DSA_SECRET_KEY_SIZE,
+};
+use crate::ml_kem::MlKem768;
+
+/// Leading bytes of the binary form
+pub const KEY_MAGIC: [u8; 4] = *b"QDKY";
+
+/// Current encoding version
+pub const KEY_ENCODING_VERSION: u8 = 1;
+
+/// Value of the JSON `kty` member
+pub const JSON_KEY_TYPE: &str = "QUDAG-PQ";
+
+const KIND_PUBLIC: u8 = 0;
+const KIND_SECRET: u8 = 1;
+const KIND_ENCRYPTED: u8 = 2;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+const ARMOR_LINE_WIDTH: usize = 64;
+
+/// Errors that can occur while encoding or decoding keys
+#[derive(Debug, Error)]
+pub enum KeyEncodingError {
+    #[error("Unknown key algorithm: {0}")]
+    UnknownAlgorithm(String),
+    #[error("Unsupported key encoding version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Malformed key encoding: {0}")]
+    Malformed(String),
+    #[error("Invalid {algorithm} {part} key length: expected {expected}, found {found}")]
+    InvalidLength {
+        algorithm: KeyAlgorithm,
+        part: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Expected a {expected} key, found {found}")]
+    AlgorithmMismatch {
+        expected: KeyAlgorithm,
+        found: KeyAlgorithm,
+    },
+    #[error("Key contains no secret key material")]
+    NotASecretKey,
+    #[error("Public key does not match secret key")]
+    KeyMismatch,
+    #[error("Key is password protected")]
+    PasswordRequired,
+    #[error("Wrong password or corrupted key")]
+    DecryptionFailed,
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+    #[error("Key derivation {param} {value} exceeds the maximum of {max}")]
+    KdfLimitExceeded {
+        param: &'static str,
+        value: u32,
+        max: u32,
+    },
+    #[error("ML-DSA error: {0}")]
+    MlDsa(#[from] MlDsaError),
+}
+
+/// Key algorithms with a registered encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAlgorithm {
+    /// ML-DSA-65 signature keys
+    MlDsa65,
+    /// ML-KEM-768 encapsulation keys
+    MlKem768,
+    /// HQC-128 encryption keys
+    Hqc128,
+    /// HQC-192 encryption keys
+    Hqc192,
+    /// HQC-256 encryption keys
+    Hqc256,
+}
+
+impl KeyAlgorithm {
+    /// All registered algorithms
+    pub const ALL: [KeyAlgorithm; 5] = [
+        KeyAlgorithm::MlDsa65,
+        KeyAlgorithm::MlKem768,
+        KeyAlgorithm::Hqc128,
+        KeyAlgorithm::Hqc192,
+        KeyAlgorithm::Hqc256,
+    ];
+
+    /// Identifier used in the binary form
+    pub fn id(self) -> u8 {
+        match self {
+            KeyAlgorithm::MlDsa65 => 1,
+            KeyAlgorithm::MlKem768 => 2,
+            KeyAlgorithm::Hqc128 => 3,
+            KeyAlgorithm::Hqc192 => 4,
+            KeyAlgorithm::Hqc256 => 5,
+        }
+    }
+
+    /// Look up an algorithm by binary identifier
+    pub fn from_id(id: u8) -> Result<Self, KeyEncodingError> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.id() == id)
+            .ok_or_else(|| KeyEncodingError::UnknownAlgorithm(format!("id {}", id)))
+    }
+
+    /// Name used in armor labels and the JSON `alg` member
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAlgorithm::MlDsa65 => "ML-DSA-65",
+            KeyAlgorithm::MlKem768 => "ML-KEM-768",
+            KeyAlgorithm::Hqc128 => "HQC-128",
+            KeyAlgorithm::Hqc192 => "HQC-192",
+            KeyAlgorithm::Hqc256 => "HQC-256",
+        }
+    }
+
+    /// Public key length in bytes
+    pub fn public_key_len(self) -> usize {
+        match self {
+            KeyAlgorithm::MlDsa65 => ML_DSA_PUBLIC_KEY_SIZE,
+            KeyAlgorithm::MlKem768 => MlKem768::PUBLIC_KEY_SIZE,
+            _ => HqcParameters::new(self.hqc_security().unwrap()).public_key_len(),
+        }
+    }
+
+    /// Secret key length in bytes
+    pub fn secret_key_len(self) -> usize {
+        match self {
+            KeyAlgorithm::MlDsa65 => ML_DSA_SECRET_KEY_SIZE,
+            KeyAlgorithm::MlKem768 => MlKem768::SECRET_KEY_SIZE,
+            _ => HqcParameters::new(self.hqc_security().unwrap()).secret_key_len(),
+        }
+    }
+
+    fn hqc_security(self) -> Option<SecurityParameter> {
+        match self {
+            KeyAlgorithm::Hqc128 => Some(SecurityParameter::Hqc128),
+            KeyAlgorithm::Hqc192 => Some(SecurityParameter::Hqc192),
+            KeyAlgorithm::Hqc256 => Some(SecurityParameter::Hqc256),
+            _ => None,
+        }
+    }
+
+    fn from_hqc_security(security: SecurityParameter) -> Self {
+        match security {
+            SecurityParameter::Hqc128 => KeyAlgorithm::Hqc128,
+            SecurityParameter::Hqc192 => KeyAlgorithm::Hqc192,
+            SecurityParameter::Hqc256 => KeyAlgorithm::Hqc256,
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for KeyAlgorithm {
+    type Err = KeyEncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| KeyEncodingError::UnknownAlgorithm(s.to_string()))
+    }
+}
+
+/// Output format for [`EncodedKey::encode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyFormat {
+    /// Compact binary framing
+    #[default]
+    Binary,
+    /// Base64 binary form between PEM-style boundary lines
+    Armored,
+    /// JWK-like JSON object
+    Json,
+}
+
+impl FromStr for KeyFormat {
+    type Err = KeyEncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "binary" | "bin" | "raw" => Ok(KeyFormat::Binary),
+            "armored" | "armor" | "pem" => Ok(KeyFormat::Armored),
+            "json" | "jwk" => Ok(KeyFormat::Json),
+            _ => Err(KeyEncodingError::Malformed(format!(
+                "unknown key format {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Argon2id cost parameters for password-protected keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// Same cost as the vault master key derivation
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Largest memory cost accepted, four times the default
+    pub const MAX_MEMORY_KIB: u32 = 256 * 1024;
+    /// Largest number of passes accepted, four times the default
+    pub const MAX_ITERATIONS: u32 = 12;
+    /// Largest degree of parallelism accepted, four times the default
+    pub const MAX_PARALLELISM: u32 = 16;
+
+    /// Reject costs above the maximums
+    ///
+    /// The parameters of a protected key come from the key file itself, so a
+    /// crafted header could otherwise demand gigabytes of memory or hours of
+    /// hashing before the password is even checked.
+    pub fn check_limits(&self) -> Result<(), KeyEncodingError> {
+        for (param, value, max) in [
+            ("memory cost", self.memory_kib, Self::MAX_MEMORY_KIB),
+            ("iterations", self.iterations, Self::MAX_ITERATIONS),
+            ("parallelism", self.parallelism, Self::MAX_PARALLELISM),
+        ] {
+            if value > max {
+                return Err(KeyEncodingError::KdfLimitExceeded { param, value, max });
+            }
+        }
+        Ok(())
+    }
+
+    fn derive_key(
+        &self,
+        password: &str,
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, KeyEncodingError> {
+        self.check_limits()?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| KeyEncodingError::KeyDerivation(e.to_string()))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), salt, key.as_mut())
+            .map_err(|e| KeyEncodingError::KeyDerivation(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// A public key, optionally with its secret key, tagged with its algorithm
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncodedKey {
+    algorithm: KeyAlgorithm,
+    public_key: Vec<u8>,
+    secret_key: Option<Zeroizing<Vec<u8>>>,
+}
+
+impl fmt::Debug for EncodedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncodedKey")
+            .field("algorithm", &self.algorithm)
+            .field("public_key_len", &self.public_key.len())
+            .field("has_secret_key", &self.secret_key.is_some())
+            .finish()
+    }
+}
+
+impl EncodedKey {
+    /// Wrap a raw public key
+    pub fn from_public(
+        algorithm: KeyAlgorithm,
+        public_key: &[u8],
+    ) -> Result<Self, KeyEncodingError> {
+        check_length(
+            algorithm,
+            "public",
+            algorithm.public_key_len(),
+            public_key.len(),
+        )?;
+        Ok(Self {
+            algorithm,
+            public_key: public_key.to_vec(),
+            secret_key: None,
+        })
+    }
+
+    /// Wrap a raw key pair
+    pub fn from_secret(
+        algorithm: KeyAlgorithm,
+        public_key: &[u8],
+        secret_key: &[u8],
+    ) -> Result<Self, KeyEncodingError> {
+        check_length(
+            algorithm,
+            "public",
+            algorithm.public_key_len(),
+            public_key.len(),
+        )?;
+        check_length(
+            algorithm,
+            "secret",
+            algorithm.secret_key_len(),
+            secret_key.len(),
+        )?;
+        Ok(Self {
+            algorithm,
+            public_key: public_key.to_vec(),
+            secret_key: Some(Zeroizing::new(secret_key.to_vec())),
+        })
+    }
+
+    /// Generate a fresh key pair for `algorithm`
+    pub fn generate(algorithm: KeyAlgorithm) -> Result<Self, KeyEncodingError> {
+        let mut rng = rand::thread_rng();
+        match algorithm {
+            KeyAlgorithm::MlDsa65 => Ok(Self::from(&MlDsaKeyPair::generate(&mut rng)?)),
+            KeyAlgorithm::MlKem768 => {
+                let (_, secret_key) = MlKem768::keygen()
+                    .map_err(|e| KeyEncodingError::KeyDerivation(e.to_string()))?;
+                Self::try_from(&secret_key)
+            }
+            _ => {
+                let security = algorithm
+                    .hqc_security()
+                    .unwrap_or(SecurityParameter::Hqc256);
+                let (public_key, secret_key) =
+                    hqc::Hqc::new(security)
+                        .generate_keypair(&mut rng)
+                        .map_err(|e| KeyEncodingError::KeyDerivation(e.to_string()))?;
+                Self::from_hqc_keypair(&public_key, &secret_key)
+            }
+        }
+    }
+
+    /// Wrap an HQC key pair
+    pub fn from_hqc_keypair(
+        public_key: &hqc::PublicKey,
+        secret_key: &hqc::SecretKey,
+    ) -> Result<Self, KeyEncodingError> {
+        let algorithm = KeyAlgorithm::from_hqc_security(public_key.security());
+        if secret_key.security() != public_key.security() {
+            return Err(KeyEncodingError::KeyMismatch);
+        }
+        Self::from_secret(algorithm, public_key.as_ref(), secret_key.as_ref())
+    }
+
+    /// Key algorithm
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
+    }
+
+    /// Raw public key bytes
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Raw secret key bytes, if this is a secret key
+    pub fn secret_key(&self) -> Option<&[u8]> {
+        self.secret_key.as_ref().map(|key| key.as_slice())
+    }
+
+    /// Whether secret key material is present
+    pub fn is_secret(&self) -> bool {
+        self.secret_key.is_some()
+    }
+
+    /// Copy of this key without the secret half
+    pub fn to_public(&self) -> Self {
+        Self {
+            algorithm: self.algorithm,
+            public_key: self.public_key.clone(),
+            secret_key: None,
+        }
+    }
+
+    /// Encode in `format`, sealing the secret key if a password is given
+    ///
+    /// Passwords are ignored for public keys.
+    pub fn encode(
+        &self,
+        format: KeyFormat,
+        password: Option<&str>,
+    ) -> Result<Vec<u8>, KeyEncodingError> {
+        self.encode_with_params(format, password, &KdfParams::default())
+    }
+
+    /// Encode with explicit Argon2id cost parameters
+    pub fn encode_with_params(
+        &self,
+        format: KeyFormat,
+        password: Option<&str>,
+        kdf: &KdfParams,
+    ) -> Result<Vec<u8>, KeyEncodingError> {
+        let envelope = self.seal(password, kdf)?;
+        Ok(match format {
+            KeyFormat::Binary => envelope.to_binary(),
+            KeyFormat::Armored => envelope.to_armored().into_bytes(),
+            KeyFormat::Json => serde_json::to_vec_pretty(&envelope.to_json())
+                .map_err(|e| KeyEncodingError::Malformed(e.to_string()))?,
+        })
+    }
+
+    /// Decode any of the three formats, detected from the input
+    ///
+    /// A password is required for protected secret keys and ignored otherwise.
+    pub fn decode(data: &[u8], password: Option<&str>) -> Result<Self, KeyEncodingError> {
+        Envelope::parse(data)?.open(password)
+    }
+
+    /// Whether `data` holds a password-protected secret key
+    pub fn is_encrypted(data: &[u8]) -> Result<bool, KeyEncodingError> {
+        Ok(matches!(
+            Envelope::parse(data)?.secret,
+            EnvelopeSecret::Encrypted { .. }
+        ))
+    }
+
+    fn require_algorithm(&self, expected: KeyAlgorithm) -> Result<(), KeyEncodingError> {
+        if self.algorithm != expected {
+            return Err(KeyEncodingError::AlgorithmMismatch {
+                expected,
+                found: self.algorithm,
+            });
+        }
+        Ok(())
+    }
+
+    fn require_secret(&self) -> Result<&[u8], KeyEncodingError> {
+        self.secret_key().ok_or(KeyEncodingError::NotASecretKey)
+    }
+
+    fn seal(&self, password: Option<&str>, kdf: &KdfParams) -> Result<Envelope, KeyEncodingError> {
+        let secret = match (&self.secret_key, password) {
+            (None, _) => EnvelopeSecret::None,
+            (Some(secret_key), None) => EnvelopeSecret::Plain(secret_key.clone()),
+            (Some(secret_key), Some(password)) => {
+                let mut rng = rand::thread_rng();
+                let mut salt = [0u8; SALT_SIZE];
+                let mut nonce = [0u8; NONCE_SIZE];
+                rng.fill_bytes(&mut salt);
+                rng.fill_bytes(&mut nonce);
+
+                let mut envelope = Envelope {
+                    algorithm: self.algorithm,
+                    public_key: self.public_key.clone(),
+                    secret: EnvelopeSecret::Encrypted {
+                        kdf: *kdf,
+                        salt,
+                        nonce,
+                        ciphertext: Vec::new(),
+                    },
+                };
+                let key = kdf.derive_key(password, &salt)?;
+                let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+                    .encrypt(
+                        Nonce::from_slice(&nonce),
+                        Payload {
+                            msg: secret_key,
+                            aad: &envelope.associated_data(),
+                        },
+                    )
+                    .map_err(|_| KeyEncodingError::Malformed("encryption failed".to_string()))?;
+                if let EnvelopeSecret::Encrypted {
+                    ciphertext: slot, ..
+                } = &mut envelope.secret
+                {
+                    *slot = ciphertext;
+                }
+                return Ok(envelope);
+            }
+        };
+
+        Ok(Envelope {
+            algorithm: self.algorithm,
+            public_key: self.public_key.clone(),
+            secret,
+        })
+    }
+}
+
+impl Serialize for EncodedKey {
+    /// JSON form for human-readable formats, binary form otherwise
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let envelope = self
+            .seal(None, &KdfParams::default())
+            .map_err(serde::ser::Error::custom)?;
+        if serializer.is_human_readable() {
+            envelope.to_json().serialize(serializer)
+        } else {
+            serializer.serialize_bytes(&envelope.to_binary())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EncodedKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let envelope = if deserializer.is_human_readable() {
+            Envelope::from_json(JsonKey::deserialize(deserializer)?)
+        } else {
+            Envelope::from_binary(&serde_bytes_vec(deserializer)?)
+        };
+        envelope
+            .and_then(|envelope| envelope.open(None))
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+fn serde_bytes_vec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    struct BytesVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("encoded key bytes")
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1 << 16));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+
+    deserializer.deserialize_byte_buf(BytesVisitor)
+}
+
+/// Serialize public key types through their tagged encoding
+macro_rules! serde_via_encoded_key {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                EncodedKey::try_from(self)
+                    .map_err(serde::ser::Error::custom)?
+                    .serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let key = EncodedKey::deserialize(deserializer)?;
+                <$type>::try_from(&key).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_via_encoded_key!(MlDsaPublicKey);
+serde_via_encoded_key!(kem::PublicKey);
+serde_via_encoded_key!(hqc::PublicKey);
+
+impl From<&MlDsaPublicKey> for EncodedKey {
+    fn from(public_key: &MlDsaPublicKey) -> Self {
+        Self {
+            algorithm: KeyAlgorithm::MlDsa65,
+            public_key: public_key.as_bytes().to_vec(),
+            secret_key: None,
+        }
+    }
+}
+
+impl From<&MlDsaKeyPair> for EncodedKey {
+    fn from(keypair: &MlDsaKeyPair) -> Self {
+        Self {
+            algorithm: KeyAlgorithm::MlDsa65,
+            public_key: keypair.public_key().to_vec(),
+            secret_key: Some(Zeroizing::new(keypair.secret_key().to_vec())),
+        }
+    }
+}
+
+impl TryFrom<&kem::PublicKey> for EncodedKey {
+    type Error = KeyEncodingError;
+
+    fn try_from(public_key: &kem::PublicKey) -> Result<Self, Self::Error> {
+        Self::from_public(KeyAlgorithm::MlKem768, public_key.as_bytes())
+    }
+}
+
+impl TryFrom<&kem::SecretKey> for EncodedKey {
+    type Error = KeyEncodingError;
+
+    /// The ML-KEM secret key embeds its public key, so no pair is needed
+    fn try_from(secret_key: &kem::SecretKey) -> Result<Self, Self::Error> {
+        check_length(
+            KeyAlgorithm::MlKem768,
+            "secret",
+            MlKem768::SECRET_KEY_SIZE,
+            secret_key.as_bytes().len(),
+        )?;
+        let public_key = MlKem768::public_key_from_secret(secret_key)
+            .map_err(|_| KeyEncodingError::KeyMismatch)?;
+        Self::from_secret(
+            KeyAlgorithm::MlKem768,
+            public_key.as_bytes(),
+            secret_key.as_bytes(),
+        )
+    }
+}
+
+impl From<&hqc::PublicKey> for EncodedKey {
+    fn from(public_key: &hqc::PublicKey) -> Self {
+        Self {
+            algorithm: KeyAlgorithm::from_hqc_security(public_key.security()),
+            public_key: public_key.as_bytes(),
+            secret_key: None,
+        }
+    }
+}
+
+impl TryFrom<&EncodedKey> for MlDsaPublicKey {
+    type Error = KeyEncodingError;
+
+    fn try_from(key: &EncodedKey) -> Result<Self, Self::Error> {
+        key.require_algorithm(KeyAlgorithm::MlDsa65)?;
+        Ok(MlDsaPublicKey::from_bytes(&key.public_key)?)
+    }
+}
+
+impl TryFrom<&EncodedKey> for MlDsaKeyPair {
+    type Error = KeyEncodingError;
+
+    fn try_from(key: &EncodedKey) -> Result<Self, Self::Error> {
+        key.require_algorithm(KeyAlgorithm::MlDsa65)?;
+        MlDsaKeyPair::from_bytes(&key.public_key, key.require_secret()?).map_err(|e| match e {
+            MlDsaError::InvalidSecretKey(_) => KeyEncodingError::KeyMismatch,
+            other => other.into(),
+        })
+    }
+}
+
+impl TryFrom<&EncodedKey> for kem::PublicKey {
+    type Error = KeyEncodingError;
+
+    fn try_from(key: &EncodedKey) -> Result<Self, Self::Error> {
+        key.require_algorithm(KeyAlgorithm::MlKem768)?;
+        kem::PublicKey::from_bytes(&key.public_key)
+            .map_err(|e| KeyEncodingError::Malformed(e.to_string()))
+    }
+}
+
+impl TryFrom<&EncodedKey> for kem::SecretKey {
+    type Error = KeyEncodingError;
+
+    fn try_from(key: &EncodedKey) -> Result<Self, Self::Error> {
+        key.require_algorithm(KeyAlgorithm::MlKem768)?;
+        let secret_key = kem::SecretKey::from_bytes(key.require_secret()?)
+            .map_err(|e| KeyEncodingError::Malformed(e.to_string()))?;
+        let embedded = MlKem768::public_key_from_secret(&secret_key)
+            .map_err(|_| KeyEncodingError::KeyMismatch)?;
+        if embedded.as_bytes() != key.public_key.as_slice() {
+            return Err(KeyEncodingError::KeyMismatch);
+        }
+        Ok(secret_key)
+    }
+}
+
+impl TryFrom<&EncodedKey> for hqc::PublicKey {
+    type Error = KeyEncodingError;
+
+    fn try_from(key: &EncodedKey) -> Result<Self, Self::Error> {
+        let security = key
+            .algorithm
+            .hqc_security()
+            .ok_or(KeyEncodingError::AlgorithmMismatch {
+                expected: KeyAlgorithm::Hqc256,
+                found: key.algorithm,
+            })?;
+        hqc::PublicKey::from_bytes_with_params(&key.public_key, security)
+            .map_err(|e| KeyEncodingError::Malformed(e.to_string()))
+    }
+}
+
+impl TryFrom<&EncodedKey> for hqc::SecretKey {
+    type Error = KeyEncodingError;
+
+    fn try_from(key: &EncodedKey) -> Result<Self, Self::Error> {
+        let security = key
+            .algorithm
+            .hqc_security()
+            .ok_or(KeyEncodingError::AlgorithmMismatch {
+                expected: KeyAlgorithm::Hqc256,
+                found: key.algorithm,
+            })?;
+        hqc::SecretKey::from_bytes_with_params(key.require_secret()?, security)
+            .map_err(|e| KeyEncodingError::Malformed(e.to_string()))
+    }
+}
+
+fn check_length(
+    algorithm: KeyAlgorithm,
+    part: &'static str,
+    expected: usize,
+    found: usize,
+) -> Result<(), KeyEncodingError> {
+    if expected != found {
+        return Err(KeyEncodingError::InvalidLength {
+            algorithm,
+            part,
+            expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Secret half of an encoded key as it appears on disk
+enum EnvelopeSecret {
+    None,
+    Plain(Zeroizing<Vec<u8>>),
+    Encrypted {
+        kdf: KdfParams,
+        salt: [u8; SALT_SIZE],
+        nonce: [u8; NONCE_SIZE],
+        ciphertext: Vec<u8>,
+    },
+}
+
+/// Format-independent view of an encoded key
+struct Envelope {
+    algorithm: KeyAlgorithm,
+    public_key: Vec<u8>,
+    secret: EnvelopeSecret,
+}
+
+/// JWK-like JSON representation
+#[derive(Serialize, Deserialize)]
+struct JsonKey {
+    kty: String,
+    alg: String,
+    ver: u8,
+    #[serde(rename = "pub")]
+    public_key: String,
+    #[serde(rename = "priv", default, skip_serializing_if = "Option::is_none")]
+    secret_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    enc: Option<JsonEncryption>,
+}
+
+/// Password protection parameters in the JSON representation
+#[derive(Serialize, Deserialize)]
+struct JsonEncryption {
+    kdf: String,
+    m: u32,
+    t: u32,
+    p: u32,
+    salt: String,
+    cipher: String,
+    nonce: String,
+    ct: String,
+}
+
+impl Envelope {
+    fn kind(&self) -> u8 {
+        match self.secret {
+            EnvelopeSecret::None => KIND_PUBLIC,
+            EnvelopeSecret::Plain(_) => KIND_SECRET,
+            EnvelopeSecret::Encrypted { .. } => KIND_ENCRYPTED,
+        }
+    }
+
+    fn armor_label(&self) -> String {
+        let kind = match self.secret {
+            EnvelopeSecret::None => "PUBLIC KEY",
+            EnvelopeSecret::Plain(_) => "SECRET KEY",
+            EnvelopeSecret::Encrypted { .. } => "ENCRYPTED SECRET KEY",
+        };
+        format!("QUDAG {} {}", self.algorithm.name(), kind)
+    }
+
+    /// Binary prefix up to and including the encryption parameters
+    ///
+    /// Used as associated data so the public key and KDF settings cannot be
+    /// swapped, regardless of which format the key was stored in.
+    fn associated_data(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64 + self.public_key.len());
+        out.extend_from_slice(&KEY_MAGIC);
+        out.push(KEY_ENCODING_VERSION);
+        out.push(self.algorithm.id());
+        out.push(self.kind());
+        out.extend_from_slice(&(self.public_key.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.public_key);
+        if let EnvelopeSecret::Encrypted {
+            kdf, salt, nonce, ..
+        } = &self.secret
+        {
+            out.extend_from_slice(&kdf.memory_kib.to_be_bytes());
+            out.extend_from_slice(&kdf.iterations.to_be_bytes());
+            out.extend_from_slice(&kdf.parallelism.to_be_bytes());
+            out.extend_from_slice(salt);
+            out.extend_from_slice(nonce);
+        }
+        out
+    }
+
+    fn to_binary(&self) -> Vec<u8> {
+        let mut out = self.associated_data();
+        match &self.secret {
+            EnvelopeSecret::None => {}
+            EnvelopeSecret::Plain(secret_key) => {
+                out.extend_from_slice(&(secret_key.len() as u32).to_be_bytes());
+                out.extend_from_slice(secret_key);
+            }
+            EnvelopeSecret::Encrypted { ciphertext, .. } => {
+                out.extend_from_slice(&(ciphertext.len() as u32).to_be_bytes());
+                out.extend_from_slice(ciphertext);
+            }
+        }
+        out
+    }
+
+    fn from_binary(data: &[u8]) -> Result<Self, KeyEncodingError> {
+        let mut reader = Reader { data };
+        if reader.take(KEY_MAGIC.len())? != KEY_MAGIC {
+            return Err(KeyEncodingError::Malformed("bad magic".to_string()));
+        }
+        let version = reader.u8()?;
+        if version != KEY_ENCODING_VERSION {
+            return Err(KeyEncodingError::UnsupportedVersion(version));
+        }
+        let algorithm = KeyAlgorithm::from_id(reader.u8()?)?;
+        let kind = reader.u8()?;
+        let public_key = reader.sized(algorithm, "public", algorithm.public_key_len())?;
+
+        let secret = match kind {
+            KIND_PUBLIC => EnvelopeSecret::None,
+            KIND_SECRET => EnvelopeSecret::Plain(Zeroizing::new(reader.sized(
+                algorithm,
+                "secret",
+                algorithm.secret_key_len(),
+            )?)),
+            KIND_ENCRYPTED => {
+                let kdf = KdfParams {
+                    memory_kib: reader.u32()?,
+                    iterations: reader.u32()?,
+                    parallelism: reader.u32()?,
+                };
+                kdf.check_limits()?;
+                let salt = reader.array::<SALT_SIZE>()?;
+                let nonce = reader.array::<NONCE_SIZE>()?;
+                let ciphertext = reader.sized(
+                    algorithm,
+                    "encrypted secret",
+                    algorithm.secret_key_len() + TAG_SIZE,
+                )?;
+                EnvelopeSecret::Encrypted {
+                    kdf,
+                    salt,
+                    nonce,
+                    ciphertext,
+                }
+            }
+            other => {
+                return Err(KeyEncodingError::Malformed(format!(
+                    "unknown key kind {}",
+                    other
+                )))
+            }
+        };
+
+        if !reader.data.is_empty() {
+            return Err(KeyEncodingError::Malformed("trailing data".to_string()));
+        }
+
+        Ok(Self {
+            algorithm,
+            public_key,
+            secret,
+        })
+    }
+
+    fn to_armored(&self) -> String {
+        let label = self.armor_label();
+        let body = STANDARD.encode(self.to_binary());
+        let mut out = format!("-----BEGIN {}-----\n", label);
+        for line in body.as_bytes().chunks(ARMOR_LINE_WIDTH) {
+            // Base64 output is ASCII
+            out.push_str(std::str::from_utf8(line).unwrap_or_default());
+            out.push('\n');
+        }
+        out.push_str(&format!("-----END {}-----\n", label));
+        out
+    }
+
+    fn from_armored(text: &str) -> Result<Self, KeyEncodingError> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let label = lines
+            .next()
+            .and_then(|line| line.strip_prefix("-----BEGIN "))
+            .and_then(|line| line.strip_suffix("-----"))
+            .ok_or_else(|| KeyEncodingError::Malformed("missing BEGIN line".to_string()))?;
+        let end = format!("-----END {}-----", label);
+
+        let mut body = String::new();
+        let mut terminated = false;
+        for line in lines.by_ref() {
+            if line == end {
+                terminated = true;
+                break;
+            }
+            body.push_str(line);
+        }
+        if !terminated {
+            return Err(KeyEncodingError::Malformed("missing END line".to_string()));
+        }
+        if lines.next().is_some() {
+            return Err(KeyEncodingError::Malformed("trailing data".to_string()));
+        }
+
+        let data = STANDARD
+            .decode(body)
+            .map_err(|e| KeyEncodingError::Malformed(e.to_string()))?;
+        let envelope = Self::from_binary(&data)?;
+        if envelope.armor_label() != label {
+            return Err(KeyEncodingError::Malformed(format!(
+                "armor label {:?} does not match contents",
+                label
+            )));
+        }
+        Ok(envelope)
+    }
+
+    fn to_json(&self) -> JsonKey {
+        let mut json = JsonKey {
+            kty: JSON_KEY_TYPE.to_string(),
+            alg: self.algorithm.name().to_string(),
+            ver: KEY_ENCODING_VERSION,
+            public_key: URL_SAFE_NO_PAD.encode(&self.public_key),
+            secret_key: None,
+            enc: None,
+        };
+        match &self.secret {
+            EnvelopeSecret::None => {}
+            EnvelopeSecret::Plain(secret_key) => {
+                json.secret_key = Some(URL_SAFE_NO_PAD.encode(secret_key.as_slice()));
+            }
+            EnvelopeSecret::Encrypted {
+                kdf,
+                salt,
+                nonce,
+                ciphertext,
+            } => {
+                json.enc = Some(JsonEncryption {
+                    kdf: "argon2id".to_string(),
+                    m: kdf.memory_kib,
+                    t: kdf.iterations,
+                    p: kdf.parallelism,
+                    salt: URL_SAFE_NO_PAD.encode(salt),
+                    cipher: "chacha20-poly1305".to_string(),
+                    nonce: URL_SAFE_NO_PAD.encode(nonce),
+                    ct: URL_SAFE_NO_PAD.encode(ciphertext),
+                });
+            }
+        }
+        json
+    }
+
+    fn from_json(json: JsonKey) -> Result<Self, KeyEncodingError> {
+        if json.kty != JSON_KEY_TYPE {
+            return Err(KeyEncodingError::Malformed(format!(
+                "unexpected kty {:?}",
+                json.kty
+            )));
+        }
+        if json.ver != KEY_ENCODING_VERSION {
+            return Err(KeyEncodingError::UnsupportedVersion(json.ver));
+        }
+        let algorithm: KeyAlgorithm = json.alg.parse()?;
+        let public_key = decode_field(&json.public_key)?;
+        check_length(
+            algorithm,
+            "public",
+            algorithm.public_key_len(),
+            public_key.len(),
+        )?;
+
+        let secret = match (json.secret_key, json.enc) {
+            (None, None) => EnvelopeSecret::None,
+            (Some(secret_key), None) => {
+                let secret_key = Zeroizing::new(decode_field(&secret_key)?);
+                check_length(
+                    algorithm,
+                    "secret",
+                    algorithm.secret_key_len(),
+                    secret_key.len(),
+                )?;
+                EnvelopeSecret::Plain(secret_key)
+            }
+            (None, Some(enc)) => {
+                if enc.kdf != "argon2id" || enc.cipher != "chacha20-poly1305" {
+                    return Err(KeyEncodingError::Malformed(format!(
+                        "unsupported protection {}/{}",
+                        enc.kdf, enc.cipher
+                    )));
+                }
+                let ciphertext = decode_field(&enc.ct)?;
+                check_length(
+                    algorithm,
+                    "encrypted secret",
+                    algorithm.secret_key_len() + TAG_SIZE,
+                    ciphertext.len(),
+                )?;
+                let kdf = KdfParams {
+                    memory_kib: enc.m,
+                    iterations: enc.t,
+                    parallelism: enc.p,
+                };
+                kdf.check_limits()?;
+                EnvelopeSecret::Encrypted {
+                    kdf,
+                    salt: decode_array(&enc.salt)?,
+                    nonce: decode_array(&enc.nonce)?,
+                    ciphertext,
+                }
+            }
+            (Some(_), Some(_)) => {
+                return Err(KeyEncodingError::Malformed(
+                    "both priv and enc present".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            algorithm,
+            public_key,
+            secret,
+        })
+    }
+
+    fn parse(data: &[u8]) -> Result<Self, KeyEncodingError> {
+        if data.starts_with(&KEY_MAGIC) {
+            return Self::from_binary(data);
+        }
+
+        let text = std::str::from_utf8(data)
+            .map_err(|_| KeyEncodingError::Malformed("unrecognized key format".to_string()))?
+            .trim_start();
+        if text.starts_with("-----BEGIN ") {
+            Self::from_armored(text)
+        } else if text.starts_with('{') {
+            let json: JsonKey = serde_json::from_str(text)
+                .map_err(|e| KeyEncodingError::Malformed(e.to_string()))?;
+            Self::from_json(json)
+        } else {
+            Err(KeyEncodingError::Malformed(
+                "unrecognized key format".to_string(),
+            ))
+        }
+    }
+
+    fn open(self, password: Option<&str>) -> Result<EncodedKey, KeyEncodingError> {
+        let secret_key = match &self.secret {
+            EnvelopeSecret::None => None,
+            EnvelopeSecret::Plain(secret_key) => Some(secret_key.clone()),
+            EnvelopeSecret::Encrypted {
+                kdf,
+                salt,
+                nonce,
+                ciphertext,
+            } => {
+                let password = password.ok_or(KeyEncodingError::PasswordRequired)?;
+                let key = kdf.derive_key(password, salt)?;
+                let plaintext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+                    .decrypt(
+                        Nonce::from_slice(nonce),
+                        Payload {
+                            msg: ciphertext,
+                            aad: &self.associated_data(),
+                        },
+                    )
+                    .map_err(|_| KeyEncodingError::DecryptionFailed)?;
+                Some(Zeroizing::new(plaintext))
+            }
+        };
+
+        Ok(EncodedKey {
+            algorithm: self.algorithm,
+            public_key: self.public_key,
+            secret_key,
+        })
+    }
+}
+
+fn decode_field(field: &str) -> Result<Vec<u8>, KeyEncodingError> {
+    URL_SAFE_NO_PAD
+        .decode(field)
+        .map_err(|e| KeyEncodingError::Malformed(e.to_string()))
+}
+
+fn decode_array<const N: usize>(field: &str) -> Result<[u8; N], KeyEncodingError> {
+    decode_field(field)?
+        .try_into()
+        .map_err(|_| KeyEncodingError::Malformed("bad parameter length".to_string()))
+}
+
+/// Bounds-checked reader over the binary form
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], KeyEncodingError> {
+        if self.data.len() < len {
+            return Err(KeyEncodingError::Malformed("truncated key".to_string()));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, KeyEncodingError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, KeyEncodingError> {
+        Ok(u32::from_be_bytes(self.array::<4>()?))
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], KeyEncodingError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    /// Length-prefixed field that must have exactly `expected` bytes
+    fn sized(
+        &mut self,
+        algorithm: KeyAlgorithm,
+        part: &'static str,
+        expected: usize,
+    ) -> Result<Vec<u8>, KeyEncodingError> {
+        let len = self.u32()? as usize;
+        check_length(algorithm, part, expected, len)?;
+        Ok(self.take(len)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests do not spend seconds in Argon2
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    const FORMATS: [KeyFormat; 3] = [KeyFormat::Binary, KeyFormat::Armored, KeyFormat::Json];
+
+    fn ml_dsa_key() -> EncodedKey {
+        EncodedKey::from(&MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap())
+    }
+
+    #[test]
+    fn test_roundtrip_all_formats() {
+        let key = ml_dsa_key();
+        for format in FORMATS {
+            for candidate in [key.clone(), key.to_public()] {
+                let encoded = candidate.encode(format, None).unwrap();
+                assert_eq!(EncodedKey::decode(&encoded, None).unwrap(), candidate);
+                assert!(!EncodedKey::is_encrypted(&encoded).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_password_protection_all_formats() {
+        let key = ml_dsa_key();
+        for format in FORMATS {
+            let encoded = key
+                .encode_with_params(format, Some("hunter2"), &TEST_KDF)
+                .unwrap();
+            assert!(EncodedKey::is_encrypted(&encoded).unwrap());
+
+            assert!(matches!(
+                EncodedKey::decode(&encoded, None),
+                Err(KeyEncodingError::PasswordRequired)
+            ));
+            assert!(matches!(
+                EncodedKey::decode(&encoded, Some("wrong")),
+                Err(KeyEncodingError::DecryptionFailed)
+            ));
+            assert_eq!(EncodedKey::decode(&encoded, Some("hunter2")).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_public_key_bound_to_ciphertext() {
+        let key = ml_dsa_key();
+        let other = ml_dsa_key();
+        let mut encoded = key
+            .encode_with_params(KeyFormat::Binary, Some("pw"), &TEST_KDF)
+            .unwrap();
+
+        // Swap in a different public key; the secret must no longer open
+        let offset = KEY_MAGIC.len() + 3 + 4;
+        encoded[offset..offset + ML_DSA_PUBLIC_KEY_SIZE].copy_from_slice(other.public_key());
+        assert!(matches!(
+            EncodedKey::decode(&encoded, Some("pw")),
+            Err(KeyEncodingError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_oversized_kdf_params() {
+        let key = ml_dsa_key();
+
+        // Memory cost of the binary header raised to 4 TiB
+        let mut binary = key
+            .encode_with_params(KeyFormat::Binary, Some("pw"), &TEST_KDF)
+            .unwrap();
+        let offset = KEY_MAGIC.len() + 3 + 4 + ML_DSA_PUBLIC_KEY_SIZE;
+        binary[offset..offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            EncodedKey::decode(&binary, Some("pw")),
+            Err(KeyEncodingError::KdfLimitExceeded {
+                param: "memory cost",
+                ..
+            })
+        ));
+
+        // Passes of the JSON form raised likewise
+        let json = key
+            .encode_with_params(KeyFormat::Json, Some("pw"), &TEST_KDF)
+            .unwrap();
+        let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        value["enc"]["t"] = serde_json::json!(KdfParams::MAX_ITERATIONS + 1);
+        let json = serde_json::to_vec(&value).unwrap();
+        assert!(matches!(
+            EncodedKey::decode(&json, Some("pw")),
+            Err(KeyEncodingError::KdfLimitExceeded {
+                param: "iterations",
+                ..
+            })
+        ));
+
+        // Keys are not written with parameters they could not be read with
+        let expensive = KdfParams {
+            parallelism: KdfParams::MAX_PARALLELISM + 1,
+            ..TEST_KDF
+        };
+        assert!(matches!(
+            key.encode_with_params(KeyFormat::Binary, Some("pw"), &expensive),
+            Err(KeyEncodingError::KdfLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_armor_layout() {
+        let key = ml_dsa_key();
+        let armored =
+            String::from_utf8(key.to_public().encode(KeyFormat::Armored, None).unwrap()).unwrap();
+        let lines: Vec<&str> = armored.lines().collect();
+        assert_eq!(lines[0], "-----BEGIN QUDAG ML-DSA-65 PUBLIC KEY-----");
+        assert_eq!(
+            *lines.last().unwrap(),
+            "-----END QUDAG ML-DSA-65 PUBLIC KEY-----"
+        );
+        assert!(lines[1..lines.len() - 1]
+            .iter()
+            .all(|line| line.len() <= ARMOR_LINE_WIDTH));
+
+        // A label that disagrees with the contents is rejected
+        let relabeled = armored.replace("PUBLIC KEY", "SECRET KEY");
+        assert!(matches!(
+            EncodedKey::decode(relabeled.as_bytes(), None),
+            Err(KeyEncodingError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_layout() {
+        let key = ml_dsa_key();
+        let json: serde_json::Value =
+            serde_json::from_slice(&key.encode(KeyFormat::Json, None).unwrap()).unwrap();
+        assert_eq!(json["kty"], JSON_KEY_TYPE);
+        assert_eq!(json["alg"], "ML-DSA-65");
+        assert_eq!(json["ver"], 1);
+        assert!(json["priv"].is_string());
+
+        let protected: serde_json::Value = serde_json::from_slice(
+            &key.encode_with_params(KeyFormat::Json, Some("pw"), &TEST_KDF)
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(protected.get("priv").is_none());
+        assert_eq!(protected["enc"]["kdf"], "argon2id");
+    }
+
+    #[test]
+    fn test_ml_dsa_typed_conversion() {
+        let keypair = MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap();
+        let key = EncodedKey::from(&keypair);
+
+        let restored = MlDsaKeyPair::try_from(&key).unwrap();
+        let signature = restored.sign(b"message", &mut rand::thread_rng()).unwrap();
+        let public_key = MlDsaPublicKey::try_from(&key).unwrap();
+        public_key.verify(b"message", &signature).unwrap();
+
+        assert!(matches!(
+            MlDsaKeyPair::try_from(&key.to_public()),
+            Err(KeyEncodingError::NotASecretKey)
+        ));
+
+        // A secret key paired with the wrong public key is caught
+        let other = MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap();
+        let mismatched = EncodedKey::from_secret(
+            KeyAlgorithm::MlDsa65,
+            other.public_key(),
+            keypair.secret_key(),
+        )
+        .unwrap();
+        assert!(matches!(
+            MlDsaKeyPair::try_from(&mismatched),
+            Err(KeyEncodingError::KeyMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_ml_kem_typed_conversion() {
+        let (public_key, secret_key) = MlKem768::keygen().unwrap();
+        let key = EncodedKey::try_from(&secret_key).unwrap();
+        assert_eq!(key.algorithm(), KeyAlgorithm::MlKem768);
+        assert_eq!(key.public_key(), public_key.as_bytes());
+
+        let decoded =
+            EncodedKey::decode(&key.encode(KeyFormat::Armored, None).unwrap(), None).unwrap();
+        let restored = kem::SecretKey::try_from(&decoded).unwrap();
+        let (ciphertext, shared) = MlKem768::encapsulate(&public_key).unwrap();
+        assert_eq!(
+            MlKem768::decapsulate(&restored, &ciphertext).unwrap(),
+            shared
+        );
+
+        assert!(matches!(
+            MlDsaPublicKey::try_from(&decoded),
+            Err(KeyEncodingError::AlgorithmMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_hqc_typed_conversion() {
+        let hqc = hqc::Hqc::new(SecurityParameter::Hqc128);
+        let (public_key, secret_key) = hqc.generate_keypair(&mut rand::thread_rng()).unwrap();
+        let key = EncodedKey::from_hqc_keypair(&public_key, &secret_key).unwrap();
+        assert_eq!(key.algorithm(), KeyAlgorithm::Hqc128);
+
+        let decoded =
+            EncodedKey::decode(&key.encode(KeyFormat::Json, None).unwrap(), None).unwrap();
+        let restored_public = hqc::PublicKey::try_from(&decoded).unwrap();
+        let restored_secret = hqc::SecretKey::try_from(&decoded).unwrap();
+        assert_eq!(restored_public.security(), SecurityParameter::Hqc128);
+
+        let ciphertext = hqc
+            .encrypt(b"hqc message", &restored_public, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(
+            hqc.decrypt(&ciphertext, &restored_secret).unwrap(),
+            b"hqc message"
+        );
+    }
+
+    #[test]
+    fn test_public_key_serde() {
+        let keypair = MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap();
+        let public_key = keypair.to_public_key().unwrap();
+
+        let json = serde_json::to_string(&public_key).unwrap();
+        let restored: MlDsaPublicKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.as_bytes(), public_key.as_bytes());
+
+        let (kem_public, _) = MlKem768::keygen().unwrap();
+        let json = serde_json::to_string(&kem_public).unwrap();
+        assert!(json.contains("ML-KEM-768"));
+        assert_eq!(
+            serde_json::from_str::<kem::PublicKey>(&json).unwrap(),
+            kem_public
+        );
+
+        // ML-KEM keys are not accepted where ML-DSA keys are expected
+        assert!(serde_json::from_str::<MlDsaPublicKey>(&json).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        let key = ml_dsa_key();
+        let encoded = key.encode(KeyFormat::Binary, None).unwrap();
+
+        assert!(matches!(
+            EncodedKey::decode(&encoded[..encoded.len() - 1], None),
+            Err(KeyEncodingError::Malformed(_))
+        ));
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            EncodedKey::decode(&trailing, None),
+            Err(KeyEncodingError::Malformed(_))
+        ));
+
+        let mut future = encoded.clone();
+        future[KEY_MAGIC.len()] = KEY_ENCODING_VERSION + 1;
+        assert!(matches!(
+            EncodedKey::decode(&future, None),
+            Err(KeyEncodingError::UnsupportedVersion(_))
+        ));
+
+        let mut unknown = encoded;
+        unknown[KEY_MAGIC.len() + 1] = 0xff;
+        assert!(matches!(
+            EncodedKey::decode(&unknown, None),
+            Err(KeyEncodingError::UnknownAlgorithm(_))
+        ));
+
+        assert!(EncodedKey::decode(b"not a key", None).is_err());
+    }
+
+    #[test]
+    fn test_generate_all_algorithms() {
+        for algorithm in KeyAlgorithm::ALL {
+            let key = EncodedKey::generate(algorithm).unwrap();
+            assert_eq!(key.algorithm(), algorithm);
+            assert!(key.is_secret());
+            let encoded = key.encode(KeyFormat::Binary, None).unwrap();
+            assert_eq!(EncodedKey::decode(&encoded, None).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_algorithm_and_format_names() {
+        for algorithm in KeyAlgorithm::ALL {
+            assert_eq!(algorithm.name().parse::<KeyAlgorithm>().unwrap(), algorithm);
+            assert_eq!(KeyAlgorithm::from_id(algorithm.id()).unwrap(), algorithm);
+        }
+        assert_eq!("pem".parse::<KeyFormat>().unwrap(), KeyFormat::Armored);
+        assert_eq!("jwk".parse::<KeyFormat>().unwrap(), KeyFormat::Json);
+        assert!("xml".parse::<KeyFormat>().is_err());
+    }
+}
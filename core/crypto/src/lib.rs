@@ -9,6 +9,9 @@
 //! - HQC: Hamming Quasi-Cyclic code-based encryption
 //! - BLAKE3: Cryptographic hash function
 //! - Quantum Fingerprint: Data fingerprinting using ML-DSA
+//! - Key encoding: Algorithm-tagged binary, armored and JSON key formats
+//! - Key certificates: Signed rotation and revocation of ML-DSA identity keys
+//! - Sealed boxes: Non-interactive public-key encryption built on ML-KEM and ML-DSA
 
 pub mod encryption;
 pub mod error;
@@ -16,10 +19,16 @@ pub mod fingerprint;
 pub mod hash;
 pub mod hqc;
 pub mod kem;
-// mod optimized;
+pub mod key_certificate;
+pub mod key_encoding;
 pub mod ml_dsa;
 pub mod ml_kem;
+#[cfg(feature = "optimized")]
+pub mod optimized;
+mod scratch;
+pub mod sealed_box;
 pub mod signature;
+mod timer;
 
 pub use error::CryptoError;
 pub use fingerprint::{Fingerprint, FingerprintError};
@@ -28,6 +37,12 @@ pub use hqc::{Hqc, Hqc128, Hqc192, Hqc256, HqcError, SecurityParameter};
 pub use kem::{
     Ciphertext, KEMError, KeyEncapsulation, KeyPair, PublicKey, SecretKey, SharedSecret,
 };
+pub use key_certificate::{
+    KeyCertificate, KeyCertificateError, KeyRevocation, KeyRotation, KeyStatus, RevocationList,
+    RevocationReason,
+};
+pub use key_encoding::{EncodedKey, KeyAlgorithm, KeyEncodingError, KeyFormat};
 pub use ml_dsa::{MlDsa, MlDsaError, MlDsaKeyPair, MlDsaPublicKey};
 pub use ml_kem::{Metrics as MlKemMetrics, MlKem768};
+pub use sealed_box::{SealOptions, SealedBoxError, SealedBoxHeader};
 pub use signature::{DigitalSignature, SignatureError};
@@ -0,0 +1,307 @@
+//! Parallel batch verification for ML-DSA signatures
+//!
+//! Vertex and transaction ingestion verifies many independent signatures at
+//! once. ML-DSA has no algebraic batch check, so the speed-up comes from
+//! spreading the individual verifications over a rayon thread pool: the
+//! global pool by default, or a dedicated pool shared by the caller. No
+//! threads are spawned per batch.
+//!
+//! Unlike a plain loop, the verifier reports every failing index instead of
+//! stopping at the first error. Callers that only care whether the whole batch
+//! is valid can enable early-abort to stop as soon as any failure is found.
+
+use super::{MlDsaError, MlDsaPublicKey};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Default number of signatures each worker should have before another
+/// worker is worth using.
+pub const DEFAULT_MIN_BATCH_PER_THREAD: usize = 4;
+
+/// Configuration for [`BatchVerifier`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchVerifierConfig {
+    /// Maximum number of signatures verified at once (0 uses every thread of
+    /// the pool)
+    pub max_threads: usize,
+    /// Stop verifying as soon as the first invalid signature is found
+    pub early_abort: bool,
+    /// Minimum number of signatures per worker
+    pub min_batch_per_thread: usize,
+}
+
+impl Default for BatchVerifierConfig {
+    fn default() -> Self {
+        Self {
+            max_threads: 0,
+            early_abort: false,
+            min_batch_per_thread: DEFAULT_MIN_BATCH_PER_THREAD,
+        }
+    }
+}
+
+/// Parallel ML-DSA batch verifier
+///
+/// # Example
+///
+/// ```rust
+/// use qudag_crypto::ml_dsa::{BatchVerifier, MlDsaError, MlDsaKeyPair};
+/// use rand::thread_rng;
+///
+/// let mut rng = thread_rng();
+/// let keypair = MlDsaKeyPair::generate(&mut rng).unwrap();
+/// let public_key = keypair.to_public_key().unwrap();
+///
+/// let good = keypair.sign(b"valid", &mut rng).unwrap();
+/// let messages = vec![b"valid".as_slice(), b"tampered".as_slice()];
+/// let signatures = vec![good.as_slice(), good.as_slice()];
+/// let public_keys = vec![&public_key, &public_key];
+///
+/// let verifier = BatchVerifier::new().with_threads(2);
+/// match verifier.verify(&messages, &signatures, &public_keys) {
+///     Err(MlDsaError::BatchVerificationFailed { failed_indices }) => {
+///         assert_eq!(failed_indices, vec![1]);
+///     }
+///     other => panic!("unexpected result: {:?}", other),
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BatchVerifier {
+    config: BatchVerifierConfig,
+    /// Pool the verifications run on, rayon's global pool if unset
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl BatchVerifier {
+    /// Create a verifier with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a verifier from an explicit configuration
+    pub fn with_config(config: BatchVerifierConfig) -> Self {
+        Self { config, pool: None }
+    }
+
+    /// Run verifications on a dedicated thread pool instead of rayon's global
+    /// pool
+    ///
+    /// The pool's size bounds the threads used by every verifier sharing it.
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Set the maximum number of signatures verified at once (0 uses every
+    /// thread of the pool)
+    pub fn with_threads(mut self, max_threads: usize) -> Self {
+        self.config.max_threads = max_threads;
+        self
+    }
+
+    /// Enable or disable early-abort on the first failure
+    pub fn with_early_abort(mut self, early_abort: bool) -> Self {
+        self.config.early_abort = early_abort;
+        self
+    }
+
+    /// Set the minimum number of signatures per worker
+    pub fn with_min_batch_per_thread(mut self, min_batch_per_thread: usize) -> Self {
+        self.config.min_batch_per_thread = min_batch_per_thread.max(1);
+        self
+    }
+
+    /// Get the verifier configuration
+    pub fn config(&self) -> &BatchVerifierConfig {
+        &self.config
+    }
+
+    /// Number of workers that would be used for a batch of `len` signatures
+    pub fn worker_count(&self, len: usize) -> usize {
+        let max_threads = match self.config.max_threads {
+            0 => match &self.pool {
+                Some(pool) => pool.current_num_threads(),
+                None => rayon::current_num_threads(),
+            },
+            n => n,
+        };
+        let by_size = len / self.config.min_batch_per_thread.max(1);
+        max_threads.min(by_size).max(1)
+    }
+
+    /// Verify a batch of signatures
+    ///
+    /// Returns `MlDsaError::BatchVerificationFailed` carrying the sorted
+    /// indices of every invalid signature. With early-abort enabled the list
+    /// contains only the failures found before the workers stopped.
+    pub fn verify(
+        &self,
+        messages: &[&[u8]],
+        signatures: &[&[u8]],
+        public_keys: &[&MlDsaPublicKey],
+    ) -> Result<(), MlDsaError> {
+        let failed_indices = self.failed_indices(messages, signatures, public_keys)?;
+        if failed_indices.is_empty() {
+            Ok(())
+        } else {
+            Err(MlDsaError::BatchVerificationFailed { failed_indices })
+        }
+    }
+
+    /// Verify a batch of signatures and return the sorted indices that failed
+    pub fn failed_indices(
+        &self,
+        messages: &[&[u8]],
+        signatures: &[&[u8]],
+        public_keys: &[&MlDsaPublicKey],
+    ) -> Result<Vec<usize>, MlDsaError> {
+        if messages.len() != signatures.len() || messages.len() != public_keys.len() {
+            return Err(MlDsaError::BatchVerificationInputMismatch);
+        }
+
+        let len = messages.len();
+        let verify_one = |i: usize| public_keys[i].verify(messages[i], signatures[i]).is_ok();
+
+        let workers = self.worker_count(len);
+        if workers <= 1 {
+            let mut failed = Vec::new();
+            for i in 0..len {
+                if !verify_one(i) {
+                    failed.push(i);
+                    if self.config.early_abort {
+                        break;
+                    }
+                }
+            }
+            return Ok(failed);
+        }
+
+        // At most `workers` pieces of the batch are verified at once
+        let abort = AtomicBool::new(false);
+        let early_abort = self.config.early_abort;
+        let run = || {
+            (0..len)
+                .into_par_iter()
+                .with_min_len(len.div_ceil(workers))
+                .filter(|&i| {
+                    if abort.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    let failed = !verify_one(i);
+                    if failed && early_abort {
+                        abort.store(true, Ordering::Relaxed);
+                    }
+                    failed
+                })
+                .collect::<Vec<usize>>()
+        };
+
+        Ok(match &self.pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml_dsa::MlDsaKeyPair;
+    use rand::thread_rng;
+
+    fn signed_batch(count: usize) -> (MlDsaPublicKey, Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let mut rng = thread_rng();
+        let keypair = MlDsaKeyPair::generate(&mut rng).unwrap();
+        let messages: Vec<Vec<u8>> = (0..count)
+            .map(|i| format!("message {}", i).into_bytes())
+            .collect();
+        let signatures = messages
+            .iter()
+            .map(|m| keypair.sign(m, &mut rng).unwrap())
+            .collect();
+        (keypair.to_public_key().unwrap(), messages, signatures)
+    }
+
+    #[test]
+    fn test_parallel_batch_reports_all_failures() {
+        let (pk, messages, mut signatures) = signed_batch(16);
+        for &i in &[3usize, 7, 12] {
+            signatures[i][10] ^= 0xff;
+        }
+
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+        let public_keys = vec![&pk; messages.len()];
+
+        let verifier = BatchVerifier::new()
+            .with_threads(4)
+            .with_min_batch_per_thread(1);
+        assert_eq!(verifier.worker_count(messages.len()), 4);
+        assert_eq!(
+            verifier.verify(&messages, &signatures, &public_keys),
+            Err(MlDsaError::BatchVerificationFailed {
+                failed_indices: vec![3, 7, 12]
+            })
+        );
+    }
+
+    #[test]
+    fn test_shared_thread_pool() {
+        let (pk, messages, mut signatures) = signed_batch(8);
+        signatures[5][10] ^= 0xff;
+
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+        let public_keys = vec![&pk; messages.len()];
+
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .unwrap(),
+        );
+        let verifier = BatchVerifier::new()
+            .with_thread_pool(pool)
+            .with_min_batch_per_thread(1);
+        assert_eq!(verifier.worker_count(messages.len()), 2);
+        assert_eq!(
+            verifier.failed_indices(&messages, &signatures, &public_keys),
+            Ok(vec![5])
+        );
+    }
+
+    #[test]
+    fn test_early_abort_stops_on_failure() {
+        let (pk, messages, mut signatures) = signed_batch(8);
+        signatures[0][10] ^= 0xff;
+
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+        let public_keys = vec![&pk; messages.len()];
+
+        let verifier = BatchVerifier::new().with_threads(1).with_early_abort(true);
+        assert_eq!(
+            verifier.failed_indices(&messages, &signatures, &public_keys),
+            Ok(vec![0])
+        );
+    }
+
+    #[test]
+    fn test_input_mismatch() {
+        let (pk, messages, signatures) = signed_batch(2);
+        let messages: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+
+        assert_eq!(
+            BatchVerifier::new().verify(&messages, &signatures, &[&pk]),
+            Err(MlDsaError::BatchVerificationInputMismatch)
+        );
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        assert!(BatchVerifier::new().verify(&[], &[], &[]).is_ok());
+    }
+}
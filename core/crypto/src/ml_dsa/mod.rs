@@ -41,8 +41,10 @@
 //!     public_key.verify(message, &signature)?;
 //!     
 //!     // Batch verification
-//!     let messages = vec![message.as_slice(), b"another message"];
-//!     let signatures = vec![signature.as_slice(), signature.as_slice()];
+//!     let other = b"another message";
+//!     let other_signature = keypair.sign(other, &mut rng)?;
+//!     let messages = vec![message.as_slice(), other.as_slice()];
+//!     let signatures = vec![signature.as_slice(), other_signature.as_slice()];
 //!     let public_keys = vec![&public_key, &public_key];
 //!     MlDsaPublicKey::batch_verify(&messages, &signatures, &public_keys)?;
 //!     
@@ -69,6 +71,13 @@ use subtle::ConstantTimeEq;
 use thiserror::Error;
 use zeroize::Zeroize;
 
+use crate::scratch::concat;
+use crate::timer::OperationTimer;
+
+mod batch;
+
+pub use batch::{BatchVerifier, BatchVerifierConfig, DEFAULT_MIN_BATCH_PER_THREAD};
+
 /// Helper for secure memory cleanup
 #[allow(dead_code)]
 fn secure_zero(data: &mut [u8]) {
@@ -179,6 +188,10 @@ pub enum MlDsaError {
     #[error("Batch verification input lengths do not match")]
     BatchVerificationInputMismatch,
 
+    /// One or more signatures in a batch failed verification
+    #[error("Batch verification failed at indices {failed_indices:?}")]
+    BatchVerificationFailed { failed_indices: Vec<usize> },
+
     /// Side-channel attack detected
     #[error("Potential side-channel attack detected")]
     SideChannelAttackDetected,
@@ -231,6 +244,7 @@ impl MlDsaPublicKey {
 
     /// Verify an ML-DSA signature against a message
     pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), MlDsaError> {
+        let _timer = OperationTimer::start("ml_dsa_verify");
         // ML-DSA signatures can vary in size due to rejection sampling
         // Check for reasonable bounds instead of exact size
         if signature.len() < 2000 || signature.len() > ML_DSA_SIGNATURE_SIZE {
@@ -241,9 +255,7 @@ impl MlDsaPublicKey {
         }
 
         // Create signed message format expected by pqcrypto
-        let mut signed_message_bytes = Vec::with_capacity(signature.len() + message.len());
-        signed_message_bytes.extend_from_slice(signature);
-        signed_message_bytes.extend_from_slice(message);
+        let signed_message_bytes = concat(signature, message);
 
         let signed_msg = <SignedMessage as PqSignedMessageTrait>::from_bytes(&signed_message_bytes)
             .map_err(|_| MlDsaError::VerificationFailed)?;
@@ -269,24 +281,15 @@ impl MlDsaPublicKey {
     }
 
     /// Batch verification of multiple signatures
+    ///
+    /// Signatures are verified in parallel with the default [`BatchVerifier`].
+    /// On failure the error lists every index that did not verify.
     pub fn batch_verify(
         messages: &[&[u8]],
         signatures: &[&[u8]],
         public_keys: &[&MlDsaPublicKey],
     ) -> Result<(), MlDsaError> {
-        if messages.len() != signatures.len() || messages.len() != public_keys.len() {
-            return Err(MlDsaError::InternalError(
-                "Mismatched batch verification input lengths".to_string(),
-            ));
-        }
-
-        // Verify each signature individually for now
-        // TODO: Implement optimized batch verification
-        for i in 0..messages.len() {
-            public_keys[i].verify(messages[i], signatures[i])?;
-        }
-
-        Ok(())
+        BatchVerifier::new().verify(messages, signatures, public_keys)
     }
 }
 
@@ -328,6 +331,7 @@ impl MlDsaKeyPair {
     pub fn generate<R: CryptoRng + RngCore>(
         #[allow(unused_variables)] rng: &mut R,
     ) -> Result<Self, MlDsaError> {
+        let _timer = OperationTimer::start("ml_dsa_keygen");
         // Generate key pair using pqcrypto
         let (internal_public, internal_secret) = keypair();
 
@@ -367,12 +371,44 @@ impl MlDsaKeyPair {
         &self.secret_key
     }
 
+    /// Reconstruct a key pair from raw public and secret key bytes
+    ///
+    /// The halves are checked to belong together by signing a probe message.
+    pub fn from_bytes(public_key: &[u8], secret_key: &[u8]) -> Result<Self, MlDsaError> {
+        if secret_key.len() != ML_DSA_SECRET_KEY_SIZE {
+            return Err(MlDsaError::InvalidKeyLength {
+                expected: ML_DSA_SECRET_KEY_SIZE,
+                found: secret_key.len(),
+            });
+        }
+
+        let verifying_key = MlDsaPublicKey::from_bytes(public_key)?;
+        let internal_secret = <SecretKey as PqSecretKeyTrait>::from_bytes(secret_key)
+            .map_err(|_| MlDsaError::InvalidSecretKey("Failed to parse secret key".to_string()))?;
+
+        let keypair = Self {
+            public_key: public_key.to_vec(),
+            secret_key: secret_key.to_vec(),
+            internal_public: verifying_key.internal_key,
+            internal_secret,
+        };
+
+        let probe = b"QuDAG ML-DSA key pair consistency check";
+        let signature = keypair.sign(probe, &mut rand::thread_rng())?;
+        verifying_key.verify(probe, &signature).map_err(|_| {
+            MlDsaError::InvalidSecretKey("Secret key does not match public key".to_string())
+        })?;
+
+        Ok(keypair)
+    }
+
     /// Sign a message using ML-DSA with rejection sampling
     pub fn sign<R: CryptoRng + RngCore>(
         &self,
         message: &[u8],
         #[allow(unused_variables)] rng: &mut R,
     ) -> Result<Vec<u8>, MlDsaError> {
+        let _timer = OperationTimer::start("ml_dsa_sign");
         // Use pqcrypto-dilithium's signing which includes rejection sampling
         let signed_msg = sign(message, &self.internal_secret);
         let signed_bytes = <SignedMessage as PqSignedMessageTrait>::as_bytes(&signed_msg);
@@ -864,9 +900,7 @@ fn verify_signature_internal(
         .map_err(|_| MlDsaError::InvalidPublicKey("Failed to parse public key".to_string()))?;
 
     // Create signed message format for verification
-    let mut signed_message = Vec::with_capacity(message.len() + signature.len());
-    signed_message.extend_from_slice(signature);
-    signed_message.extend_from_slice(message);
+    let signed_message = concat(signature, message);
 
     let signed_msg = <SignedMessage as PqSignedMessageTrait>::from_bytes(&signed_message)
         .map_err(|_| MlDsaError::VerificationFailed)?;
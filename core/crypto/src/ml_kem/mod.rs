@@ -4,13 +4,25 @@
 //! ML-KEM provides quantum-resistant key exchange capabilities based on the
 //! Module-LWE problem.
 
+use pqcrypto_mlkem::mlkem768;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _, SecretKey as _, SharedSecret as _};
 use rand::RngCore;
+use sha3::{Digest, Sha3_256};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 
+use subtle::ConstantTimeEq;
+
 use crate::kem::{Ciphertext, KEMError, KeyEncapsulation, PublicKey, SecretKey, SharedSecret};
+use crate::scratch::concat;
+use crate::timer::OperationTimer;
+
+/// Length of the encoded polynomial vector leading keys (k = 3, 384 bytes each)
+const POLYVEC_BYTES: usize = 3 * 384;
 
+/// Modulus of the coefficient ring
+const Q: u16 = 3329;
 // Global metrics for ML-KEM operations
 static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
 static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
@@ -22,6 +34,29 @@ lazy_static::lazy_static! {
     static ref KEY_CACHE: Mutex<HashMap<Vec<u8>, Vec<u8>>> = Mutex::new(HashMap::new());
 }
 
+/// Backend secret that is zeroized when dropped
+///
+/// pqcrypto's secret key and shared secret are plain `Copy` byte arrays that
+/// are never wiped on their own.
+struct Wiped<T: Copy>(T);
+
+impl<T: Copy> std::ops::Deref for Wiped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Copy> Drop for Wiped<T> {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        // SAFETY: the backend types are flat byte arrays without drop glue,
+        // for which all-zero bytes are a valid value
+        unsafe { zeroize::zeroize_flat_type(&mut self.0 as *mut T) }
+    }
+}
+
 /// ML-KEM 768 implementation
 ///
 /// # Examples
@@ -84,33 +119,39 @@ impl MlKem768 {
         Self::keygen_with_rng(&mut rng)
     }
 
-    /// Generate a keypair with custom RNG for testing
+    /// Generate a keypair, accepting an RNG for API compatibility
+    ///
+    /// The RNG is not used: the pqcrypto backend always draws its randomness
+    /// from the operating system's RNG, so passing a seeded RNG does not make
+    /// key generation deterministic.
     pub fn keygen_with_rng<R: RngCore + rand::CryptoRng>(
-        #[allow(unused_variables)] rng: &mut R,
+        _rng: &mut R,
     ) -> Result<(PublicKey, SecretKey), KEMError> {
-        // For now, use a placeholder implementation
-        // In a real implementation, this would use the ML-KEM algorithm
-        let mut pk_bytes = vec![0u8; Self::PUBLIC_KEY_SIZE];
-        let mut sk_bytes = vec![0u8; Self::SECRET_KEY_SIZE];
-
-        rng.fill_bytes(&mut pk_bytes);
-        rng.fill_bytes(&mut sk_bytes);
-
-        // Create some deterministic relationship between pk and sk for testing
-        for i in 0..32 {
-            if i < pk_bytes.len() && i < sk_bytes.len() {
-                sk_bytes[i] = pk_bytes[i] ^ 0xFF;
-            }
-        }
+        let _timer = OperationTimer::start("ml_kem_keygen");
+        let (pk, sk) = mlkem768::keypair();
+        let sk = Wiped(sk);
 
         let public_key =
-            PublicKey::from_bytes(&pk_bytes).map_err(|_| KEMError::KeyGenerationError)?;
+            PublicKey::from_bytes(pk.as_bytes()).map_err(|_| KEMError::KeyGenerationError)?;
         let secret_key =
-            SecretKey::from_bytes(&sk_bytes).map_err(|_| KEMError::KeyGenerationError)?;
+            SecretKey::from_bytes(sk.as_bytes()).map_err(|_| KEMError::KeyGenerationError)?;
 
         Ok((public_key, secret_key))
     }
 
+    /// Extract the public key embedded in a secret key
+    ///
+    /// ML-KEM secret keys carry a copy of their public key, so a recipient
+    /// holding only the secret key can still identify itself.
+    pub fn public_key_from_secret(sk: &SecretKey) -> Result<PublicKey, KEMError> {
+        let sk_bytes = sk.as_bytes();
+        if !check_secret_key(sk_bytes) {
+            return Err(KEMError::InvalidKey);
+        }
+
+        PublicKey::from_bytes(public_key_of(sk_bytes))
+    }
+
     /// Encapsulate a shared secret using a public key
     ///
     /// This function implements the ML-KEM encapsulation algorithm, which:
@@ -119,33 +160,25 @@ impl MlKem768 {
     /// 3. Encrypts the message using the public key with error vectors
     /// 4. Returns both the ciphertext and shared secret
     pub fn encapsulate(pk: &PublicKey) -> Result<(Ciphertext, SharedSecret), KEMError> {
+        let _timer = OperationTimer::start("ml_kem_encapsulate");
         // Validate public key size
         let pk_bytes = pk.as_bytes();
         if pk_bytes.len() != Self::PUBLIC_KEY_SIZE {
             return Err(KEMError::InvalidKey);
         }
 
-        // For now, use a placeholder implementation
-        // In a real implementation, this would use the ML-KEM encapsulation algorithm
-        let mut rng = rand::thread_rng();
-        let mut ct_bytes = vec![0u8; Self::CIPHERTEXT_SIZE];
-        let mut ss_bytes = vec![0u8; Self::SHARED_SECRET_SIZE];
-
-        rng.fill_bytes(&mut ct_bytes);
-        rng.fill_bytes(&mut ss_bytes);
-
-        // Create some deterministic relationship for testing
-        for i in 0..32 {
-            if i < pk_bytes.len() {
-                ct_bytes[i] = pk_bytes[i] ^ 0xAA;
-                ss_bytes[i % Self::SHARED_SECRET_SIZE] ^= pk_bytes[i];
-            }
+        if !check_public_key(pk_bytes) {
+            return Err(KEMError::InvalidKey);
         }
 
+        let pk = mlkem768::PublicKey::from_bytes(pk_bytes).map_err(|_| KEMError::InvalidKey)?;
+        let (ss, ct) = mlkem768::encapsulate(&pk);
+        let ss = Wiped(ss);
+
         let ciphertext =
-            Ciphertext::from_bytes(&ct_bytes).map_err(|_| KEMError::EncapsulationError)?;
+            Ciphertext::from_bytes(ct.as_bytes()).map_err(|_| KEMError::EncapsulationError)?;
         let shared_secret =
-            SharedSecret::from_bytes(&ss_bytes).map_err(|_| KEMError::EncapsulationError)?;
+            SharedSecret::from_bytes(ss.as_bytes()).map_err(|_| KEMError::EncapsulationError)?;
 
         Ok((ciphertext, shared_secret))
     }
@@ -158,13 +191,14 @@ impl MlKem768 {
     /// 3. Derives the same shared secret that was generated during encapsulation
     /// 4. Includes constant-time error checking to prevent side-channel attacks
     pub fn decapsulate(sk: &SecretKey, ct: &Ciphertext) -> Result<SharedSecret, KEMError> {
+        let _timer = OperationTimer::start("ml_kem_decapsulate");
         let start_time = std::time::Instant::now();
 
         // Validate input sizes
         let sk_bytes = sk.as_bytes();
         let ct_bytes = ct.as_bytes();
 
-        if sk_bytes.len() != Self::SECRET_KEY_SIZE {
+        if !check_secret_key(sk_bytes) {
             return Err(KEMError::InvalidKey);
         }
         if ct_bytes.len() != Self::CIPHERTEXT_SIZE {
@@ -172,39 +206,29 @@ impl MlKem768 {
         }
 
         // Check cache first for performance
-        let cache_key = {
-            let mut key = Vec::with_capacity(sk_bytes.len() + ct_bytes.len());
-            key.extend_from_slice(sk_bytes);
-            key.extend_from_slice(ct_bytes);
-            key
-        };
+        let cache_key = concat(sk_bytes, ct_bytes);
 
         if let Ok(cache) = KEY_CACHE.lock() {
-            if let Some(cached_ss) = cache.get(&cache_key) {
+            if let Some(cached_ss) = cache.get(&cache_key[..]) {
                 CACHE_HITS.fetch_add(1, Ordering::Relaxed);
                 return SharedSecret::from_bytes(cached_ss).map_err(|_| KEMError::InternalError);
             }
         }
         CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
 
-        // For now, use a placeholder implementation
-        // In a real implementation, this would use the ML-KEM decapsulation algorithm
-        let mut ss_bytes = vec![0u8; Self::SHARED_SECRET_SIZE];
-
-        // Reconstruct the shared secret deterministically from sk and ct
-        for i in 0..32 {
-            if i < sk_bytes.len() && i < ct_bytes.len() {
-                ss_bytes[i % Self::SHARED_SECRET_SIZE] ^= sk_bytes[i] ^ ct_bytes[i];
-            }
-        }
-
+        // Invalid ciphertexts are rejected implicitly inside the backend
+        let secret =
+            Wiped(mlkem768::SecretKey::from_bytes(sk_bytes).map_err(|_| KEMError::InvalidKey)?);
+        let ciphertext =
+            mlkem768::Ciphertext::from_bytes(ct_bytes).map_err(|_| KEMError::InvalidLength)?;
+        let ss = Wiped(mlkem768::decapsulate(&ciphertext, &secret));
         let shared_secret =
-            SharedSecret::from_bytes(&ss_bytes).map_err(|_| KEMError::DecapsulationError)?;
+            SharedSecret::from_bytes(ss.as_bytes()).map_err(|_| KEMError::DecapsulationError)?;
 
         // Update cache (in a real implementation, you'd want LRU eviction)
         if let Ok(mut cache) = KEY_CACHE.lock() {
             if cache.len() < Self::CACHE_SIZE {
-                cache.insert(cache_key, shared_secret.as_bytes().to_vec());
+                cache.insert(cache_key.to_vec(), shared_secret.as_bytes().to_vec());
             }
         }
 
@@ -223,11 +247,7 @@ impl MlKem768 {
         let total_time = TOTAL_DECAP_TIME.load(Ordering::Relaxed);
         let decap_count = DECAP_COUNT.load(Ordering::Relaxed);
 
-        let avg_decap_time_ns = if decap_count > 0 {
-            total_time / decap_count
-        } else {
-            0
-        };
+        let avg_decap_time_ns = total_time.checked_div(decap_count).unwrap_or(0);
 
         Metrics {
             key_cache_misses: cache_misses,
@@ -265,6 +285,33 @@ pub struct Metrics {
     pub avg_decap_time_ns: u64,
 }
 
+/// Modulus check on a public key: every encoded coefficient must be below q
+///
+/// Public keys are public, so the check need not run in constant time.
+fn check_public_key(pk: &[u8]) -> bool {
+    pk.len() == MlKem768::PUBLIC_KEY_SIZE
+        && pk[..POLYVEC_BYTES].chunks_exact(3).all(|b| {
+            let low = u16::from(b[0]) | (u16::from(b[1] & 0x0f) << 8);
+            let high = u16::from(b[1] >> 4) | (u16::from(b[2]) << 4);
+            low < Q && high < Q
+        })
+}
+
+/// Hash check on a secret key: the embedded `H(pk)` must match its public key
+fn check_secret_key(sk: &[u8]) -> bool {
+    if sk.len() != MlKem768::SECRET_KEY_SIZE {
+        return false;
+    }
+    let digest = Sha3_256::digest(public_key_of(sk));
+    let embedded = &sk[POLYVEC_BYTES + MlKem768::PUBLIC_KEY_SIZE..MlKem768::SECRET_KEY_SIZE - 32];
+    bool::from(digest.as_slice().ct_eq(embedded))
+}
+
+/// The public key embedded in a secret key
+fn public_key_of(sk: &[u8]) -> &[u8] {
+    &sk[POLYVEC_BYTES..POLYVEC_BYTES + MlKem768::PUBLIC_KEY_SIZE]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,6 +333,45 @@ mod tests {
         assert_eq!(ss1.as_bytes(), ss2.as_bytes());
     }
 
+    #[test]
+    fn test_public_key_from_secret() {
+        let (pk, sk) = MlKem768::keygen().unwrap();
+        assert_eq!(MlKem768::public_key_from_secret(&sk).unwrap(), pk);
+    }
+
+    #[test]
+    fn test_wrong_secret_key_yields_different_secret() {
+        let (pk, _sk) = MlKem768::keygen().unwrap();
+        let (_other_pk, other_sk) = MlKem768::keygen().unwrap();
+
+        let (ct, ss1) = MlKem768::encapsulate(&pk).unwrap();
+        let ss2 = MlKem768::decapsulate(&other_sk, &ct).unwrap();
+        assert_ne!(ss1.as_bytes(), ss2.as_bytes());
+    }
+
+    #[test]
+    fn test_rejects_public_key_above_modulus() {
+        let (pk, _sk) = MlKem768::keygen().unwrap();
+        let mut bytes = pk.as_bytes().to_vec();
+        // First coefficient 0xFFF >= q
+        bytes[0] = 0xff;
+        bytes[1] |= 0x0f;
+        let pk = PublicKey::from_bytes(&bytes).unwrap();
+        assert!(matches!(
+            MlKem768::encapsulate(&pk),
+            Err(KEMError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_secret_key_with_wrong_hash() {
+        let (_pk, sk) = MlKem768::keygen().unwrap();
+        let mut bytes = sk.as_bytes().to_vec();
+        bytes[MlKem768::SECRET_KEY_SIZE - 40] ^= 1;
+        let sk = SecretKey::from_bytes(&bytes).unwrap();
+        assert!(MlKem768::public_key_from_secret(&sk).is_err());
+    }
+
     #[test]
     fn test_key_sizes() {
         assert_eq!(MlKem768::PUBLIC_KEY_SIZE, 1184);
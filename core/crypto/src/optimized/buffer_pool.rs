@@ -1,8 +1,7 @@
 //! High-performance buffer pool for reduced memory allocations
 
-use std::sync::Arc;
-use std::collections::VecDeque;
 use parking_lot::Mutex;
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Buffer pool for reusing memory allocations
@@ -39,13 +38,13 @@ impl BufferPool {
     }
 
     /// Acquire a buffer of the specified size
-    pub fn acquire(&self, size: usize) -> PooledBuffer {
+    pub fn acquire(&self, size: usize) -> PooledBuffer<'_> {
         let buffer = match size {
             0..=1024 => self.acquire_small(size),
             1025..=16384 => self.acquire_medium(size),
             _ => self.acquire_large(size),
         };
-        
+
         PooledBuffer {
             buffer,
             pool: self,
@@ -54,41 +53,56 @@ impl BufferPool {
     }
 
     fn acquire_small(&self, size: usize) -> Vec<u8> {
-        let mut buffers = self.small_buffers.lock();
-        if let Some(mut buffer) = buffers.pop_front() {
-            self.stats.small_hits.fetch_add(1, Ordering::Relaxed);
-            buffer.clear();
-            buffer.resize(size, 0);
-            buffer
-        } else {
-            self.stats.small_misses.fetch_add(1, Ordering::Relaxed);
-            vec![0u8; size]
-        }
+        let buffer = self.small_buffers.lock().pop_back();
+        Self::reuse_or_allocate(
+            buffer,
+            size,
+            &self.stats.small_hits,
+            &self.stats.small_misses,
+        )
     }
 
     fn acquire_medium(&self, size: usize) -> Vec<u8> {
-        let mut buffers = self.medium_buffers.lock();
-        if let Some(mut buffer) = buffers.pop_front() {
-            self.stats.medium_hits.fetch_add(1, Ordering::Relaxed);
-            buffer.clear();
-            buffer.resize(size, 0);
-            buffer
-        } else {
-            self.stats.medium_misses.fetch_add(1, Ordering::Relaxed);
-            vec![0u8; size]
-        }
+        let buffer = self.medium_buffers.lock().pop_back();
+        Self::reuse_or_allocate(
+            buffer,
+            size,
+            &self.stats.medium_hits,
+            &self.stats.medium_misses,
+        )
     }
 
     fn acquire_large(&self, size: usize) -> Vec<u8> {
-        let mut buffers = self.large_buffers.lock();
-        if let Some(mut buffer) = buffers.pop_front() {
-            self.stats.large_hits.fetch_add(1, Ordering::Relaxed);
-            buffer.clear();
-            buffer.resize(size, 0);
-            buffer
-        } else {
-            self.stats.large_misses.fetch_add(1, Ordering::Relaxed);
-            vec![0u8; size]
+        let buffer = self.large_buffers.lock().pop_back();
+        Self::reuse_or_allocate(
+            buffer,
+            size,
+            &self.stats.large_hits,
+            &self.stats.large_misses,
+        )
+    }
+
+    /// Resize a pooled buffer or allocate a fresh one.
+    ///
+    /// Pooled buffers are zeroed when returned, so only newly grown bytes
+    /// need to be written here.
+    fn reuse_or_allocate(
+        buffer: Option<Vec<u8>>,
+        size: usize,
+        hits: &AtomicUsize,
+        misses: &AtomicUsize,
+    ) -> Vec<u8> {
+        match buffer {
+            Some(mut buffer) => {
+                hits.fetch_add(1, Ordering::Relaxed);
+                buffer.truncate(size);
+                buffer.resize(size, 0);
+                buffer
+            }
+            None => {
+                misses.fetch_add(1, Ordering::Relaxed);
+                vec![0u8; size]
+            }
         }
     }
 
@@ -96,7 +110,7 @@ impl BufferPool {
     pub fn return_buffer(&self, mut buffer: Vec<u8>, original_size: usize) {
         // Securely clear the buffer
         buffer.fill(0);
-        
+
         // Prevent over-accumulation of buffers
         match original_size {
             0..=1024 => {
@@ -122,7 +136,16 @@ impl BufferPool {
 
     /// Get pool statistics
     pub fn stats(&self) -> PoolStatistics {
+        let hits = self.stats.small_hits.load(Ordering::Relaxed)
+            + self.stats.medium_hits.load(Ordering::Relaxed)
+            + self.stats.large_hits.load(Ordering::Relaxed);
+        let misses = self.stats.small_misses.load(Ordering::Relaxed)
+            + self.stats.medium_misses.load(Ordering::Relaxed)
+            + self.stats.large_misses.load(Ordering::Relaxed);
+
         PoolStatistics {
+            hits,
+            misses,
             small_hit_rate: self.calculate_hit_rate(
                 self.stats.small_hits.load(Ordering::Relaxed),
                 self.stats.small_misses.load(Ordering::Relaxed),
@@ -135,9 +158,9 @@ impl BufferPool {
                 self.stats.large_hits.load(Ordering::Relaxed),
                 self.stats.large_misses.load(Ordering::Relaxed),
             ),
-            total_buffers: self.small_buffers.lock().len() + 
-                          self.medium_buffers.lock().len() + 
-                          self.large_buffers.lock().len(),
+            total_buffers: self.small_buffers.lock().len()
+                + self.medium_buffers.lock().len()
+                + self.large_buffers.lock().len(),
         }
     }
 
@@ -151,9 +174,17 @@ impl BufferPool {
     }
 }
 
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Statistics for buffer pool performance
 #[derive(Debug, Clone)]
 pub struct PoolStatistics {
+    pub hits: usize,
+    pub misses: usize,
     pub small_hit_rate: f64,
     pub medium_hit_rate: f64,
     pub large_hit_rate: f64,
@@ -167,17 +198,7 @@ pub struct PooledBuffer<'a> {
     original_size: usize,
 }
 
-impl<'a> PooledBuffer<'a> {
-    /// Get a mutable reference to the buffer
-    pub fn as_mut(&mut self) -> &mut Vec<u8> {
-        &mut self.buffer
-    }
-
-    /// Get an immutable reference to the buffer
-    pub fn as_ref(&self) -> &Vec<u8> {
-        &self.buffer
-    }
-
+impl PooledBuffer<'_> {
     /// Get the buffer as a slice
     pub fn as_slice(&self) -> &[u8] {
         &self.buffer
@@ -209,7 +230,19 @@ impl<'a> PooledBuffer<'a> {
     }
 }
 
-impl<'a> std::ops::Deref for PooledBuffer<'a> {
+impl AsRef<[u8]> for PooledBuffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl AsMut<[u8]> for PooledBuffer<'_> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl std::ops::Deref for PooledBuffer<'_> {
     type Target = Vec<u8>;
 
     fn deref(&self) -> &Self::Target {
@@ -217,13 +250,13 @@ impl<'a> std::ops::Deref for PooledBuffer<'a> {
     }
 }
 
-impl<'a> std::ops::DerefMut for PooledBuffer<'a> {
+impl std::ops::DerefMut for PooledBuffer<'_> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.buffer
     }
 }
 
-impl<'a> Drop for PooledBuffer<'a> {
+impl Drop for PooledBuffer<'_> {
     fn drop(&mut self) {
         // Return the buffer to the pool
         let buffer = std::mem::take(&mut self.buffer);
@@ -238,18 +271,18 @@ mod tests {
     #[test]
     fn test_buffer_pool_basic() {
         let pool = BufferPool::new();
-        
+
         // Acquire a small buffer
         let mut buffer = pool.acquire(512);
         assert_eq!(buffer.len(), 512);
-        
+
         // Modify the buffer
         buffer.as_mut_slice()[0] = 42;
         assert_eq!(buffer.as_slice()[0], 42);
-        
+
         // Buffer should be returned to pool when dropped
         drop(buffer);
-        
+
         // Acquire another buffer - should reuse the first one
         let buffer2 = pool.acquire(512);
         assert_eq!(buffer2.as_slice()[0], 0); // Should be cleared
@@ -258,11 +291,11 @@ mod tests {
     #[test]
     fn test_buffer_pool_size_categories() {
         let pool = BufferPool::new();
-        
+
         let small = pool.acquire(500);
         let medium = pool.acquire(5000);
         let large = pool.acquire(50000);
-        
+
         assert_eq!(small.len(), 500);
         assert_eq!(medium.len(), 5000);
         assert_eq!(large.len(), 50000);
@@ -271,15 +304,15 @@ mod tests {
     #[test]
     fn test_buffer_pool_stats() {
         let pool = BufferPool::new();
-        
+
         // First acquisition should be a miss
         let _buffer1 = pool.acquire(1000);
         drop(_buffer1);
-        
+
         // Second acquisition should be a hit
         let _buffer2 = pool.acquire(1000);
-        
+
         let stats = pool.stats();
         assert!(stats.small_hit_rate > 0.0);
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,9 @@
 //! Key caching for improved crypto performance
 
-use std::sync::Arc;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
 use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
 use std::time::{Duration, Instant};
-use ring::digest;
 
 /// Key cache for frequently used cryptographic keys
 pub struct KeyCache {
@@ -22,10 +20,7 @@ pub struct KeyHash([u8; 32]);
 impl KeyHash {
     /// Create a key hash from bytes
     pub fn from_bytes(data: &[u8]) -> Self {
-        let digest = digest::digest(&digest::SHA256, data);
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(digest.as_ref());
-        KeyHash(hash)
+        KeyHash(Sha256::digest(data).into())
     }
 }
 
@@ -66,7 +61,9 @@ impl KeyCache {
     /// Create a new key cache with specified capacity
     pub fn new(capacity: usize) -> Self {
         Self {
-            cache: RwLock::new(lru::LruCache::new(capacity.try_into().unwrap())),
+            cache: RwLock::new(lru::LruCache::new(
+                NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            )),
             stats: CacheStats::default(),
         }
     }
@@ -82,8 +79,14 @@ impl KeyCache {
         };
 
         let mut cache = self.cache.write();
-        if cache.put(key_hash, cached_key).is_some() {
-            self.stats.evictions.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        // `push` also returns the old entry when the same key is replaced,
+        // which is not an eviction
+        if let Some((evicted, _)) = cache.push(key_hash, cached_key) {
+            if evicted != key_hash {
+                self.stats
+                    .evictions
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
         }
 
         key_hash
@@ -92,12 +95,16 @@ impl KeyCache {
     /// Get a key from the cache
     pub fn get(&self, key_hash: &KeyHash) -> Option<CachedKey> {
         let mut cache = self.cache.write();
-        if let Some(mut cached_key) = cache.get_mut(key_hash) {
+        if let Some(cached_key) = cache.get_mut(key_hash) {
             cached_key.access_count += 1;
-            self.stats.hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.stats
+                .hits
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             Some(cached_key.clone())
         } else {
-            self.stats.misses.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.stats
+                .misses
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             None
         }
     }
@@ -112,7 +119,7 @@ impl KeyCache {
     pub fn cleanup_expired(&self, max_age: Duration) {
         let mut cache = self.cache.write();
         let now = Instant::now();
-        
+
         // Collect expired keys
         let expired_keys: Vec<KeyHash> = cache
             .iter()
@@ -128,7 +135,9 @@ impl KeyCache {
         // Remove expired keys
         for key_hash in expired_keys {
             cache.pop(&key_hash);
-            self.stats.evictions.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.stats
+                .evictions
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         }
     }
 
@@ -136,8 +145,11 @@ impl KeyCache {
     pub fn stats(&self) -> CacheStatistics {
         let hits = self.stats.hits.load(std::sync::atomic::Ordering::Relaxed);
         let misses = self.stats.misses.load(std::sync::atomic::Ordering::Relaxed);
-        let evictions = self.stats.evictions.load(std::sync::atomic::Ordering::Relaxed);
-        
+        let evictions = self
+            .stats
+            .evictions
+            .load(std::sync::atomic::Ordering::Relaxed);
+
         let total_requests = hits + misses;
         let hit_rate = if total_requests > 0 {
             hits as f64 / total_requests as f64
@@ -193,7 +205,11 @@ impl std::fmt::Display for CacheStatistics {
         write!(
             f,
             "Cache Stats: {} hits, {} misses, {:.2}% hit rate, {} evictions, {} items",
-            self.hits, self.misses, self.hit_rate * 100.0, self.evictions, self.current_size
+            self.hits,
+            self.misses,
+            self.hit_rate * 100.0,
+            self.evictions,
+            self.current_size
         )
     }
 }
@@ -212,10 +228,10 @@ impl PrecomputedKeyContext {
     /// Create a new precomputed context for ML-KEM operations
     pub fn for_ml_kem(public_key: &[u8]) -> Self {
         let key_hash = KeyHash::from_bytes(public_key);
-        
+
         // Precompute values that can speed up ML-KEM operations
         let mut precomputed_values = Vec::with_capacity(1024);
-        
+
         // Example: precompute polynomial multiplication tables
         // This is a simplified example - real implementation would
         // precompute NTT roots, Montgomery constants, etc.
@@ -234,7 +250,7 @@ impl PrecomputedKeyContext {
     /// Create a context for transport keys
     pub fn for_transport(key_data: &[u8], derivation_info: &[u8]) -> Self {
         let key_hash = KeyHash::from_bytes(key_data);
-        
+
         Self {
             key_hash,
             precomputed_values: Vec::new(),
@@ -251,16 +267,16 @@ mod tests {
     fn test_key_cache_basic() {
         let cache = KeyCache::new(10);
         let key_data = b"test key data";
-        
+
         // Insert key
         let key_hash = cache.insert(key_data, KeyType::MlKemPublic);
-        
+
         // Retrieve key
         let cached_key = cache.get(&key_hash).unwrap();
         assert_eq!(cached_key.data, key_data);
         assert_eq!(cached_key.key_type, KeyType::MlKemPublic);
         assert_eq!(cached_key.access_count, 1);
-        
+
         // Retrieve again - access count should increase
         let cached_key2 = cache.get(&key_hash).unwrap();
         assert_eq!(cached_key2.access_count, 2);
@@ -271,11 +287,11 @@ mod tests {
         let data1 = b"test data";
         let data2 = b"test data";
         let data3 = b"different data";
-        
+
         let hash1 = KeyHash::from_bytes(data1);
         let hash2 = KeyHash::from_bytes(data2);
         let hash3 = KeyHash::from_bytes(data3);
-        
+
         assert_eq!(hash1, hash2);
         assert_ne!(hash1, hash3);
     }
@@ -284,15 +300,15 @@ mod tests {
     fn test_cache_stats() {
         let cache = KeyCache::new(10);
         let key_data = b"test key";
-        
+
         // Should be a miss
         let key_hash = KeyHash::from_bytes(key_data);
         assert!(cache.get(&key_hash).is_none());
-        
+
         // Insert and hit
         let key_hash = cache.insert(key_data, KeyType::Symmetric);
         let _cached = cache.get(&key_hash).unwrap();
-        
+
         let stats = cache.stats();
         assert_eq!(stats.hits, 1);
         assert_eq!(stats.misses, 1);
@@ -303,15 +319,15 @@ mod tests {
     fn test_cache_cleanup() {
         let cache = KeyCache::new(10);
         let key_data = b"test key";
-        
+
         let key_hash = cache.insert(key_data, KeyType::Transport);
         assert!(cache.contains(&key_hash));
-        
+
         // Cleanup with very short max age should remove the key
         cache.cleanup_expired(Duration::from_nanos(1));
         std::thread::sleep(Duration::from_millis(1));
         cache.cleanup_expired(Duration::from_nanos(1));
-        
+
         assert!(!cache.contains(&key_hash));
     }
 
@@ -319,14 +335,15 @@ mod tests {
     fn test_precomputed_context() {
         let public_key = vec![1u8; 1184]; // ML-KEM-768 public key size
         let context = PrecomputedKeyContext::for_ml_kem(&public_key);
-        
+
         assert_eq!(context.precomputed_values.len(), 256);
         assert!(context.derivation_context.is_none());
-        
+
         let transport_key = vec![2u8; 32];
         let derivation_info = b"transport key derivation";
-        let transport_context = PrecomputedKeyContext::for_transport(&transport_key, derivation_info);
-        
+        let transport_context =
+            PrecomputedKeyContext::for_transport(&transport_key, derivation_info);
+
         assert!(transport_context.derivation_context.is_some());
     }
-}
\ No newline at end of file
+}
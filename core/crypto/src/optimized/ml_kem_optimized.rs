@@ -1,23 +1,37 @@
-//! Optimized ML-KEM implementation with reduced allocations and improved cache efficiency
-
-use crate::kem::{KEMError, KeyEncapsulation, PublicKey, SecretKey, Ciphertext, SharedSecret};
+//! Optimized ML-KEM front-end with reduced allocations and key caching
+//!
+//! The cryptographic work is done by [`MlKem768`], whose scratch buffers come
+//! from the global buffer pool when the `optimized` feature is enabled. This
+//! wrapper adds a public key cache (so repeated encapsulations to the same
+//! peer skip key validation) and per-instance performance metrics.
+
+use crate::kem::{Ciphertext, KEMError, KeyEncapsulation, PublicKey, SecretKey, SharedSecret};
+use crate::ml_kem::MlKem768;
+use crate::optimized::cache::{KeyHash, KeyType};
 use crate::optimized::{CRYPTO_BUFFER_POOL, KEY_CACHE};
-use crate::optimized::cache::{KeyType, KeyHash, PrecomputedKeyContext};
-use ring::rand::SystemRandom;
-use std::sync::Arc;
 use std::time::Instant;
-use zeroize::Zeroize;
 
 /// Optimized ML-KEM 768 implementation with performance enhancements
+#[derive(Default)]
 pub struct OptimizedMlKem768 {
-    /// Random number generator (reused to avoid initialization overhead)
-    rng: SystemRandom,
     /// Performance metrics
     metrics: OptimizedMetrics,
+    /// Accumulated operation times used to derive the averages
+    timings: OperationTimings,
 }
 
-/// Performance metrics for the optimized implementation
 #[derive(Default, Clone)]
+struct OperationTimings {
+    keygen_ns: u64,
+    keygen_count: u64,
+    encap_ns: u64,
+    encap_count: u64,
+    decap_ns: u64,
+    decap_count: u64,
+}
+
+/// Performance metrics for the optimized implementation
+#[derive(Debug, Default, Clone)]
 pub struct OptimizedMetrics {
     /// Number of buffer pool hits
     pub buffer_pool_hits: usize,
@@ -37,128 +51,97 @@ pub struct OptimizedMetrics {
     pub total_operations: usize,
 }
 
+fn average(total: u64, count: u64) -> u64 {
+    total.checked_div(count).unwrap_or(0)
+}
+
 impl OptimizedMlKem768 {
     /// Size of public keys in bytes
-    pub const PUBLIC_KEY_SIZE: usize = 1184;
-    
+    pub const PUBLIC_KEY_SIZE: usize = MlKem768::PUBLIC_KEY_SIZE;
+
     /// Size of secret keys in bytes
-    pub const SECRET_KEY_SIZE: usize = 2400;
-    
+    pub const SECRET_KEY_SIZE: usize = MlKem768::SECRET_KEY_SIZE;
+
     /// Size of ciphertexts in bytes
-    pub const CIPHERTEXT_SIZE: usize = 1088;
-    
+    pub const CIPHERTEXT_SIZE: usize = MlKem768::CIPHERTEXT_SIZE;
+
     /// Size of shared secrets in bytes
-    pub const SHARED_SECRET_SIZE: usize = 32;
+    pub const SHARED_SECRET_SIZE: usize = MlKem768::SHARED_SECRET_SIZE;
 
     /// Create a new optimized ML-KEM instance
     pub fn new() -> Self {
-        Self {
-            rng: SystemRandom::new(),
-            metrics: OptimizedMetrics::default(),
-        }
+        Self::default()
+    }
+
+    /// Run an operation while tracking buffer pool usage
+    fn track_pool<T>(&mut self, op: impl FnOnce() -> T) -> T {
+        let before = CRYPTO_BUFFER_POOL.stats();
+        let result = op();
+        let after = CRYPTO_BUFFER_POOL.stats();
+        self.metrics.buffer_pool_hits += after.hits.saturating_sub(before.hits);
+        self.metrics.buffer_pool_misses += after.misses.saturating_sub(before.misses);
+        result
     }
 
     /// Generate a new keypair with optimized buffer management
     pub fn keygen_optimized(&mut self) -> Result<(PublicKey, SecretKey), KEMError> {
         let start_time = Instant::now();
+        let keypair = self.track_pool(MlKem768::keygen)?;
 
-        // Use buffer pool for key generation to reduce allocations
-        let mut pk_buffer = CRYPTO_BUFFER_POOL.acquire(Self::PUBLIC_KEY_SIZE);
-        let mut sk_buffer = CRYPTO_BUFFER_POOL.acquire(Self::SECRET_KEY_SIZE);
-
-        // Fill with random data (placeholder implementation)
-        self.rng.fill(&mut pk_buffer.as_mut_slice())
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-        self.rng.fill(&mut sk_buffer.as_mut_slice())
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-
-        // Cache the generated keys for potential reuse
-        let pk_hash = KEY_CACHE.insert(&pk_buffer, KeyType::MlKemPublic);
-        let sk_hash = KEY_CACHE.insert(&sk_buffer, KeyType::MlKemSecret);
-
-        // Create keys from buffers
-        let public_key = PublicKey::from_bytes(&pk_buffer)?;
-        let secret_key = SecretKey::from_bytes(&sk_buffer)?;
+        // Own public keys are likely to be used for encapsulation soon
+        KEY_CACHE.insert(keypair.0.as_bytes(), KeyType::MlKemPublic);
 
-        // Update metrics
-        let elapsed = start_time.elapsed();
-        self.metrics.avg_keygen_time_ns = 
-            (self.metrics.avg_keygen_time_ns + elapsed.as_nanos() as u64) / 2;
+        self.timings.keygen_ns += start_time.elapsed().as_nanos() as u64;
+        self.timings.keygen_count += 1;
+        self.metrics.avg_keygen_time_ns =
+            average(self.timings.keygen_ns, self.timings.keygen_count);
         self.metrics.total_operations += 1;
 
-        Ok((public_key, secret_key))
+        Ok(keypair)
     }
 
     /// Encapsulate with key caching and buffer reuse
-    pub fn encapsulate_optimized(&mut self, pk: &PublicKey) -> Result<(Ciphertext, SharedSecret), KEMError> {
+    pub fn encapsulate_optimized(
+        &mut self,
+        pk: &PublicKey,
+    ) -> Result<(Ciphertext, SharedSecret), KEMError> {
         let start_time = Instant::now();
 
-        // Check if we have a precomputed context for this key
         let pk_bytes = pk.as_bytes();
         let pk_hash = KeyHash::from_bytes(pk_bytes);
-        
-        let use_cached = if let Some(_cached_key) = KEY_CACHE.get(&pk_hash) {
+        if KEY_CACHE.get(&pk_hash).is_some() {
             self.metrics.key_cache_hits += 1;
-            true
         } else {
             self.metrics.key_cache_misses += 1;
-            // Cache the key for future operations
+            if pk_bytes.len() != Self::PUBLIC_KEY_SIZE {
+                return Err(KEMError::InvalidKey);
+            }
             KEY_CACHE.insert(pk_bytes, KeyType::MlKemPublic);
-            false
-        };
-
-        // Use buffer pool for temporary allocations
-        let mut ct_buffer = CRYPTO_BUFFER_POOL.acquire(Self::CIPHERTEXT_SIZE);
-        let mut ss_buffer = CRYPTO_BUFFER_POOL.acquire(Self::SHARED_SECRET_SIZE);
-
-        if use_cached {
-            // Use optimized path with cached computations
-            self.encapsulate_with_cache(&pk_hash, &mut ct_buffer, &mut ss_buffer)?;
-        } else {
-            // Standard path with caching for future use
-            self.encapsulate_standard(pk, &mut ct_buffer, &mut ss_buffer)?;
         }
 
-        let ciphertext = Ciphertext::from_bytes(&ct_buffer)?;
-        let shared_secret = SharedSecret::from_bytes(&ss_buffer)?;
+        let result = self.track_pool(|| MlKem768::encapsulate(pk))?;
 
-        // Update metrics
-        let elapsed = start_time.elapsed();
-        self.metrics.avg_encap_time_ns = 
-            (self.metrics.avg_encap_time_ns + elapsed.as_nanos() as u64) / 2;
+        self.timings.encap_ns += start_time.elapsed().as_nanos() as u64;
+        self.timings.encap_count += 1;
+        self.metrics.avg_encap_time_ns = average(self.timings.encap_ns, self.timings.encap_count);
         self.metrics.total_operations += 1;
 
-        Ok((ciphertext, shared_secret))
+        Ok(result)
     }
 
     /// Decapsulate with optimized buffer management
-    pub fn decapsulate_optimized(&mut self, sk: &SecretKey, ct: &Ciphertext) -> Result<SharedSecret, KEMError> {
+    pub fn decapsulate_optimized(
+        &mut self,
+        sk: &SecretKey,
+        ct: &Ciphertext,
+    ) -> Result<SharedSecret, KEMError> {
         let start_time = Instant::now();
 
-        // Check for cached secret key
-        let sk_bytes = sk.as_bytes();
-        let sk_hash = KeyHash::from_bytes(sk_bytes);
-        
-        if let Some(_cached_key) = KEY_CACHE.get(&sk_hash) {
-            self.metrics.key_cache_hits += 1;
-        } else {
-            self.metrics.key_cache_misses += 1;
-            KEY_CACHE.insert(sk_bytes, KeyType::MlKemSecret);
-        }
+        let shared_secret = self.track_pool(|| MlKem768::decapsulate(sk, ct))?;
 
-        // Use buffer pool for shared secret computation
-        let mut ss_buffer = CRYPTO_BUFFER_POOL.acquire(Self::SHARED_SECRET_SIZE);
-
-        // Perform decapsulation (placeholder implementation)
-        self.rng.fill(&mut ss_buffer.as_mut_slice())
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-
-        let shared_secret = SharedSecret::from_bytes(&ss_buffer)?;
-
-        // Update metrics
-        let elapsed = start_time.elapsed();
-        self.metrics.avg_decap_time_ns = 
-            (self.metrics.avg_decap_time_ns + elapsed.as_nanos() as u64) / 2;
+        self.timings.decap_ns += start_time.elapsed().as_nanos() as u64;
+        self.timings.decap_count += 1;
+        self.metrics.avg_decap_time_ns = average(self.timings.decap_ns, self.timings.decap_count);
         self.metrics.total_operations += 1;
 
         Ok(shared_secret)
@@ -166,68 +149,7 @@ impl OptimizedMlKem768 {
 
     /// Batch key generation for improved throughput
     pub fn batch_keygen(&mut self, count: usize) -> Result<Vec<(PublicKey, SecretKey)>, KEMError> {
-        let mut keypairs = Vec::with_capacity(count);
-        
-        // Pre-allocate all buffers to reduce allocation overhead
-        let mut pk_buffers: Vec<_> = (0..count)
-            .map(|_| CRYPTO_BUFFER_POOL.acquire(Self::PUBLIC_KEY_SIZE))
-            .collect();
-        let mut sk_buffers: Vec<_> = (0..count)
-            .map(|_| CRYPTO_BUFFER_POOL.acquire(Self::SECRET_KEY_SIZE))
-            .collect();
-
-        // Generate all keys in batch
-        for i in 0..count {
-            self.rng.fill(&mut pk_buffers[i].as_mut_slice())
-                .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-            self.rng.fill(&mut sk_buffers[i].as_mut_slice())
-                .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-
-            let public_key = PublicKey::from_bytes(&pk_buffers[i])?;
-            let secret_key = SecretKey::from_bytes(&sk_buffers[i])?;
-            
-            keypairs.push((public_key, secret_key));
-        }
-
-        self.metrics.total_operations += count;
-        Ok(keypairs)
-    }
-
-    /// Encapsulation with cached key computations
-    fn encapsulate_with_cache(
-        &self,
-        _pk_hash: &KeyHash,
-        ct_buffer: &mut [u8],
-        ss_buffer: &mut [u8],
-    ) -> Result<(), KEMError> {
-        // Use precomputed values for faster encapsulation
-        // This is a placeholder - real implementation would use
-        // cached NTT transforms, precomputed matrices, etc.
-        
-        self.rng.fill(ct_buffer)
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-        self.rng.fill(ss_buffer)
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-            
-        Ok(())
-    }
-
-    /// Standard encapsulation path
-    fn encapsulate_standard(
-        &self,
-        pk: &PublicKey,
-        ct_buffer: &mut [u8],
-        ss_buffer: &mut [u8],
-    ) -> Result<(), KEMError> {
-        // Standard ML-KEM encapsulation
-        // This is a placeholder implementation
-        
-        self.rng.fill(ct_buffer)
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-        self.rng.fill(ss_buffer)
-            .map_err(|_| KEMError::InvalidInput("Random generation failed".into()))?;
-            
-        Ok(())
+        (0..count).map(|_| self.keygen_optimized()).collect()
     }
 
     /// Get performance metrics
@@ -238,40 +160,31 @@ impl OptimizedMlKem768 {
     /// Reset performance metrics
     pub fn reset_metrics(&mut self) {
         self.metrics = OptimizedMetrics::default();
+        self.timings = OperationTimings::default();
     }
 
-    /// Warm up caches with frequently used keys
-    pub fn warm_cache(&mut self, public_keys: &[&[u8]], secret_keys: &[&[u8]]) {
+    /// Warm up the cache with frequently used public keys
+    pub fn warm_cache(&mut self, public_keys: &[&[u8]]) {
         for pk_bytes in public_keys {
             KEY_CACHE.insert(pk_bytes, KeyType::MlKemPublic);
         }
-        
-        for sk_bytes in secret_keys {
-            KEY_CACHE.insert(sk_bytes, KeyType::MlKemSecret);
-        }
-    }
-}
-
-impl Default for OptimizedMlKem768 {
-    fn default() -> Self {
-        Self::new()
     }
 }
 
 impl KeyEncapsulation for OptimizedMlKem768 {
     fn keygen() -> Result<(PublicKey, SecretKey), KEMError> {
-        let mut instance = Self::new();
-        instance.keygen_optimized()
+        Self::new().keygen_optimized()
     }
-    
+
     fn encapsulate(public_key: &PublicKey) -> Result<(Ciphertext, SharedSecret), KEMError> {
-        let mut instance = Self::new();
-        instance.encapsulate_optimized(public_key)
+        Self::new().encapsulate_optimized(public_key)
     }
-    
-    fn decapsulate(secret_key: &SecretKey, ciphertext: &Ciphertext) -> Result<SharedSecret, KEMError> {
-        let mut instance = Self::new();
-        instance.decapsulate_optimized(secret_key, ciphertext)
+
+    fn decapsulate(
+        secret_key: &SecretKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<SharedSecret, KEMError> {
+        Self::new().decapsulate_optimized(secret_key, ciphertext)
     }
 }
 
@@ -284,7 +197,7 @@ pub struct BatchProcessor {
 impl BatchProcessor {
     /// Create a new batch processor with multiple instances
     pub fn new(num_instances: usize) -> Self {
-        let instances = (0..num_instances)
+        let instances = (0..num_instances.max(1))
             .map(|_| OptimizedMlKem768::new())
             .collect();
 
@@ -295,65 +208,69 @@ impl BatchProcessor {
     }
 
     /// Process a batch of key generations across multiple instances
-    pub fn batch_keygen_parallel(&mut self, total_count: usize) -> Result<Vec<(PublicKey, SecretKey)>, KEMError> {
+    pub fn batch_keygen_parallel(
+        &mut self,
+        total_count: usize,
+    ) -> Result<Vec<(PublicKey, SecretKey)>, KEMError> {
         let per_instance = total_count / self.instances.len();
         let remainder = total_count % self.instances.len();
 
-        let mut all_keypairs = Vec::with_capacity(total_count);
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .instances
+                .iter_mut()
+                .enumerate()
+                .map(|(i, instance)| {
+                    let count = per_instance + usize::from(i < remainder);
+                    scope.spawn(move || instance.batch_keygen(count))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or(Err(KEMError::InternalError)))
+                .collect()
+        });
 
-        // Distribute work across instances
-        for (i, instance) in self.instances.iter_mut().enumerate() {
-            let count = if i < remainder {
-                per_instance + 1
-            } else {
-                per_instance
-            };
-
-            if count > 0 {
-                let mut keypairs = instance.batch_keygen(count)?;
-                all_keypairs.append(&mut keypairs);
-            }
+        let mut all_keypairs = Vec::with_capacity(total_count);
+        for keypairs in results {
+            all_keypairs.extend(keypairs?);
         }
-
         Ok(all_keypairs)
     }
 
     /// Get the next available instance for load balancing
     pub fn get_next_instance(&mut self) -> &mut OptimizedMlKem768 {
-        let instance = &mut self.instances[self.current_instance];
-        self.current_instance = (self.current_instance + 1) % self.instances.len();
-        instance
+        let index = self.current_instance;
+        self.current_instance = (index + 1) % self.instances.len();
+        &mut self.instances[index]
     }
 
     /// Get aggregated metrics from all instances
     pub fn get_aggregated_metrics(&self) -> OptimizedMetrics {
         let mut aggregated = OptimizedMetrics::default();
+        let mut timings = OperationTimings::default();
 
         for instance in &self.instances {
-            let metrics = instance.get_metrics();
+            let metrics = &instance.metrics;
             aggregated.buffer_pool_hits += metrics.buffer_pool_hits;
             aggregated.buffer_pool_misses += metrics.buffer_pool_misses;
             aggregated.key_cache_hits += metrics.key_cache_hits;
             aggregated.key_cache_misses += metrics.key_cache_misses;
             aggregated.total_operations += metrics.total_operations;
-        }
 
-        // Average the timing metrics
-        let num_instances = self.instances.len() as u64;
-        if num_instances > 0 {
-            aggregated.avg_keygen_time_ns = self.instances.iter()
-                .map(|i| i.get_metrics().avg_keygen_time_ns)
-                .sum::<u64>() / num_instances;
-                
-            aggregated.avg_encap_time_ns = self.instances.iter()
-                .map(|i| i.get_metrics().avg_encap_time_ns)
-                .sum::<u64>() / num_instances;
-                
-            aggregated.avg_decap_time_ns = self.instances.iter()
-                .map(|i| i.get_metrics().avg_decap_time_ns)
-                .sum::<u64>() / num_instances;
+            timings.keygen_ns += instance.timings.keygen_ns;
+            timings.keygen_count += instance.timings.keygen_count;
+            timings.encap_ns += instance.timings.encap_ns;
+            timings.encap_count += instance.timings.encap_count;
+            timings.decap_ns += instance.timings.decap_ns;
+            timings.decap_count += instance.timings.decap_count;
         }
 
+        aggregated.avg_keygen_time_ns = average(timings.keygen_ns, timings.keygen_count);
+        aggregated.avg_encap_time_ns = average(timings.encap_ns, timings.encap_count);
+        aggregated.avg_decap_time_ns = average(timings.decap_ns, timings.decap_count);
+
         aggregated
     }
 }
@@ -366,7 +283,7 @@ mod tests {
     fn test_optimized_keygen() {
         let mut ml_kem = OptimizedMlKem768::new();
         let (pk, sk) = ml_kem.keygen_optimized().unwrap();
-        
+
         assert_eq!(pk.as_bytes().len(), OptimizedMlKem768::PUBLIC_KEY_SIZE);
         assert_eq!(sk.as_bytes().len(), OptimizedMlKem768::SECRET_KEY_SIZE);
     }
@@ -375,18 +292,29 @@ mod tests {
     fn test_optimized_encapsulation() {
         let mut ml_kem = OptimizedMlKem768::new();
         let (pk, _sk) = ml_kem.keygen_optimized().unwrap();
-        
+
         let (ct, ss) = ml_kem.encapsulate_optimized(&pk).unwrap();
-        
+
         assert_eq!(ct.as_bytes().len(), OptimizedMlKem768::CIPHERTEXT_SIZE);
         assert_eq!(ss.as_bytes().len(), OptimizedMlKem768::SHARED_SECRET_SIZE);
     }
 
+    #[test]
+    fn test_rejects_invalid_public_key() {
+        let mut ml_kem = OptimizedMlKem768::new();
+        let pk = PublicKey::from_bytes(&[0u8; 16]).unwrap();
+
+        assert!(matches!(
+            ml_kem.encapsulate_optimized(&pk),
+            Err(KEMError::InvalidKey)
+        ));
+    }
+
     #[test]
     fn test_batch_keygen() {
         let mut ml_kem = OptimizedMlKem768::new();
         let keypairs = ml_kem.batch_keygen(10).unwrap();
-        
+
         assert_eq!(keypairs.len(), 10);
         for (pk, sk) in keypairs {
             assert_eq!(pk.as_bytes().len(), OptimizedMlKem768::PUBLIC_KEY_SIZE);
@@ -397,44 +325,33 @@ mod tests {
     #[test]
     fn test_metrics_tracking() {
         let mut ml_kem = OptimizedMlKem768::new();
-        
-        // Perform some operations
+
         let (_pk, _sk) = ml_kem.keygen_optimized().unwrap();
         let metrics = ml_kem.get_metrics();
-        
-        assert!(metrics.total_operations > 0);
-        assert!(metrics.avg_keygen_time_ns > 0);
+
+        assert_eq!(metrics.total_operations, 1);
+        assert!(metrics.buffer_pool_hits + metrics.buffer_pool_misses > 0);
     }
 
     #[test]
     fn test_batch_processor() {
         let mut processor = BatchProcessor::new(4);
-        let keypairs = processor.batch_keygen_parallel(20).unwrap();
-        
-        assert_eq!(keypairs.len(), 20);
-        
+        let keypairs = processor.batch_keygen_parallel(22).unwrap();
+
+        assert_eq!(keypairs.len(), 22);
+
         let metrics = processor.get_aggregated_metrics();
-        assert!(metrics.total_operations >= 20);
+        assert_eq!(metrics.total_operations, 22);
     }
 
     #[test]
     fn test_cache_warming() {
         let mut ml_kem = OptimizedMlKem768::new();
-        
-        // Generate some keys
-        let (pk1, sk1) = ml_kem.keygen_optimized().unwrap();
-        let (pk2, sk2) = ml_kem.keygen_optimized().unwrap();
-        
-        // Warm cache
-        ml_kem.warm_cache(
-            &[pk1.as_bytes(), pk2.as_bytes()],
-            &[sk1.as_bytes(), sk2.as_bytes()]
-        );
-        
-        // Using cached keys should improve performance
-        let (_ct, _ss) = ml_kem.encapsulate_optimized(&pk1).unwrap();
-        let metrics = ml_kem.get_metrics();
-        
-        assert!(metrics.key_cache_hits > 0);
+        let (pk, _sk) = MlKem768::keygen().unwrap();
+
+        ml_kem.warm_cache(&[pk.as_bytes()]);
+        let (_ct, _ss) = ml_kem.encapsulate_optimized(&pk).unwrap();
+
+        assert!(ml_kem.get_metrics().key_cache_hits > 0);
     }
-}
\ No newline at end of file
+}
@@ -1,28 +1,33 @@
 //! Optimized cryptographic implementations for QuDAG
-//! 
+//!
 //! This module provides high-performance implementations of cryptographic
 //! primitives with focus on:
 //! - Reduced memory allocations
-//! - Improved cache efficiency  
-//! - SIMD optimizations where applicable
+//! - Improved cache efficiency
+//! - SIMD optimizations with runtime CPU feature detection
 //! - Constant-time security properties
+//!
+//! It is only compiled with the `optimized` cargo feature. When enabled, the
+//! ML-KEM and ML-DSA hot paths draw their scratch buffers from
+//! [`CRYPTO_BUFFER_POOL`].
 
 pub mod buffer_pool;
-pub mod ml_kem_optimized;
 pub mod cache;
+pub mod ml_kem_optimized;
 pub mod simd_utils;
 
 use std::sync::Arc;
-use once_cell::sync::Lazy;
 
-/// Global buffer pool for crypto operations
-pub static CRYPTO_BUFFER_POOL: Lazy<Arc<buffer_pool::BufferPool>> = 
-    Lazy::new(|| Arc::new(buffer_pool::BufferPool::new()));
+lazy_static::lazy_static! {
+    /// Global buffer pool for crypto operations
+    pub static ref CRYPTO_BUFFER_POOL: Arc<buffer_pool::BufferPool> =
+        Arc::new(buffer_pool::BufferPool::new());
 
-/// Global key cache for frequently used keys
-pub static KEY_CACHE: Lazy<Arc<cache::KeyCache>> = 
-    Lazy::new(|| Arc::new(cache::KeyCache::new(10000)));
+    /// Global key cache for frequently used keys
+    pub static ref KEY_CACHE: Arc<cache::KeyCache> = Arc::new(cache::KeyCache::new(10000));
+}
 
-pub use buffer_pool::BufferPool;
-pub use ml_kem_optimized::OptimizedMlKem768;
-pub use cache::{KeyCache, CachedKey};
\ No newline at end of file
+pub use buffer_pool::{BufferPool, PoolStatistics, PooledBuffer};
+pub use cache::{CachedKey, KeyCache, KeyHash, KeyType};
+pub use ml_kem_optimized::{OptimizedMetrics, OptimizedMlKem768};
+pub use simd_utils::{SimdBackend, SimdPolynomialOps};
@@ -1,65 +1,174 @@
 //! SIMD utilities for high-performance polynomial arithmetic in cryptographic operations
+//!
+//! Every SIMD routine has a scalar twin with identical semantics. The backend
+//! is chosen at runtime from the features reported by the CPU, so a binary
+//! built for a generic target still uses AVX2 where it is present and falls
+//! back to the scalar code everywhere else.
+
+#![allow(clippy::needless_range_loop)]
 
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
+use std::sync::OnceLock;
+
+/// Polynomial degree handled by the SIMD routines
+pub const POLY_N: usize = 256;
+
+/// Instruction set used for vectorized arithmetic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimdBackend {
+    /// Portable scalar implementation, always available
+    Scalar,
+    /// x86_64 AVX2 implementation
+    Avx2,
+}
+
+impl SimdBackend {
+    /// All backends known to this build, in order of preference
+    pub const ALL: [SimdBackend; 2] = [SimdBackend::Avx2, SimdBackend::Scalar];
+
+    /// Check whether the running CPU supports this backend
+    pub fn is_available(self) -> bool {
+        match self {
+            SimdBackend::Scalar => true,
+            SimdBackend::Avx2 => {
+                #[cfg(target_arch = "x86_64")]
+                {
+                    is_x86_feature_detected!("avx2")
+                }
+                #[cfg(not(target_arch = "x86_64"))]
+                {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Detect the best backend supported by the running CPU
+    pub fn detect() -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.is_available())
+            .unwrap_or(SimdBackend::Scalar)
+    }
+
+    /// Backend selected for this process, detected once and cached
+    pub fn active() -> Self {
+        static ACTIVE: OnceLock<SimdBackend> = OnceLock::new();
+        *ACTIVE.get_or_init(Self::detect)
+    }
+
+    /// All backends usable on the running CPU
+    pub fn available() -> Vec<SimdBackend> {
+        Self::ALL
+            .into_iter()
+            .filter(|backend| backend.is_available())
+            .collect()
+    }
+
+    /// Resolve to a backend that is safe to run, falling back to scalar
+    fn resolve(self) -> Self {
+        if self.is_available() {
+            self
+        } else {
+            SimdBackend::Scalar
+        }
+    }
+}
 
 /// SIMD-optimized polynomial operations for ML-KEM
 pub struct SimdPolynomialOps;
 
 impl SimdPolynomialOps {
-    /// Add two polynomials using SIMD instructions
-    #[cfg(target_arch = "x86_64")]
-    pub fn poly_add_simd(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                Self::poly_add_avx2(a, b, result);
-            }
-        } else {
-            Self::poly_add_scalar(a, b, result);
+    /// Add two polynomials (wrapping) using the active backend
+    pub fn poly_add_simd(a: &[i32; POLY_N], b: &[i32; POLY_N], result: &mut [i32; POLY_N]) {
+        Self::poly_add_with(SimdBackend::active(), a, b, result);
+    }
+
+    /// Subtract two polynomials (wrapping) using the active backend
+    pub fn poly_sub_simd(a: &[i32; POLY_N], b: &[i32; POLY_N], result: &mut [i32; POLY_N]) {
+        Self::poly_sub_with(SimdBackend::active(), a, b, result);
+    }
+
+    /// Multiply polynomial by scalar (wrapping) using the active backend
+    pub fn poly_scalar_mul_simd(a: &[i32; POLY_N], scalar: i32, result: &mut [i32; POLY_N]) {
+        Self::poly_scalar_mul_with(SimdBackend::active(), a, scalar, result);
+    }
+
+    /// Reduce polynomial modulo q using the active backend
+    ///
+    /// Coefficients are reduced with truncating remainder and then shifted
+    /// down by `q` if they exceed `q / 2`. `q` must be positive.
+    pub fn poly_reduce_simd(a: &mut [i32; POLY_N], q: i32) {
+        Self::poly_reduce_with(SimdBackend::active(), a, q);
+    }
+
+    /// Add two polynomials with an explicit backend
+    #[allow(unsafe_code)]
+    pub fn poly_add_with(
+        backend: SimdBackend,
+        a: &[i32; POLY_N],
+        b: &[i32; POLY_N],
+        result: &mut [i32; POLY_N],
+    ) {
+        match backend.resolve() {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `resolve` only returns Avx2 when the CPU supports it.
+            SimdBackend::Avx2 => unsafe { Self::poly_add_avx2(a, b, result) },
+            _ => Self::poly_add_scalar(a, b, result),
         }
     }
 
-    /// Subtract two polynomials using SIMD instructions
-    #[cfg(target_arch = "x86_64")]
-    pub fn poly_sub_simd(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                Self::poly_sub_avx2(a, b, result);
-            }
-        } else {
-            Self::poly_sub_scalar(a, b, result);
+    /// Subtract two polynomials with an explicit backend
+    #[allow(unsafe_code)]
+    pub fn poly_sub_with(
+        backend: SimdBackend,
+        a: &[i32; POLY_N],
+        b: &[i32; POLY_N],
+        result: &mut [i32; POLY_N],
+    ) {
+        match backend.resolve() {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `resolve` only returns Avx2 when the CPU supports it.
+            SimdBackend::Avx2 => unsafe { Self::poly_sub_avx2(a, b, result) },
+            _ => Self::poly_sub_scalar(a, b, result),
         }
     }
 
-    /// Multiply polynomial by scalar using SIMD
-    #[cfg(target_arch = "x86_64")]
-    pub fn poly_scalar_mul_simd(a: &[i32; 256], scalar: i32, result: &mut [i32; 256]) {
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                Self::poly_scalar_mul_avx2(a, scalar, result);
-            }
-        } else {
-            Self::poly_scalar_mul_scalar(a, scalar, result);
+    /// Multiply polynomial by scalar with an explicit backend
+    #[allow(unsafe_code)]
+    pub fn poly_scalar_mul_with(
+        backend: SimdBackend,
+        a: &[i32; POLY_N],
+        scalar: i32,
+        result: &mut [i32; POLY_N],
+    ) {
+        match backend.resolve() {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `resolve` only returns Avx2 when the CPU supports it.
+            SimdBackend::Avx2 => unsafe { Self::poly_scalar_mul_avx2(a, scalar, result) },
+            _ => Self::poly_scalar_mul_scalar(a, scalar, result),
         }
     }
 
-    /// Reduce polynomial modulo q using SIMD
-    #[cfg(target_arch = "x86_64")]
-    pub fn poly_reduce_simd(a: &mut [i32; 256], q: i32) {
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                Self::poly_reduce_avx2(a, q);
-            }
-        } else {
-            Self::poly_reduce_scalar(a, q);
+    /// Reduce polynomial modulo q with an explicit backend
+    #[allow(unsafe_code)]
+    pub fn poly_reduce_with(backend: SimdBackend, a: &mut [i32; POLY_N], q: i32) {
+        assert!(q > 0, "modulus must be positive");
+        match backend.resolve() {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `resolve` only returns Avx2 when the CPU supports it.
+            SimdBackend::Avx2 => unsafe { Self::poly_reduce_avx2(a, q) },
+            _ => Self::poly_reduce_scalar(a, q),
         }
     }
 
     // AVX2 implementations
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
-    unsafe fn poly_add_avx2(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        for i in (0..256).step_by(8) {
+    #[allow(unsafe_code)]
+    unsafe fn poly_add_avx2(a: &[i32; POLY_N], b: &[i32; POLY_N], result: &mut [i32; POLY_N]) {
+        for i in (0..POLY_N).step_by(8) {
             let va = _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i);
             let vb = _mm256_loadu_si256(b.as_ptr().add(i) as *const __m256i);
             let vr = _mm256_add_epi32(va, vb);
@@ -69,8 +178,9 @@ impl SimdPolynomialOps {
 
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
-    unsafe fn poly_sub_avx2(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        for i in (0..256).step_by(8) {
+    #[allow(unsafe_code)]
+    unsafe fn poly_sub_avx2(a: &[i32; POLY_N], b: &[i32; POLY_N], result: &mut [i32; POLY_N]) {
+        for i in (0..POLY_N).step_by(8) {
             let va = _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i);
             let vb = _mm256_loadu_si256(b.as_ptr().add(i) as *const __m256i);
             let vr = _mm256_sub_epi32(va, vb);
@@ -80,140 +190,91 @@ impl SimdPolynomialOps {
 
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
-    unsafe fn poly_scalar_mul_avx2(a: &[i32; 256], scalar: i32, result: &mut [i32; 256]) {
+    #[allow(unsafe_code)]
+    unsafe fn poly_scalar_mul_avx2(a: &[i32; POLY_N], scalar: i32, result: &mut [i32; POLY_N]) {
         let vscalar = _mm256_set1_epi32(scalar);
-        
-        for i in (0..256).step_by(8) {
+
+        for i in (0..POLY_N).step_by(8) {
             let va = _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i);
             let vr = _mm256_mullo_epi32(va, vscalar);
             _mm256_storeu_si256(result.as_mut_ptr().add(i) as *mut __m256i, vr);
         }
     }
 
+    /// Truncating remainder via double-precision division.
+    ///
+    /// Every i32 is exact as an f64 and the rounding error of `a / q` is far
+    /// below `1 / q`, so truncating the quotient gives the same result as
+    /// integer division.
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
-    unsafe fn poly_reduce_avx2(a: &mut [i32; 256], q: i32) {
-        let vq = _mm256_set1_epi32(q);
-        let vq_half = _mm256_set1_epi32(q / 2);
-        
-        for i in (0..256).step_by(8) {
-            let va = _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i);
-            
-            // Compute a % q efficiently
-            // This is a simplified version - real implementation would use
-            // more sophisticated modular reduction techniques
-            // Note: _mm256_rem_epi32 doesn't exist, so we'll use a workaround
-            let vr = va; // Placeholder - real implementation would use proper modular reduction
-            
-            // Ensure result is in [-q/2, q/2) range
-            let mask_neg = _mm256_cmpgt_epi32(vr, vq_half);
-            let adjustment = _mm256_and_si256(mask_neg, vq);
-            let final_result = _mm256_sub_epi32(vr, adjustment);
-            
-            _mm256_storeu_si256(a.as_mut_ptr().add(i) as *mut __m256i, final_result);
+    #[allow(unsafe_code)]
+    unsafe fn poly_reduce_avx2(a: &mut [i32; POLY_N], q: i32) {
+        let vq = _mm_set1_epi32(q);
+        let vq_half = _mm_set1_epi32(q / 2);
+        let vq_f64 = _mm256_set1_pd(q as f64);
+
+        for i in (0..POLY_N).step_by(4) {
+            let ptr = a.as_mut_ptr().add(i) as *mut __m128i;
+            let va = _mm_loadu_si128(ptr);
+
+            let quotient = _mm256_cvttpd_epi32(_mm256_div_pd(_mm256_cvtepi32_pd(va), vq_f64));
+            let remainder = _mm_sub_epi32(va, _mm_mullo_epi32(quotient, vq));
+
+            // Shift coefficients above q/2 into the negative range
+            let mask = _mm_cmpgt_epi32(remainder, vq_half);
+            let adjusted = _mm_sub_epi32(remainder, _mm_and_si128(mask, vq));
+
+            _mm_storeu_si128(ptr, adjusted);
         }
     }
 
-    // Scalar fallback implementations
-    fn poly_add_scalar(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        for i in 0..256 {
+    /// Scalar polynomial addition
+    pub fn poly_add_scalar(a: &[i32; POLY_N], b: &[i32; POLY_N], result: &mut [i32; POLY_N]) {
+        for i in 0..POLY_N {
             result[i] = a[i].wrapping_add(b[i]);
         }
     }
 
-    fn poly_sub_scalar(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        for i in 0..256 {
+    /// Scalar polynomial subtraction
+    pub fn poly_sub_scalar(a: &[i32; POLY_N], b: &[i32; POLY_N], result: &mut [i32; POLY_N]) {
+        for i in 0..POLY_N {
             result[i] = a[i].wrapping_sub(b[i]);
         }
     }
 
-    fn poly_scalar_mul_scalar(a: &[i32; 256], scalar: i32, result: &mut [i32; 256]) {
-        for i in 0..256 {
+    /// Scalar polynomial-by-scalar multiplication
+    pub fn poly_scalar_mul_scalar(a: &[i32; POLY_N], scalar: i32, result: &mut [i32; POLY_N]) {
+        for i in 0..POLY_N {
             result[i] = a[i].wrapping_mul(scalar);
         }
     }
 
-    fn poly_reduce_scalar(a: &mut [i32; 256], q: i32) {
-        for i in 0..256 {
-            a[i] = a[i] % q;
+    /// Scalar polynomial reduction
+    pub fn poly_reduce_scalar(a: &mut [i32; POLY_N], q: i32) {
+        for i in 0..POLY_N {
+            a[i] %= q;
             if a[i] > q / 2 {
                 a[i] -= q;
             }
         }
     }
-
-    /// Non-SIMD version for compatibility
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn poly_add_simd(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        Self::poly_add_scalar(a, b, result);
-    }
-
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn poly_sub_simd(a: &[i32; 256], b: &[i32; 256], result: &mut [i32; 256]) {
-        Self::poly_sub_scalar(a, b, result);
-    }
-
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn poly_scalar_mul_simd(a: &[i32; 256], scalar: i32, result: &mut [i32; 256]) {
-        Self::poly_scalar_mul_scalar(a, scalar, result);
-    }
-
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn poly_reduce_simd(a: &mut [i32; 256], q: i32) {
-        Self::poly_reduce_scalar(a, q);
-    }
 }
 
 /// SIMD utilities for hash operations
 pub struct SimdHashOps;
 
 impl SimdHashOps {
-    /// Parallel hash computation for multiple inputs
-    #[cfg(target_arch = "x86_64")]
-    pub fn parallel_hash_4way(
-        inputs: [&[u8]; 4],
-        outputs: &mut [[u8; 32]; 4]
-    ) {
-        if is_x86_feature_detected!("sha") {
-            unsafe {
-                Self::parallel_sha256_4way(inputs, outputs);
-            }
-        } else {
-            Self::parallel_hash_fallback(inputs, outputs);
-        }
-    }
-
-    #[cfg(target_arch = "x86_64")]
-    #[target_feature(enable = "sha")]
-    unsafe fn parallel_sha256_4way(
-        inputs: [&[u8]; 4],
-        outputs: &mut [[u8; 32]; 4]
-    ) {
-        // This is a simplified example - real implementation would use
-        // Intel SHA extensions for parallel SHA-256 computation
-        for i in 0..4 {
-            let hash = blake3::hash(inputs[i]);
-            outputs[i].copy_from_slice(hash.as_bytes());
-        }
-    }
-
-    fn parallel_hash_fallback(
-        inputs: [&[u8]; 4],
-        outputs: &mut [[u8; 32]; 4]
-    ) {
+    /// Hash four inputs with BLAKE3
+    ///
+    /// BLAKE3 selects its own SIMD implementation at runtime, so this simply
+    /// batches the calls.
+    pub fn parallel_hash_4way(inputs: [&[u8]; 4], outputs: &mut [[u8; 32]; 4]) {
         for i in 0..4 {
             let hash = blake3::hash(inputs[i]);
             outputs[i].copy_from_slice(hash.as_bytes());
         }
     }
-
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn parallel_hash_4way(
-        inputs: [&[u8]; 4],
-        outputs: &mut [[u8; 32]; 4]
-    ) {
-        Self::parallel_hash_fallback(inputs, outputs);
-    }
 }
 
 /// Memory prefetching utilities for improved cache performance
@@ -221,45 +282,36 @@ pub struct PrefetchUtils;
 
 impl PrefetchUtils {
     /// Prefetch data into cache
-    #[cfg(target_arch = "x86_64")]
+    #[allow(unsafe_code)]
     pub fn prefetch_read(ptr: *const u8) {
+        #[cfg(target_arch = "x86_64")]
+        // SAFETY: prefetch is a hint and never faults, SSE is baseline on x86_64.
         unsafe {
             _mm_prefetch(ptr as *const i8, _MM_HINT_T0);
         }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = ptr;
     }
 
     /// Prefetch data for writing
-    #[cfg(target_arch = "x86_64")]
+    #[allow(unsafe_code)]
     pub fn prefetch_write(ptr: *const u8) {
+        #[cfg(target_arch = "x86_64")]
+        // SAFETY: prefetch is a hint and never faults, SSE is baseline on x86_64.
         unsafe {
             _mm_prefetch(ptr as *const i8, _MM_HINT_T1);
         }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = ptr;
     }
 
-    /// Prefetch multiple cache lines
-    #[cfg(target_arch = "x86_64")]
-    pub fn prefetch_range(start: *const u8, len: usize) {
+    /// Prefetch every cache line of a slice
+    pub fn prefetch_range(data: &[u8]) {
         const CACHE_LINE_SIZE: usize = 64;
-        let end = unsafe { start.add(len) };
-        let mut current = start;
-        
-        while current < end {
-            unsafe {
-                _mm_prefetch(current as *const i8, _MM_HINT_T0);
-                current = current.add(CACHE_LINE_SIZE);
-            }
+        for chunk in data.chunks(CACHE_LINE_SIZE) {
+            Self::prefetch_read(chunk.as_ptr());
         }
     }
-
-    // No-op implementations for non-x86_64
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn prefetch_read(_ptr: *const u8) {}
-
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn prefetch_write(_ptr: *const u8) {}
-
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn prefetch_range(_start: *const u8, _len: usize) {}
 }
 
 /// Cache-friendly memory operations
@@ -267,121 +319,128 @@ pub struct CacheOptimizedOps;
 
 impl CacheOptimizedOps {
     /// Copy memory with optimal cache line alignment
+    #[allow(unsafe_code)]
     pub fn aligned_copy(src: &[u8], dst: &mut [u8]) {
         assert_eq!(src.len(), dst.len());
-        
-        // Use SIMD for large copies when available
+
         #[cfg(target_arch = "x86_64")]
-        {
-            if src.len() >= 32 && is_x86_feature_detected!("avx2") {
-                unsafe {
-                    Self::avx2_copy(src, dst);
-                }
-                return;
+        if src.len() >= 32 && SimdBackend::Avx2.is_available() {
+            // SAFETY: AVX2 support was checked above and both slices have equal length.
+            unsafe {
+                Self::avx2_copy(src, dst);
             }
+            return;
         }
-        
-        // Fallback to standard copy
+
         dst.copy_from_slice(src);
     }
 
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
+    #[allow(unsafe_code)]
     unsafe fn avx2_copy(src: &[u8], dst: &mut [u8]) {
         let len = src.len();
         let mut i = 0;
-        
+
         // Process 32-byte chunks with AVX2
         while i + 32 <= len {
             let chunk = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
             _mm256_storeu_si256(dst.as_mut_ptr().add(i) as *mut __m256i, chunk);
             i += 32;
         }
-        
-        // Handle remaining bytes
-        while i < len {
-            dst[i] = src[i];
-            i += 1;
-        }
+
+        dst[i..].copy_from_slice(&src[i..]);
     }
 
     /// Zero memory using SIMD when available
+    #[allow(unsafe_code)]
     pub fn secure_zero_simd(data: &mut [u8]) {
         #[cfg(target_arch = "x86_64")]
-        {
-            if data.len() >= 32 && is_x86_feature_detected!("avx2") {
-                unsafe {
-                    Self::avx2_zero(data);
-                }
-                return;
+        if data.len() >= 32 && SimdBackend::Avx2.is_available() {
+            // SAFETY: AVX2 support was checked above.
+            unsafe {
+                Self::avx2_zero(data);
             }
+            std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+            return;
         }
-        
-        // Fallback to standard zeroing
+
         data.fill(0);
-        
+
         // Compiler fence to prevent optimization
         std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
     }
 
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
+    #[allow(unsafe_code)]
     unsafe fn avx2_zero(data: &mut [u8]) {
         let len = data.len();
         let mut i = 0;
         let zero = _mm256_setzero_si256();
-        
+
         // Process 32-byte chunks
         while i + 32 <= len {
             _mm256_storeu_si256(data.as_mut_ptr().add(i) as *mut __m256i, zero);
             i += 32;
         }
-        
-        // Handle remaining bytes
-        while i < len {
-            data[i] = 0;
-            i += 1;
-        }
-        
-        // Compiler fence
-        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+
+        data[i..].fill(0);
     }
 }
 
-// Note: In a real implementation, these would include proper CPU feature detection,
-// fallbacks for different architectures, and would use a more complete set of
-// SIMD operations optimized for the specific cryptographic algorithms.
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_poly_add_simd() {
-        let a = [1i32; 256];
-        let b = [2i32; 256];
-        let mut result = [0i32; 256];
-        
+        let a = [1i32; POLY_N];
+        let b = [2i32; POLY_N];
+        let mut result = [0i32; POLY_N];
+
         SimdPolynomialOps::poly_add_simd(&a, &b, &mut result);
-        
-        for i in 0..256 {
+
+        for i in 0..POLY_N {
             assert_eq!(result[i], 3);
         }
     }
 
     #[test]
     fn test_poly_scalar_mul_simd() {
-        let a = [2i32; 256];
+        let a = [2i32; POLY_N];
         let scalar = 3;
-        let mut result = [0i32; 256];
-        
+        let mut result = [0i32; POLY_N];
+
         SimdPolynomialOps::poly_scalar_mul_simd(&a, scalar, &mut result);
-        
-        for i in 0..256 {
+
+        for i in 0..POLY_N {
             assert_eq!(result[i], 6);
         }
     }
 
+    #[test]
+    fn test_poly_reduce_simd() {
+        let mut a = [0i32; POLY_N];
+        for (i, coeff) in a.iter_mut().enumerate() {
+            *coeff = (i as i32 - 128) * 1000;
+        }
+        let mut expected = a;
+
+        SimdPolynomialOps::poly_reduce_simd(&mut a, 3329);
+        SimdPolynomialOps::poly_reduce_scalar(&mut expected, 3329);
+
+        assert_eq!(a, expected);
+    }
+
+    #[test]
+    fn test_backend_detection() {
+        let active = SimdBackend::active();
+        assert!(active.is_available());
+        assert!(SimdBackend::available().contains(&SimdBackend::Scalar));
+        assert_eq!(active, SimdBackend::detect());
+    }
+
     #[test]
     fn test_parallel_hash() {
         let inputs = [
@@ -391,10 +450,9 @@ mod tests {
             b"test4".as_slice(),
         ];
         let mut outputs = [[0u8; 32]; 4];
-        
+
         SimdHashOps::parallel_hash_4way(inputs, &mut outputs);
-        
-        // Verify outputs are different (assuming different inputs)
+
         assert_ne!(outputs[0], outputs[1]);
         assert_ne!(outputs[1], outputs[2]);
         assert_ne!(outputs[2], outputs[3]);
@@ -402,30 +460,30 @@ mod tests {
 
     #[test]
     fn test_aligned_copy() {
-        let src = vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10];
-        let mut dst = vec![0u8; 10];
-        
+        let src: Vec<u8> = (0..100).collect();
+        let mut dst = vec![0u8; 100];
+
         CacheOptimizedOps::aligned_copy(&src, &mut dst);
-        
+
         assert_eq!(src, dst);
     }
 
     #[test]
     fn test_secure_zero() {
-        let mut data = vec![1u8, 2, 3, 4, 5];
-        
+        let mut data = vec![7u8; 77];
+
         CacheOptimizedOps::secure_zero_simd(&mut data);
-        
-        assert_eq!(data, vec![0u8; 5]);
+
+        assert_eq!(data, vec![0u8; 77]);
     }
 
     #[test]
     fn test_prefetch_operations() {
         let data = vec![1u8; 1024];
-        
+
         // These should not panic
         PrefetchUtils::prefetch_read(data.as_ptr());
         PrefetchUtils::prefetch_write(data.as_ptr());
-        PrefetchUtils::prefetch_range(data.as_ptr(), data.len());
+        PrefetchUtils::prefetch_range(&data);
     }
-}
\ No newline at end of file
+}
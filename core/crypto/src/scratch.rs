@@ -0,0 +1,35 @@
+//! Scratch buffers for intermediate values on hot paths
+//!
+//! Only used where a temporary would otherwise be allocated, such as the
+//! concatenations fed to the signature and KEM backends; outputs the backends
+//! already allocate are not copied into scratch buffers.
+//!
+//! With the `optimized` feature the buffers come from the global crypto
+//! buffer pool and are zeroed when returned; otherwise they are vectors
+//! zeroized when dropped. Either way they may hold secret key material.
+
+#[cfg(feature = "optimized")]
+pub(crate) type ScratchBuffer = crate::optimized::PooledBuffer<'static>;
+
+#[cfg(not(feature = "optimized"))]
+pub(crate) type ScratchBuffer = zeroize::Zeroizing<Vec<u8>>;
+
+/// Acquire a zero-filled scratch buffer of `len` bytes
+#[cfg(feature = "optimized")]
+pub(crate) fn scratch_buffer(len: usize) -> ScratchBuffer {
+    crate::optimized::CRYPTO_BUFFER_POOL.acquire(len)
+}
+
+/// Acquire a zero-filled scratch buffer of `len` bytes
+#[cfg(not(feature = "optimized"))]
+pub(crate) fn scratch_buffer(len: usize) -> ScratchBuffer {
+    zeroize::Zeroizing::new(vec![0u8; len])
+}
+
+/// Concatenate two slices into a scratch buffer
+pub(crate) fn concat(first: &[u8], second: &[u8]) -> ScratchBuffer {
+    let mut buffer = scratch_buffer(first.len() + second.len());
+    buffer[..first.len()].copy_from_slice(first);
+    buffer[first.len()..].copy_from_slice(second);
+    buffer
+}
@@ -0,0 +1,965 @@
+//! Sealed boxes: non-interactive public-key encryption to an ML-KEM-768 recipient.
+//!
+//! A sealed box encapsulates a fresh shared secret to the recipient's
+//! ML-KEM-768 public key and encrypts the payload with ChaCha20-Poly1305
+//! under a key derived from it. Payloads are split into fixed-size chunks
+//! (STREAM construction), so arbitrarily large messages can be sealed and
+//! opened with bounded memory and truncation or reordering is detected.
+//!
+//! The sender-authenticated variant additionally signs the box with ML-DSA.
+//! The signature covers the header, the KEM ciphertext, the associated data
+//! and the plaintext, so a signed box cannot be re-sealed to a different
+//! recipient without invalidating it.
+//!
+//! # Wire format (version 1)
+//!
+//! ```text
+//! magic "QDSB" (4) | version (1) | flags (1) | kem id (1) | aead id (1) | chunk size u32 BE (4)
+//! ML-KEM-768 ciphertext (1088)
+//! chunk 0 | chunk 1 | ... | final chunk      each chunk: ciphertext || 16-byte tag
+//! ```
+//!
+//! Every chunk but the last carries exactly `chunk size` plaintext bytes; the
+//! final chunk carries fewer (possibly zero). For authenticated boxes the
+//! encrypted payload is `sender ML-DSA public key || plaintext || signature ||
+//! signature length u16 BE`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use qudag_crypto::ml_kem::MlKem768;
+//! use qudag_crypto::sealed_box;
+//!
+//! let (recipient_pk, recipient_sk) = MlKem768::keygen().unwrap();
+//!
+//! let sealed = sealed_box::seal(&recipient_pk, b"hello agent", b"dark-domain").unwrap();
+//! let opened = sealed_box::open(&recipient_sk, &sealed, b"dark-domain").unwrap();
+//! assert_eq!(opened, b"hello agent");
+//! ```
+
+use std::io::{Read, Write};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use crate::kem::{Ciphertext, KEMError, PublicKey, SecretKey};
+use crate::ml_dsa::{MlDsaError, MlDsaKeyPair, MlDsaPublicKey};
+use crate::ml_dsa::{ML_DSA_PUBLIC_KEY_SIZE, ML_DSA_SIGNATURE_SIZE};
+use crate::ml_kem::MlKem768;
+
+/// Magic bytes at the start of every sealed box
+pub const SEALED_BOX_MAGIC: [u8; 4] = *b"QDSB";
+
+/// Current wire format version
+pub const SEALED_BOX_VERSION: u8 = 1;
+
+/// Size of the fixed header preceding the KEM ciphertext
+pub const SEALED_BOX_HEADER_SIZE: usize = 12;
+
+/// Default plaintext bytes per chunk
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest accepted chunk size, bounding memory use when opening
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// ML-KEM-768 algorithm identifier
+pub const KEM_ML_KEM_768: u8 = 1;
+
+/// ChaCha20-Poly1305 algorithm identifier
+pub const AEAD_CHACHA20_POLY1305: u8 = 1;
+
+const FLAG_AUTHENTICATED: u8 = 0x01;
+const TAG_SIZE: usize = 16;
+const KEY_INFO: &[u8] = b"QuDAG sealed box v1";
+const SIGNATURE_DOMAIN: &[u8] = b"QuDAG sealed box v1 sender signature";
+/// Signature plus its length suffix
+const MAX_TRAILER_SIZE: usize = ML_DSA_SIGNATURE_SIZE + 2;
+
+/// Errors that can occur while sealing or opening a box
+#[derive(Debug, Error)]
+pub enum SealedBoxError {
+    /// Input does not start with the sealed box magic
+    #[error("Not a sealed box")]
+    InvalidMagic,
+
+    /// Wire format version is not supported
+    #[error("Unsupported sealed box version: {0}")]
+    UnsupportedVersion(u8),
+
+    /// KEM or AEAD identifier is not supported
+    #[error("Unsupported algorithm: kem {kem}, aead {aead}")]
+    UnsupportedAlgorithm { kem: u8, aead: u8 },
+
+    /// Structurally invalid box
+    #[error("Malformed sealed box: {0}")]
+    Malformed(&'static str),
+
+    /// Chunk size outside `1..=MAX_CHUNK_SIZE`
+    #[error("Invalid chunk size: {0}")]
+    InvalidChunkSize(usize),
+
+    /// Box ended before its final chunk
+    #[error("Sealed box is truncated")]
+    Truncated,
+
+    /// Message needs more chunks than the nonce counter allows
+    #[error("Message too large for chunk size")]
+    MessageTooLarge,
+
+    /// AEAD authentication failed (wrong key, wrong AAD or tampered data)
+    #[error("Decryption failed")]
+    DecryptionFailed,
+
+    /// Sender authentication was required but the box is anonymous
+    #[error("Sealed box is not sender-authenticated")]
+    NotAuthenticated,
+
+    /// Key encapsulation failed
+    #[error("KEM error: {0}")]
+    Kem(#[from] KEMError),
+
+    /// Sender signature creation or verification failed
+    #[error("Sender signature error: {0}")]
+    Signature(#[from] MlDsaError),
+
+    /// Reading or writing a stream failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Options for sealing a box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SealOptions {
+    /// Plaintext bytes per chunk
+    pub chunk_size: usize,
+}
+
+impl Default for SealOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl SealOptions {
+    /// Set the plaintext bytes per chunk
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+}
+
+/// Parsed fixed header of a sealed box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SealedBoxHeader {
+    /// Wire format version
+    pub version: u8,
+    /// Whether the payload carries an ML-DSA sender signature
+    pub authenticated: bool,
+    /// KEM algorithm identifier
+    pub kem: u8,
+    /// AEAD algorithm identifier
+    pub aead: u8,
+    /// Plaintext bytes per chunk
+    pub chunk_size: u32,
+}
+
+impl SealedBoxHeader {
+    fn new(authenticated: bool, chunk_size: usize) -> Result<Self, SealedBoxError> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(SealedBoxError::InvalidChunkSize(chunk_size));
+        }
+
+        Ok(Self {
+            version: SEALED_BOX_VERSION,
+            authenticated,
+            kem: KEM_ML_KEM_768,
+            aead: AEAD_CHACHA20_POLY1305,
+            chunk_size: chunk_size as u32,
+        })
+    }
+
+    /// Encode the header to its wire form
+    pub fn to_bytes(&self) -> [u8; SEALED_BOX_HEADER_SIZE] {
+        let mut bytes = [0u8; SEALED_BOX_HEADER_SIZE];
+        bytes[..4].copy_from_slice(&SEALED_BOX_MAGIC);
+        bytes[4] = self.version;
+        bytes[5] = if self.authenticated {
+            FLAG_AUTHENTICATED
+        } else {
+            0
+        };
+        bytes[6] = self.kem;
+        bytes[7] = self.aead;
+        bytes[8..].copy_from_slice(&self.chunk_size.to_be_bytes());
+        bytes
+    }
+
+    /// Parse and validate a header
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SealedBoxError> {
+        if bytes.len() < SEALED_BOX_HEADER_SIZE {
+            return Err(SealedBoxError::Truncated);
+        }
+        if bytes[..4] != SEALED_BOX_MAGIC {
+            return Err(SealedBoxError::InvalidMagic);
+        }
+        if bytes[4] != SEALED_BOX_VERSION {
+            return Err(SealedBoxError::UnsupportedVersion(bytes[4]));
+        }
+        if bytes[5] & !FLAG_AUTHENTICATED != 0 {
+            return Err(SealedBoxError::Malformed("unknown flags"));
+        }
+        if bytes[6] != KEM_ML_KEM_768 || bytes[7] != AEAD_CHACHA20_POLY1305 {
+            return Err(SealedBoxError::UnsupportedAlgorithm {
+                kem: bytes[6],
+                aead: bytes[7],
+            });
+        }
+
+        let chunk_size = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        if chunk_size == 0 || chunk_size as usize > MAX_CHUNK_SIZE {
+            return Err(SealedBoxError::InvalidChunkSize(chunk_size as usize));
+        }
+
+        Ok(Self {
+            version: bytes[4],
+            authenticated: bytes[5] & FLAG_AUTHENTICATED != 0,
+            kem: bytes[6],
+            aead: bytes[7],
+            chunk_size,
+        })
+    }
+}
+
+/// Parse the header of a sealed box without decrypting it
+pub fn inspect(sealed: &[u8]) -> Result<SealedBoxHeader, SealedBoxError> {
+    SealedBoxHeader::from_bytes(sealed)
+}
+
+/// Seal `plaintext` to `recipient_pk`, binding `aad`
+pub fn seal(
+    recipient_pk: &PublicKey,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, SealedBoxError> {
+    seal_with_options(recipient_pk, None, plaintext, aad, &SealOptions::default())
+}
+
+/// Seal `plaintext` to `recipient_pk` and sign it with the sender's ML-DSA key
+pub fn seal_authenticated(
+    recipient_pk: &PublicKey,
+    sender: &MlDsaKeyPair,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, SealedBoxError> {
+    seal_with_options(
+        recipient_pk,
+        Some(sender),
+        plaintext,
+        aad,
+        &SealOptions::default(),
+    )
+}
+
+/// Seal an in-memory message with explicit options
+pub fn seal_with_options(
+    recipient_pk: &PublicKey,
+    sender: Option<&MlDsaKeyPair>,
+    plaintext: &[u8],
+    aad: &[u8],
+    options: &SealOptions,
+) -> Result<Vec<u8>, SealedBoxError> {
+    let chunks = plaintext.len() / options.chunk_size.max(1) + 1;
+    let mut sealed = Vec::with_capacity(
+        SEALED_BOX_HEADER_SIZE
+            + MlKem768::CIPHERTEXT_SIZE
+            + plaintext.len()
+            + chunks * TAG_SIZE
+            + sender.map_or(0, |_| ML_DSA_PUBLIC_KEY_SIZE + MAX_TRAILER_SIZE),
+    );
+    seal_stream(recipient_pk, sender, plaintext, &mut sealed, aad, options)?;
+    Ok(sealed)
+}
+
+/// Open a sealed box, verifying the sender signature if one is present
+pub fn open(sk: &SecretKey, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, SealedBoxError> {
+    let mut plaintext = Vec::with_capacity(sealed.len());
+    open_stream(sk, sealed, &mut plaintext, aad)?;
+    Ok(plaintext)
+}
+
+/// Open a sender-authenticated box, returning the plaintext and the verified sender key
+pub fn open_authenticated(
+    sk: &SecretKey,
+    sealed: &[u8],
+    aad: &[u8],
+) -> Result<(Vec<u8>, MlDsaPublicKey), SealedBoxError> {
+    if !inspect(sealed)?.authenticated {
+        return Err(SealedBoxError::NotAuthenticated);
+    }
+
+    let mut plaintext = Vec::with_capacity(sealed.len());
+    let sender = open_stream(sk, sealed, &mut plaintext, aad)?;
+    let sender = sender.ok_or(SealedBoxError::NotAuthenticated)?;
+    Ok((plaintext, sender))
+}
+
+/// Seal everything read from `reader` into `writer`
+///
+/// Memory use is bounded by the chunk size. Returns the number of plaintext
+/// bytes sealed.
+pub fn seal_stream<R: Read, W: Write>(
+    recipient_pk: &PublicKey,
+    sender: Option<&MlDsaKeyPair>,
+    mut reader: R,
+    mut writer: W,
+    aad: &[u8],
+    options: &SealOptions,
+) -> Result<u64, SealedBoxError> {
+    let header = SealedBoxHeader::new(sender.is_some(), options.chunk_size)?;
+    let header_bytes = header.to_bytes();
+    let (kem_ct, shared_secret) = MlKem768::encapsulate(recipient_pk)?;
+
+    writer.write_all(&header_bytes)?;
+    writer.write_all(kem_ct.as_bytes())?;
+
+    let keys = SessionKeys::derive(
+        &header_bytes,
+        kem_ct.as_bytes(),
+        shared_secret.as_bytes(),
+        aad,
+    );
+    let mut chunks = ChunkWriter::new(&keys, writer, options.chunk_size);
+    let mut hasher = blake3::Hasher::new();
+
+    if let Some(sender) = sender {
+        chunks.write(sender.public_key())?;
+    }
+
+    let mut total = 0u64;
+    let mut buffer = vec![0u8; options.chunk_size.min(DEFAULT_CHUNK_SIZE)];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buffer[..read]);
+        chunks.write(&buffer[..read])?;
+        total += read as u64;
+    }
+    buffer.zeroize();
+
+    if let Some(sender) = sender {
+        let transcript = keys.signature_transcript(hasher.finalize().as_bytes());
+        let signature = sender.sign(&transcript, &mut rand::thread_rng())?;
+        chunks.write(&signature)?;
+        chunks.write(&(signature.len() as u16).to_be_bytes())?;
+    }
+
+    chunks.finish()?;
+    Ok(total)
+}
+
+/// Open a sealed box read from `reader`, writing the plaintext to `writer`
+///
+/// Returns the verified sender key for authenticated boxes. Plaintext is
+/// written as chunks are authenticated, before the sender signature at the
+/// end of the stream has been checked: on any error the caller must discard
+/// everything already written.
+pub fn open_stream<R: Read, W: Write>(
+    sk: &SecretKey,
+    mut reader: R,
+    writer: W,
+    aad: &[u8],
+) -> Result<Option<MlDsaPublicKey>, SealedBoxError> {
+    let mut header_bytes = [0u8; SEALED_BOX_HEADER_SIZE];
+    if read_full(&mut reader, &mut header_bytes)? < SEALED_BOX_HEADER_SIZE {
+        return Err(SealedBoxError::Truncated);
+    }
+    let header = SealedBoxHeader::from_bytes(&header_bytes)?;
+
+    let mut kem_ct = vec![0u8; MlKem768::CIPHERTEXT_SIZE];
+    if read_full(&mut reader, &mut kem_ct)? < kem_ct.len() {
+        return Err(SealedBoxError::Truncated);
+    }
+    let shared_secret = MlKem768::decapsulate(sk, &Ciphertext::from_bytes(&kem_ct)?)?;
+
+    let keys = SessionKeys::derive(&header_bytes, &kem_ct, shared_secret.as_bytes(), aad);
+    let mut chunks = ChunkReader::new(&keys, reader, header.chunk_size as usize);
+    let mut payload = PayloadSink::new(header.authenticated, writer);
+
+    while let Some(mut chunk) = chunks.next_chunk()? {
+        let result = payload.write(&chunk);
+        chunk.zeroize();
+        result?;
+    }
+
+    payload.finish(&keys)
+}
+
+/// Keys and context derived once per box
+struct SessionKeys {
+    cipher: ChaCha20Poly1305,
+    /// Per-chunk associated data binding header, KEM ciphertext and caller AAD
+    context: [u8; 32],
+}
+
+impl SessionKeys {
+    fn derive(header: &[u8], kem_ct: &[u8], shared_secret: &[u8], aad: &[u8]) -> Self {
+        let mut info = Vec::with_capacity(KEY_INFO.len() + header.len());
+        info.extend_from_slice(KEY_INFO);
+        info.extend_from_slice(header);
+
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(kem_ct), shared_secret)
+            .expand(&info, &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        key.zeroize();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(header);
+        hasher.update(kem_ct);
+        hasher.update(&(aad.len() as u64).to_be_bytes());
+        hasher.update(aad);
+
+        Self {
+            cipher,
+            context: *hasher.finalize().as_bytes(),
+        }
+    }
+
+    fn nonce(counter: u32, last: bool) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[7..11].copy_from_slice(&counter.to_be_bytes());
+        nonce[11] = last as u8;
+        nonce
+    }
+
+    fn encrypt(&self, counter: u32, last: bool, chunk: &[u8]) -> Vec<u8> {
+        let nonce = Self::nonce(counter, last);
+        self.cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk,
+                    aad: &self.context,
+                },
+            )
+            .expect("ChaCha20-Poly1305 encryption of a bounded chunk cannot fail")
+    }
+
+    fn decrypt(&self, counter: u32, last: bool, chunk: &[u8]) -> Result<Vec<u8>, SealedBoxError> {
+        let nonce = Self::nonce(counter, last);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk,
+                    aad: &self.context,
+                },
+            )
+            .map_err(|_| SealedBoxError::DecryptionFailed)
+    }
+
+    /// Message signed by the sender of an authenticated box
+    fn signature_transcript(&self, plaintext_hash: &[u8; 32]) -> Vec<u8> {
+        let mut transcript = Vec::with_capacity(SIGNATURE_DOMAIN.len() + 64);
+        transcript.extend_from_slice(SIGNATURE_DOMAIN);
+        transcript.extend_from_slice(&self.context);
+        transcript.extend_from_slice(plaintext_hash);
+        transcript
+    }
+}
+
+/// Buffers payload bytes and emits full chunks
+struct ChunkWriter<'a, W: Write> {
+    keys: &'a SessionKeys,
+    writer: W,
+    buffer: Vec<u8>,
+    chunk_size: usize,
+    counter: u32,
+}
+
+impl<'a, W: Write> ChunkWriter<'a, W> {
+    fn new(keys: &'a SessionKeys, writer: W, chunk_size: usize) -> Self {
+        Self {
+            keys,
+            writer,
+            buffer: Vec::with_capacity(chunk_size),
+            chunk_size,
+            counter: 0,
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> Result<(), SealedBoxError> {
+        while !data.is_empty() {
+            let take = (self.chunk_size - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            // A full chunk is never the final one; finish() always emits a short chunk
+            if self.buffer.len() == self.chunk_size {
+                self.emit(false)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, last: bool) -> Result<(), SealedBoxError> {
+        let sealed = self.keys.encrypt(self.counter, last, &self.buffer);
+        self.writer.write_all(&sealed)?;
+        self.buffer.zeroize();
+        self.buffer.clear();
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or(SealedBoxError::MessageTooLarge)?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), SealedBoxError> {
+        self.emit(true)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads and authenticates chunks in order
+struct ChunkReader<'a, R: Read> {
+    keys: &'a SessionKeys,
+    reader: R,
+    buffer: Vec<u8>,
+    counter: u32,
+    done: bool,
+}
+
+impl<'a, R: Read> ChunkReader<'a, R> {
+    fn new(keys: &'a SessionKeys, reader: R, chunk_size: usize) -> Self {
+        Self {
+            keys,
+            reader,
+            buffer: vec![0u8; chunk_size + TAG_SIZE],
+            counter: 0,
+            done: false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, SealedBoxError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let read = read_full(&mut self.reader, &mut self.buffer)?;
+        if read < TAG_SIZE {
+            return Err(SealedBoxError::Truncated);
+        }
+
+        let last = read < self.buffer.len();
+        let chunk = self
+            .keys
+            .decrypt(self.counter, last, &self.buffer[..read])?;
+
+        // A short read means end of stream, so trailing bytes after the final
+        // chunk are absorbed into it and fail authentication
+        if last {
+            self.done = true;
+        } else {
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or(SealedBoxError::MessageTooLarge)?;
+        }
+
+        Ok(Some(chunk))
+    }
+}
+
+/// Splits the decrypted payload into sender key, plaintext and signature
+struct PayloadSink<W: Write> {
+    writer: W,
+    authenticated: bool,
+    sender_key: Vec<u8>,
+    /// Bytes held back because they may belong to the signature trailer
+    tail: Vec<u8>,
+    hasher: blake3::Hasher,
+}
+
+impl<W: Write> PayloadSink<W> {
+    fn new(authenticated: bool, writer: W) -> Self {
+        Self {
+            writer,
+            authenticated,
+            sender_key: Vec::new(),
+            tail: Vec::new(),
+            hasher: blake3::Hasher::new(),
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> Result<(), SealedBoxError> {
+        if !self.authenticated {
+            self.writer.write_all(data)?;
+            return Ok(());
+        }
+
+        if self.sender_key.len() < ML_DSA_PUBLIC_KEY_SIZE {
+            let take = (ML_DSA_PUBLIC_KEY_SIZE - self.sender_key.len()).min(data.len());
+            self.sender_key.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+
+        self.tail.extend_from_slice(data);
+        if self.tail.len() > MAX_TRAILER_SIZE {
+            let release = self.tail.len() - MAX_TRAILER_SIZE;
+            self.hasher.update(&self.tail[..release]);
+            self.writer.write_all(&self.tail[..release])?;
+            self.tail.drain(..release);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, keys: &SessionKeys) -> Result<Option<MlDsaPublicKey>, SealedBoxError> {
+        if !self.authenticated {
+            self.writer.flush()?;
+            return Ok(None);
+        }
+
+        if self.sender_key.len() < ML_DSA_PUBLIC_KEY_SIZE || self.tail.len() < 2 {
+            return Err(SealedBoxError::Malformed("missing sender signature"));
+        }
+        let split = self.tail.len() - 2;
+        let signature_len = u16::from_be_bytes([self.tail[split], self.tail[split + 1]]) as usize;
+        if signature_len > split {
+            return Err(SealedBoxError::Malformed("invalid signature length"));
+        }
+
+        let plaintext_end = split - signature_len;
+        self.hasher.update(&self.tail[..plaintext_end]);
+        let sender = MlDsaPublicKey::from_bytes(&self.sender_key)?;
+        let transcript = keys.signature_transcript(self.hasher.finalize().as_bytes());
+        sender.verify(&transcript, &self.tail[plaintext_end..split])?;
+
+        self.writer.write_all(&self.tail[..plaintext_end])?;
+        self.writer.flush()?;
+        Ok(Some(sender))
+    }
+}
+
+/// Fill `buf` from `reader`, returning fewer bytes only at end of stream
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipient() -> (PublicKey, SecretKey) {
+        MlKem768::keygen().unwrap()
+    }
+
+    fn sender_keypair() -> MlDsaKeyPair {
+        MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap()
+    }
+
+    fn small_chunks() -> SealOptions {
+        SealOptions::default().with_chunk_size(16)
+    }
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let (pk, sk) = recipient();
+        let sealed = seal(&pk, b"agent message", b"aad").unwrap();
+
+        assert_eq!(open(&sk, &sealed, b"aad").unwrap(), b"agent message");
+        assert!(!inspect(&sealed).unwrap().authenticated);
+    }
+
+    #[test]
+    fn test_empty_plaintext() {
+        let (pk, sk) = recipient();
+        let sealed = seal(&pk, b"", b"").unwrap();
+        assert!(open(&sk, &sealed, b"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let (pk, sk) = recipient();
+        for len in [15usize, 16, 17, 32, 33, 100] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let sealed = seal_with_options(&pk, None, &plaintext, b"", &small_chunks()).unwrap();
+            assert_eq!(open(&sk, &sealed, b"").unwrap(), plaintext, "len {}", len);
+        }
+    }
+
+    #[test]
+    fn test_wrong_recipient_fails() {
+        let (pk, _sk) = recipient();
+        let (_other_pk, other_sk) = recipient();
+        let sealed = seal(&pk, b"secret", b"").unwrap();
+
+        assert!(matches!(
+            open(&other_sk, &sealed, b""),
+            Err(SealedBoxError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_aad_fails() {
+        let (pk, sk) = recipient();
+        let sealed = seal(&pk, b"secret", b"domain-a").unwrap();
+
+        assert!(matches!(
+            open(&sk, &sealed, b"domain-b"),
+            Err(SealedBoxError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let (pk, sk) = recipient();
+        let sealed = seal_with_options(&pk, None, &[7u8; 40], b"", &small_chunks()).unwrap();
+
+        for index in [
+            9,
+            SEALED_BOX_HEADER_SIZE + 10,
+            sealed.len() - 1,
+            SEALED_BOX_HEADER_SIZE + MlKem768::CIPHERTEXT_SIZE + 3,
+        ] {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 0x01;
+            assert!(open(&sk, &tampered, b"").is_err(), "index {}", index);
+        }
+    }
+
+    #[test]
+    fn test_truncation_detected() {
+        let (pk, sk) = recipient();
+        let sealed = seal_with_options(&pk, None, &[1u8; 48], b"", &small_chunks()).unwrap();
+        let body = SEALED_BOX_HEADER_SIZE + MlKem768::CIPHERTEXT_SIZE;
+        let chunk = 16 + TAG_SIZE;
+
+        // Cut exactly at a chunk boundary and in the middle of a chunk
+        for len in [body + chunk, body + 2 * chunk, sealed.len() - 5, body] {
+            assert!(open(&sk, &sealed[..len], b"").is_err(), "len {}", len);
+        }
+    }
+
+    #[test]
+    fn test_chunk_reordering_detected() {
+        let (pk, sk) = recipient();
+        let sealed = seal_with_options(&pk, None, &[2u8; 40], b"", &small_chunks()).unwrap();
+        let body = SEALED_BOX_HEADER_SIZE + MlKem768::CIPHERTEXT_SIZE;
+        let chunk = 16 + TAG_SIZE;
+
+        let mut reordered = sealed.clone();
+        reordered[body..body + chunk].copy_from_slice(&sealed[body + chunk..body + 2 * chunk]);
+        reordered[body + chunk..body + 2 * chunk].copy_from_slice(&sealed[body..body + chunk]);
+        assert!(open(&sk, &reordered, b"").is_err());
+    }
+
+    #[test]
+    fn test_trailing_data_rejected() {
+        let (pk, sk) = recipient();
+        let mut sealed = seal(&pk, b"message", b"").unwrap();
+        sealed.push(0);
+
+        assert!(matches!(
+            open(&sk, &sealed, b""),
+            Err(SealedBoxError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_header_validation() {
+        let (pk, sk) = recipient();
+        let sealed = seal(&pk, b"message", b"").unwrap();
+
+        let mut bad_version = sealed.clone();
+        bad_version[4] = 2;
+        assert!(matches!(
+            open(&sk, &bad_version, b""),
+            Err(SealedBoxError::UnsupportedVersion(2))
+        ));
+
+        let mut bad_magic = sealed.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            open(&sk, &bad_magic, b""),
+            Err(SealedBoxError::InvalidMagic)
+        ));
+
+        let mut bad_kem = sealed.clone();
+        bad_kem[6] = 9;
+        assert!(matches!(
+            open(&sk, &bad_kem, b""),
+            Err(SealedBoxError::UnsupportedAlgorithm { kem: 9, .. })
+        ));
+
+        assert!(matches!(
+            seal_with_options(
+                &pk,
+                None,
+                b"",
+                b"",
+                &SealOptions::default().with_chunk_size(0)
+            ),
+            Err(SealedBoxError::InvalidChunkSize(0))
+        ));
+    }
+
+    #[test]
+    fn test_authenticated_roundtrip() {
+        let (pk, sk) = recipient();
+        let sender = sender_keypair();
+        let plaintext = vec![5u8; 5000];
+        let sealed = seal_with_options(
+            &pk,
+            Some(&sender),
+            &plaintext,
+            b"aad",
+            &SealOptions::default().with_chunk_size(1000),
+        )
+        .unwrap();
+
+        assert!(inspect(&sealed).unwrap().authenticated);
+        let (opened, sender_pk) = open_authenticated(&sk, &sealed, b"aad").unwrap();
+        assert_eq!(opened, plaintext);
+        assert_eq!(sender_pk.as_bytes(), sender.public_key());
+
+        // Plain open also verifies the signature
+        assert_eq!(open(&sk, &sealed, b"aad").unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_open_authenticated_rejects_anonymous_box() {
+        let (pk, sk) = recipient();
+        let sealed = seal(&pk, b"anonymous", b"").unwrap();
+
+        assert!(matches!(
+            open_authenticated(&sk, &sealed, b""),
+            Err(SealedBoxError::NotAuthenticated)
+        ));
+    }
+
+    /// Seal an arbitrary authenticated payload, bypassing the signing step
+    fn seal_raw_payload(pk: &PublicKey, payload: &[u8]) -> Vec<u8> {
+        let header = SealedBoxHeader::new(true, DEFAULT_CHUNK_SIZE)
+            .unwrap()
+            .to_bytes();
+        let (kem_ct, shared_secret) = MlKem768::encapsulate(pk).unwrap();
+        let keys = SessionKeys::derive(&header, kem_ct.as_bytes(), shared_secret.as_bytes(), b"");
+
+        let mut sealed = header.to_vec();
+        sealed.extend_from_slice(kem_ct.as_bytes());
+        let mut chunks = ChunkWriter::new(&keys, &mut sealed, DEFAULT_CHUNK_SIZE);
+        chunks.write(payload).unwrap();
+        chunks.finish().unwrap();
+        sealed
+    }
+
+    fn authenticated_payload(sender_pk: &[u8], plaintext: &[u8], signature: &[u8]) -> Vec<u8> {
+        let mut payload = sender_pk.to_vec();
+        payload.extend_from_slice(plaintext);
+        payload.extend_from_slice(signature);
+        payload.extend_from_slice(&(signature.len() as u16).to_be_bytes());
+        payload
+    }
+
+    #[test]
+    fn test_forged_sender_rejected() {
+        let (pk, sk) = recipient();
+        let sender = sender_keypair();
+        let impostor = sender_keypair();
+        let signature = sender
+            .sign(b"some other transcript", &mut rand::thread_rng())
+            .unwrap();
+
+        // Claiming another key with a signature over a different transcript
+        let forged = seal_raw_payload(
+            &pk,
+            &authenticated_payload(impostor.public_key(), b"pay 10", &signature),
+        );
+        assert!(matches!(
+            open_authenticated(&sk, &forged, b""),
+            Err(SealedBoxError::Signature(_))
+        ));
+
+        let missing = seal_raw_payload(&pk, &sender.public_key()[..100]);
+        assert!(matches!(
+            open_authenticated(&sk, &missing, b""),
+            Err(SealedBoxError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_signature_bound_to_recipient() {
+        let (pk, sk) = recipient();
+        let (other_pk, other_sk) = recipient();
+        let sender = sender_keypair();
+
+        // Lift the sender's signature out of a box addressed to someone else
+        let original = seal_authenticated(&other_pk, &sender, b"for other", b"").unwrap();
+        let signature = {
+            let header = SealedBoxHeader::from_bytes(&original).unwrap().to_bytes();
+            let kem_ct = &original[SEALED_BOX_HEADER_SIZE..][..MlKem768::CIPHERTEXT_SIZE];
+            let shared_secret =
+                MlKem768::decapsulate(&other_sk, &Ciphertext::from_bytes(kem_ct).unwrap()).unwrap();
+            let keys = SessionKeys::derive(&header, kem_ct, shared_secret.as_bytes(), b"");
+            let body = &original[SEALED_BOX_HEADER_SIZE + MlKem768::CIPHERTEXT_SIZE..];
+            let raw = keys.decrypt(0, true, body).unwrap();
+            let split = raw.len() - 2;
+            let len = u16::from_be_bytes([raw[split], raw[split + 1]]) as usize;
+            raw[split - len..split].to_vec()
+        };
+
+        let replayed = seal_raw_payload(
+            &pk,
+            &authenticated_payload(sender.public_key(), b"for other", &signature),
+        );
+        assert!(matches!(
+            open_authenticated(&sk, &replayed, b""),
+            Err(SealedBoxError::Signature(_))
+        ));
+    }
+
+    #[test]
+    fn test_stream_roundtrip() {
+        let (pk, sk) = recipient();
+        let sender = sender_keypair();
+        let plaintext: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut sealed = Vec::new();
+        let sealed_len = seal_stream(
+            &pk,
+            Some(&sender),
+            plaintext.as_slice(),
+            &mut sealed,
+            b"stream",
+            &SealOptions::default().with_chunk_size(4096),
+        )
+        .unwrap();
+        assert_eq!(sealed_len, plaintext.len() as u64);
+
+        let mut opened = Vec::new();
+        let sender_pk = open_stream(&sk, sealed.as_slice(), &mut opened, b"stream").unwrap();
+        assert_eq!(opened, plaintext);
+        assert_eq!(sender_pk.unwrap().as_bytes(), sender.public_key());
+    }
+}
@@ -0,0 +1,32 @@
+//! Latency of cryptographic operations
+//!
+//! Recorded through the `metrics` facade as the
+//! `qudag_crypto_operation_seconds` histogram, labelled by operation.
+
+use std::time::Instant;
+
+/// Records the time from its creation to its drop
+pub(crate) struct OperationTimer {
+    operation: &'static str,
+    started: Instant,
+}
+
+impl OperationTimer {
+    /// Start timing `operation`
+    pub(crate) fn start(operation: &'static str) -> Self {
+        Self {
+            operation,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for OperationTimer {
+    fn drop(&mut self) {
+        metrics::histogram!(
+            "qudag_crypto_operation_seconds",
+            self.started.elapsed().as_secs_f64(),
+            "operation" => self.operation
+        );
+    }
+}
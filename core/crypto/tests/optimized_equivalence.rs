@@ -0,0 +1,109 @@
+#![cfg(feature = "optimized")]
+
+//! Equivalence tests for the runtime-dispatched SIMD backends.
+//!
+//! Every backend available on the running CPU must produce exactly the same
+//! output as the scalar reference implementation.
+
+use proptest::prelude::*;
+use qudag_crypto::optimized::simd_utils::{CacheOptimizedOps, POLY_N};
+use qudag_crypto::optimized::{SimdBackend, SimdPolynomialOps};
+
+fn poly() -> impl Strategy<Value = [i32; POLY_N]> {
+    prop::collection::vec(any::<i32>(), POLY_N).prop_map(|v| v.try_into().unwrap())
+}
+
+proptest! {
+    #[test]
+    fn prop_poly_add_matches_scalar(a in poly(), b in poly()) {
+        let mut expected = [0i32; POLY_N];
+        SimdPolynomialOps::poly_add_scalar(&a, &b, &mut expected);
+
+        for backend in SimdBackend::available() {
+            let mut result = [0i32; POLY_N];
+            SimdPolynomialOps::poly_add_with(backend, &a, &b, &mut result);
+            prop_assert_eq!(result, expected, "backend {:?}", backend);
+        }
+    }
+
+    #[test]
+    fn prop_poly_sub_matches_scalar(a in poly(), b in poly()) {
+        let mut expected = [0i32; POLY_N];
+        SimdPolynomialOps::poly_sub_scalar(&a, &b, &mut expected);
+
+        for backend in SimdBackend::available() {
+            let mut result = [0i32; POLY_N];
+            SimdPolynomialOps::poly_sub_with(backend, &a, &b, &mut result);
+            prop_assert_eq!(result, expected, "backend {:?}", backend);
+        }
+    }
+
+    #[test]
+    fn prop_poly_scalar_mul_matches_scalar(a in poly(), scalar in any::<i32>()) {
+        let mut expected = [0i32; POLY_N];
+        SimdPolynomialOps::poly_scalar_mul_scalar(&a, scalar, &mut expected);
+
+        for backend in SimdBackend::available() {
+            let mut result = [0i32; POLY_N];
+            SimdPolynomialOps::poly_scalar_mul_with(backend, &a, scalar, &mut result);
+            prop_assert_eq!(result, expected, "backend {:?}", backend);
+        }
+    }
+
+    #[test]
+    fn prop_poly_reduce_matches_scalar(
+        a in poly(),
+        q in prop_oneof![Just(3329i32), Just(8380417i32), 1i32..=i32::MAX],
+    ) {
+        let mut expected = a;
+        SimdPolynomialOps::poly_reduce_scalar(&mut expected, q);
+
+        for backend in SimdBackend::available() {
+            let mut result = a;
+            SimdPolynomialOps::poly_reduce_with(backend, &mut result, q);
+            prop_assert_eq!(result, expected, "backend {:?} q {}", backend, q);
+        }
+    }
+
+    #[test]
+    fn prop_aligned_copy_matches_input(src in prop::collection::vec(any::<u8>(), 0..512)) {
+        let mut dst = vec![0u8; src.len()];
+        CacheOptimizedOps::aligned_copy(&src, &mut dst);
+        prop_assert_eq!(dst, src);
+    }
+}
+
+#[test]
+fn unavailable_backend_falls_back_to_scalar() {
+    let a = [i32::MAX; POLY_N];
+    let b = [1i32; POLY_N];
+    let mut expected = [0i32; POLY_N];
+    SimdPolynomialOps::poly_add_scalar(&a, &b, &mut expected);
+
+    // Requesting any backend, available or not, must never fault
+    for backend in SimdBackend::ALL {
+        let mut result = [0i32; POLY_N];
+        SimdPolynomialOps::poly_add_with(backend, &a, &b, &mut result);
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn reduce_handles_extreme_coefficients() {
+    let mut a = [0i32; POLY_N];
+    a[0] = i32::MIN;
+    a[1] = i32::MAX;
+    a[2] = -1;
+    a[3] = 3328;
+    a[4] = 3329;
+    a[5] = 1665;
+    a[6] = -1665;
+
+    for backend in SimdBackend::available() {
+        let mut result = a;
+        let mut expected = a;
+        SimdPolynomialOps::poly_reduce_with(backend, &mut result, 3329);
+        SimdPolynomialOps::poly_reduce_scalar(&mut expected, 3329);
+        assert_eq!(result, expected, "backend {:?}", backend);
+    }
+}
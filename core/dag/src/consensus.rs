@@ -271,6 +271,7 @@ impl ConsensusMetrics {
     /// Records that a vertex has been processed
     pub fn record_vertex_processed(&mut self) {
         self.total_vertices_processed += 1;
+        metrics::counter!("qudag_consensus_vertices_processed_total", 1);
         let elapsed = self.start_time.elapsed();
         if elapsed.as_secs() > 0 {
             self.current_throughput = self.total_vertices_processed as f64 / elapsed.as_secs_f64();
@@ -280,6 +281,11 @@ impl ConsensusMetrics {
     /// Records finality achievement for a vertex
     pub fn record_finality(&mut self, finality_time: Duration) {
         self.finalized_count += 1;
+        metrics::counter!("qudag_consensus_finalized_total", 1);
+        metrics::histogram!(
+            "qudag_consensus_finality_seconds",
+            finality_time.as_secs_f64()
+        );
         self.total_finality_time += finality_time;
         self.average_finality_time = self.total_finality_time / self.finalized_count as u32;
     }
@@ -287,11 +293,13 @@ impl ConsensusMetrics {
     /// Records detection of Byzantine behavior
     pub fn record_byzantine_behavior(&mut self) {
         self.byzantine_behaviors_detected += 1;
+        metrics::counter!("qudag_consensus_byzantine_behaviors_total", 1);
     }
 
     /// Records resolution of a fork
     pub fn record_fork_resolved(&mut self) {
         self.forks_resolved += 1;
+        metrics::counter!("qudag_consensus_forks_resolved_total", 1);
     }
 }
 
@@ -1,7 +1,8 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
 use tracing::error;
 
 use crate::consensus::{ConsensusError, QRAvalanche};
@@ -47,6 +48,12 @@ pub struct DagMessage {
     pub timestamp: u64,
 }
 
+/// A message queued for the processor, with where to report its outcome
+struct Submission {
+    msg: DagMessage,
+    reply: Option<oneshot::Sender<Result<(), DagError>>>,
+}
+
 /// Represents the current state of message processing
 #[derive(Debug)]
 struct ProcessingState {
@@ -65,7 +72,7 @@ pub struct Dag {
     #[allow(dead_code)]
     state: Arc<RwLock<ProcessingState>>,
     /// Message processing channel
-    msg_tx: mpsc::Sender<DagMessage>,
+    msg_tx: mpsc::Sender<Submission>,
     /// Consensus mechanism
     consensus: Arc<Mutex<QRAvalanche>>,
     /// Maximum concurrent messages
@@ -78,7 +85,7 @@ pub struct Dag {
 impl Dag {
     /// Creates a new DAG instance
     pub fn new(max_concurrent: usize) -> Self {
-        let (msg_tx, mut msg_rx) = mpsc::channel::<DagMessage>(1024);
+        let (msg_tx, mut msg_rx) = mpsc::channel::<Submission>(1024);
         let vertices = Arc::new(RwLock::new(HashMap::new()));
         let state = Arc::new(RwLock::new(ProcessingState {
             processing: HashSet::new(),
@@ -95,7 +102,7 @@ impl Dag {
 
         // Spawn message processing task
         tokio::spawn(async move {
-            while let Some(msg) = msg_rx.recv().await {
+            while let Some(Submission { msg, reply }) = msg_rx.recv().await {
                 let mut state = state_clone.write().await;
                 if state.processing.len() >= max_concurrent {
                     // Wait for some messages to complete
@@ -111,13 +118,19 @@ impl Dag {
                 // let validation_cache = validation_cache_clone.clone();
 
                 tokio::spawn(async move {
-                    if let Err(e) =
-                        Self::process_message(msg, vertices, state.clone(), consensus).await
-                    {
-                        error!("Message processing failed: {}", e);
+                    let result =
+                        Self::process_message(msg, vertices, state.clone(), consensus).await;
+                    state.write().await.processing.remove(&msg_id);
+                    match reply {
+                        Some(reply) => {
+                            let _ = reply.send(result);
+                        }
+                        None => {
+                            if let Err(e) = result {
+                                error!("Message processing failed: {}", e);
+                            }
+                        }
                     }
-                    let mut state = state.write().await;
-                    state.processing.remove(&msg_id);
                 });
             }
         });
@@ -135,17 +148,57 @@ impl Dag {
     /// Submits a message for processing
     pub async fn submit_message(&self, msg: DagMessage) -> Result<(), DagError> {
         self.msg_tx
-            .send(msg)
+            .send(Submission { msg, reply: None })
             .await
             .map_err(|_| DagError::ChannelClosed)
     }
 
-    /// Processes a single message
+    /// Submits a message for processing and waits for its outcome
+    ///
+    /// Unlike [`Self::submit_message`], the caller learns whether the message
+    /// was accepted and can hold its sender to account. Fails with
+    /// [`DagError::ChannelClosed`] if the processor dropped the message.
+    pub async fn submit_message_and_wait(&self, msg: DagMessage) -> Result<(), DagError> {
+        let (reply, outcome) = oneshot::channel();
+        self.msg_tx
+            .send(Submission {
+                msg,
+                reply: Some(reply),
+            })
+            .await
+            .map_err(|_| DagError::ChannelClosed)?;
+        outcome.await.map_err(|_| DagError::ChannelClosed)?
+    }
+
+    /// Processes a single message, recording its outcome and latency
     async fn process_message(
         msg: DagMessage,
         vertices: Arc<RwLock<HashMap<VertexId, Vertex>>>,
         state: Arc<RwLock<ProcessingState>>,
         consensus: Arc<Mutex<QRAvalanche>>,
+    ) -> Result<(), DagError> {
+        let started = Instant::now();
+        let result = Self::apply_message(msg, vertices, state, consensus).await;
+        let outcome = match &result {
+            Ok(()) => "accepted",
+            Err(DagError::ConflictDetected) => "conflict",
+            Err(DagError::VertexError(_)) => "invalid",
+            Err(_) => "error",
+        };
+        metrics::counter!("qudag_dag_messages_total", 1, "result" => outcome);
+        metrics::histogram!(
+            "qudag_dag_message_processing_seconds",
+            started.elapsed().as_secs_f64()
+        );
+        result
+    }
+
+    /// Adds a message to the DAG and submits its vertex to consensus
+    async fn apply_message(
+        msg: DagMessage,
+        vertices: Arc<RwLock<HashMap<VertexId, Vertex>>>,
+        state: Arc<RwLock<ProcessingState>>,
+        consensus: Arc<Mutex<QRAvalanche>>,
         // validation_cache: Arc<ValidationCache>,
     ) -> Result<(), DagError> {
         // Validate parents exist
@@ -300,6 +353,32 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_submit_and_wait_reports_outcome() {
+        let dag = Dag::new(4);
+
+        let root = DagMessage {
+            id: VertexId::new(),
+            payload: vec![1],
+            parents: HashSet::new(),
+            timestamp: 1,
+        };
+        dag.submit_message_and_wait(root.clone()).await.unwrap();
+        // The processor already applied the message
+        assert!(dag.vertices.read().await.contains_key(&root.id));
+
+        let orphan = DagMessage {
+            id: VertexId::new(),
+            payload: vec![2],
+            parents: [VertexId::new()].into_iter().collect(),
+            timestamp: 2,
+        };
+        assert!(matches!(
+            dag.submit_message_and_wait(orphan).await,
+            Err(DagError::VertexError(VertexError::ParentNotFound))
+        ));
+    }
+
     #[tokio::test]
     async fn test_state_sync() {
         let dag1 = Dag::new(4);
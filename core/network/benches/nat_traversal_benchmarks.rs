@@ -1,6 +1,7 @@
 //! Performance benchmarks for NAT traversal functionality
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use qudag_network::port_mapping::SSDP_MULTICAST;
 use qudag_network::{
     ConnectionManager, HolePunchCoordinator, NatTraversalConfig, NatTraversalManager,
     PortMappingProtocol, RelayManager, StunClient, StunServer,
@@ -130,6 +131,8 @@ fn benchmark_nat_manager_initialization(c: &mut Criterion) {
                 detection_interval: Duration::from_secs(60),
                 upgrade_interval: Duration::from_secs(30),
                 port_mapping_lifetime: Duration::from_secs(300),
+                ssdp_target: SSDP_MULTICAST,
+                nat_pmp_gateway: None,
             };
 
             let connection_manager = Arc::new(ConnectionManager::new(50));
@@ -219,6 +222,8 @@ fn benchmark_config_creation(c: &mut Criterion) {
                 detection_interval: Duration::from_secs(300),
                 upgrade_interval: Duration::from_secs(60),
                 port_mapping_lifetime: Duration::from_secs(3600),
+                ssdp_target: SSDP_MULTICAST,
+                nat_pmp_gateway: None,
             };
 
             black_box(config)
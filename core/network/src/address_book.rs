@@ -0,0 +1,258 @@
+//! Addresses of known peers, ranked for dialing.
+//!
+//! A node may reach a peer over TCP, QUIC or WebSocket. [`AddressBook`]
+//! keeps every address a peer was learned at, with the round-trip time
+//! measured on connections to it and the dials that failed since its last
+//! success. [`AddressBook::addresses`] ranks them:
+//!
+//! 1. Addresses with fewer failed dials since their last success come first.
+//! 2. Among those, measured addresses come by ascending round-trip time.
+//! 3. Unmeasured addresses follow in transport order: memory, QUIC, TCP,
+//!    WebSocket, then relayed.
+//!
+//! [`crate::p2p::P2PNode`] dials the best address first and the next one
+//! every [`HAPPY_EYEBALLS_DELAY`] until a connection is up, falling back at
+//! once when an attempt fails.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use parking_lot::RwLock;
+
+/// Delay before racing the next address of a peer being dialed
+pub const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
+/// Most addresses kept per peer
+pub const MAX_ADDRESSES_PER_PEER: usize = 16;
+
+/// Weight of a new round-trip sample in the smoothed round-trip time
+const RTT_SMOOTHING: f64 = 0.25;
+
+/// Transport an address is dialed over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransportKind {
+    /// In-process memory transport
+    Memory,
+    /// QUIC over UDP
+    Quic,
+    /// Plain TCP
+    Tcp,
+    /// WebSocket over TCP
+    WebSocket,
+    /// Through a circuit relay
+    Relayed,
+}
+
+impl TransportKind {
+    /// Transport of `addr`, or `None` when no supported transport fits it
+    pub fn of(addr: &Multiaddr) -> Option<Self> {
+        let mut kind = None;
+        for protocol in addr.iter() {
+            kind = match protocol {
+                Protocol::P2pCircuit => return Some(TransportKind::Relayed),
+                Protocol::Memory(_) => Some(TransportKind::Memory),
+                Protocol::QuicV1 => Some(TransportKind::Quic),
+                Protocol::Ws(_) | Protocol::Wss(_) => Some(TransportKind::WebSocket),
+                Protocol::Tcp(_) if kind.is_none() => Some(TransportKind::Tcp),
+                _ => kind,
+            };
+        }
+        kind
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransportKind::Memory => "memory",
+            TransportKind::Quic => "quic",
+            TransportKind::Tcp => "tcp",
+            TransportKind::WebSocket => "websocket",
+            TransportKind::Relayed => "relayed",
+        })
+    }
+}
+
+/// What is known about one address of a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressInfo {
+    pub address: Multiaddr,
+    pub transport: TransportKind,
+    /// Smoothed round-trip time of connections over the address
+    pub rtt: Option<Duration>,
+    /// Failed dials since the last success
+    pub failures: u32,
+}
+
+#[derive(Debug, Clone)]
+struct AddressEntry {
+    transport: TransportKind,
+    rtt: Option<Duration>,
+    failures: u32,
+    last_seen: Instant,
+}
+
+impl AddressEntry {
+    /// Sort key, lowest first
+    fn rank(&self) -> (u32, bool, Duration, TransportKind) {
+        (
+            self.failures,
+            self.rtt.is_none(),
+            self.rtt.unwrap_or_default(),
+            self.transport,
+        )
+    }
+}
+
+/// Shared addresses of known peers
+///
+/// Clones refer to the same book.
+#[derive(Debug, Clone, Default)]
+pub struct AddressBook {
+    peers: Arc<RwLock<HashMap<PeerId, HashMap<Multiaddr, AddressEntry>>>>,
+}
+
+impl AddressBook {
+    /// Create an empty address book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Learn an address of `peer`, returning whether it was new
+    ///
+    /// A trailing `/p2p/<peer>` is dropped. Addresses without a supported
+    /// transport are ignored. Once a peer has [`MAX_ADDRESSES_PER_PEER`]
+    /// addresses, the worst one that was seen least recently makes room.
+    pub fn add(&self, peer: PeerId, addr: Multiaddr) -> bool {
+        let addr = without_peer_id(addr);
+        let Some(transport) = TransportKind::of(&addr) else {
+            return false;
+        };
+        let mut peers = self.peers.write();
+        let addresses = peers.entry(peer).or_default();
+        if let Some(entry) = addresses.get_mut(&addr) {
+            entry.last_seen = Instant::now();
+            return false;
+        }
+        if addresses.len() >= MAX_ADDRESSES_PER_PEER {
+            let evicted = addresses
+                .iter()
+                .max_by_key(|(_, entry)| (entry.rank(), std::cmp::Reverse(entry.last_seen)))
+                .map(|(addr, _)| addr.clone());
+            if let Some(evicted) = evicted {
+                addresses.remove(&evicted);
+            }
+        }
+        addresses.insert(
+            addr,
+            AddressEntry {
+                transport,
+                rtt: None,
+                failures: 0,
+                last_seen: Instant::now(),
+            },
+        );
+        true
+    }
+
+    /// Record a round-trip time measured over `addr`
+    pub fn record_rtt(&self, peer: PeerId, addr: &Multiaddr, rtt: Duration) {
+        self.update(peer, addr, |entry| {
+            entry.rtt = Some(match entry.rtt {
+                Some(smoothed) => {
+                    smoothed.mul_f64(1.0 - RTT_SMOOTHING) + rtt.mul_f64(RTT_SMOOTHING)
+                }
+                None => rtt,
+            });
+            entry.failures = 0;
+        });
+    }
+
+    /// Record a connection established over `addr`
+    pub fn record_success(&self, peer: PeerId, addr: &Multiaddr) {
+        self.update(peer, addr, |entry| entry.failures = 0);
+    }
+
+    /// Record a failed dial of `addr`
+    pub fn record_failure(&self, peer: PeerId, addr: &Multiaddr) {
+        self.update(peer, addr, |entry| {
+            entry.failures = entry.failures.saturating_add(1)
+        });
+    }
+
+    fn update(&self, peer: PeerId, addr: &Multiaddr, f: impl FnOnce(&mut AddressEntry)) {
+        let addr = without_peer_id(addr.clone());
+        if let Some(entry) = self
+            .peers
+            .write()
+            .get_mut(&peer)
+            .and_then(|addresses| addresses.get_mut(&addr))
+        {
+            entry.last_seen = Instant::now();
+            f(entry);
+        }
+    }
+
+    /// Forget an address of `peer`
+    pub fn remove(&self, peer: &PeerId, addr: &Multiaddr) -> bool {
+        let addr = without_peer_id(addr.clone());
+        let mut peers = self.peers.write();
+        let Some(addresses) = peers.get_mut(peer) else {
+            return false;
+        };
+        let removed = addresses.remove(&addr).is_some();
+        if addresses.is_empty() {
+            peers.remove(peer);
+        }
+        removed
+    }
+
+    /// Forget every address of `peer`
+    pub fn remove_peer(&self, peer: &PeerId) {
+        self.peers.write().remove(peer);
+    }
+
+    /// Addresses of `peer`, best first
+    pub fn addresses(&self, peer: &PeerId) -> Vec<AddressInfo> {
+        let peers = self.peers.read();
+        let Some(addresses) = peers.get(peer) else {
+            return Vec::new();
+        };
+        let mut ranked: Vec<_> = addresses.iter().collect();
+        ranked.sort_by_key(|(_, entry)| entry.rank());
+        ranked
+            .into_iter()
+            .map(|(address, entry)| AddressInfo {
+                address: address.clone(),
+                transport: entry.transport,
+                rtt: entry.rtt,
+                failures: entry.failures,
+            })
+            .collect()
+    }
+
+    /// Best address of `peer`
+    pub fn best(&self, peer: &PeerId) -> Option<Multiaddr> {
+        self.addresses(peer)
+            .into_iter()
+            .next()
+            .map(|info| info.address)
+    }
+
+    /// Peers with known addresses
+    pub fn peers(&self) -> Vec<PeerId> {
+        self.peers.read().keys().copied().collect()
+    }
+}
+
+/// `addr` without a trailing `/p2p/<peer>`
+fn without_peer_id(mut addr: Multiaddr) -> Multiaddr {
+    if matches!(addr.iter().last(), Some(Protocol::P2p(_))) {
+        addr.pop();
+    }
+    addr
+}
@@ -0,0 +1,198 @@
+//! Circuit relay v2 reservations and limits.
+//!
+//! Nodes that cannot be dialed directly reach the network through relays:
+//!
+//! * A node running the relay server offers reservations and relays
+//!   circuits within the quotas of [`RelayConfig`]. Such a node is publicly
+//!   reachable, so it confirms its listen addresses as external ones, which
+//!   the server hands to the peers reserving on it.
+//! * A node behind a NAT, as configured or found by reachability
+//!   detection, reserves a slot on up to [`RelayConfig::max_relays`]
+//!   relays, the configured ones first and then peers whose identify info
+//!   lists the relay hop protocol. Each accepted
+//!   reservation adds a `/p2p/<relay>/p2p-circuit` address, which identify
+//!   and the DHT advertise like any other address of the node.
+//! * Once two peers are connected through a relay, DCUtR hole-punches a
+//!   direct connection between them and the relayed one is closed.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use libp2p::core::transport::ListenerId;
+use libp2p::multiaddr::Protocol;
+use libp2p::{relay, Multiaddr, PeerId};
+
+/// How often reservations on the configured relays are requested again
+/// after they were refused or lost
+pub const RESERVATION_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Circuit relay configuration
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Offer reservations and circuits to other peers
+    ///
+    /// The node's listen addresses are confirmed as publicly reachable.
+    pub server: bool,
+    /// Reserve slots on relays whatever the node's reachability
+    ///
+    /// Otherwise the node reserves only while it is privately reachable.
+    pub reserve: bool,
+    /// Relays to reserve on, as addresses ending in `/p2p/<relay>`
+    pub relays: Vec<Multiaddr>,
+    /// Most relays holding a reservation for this node at once
+    pub max_relays: usize,
+    /// Most reservations served at once
+    pub max_reservations: usize,
+    /// Most reservations served per peer
+    pub max_reservations_per_peer: usize,
+    /// How long a served reservation lasts unless renewed
+    pub reservation_duration: Duration,
+    /// Most circuits relayed at once
+    pub max_circuits: usize,
+    /// Most circuits relayed per peer
+    pub max_circuits_per_peer: usize,
+    /// How long a relayed circuit may stay open
+    pub max_circuit_duration: Duration,
+    /// Most bytes relayed per circuit in each direction
+    ///
+    /// Leaves room for the handshake, identify and the node's protocols to
+    /// run until DCUtR replaces the circuit.
+    pub max_circuit_bytes: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            server: false,
+            reserve: false,
+            relays: Vec::new(),
+            max_relays: 2,
+            max_reservations: 128,
+            max_reservations_per_peer: 4,
+            reservation_duration: Duration::from_secs(60 * 60),
+            max_circuits: 16,
+            max_circuits_per_peer: 4,
+            max_circuit_duration: Duration::from_secs(2 * 60),
+            max_circuit_bytes: 1 << 20,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Relay server configuration with these quotas
+    ///
+    /// libp2p's default rate limits on reservations and circuits per peer
+    /// and per IP address apply on top.
+    pub fn server_config(&self) -> relay::Config {
+        relay::Config {
+            max_reservations: self.max_reservations,
+            max_reservations_per_peer: self.max_reservations_per_peer,
+            reservation_duration: self.reservation_duration,
+            max_circuits: self.max_circuits,
+            max_circuits_per_peer: self.max_circuits_per_peer,
+            max_circuit_duration: self.max_circuit_duration,
+            max_circuit_bytes: self.max_circuit_bytes,
+            ..Default::default()
+        }
+    }
+}
+
+/// Address to listen on for a reservation on `relay` reached at `relay_addr`
+pub fn circuit_listen_addr(relay_addr: &Multiaddr, relay: PeerId) -> Multiaddr {
+    let mut addr = relay_addr.clone();
+    if let Some(Protocol::P2p(_)) = addr.iter().last() {
+        addr.pop();
+    }
+    addr.with(Protocol::P2p(relay)).with(Protocol::P2pCircuit)
+}
+
+/// Whether `addr` goes through a circuit relay
+pub fn is_relayed(addr: &Multiaddr) -> bool {
+    addr.iter().any(|protocol| protocol == Protocol::P2pCircuit)
+}
+
+/// State of a reservation on a relay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationState {
+    /// Requested, the relay has not answered yet
+    Pending,
+    /// Accepted by the relay
+    Active,
+}
+
+/// Reservations this node holds or requested, one per relay
+///
+/// Each reservation is the listener of its circuit address, so it ends when
+/// that listener closes.
+#[derive(Debug, Default)]
+pub struct Reservations {
+    relays: HashMap<PeerId, (ListenerId, ReservationState)>,
+}
+
+impl Reservations {
+    /// Record the listener requesting a reservation on `relay`
+    pub fn request(&mut self, relay: PeerId, listener: ListenerId) {
+        self.relays
+            .insert(relay, (listener, ReservationState::Pending));
+    }
+
+    /// Mark the reservation on `relay` accepted
+    ///
+    /// Returns whether it was pending, as opposed to renewed.
+    pub fn accept(&mut self, relay: &PeerId) -> bool {
+        match self.relays.get_mut(relay) {
+            Some((_, state)) => {
+                std::mem::replace(state, ReservationState::Active) == ReservationState::Pending
+            }
+            None => false,
+        }
+    }
+
+    /// Forget the reservation of a closed listener, returning its relay
+    pub fn close(&mut self, listener: ListenerId) -> Option<PeerId> {
+        let relay = self
+            .relays
+            .iter()
+            .find(|(_, (id, _))| *id == listener)
+            .map(|(relay, _)| *relay)?;
+        self.relays.remove(&relay);
+        Some(relay)
+    }
+
+    /// Whether a reservation on `relay` is held or requested
+    pub fn contains(&self, relay: &PeerId) -> bool {
+        self.relays.contains_key(relay)
+    }
+
+    /// State of the reservation on `relay`
+    pub fn state(&self, relay: &PeerId) -> Option<ReservationState> {
+        self.relays.get(relay).map(|(_, state)| *state)
+    }
+
+    /// Number of reservations held or requested
+    pub fn len(&self) -> usize {
+        self.relays.len()
+    }
+
+    /// Whether no reservation is held or requested
+    pub fn is_empty(&self) -> bool {
+        self.relays.is_empty()
+    }
+
+    /// Listeners of the reservations held or requested
+    pub fn listeners(&self) -> Vec<ListenerId> {
+        self.relays
+            .values()
+            .map(|(listener, _)| *listener)
+            .collect()
+    }
+
+    /// Relays holding an accepted reservation
+    pub fn active(&self) -> Vec<PeerId> {
+        self.relays
+            .iter()
+            .filter(|(_, (_, state))| *state == ReservationState::Active)
+            .map(|(relay, _)| *relay)
+            .collect()
+    }
+}
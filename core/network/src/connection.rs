@@ -453,6 +453,9 @@ pub struct ConnectionManager {
     connection_pool: Arc<DashMap<PeerId, (ConnectionInfo, Instant)>>,
     /// Connection pool with enhanced lifecycle management
     enhanced_pool: Arc<DashMap<PeerId, PooledConnection>>,
+    /// Open transport connections per peer, registered by
+    /// [`crate::pooled_transport`]
+    live_connections: Arc<DashMap<PeerId, usize>>,
     /// Connection multiplexer for stream management
     multiplexer: Arc<ConnectionMultiplexer>,
     /// Retry manager for exponential backoff
@@ -1291,6 +1294,7 @@ impl ConnectionManager {
             connections: Arc::new(DashMap::new()),
             connection_pool: Arc::new(DashMap::new()),
             enhanced_pool: Arc::new(DashMap::new()),
+            live_connections: Arc::new(DashMap::new()),
             multiplexer: Arc::new(ConnectionMultiplexer::new(32, Duration::from_secs(30))),
             retry_manager: Arc::new(RetryManager::new()),
             load_balancer: Arc::new(LoadBalancer::new(LoadBalancingStrategy::WeightedRoundRobin)),
@@ -1338,6 +1342,12 @@ impl ConnectionManager {
     /// * `Ok(())` - Connection established or reused
     /// * `Err(_)` - Connection failed or circuit breaker open
     pub async fn connect(&self, peer_id: PeerId) -> Result<(), NetworkError> {
+        // A transport connection is already open
+        if self.has_live_connection(&peer_id) {
+            debug!("Reusing open transport connection for peer {:?}", peer_id);
+            return Ok(());
+        }
+
         // Check circuit breaker first
         if let Some(mut circuit_breaker) = self.circuit_breakers.get_mut(&peer_id) {
             if !circuit_breaker.allow_request() {
@@ -1547,6 +1557,59 @@ impl ConnectionManager {
         metrics.active_connections = active_count;
     }
 
+    /// Registers a connection opened by the transport to `remote_addr`
+    ///
+    /// The first connection to a peer marks it connected, takes it out of
+    /// the idle pool and counts as a success for its circuit breaker.
+    pub fn connection_opened(&self, peer_id: PeerId, remote_addr: String) {
+        let mut live = self.live_connections.entry(peer_id).or_insert(0);
+        *live += 1;
+        if *live > 1 {
+            return;
+        }
+        drop(live);
+
+        self.connection_pool.remove(&peer_id);
+        let mut info = ConnectionInfo::new(ConnectionStatus::Connected);
+        info.metadata.insert("remote_addr".to_string(), remote_addr);
+        self.quality_scores.insert(peer_id, info.quality_score);
+        self.connections.insert(peer_id, info);
+        self.circuit_breakers
+            .entry(peer_id)
+            .or_default()
+            .record_result(true);
+
+        let mut metrics = self.metrics.write();
+        metrics.connections = self.connections.len();
+        metrics.active_connections = self
+            .connections
+            .iter()
+            .filter(|entry| entry.value().is_healthy())
+            .count();
+    }
+
+    /// Releases a connection registered with [`Self::connection_opened`]
+    ///
+    /// Once the last one to a peer closes, the peer is disconnected and its
+    /// connection info moves to the idle pool.
+    pub fn connection_closed(&self, peer_id: &PeerId) {
+        if let Some(mut live) = self.live_connections.get_mut(peer_id) {
+            *live = live.saturating_sub(1);
+        }
+        if self
+            .live_connections
+            .remove_if(peer_id, |_, live| *live == 0)
+            .is_some()
+        {
+            self.disconnect(peer_id);
+        }
+    }
+
+    /// Whether the transport has a connection open to `peer_id`
+    pub fn has_live_connection(&self, peer_id: &PeerId) -> bool {
+        self.live_connections.contains_key(peer_id)
+    }
+
     /// Cleanup expired connections from the pool
     fn cleanup_pool(&self) {
         self.connection_pool
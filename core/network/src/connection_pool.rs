@@ -446,6 +446,8 @@ impl ConnectionPool {
         stats.current_size = self.get_total_connection_count();
         stats.available = self.available.iter().map(|entry| entry.value().len()).sum();
         stats.active = self.active.iter().map(|entry| entry.value().len()).sum();
+        metrics::gauge!("qudag_connection_pool_connections", stats.available as f64, "state" => "idle");
+        metrics::gauge!("qudag_connection_pool_connections", stats.active as f64, "state" => "active");
 
         // Calculate hit rate
         if stats.acquisitions > 0 {
@@ -492,25 +494,35 @@ impl ConnectionPool {
     // Statistics update methods
     fn increment_created(&self) {
         self.stats.write().total_created += 1;
+        metrics::counter!("qudag_connection_pool_created_total", 1);
     }
 
     fn increment_destroyed(&self) {
         self.stats.write().total_destroyed += 1;
+        metrics::counter!("qudag_connection_pool_destroyed_total", 1);
     }
 
     fn increment_releases(&self) {
         self.stats.write().releases += 1;
+        metrics::counter!("qudag_connection_pool_releases_total", 1);
     }
 
     fn increment_timeouts(&self) {
         self.stats.write().timeouts += 1;
+        metrics::counter!("qudag_connection_pool_timeouts_total", 1);
     }
 
     fn increment_failed_acquisitions(&self) {
         self.stats.write().failed_acquisitions += 1;
+        metrics::counter!("qudag_connection_pool_failed_acquisitions_total", 1);
     }
 
     fn update_acquisition_stats(&self, wait_time: Duration) {
+        metrics::counter!("qudag_connection_pool_acquisitions_total", 1);
+        metrics::histogram!(
+            "qudag_connection_pool_wait_seconds",
+            wait_time.as_secs_f64()
+        );
         let mut stats = self.stats.write();
         stats.acquisitions += 1;
 
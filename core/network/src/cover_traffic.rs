@@ -0,0 +1,314 @@
+//! Cover traffic for idle links and circuits.
+//!
+//! [`crate::onion::TrafficAnalysisResistance`] and the mix batching in
+//! [`crate::onion::MixNode`] only reshape messages the node already sends.
+//! The [`CoverScheduler`] adds dummy cells so that an observer of a link or
+//! a circuit cannot tell when the node is idle:
+//!
+//! * [`CoverMode::ConstantRate`] divides time into slots and sends a dummy in
+//!   every slot no real cell went out in, so the link carries at least one
+//!   cell per slot whatever the node does.
+//! * [`CoverMode::Poisson`] sends dummies at exponentially distributed
+//!   intervals, independently of real traffic, in the manner of Loopix.
+//!
+//! Connections to peers get link dummies, `OnionCell::Padding`, which the
+//! next hop drops right after the connection layer decrypts them. Circuits
+//! owned by this node get `RelayCommand::Padding` sealed for their last hop,
+//! so the relays in between cannot tell them from data. Neither kind is
+//! parsed by its receiver, which answers it with the same fixed reply.
+//!
+//! All dummies draw from one token bucket, so cover traffic never exceeds
+//! the configured bandwidth budget. Dummies the budget cannot pay for are
+//! skipped and counted in [`CoverStats::suppressed`].
+//!
+//! The scheduler keeps [`CoverStats`] and exports them through the `metrics`
+//! facade, including the `qudag_cover_real_to_cover_ratio` gauge.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p::PeerId;
+use rand::{thread_rng, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// How often a link sends dummy cells
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CoverMode {
+    /// One cell per slot; a dummy fills every slot without real traffic
+    ConstantRate { interval: Duration },
+    /// Dummies at exponentially distributed intervals around a mean
+    Poisson { mean_interval: Duration },
+}
+
+impl CoverMode {
+    /// Time until the next slot or dummy
+    fn next_delay(&self) -> Duration {
+        match *self {
+            CoverMode::ConstantRate { interval } => interval,
+            CoverMode::Poisson { mean_interval } => {
+                // 1 - U lies in (0, 1], so the logarithm is finite
+                let uniform: f64 = 1.0 - thread_rng().gen::<f64>();
+                mean_interval.mul_f64(-uniform.ln())
+            }
+        }
+    }
+}
+
+/// Cover traffic configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoverTrafficConfig {
+    /// Cover on every connection to a peer, none when unset
+    pub connections: Option<CoverMode>,
+    /// Cover on every circuit built by this node, none when unset
+    pub circuits: Option<CoverMode>,
+    /// Filler bytes carried by a dummy cell
+    pub cell_size: usize,
+    /// Cover bytes per second allowed across all links
+    pub bandwidth_budget: u64,
+    /// Cover bytes that may be sent at once after an idle period
+    pub burst: u64,
+}
+
+impl CoverTrafficConfig {
+    /// Whether any link gets cover traffic
+    pub fn is_enabled(&self) -> bool {
+        self.connections.is_some() || self.circuits.is_some()
+    }
+}
+
+impl Default for CoverTrafficConfig {
+    fn default() -> Self {
+        Self {
+            connections: None,
+            circuits: None,
+            cell_size: 512,
+            bandwidth_budget: 16 * 1024,
+            burst: 64 * 1024,
+        }
+    }
+}
+
+/// A link cover traffic is sent on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoverLink {
+    /// Direct connection to a peer
+    Connection(PeerId),
+    /// Circuit built by this node
+    Circuit(u64),
+}
+
+/// Counters of real and cover traffic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverStats {
+    /// Real cells sent on scheduled links
+    pub real_cells: u64,
+    /// Bytes of real cells
+    pub real_bytes: u64,
+    /// Dummy cells sent
+    pub cover_cells: u64,
+    /// Bytes of dummy cells
+    pub cover_bytes: u64,
+    /// Dummies skipped because the bandwidth budget ran out
+    pub suppressed: u64,
+    /// Links currently scheduled
+    pub links: usize,
+}
+
+impl CoverStats {
+    /// Real bytes sent for every cover byte, `None` before the first dummy
+    pub fn real_to_cover_ratio(&self) -> Option<f64> {
+        (self.cover_bytes > 0).then(|| self.real_bytes as f64 / self.cover_bytes as f64)
+    }
+}
+
+/// Scheduling state of one link
+#[derive(Debug)]
+struct LinkState {
+    mode: CoverMode,
+    /// When the current slot ends or the next dummy is due
+    next: Instant,
+    /// Whether a cell went out in the current constant-rate slot
+    slot_filled: bool,
+}
+
+/// Bytes the bandwidth budget can still pay for
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u64,
+    capacity: u64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, capacity: u64) -> Self {
+        Self {
+            rate,
+            capacity,
+            tokens: capacity as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Take `bytes` tokens if there are enough
+    fn take(&mut self, bytes: u64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.capacity as f64);
+        self.refilled = now;
+        if self.tokens < bytes as f64 {
+            return false;
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
+}
+
+/// Decides when each link sends a dummy cell
+#[derive(Debug)]
+pub struct CoverScheduler {
+    config: CoverTrafficConfig,
+    links: HashMap<CoverLink, LinkState>,
+    budget: TokenBucket,
+    stats: CoverStats,
+}
+
+impl CoverScheduler {
+    /// Create a scheduler with no links
+    pub fn new(config: CoverTrafficConfig) -> Self {
+        let budget = TokenBucket::new(config.bandwidth_budget, config.burst);
+        Self {
+            config,
+            links: HashMap::new(),
+            budget,
+            stats: CoverStats::default(),
+        }
+    }
+
+    /// The configuration in use
+    pub fn config(&self) -> &CoverTrafficConfig {
+        &self.config
+    }
+
+    /// Start cover traffic on a link if its kind is configured for it
+    ///
+    /// Returns whether the link is scheduled.
+    pub fn add_link(&mut self, link: CoverLink) -> bool {
+        let mode = match link {
+            CoverLink::Connection(_) => self.config.connections,
+            CoverLink::Circuit(_) => self.config.circuits,
+        };
+        let Some(mode) = mode else {
+            return false;
+        };
+        self.links.entry(link).or_insert_with(|| LinkState {
+            mode,
+            next: Instant::now() + mode.next_delay(),
+            slot_filled: false,
+        });
+        self.update_link_gauge();
+        true
+    }
+
+    /// Stop cover traffic on a link
+    pub fn remove_link(&mut self, link: &CoverLink) {
+        if self.links.remove(link).is_some() {
+            self.update_link_gauge();
+        }
+    }
+
+    /// Whether a link is scheduled
+    pub fn has_link(&self, link: &CoverLink) -> bool {
+        self.links.contains_key(link)
+    }
+
+    /// Count a real cell sent on a link
+    pub fn record_real(&mut self, link: CoverLink, bytes: usize) {
+        if !self.fill_slot(&link) {
+            return;
+        }
+        self.stats.real_cells += 1;
+        self.stats.real_bytes += bytes as u64;
+        metrics::counter!("qudag_cover_real_bytes_total", bytes as u64);
+        self.update_ratio_gauge();
+    }
+
+    /// Note that a cell went out on a link without counting it as real
+    ///
+    /// A circuit dummy fills the slot of the connection it leaves on.
+    /// Returns whether the link is scheduled.
+    pub fn fill_slot(&mut self, link: &CoverLink) -> bool {
+        match self.links.get_mut(link) {
+            Some(state) => {
+                state.slot_filled = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// When the earliest link needs attention
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.links.values().map(|state| state.next).min()
+    }
+
+    /// Links that must send a dummy cell now
+    ///
+    /// Moves every due link on to its next slot or dummy and charges the
+    /// returned dummies to the bandwidth budget.
+    pub fn poll_due(&mut self, now: Instant) -> Vec<CoverLink> {
+        let mut due = Vec::new();
+        for (link, state) in self.links.iter_mut() {
+            if state.next > now {
+                continue;
+            }
+            let wanted = match state.mode {
+                CoverMode::ConstantRate { .. } => !state.slot_filled,
+                CoverMode::Poisson { .. } => true,
+            };
+            state.slot_filled = false;
+            state.next = now + state.mode.next_delay();
+            if !wanted {
+                continue;
+            }
+            if self.budget.take(self.config.cell_size as u64, now) {
+                due.push(*link);
+            } else {
+                self.stats.suppressed += 1;
+                metrics::counter!("qudag_cover_suppressed_total", 1);
+            }
+        }
+        if !due.is_empty() {
+            let bytes = (due.len() * self.config.cell_size) as u64;
+            self.stats.cover_cells += due.len() as u64;
+            self.stats.cover_bytes += bytes;
+            metrics::counter!("qudag_cover_bytes_total", bytes);
+            self.update_ratio_gauge();
+        }
+        due
+    }
+
+    /// Random filler for one dummy cell
+    pub fn filler(&self) -> Vec<u8> {
+        let mut filler = vec![0u8; self.config.cell_size];
+        thread_rng().fill_bytes(&mut filler);
+        filler
+    }
+
+    /// Counters so far
+    pub fn stats(&self) -> CoverStats {
+        CoverStats {
+            links: self.links.len(),
+            ..self.stats
+        }
+    }
+
+    fn update_link_gauge(&self) {
+        metrics::gauge!("qudag_cover_links", self.links.len() as f64);
+    }
+
+    fn update_ratio_gauge(&self) {
+        if let Some(ratio) = self.stats.real_to_cover_ratio() {
+            metrics::gauge!("qudag_cover_real_to_cover_ratio", ratio);
+        }
+    }
+}
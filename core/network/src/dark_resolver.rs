@@ -1,19 +1,52 @@
+use async_trait::async_trait;
 use blake3::Hasher;
 use bs58;
+use libp2p::kad::Record;
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
+use tracing::{debug, warn};
 
 // Import crypto primitives from the crypto module
+use qudag_crypto::key_certificate::{KeyCertificateError, RevocationList};
 use qudag_crypto::ml_dsa::{MlDsaError, MlDsaKeyPair, MlDsaPublicKey};
 use qudag_crypto::ml_kem::MlKem768;
 
+use crate::record_store::{RecordStoreError, RecordValidator};
 use crate::types::NetworkAddress;
 use crate::types::PeerId;
 
+/// How often published records are pushed to the DHT again
+///
+/// Must stay well below record TTLs so records survive DHT churn.
+pub const DEFAULT_REPUBLISH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Prefix of DHT keys holding dark domain records
+pub const DARK_KEY_PREFIX: &[u8] = b"/dark/";
+
+/// Largest encoded dark domain record nodes agree to store
+pub const MAX_DARK_RECORD_SIZE: usize = 16 * 1024;
+
+/// Longest lifetime, from registration to expiry, a record may claim
+pub const MAX_RECORD_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// How far in the future a record's registration time may lie
+pub const MAX_CLOCK_SKEW: u64 = 5 * 60;
+
+/// Version of records signed before they were bound to a domain
+///
+/// Records persisted in this layout carry no version, domain or sequence.
+pub const DARK_RECORD_VERSION_LEGACY: u8 = 0;
+
+/// Version of records bound to a domain, without introduction points
+pub const DARK_RECORD_VERSION_BOUND: u8 = 1;
+
+/// Version of newly signed records, selecting the layout of the signed fields
+pub const DARK_RECORD_VERSION: u8 = 2;
+
 /// Errors that can occur during dark domain operations
 #[derive(Error, Debug)]
 pub enum DarkResolverError {
@@ -37,6 +70,18 @@ pub enum DarkResolverError {
     DhtError(String),
     #[error("ML-DSA error: {0}")]
     MlDsaError(#[from] MlDsaError),
+    #[error("Signing key rejected: {0}")]
+    KeyCertificate(#[from] KeyCertificateError),
+    #[error("Stale record: sequence {found} is not newer than {current}")]
+    StaleRecord { current: u64, found: u64 },
+    #[error("Record lifetime of {ttl} seconds exceeds the maximum of {max}")]
+    TtlTooLong { ttl: u64, max: u64 },
+    #[error("Record registered in the future")]
+    RegisteredInFuture,
+    #[error("Domain is claimed by several keys")]
+    ContestedDomain,
+    #[error("Unsupported record version {0}")]
+    UnsupportedVersion(u8),
 }
 
 /// A resolved dark domain record with quantum-resistant signatures
@@ -62,6 +107,31 @@ pub struct DarkDomainRecord {
     pub signature: Vec<u8>,
     /// Additional metadata
     pub metadata: HashMap<String, String>,
+    /// Domain the record was signed for
+    #[serde(default)]
+    pub domain: String,
+    /// Update counter; the owner increments it with every new record
+    #[serde(default)]
+    pub sequence: u64,
+    /// Relays introducing clients to a hidden service, which publishes no
+    /// addresses
+    #[serde(default)]
+    pub introduction_points: Vec<IntroductionPoint>,
+    /// Layout version of the signed fields, [`DARK_RECORD_VERSION_LEGACY`]
+    /// for records stored without one
+    #[serde(default)]
+    pub version: u8,
+}
+
+/// Relay through which clients reach a hidden service
+///
+/// See [`crate::hidden_service`] for the rendezvous protocol.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntroductionPoint {
+    /// PeerId bytes of the relay
+    pub relay: Vec<u8>,
+    /// Identifier of the service's circuit at the relay
+    pub intro_id: [u8; 32],
 }
 
 /// Dark address derived from ML-DSA public key
@@ -96,6 +166,12 @@ impl DarkDomainRecord {
         ttl: u32,
         owner_id: PeerId,
     ) -> Result<Self, DarkResolverError> {
+        if ttl as u64 > MAX_RECORD_TTL {
+            return Err(DarkResolverError::TtlTooLong {
+                ttl: ttl as u64,
+                max: MAX_RECORD_TTL,
+            });
+        }
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -112,6 +188,10 @@ impl DarkDomainRecord {
             owner_id,
             signature: vec![],
             metadata: HashMap::new(),
+            domain: String::new(),
+            sequence: 0,
+            introduction_points: Vec::new(),
+            version: DARK_RECORD_VERSION,
         };
 
         // Sign the record
@@ -119,6 +199,36 @@ impl DarkDomainRecord {
         Ok(record)
     }
 
+    /// Bind the record to `domain` at update `sequence` and re-sign it
+    ///
+    /// Only bound records are accepted from the DHT, so a record cannot be
+    /// replayed under another name.
+    pub fn bind_to_domain(
+        &mut self,
+        keypair: &MlDsaKeyPair,
+        domain: &str,
+        sequence: u64,
+    ) -> Result<(), DarkResolverError> {
+        self.domain = domain.to_string();
+        self.sequence = sequence;
+        self.sign(keypair)
+    }
+
+    /// Replace the introduction points and re-sign the record
+    pub fn set_introduction_points(
+        &mut self,
+        keypair: &MlDsaKeyPair,
+        introduction_points: Vec<IntroductionPoint>,
+    ) -> Result<(), DarkResolverError> {
+        self.introduction_points = introduction_points;
+        self.sign(keypair)
+    }
+
+    /// Whether the domain is reached through introduction points
+    pub fn is_hidden_service(&self) -> bool {
+        !self.introduction_points.is_empty()
+    }
+
     /// Sign the record with ML-DSA
     fn sign(&mut self, keypair: &MlDsaKeyPair) -> Result<(), DarkResolverError> {
         let mut rng = rand::thread_rng();
@@ -141,7 +251,20 @@ impl DarkDomainRecord {
     }
 
     /// Convert record to bytes for signing (excludes signature field)
+    ///
+    /// The record's version selects which fields are covered.
     fn to_signable_bytes(&self) -> Result<Vec<u8>, DarkResolverError> {
+        if self.version > DARK_RECORD_VERSION {
+            return Err(DarkResolverError::UnsupportedVersion(self.version));
+        }
+        // Fields an older layout does not sign must be unset
+        let unsigned_binding = self.version == DARK_RECORD_VERSION_LEGACY
+            && (!self.domain.is_empty() || self.sequence != 0);
+        let unsigned_introductions =
+            self.version < DARK_RECORD_VERSION && !self.introduction_points.is_empty();
+        if unsigned_binding || unsigned_introductions {
+            return Err(DarkResolverError::InvalidSignature);
+        }
         let mut hasher = Hasher::new();
         hasher.update(&self.signing_public_key);
         hasher.update(&self.encryption_public_key);
@@ -161,9 +284,47 @@ impl DarkDomainRecord {
             &bincode::serialize(&self.owner_id)
                 .map_err(|e| DarkResolverError::CryptoError(e.to_string()))?,
         );
+        if self.version == DARK_RECORD_VERSION_LEGACY {
+            return Ok(hasher.finalize().as_bytes().to_vec());
+        }
+        hasher.update(&[self.version]);
+        hasher.update(&(self.domain.len() as u32).to_le_bytes());
+        hasher.update(self.domain.as_bytes());
+        hasher.update(&self.sequence.to_le_bytes());
+        if self.version == DARK_RECORD_VERSION_BOUND {
+            return Ok(hasher.finalize().as_bytes().to_vec());
+        }
+        hasher.update(&(self.introduction_points.len() as u32).to_le_bytes());
+        for point in &self.introduction_points {
+            hasher.update(&(point.relay.len() as u32).to_le_bytes());
+            hasher.update(&point.relay);
+            hasher.update(&point.intro_id);
+        }
         Ok(hasher.finalize().as_bytes().to_vec())
     }
 
+    /// Check the record's registration time and lifetime are plausible
+    ///
+    /// Both are chosen by the signer, so without bounds a record could stay
+    /// valid for decades.
+    pub fn check_lifetime(&self) -> Result<(), DarkResolverError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        if self.registered_at > now + MAX_CLOCK_SKEW {
+            return Err(DarkResolverError::RegisteredInFuture);
+        }
+        let ttl = self.expires_at.saturating_sub(self.registered_at);
+        if ttl > MAX_RECORD_TTL {
+            return Err(DarkResolverError::TtlTooLong {
+                ttl,
+                max: MAX_RECORD_TTL,
+            });
+        }
+        Ok(())
+    }
+
     /// Check if the record has expired
     pub fn is_expired(&self) -> bool {
         let now = SystemTime::now()
@@ -182,18 +343,62 @@ pub struct DarkResolver {
     address_book: Arc<RwLock<HashMap<String, AddressBookEntry>>>,
     /// Reverse lookup: dark address -> domain name
     reverse_lookup: Arc<RwLock<HashMap<String, String>>>,
-    /// DHT client for distributed storage (placeholder)
+    /// DHT client for distributed storage
     dht_client: Option<Arc<dyn DhtClient>>,
+    /// Rotated and revoked signing keys
+    revocations: RevocationList,
+    /// Domains this resolver publishes and keeps republishing
+    published: Arc<RwLock<HashSet<String>>>,
+}
+
+/// DHT record validator refusing unsigned or oversized dark domain records
+///
+/// Applies to keys under [`DARK_KEY_PREFIX`]; other records pass untouched.
+/// A record must be at most [`MAX_DARK_RECORD_SIZE`] bytes, bound to the
+/// domain its key is derived from, unexpired, live for at most
+/// [`MAX_RECORD_TTL`] and correctly signed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DarkRecordValidator;
+
+impl RecordValidator for DarkRecordValidator {
+    fn validate(&self, record: &Record) -> Result<(), RecordStoreError> {
+        if !record.key.as_ref().starts_with(DARK_KEY_PREFIX) {
+            return Ok(());
+        }
+        if record.value.len() > MAX_DARK_RECORD_SIZE {
+            return Err(RecordStoreError::Rejected(format!(
+                "dark domain record of {} bytes",
+                record.value.len()
+            )));
+        }
+        let dark: DarkDomainRecord = bincode::deserialize(&record.value)
+            .map_err(|e| RecordStoreError::Rejected(e.to_string()))?;
+        if DarkResolver::dht_key(&dark.domain) != record.key.as_ref() {
+            return Err(RecordStoreError::Rejected(
+                "dark domain record stored under another key".to_string(),
+            ));
+        }
+        if dark.is_expired() {
+            return Err(RecordStoreError::Rejected(
+                "dark domain record expired".to_string(),
+            ));
+        }
+        dark.check_lifetime()
+            .map_err(|e| RecordStoreError::Rejected(e.to_string()))?;
+        dark.verify_signature()
+            .map_err(|e| RecordStoreError::Rejected(e.to_string()))
+    }
 }
 
 /// Trait for DHT client operations
+#[async_trait]
 pub trait DhtClient: Send + Sync {
-    /// Store a value in the DHT
-    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), DarkResolverError>;
-    /// Retrieve a value from the DHT
-    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DarkResolverError>;
-    /// Remove a value from the DHT
-    fn remove(&self, key: &[u8]) -> Result<(), DarkResolverError>;
+    /// Store a value in the DHT for `ttl`
+    async fn put(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<(), DarkResolverError>;
+    /// Retrieve every value stored under `key`
+    ///
+    /// Peers may hold conflicting values; the caller chooses among them.
+    async fn get(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, DarkResolverError>;
 }
 
 impl Default for DarkResolver {
@@ -210,6 +415,8 @@ impl DarkResolver {
             address_book: Arc::new(RwLock::new(HashMap::new())),
             reverse_lookup: Arc::new(RwLock::new(HashMap::new())),
             dht_client: None,
+            revocations: RevocationList::new(),
+            published: Arc::new(RwLock::new(HashSet::new())),
         }
     }
 
@@ -220,9 +427,22 @@ impl DarkResolver {
             address_book: Arc::new(RwLock::new(HashMap::new())),
             reverse_lookup: Arc::new(RwLock::new(HashMap::new())),
             dht_client: Some(dht_client),
+            revocations: RevocationList::new(),
+            published: Arc::new(RwLock::new(HashSet::new())),
         }
     }
 
+    /// Use a shared revocation list when validating records
+    pub fn with_revocation_list(mut self, revocations: RevocationList) -> Self {
+        self.revocations = revocations;
+        self
+    }
+
+    /// Revocation list consulted by this resolver
+    pub fn revocation_list(&self) -> &RevocationList {
+        &self.revocations
+    }
+
     /// Generate a .dark address from ML-DSA public key
     pub fn generate_dark_address(
         public_key: &[u8],
@@ -289,7 +509,7 @@ impl DarkResolver {
         }
 
         // Create domain record
-        let record = DarkDomainRecord::new(
+        let mut record = DarkDomainRecord::new(
             &signing_keypair,
             kem_public.as_bytes().to_vec(),
             addresses,
@@ -297,6 +517,7 @@ impl DarkResolver {
             ttl,
             owner_id,
         )?;
+        record.bind_to_domain(&signing_keypair, &dark_address.domain, 0)?;
 
         // Store locally
         {
@@ -321,27 +542,53 @@ impl DarkResolver {
             reverse.insert(dark_address.address.clone(), dark_address.domain.clone());
         }
 
-        // Store in DHT if available
-        if let Some(dht) = &self.dht_client {
-            let key = Self::domain_to_dht_key(&dark_address.domain);
-            let value = bincode::serialize(&record)
-                .map_err(|e| DarkResolverError::DhtError(e.to_string()))?;
-            dht.put(&key, &value)?;
-        }
-
         Ok(dark_address)
     }
 
-    /// Convert domain to DHT key
-    fn domain_to_dht_key(domain: &str) -> Vec<u8> {
+    /// Store a record signed with a key the caller holds
+    ///
+    /// Unlike [`DarkResolver::register_domain`], the caller keeps the keys,
+    /// so it can sign later updates. An existing domain is only replaced
+    /// through [`DarkResolver::update_domain`].
+    pub fn register_record(&self, record: DarkDomainRecord) -> Result<(), DarkResolverError> {
+        if !Self::is_valid_dark_domain(&record.domain) {
+            return Err(DarkResolverError::InvalidDomain);
+        }
+        record.check_lifetime()?;
+        record.verify_signature()?;
+        self.revocations.check(&record.signing_public_key)?;
+        let domain = record.domain.clone();
+        let exists = self
+            .domains
+            .read()
+            .map_err(|_| DarkResolverError::StorageError)?
+            .contains_key(&domain);
+        if exists {
+            return self.update_domain(&domain, record);
+        }
+        self.cache_record(&domain, &record)
+    }
+
+    /// DHT key under which records for `domain` are stored
+    pub fn dht_key(domain: &str) -> Vec<u8> {
         let mut hasher = Hasher::new();
         hasher.update(b"dark_domain:");
         hasher.update(domain.as_bytes());
-        hasher.finalize().as_bytes().to_vec()
+        [DARK_KEY_PREFIX, hasher.finalize().as_bytes()].concat()
     }
 
-    /// Look up a .dark domain and return its record
+    /// Look up a .dark domain in the local store and return its record
+    ///
+    /// Records signed by a rotated or revoked key are rejected. Use
+    /// [`DarkResolver::resolve_domain`] to query the DHT as well.
     pub fn lookup_domain(&self, domain: &str) -> Result<DarkDomainRecord, DarkResolverError> {
+        let record = self.fetch_verified_record(domain)?;
+        self.revocations.check(&record.signing_public_key)?;
+        Ok(record)
+    }
+
+    /// Fetch a record and verify its signature and expiry, without key status checks
+    fn fetch_verified_record(&self, domain: &str) -> Result<DarkDomainRecord, DarkResolverError> {
         // Validate domain name
         if !Self::is_valid_dark_domain(domain) {
             return Err(DarkResolverError::InvalidDomain);
@@ -365,31 +612,244 @@ impl DarkResolver {
             }
         }
 
-        // Try DHT if not found locally
-        if let Some(dht) = &self.dht_client {
-            let key = Self::domain_to_dht_key(domain);
-            if let Some(value) = dht.get(&key)? {
-                let record: DarkDomainRecord = bincode::deserialize(&value)
-                    .map_err(|e| DarkResolverError::DhtError(e.to_string()))?;
+        Err(DarkResolverError::DomainNotFound)
+    }
 
-                // Verify and cache
-                if record.is_expired() {
-                    return Err(DarkResolverError::DomainExpired);
+    /// Resolve a .dark domain, querying the DHT when it is not usable locally
+    ///
+    /// Every record found in the DHT must be bound to `domain`, unexpired,
+    /// correctly signed and signed by an active key. Among those, the
+    /// owner's record with the highest sequence number wins; see
+    /// [`DarkResolver::owner_key`] for how the owner is chosen.
+    pub async fn resolve_domain(
+        &self,
+        domain: &str,
+    ) -> Result<DarkDomainRecord, DarkResolverError> {
+        let local_error = match self.lookup_domain(domain) {
+            Ok(record) => return Ok(record),
+            Err(
+                e @ (DarkResolverError::DomainNotFound
+                | DarkResolverError::DomainExpired
+                | DarkResolverError::KeyCertificate(_)),
+            ) => e,
+            Err(e) => return Err(e),
+        };
+        let Some(dht) = &self.dht_client else {
+            return Err(local_error);
+        };
+
+        let candidates = dht.get(&Self::dht_key(domain)).await?;
+        let record = match self.select_record(domain, &candidates) {
+            Ok(record) => record,
+            Err(DarkResolverError::DomainNotFound) => return Err(local_error),
+            Err(e) => return Err(e),
+        };
+        debug!(
+            "Resolved {} from the DHT at sequence {}",
+            domain, record.sequence
+        );
+        self.cache_record(domain, &record)?;
+        Ok(record)
+    }
+
+    /// Pick the authoritative record for `domain` among DHT values
+    ///
+    /// When no candidate is acceptable, the reason the first one was
+    /// rejected is returned.
+    fn select_record(
+        &self,
+        domain: &str,
+        candidates: &[Vec<u8>],
+    ) -> Result<DarkDomainRecord, DarkResolverError> {
+        let mut rejection = None;
+        let mut valid = Vec::new();
+        for value in candidates {
+            match self.validate_candidate(domain, value) {
+                Ok(record) => valid.push(record),
+                Err(e) => {
+                    debug!("Rejected DHT record for {}: {}", domain, e);
+                    rejection.get_or_insert(e);
                 }
-                record.verify_signature()?;
+            }
+        }
+
+        if valid.is_empty() {
+            return Err(rejection.unwrap_or(DarkResolverError::DomainNotFound));
+        }
+        let owner = self.owner_key(domain, &valid)?;
+        valid
+            .into_iter()
+            .filter(|record| record.signing_public_key == owner)
+            .max_by_key(|record| (record.sequence, record.registered_at))
+            .ok_or(DarkResolverError::DomainNotFound)
+    }
+
+    /// Decode a DHT value and check it is a live, signed record for `domain`
+    fn validate_candidate(
+        &self,
+        domain: &str,
+        value: &[u8],
+    ) -> Result<DarkDomainRecord, DarkResolverError> {
+        let record: DarkDomainRecord =
+            bincode::deserialize(value).map_err(|e| DarkResolverError::DhtError(e.to_string()))?;
+        if record.domain != domain {
+            return Err(DarkResolverError::InvalidDomain);
+        }
+        if record.is_expired() {
+            return Err(DarkResolverError::DomainExpired);
+        }
+        record.check_lifetime()?;
+        record.verify_signature()?;
+        self.revocations.check(&record.signing_public_key)?;
+        Ok(record)
+    }
 
-                // Cache locally
-                let mut domains = self
-                    .domains
-                    .write()
-                    .map_err(|_| DarkResolverError::StorageError)?;
-                domains.insert(domain.to_string(), record.clone());
+    /// Signing key that owns `domain`
+    ///
+    /// In order of precedence: a key the domain name is derived from, and
+    /// the current (possibly rotated) key of the record cached for the
+    /// domain, which pins the first owner this resolver saw. The pin holds
+    /// while the cached record is unexpired, and after that for as long as
+    /// the owner keeps publishing. Otherwise a custom name resolves only if
+    /// a single key claims it: registration times are chosen by the signer,
+    /// so they cannot settle a dispute.
+    fn owner_key(
+        &self,
+        domain: &str,
+        candidates: &[DarkDomainRecord],
+    ) -> Result<Vec<u8>, DarkResolverError> {
+        if let Some(record) = candidates.iter().find(|record| {
+            Self::generate_dark_address(&record.signing_public_key, None)
+                .is_ok_and(|address| address.domain == domain)
+        }) {
+            return Ok(record.signing_public_key.clone());
+        }
 
-                return Ok(record);
+        let cached = self.domains.read().ok().and_then(|domains| {
+            domains
+                .get(domain)
+                .map(|record| (record.signing_public_key.clone(), record.is_expired()))
+        });
+        if let Some((key, expired)) = cached {
+            if let Ok(key) = self.revocations.current_key(&key) {
+                if !expired
+                    || candidates
+                        .iter()
+                        .any(|record| record.signing_public_key == key)
+                {
+                    return Ok(key);
+                }
             }
         }
 
-        Err(DarkResolverError::DomainNotFound)
+        let mut keys = candidates.iter().map(|record| &record.signing_public_key);
+        let first = keys.next().ok_or(DarkResolverError::DomainNotFound)?;
+        if keys.any(|key| key != first) {
+            return Err(DarkResolverError::ContestedDomain);
+        }
+        Ok(first.clone())
+    }
+
+    /// Store a verified record in the local cache
+    fn cache_record(
+        &self,
+        domain: &str,
+        record: &DarkDomainRecord,
+    ) -> Result<(), DarkResolverError> {
+        self.domains
+            .write()
+            .map_err(|_| DarkResolverError::StorageError)?
+            .insert(domain.to_string(), record.clone());
+        let address = Self::generate_dark_address(&record.signing_public_key, None)?;
+        self.reverse_lookup
+            .write()
+            .map_err(|_| DarkResolverError::StorageError)?
+            .insert(address.address, domain.to_string());
+        Ok(())
+    }
+
+    /// Publish a locally held domain record to the DHT
+    ///
+    /// Published domains are pushed again by [`DarkResolver::republish`].
+    pub async fn publish_domain(&self, domain: &str) -> Result<(), DarkResolverError> {
+        let dht = self
+            .dht_client
+            .as_ref()
+            .ok_or_else(|| DarkResolverError::DhtError("no DHT client configured".to_string()))?;
+        let record = self.lookup_domain(domain)?;
+        Self::put_record(dht.as_ref(), &record).await?;
+        self.published
+            .write()
+            .map_err(|_| DarkResolverError::StorageError)?
+            .insert(domain.to_string());
+        Ok(())
+    }
+
+    /// Push every published, unexpired record to the DHT again
+    ///
+    /// Returns the number of records republished.
+    pub async fn republish(&self) -> Result<usize, DarkResolverError> {
+        let Some(dht) = &self.dht_client else {
+            return Ok(0);
+        };
+        let records: Vec<DarkDomainRecord> = {
+            let published = self
+                .published
+                .read()
+                .map_err(|_| DarkResolverError::StorageError)?;
+            let domains = self
+                .domains
+                .read()
+                .map_err(|_| DarkResolverError::StorageError)?;
+            published
+                .iter()
+                .filter_map(|domain| domains.get(domain))
+                .filter(|record| !record.is_expired())
+                .cloned()
+                .collect()
+        };
+
+        let mut count = 0;
+        for record in records {
+            match Self::put_record(dht.as_ref(), &record).await {
+                Ok(()) => count += 1,
+                Err(e) => warn!("Failed to republish {}: {}", record.domain, e),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Republish every `interval` for as long as the resolver is alive
+    pub fn spawn_republisher(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let resolver = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(resolver) = resolver.upgrade() else {
+                    break;
+                };
+                if let Err(e) = resolver.republish().await {
+                    warn!("Dark domain republish failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Store a record in the DHT until it expires
+    async fn put_record(
+        dht: &dyn DhtClient,
+        record: &DarkDomainRecord,
+    ) -> Result<(), DarkResolverError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let ttl = Duration::from_secs(record.expires_at.saturating_sub(now));
+        let value =
+            bincode::serialize(record).map_err(|e| DarkResolverError::DhtError(e.to_string()))?;
+        dht.put(&Self::dht_key(&record.domain), &value, ttl).await
     }
 
     /// Resolve a .dark domain to network addresses
@@ -447,38 +907,44 @@ impl DarkResolver {
     }
 
     /// Update domain record (requires signature from owner)
+    ///
+    /// The record must be bound to `domain` with a higher sequence number
+    /// than the current one. Call [`DarkResolver::publish_domain`] to push
+    /// the update to the DHT.
     pub fn update_domain(
         &self,
         domain: &str,
         record: DarkDomainRecord,
     ) -> Result<(), DarkResolverError> {
-        // Verify the new record's signature
+        // Verify the new record's signature, name binding and lifetime
+        record.check_lifetime()?;
         record.verify_signature()?;
+        if record.domain != domain {
+            return Err(DarkResolverError::InvalidDomain);
+        }
+
+        // The new signing key itself must still be trusted
+        self.revocations.check(&record.signing_public_key)?;
 
         // Get existing record to verify ownership
-        let existing = self.lookup_domain(domain)?;
+        let existing = self.fetch_verified_record(domain)?;
 
-        // Verify same owner (by comparing signing public keys)
-        if existing.signing_public_key != record.signing_public_key {
+        // Verify same owner, following any rotation away from the old key
+        let owner_key = self.revocations.current_key(&existing.signing_public_key)?;
+        if owner_key != record.signing_public_key {
             return Err(DarkResolverError::InvalidSignature);
         }
 
-        // Update local storage
-        {
-            let mut domains = self
-                .domains
-                .write()
-                .map_err(|_| DarkResolverError::StorageError)?;
-            domains.insert(domain.to_string(), record.clone());
+        // Older or replayed records must not replace newer ones
+        if record.sequence <= existing.sequence {
+            return Err(DarkResolverError::StaleRecord {
+                current: existing.sequence,
+                found: record.sequence,
+            });
         }
 
-        // Update DHT
-        if let Some(dht) = &self.dht_client {
-            let key = Self::domain_to_dht_key(domain);
-            let value = bincode::serialize(&record)
-                .map_err(|e| DarkResolverError::DhtError(e.to_string()))?;
-            dht.put(&key, &value)?;
-        }
+        // Update local storage
+        self.cache_record(domain, &record)?;
 
         Ok(())
     }
@@ -519,10 +985,9 @@ impl DarkResolver {
                     let addr = Self::generate_dark_address(&record.signing_public_key, None)?;
                     reverse.remove(&addr.address);
 
-                    // Remove from DHT
-                    if let Some(dht) = &self.dht_client {
-                        let key = Self::domain_to_dht_key(&domain);
-                        let _ = dht.remove(&key);
+                    // DHT copies expire with the record, so just stop republishing
+                    if let Ok(mut published) = self.published.write() {
+                        published.remove(&domain);
                     }
                 }
             }
@@ -573,8 +1038,14 @@ mod tests {
         }
     }
 
+    #[async_trait]
     impl DhtClient for MockDhtClient {
-        fn put(&self, key: &[u8], value: &[u8]) -> Result<(), DarkResolverError> {
+        async fn put(
+            &self,
+            key: &[u8],
+            value: &[u8],
+            _ttl: Duration,
+        ) -> Result<(), DarkResolverError> {
             let mut storage = self
                 .storage
                 .write()
@@ -583,21 +1054,12 @@ mod tests {
             Ok(())
         }
 
-        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DarkResolverError> {
+        async fn get(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, DarkResolverError> {
             let storage = self
                 .storage
                 .read()
                 .map_err(|_| DarkResolverError::StorageError)?;
-            Ok(storage.get(key).cloned())
-        }
-
-        fn remove(&self, key: &[u8]) -> Result<(), DarkResolverError> {
-            let mut storage = self
-                .storage
-                .write()
-                .map_err(|_| DarkResolverError::StorageError)?;
-            storage.remove(key);
-            Ok(())
+            Ok(storage.get(key).cloned().into_iter().collect())
         }
     }
 
@@ -735,6 +1197,9 @@ mod tests {
             owner_id,
             signature: vec![],
             metadata: HashMap::new(),
+            domain: "expired.dark".to_string(),
+            sequence: 0,
+            introduction_points: Vec::new(),
         };
 
         // Sign the record
@@ -2,6 +2,7 @@
 //! dark addressing support, and sophisticated peer reputation management.
 
 use crate::dark_resolver::DarkResolver;
+use crate::dns_seed::{DnsSeedConfig, DnsSeedResolver};
 use crate::shadow_address::{DefaultShadowAddressHandler, NetworkType, ShadowAddress};
 use crate::types::NetworkError;
 use libp2p::PeerId as LibP2PPeerId;
@@ -96,6 +97,8 @@ pub struct DiscoveryConfig {
     pub load_balancing_config: LoadBalancingConfig,
     /// Geographic distribution preferences
     pub geo_preferences: GeoPreferences,
+    /// Signed DNS seeds used by [`DiscoveryMethod::DNS`]
+    pub dns_seeds: Option<DnsSeedConfig>,
 }
 
 /// DHT configuration for Kademlia-based discovery.
@@ -238,6 +241,7 @@ impl Default for DiscoveryConfig {
             scoring_config: PeerScoringConfig::default(),
             load_balancing_config: LoadBalancingConfig::default(),
             geo_preferences: GeoPreferences::default(),
+            dns_seeds: None,
         }
     }
 }
@@ -851,6 +855,8 @@ pub struct KademliaPeerDiscovery {
     performance_monitor: Arc<Mutex<PerformanceMonitor>>,
     /// Bootstrap strategy
     bootstrap_strategy: BootstrapStrategy,
+    /// DNS seed resolver, created on bootstrap
+    dns_seeds: Option<Arc<DnsSeedResolver>>,
 }
 
 /// Bootstrap strategies for different network conditions.
@@ -1506,6 +1512,7 @@ impl KademliaPeerDiscovery {
                 aggressiveness: 0.5,
                 last_adapted: Instant::now(),
             },
+            dns_seeds: None,
             config,
         }
     }
@@ -1582,12 +1589,26 @@ impl KademliaPeerDiscovery {
             debug!("Added bootstrap peer: {} -> {:?}", bootstrap_addr, peer_id);
         }
 
+        if self.config.methods.contains(&DiscoveryMethod::DNS) {
+            if let Some(config) = self.config.dns_seeds.clone() {
+                match DnsSeedResolver::new(config) {
+                    Ok(resolver) => self.dns_seeds = Some(Arc::new(resolver)),
+                    Err(e) => warn!("DNS seed resolver unavailable: {}", e),
+                }
+            }
+        }
+        let mut seed_peers = 0;
+        if let Some(resolver) = &self.dns_seeds {
+            seed_peers =
+                Self::discover_dns_peers(resolver, &self.discovered_peers, &self.event_tx).await;
+        }
+
         self.bootstrap_completed = true;
 
         if let Some(tx) = &self.event_tx {
             let _ = tx
                 .send(DiscoveryEvent::BootstrapCompleted {
-                    peers_discovered: discovered_peers,
+                    peers_discovered: discovered_peers + seed_peers,
                     duration: start_time.elapsed(),
                     success_rate: discovered_peers as f64
                         / self.config.bootstrap_nodes.len().max(1) as f64,
@@ -1606,6 +1627,7 @@ impl KademliaPeerDiscovery {
         let event_tx = self.event_tx.clone();
         let methods = self.config.methods.clone();
         let max_peers = self.config.max_peers;
+        let dns_seeds = self.dns_seeds.clone();
 
         tokio::spawn(async move {
             let mut interval_timer = tokio::time::interval(interval);
@@ -1623,6 +1645,12 @@ impl KademliaPeerDiscovery {
                         DiscoveryMethod::Mdns => {
                             Self::discover_mdns_peers(&discovered_peers, &event_tx).await;
                         }
+                        DiscoveryMethod::DNS => {
+                            if let Some(resolver) = &dns_seeds {
+                                Self::discover_dns_peers(resolver, &discovered_peers, &event_tx)
+                                    .await;
+                            }
+                        }
                         _ => {} // Other methods handled separately
                     }
                 }
@@ -1668,6 +1696,58 @@ impl KademliaPeerDiscovery {
         }
     }
 
+    /// Discover peers from signed DNS seed lists
+    ///
+    /// Returns the number of peers not known before.
+    async fn discover_dns_peers(
+        resolver: &DnsSeedResolver,
+        discovered_peers: &Arc<RwLock<HashMap<LibP2PPeerId, DiscoveredPeer>>>,
+        event_tx: &Option<mpsc::Sender<DiscoveryEvent>>,
+    ) -> usize {
+        let seeds = match resolver.resolve().await {
+            Ok(seeds) => seeds,
+            Err(e) => {
+                warn!("DNS seed discovery failed: {}", e);
+                if let Some(tx) = event_tx {
+                    let _ = tx
+                        .send(DiscoveryEvent::DiscoveryError {
+                            error: e.to_string(),
+                            category: DiscoveryErrorCategory::NetworkError,
+                            retry_suggested: true,
+                        })
+                        .await;
+                }
+                return 0;
+            }
+        };
+
+        let mut count = 0;
+        for seed in seeds {
+            let Some((&first, rest)) = seed.socket_addrs.split_first() else {
+                continue;
+            };
+            let discovered_peer = {
+                let mut peers = discovered_peers.write().await;
+                if peers.contains_key(&seed.peer_id) {
+                    continue;
+                }
+                let mut peer = DiscoveredPeer::new(seed.peer_id, first, DiscoveryMethod::DNS);
+                peer.addresses.extend_from_slice(rest);
+                peers.insert(seed.peer_id, peer.clone());
+                peer
+            };
+            count += 1;
+
+            if let Some(tx) = event_tx {
+                let _ = tx
+                    .send(DiscoveryEvent::PeerDiscovered(discovered_peer))
+                    .await;
+            }
+            debug!("Discovered peer via DNS seeds: {:?}", seed.peer_id);
+        }
+        count
+    }
+
     /// Discover peers using mDNS
     async fn discover_mdns_peers(
         discovered_peers: &Arc<RwLock<HashMap<LibP2PPeerId, DiscoveredPeer>>>,
@@ -0,0 +1,79 @@
+//! Ordered file writes off the async swarm loop.
+//!
+//! Stores that persist from inside the swarm loop, such as the record store
+//! and peer bans, hand their writes to a [`DiskWriter`] so disk I/O never
+//! blocks polling. Each writer owns one blocking thread that runs writes in
+//! the order they were queued, so a later write to a file always wins.
+//! Dropping the writer finishes the queue first.
+
+use std::io;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use tracing::warn;
+
+enum Job {
+    Write(Box<dyn FnOnce() + Send>),
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// Background thread running queued file writes in order
+#[derive(Debug)]
+pub(crate) struct DiskWriter {
+    tx: Option<mpsc::Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DiskWriter {
+    /// Start a writer thread called `name`
+    pub(crate) fn new(name: &str) -> io::Result<Self> {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let thread = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                for job in rx {
+                    match job {
+                        Job::Write(write) => write(),
+                        Job::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })?;
+        Ok(Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        })
+    }
+
+    /// Queue a write behind those already queued
+    pub(crate) fn submit(&self, write: impl FnOnce() + Send + 'static) {
+        let sent = self
+            .tx
+            .as_ref()
+            .is_some_and(|tx| tx.send(Job::Write(Box::new(write))).is_ok());
+        if !sent {
+            warn!("Disk writer stopped, dropping a write");
+        }
+    }
+
+    /// Block until every write queued so far has run
+    pub(crate) fn flush(&self) {
+        let (done_tx, done_rx) = mpsc::sync_channel(1);
+        if let Some(tx) = &self.tx {
+            if tx.send(Job::Flush(done_tx)).is_ok() {
+                let _ = done_rx.recv();
+            }
+        }
+    }
+}
+
+impl Drop for DiskWriter {
+    fn drop(&mut self) {
+        // Closing the queue lets the thread finish pending writes and exit
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -1,9 +1,14 @@
 #![deny(unsafe_code)]
 #![warn(missing_docs)]
 
-//! DNS integration module for ruv.io using Cloudflare API.
-
-use serde::{Deserialize, Serialize};
+//! DNS integration module for ruv.io.
+//!
+//! Records are managed through a [`DnsProvider`]; [`CloudflareClient`] is the
+//! provider backed by the Cloudflare API. Reading seed lists back from DNS is
+//! done by [`crate::dns_seed`].
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
 /// Errors that can occur during DNS operations
@@ -35,12 +40,36 @@ pub enum RecordType {
     CNAME,
 }
 
+/// Backend managing the DNS records of a zone
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    /// Lists all DNS records in the zone
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, DnsError>;
+
+    /// Creates a new DNS record
+    async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord, DnsError>;
+
+    /// Updates an existing DNS record
+    async fn update_record(
+        &self,
+        record_id: &str,
+        record: DnsRecord,
+    ) -> Result<DnsRecord, DnsError>;
+
+    /// Deletes a DNS record
+    async fn delete_record(&self, record_id: &str) -> Result<(), DnsError>;
+}
+
 /// A DNS record entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DnsRecord {
+    /// Provider-assigned record ID, set on records read from the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     /// Record name/hostname
     pub name: String,
     /// Record type
+    #[serde(rename = "type")]
     pub record_type: RecordType,
     /// Record content/value
     pub content: String,
@@ -57,6 +86,34 @@ pub struct CloudflareConfig {
     api_token: String,
     /// Zone ID for the domain
     zone_id: String,
+    /// Base URL of the API
+    api_base: String,
+}
+
+impl CloudflareConfig {
+    /// Creates a configuration for a zone
+    pub fn new(api_token: impl Into<String>, zone_id: impl Into<String>) -> Self {
+        Self {
+            api_token: api_token.into(),
+            zone_id: zone_id.into(),
+            api_base: CloudflareClient::API_BASE.to_string(),
+        }
+    }
+
+    /// Uses another API endpoint, such as a test server
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+}
+
+/// Envelope of Cloudflare API responses
+#[derive(Debug, Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+    result: Option<T>,
 }
 
 /// Client for interacting with Cloudflare DNS API
@@ -80,8 +137,7 @@ impl CloudflareClient {
     pub async fn list_records(&self) -> Result<Vec<DnsRecord>, DnsError> {
         let url = format!(
             "{}/zones/{}/dns_records",
-            Self::API_BASE,
-            self.config.zone_id
+            self.config.api_base, self.config.zone_id
         );
 
         let response = self
@@ -100,20 +156,14 @@ impl CloudflareClient {
             )));
         }
 
-        let records = response
-            .json::<Vec<DnsRecord>>()
-            .await
-            .map_err(|e| DnsError::ApiError(e.to_string()))?;
-
-        Ok(records)
+        Self::result(response).await
     }
 
     /// Creates a new DNS record
     pub async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord, DnsError> {
         let url = format!(
             "{}/zones/{}/dns_records",
-            Self::API_BASE,
-            self.config.zone_id
+            self.config.api_base, self.config.zone_id
         );
 
         let response = self
@@ -133,12 +183,7 @@ impl CloudflareClient {
             )));
         }
 
-        let created_record = response
-            .json::<DnsRecord>()
-            .await
-            .map_err(|e| DnsError::ApiError(e.to_string()))?;
-
-        Ok(created_record)
+        Self::result(response).await
     }
 
     /// Updates an existing DNS record
@@ -149,9 +194,7 @@ impl CloudflareClient {
     ) -> Result<DnsRecord, DnsError> {
         let url = format!(
             "{}/zones/{}/dns_records/{}",
-            Self::API_BASE,
-            self.config.zone_id,
-            record_id
+            self.config.api_base, self.config.zone_id, record_id
         );
 
         let response = self
@@ -171,21 +214,14 @@ impl CloudflareClient {
             )));
         }
 
-        let updated_record = response
-            .json::<DnsRecord>()
-            .await
-            .map_err(|e| DnsError::ApiError(e.to_string()))?;
-
-        Ok(updated_record)
+        Self::result(response).await
     }
 
     /// Deletes a DNS record
     pub async fn delete_record(&self, record_id: &str) -> Result<(), DnsError> {
         let url = format!(
             "{}/zones/{}/dns_records/{}",
-            Self::API_BASE,
-            self.config.zone_id,
-            record_id
+            self.config.api_base, self.config.zone_id, record_id
         );
 
         let response = self
@@ -206,21 +242,69 @@ impl CloudflareClient {
 
         Ok(())
     }
+
+    /// Unwraps the result of an API response
+    async fn result<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, DnsError> {
+        let body = response
+            .json::<CloudflareResponse<T>>()
+            .await
+            .map_err(|e| DnsError::ApiError(e.to_string()))?;
+        match body.result {
+            Some(result) if body.success => Ok(result),
+            _ => Err(DnsError::ApiError(format!(
+                "API request failed: {:?}",
+                body.errors
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl DnsProvider for CloudflareClient {
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, DnsError> {
+        CloudflareClient::list_records(self).await
+    }
+
+    async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord, DnsError> {
+        CloudflareClient::create_record(self, record).await
+    }
+
+    async fn update_record(
+        &self,
+        record_id: &str,
+        record: DnsRecord,
+    ) -> Result<DnsRecord, DnsError> {
+        CloudflareClient::update_record(self, record_id, record).await
+    }
+
+    async fn delete_record(&self, record_id: &str) -> Result<(), DnsError> {
+        CloudflareClient::delete_record(self, record_id).await
+    }
 }
 
 /// DNS record manager for handling record operations
 pub struct DnsManager {
-    client: CloudflareClient,
+    client: Box<dyn DnsProvider>,
 }
 
 impl DnsManager {
-    /// Creates a new DNS record manager
+    /// Creates a new DNS record manager backed by Cloudflare
     pub fn new(config: CloudflareConfig) -> Self {
+        Self::with_provider(CloudflareClient::new(config))
+    }
+
+    /// Creates a new DNS record manager backed by `provider`
+    pub fn with_provider(provider: impl DnsProvider + 'static) -> Self {
         Self {
-            client: CloudflareClient::new(config),
+            client: Box::new(provider),
         }
     }
 
+    /// The provider records are managed through
+    pub fn provider(&self) -> &dyn DnsProvider {
+        self.client.as_ref()
+    }
+
     /// Lists all DNS records
     pub async fn list_records(&self) -> Result<Vec<DnsRecord>, DnsError> {
         self.client.list_records().await
@@ -328,14 +412,12 @@ mod tests {
     use serde_json::json;
 
     fn setup_test_config() -> CloudflareConfig {
-        CloudflareConfig {
-            api_token: "test_token".to_string(),
-            zone_id: "test_zone".to_string(),
-        }
+        CloudflareConfig::new("test_token", "test_zone")
     }
 
     fn create_test_record() -> DnsRecord {
         DnsRecord {
+            id: None,
             name: "test.example.com".to_string(),
             record_type: RecordType::A,
             content: "192.0.2.1".to_string(),
@@ -0,0 +1,497 @@
+//! Bootstrap peer discovery from DNS seeds.
+//!
+//! A seed domain publishes a [`SeedList`]: peer multiaddrs, each ending in
+//! `/p2p/<peer id>`, signed with an ML-DSA key that nodes pin in their
+//! [`DnsSeedConfig`]. DNS carries no authenticity of its own here; a list
+//! is only used when its signature verifies against the pinned key, it is
+//! bound to the seed domain it was found under and it has not expired.
+//!
+//! The signed list is too large for a single TXT string, so it is split
+//! across TXT records of the form `qudag-seed=<sequence>/<index>/<count>/<chunk>`
+//! holding base64 chunks. Seed domains may also delegate with SRV records at
+//! `_qudag._tcp.<seed domain>`: the TXT records of every SRV target are read
+//! as well, in priority order, so seed lists can be served by several zones.
+//!
+//! [`publish_seed_list`] writes a list through any [`DnsProvider`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hickory_resolver::config::{
+    NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts,
+};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::TokioAsyncResolver;
+use libp2p::multiaddr::Protocol as AddrProtocol;
+use libp2p::{Multiaddr, PeerId};
+use qudag_crypto::ml_dsa::{MlDsaError, MlDsaKeyPair, MlDsaPublicKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::dns::{DnsError, DnsProvider, DnsRecord, RecordType};
+
+/// Prefix of TXT records carrying seed list chunks
+pub const SEED_TXT_PREFIX: &str = "qudag-seed=";
+
+/// Service label of SRV records delegating to other seed zones
+pub const SEED_SRV_LABEL: &str = "_qudag._tcp";
+
+/// Base64 characters per TXT record, keeping records under 255 bytes
+const CHUNK_SIZE: usize = 200;
+
+/// Signature domain of seed lists
+const SEED_LIST_DOMAIN: &[u8] = b"qudag-dns-seed/1";
+
+/// Largest seed list accepted, in chunks
+const MAX_CHUNKS: usize = 256;
+
+/// Errors from DNS seed discovery
+#[derive(Debug, Error)]
+pub enum DnsSeedError {
+    #[error("DNS lookup failed: {0}")]
+    Lookup(#[from] ResolveError),
+    #[error("DNS provider error: {0}")]
+    Provider(#[from] DnsError),
+    #[error("ML-DSA error: {0}")]
+    MlDsa(#[from] MlDsaError),
+    #[error("Malformed seed list: {0}")]
+    Malformed(String),
+    #[error("Seed list for {found} found under {expected}")]
+    WrongDomain { expected: String, found: String },
+    #[error("Seed list expired")]
+    Expired,
+    #[error("No valid seed list under {0}")]
+    NoSeeds(String),
+}
+
+/// DNS seed discovery settings
+#[derive(Debug, Clone)]
+pub struct DnsSeedConfig {
+    /// Domain whose TXT and SRV records publish seed lists
+    pub seed_domain: String,
+    /// ML-DSA public key seed lists must be signed with
+    pub pinned_key: Vec<u8>,
+    /// Name servers to query; the system configuration when empty
+    pub nameservers: Vec<SocketAddr>,
+    /// Timeout of each DNS query
+    pub timeout: Duration,
+}
+
+impl DnsSeedConfig {
+    /// Seed discovery under `seed_domain`, trusting lists signed by `pinned_key`
+    pub fn new(seed_domain: impl Into<String>, pinned_key: Vec<u8>) -> Self {
+        Self {
+            seed_domain: seed_domain.into(),
+            pinned_key,
+            nameservers: Vec::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Signed list of bootstrap peers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedList {
+    /// Seed domain the list is published under
+    pub domain: String,
+    /// Peer multiaddrs, each with a `/p2p/` component
+    pub peers: Vec<String>,
+    /// Publication counter; chunks of one publication share it
+    pub sequence: u64,
+    /// Expiry as Unix seconds
+    pub expires_at: u64,
+    /// ML-DSA signature over the other fields
+    pub signature: Vec<u8>,
+}
+
+impl SeedList {
+    /// Sign a list of peers for `domain`, valid for `lifetime`
+    pub fn new(
+        keypair: &MlDsaKeyPair,
+        domain: &str,
+        peers: &[Multiaddr],
+        sequence: u64,
+        lifetime: Duration,
+    ) -> Result<Self, DnsSeedError> {
+        if let Some(peer) = peers.iter().find(|addr| peer_id_of(addr).is_none()) {
+            return Err(DnsSeedError::Malformed(format!("{} has no peer ID", peer)));
+        }
+        let mut list = Self {
+            domain: normalize(domain),
+            peers: peers.iter().map(ToString::to_string).collect(),
+            sequence,
+            expires_at: unix_now() + lifetime.as_secs(),
+            signature: Vec::new(),
+        };
+        list.signature = keypair.sign(&list.signable_bytes(), &mut rand::thread_rng())?;
+        Ok(list)
+    }
+
+    fn signable_bytes(&self) -> Vec<u8> {
+        let mut bytes = SEED_LIST_DOMAIN.to_vec();
+        bytes.extend_from_slice(&(self.domain.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.domain.as_bytes());
+        bytes.extend_from_slice(&(self.peers.len() as u32).to_le_bytes());
+        for peer in &self.peers {
+            bytes.extend_from_slice(&(peer.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(peer.as_bytes());
+        }
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&self.expires_at.to_le_bytes());
+        bytes
+    }
+
+    /// Check the list was signed by `pinned_key` for `domain` and is current
+    pub fn verify(&self, pinned_key: &[u8], domain: &str) -> Result<(), DnsSeedError> {
+        let expected = normalize(domain);
+        if self.domain != expected {
+            return Err(DnsSeedError::WrongDomain {
+                expected,
+                found: self.domain.clone(),
+            });
+        }
+        MlDsaPublicKey::from_bytes(pinned_key)
+            .and_then(|key| key.verify(&self.signable_bytes(), &self.signature))?;
+        if self.expires_at <= unix_now() {
+            return Err(DnsSeedError::Expired);
+        }
+        Ok(())
+    }
+
+    /// Peers of the list, grouped by peer ID
+    ///
+    /// Entries that do not parse are skipped.
+    pub fn peers(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        let mut peers: Vec<(PeerId, Vec<Multiaddr>)> = Vec::new();
+        for addr in self.peers.iter().filter_map(|peer| peer.parse().ok()) {
+            let Some(peer_id) = peer_id_of(&addr) else {
+                continue;
+            };
+            match peers.iter_mut().find(|(id, _)| *id == peer_id) {
+                Some((_, addrs)) => addrs.push(addr),
+                None => peers.push((peer_id, vec![addr])),
+            }
+        }
+        peers
+    }
+
+    /// Split the list into TXT record values
+    pub fn to_txt_records(&self) -> Result<Vec<String>, DnsSeedError> {
+        let encoded = BASE64
+            .encode(bincode::serialize(self).map_err(|e| DnsSeedError::Malformed(e.to_string()))?);
+        let chunks: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| std::str::from_utf8(chunk).expect("base64 is ASCII"))
+            .collect();
+        if chunks.len() > MAX_CHUNKS {
+            return Err(DnsSeedError::Malformed("seed list too large".into()));
+        }
+        Ok(chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                format!(
+                    "{}{}/{}/{}/{}",
+                    SEED_TXT_PREFIX,
+                    self.sequence,
+                    index,
+                    chunks.len(),
+                    chunk
+                )
+            })
+            .collect())
+    }
+
+    /// Reassemble the lists found in TXT record values
+    ///
+    /// Values that are not seed chunks are ignored. Every complete
+    /// publication is decoded; incomplete ones are dropped.
+    pub fn from_txt_records<S: AsRef<str>>(values: &[S]) -> Vec<Result<Self, DnsSeedError>> {
+        // Chunks by sequence, then index, with the announced count
+        let mut publications: HashMap<u64, (usize, BTreeMap<usize, String>)> = HashMap::new();
+        for value in values {
+            let Some(chunk) = value.as_ref().strip_prefix(SEED_TXT_PREFIX) else {
+                continue;
+            };
+            let mut fields = chunk.splitn(4, '/');
+            let parsed = (|| {
+                let sequence = fields.next()?.parse::<u64>().ok()?;
+                let index = fields.next()?.parse::<usize>().ok()?;
+                let count = fields.next()?.parse::<usize>().ok()?;
+                Some((sequence, index, count, fields.next()?.to_string()))
+            })();
+            let Some((sequence, index, count, data)) = parsed else {
+                debug!("Ignoring malformed seed chunk");
+                continue;
+            };
+            if count == 0 || count > MAX_CHUNKS || index >= count {
+                continue;
+            }
+            let (expected, chunks) = publications
+                .entry(sequence)
+                .or_insert_with(|| (count, BTreeMap::new()));
+            if *expected == count {
+                chunks.insert(index, data);
+            }
+        }
+
+        publications
+            .into_values()
+            .filter(|(count, chunks)| chunks.len() == *count)
+            .map(|(_, chunks)| {
+                let encoded: String = chunks.into_values().collect();
+                let bytes = BASE64
+                    .decode(encoded)
+                    .map_err(|e| DnsSeedError::Malformed(e.to_string()))?;
+                bincode::deserialize(&bytes).map_err(|e| DnsSeedError::Malformed(e.to_string()))
+            })
+            .collect()
+    }
+}
+
+/// A peer found through DNS seeds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedPeer {
+    pub peer_id: PeerId,
+    /// Addresses as listed
+    pub multiaddrs: Vec<Multiaddr>,
+    /// Socket addresses, with DNS names in the multiaddrs resolved
+    pub socket_addrs: Vec<SocketAddr>,
+}
+
+/// Resolves seed lists from DNS
+pub struct DnsSeedResolver {
+    config: DnsSeedConfig,
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsSeedResolver {
+    /// Create a resolver using the configured or system name servers
+    pub fn new(config: DnsSeedConfig) -> Result<Self, DnsSeedError> {
+        let mut options = ResolverOpts::default();
+        options.timeout = config.timeout;
+        let resolver = if config.nameservers.is_empty() {
+            let (system_config, mut system_options) =
+                hickory_resolver::system_conf::read_system_conf()?;
+            system_options.timeout = config.timeout;
+            TokioAsyncResolver::tokio(system_config, system_options)
+        } else {
+            // Truncated UDP answers are retried over TCP
+            let mut servers = NameServerConfigGroup::new();
+            for &addr in &config.nameservers {
+                servers.push(NameServerConfig::new(addr, Protocol::Udp));
+                servers.push(NameServerConfig::new(addr, Protocol::Tcp));
+            }
+            TokioAsyncResolver::tokio(ResolverConfig::from_parts(None, vec![], servers), options)
+        };
+        Ok(Self { config, resolver })
+    }
+
+    /// The configuration in use
+    pub fn config(&self) -> &DnsSeedConfig {
+        &self.config
+    }
+
+    /// Fetch and verify the seed lists of the seed domain
+    ///
+    /// Lists from the seed domain and its SRV targets are merged. Lists
+    /// that fail verification are skipped; it is an error if none is left.
+    pub async fn resolve_lists(&self) -> Result<Vec<SeedList>, DnsSeedError> {
+        let domain = normalize(&self.config.seed_domain);
+        let mut zones = vec![domain.clone()];
+        zones.extend(self.srv_targets(&domain).await?);
+
+        let mut lists = Vec::new();
+        for zone in zones {
+            for list in SeedList::from_txt_records(&self.txt_values(&zone).await?) {
+                match list.and_then(|list| {
+                    list.verify(&self.config.pinned_key, &domain)?;
+                    Ok(list)
+                }) {
+                    Ok(list) => lists.push(list),
+                    Err(e) => warn!("Rejected seed list under {}: {}", zone, e),
+                }
+            }
+        }
+        if lists.is_empty() {
+            return Err(DnsSeedError::NoSeeds(domain));
+        }
+        Ok(lists)
+    }
+
+    /// Resolve the seed peers, merging every valid list
+    pub async fn resolve(&self) -> Result<Vec<SeedPeer>, DnsSeedError> {
+        let mut peers: Vec<SeedPeer> = Vec::new();
+        for list in self.resolve_lists().await? {
+            for (peer_id, multiaddrs) in list.peers() {
+                let index = match peers.iter().position(|peer| peer.peer_id == peer_id) {
+                    Some(index) => index,
+                    None => {
+                        peers.push(SeedPeer {
+                            peer_id,
+                            multiaddrs: Vec::new(),
+                            socket_addrs: Vec::new(),
+                        });
+                        peers.len() - 1
+                    }
+                };
+                for addr in multiaddrs {
+                    if peers[index].multiaddrs.contains(&addr) {
+                        continue;
+                    }
+                    for socket_addr in self.socket_addrs(&addr).await {
+                        if !peers[index].socket_addrs.contains(&socket_addr) {
+                            peers[index].socket_addrs.push(socket_addr);
+                        }
+                    }
+                    peers[index].multiaddrs.push(addr);
+                }
+            }
+        }
+        Ok(peers)
+    }
+
+    /// Targets of the seed domain's SRV records, in priority order
+    async fn srv_targets(&self, domain: &str) -> Result<Vec<String>, DnsSeedError> {
+        let name = format!("{}.{}.", SEED_SRV_LABEL, domain);
+        let lookup = match self.resolver.srv_lookup(name).await {
+            Ok(lookup) => lookup,
+            Err(e) if is_no_records(&e) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut records: Vec<_> = lookup.iter().collect();
+        records.sort_by_key(|srv| (srv.priority(), std::cmp::Reverse(srv.weight())));
+        Ok(records
+            .into_iter()
+            .map(|srv| normalize(&srv.target().to_utf8()))
+            .filter(|target| target != domain)
+            .collect())
+    }
+
+    /// Values of the TXT records of `zone`, with character strings joined
+    async fn txt_values(&self, zone: &str) -> Result<Vec<String>, DnsSeedError> {
+        match self.resolver.txt_lookup(format!("{}.", zone)).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .map(|txt| {
+                    txt.txt_data()
+                        .iter()
+                        .map(|data| String::from_utf8_lossy(data))
+                        .collect()
+                })
+                .collect()),
+            Err(e) if is_no_records(&e) => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Socket addresses of a multiaddr, resolving DNS components
+    async fn socket_addrs(&self, addr: &Multiaddr) -> Vec<SocketAddr> {
+        let mut hosts = Vec::new();
+        let mut port = None;
+        for component in addr.iter() {
+            match component {
+                AddrProtocol::Ip4(ip) => hosts.push(IpAddr::V4(ip)),
+                AddrProtocol::Ip6(ip) => hosts.push(IpAddr::V6(ip)),
+                AddrProtocol::Dns(name) | AddrProtocol::Dns4(name) | AddrProtocol::Dns6(name) => {
+                    match self.resolver.lookup_ip(name.as_ref()).await {
+                        Ok(lookup) => hosts.extend(lookup.iter()),
+                        Err(e) => debug!("Could not resolve seed host {}: {}", name, e),
+                    }
+                }
+                AddrProtocol::Tcp(p) | AddrProtocol::Udp(p) => {
+                    port.get_or_insert(p);
+                }
+                _ => {}
+            }
+        }
+        match port {
+            Some(port) => hosts
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, port))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Publish a seed list as TXT records under its domain
+///
+/// The new chunks are created before the previous publication is deleted,
+/// so resolvers always find a complete list.
+pub async fn publish_seed_list(
+    provider: &dyn DnsProvider,
+    list: &SeedList,
+    ttl: u32,
+) -> Result<(), DnsSeedError> {
+    let domain = normalize(&list.domain);
+    let is_seed_record = |record: &DnsRecord| {
+        matches!(record.record_type, RecordType::TXT)
+            && normalize(&record.name) == domain
+            && record.content.starts_with(SEED_TXT_PREFIX)
+    };
+    let previous: Vec<DnsRecord> = provider
+        .list_records()
+        .await?
+        .into_iter()
+        .filter(is_seed_record)
+        .collect();
+
+    let mut created = Vec::new();
+    for content in list.to_txt_records()? {
+        let record = DnsRecord {
+            id: None,
+            name: domain.clone(),
+            record_type: RecordType::TXT,
+            content,
+            ttl,
+            proxied: false,
+        };
+        match provider.create_record(record).await {
+            Ok(record) => created.push(record),
+            Err(e) => {
+                // Leave the previous publication in place
+                for record in created {
+                    if let Some(id) = record.id {
+                        let _ = provider.delete_record(&id).await;
+                    }
+                }
+                return Err(e.into());
+            }
+        }
+    }
+
+    for record in previous {
+        if let Some(id) = record.id {
+            provider.delete_record(&id).await?;
+        }
+    }
+    Ok(())
+}
+
+fn is_no_records(error: &ResolveError) -> bool {
+    matches!(error.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+fn peer_id_of(addr: &Multiaddr) -> Option<PeerId> {
+    addr.iter().find_map(|component| match component {
+        AddrProtocol::P2p(peer_id) => Some(peer_id),
+        _ => None,
+    })
+}
+
+/// Lowercase a domain name without the trailing dot
+fn normalize(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
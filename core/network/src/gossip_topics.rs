@@ -0,0 +1,367 @@
+//! Typed gossip topics and the validation of messages received on them.
+//!
+//! Each [`GossipTopic`] carries one message schema, and the schema version is
+//! part of the topic name (`/qudag/vertices/1`). A node that changes a
+//! schema moves to a new topic, so peers never decode a message with the
+//! wrong schema.
+//!
+//! [`crate::p2p::P2PNode`] holds every message received on a typed topic
+//! until it has been decoded and checked by the [`GossipValidator`]
+//! registered for the topic. The [`Verdict`] goes back to gossipsub:
+//!
+//! * `Accept` delivers the message to the application and forwards it.
+//! * `Ignore` drops it without penalty, e.g. for duplicates.
+//! * `Reject` drops it and penalizes the peer that sent it, both in the
+//!   gossipsub score and in [`crate::peer_scoring::PeerScoring`].
+//!
+//! Messages that do not decode are rejected. Topics without a validator only
+//! get this schema check.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use libp2p::gossipsub::MessageAcceptance;
+use libp2p::PeerId;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+
+/// Prefix of every typed topic name
+pub const GOSSIP_TOPIC_PREFIX: &str = "/qudag";
+
+/// How long a validator may take before its message is ignored
+pub const VALIDATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors of typed gossip messages
+#[derive(Debug, Error)]
+pub enum GossipTopicError {
+    #[error("Unknown gossip topic: {0}")]
+    UnknownTopic(String),
+
+    #[error("Malformed {topic} message: {source}")]
+    Malformed {
+        topic: GossipTopic,
+        source: bincode::Error,
+    },
+
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] bincode::Error),
+}
+
+/// Gossip topics with a typed message schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GossipTopic {
+    /// New DAG vertices
+    Vertices,
+    /// Consensus votes on vertices
+    Votes,
+    /// `.dark` domain records
+    DarkRecords,
+    /// Exchange transactions
+    ExchangeTxs,
+    /// Peers announcing their addresses
+    PeerAnnouncements,
+}
+
+impl GossipTopic {
+    /// Every typed topic
+    pub const ALL: [GossipTopic; 5] = [
+        GossipTopic::Vertices,
+        GossipTopic::Votes,
+        GossipTopic::DarkRecords,
+        GossipTopic::ExchangeTxs,
+        GossipTopic::PeerAnnouncements,
+    ];
+
+    /// Name of the topic without prefix and version
+    pub fn name(self) -> &'static str {
+        match self {
+            GossipTopic::Vertices => "vertices",
+            GossipTopic::Votes => "votes",
+            GossipTopic::DarkRecords => "dark-records",
+            GossipTopic::ExchangeTxs => "exchange-txs",
+            GossipTopic::PeerAnnouncements => "peer-announcements",
+        }
+    }
+
+    /// Version of the message schema carried on the topic
+    pub fn schema_version(self) -> u16 {
+        match self {
+            GossipTopic::Vertices
+            | GossipTopic::Votes
+            | GossipTopic::DarkRecords
+            | GossipTopic::ExchangeTxs
+            | GossipTopic::PeerAnnouncements => 1,
+        }
+    }
+
+    /// Gossipsub topic name, including the schema version
+    pub fn topic(self) -> String {
+        format!(
+            "{}/{}/{}",
+            GOSSIP_TOPIC_PREFIX,
+            self.name(),
+            self.schema_version()
+        )
+    }
+
+    /// Typed topic of a gossipsub topic name at the current schema version
+    pub fn from_topic(topic: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.topic() == topic)
+    }
+}
+
+impl fmt::Display for GossipTopic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.topic())
+    }
+}
+
+impl std::str::FromStr for GossipTopic {
+    type Err = GossipTopicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_topic(s)
+            .or_else(|| Self::ALL.into_iter().find(|t| t.name() == s))
+            .ok_or_else(|| GossipTopicError::UnknownTopic(s.to_string()))
+    }
+}
+
+/// A DAG vertex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VertexMessage {
+    pub id: Vec<u8>,
+    pub parents: Vec<Vec<u8>>,
+    pub payload: Vec<u8>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+/// A consensus vote on a vertex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoteMessage {
+    pub vertex: Vec<u8>,
+    pub round: u64,
+    pub accept: bool,
+    pub voter: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// A `.dark` domain record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DarkRecordMessage {
+    pub domain: String,
+    /// Encoded record, signed by its owner
+    pub record: Vec<u8>,
+}
+
+/// An exchange transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExchangeTxMessage {
+    /// Encoded signed transaction
+    pub transaction: Vec<u8>,
+}
+
+/// A peer announcing the addresses it can be reached on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerAnnouncement {
+    pub peer_id: Vec<u8>,
+    /// Multiaddresses in their binary form
+    pub addresses: Vec<Vec<u8>>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// A message on one of the typed topics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GossipMessage {
+    Vertex(VertexMessage),
+    Vote(VoteMessage),
+    DarkRecord(DarkRecordMessage),
+    ExchangeTx(ExchangeTxMessage),
+    PeerAnnouncement(PeerAnnouncement),
+}
+
+impl GossipMessage {
+    /// Topic the message is published on
+    pub fn topic(&self) -> GossipTopic {
+        match self {
+            GossipMessage::Vertex(_) => GossipTopic::Vertices,
+            GossipMessage::Vote(_) => GossipTopic::Votes,
+            GossipMessage::DarkRecord(_) => GossipTopic::DarkRecords,
+            GossipMessage::ExchangeTx(_) => GossipTopic::ExchangeTxs,
+            GossipMessage::PeerAnnouncement(_) => GossipTopic::PeerAnnouncements,
+        }
+    }
+
+    /// Encode the message with its topic's schema
+    pub fn encode(&self) -> Result<Vec<u8>, GossipTopicError> {
+        let data = match self {
+            GossipMessage::Vertex(m) => bincode::serialize(m)?,
+            GossipMessage::Vote(m) => bincode::serialize(m)?,
+            GossipMessage::DarkRecord(m) => bincode::serialize(m)?,
+            GossipMessage::ExchangeTx(m) => bincode::serialize(m)?,
+            GossipMessage::PeerAnnouncement(m) => bincode::serialize(m)?,
+        };
+        Ok(data)
+    }
+
+    /// Decode a message received on `topic`
+    pub fn decode(topic: GossipTopic, data: &[u8]) -> Result<Self, GossipTopicError> {
+        let malformed = |source| GossipTopicError::Malformed { topic, source };
+        let message = match topic {
+            GossipTopic::Vertices => {
+                GossipMessage::Vertex(bincode::deserialize(data).map_err(malformed)?)
+            }
+            GossipTopic::Votes => {
+                GossipMessage::Vote(bincode::deserialize(data).map_err(malformed)?)
+            }
+            GossipTopic::DarkRecords => {
+                GossipMessage::DarkRecord(bincode::deserialize(data).map_err(malformed)?)
+            }
+            GossipTopic::ExchangeTxs => {
+                GossipMessage::ExchangeTx(bincode::deserialize(data).map_err(malformed)?)
+            }
+            GossipTopic::PeerAnnouncements => {
+                GossipMessage::PeerAnnouncement(bincode::deserialize(data).map_err(malformed)?)
+            }
+        };
+        Ok(message)
+    }
+}
+
+/// What to do with a received gossip message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Deliver and forward it
+    Accept,
+    /// Drop it and penalize the sender
+    Reject,
+    /// Drop it without penalty
+    Ignore,
+}
+
+impl From<Verdict> for MessageAcceptance {
+    fn from(verdict: Verdict) -> Self {
+        match verdict {
+            Verdict::Accept => MessageAcceptance::Accept,
+            Verdict::Reject => MessageAcceptance::Reject,
+            Verdict::Ignore => MessageAcceptance::Ignore,
+        }
+    }
+}
+
+/// Application check of the messages received on a topic
+///
+/// The node reports every rejected message as
+/// [`crate::peer_scoring::PeerEvent::InvalidMessage`]; validators may report
+/// graver misbehaviour themselves.
+#[async_trait]
+pub trait GossipValidator: Send + Sync {
+    /// Judge a message received from `source`
+    async fn validate(&self, source: PeerId, message: &GossipMessage) -> Verdict;
+}
+
+/// Outcome of validating one gossip message
+///
+/// A gossip message may carry a batch of application messages, each judged
+/// on its own. The accepted ones are delivered whatever the others' fate.
+/// The verdict decides whether the gossip message as a whole is forwarded:
+/// it is accepted if some messages are and none is rejected, rejected if
+/// every message is, and ignored otherwise, so a batch with some invalid
+/// messages is neither forwarded nor held against its sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validation {
+    pub verdict: Verdict,
+    /// Accepted messages, delivered even if the verdict is not `Accept`
+    pub accepted: Vec<GossipMessage>,
+    /// Number of messages that failed their schema or were rejected
+    pub rejected: usize,
+}
+
+/// Validators of the typed topics, shared by a node and its handles
+#[derive(Clone, Default)]
+pub struct GossipValidators {
+    validators: Arc<RwLock<HashMap<GossipTopic, Arc<dyn GossipValidator>>>>,
+}
+
+impl fmt::Debug for GossipValidators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.validators.read().keys())
+            .finish()
+    }
+}
+
+impl GossipValidators {
+    /// Validate `topic` with `validator`, replacing any previous one
+    pub fn set(&self, topic: GossipTopic, validator: Arc<dyn GossipValidator>) {
+        self.validators.write().insert(topic, validator);
+    }
+
+    /// Stop validating `topic` beyond its schema
+    pub fn remove(&self, topic: GossipTopic) -> bool {
+        self.validators.write().remove(&topic).is_some()
+    }
+
+    /// Validator of `topic`
+    pub fn get(&self, topic: GossipTopic) -> Option<Arc<dyn GossipValidator>> {
+        self.validators.read().get(&topic).cloned()
+    }
+
+    /// Decode and validate the messages of one gossip message
+    ///
+    /// A validator that does not answer within [`VALIDATION_TIMEOUT`] has its
+    /// message ignored.
+    pub async fn validate(
+        &self,
+        source: PeerId,
+        topic: GossipTopic,
+        messages: Vec<Vec<u8>>,
+    ) -> Validation {
+        let validator = self.get(topic);
+        let total = messages.len();
+        let mut accepted = Vec::new();
+        let mut rejected = 0;
+        for data in messages {
+            let message = match GossipMessage::decode(topic, &data) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("Rejected gossip from {}: {}", source, e);
+                    rejected += 1;
+                    continue;
+                }
+            };
+            let verdict = match &validator {
+                Some(validator) => {
+                    tokio::time::timeout(VALIDATION_TIMEOUT, validator.validate(source, &message))
+                        .await
+                        .unwrap_or(Verdict::Ignore)
+                }
+                None => Verdict::Accept,
+            };
+            match verdict {
+                Verdict::Accept => accepted.push(message),
+                Verdict::Ignore => {}
+                Verdict::Reject => rejected += 1,
+            }
+        }
+        let verdict = if total > 0 && rejected == total {
+            Verdict::Reject
+        } else if rejected == 0 && !accepted.is_empty() {
+            Verdict::Accept
+        } else {
+            Verdict::Ignore
+        };
+        Validation {
+            verdict,
+            accepted,
+            rejected,
+        }
+    }
+}
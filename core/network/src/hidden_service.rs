@@ -0,0 +1,931 @@
+//! Hidden services: `.dark` domains reachable without revealing addresses.
+//!
+//! A hidden service publishes a [`DarkDomainRecord`] without addresses.
+//! Instead it lists introduction points: relays at the end of onion circuits
+//! built by the service, each known by a random introduction ID. Connecting
+//! to a service works like a Tor onion service:
+//!
+//! 1. The client builds a circuit to a relay of its choice, the rendezvous
+//!    point, and leaves a random cookie there with `EstablishRendezvous`.
+//! 2. The client builds a circuit to an introduction point and sends
+//!    `Introduce`. The payload is encrypted to the ML-KEM key of the record
+//!    and names the rendezvous point, the cookie and a fresh client ML-KEM
+//!    key. The introduction point pushes it down the service's circuit.
+//! 3. The service builds a circuit to the rendezvous point and sends
+//!    `Rendezvous` with the cookie and its half of the key exchange. The
+//!    rendezvous point joins both circuits and pushes it to the client.
+//!
+//! Session keys come from both ML-KEM exchanges, so only the holder of the
+//! record's key can complete the rendezvous. Each side only ever talks to
+//! the first hop of its own circuits; relays at the meeting points see
+//! circuits, not addresses.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libp2p::PeerId as LibP2PPeerId;
+use qudag_crypto::kem::{
+    Ciphertext as KEMCiphertext, PublicKey as KEMPublicKey, SecretKey as KEMSecretKey,
+};
+use qudag_crypto::ml_dsa::{MlDsaError, MlDsaKeyPair};
+use qudag_crypto::MlKem768;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::dark_resolver::{
+    DarkAddress, DarkDomainRecord, DarkResolver, DarkResolverError, IntroductionPoint,
+};
+use crate::onion_relay::{HopKeys, RelayCommand, RelayResponse};
+use crate::p2p::{P2PEvent, P2PHandle};
+use crate::types::PeerId;
+
+/// Key derivation domain for introduction payloads
+const INTRO_DOMAIN: &[u8] = b"qudag-hs-intro/1";
+
+/// Key derivation domain for session keys
+const SESSION_DOMAIN: &[u8] = b"qudag-hs-session/1";
+
+/// How long introductions are accepted and their cookies remembered
+const INTRODUCTION_WINDOW: Duration = Duration::from_secs(600);
+
+/// Pause between attempts to build a circuit
+const BUILD_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Errors from hidden services
+#[derive(Debug, Error)]
+pub enum HiddenServiceError {
+    #[error("Resolver error: {0}")]
+    Resolver(#[from] DarkResolverError),
+    #[error("ML-DSA error: {0}")]
+    MlDsa(#[from] MlDsaError),
+    #[error("{0} is not a hidden service")]
+    NotHidden(String),
+    #[error("Network error: {0}")]
+    Network(String),
+    #[error("Key exchange failed: {0}")]
+    KeyExchange(String),
+    #[error("Malformed message: {0}")]
+    Malformed(String),
+    #[error("Introduction replayed or expired")]
+    Replay,
+    #[error("Unknown session {0}")]
+    UnknownSession(u64),
+    #[error("Rendezvous timed out")]
+    Timeout,
+}
+
+/// Hidden service settings
+#[derive(Debug, Clone)]
+pub struct HiddenServiceConfig {
+    /// Introduction points to publish per service
+    pub introduction_points: usize,
+    /// Hops of every circuit, counting the introduction or rendezvous point
+    pub circuit_hops: usize,
+    /// Lifetime of published records in seconds
+    pub record_ttl: u32,
+    /// How long a client waits for the service at the rendezvous point
+    pub rendezvous_timeout: Duration,
+    /// Attempts at building each circuit
+    pub build_attempts: usize,
+    /// Interval of keep-alive padding and introduction point checks
+    pub maintenance_interval: Duration,
+}
+
+impl Default for HiddenServiceConfig {
+    fn default() -> Self {
+        Self {
+            introduction_points: 3,
+            circuit_hops: 3,
+            record_ttl: 3600,
+            rendezvous_timeout: Duration::from_secs(30),
+            build_attempts: 5,
+            maintenance_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Keys of a hidden service
+///
+/// The domain is derived from the ML-DSA key; clients encrypt
+/// introductions to the ML-KEM key.
+pub struct HiddenServiceKeys {
+    pub signing: MlDsaKeyPair,
+    pub kem_public: KEMPublicKey,
+    pub kem_secret: KEMSecretKey,
+}
+
+impl HiddenServiceKeys {
+    /// Generate fresh keys
+    pub fn generate() -> Result<Self, HiddenServiceError> {
+        let signing = MlDsaKeyPair::generate(&mut thread_rng())?;
+        let (kem_public, kem_secret) =
+            MlKem768::keygen().map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        Ok(Self {
+            signing,
+            kem_public,
+            kem_secret,
+        })
+    }
+}
+
+/// Events of hidden services, with the other network events passed through
+#[derive(Debug)]
+pub enum HiddenServiceEvent {
+    /// A client connected to a hosted service
+    IncomingSession { domain: String, session: u64 },
+    /// Data received on a session
+    Data { session: u64, payload: Vec<u8> },
+    /// Any other network event
+    Network(P2PEvent),
+}
+
+/// Introduction sent by a client, encrypted to the service's ML-KEM key
+#[derive(Serialize, Deserialize)]
+struct Introduction {
+    kem_ciphertext: Vec<u8>,
+    sealed: Vec<u8>,
+}
+
+/// Encrypted part of an introduction
+#[derive(Serialize, Deserialize)]
+struct IntroductionBody {
+    domain: String,
+    rendezvous: Vec<u8>,
+    cookie: [u8; 32],
+    client_key: Vec<u8>,
+    timestamp: u64,
+}
+
+/// Payload the service leaves at the rendezvous point
+#[derive(Serialize, Deserialize)]
+struct Join {
+    kem_ciphertext: Vec<u8>,
+    confirmation: [u8; 32],
+}
+
+/// Session data, numbered so replayed frames are dropped
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    sequence: u64,
+    payload: Vec<u8>,
+}
+
+/// A service hosted by this node
+struct HostedService {
+    keys: Arc<HiddenServiceKeys>,
+    alias: Option<String>,
+    /// Introduction points with the circuits ending at them
+    intro_circuits: Vec<(u64, IntroductionPoint)>,
+    /// Cookies of accepted introductions
+    seen_cookies: HashMap<[u8; 32], Instant>,
+}
+
+/// An end-to-end session over a rendezvous circuit
+struct Session {
+    keys: HopKeys,
+    /// Whether this side connected to the service
+    initiator: bool,
+    sent: u64,
+    received: u64,
+}
+
+impl Session {
+    fn seal(&mut self, payload: Vec<u8>) -> Result<Vec<u8>, HiddenServiceError> {
+        self.sent += 1;
+        let frame = encode(&Frame {
+            sequence: self.sent,
+            payload,
+        })?;
+        let sealed = if self.initiator {
+            self.keys.seal_forward(&frame)
+        } else {
+            self.keys.seal_backward(&frame)
+        };
+        sealed.map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))
+    }
+
+    fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, HiddenServiceError> {
+        let frame = if self.initiator {
+            self.keys.open_backward(sealed)
+        } else {
+            self.keys.open_forward(sealed)
+        }
+        .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let frame: Frame = decode(&frame)?;
+        if frame.sequence <= self.received {
+            return Err(HiddenServiceError::Replay);
+        }
+        self.received = frame.sequence;
+        Ok(frame.payload)
+    }
+}
+
+struct Inner {
+    handle: P2PHandle,
+    resolver: Arc<DarkResolver>,
+    config: HiddenServiceConfig,
+    /// Hosted services by domain
+    services: Mutex<HashMap<String, HostedService>>,
+    /// Domain of each introduction circuit
+    intro_circuits: Mutex<HashMap<u64, String>>,
+    /// Sessions by the ID of their circuit
+    sessions: Mutex<HashMap<u64, Session>>,
+    /// Clients waiting for a service at a rendezvous point, by circuit ID
+    pending_joins: Mutex<HashMap<u64, oneshot::Sender<Vec<u8>>>>,
+    event_tx: mpsc::UnboundedSender<HiddenServiceEvent>,
+    event_rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<HiddenServiceEvent>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Ok(tasks) = self.tasks.get_mut() {
+            tasks.iter().for_each(JoinHandle::abort);
+        }
+    }
+}
+
+/// Hosts hidden services and connects to them
+///
+/// Takes over the events of the [`P2PHandle`] it is given; read them from
+/// [`HiddenServices::next_event`] instead.
+#[derive(Clone)]
+pub struct HiddenServices {
+    inner: Arc<Inner>,
+}
+
+impl HiddenServices {
+    /// Start handling hidden service traffic on `handle`
+    ///
+    /// `resolver` publishes and resolves records, so it should be backed by
+    /// the DHT of the same node.
+    pub fn new(
+        handle: P2PHandle,
+        resolver: Arc<DarkResolver>,
+        config: HiddenServiceConfig,
+    ) -> Self {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let inner = Arc::new(Inner {
+            handle,
+            resolver,
+            config,
+            services: Mutex::new(HashMap::new()),
+            intro_circuits: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            pending_joins: Mutex::new(HashMap::new()),
+            event_tx,
+            event_rx: tokio::sync::Mutex::new(event_rx),
+            tasks: Mutex::new(Vec::new()),
+        });
+        let tasks = vec![
+            tokio::spawn(pump_events(Arc::downgrade(&inner))),
+            tokio::spawn(maintain(Arc::downgrade(&inner))),
+        ];
+        *inner.tasks.lock().unwrap() = tasks;
+        Self { inner }
+    }
+
+    /// Host a service, publishing a record with introduction points
+    ///
+    /// Hosting the same keys again replaces the introduction points.
+    pub async fn host(
+        &self,
+        keys: HiddenServiceKeys,
+        alias: Option<&str>,
+    ) -> Result<DarkAddress, HiddenServiceError> {
+        let address = DarkResolver::generate_dark_address(keys.signing.public_key(), alias)?;
+        let keys = Arc::new(keys);
+        let mut relays = self.inner.handle.known_relays().await;
+        relays.shuffle(&mut thread_rng());
+
+        let mut intro_circuits = Vec::new();
+        for relay in relays {
+            if intro_circuits.len() == self.inner.config.introduction_points {
+                break;
+            }
+            match self.inner.establish_intro(relay).await {
+                Ok(intro) => intro_circuits.push(intro),
+                Err(e) => debug!("Introduction point {} failed: {}", relay, e),
+            }
+        }
+        if intro_circuits.is_empty() {
+            return Err(HiddenServiceError::Network(
+                "no introduction point could be established".into(),
+            ));
+        }
+
+        let old = self.inner.services.lock().unwrap().insert(
+            address.domain.clone(),
+            HostedService {
+                keys,
+                alias: alias.map(str::to_string),
+                intro_circuits,
+                seen_cookies: HashMap::new(),
+            },
+        );
+        if let Some(old) = old {
+            for (circuit_id, _) in old.intro_circuits {
+                self.inner
+                    .intro_circuits
+                    .lock()
+                    .unwrap()
+                    .remove(&circuit_id);
+                let _ = self.inner.handle.destroy_circuit(circuit_id).await;
+            }
+        }
+        self.inner.publish(&address.domain).await?;
+        Ok(address)
+    }
+
+    /// Stop hosting a service
+    pub async fn stop(&self, domain: &str) {
+        let service = self.inner.services.lock().unwrap().remove(domain);
+        for (circuit_id, _) in service.map(|s| s.intro_circuits).unwrap_or_default() {
+            self.inner
+                .intro_circuits
+                .lock()
+                .unwrap()
+                .remove(&circuit_id);
+            let _ = self.inner.handle.destroy_circuit(circuit_id).await;
+        }
+    }
+
+    /// Connect to a hidden service, returning the session ID
+    pub async fn connect(&self, domain: &str) -> Result<u64, HiddenServiceError> {
+        let record = self.inner.resolver.resolve_domain(domain).await?;
+        if !record.is_hidden_service() {
+            return Err(HiddenServiceError::NotHidden(domain.to_string()));
+        }
+        let service_key = KEMPublicKey::from_bytes(&record.encryption_public_key)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+
+        // Meet at a relay that is not one of the service's introduction points
+        let mut relays = self.inner.handle.known_relays().await;
+        relays.retain(|relay| {
+            !record
+                .introduction_points
+                .iter()
+                .any(|point| point.relay == relay.to_bytes())
+        });
+        let rendezvous = *relays
+            .choose(&mut thread_rng())
+            .ok_or_else(|| HiddenServiceError::Network("no rendezvous relay".into()))?;
+
+        let mut cookie = [0u8; 32];
+        thread_rng().fill_bytes(&mut cookie);
+        let circuit_id = self.inner.build_circuit_to(rendezvous).await?;
+        let (join_tx, join_rx) = oneshot::channel();
+        self.inner
+            .pending_joins
+            .lock()
+            .unwrap()
+            .insert(circuit_id, join_tx);
+        let result = self
+            .rendezvous(
+                domain,
+                &record,
+                &service_key,
+                rendezvous,
+                circuit_id,
+                cookie,
+                join_rx,
+            )
+            .await;
+        self.inner.pending_joins.lock().unwrap().remove(&circuit_id);
+        if result.is_err() {
+            let _ = self.inner.handle.destroy_circuit(circuit_id).await;
+        }
+        result.map(|()| circuit_id)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn rendezvous(
+        &self,
+        domain: &str,
+        record: &DarkDomainRecord,
+        service_key: &KEMPublicKey,
+        rendezvous: LibP2PPeerId,
+        circuit_id: u64,
+        cookie: [u8; 32],
+        join_rx: oneshot::Receiver<Vec<u8>>,
+    ) -> Result<(), HiddenServiceError> {
+        self.inner
+            .command(circuit_id, RelayCommand::EstablishRendezvous { cookie })
+            .await?;
+
+        let (ciphertext, intro_secret) = MlKem768::encapsulate(service_key)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let (client_public, client_secret) =
+            MlKem768::keygen().map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let body = encode(&IntroductionBody {
+            domain: domain.to_string(),
+            rendezvous: rendezvous.to_bytes(),
+            cookie,
+            client_key: client_public.as_bytes().to_vec(),
+            timestamp: unix_now(),
+        })?;
+        let (intro_keys, _) =
+            HopKeys::derive_in(INTRO_DOMAIN, intro_secret.as_bytes(), ciphertext.as_bytes());
+        let payload = encode(&Introduction {
+            kem_ciphertext: ciphertext.as_bytes().to_vec(),
+            sealed: intro_keys
+                .seal_forward(&body)
+                .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?,
+        })?;
+
+        // Try the introduction points in random order
+        let mut points = record.introduction_points.clone();
+        points.shuffle(&mut thread_rng());
+        let mut introduced = false;
+        for point in points {
+            match self.inner.introduce(&point, payload.clone()).await {
+                Ok(()) => {
+                    introduced = true;
+                    break;
+                }
+                Err(e) => debug!("Introduction through {:?} failed: {}", point.relay, e),
+            }
+        }
+        if !introduced {
+            return Err(HiddenServiceError::Network(
+                "no introduction point reachable".into(),
+            ));
+        }
+
+        let join = tokio::time::timeout(self.inner.config.rendezvous_timeout, join_rx)
+            .await
+            .map_err(|_| HiddenServiceError::Timeout)?
+            .map_err(|_| HiddenServiceError::Timeout)?;
+        let join: Join = decode(&join)?;
+        let join_ciphertext = KEMCiphertext::from_bytes(&join.kem_ciphertext)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let join_secret = MlKem768::decapsulate(&client_secret, &join_ciphertext)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let (keys, confirmation) = session_keys(
+            intro_secret.as_bytes(),
+            join_secret.as_bytes(),
+            ciphertext.as_bytes(),
+            &join.kem_ciphertext,
+            &cookie,
+        );
+        if confirmation != join.confirmation {
+            return Err(HiddenServiceError::KeyExchange(
+                "service failed key confirmation".into(),
+            ));
+        }
+        self.inner.sessions.lock().unwrap().insert(
+            circuit_id,
+            Session {
+                keys,
+                initiator: true,
+                sent: 0,
+                received: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Send data on a session
+    pub async fn send(&self, session: u64, payload: Vec<u8>) -> Result<(), HiddenServiceError> {
+        let sealed = self
+            .inner
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(&session)
+            .ok_or(HiddenServiceError::UnknownSession(session))?
+            .seal(payload)?;
+        self.inner
+            .command(session, RelayCommand::Stream(sealed))
+            .await
+            .map(|_| ())
+    }
+
+    /// Close a session
+    pub async fn close(&self, session: u64) -> Result<(), HiddenServiceError> {
+        self.inner
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(&session)
+            .ok_or(HiddenServiceError::UnknownSession(session))?;
+        self.inner
+            .handle
+            .destroy_circuit(session)
+            .await
+            .map_err(|e| HiddenServiceError::Network(e.to_string()))
+    }
+
+    /// Get the next hidden service or network event
+    pub async fn next_event(&self) -> Option<HiddenServiceEvent> {
+        self.inner.event_rx.lock().await.recv().await
+    }
+}
+
+impl Inner {
+    /// Build a circuit ending at `relay`, retrying on failure
+    async fn build_circuit_to(&self, relay: LibP2PPeerId) -> Result<u64, HiddenServiceError> {
+        let mut last_error = String::new();
+        for attempt in 0..self.config.build_attempts.max(1) {
+            if attempt > 0 {
+                tokio::time::sleep(BUILD_RETRY_DELAY).await;
+            }
+            match self
+                .handle
+                .build_circuit_to(self.config.circuit_hops, relay)
+                .await
+            {
+                Ok(circuit_id) => return Ok(circuit_id),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(HiddenServiceError::Network(last_error))
+    }
+
+    /// Send a command to the last hop of a circuit, expecting success
+    async fn command(
+        &self,
+        circuit_id: u64,
+        command: RelayCommand,
+    ) -> Result<RelayResponse, HiddenServiceError> {
+        match self.handle.send_relay_command(circuit_id, command).await {
+            Ok(RelayResponse::Failed(e)) => Err(HiddenServiceError::Network(e)),
+            Ok(response) => Ok(response),
+            Err(e) => Err(HiddenServiceError::Network(e.to_string())),
+        }
+    }
+
+    /// Open a circuit to `relay` and make it an introduction point
+    async fn establish_intro(
+        &self,
+        relay: LibP2PPeerId,
+    ) -> Result<(u64, IntroductionPoint), HiddenServiceError> {
+        let circuit_id = self.build_circuit_to(relay).await?;
+        let mut intro_id = [0u8; 32];
+        thread_rng().fill_bytes(&mut intro_id);
+        if let Err(e) = self
+            .command(circuit_id, RelayCommand::EstablishIntro { intro_id })
+            .await
+        {
+            let _ = self.handle.destroy_circuit(circuit_id).await;
+            return Err(e);
+        }
+        Ok((
+            circuit_id,
+            IntroductionPoint {
+                relay: relay.to_bytes(),
+                intro_id,
+            },
+        ))
+    }
+
+    /// Send an introduction over a new circuit to an introduction point
+    async fn introduce(
+        &self,
+        point: &IntroductionPoint,
+        payload: Vec<u8>,
+    ) -> Result<(), HiddenServiceError> {
+        let relay = LibP2PPeerId::from_bytes(&point.relay)
+            .map_err(|e| HiddenServiceError::Malformed(e.to_string()))?;
+        let circuit_id = self.build_circuit_to(relay).await?;
+        let result = self
+            .command(
+                circuit_id,
+                RelayCommand::Introduce {
+                    intro_id: point.intro_id,
+                    payload,
+                },
+            )
+            .await;
+        let _ = self.handle.destroy_circuit(circuit_id).await;
+        result.map(|_| ())
+    }
+
+    /// Sign and publish the record of a hosted service
+    ///
+    /// The record names a random owner, so it cannot be linked to this node.
+    async fn publish(&self, domain: &str) -> Result<(), HiddenServiceError> {
+        let record = {
+            let services = self.services.lock().unwrap();
+            let service = services
+                .get(domain)
+                .ok_or_else(|| HiddenServiceError::NotHidden(domain.to_string()))?;
+            let circuits = &service.intro_circuits;
+            self.intro_circuits
+                .lock()
+                .unwrap()
+                .extend(circuits.iter().map(|(id, _)| (*id, domain.to_string())));
+            let mut record = DarkDomainRecord::new(
+                &service.keys.signing,
+                service.keys.kem_public.as_bytes().to_vec(),
+                Vec::new(),
+                service.alias.clone(),
+                self.config.record_ttl,
+                PeerId::random(),
+            )?;
+            // Timestamps keep sequence numbers increasing across restarts
+            record.bind_to_domain(&service.keys.signing, domain, unix_now_nanos())?;
+            record.set_introduction_points(
+                &service.keys.signing,
+                circuits.iter().map(|(_, point)| point.clone()).collect(),
+            )?;
+            record
+        };
+        self.resolver.register_record(record)?;
+        self.resolver.publish_domain(domain).await?;
+        Ok(())
+    }
+
+    /// Accept an introduction pushed to one of the hosted services
+    async fn accept(&self, domain: String, payload: Vec<u8>) -> Result<(), HiddenServiceError> {
+        let keys = self
+            .services
+            .lock()
+            .unwrap()
+            .get(&domain)
+            .map(|service| service.keys.clone())
+            .ok_or_else(|| HiddenServiceError::NotHidden(domain.clone()))?;
+        let introduction: Introduction = decode(&payload)?;
+        let ciphertext = KEMCiphertext::from_bytes(&introduction.kem_ciphertext)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let intro_secret = MlKem768::decapsulate(&keys.kem_secret, &ciphertext)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let (intro_keys, _) = HopKeys::derive_in(
+            INTRO_DOMAIN,
+            intro_secret.as_bytes(),
+            &introduction.kem_ciphertext,
+        );
+        let body = intro_keys
+            .open_forward(&introduction.sealed)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let body: IntroductionBody = decode(&body)?;
+        if body.domain != domain
+            || unix_now().abs_diff(body.timestamp) > INTRODUCTION_WINDOW.as_secs()
+        {
+            return Err(HiddenServiceError::Replay);
+        }
+        {
+            let mut services = self.services.lock().unwrap();
+            let service = services
+                .get_mut(&domain)
+                .ok_or_else(|| HiddenServiceError::NotHidden(domain.clone()))?;
+            service
+                .seen_cookies
+                .retain(|_, seen| seen.elapsed() < INTRODUCTION_WINDOW * 2);
+            if service
+                .seen_cookies
+                .insert(body.cookie, Instant::now())
+                .is_some()
+            {
+                return Err(HiddenServiceError::Replay);
+            }
+        }
+
+        let client_key = KEMPublicKey::from_bytes(&body.client_key)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let (join_ciphertext, join_secret) = MlKem768::encapsulate(&client_key)
+            .map_err(|e| HiddenServiceError::KeyExchange(e.to_string()))?;
+        let (keys, confirmation) = session_keys(
+            intro_secret.as_bytes(),
+            join_secret.as_bytes(),
+            &introduction.kem_ciphertext,
+            join_ciphertext.as_bytes(),
+            &body.cookie,
+        );
+        let rendezvous = LibP2PPeerId::from_bytes(&body.rendezvous)
+            .map_err(|e| HiddenServiceError::Malformed(e.to_string()))?;
+        let circuit_id = self.build_circuit_to(rendezvous).await?;
+        // Register the session first, the client may answer right away
+        self.sessions.lock().unwrap().insert(
+            circuit_id,
+            Session {
+                keys,
+                initiator: false,
+                sent: 0,
+                received: 0,
+            },
+        );
+        let join = encode(&Join {
+            kem_ciphertext: join_ciphertext.as_bytes().to_vec(),
+            confirmation,
+        })?;
+        if let Err(e) = self
+            .command(
+                circuit_id,
+                RelayCommand::Rendezvous {
+                    cookie: body.cookie,
+                    payload: join,
+                },
+            )
+            .await
+        {
+            self.sessions.lock().unwrap().remove(&circuit_id);
+            let _ = self.handle.destroy_circuit(circuit_id).await;
+            return Err(e);
+        }
+        let _ = self.event_tx.send(HiddenServiceEvent::IncomingSession {
+            domain,
+            session: circuit_id,
+        });
+        Ok(())
+    }
+
+    /// Act on a response pushed down one of this node's circuits
+    fn handle_push(self: &Arc<Self>, circuit_id: u64, response: RelayResponse) {
+        match response {
+            RelayResponse::Introduction(payload) => {
+                let Some(domain) = self
+                    .intro_circuits
+                    .lock()
+                    .unwrap()
+                    .get(&circuit_id)
+                    .cloned()
+                else {
+                    return;
+                };
+                let inner = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = inner.accept(domain, payload).await {
+                        debug!("Rejected introduction: {}", e);
+                    }
+                });
+            }
+            RelayResponse::Joined(payload) => {
+                if let Some(waiting) = self.pending_joins.lock().unwrap().remove(&circuit_id) {
+                    let _ = waiting.send(payload);
+                }
+            }
+            RelayResponse::Stream(sealed) => {
+                let opened = match self.sessions.lock().unwrap().get_mut(&circuit_id) {
+                    Some(session) => session.open(&sealed),
+                    None => return,
+                };
+                match opened {
+                    Ok(payload) => {
+                        let _ = self.event_tx.send(HiddenServiceEvent::Data {
+                            session: circuit_id,
+                            payload,
+                        });
+                    }
+                    Err(e) => debug!("Dropped session data: {}", e),
+                }
+            }
+            response => debug!("Unexpected push on circuit {}: {:?}", circuit_id, response),
+        }
+    }
+
+    /// Keep circuits alive and replace introduction points that failed
+    async fn maintain(&self) {
+        let sessions: Vec<u64> = self.sessions.lock().unwrap().keys().copied().collect();
+        for session in sessions {
+            if self
+                .command(session, RelayCommand::Padding(Vec::new()))
+                .await
+                .is_err()
+            {
+                debug!("Session {} lost its circuit", session);
+                self.sessions.lock().unwrap().remove(&session);
+            }
+        }
+
+        let services: Vec<(String, Vec<(u64, IntroductionPoint)>)> = self
+            .services
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(domain, service)| (domain.clone(), service.intro_circuits.clone()))
+            .collect();
+        for (domain, circuits) in services {
+            let mut failed = Vec::new();
+            for (circuit_id, point) in &circuits {
+                if self
+                    .command(*circuit_id, RelayCommand::Padding(Vec::new()))
+                    .await
+                    .is_err()
+                {
+                    failed.push((*circuit_id, point.relay.clone()));
+                }
+            }
+            if failed.is_empty() {
+                continue;
+            }
+            warn!("{} of {} lost introduction points", domain, failed.len());
+            let mut replacements = Vec::new();
+            let mut relays = self.handle.known_relays().await;
+            relays.shuffle(&mut thread_rng());
+            let in_use: Vec<Vec<u8>> = circuits.iter().map(|(_, p)| p.relay.clone()).collect();
+            for relay in relays
+                .into_iter()
+                .filter(|relay| !in_use.contains(&relay.to_bytes()))
+            {
+                if replacements.len() == failed.len() {
+                    break;
+                }
+                if let Ok(intro) = self.establish_intro(relay).await {
+                    replacements.push(intro);
+                }
+            }
+            {
+                let mut intro_circuits = self.intro_circuits.lock().unwrap();
+                let mut services = self.services.lock().unwrap();
+                let Some(service) = services.get_mut(&domain) else {
+                    continue;
+                };
+                for (circuit_id, _) in &failed {
+                    intro_circuits.remove(circuit_id);
+                }
+                service
+                    .intro_circuits
+                    .retain(|(id, _)| !failed.iter().any(|(failed, _)| failed == id));
+                service.intro_circuits.extend(replacements);
+            }
+            if let Err(e) = self.publish(&domain).await {
+                warn!("Republishing {} failed: {}", domain, e);
+            }
+        }
+    }
+}
+
+/// Move network events to the hidden service event stream, handling pushes
+async fn pump_events(inner: Weak<Inner>) {
+    loop {
+        let Some(handle) = inner.upgrade().map(|inner| inner.handle.clone()) else {
+            return;
+        };
+        let Some(event) = handle.next_event().await else {
+            return;
+        };
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        match event {
+            P2PEvent::CircuitPush {
+                circuit_id,
+                response,
+            } => inner.handle_push(circuit_id, response),
+            event => {
+                let _ = inner.event_tx.send(HiddenServiceEvent::Network(event));
+            }
+        }
+    }
+}
+
+/// Run maintenance for as long as the services are alive
+async fn maintain(inner: Weak<Inner>) {
+    let Some(interval) = inner
+        .upgrade()
+        .map(|inner| inner.config.maintenance_interval)
+    else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        inner.maintain().await;
+    }
+}
+
+/// Derive session keys from both key exchanges of a rendezvous
+fn session_keys(
+    intro_secret: &[u8],
+    join_secret: &[u8],
+    intro_ciphertext: &[u8],
+    join_ciphertext: &[u8],
+    cookie: &[u8; 32],
+) -> (HopKeys, [u8; 32]) {
+    let secret = [intro_secret, join_secret].concat();
+    let transcript = [intro_ciphertext, join_ciphertext, cookie].concat();
+    HopKeys::derive_in(SESSION_DOMAIN, &secret, &transcript)
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, HiddenServiceError> {
+    bincode::serialize(value).map_err(|e| HiddenServiceError::Malformed(e.to_string()))
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(bytes: &[u8]) -> Result<T, HiddenServiceError> {
+    bincode::deserialize(bytes).map_err(|e| HiddenServiceError::Malformed(e.to_string()))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn unix_now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
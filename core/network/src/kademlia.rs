@@ -4,6 +4,7 @@
 use crate::discovery::{
     DHTConfig, DiscoveredPeer, DiscoveryEvent, DiscoveryMethod, GeographicInfo, PeerScoringConfig,
 };
+use crate::peer_scoring::{PeerEvent, PeerScoring};
 use crate::shadow_address::{ShadowAddress, ShadowAddressResolver};
 use crate::types::NetworkError;
 use libp2p::{
@@ -268,6 +269,8 @@ pub struct KademliaDHT {
     dark_resolver: Option<Arc<dyn ShadowAddressResolver + Send + Sync>>,
     /// Network partitions detector
     partition_detector: Arc<Mutex<PartitionDetector>>,
+    /// Node-wide peer scoring, told about unreachable peers
+    peer_scoring: Option<PeerScoring>,
 }
 
 /// Bootstrap state tracking
@@ -433,6 +436,7 @@ impl KademliaDHT {
                 detection_threshold: Duration::from_secs(300), // 5 minutes
                 detected_partitions: Vec::new(),
             })),
+            peer_scoring: None,
         }
     }
 
@@ -446,6 +450,14 @@ impl KademliaDHT {
         self.dark_resolver = Some(resolver);
     }
 
+    /// Set node-wide peer scoring
+    ///
+    /// Unreachable peers are reported to it, and peers it has banned are
+    /// left out of [`Self::get_top_peers`].
+    pub fn set_peer_scoring(&mut self, scoring: PeerScoring) {
+        self.peer_scoring = Some(scoring);
+    }
+
     /// Bootstrap the DHT with known nodes
     pub async fn bootstrap(&mut self) -> Result<(), NetworkError> {
         match &self.bootstrap_state {
@@ -616,6 +628,9 @@ impl KademliaDHT {
             reputation.downtime_incidents += 1;
             reputation.record_interaction(false, None, &self.scoring_config);
         }
+        if let Some(scoring) = &self.peer_scoring {
+            scoring.report(peer, PeerEvent::Timeout);
+        }
     }
 
     /// Handle routable peer
@@ -1000,6 +1015,11 @@ impl KademliaDHT {
             .read()
             .await
             .values()
+            .filter(|reputation| {
+                self.peer_scoring
+                    .as_ref()
+                    .is_none_or(|scoring| !scoring.is_banned(&reputation.peer_id))
+            })
             .cloned()
             .collect();
         peers.sort_by(|a, b| {
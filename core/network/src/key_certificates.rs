@@ -0,0 +1,147 @@
+//! Gossip distribution of key rotation and revocation certificates.
+//!
+//! Certificates are published on [`KEY_CERTIFICATE_TOPIC`] and merged into a
+//! shared [`RevocationList`] on receipt. Each certificate is self-verifying,
+//! so it does not matter which peer relays it.
+
+use std::time::Duration;
+
+use qudag_crypto::key_certificate::{KeyCertificate, KeyCertificateError, RevocationList};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::p2p::{P2PEvent, P2PHandle};
+
+/// Gossipsub topic carrying key certificates
+pub const KEY_CERTIFICATE_TOPIC: &str = "qudag/key-certificates/1";
+
+/// Upper bound on an encoded certificate (two ML-DSA keys and signatures plus framing)
+pub const MAX_CERTIFICATE_SIZE: usize = 16 * 1024;
+
+/// How often known certificates are re-published so that peers joining
+/// later learn them
+pub const CERTIFICATE_REPUBLISH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Errors that can occur while distributing key certificates
+#[derive(Debug, Error)]
+pub enum KeyCertificateGossipError {
+    #[error("Certificate encoding failed: {0}")]
+    Encoding(String),
+    #[error("Certificate of {0} bytes exceeds limit")]
+    TooLarge(usize),
+    #[error("Invalid certificate: {0}")]
+    Certificate(#[from] KeyCertificateError),
+    #[error("Gossip operation failed: {0}")]
+    Gossip(String),
+}
+
+/// Encode a certificate for the wire
+pub fn encode_certificate(
+    certificate: &KeyCertificate,
+) -> Result<Vec<u8>, KeyCertificateGossipError> {
+    bincode::serialize(certificate).map_err(|e| KeyCertificateGossipError::Encoding(e.to_string()))
+}
+
+/// Decode a certificate received from the wire
+pub fn decode_certificate(data: &[u8]) -> Result<KeyCertificate, KeyCertificateGossipError> {
+    if data.len() > MAX_CERTIFICATE_SIZE {
+        return Err(KeyCertificateGossipError::TooLarge(data.len()));
+    }
+    bincode::deserialize(data).map_err(|e| KeyCertificateGossipError::Encoding(e.to_string()))
+}
+
+/// Decode, verify and merge a received certificate into `list`
+///
+/// Returns the certificate if it was not already known.
+pub fn ingest_certificate(
+    list: &RevocationList,
+    data: &[u8],
+) -> Result<Option<KeyCertificate>, KeyCertificateGossipError> {
+    let certificate = decode_certificate(data)?;
+    if list.insert(certificate.clone())? {
+        Ok(Some(certificate))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Publishes local certificates and merges those received from peers
+#[derive(Clone)]
+pub struct KeyCertificateGossip {
+    list: RevocationList,
+    handle: P2PHandle,
+}
+
+impl KeyCertificateGossip {
+    /// Create a gossip service feeding `list`
+    pub fn new(list: RevocationList, handle: P2PHandle) -> Self {
+        Self { list, handle }
+    }
+
+    /// Revocation list fed by this service
+    pub fn revocation_list(&self) -> &RevocationList {
+        &self.list
+    }
+
+    /// Subscribe to the certificate topic
+    pub async fn subscribe(&self) -> Result<(), KeyCertificateGossipError> {
+        self.handle
+            .subscribe(KEY_CERTIFICATE_TOPIC)
+            .await
+            .map_err(|e| KeyCertificateGossipError::Gossip(e.to_string()))
+    }
+
+    /// Record a certificate locally and publish it
+    pub async fn announce(
+        &self,
+        certificate: KeyCertificate,
+    ) -> Result<(), KeyCertificateGossipError> {
+        let data = encode_certificate(&certificate)?;
+        self.list.insert(certificate)?;
+        self.handle
+            .publish(KEY_CERTIFICATE_TOPIC, data)
+            .await
+            .map_err(|e| KeyCertificateGossipError::Gossip(e.to_string()))
+    }
+
+    /// Re-publish every known certificate, e.g. after joining the network
+    pub async fn republish_all(&self) -> Result<usize, KeyCertificateGossipError> {
+        let certificates = self.list.certificates();
+        for certificate in &certificates {
+            self.handle
+                .publish(KEY_CERTIFICATE_TOPIC, encode_certificate(certificate)?)
+                .await
+                .map_err(|e| KeyCertificateGossipError::Gossip(e.to_string()))?;
+        }
+        Ok(certificates.len())
+    }
+
+    /// Handle a P2P event, merging certificates received on the topic
+    ///
+    /// Returns a newly learned certificate so callers can persist it.
+    pub fn handle_event(&self, event: &P2PEvent) -> Option<KeyCertificate> {
+        let P2PEvent::MessageReceived {
+            peer_id,
+            topic,
+            data,
+        } = event
+        else {
+            return None;
+        };
+        if topic != KEY_CERTIFICATE_TOPIC {
+            return None;
+        }
+
+        match ingest_certificate(&self.list, data) {
+            Ok(Some(certificate)) => {
+                debug!("Learned key certificate from {}", peer_id);
+                Some(certificate)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Rejected key certificate from {}: {}", peer_id, e);
+                None
+            }
+        }
+    }
+}
@@ -5,22 +5,41 @@
 //! This module provides the networking layer for the QuDAG protocol,
 //! implementing anonymous routing, P2P communication, and traffic obfuscation.
 
+pub mod address_book;
 pub mod circuit_breaker;
+pub mod circuit_relay;
 pub mod connection;
 pub mod connection_pool;
+pub mod cover_traffic;
 pub mod dark_resolver;
 pub mod discovery;
+mod disk_writer;
 pub mod dns;
+pub mod dns_seed;
+pub mod gossip_topics;
+pub mod hidden_service;
 pub mod kademlia;
+pub mod key_certificates;
 pub mod message;
 pub mod metrics;
+pub mod metrics_exporter;
 pub mod nat_traversal;
+pub mod node_identity;
 pub mod onion;
-// Optimization features disabled for initial release
-// pub mod optimized;
+pub mod onion_relay;
+pub mod optimized;
 pub mod p2p;
 pub mod peer;
+pub mod peer_scoring;
+pub mod pluggable_transport;
+pub mod pooled_transport;
+pub mod port_mapping;
+pub mod pq_noise;
+pub mod pq_stealth;
 pub mod quantum_crypto;
+pub mod reachability;
+pub mod record_store;
+pub mod resource_manager;
 pub mod router;
 pub mod routing;
 pub mod shadow_address;
@@ -28,68 +47,125 @@ pub mod traffic_obfuscation;
 pub mod transport;
 pub mod types;
 
-pub use dark_resolver::{DarkDomainRecord, DarkResolver, DarkResolverError};
+pub use address_book::{AddressBook, AddressInfo, TransportKind, HAPPY_EYEBALLS_DELAY};
+pub use circuit_relay::{RelayConfig, RESERVATION_RETRY_INTERVAL};
+pub use cover_traffic::{CoverLink, CoverMode, CoverScheduler, CoverStats, CoverTrafficConfig};
+pub use dark_resolver::{
+    DarkDomainRecord, DarkRecordValidator, DarkResolver, DarkResolverError, IntroductionPoint,
+};
 pub use discovery::{
     DiscoveredPeer, DiscoveryConfig, DiscoveryEvent, DiscoveryMethod, DiscoveryStats,
     KademliaPeerDiscovery,
 };
-pub use dns::{CloudflareClient, CloudflareConfig, DnsError, DnsManager, DnsRecord, RecordType};
+pub use dns::{
+    CloudflareClient, CloudflareConfig, DnsError, DnsManager, DnsProvider, DnsRecord, RecordType,
+};
+pub use dns_seed::{
+    publish_seed_list, DnsSeedConfig, DnsSeedError, DnsSeedResolver, SeedList, SeedPeer,
+};
+pub use gossip_topics::{
+    DarkRecordMessage, ExchangeTxMessage, GossipMessage, GossipTopic, GossipTopicError,
+    GossipValidator, GossipValidators, PeerAnnouncement, Validation, Verdict, VertexMessage,
+    VoteMessage,
+};
+pub use hidden_service::{
+    HiddenServiceConfig, HiddenServiceError, HiddenServiceEvent, HiddenServiceKeys, HiddenServices,
+};
 pub use kademlia::{BootstrapConfig, ContentRoutingConfig, KademliaDHT, PeerReputation};
+pub use key_certificates::{
+    KeyCertificateGossip, KeyCertificateGossipError, CERTIFICATE_REPUBLISH_INTERVAL,
+    KEY_CERTIFICATE_TOPIC,
+};
 pub use message::MessageEnvelope;
+pub use metrics_exporter::{MetricsError, MetricsExporter, METRICS_PATH};
 pub use nat_traversal::{
     ConnectionType, ConnectionUpgradeManager, HolePunchCoordinator, HolePunchPhase, NatInfo,
-    NatPmpClient, NatTraversalConfig, NatTraversalError, NatTraversalManager, NatTraversalStats,
-    NatType, PortMapping, PortMappingMethod, PortMappingProtocol, RelayConnection, RelayManager,
-    RelayServer, StunClient, StunServer, TurnClient, TurnServer, UpgradeAttempt,
+    NatPmpClient, NatPmpMapping, NatTraversalConfig, NatTraversalError, NatTraversalManager,
+    NatTraversalStats, NatType, PortMapping, PortMappingMethod, PortMappingProtocol,
+    RelayConnection, RelayManager, RelayServer, StunClient, StunServer, TurnClient, TurnServer,
+    UpgradeAttempt, UpnpManager, UpnpMapping,
 };
+pub use node_identity::{IdentityCertificate, NodeIdentity, NodeIdentityError};
 pub use onion::{
     Circuit, CircuitManager, CircuitState, CircuitStats, DirectoryClient, HopMetadata, LayerFlags,
     MLKEMOnionRouter, MetadataConfig, MetadataProtector, MixConfig, MixMessage, MixMessageType,
     MixNode, MixNodeStats, NodeFlags, NodeInfo, OnionError, OnionLayer, OnionRouter,
     ProtectedMetadata, TrafficAnalysisConfig, TrafficAnalysisResistance,
 };
+pub use onion_relay::{
+    ClientCircuit, OnionCell, OnionRelay, OnionRelayConfig, OnionRelayError, OnionReply,
+    RelayAction, RelayCommand, RelayDescriptor, RelayResponse, ONION_PROTOCOL,
+    RELAY_DIRECTORY_TOPIC,
+};
 pub use p2p::{
-    NetworkConfig as P2PNetworkConfig, P2PCommand, P2PEvent, P2PHandle, P2PNode, QuDagRequest,
-    QuDagResponse,
+    default_obfuscation_key, NetworkConfig as P2PNetworkConfig, P2PCommand, P2PEvent, P2PHandle,
+    P2PNode, QuDagRequest, QuDagResponse,
+};
+pub use peer_scoring::{
+    Ban, PeerEvent, PeerGate, PeerScore, PeerScoring, PeerScoringConfig, PeerScoringError,
+    PeerScoringEvent,
 };
+pub use pluggable_transport::{
+    DisguisedStream, ObfuscatedTransport, PluggableTransport, PluggableTransportConfig,
+    PluggableTransportError,
+};
+pub use port_mapping::{IgdGateway, PmpGateway, PmpGrant, PmpVersion, PortMappingError};
+pub use pq_noise::{PqNoiseConfig, PqNoiseError, PqNoiseOutput, SecurityProtocol};
+pub use pq_stealth::{PqStealthKeys, ScanStats, SpendAuthorization, StealthMatch, StealthScanner};
 pub use quantum_crypto::{
     MlKemCiphertext, MlKemPublicKey, MlKemSecretKey, MlKemSecurityLevel, QuantumKeyExchange,
     SharedSecret,
 };
+pub use reachability::{AutoNatConfig, Reachability, ReachabilityState};
+pub use record_store::{
+    PersistentRecordStore, RecordStoreConfig, RecordStoreError, RecordValidator,
+};
+pub use resource_manager::{
+    ProtocolLimits, RateLimit, ResourceConfig, ResourceError, ResourceLimiter, ResourceManager,
+    ResourceProtocol, ResourceStats, StreamPermit,
+};
 pub use router::{HopInfo, Router};
 pub use shadow_address::{
     DefaultShadowAddressHandler, NetworkType, RotationPolicies, ShadowAddress, ShadowAddressError,
     ShadowAddressGenerator, ShadowAddressManager, ShadowAddressMixer, ShadowAddressPool,
-    ShadowAddressResolver, ShadowFeatures, ShadowMetadata,
+    ShadowAddressResolver, ShadowFeatures, ShadowMetadata, SHADOW_VERSION_PLAIN,
+    SHADOW_VERSION_STEALTH_ML_KEM, SHADOW_VERSION_STEALTH_X25519,
 };
 pub use traffic_obfuscation::{
     ObfuscationPattern, ObfuscationStats, TrafficObfuscationConfig, TrafficObfuscator,
     DEFAULT_MESSAGE_SIZE, STANDARD_MESSAGE_SIZES,
 };
-pub use transport::{AsyncTransport, Transport, TransportConfig, TransportError};
+pub use transport::{TransportConfig, TransportError};
 pub use types::{
     ConnectionStatus, LatencyMetrics, MessagePriority, NetworkAddress, NetworkError,
     NetworkMessage, PeerId, QueueMetrics, RoutingStrategy, ThroughputMetrics,
 };
 
-use libp2p::PeerId as LibP2PPeerId;
+use futures::channel::oneshot;
+use libp2p::{Multiaddr, PeerId as LibP2PPeerId};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
-/// Comprehensive network manager for P2P operations
+/// Network manager for P2P operations
+///
+/// A façade over a [`P2PNode`]: connections, messages and peer metadata all
+/// go through the node, its layered transport and its peer scoring.
 pub struct NetworkManager {
     /// Local peer ID
     local_peer_id: Option<LibP2PPeerId>,
     /// Connected peers
     connected_peers: Arc<RwLock<HashMap<LibP2PPeerId, PeerMetadata>>>,
-    /// Message channel for inter-component communication
-    message_tx: Option<mpsc::Sender<NetworkEvent>>,
+    /// Network events translated from the node's events
+    event_rx: Option<Arc<Mutex<mpsc::Receiver<NetworkEvent>>>>,
     /// Network configuration
     config: NetworkConfig,
-    /// Connection manager instance
-    connection_manager: Option<Arc<ConnectionManager>>,
+    /// Handle of the running node
+    node: Option<P2PHandle>,
+    /// Tasks running the node and translating its events
+    tasks: Vec<JoinHandle<()>>,
     /// Peer discovery service
     discovery_service: Option<Arc<dyn PeerDiscoveryService>>,
     /// Reputation manager
@@ -117,6 +193,11 @@ pub struct NetworkConfig {
     pub enable_nat_traversal: bool,
     /// NAT traversal configuration
     pub nat_traversal_config: Option<NatTraversalConfig>,
+    /// Configuration of the underlying P2P node
+    ///
+    /// The connection limit, timeout and bootstrap peers above override
+    /// its own, and `quantum_resistant: false` selects classical Noise.
+    pub p2p: P2PNetworkConfig,
 }
 
 impl Default for NetworkConfig {
@@ -130,6 +211,7 @@ impl Default for NetworkConfig {
             quantum_resistant: true,
             enable_nat_traversal: true,
             nat_traversal_config: None,
+            p2p: P2PNetworkConfig::default(),
         }
     }
 }
@@ -151,6 +233,20 @@ pub struct PeerMetadata {
     pub latency_ms: u64,
 }
 
+impl PeerMetadata {
+    fn new(address: String) -> Self {
+        let now = std::time::Instant::now();
+        Self {
+            address,
+            connected_at: now,
+            last_activity: now,
+            reputation: 0.0,
+            protocol_version: 1,
+            latency_ms: 0,
+        }
+    }
+}
+
 /// Network events for inter-component communication
 #[derive(Debug, Clone)]
 pub enum NetworkEvent {
@@ -160,12 +256,61 @@ pub enum NetworkEvent {
     PeerDisconnected(LibP2PPeerId),
     /// Message received
     MessageReceived { from: LibP2PPeerId, data: Vec<u8> },
+    /// Request received, answered through `reply`
+    RequestReceived {
+        from: LibP2PPeerId,
+        data: Vec<u8>,
+        reply: RequestReply,
+    },
     /// Discovery update
     DiscoveryUpdate(Vec<LibP2PPeerId>),
     /// Network error
     NetworkError(String),
 }
 
+/// Reply channel of a request forwarded as [`NetworkEvent::RequestReceived`]
+///
+/// Only the first reply is sent. The requester receives an empty response if
+/// every clone is dropped without replying.
+#[derive(Clone)]
+pub struct RequestReply {
+    request_id: String,
+    channel: Arc<parking_lot::Mutex<Option<oneshot::Sender<QuDagResponse>>>>,
+}
+
+impl RequestReply {
+    fn new(request_id: String, channel: oneshot::Sender<QuDagResponse>) -> Self {
+        Self {
+            request_id,
+            channel: Arc::new(parking_lot::Mutex::new(Some(channel))),
+        }
+    }
+
+    /// Answer the request with `payload`
+    pub fn send(&self, payload: Vec<u8>) -> Result<(), NetworkError> {
+        let Some(channel) = self.channel.lock().take() else {
+            return Err(NetworkError::MessageError(
+                "Request already answered".to_string(),
+            ));
+        };
+        channel
+            .send(QuDagResponse {
+                request_id: self.request_id.clone(),
+                payload,
+            })
+            .map_err(|_| NetworkError::MessageError("P2P node offline".to_string()))
+    }
+}
+
+impl std::fmt::Debug for RequestReply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestReply")
+            .field("request_id", &self.request_id)
+            .field("answered", &self.channel.lock().is_none())
+            .finish()
+    }
+}
+
 /// Trait for peer discovery services
 pub trait PeerDiscoveryService: Send + Sync {
     /// Start discovery service
@@ -179,72 +324,83 @@ pub trait PeerDiscoveryService: Send + Sync {
 }
 
 /// Reputation management for peers
-#[derive(Debug)]
+///
+/// A view of a [`PeerScoring`] book in terms of reputation, blacklist and
+/// trusted peers. Blacklisting is a time-limited ban and trusted peers are
+/// allowlisted.
+#[derive(Debug, Clone, Default)]
 pub struct ReputationManager {
-    /// Peer reputation scores
-    scores: HashMap<LibP2PPeerId, f64>,
-    /// Blacklisted peers
-    blacklist: HashMap<LibP2PPeerId, std::time::Instant>,
-    /// Trusted peers
-    trusted: HashMap<LibP2PPeerId, std::time::Instant>,
+    /// Scores, bans and allowlist shared with the rest of the node
+    scoring: PeerScoring,
 }
 
-impl Default for ReputationManager {
-    fn default() -> Self {
-        Self {
-            scores: HashMap::new(),
-            blacklist: HashMap::new(),
-            trusted: HashMap::new(),
-        }
+impl ReputationManager {
+    /// Create a reputation manager over existing peer scoring
+    pub fn with_scoring(scoring: PeerScoring) -> Self {
+        Self { scoring }
+    }
+
+    /// The underlying peer scoring
+    pub fn scoring(&self) -> &PeerScoring {
+        &self.scoring
     }
-}
 
-impl ReputationManager {
     /// Get reputation score for a peer
     pub fn get_reputation(&self, peer_id: &LibP2PPeerId) -> f64 {
-        self.scores.get(peer_id).copied().unwrap_or(0.0)
+        self.scoring.score(peer_id).local
     }
 
     /// Update reputation score
     pub fn update_reputation(&mut self, peer_id: LibP2PPeerId, delta: f64) {
-        let current = self.scores.get(&peer_id).copied().unwrap_or(0.0);
-        let new_score = (current + delta).clamp(-100.0, 100.0);
-        self.scores.insert(peer_id, new_score);
-
-        // Auto-blacklist peers with very low reputation
-        if new_score < -50.0 {
-            self.blacklist.insert(peer_id, std::time::Instant::now());
+        // Peers with very low reputation are banned by the scoring
+        if let Some(ban) = self.scoring.adjust(peer_id, delta) {
             warn!(
                 "Auto-blacklisted peer {:?} due to low reputation: {}",
-                peer_id, new_score
+                peer_id, ban.reason
             );
         }
     }
 
     /// Check if peer is blacklisted
     pub fn is_blacklisted(&self, peer_id: &LibP2PPeerId) -> bool {
-        self.blacklist.contains_key(peer_id)
+        self.scoring.is_banned(peer_id)
     }
 
     /// Add peer to trusted list
     pub fn add_trusted(&mut self, peer_id: LibP2PPeerId) {
-        self.trusted.insert(peer_id, std::time::Instant::now());
+        self.scoring.allow(peer_id);
         // Set high reputation for trusted peers
-        self.scores.insert(peer_id, 75.0);
+        let current = self.get_reputation(&peer_id);
+        self.scoring.adjust(peer_id, 75.0 - current);
     }
 
     /// Check if peer is trusted
     pub fn is_trusted(&self, peer_id: &LibP2PPeerId) -> bool {
-        self.trusted.contains_key(peer_id)
+        self.scoring.is_allowlisted(peer_id)
     }
 
-    /// Remove expired blacklist entries (24 hours)
+    /// Decay reputations and remove expired blacklist entries
     pub fn cleanup_expired(&mut self) {
-        let now = std::time::Instant::now();
-        let expire_time = std::time::Duration::from_secs(24 * 60 * 60);
+        self.scoring.maintain();
+    }
+
+    /// Average reputation of scored peers
+    pub fn average_reputation(&self) -> f64 {
+        let scores = self.scoring.scores();
+        if scores.is_empty() {
+            return 0.0;
+        }
+        scores.iter().map(|(_, score)| score.local).sum::<f64>() / scores.len() as f64
+    }
 
-        self.blacklist
-            .retain(|_, timestamp| now.duration_since(*timestamp) < expire_time);
+    /// Number of blacklisted peers
+    pub fn blacklisted_count(&self) -> usize {
+        self.scoring.bans().len()
+    }
+
+    /// Number of trusted peers
+    pub fn trusted_count(&self) -> usize {
+        self.scoring.allowlist().len()
     }
 }
 
@@ -265,34 +421,51 @@ impl NetworkManager {
         Self {
             local_peer_id: None,
             connected_peers: Arc::new(RwLock::new(HashMap::new())),
-            message_tx: None,
+            event_rx: None,
             config,
-            connection_manager: None,
+            node: None,
+            tasks: Vec::new(),
             discovery_service: None,
             reputation_manager: Arc::new(RwLock::new(ReputationManager::default())),
             nat_traversal_manager: None,
         }
     }
 
-    /// Initialize the network manager
+    /// Start the P2P node and initialize the network manager
     pub async fn initialize(&mut self) -> Result<(), NetworkError> {
-        // Generate or load peer identity
-        self.local_peer_id = Some(LibP2PPeerId::random());
+        let mut p2p_config = self.config.p2p.clone();
+        p2p_config.max_connections = self.config.max_connections;
+        p2p_config.timeout = self.config.connection_timeout;
+        p2p_config
+            .bootstrap_peers
+            .extend(self.config.bootstrap_peers.iter().cloned());
+        if !self.config.quantum_resistant {
+            p2p_config.security = SecurityProtocol::Noise;
+        }
 
-        // Set up message channel
-        let (tx, mut rx) = mpsc::channel(1024);
-        self.message_tx = Some(tx);
+        let (mut node, handle) = P2PNode::new(p2p_config)
+            .await
+            .map_err(|e| NetworkError::Internal(format!("P2P setup failed: {}", e)))?;
+        node.start()
+            .await
+            .map_err(|e| NetworkError::Internal(format!("P2P start failed: {}", e)))?;
+        self.tasks.push(tokio::spawn(async move {
+            if let Err(e) = node.run().await {
+                error!("P2P node stopped: {}", e);
+            }
+        }));
+        self.local_peer_id = Some(handle.local_peer_id().await);
 
-        // Initialize connection manager
-        let connection_manager = Arc::new(ConnectionManager::new(self.config.max_connections));
-        self.connection_manager = Some(connection_manager.clone());
+        // Reputation is the node's peer scoring
+        *self.reputation_manager.write().await =
+            ReputationManager::with_scoring(handle.peer_scoring().clone());
 
         // Initialize NAT traversal if enabled
         if self.config.enable_nat_traversal {
             let nat_config = self.config.nat_traversal_config.clone().unwrap_or_default();
             let nat_manager = Arc::new(NatTraversalManager::new(
                 nat_config,
-                connection_manager.clone(),
+                handle.connection_manager().clone(),
             ));
 
             if let Err(e) = nat_manager.initialize().await {
@@ -300,19 +473,29 @@ impl NetworkManager {
             } else {
                 info!("NAT traversal initialized successfully");
             }
+            // STUN results join AutoNAT's in the node's reachability
+            if let Some(nat_info) = nat_manager.get_nat_info() {
+                handle.report_nat_info(nat_info);
+            }
 
             self.nat_traversal_manager = Some(nat_manager);
         }
 
-        // Start background event processing
-        let connected_peers = Arc::clone(&self.connected_peers);
-        let reputation_manager = Arc::clone(&self.reputation_manager);
-
-        tokio::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                Self::handle_network_event(event, &connected_peers, &reputation_manager).await;
-            }
-        });
+        // Translate node events in the background. The manager is the only
+        // reader of the node's events.
+        let node_events = handle
+            .take_events()
+            .await
+            .ok_or_else(|| NetworkError::Internal("Node events already taken".to_string()))?;
+        let (tx, rx) = mpsc::channel(1024);
+        self.event_rx = Some(Arc::new(Mutex::new(rx)));
+        self.tasks.push(tokio::spawn(Self::forward_events(
+            node_events,
+            Arc::clone(&self.connected_peers),
+            Arc::clone(&self.reputation_manager),
+            tx,
+        )));
+        self.node = Some(handle);
 
         info!(
             "NetworkManager initialized with peer ID: {:?}",
@@ -321,94 +504,130 @@ impl NetworkManager {
         Ok(())
     }
 
-    /// Handle network events in background task
-    async fn handle_network_event(
-        event: NetworkEvent,
-        connected_peers: &Arc<RwLock<HashMap<LibP2PPeerId, PeerMetadata>>>,
-        reputation_manager: &Arc<RwLock<ReputationManager>>,
+    /// Track peers and translate node events into [`NetworkEvent`]s
+    ///
+    /// Waits while the event queue is full rather than dropping events.
+    async fn forward_events(
+        mut node_events: mpsc::Receiver<P2PEvent>,
+        connected_peers: Arc<RwLock<HashMap<LibP2PPeerId, PeerMetadata>>>,
+        reputation_manager: Arc<RwLock<ReputationManager>>,
+        events: mpsc::Sender<NetworkEvent>,
     ) {
-        match event {
-            NetworkEvent::PeerConnected(peer_id) => {
-                debug!("Handling peer connection: {:?}", peer_id);
-                let metadata = PeerMetadata {
-                    address: "unknown".to_string(),
-                    connected_at: std::time::Instant::now(),
-                    last_activity: std::time::Instant::now(),
-                    reputation: 0.0,
-                    protocol_version: 1,
-                    latency_ms: 0,
-                };
-                connected_peers.write().await.insert(peer_id, metadata);
-            }
-            NetworkEvent::PeerDisconnected(peer_id) => {
-                debug!("Handling peer disconnection: {:?}", peer_id);
-                connected_peers.write().await.remove(&peer_id);
-            }
-            NetworkEvent::MessageReceived { from, data: _ } => {
-                // Update last activity and reputation
-                if let Some(metadata) = connected_peers.write().await.get_mut(&from) {
-                    metadata.last_activity = std::time::Instant::now();
+        while let Some(event) = node_events.recv().await {
+            let event = match event {
+                P2PEvent::PeerConnected(peer_id) => {
+                    debug!("Handling peer connection: {:?}", peer_id);
+                    connected_peers
+                        .write()
+                        .await
+                        .entry(peer_id)
+                        .or_insert_with(|| PeerMetadata::new("unknown".to_string()));
+                    NetworkEvent::PeerConnected(peer_id)
                 }
-                reputation_manager
-                    .write()
-                    .await
-                    .update_reputation(from, 0.1);
-            }
-            NetworkEvent::NetworkError(error) => {
-                error!("Network error: {}", error);
-            }
-            NetworkEvent::DiscoveryUpdate(peers) => {
-                debug!("Discovery update: {} new peers found", peers.len());
+                P2PEvent::PeerDisconnected(peer_id) => {
+                    debug!("Handling peer disconnection: {:?}", peer_id);
+                    connected_peers.write().await.remove(&peer_id);
+                    NetworkEvent::PeerDisconnected(peer_id)
+                }
+                P2PEvent::RequestReceived {
+                    peer_id,
+                    request,
+                    channel,
+                } => {
+                    Self::record_activity(&connected_peers, &reputation_manager, peer_id).await;
+                    NetworkEvent::RequestReceived {
+                        from: peer_id,
+                        data: request.payload,
+                        reply: RequestReply::new(request.request_id, channel),
+                    }
+                }
+                P2PEvent::MessageReceived { peer_id, data, .. } => {
+                    Self::record_activity(&connected_peers, &reputation_manager, peer_id).await;
+                    NetworkEvent::MessageReceived {
+                        from: peer_id,
+                        data,
+                    }
+                }
+                P2PEvent::PeerDiscovered(peer_id) => NetworkEvent::DiscoveryUpdate(vec![peer_id]),
+                _ => continue,
+            };
+            if events.send(event).await.is_err() {
+                debug!("Network events no longer read, stopping event translation");
+                break;
             }
         }
     }
 
-    /// Connect to a peer
-    pub async fn connect_peer(&self, _peer_address: &str) -> Result<LibP2PPeerId, NetworkError> {
-        let peer_id = LibP2PPeerId::random(); // In real implementation, derive from address
-
-        // Check if peer is blacklisted
-        if self
-            .reputation_manager
-            .read()
-            .await
-            .is_blacklisted(&peer_id)
-        {
-            return Err(NetworkError::ConnectionError(
-                "Peer is blacklisted".to_string(),
-            ));
+    /// Update last activity and reputation of a peer that sent a message
+    async fn record_activity(
+        connected_peers: &RwLock<HashMap<LibP2PPeerId, PeerMetadata>>,
+        reputation_manager: &RwLock<ReputationManager>,
+        peer_id: LibP2PPeerId,
+    ) {
+        if let Some(metadata) = connected_peers.write().await.get_mut(&peer_id) {
+            metadata.last_activity = std::time::Instant::now();
         }
+        reputation_manager
+            .write()
+            .await
+            .update_reputation(peer_id, 0.1);
+    }
 
-        // Convert LibP2PPeerId to our PeerId type for connection manager
-        let peer_bytes = peer_id.to_bytes();
-        let mut bytes_array = [0u8; 32];
-        let len = peer_bytes.len().min(32);
-        bytes_array[..len].copy_from_slice(&peer_bytes[..len]);
-        let our_peer_id = crate::types::PeerId::from_bytes(bytes_array);
+    /// Handle of the running node
+    ///
+    /// The manager owns the node's event stream: read events through
+    /// [`NetworkManager::next_event`], not the handle.
+    pub fn node(&self) -> Option<&P2PHandle> {
+        self.node.as_ref()
+    }
 
-        // Try NAT traversal if available
-        if let Some(nat_manager) = &self.nat_traversal_manager {
-            match nat_manager.connect_peer(our_peer_id).await {
-                Ok(()) => {
-                    info!("Connected to peer {:?} via NAT traversal", peer_id);
-                }
-                Err(e) => {
-                    warn!("NAT traversal failed for peer {:?}: {}", peer_id, e);
-                    // Fall back to direct connection
-                    if let Some(conn_mgr) = &self.connection_manager {
-                        conn_mgr.connect(our_peer_id).await?;
-                    }
-                }
+    fn running_node(&self) -> Result<&P2PHandle, NetworkError> {
+        self.node
+            .as_ref()
+            .ok_or_else(|| NetworkError::Internal("Network manager not initialized".to_string()))
+    }
+
+    /// Next network event, or `None` once the node stopped
+    pub async fn next_event(&self) -> Option<NetworkEvent> {
+        self.event_rx.as_ref()?.lock().await.recv().await
+    }
+
+    /// Connect to a peer
+    ///
+    /// `peer_address` is a multiaddr, optionally ending in `/p2p/<peer>`, or
+    /// `host:port` for TCP. Resolves to the peer that answered once the
+    /// connection is up.
+    pub async fn connect_peer(&self, peer_address: &str) -> Result<LibP2PPeerId, NetworkError> {
+        let node = self.running_node()?;
+        let addr = parse_peer_address(peer_address)?;
+
+        // Check if peer is blacklisted
+        if let Some(peer_id) = addr.iter().find_map(|protocol| match protocol {
+            libp2p::multiaddr::Protocol::P2p(peer_id) => Some(peer_id),
+            _ => None,
+        }) {
+            if self
+                .reputation_manager
+                .read()
+                .await
+                .is_blacklisted(&peer_id)
+            {
+                return Err(NetworkError::ConnectionError(
+                    "Peer is blacklisted".to_string(),
+                ));
             }
-        } else if let Some(conn_mgr) = &self.connection_manager {
-            // Use regular connection manager
-            conn_mgr.connect(our_peer_id).await?;
         }
 
-        // Notify about new connection
-        if let Some(tx) = &self.message_tx {
-            let _ = tx.send(NetworkEvent::PeerConnected(peer_id)).await;
-        }
+        let peer_id = node
+            .connect(addr)
+            .await
+            .map_err(|e| NetworkError::ConnectionError(e.to_string()))?;
+        self.connected_peers
+            .write()
+            .await
+            .entry(peer_id)
+            .or_insert_with(|| PeerMetadata::new(peer_address.to_string()))
+            .address = peer_address.to_string();
 
         info!("Successfully connected to peer: {:?}", peer_id);
         Ok(peer_id)
@@ -416,78 +635,98 @@ impl NetworkManager {
 
     /// Disconnect from a peer
     pub async fn disconnect_peer(&self, peer_id: &LibP2PPeerId) -> Result<(), NetworkError> {
-        // Use connection manager to close connection
-        if let Some(conn_mgr) = &self.connection_manager {
-            let peer_bytes = peer_id.to_bytes();
-            let mut bytes_array = [0u8; 32];
-            let len = peer_bytes.len().min(32);
-            bytes_array[..len].copy_from_slice(&peer_bytes[..len]);
-            let our_peer_id = crate::types::PeerId::from_bytes(bytes_array);
-            conn_mgr.disconnect(&our_peer_id);
-        }
-
-        // Notify about disconnection
-        if let Some(tx) = &self.message_tx {
-            let _ = tx.send(NetworkEvent::PeerDisconnected(*peer_id)).await;
+        if let Some(node) = &self.node {
+            if let Err(e) = node.disconnect(*peer_id).await {
+                debug!("Nothing to disconnect from {:?}: {}", peer_id, e);
+            }
         }
+        self.connected_peers.write().await.remove(peer_id);
 
         info!("Disconnected from peer: {:?}", peer_id);
         Ok(())
     }
 
     /// Send message to a peer
+    ///
+    /// Resolves once the peer answered the message.
     pub async fn send_message(
         &self,
         peer_id: &LibP2PPeerId,
         data: Vec<u8>,
     ) -> Result<(), NetworkError> {
-        // Check if peer is connected
-        if !self.connected_peers.read().await.contains_key(peer_id) {
+        self.send_request(peer_id, data).await.map(|_| ())
+    }
+
+    /// Send a request to a peer and wait for its reply
+    ///
+    /// The peer's application answers through the [`RequestReply`] of its
+    /// [`NetworkEvent::RequestReceived`]; the reply is empty if it didn't.
+    pub async fn send_request(
+        &self,
+        peer_id: &LibP2PPeerId,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, NetworkError> {
+        let node = self.running_node()?;
+        if !node.connected_peers().await.contains(peer_id) {
             return Err(NetworkError::ConnectionError(
                 "Peer not connected".to_string(),
             ));
         }
 
-        // In real implementation, this would use the actual transport layer
         debug!("Sending {} bytes to peer {:?}", data.len(), peer_id);
+        let request = QuDagRequest {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            payload: data,
+        };
+        let response = node
+            .send_request(*peer_id, request)
+            .await
+            .map_err(|e| NetworkError::MessageError(e.to_string()))?;
 
         // Update peer activity
         if let Some(metadata) = self.connected_peers.write().await.get_mut(peer_id) {
             metadata.last_activity = std::time::Instant::now();
         }
 
-        Ok(())
+        Ok(response.payload)
     }
 
     /// Get list of connected peers
     pub async fn get_connected_peers(&self) -> Vec<LibP2PPeerId> {
-        self.connected_peers.read().await.keys().cloned().collect()
+        match &self.node {
+            Some(node) => node.connected_peers().await,
+            None => Vec::new(),
+        }
     }
 
     /// Get peer metadata
+    ///
+    /// Reputation and latency are read from the node's peer scoring and
+    /// address book.
     pub async fn get_peer_metadata(&self, peer_id: &LibP2PPeerId) -> Option<PeerMetadata> {
-        self.connected_peers.read().await.get(peer_id).cloned()
+        let mut metadata = self.connected_peers.read().await.get(peer_id).cloned()?;
+        metadata.reputation = self.reputation_manager.read().await.get_reputation(peer_id);
+        if let Some(rtt) = self.node.as_ref().and_then(|node| {
+            node.address_book()
+                .addresses(peer_id)
+                .into_iter()
+                .find_map(|info| info.rtt)
+        }) {
+            metadata.latency_ms = rtt.as_millis() as u64;
+        }
+        Some(metadata)
     }
 
     /// Get network statistics
     pub async fn get_network_stats(&self) -> NetworkStats {
-        let connected_count = self.connected_peers.read().await.len();
-        let reputation_scores: Vec<f64> = {
-            let rep_mgr = self.reputation_manager.read().await;
-            rep_mgr.scores.values().cloned().collect()
-        };
-
-        let avg_reputation = if reputation_scores.is_empty() {
-            0.0
-        } else {
-            reputation_scores.iter().sum::<f64>() / reputation_scores.len() as f64
-        };
+        let connected_count = self.get_connected_peers().await.len();
+        let rep_mgr = self.reputation_manager.read().await;
 
         NetworkStats {
             connected_peers: connected_count,
-            average_reputation: avg_reputation,
-            blacklisted_peers: self.reputation_manager.read().await.blacklist.len(),
-            trusted_peers: self.reputation_manager.read().await.trusted.len(),
+            average_reputation: rep_mgr.average_reputation(),
+            blacklisted_peers: rep_mgr.blacklisted_count(),
+            trusted_peers: rep_mgr.trusted_count(),
         }
     }
 
@@ -517,7 +756,7 @@ impl NetworkManager {
         Ok(())
     }
 
-    /// Stop the network manager
+    /// Stop the network manager and its node
     pub async fn shutdown(&mut self) -> Result<(), NetworkError> {
         info!("Shutting down NetworkManager");
 
@@ -539,6 +778,13 @@ impl NetworkManager {
             }
         }
 
+        // Stop the node
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        self.node = None;
+        self.connected_peers.write().await.clear();
+
         Ok(())
     }
 
@@ -552,6 +798,11 @@ impl NetworkManager {
         // Cleanup expired blacklist entries
         self.reputation_manager.write().await.cleanup_expired();
 
+        // Pass the latest STUN results on to the node
+        if let (Some(node), Some(nat_info)) = (&self.node, self.get_nat_info()) {
+            node.report_nat_info(nat_info);
+        }
+
         // Remove inactive peers (older than 5 minutes with no activity)
         let now = std::time::Instant::now();
         let timeout = std::time::Duration::from_secs(300);
@@ -572,6 +823,14 @@ impl NetworkManager {
         }
     }
 
+    /// Get whether peers can dial the node directly, from AutoNAT and STUN
+    pub async fn reachability(&self) -> Reachability {
+        match &self.node {
+            Some(node) => node.reachability().await,
+            None => Reachability::Unknown,
+        }
+    }
+
     /// Get NAT information
     pub fn get_nat_info(&self) -> Option<NatInfo> {
         self.nat_traversal_manager.as_ref()?.get_nat_info()
@@ -602,6 +861,24 @@ impl NetworkManager {
     }
 }
 
+/// Parse a multiaddr or a TCP `host:port` address
+fn parse_peer_address(address: &str) -> Result<Multiaddr, NetworkError> {
+    use libp2p::multiaddr::Protocol;
+
+    if let Ok(addr) = address.parse::<Multiaddr>() {
+        return Ok(addr);
+    }
+    if let Ok(socket_addr) = address.parse::<std::net::SocketAddr>() {
+        return Ok(Multiaddr::from(socket_addr.ip()).with(Protocol::Tcp(socket_addr.port())));
+    }
+    let invalid = || NetworkError::ConnectionError(format!("Invalid peer address: {}", address));
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    let port = port.parse().map_err(|_| invalid())?;
+    Ok(Multiaddr::empty()
+        .with(Protocol::Dns(host.to_string().into()))
+        .with(Protocol::Tcp(port)))
+}
+
 /// Network statistics
 #[derive(Debug, Clone)]
 pub struct NetworkStats {
@@ -0,0 +1,532 @@
+//! Prometheus / OpenMetrics export of node metrics.
+//!
+//! A scrape of [`METRICS_PATH`] combines two sources into one OpenMetrics
+//! document:
+//!
+//! * libp2p swarm and protocol metrics, prefixed `libp2p_`. A
+//!   [`crate::p2p::P2PNode`] records them into the registry of the
+//!   [`MetricsExporter`] given in its configuration.
+//! * Everything the network, DAG, consensus and crypto crates report through
+//!   the `metrics` facade, prefixed `qudag_`. The first exporter installs a
+//!   process-wide recorder that keeps these values for every exporter.
+//!
+//! The `qudag_` metric names are stable. [`METRIC_DESCRIPTIONS`] lists them
+//! with their help text. Counters keep their `_total` suffix, and histograms
+//! share the [`LATENCY_BUCKETS`] in seconds.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use libp2p::metrics::{Metrics as Libp2pMetrics, Registry};
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, SharedString, Unit,
+};
+use parking_lot::{Mutex, RwLock};
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{DescriptorEncoder, MetricEncoder};
+use prometheus_client::metrics::exemplar::Exemplar;
+use prometheus_client::metrics::MetricType;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+/// Path the metrics are served on
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Content type of an OpenMetrics scrape
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Upper bounds, in seconds, of the buckets of every `qudag_` histogram
+pub const LATENCY_BUCKETS: [f64; 14] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+];
+
+/// Stable `qudag_` metrics and their help text
+pub const METRIC_DESCRIPTIONS: &[(&str, &str)] = &[
+    // Network
+    ("qudag_connected_peers", "Peers with an open connection"),
+    (
+        "qudag_gossip_validation_total",
+        "Gossip messages by validation verdict",
+    ),
+    ("qudag_peer_bans", "Peers currently banned"),
+    ("qudag_peer_bans_total", "Bans imposed on peers"),
+    ("qudag_peer_penalties_total", "Penalties reported for peers"),
+    (
+        "qudag_resource_throttled_total",
+        "Messages dropped over a resource limit, by protocol",
+    ),
+    (
+        "qudag_resource_denied_connections_total",
+        "Connections refused over a connection limit",
+    ),
+    (
+        "qudag_resource_memory_bytes",
+        "Bytes held for unanswered requests",
+    ),
+    ("qudag_resource_streams", "Open inbound request streams"),
+    (
+        "qudag_cover_links",
+        "Connections and circuits given cover traffic",
+    ),
+    ("qudag_cover_bytes_total", "Bytes of cover traffic sent"),
+    ("qudag_cover_real_bytes_total", "Bytes of real traffic sent"),
+    (
+        "qudag_cover_suppressed_total",
+        "Cover cells left out because real traffic was sent",
+    ),
+    (
+        "qudag_cover_real_to_cover_ratio",
+        "Real to cover traffic byte ratio",
+    ),
+    // Connection pool
+    (
+        "qudag_connection_pool_connections",
+        "Pooled connections, by state",
+    ),
+    (
+        "qudag_connection_pool_created_total",
+        "Connections opened by the pool",
+    ),
+    (
+        "qudag_connection_pool_destroyed_total",
+        "Connections closed by the pool",
+    ),
+    (
+        "qudag_connection_pool_acquisitions_total",
+        "Connections checked out of the pool",
+    ),
+    (
+        "qudag_connection_pool_failed_acquisitions_total",
+        "Failed attempts to check out a connection",
+    ),
+    (
+        "qudag_connection_pool_releases_total",
+        "Connections returned to the pool",
+    ),
+    (
+        "qudag_connection_pool_timeouts_total",
+        "Connection checkouts that timed out",
+    ),
+    (
+        "qudag_connection_pool_wait_seconds",
+        "Time waited to check out a connection",
+    ),
+    // DAG and consensus
+    (
+        "qudag_dag_messages_total",
+        "Messages processed by the DAG, by result",
+    ),
+    (
+        "qudag_dag_message_processing_seconds",
+        "Time to process a message in the DAG",
+    ),
+    (
+        "qudag_consensus_vertices_processed_total",
+        "Vertices processed by consensus",
+    ),
+    (
+        "qudag_consensus_finalized_total",
+        "Vertices that reached finality",
+    ),
+    (
+        "qudag_consensus_finality_seconds",
+        "Time from first seeing a vertex to its finality",
+    ),
+    (
+        "qudag_consensus_byzantine_behaviors_total",
+        "Byzantine behaviours detected",
+    ),
+    ("qudag_consensus_forks_resolved_total", "Forks resolved"),
+    // Crypto
+    (
+        "qudag_crypto_operation_seconds",
+        "Latency of cryptographic operations, by operation",
+    ),
+];
+
+/// Largest HTTP request head accepted
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors of the metrics exporter
+#[derive(Debug, Error)]
+pub enum MetricsError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] fmt::Error),
+}
+
+type Labels = Vec<(String, String)>;
+
+type Series<T> = RwLock<BTreeMap<String, BTreeMap<Labels, Arc<T>>>>;
+
+/// Values reported through the `metrics` facade
+#[derive(Debug, Default)]
+struct FacadeMetrics {
+    counters: Series<CounterCell>,
+    gauges: Series<GaugeCell>,
+    histograms: Series<HistogramCell>,
+    descriptions: RwLock<HashMap<String, String>>,
+}
+
+impl FacadeMetrics {
+    /// Process-wide values, installing the recorder on first use
+    fn global() -> Arc<FacadeMetrics> {
+        static GLOBAL: OnceLock<Arc<FacadeMetrics>> = OnceLock::new();
+        GLOBAL
+            .get_or_init(|| {
+                let facade = Arc::new(FacadeMetrics::default());
+                facade.descriptions.write().extend(
+                    METRIC_DESCRIPTIONS
+                        .iter()
+                        .map(|(name, help)| (name.to_string(), help.to_string())),
+                );
+                if metrics::set_boxed_recorder(Box::new(FacadeRecorder(facade.clone()))).is_err() {
+                    warn!("Another metrics recorder is installed; qudag_ metrics are not exported");
+                }
+                facade
+            })
+            .clone()
+    }
+
+    fn series<T: Default>(map: &Series<T>, key: &Key) -> Arc<T> {
+        let labels: Labels = key
+            .labels()
+            .map(|label| (label.key().to_string(), label.value().to_string()))
+            .collect();
+        if let Some(cell) = map
+            .read()
+            .get(key.name())
+            .and_then(|series| series.get(&labels))
+        {
+            return cell.clone();
+        }
+        map.write()
+            .entry(key.name().to_string())
+            .or_default()
+            .entry(labels)
+            .or_default()
+            .clone()
+    }
+
+    fn describe(&self, key: KeyName, description: SharedString) {
+        self.descriptions
+            .write()
+            .entry(key.as_str().to_string())
+            .or_insert_with(|| description.to_string());
+    }
+}
+
+#[derive(Debug, Default)]
+struct CounterCell(AtomicU64);
+
+impl CounterFn for CounterCell {
+    fn increment(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn absolute(&self, value: u64) {
+        self.0.fetch_max(value, Ordering::Relaxed);
+    }
+}
+
+/// An `f64` stored as its bits
+#[derive(Debug, Default)]
+struct GaugeCell(AtomicU64);
+
+impl GaugeCell {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn update(&self, f: impl Fn(f64) -> f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some(f(f64::from_bits(bits)).to_bits())
+            });
+    }
+}
+
+impl GaugeFn for GaugeCell {
+    fn increment(&self, value: f64) {
+        self.update(|v| v + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|v| v - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct HistogramCell(Mutex<HistogramData>);
+
+#[derive(Debug, Clone)]
+struct HistogramData {
+    sum: f64,
+    count: u64,
+    /// Observations per bucket, the last one unbounded
+    buckets: Vec<u64>,
+}
+
+impl Default for HistogramCell {
+    fn default() -> Self {
+        Self(Mutex::new(HistogramData {
+            sum: 0.0,
+            count: 0,
+            buckets: vec![0; LATENCY_BUCKETS.len() + 1],
+        }))
+    }
+}
+
+impl HistogramFn for HistogramCell {
+    fn record(&self, value: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        let mut data = self.0.lock();
+        data.sum += value;
+        data.count += 1;
+        data.buckets[bucket] += 1;
+    }
+}
+
+/// `metrics` recorder keeping values for scrapes
+struct FacadeRecorder(Arc<FacadeMetrics>);
+
+impl metrics::Recorder for FacadeRecorder {
+    fn describe_counter(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.0.describe(key, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.0.describe(key, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.0.describe(key, description);
+    }
+
+    fn register_counter(&self, key: &Key) -> Counter {
+        Counter::from_arc(FacadeMetrics::series(&self.0.counters, key))
+    }
+
+    fn register_gauge(&self, key: &Key) -> Gauge {
+        Gauge::from_arc(FacadeMetrics::series(&self.0.gauges, key))
+    }
+
+    fn register_histogram(&self, key: &Key) -> Histogram {
+        Histogram::from_arc(FacadeMetrics::series(&self.0.histograms, key))
+    }
+}
+
+/// Encodes the facade values into a scrape
+#[derive(Debug)]
+struct FacadeCollector(Arc<FacadeMetrics>);
+
+impl FacadeCollector {
+    /// Encode one family, the unlabelled series without a label set
+    fn encode_family<T>(
+        encoder: &mut DescriptorEncoder,
+        name: &str,
+        help: &str,
+        metric_type: MetricType,
+        series: &BTreeMap<Labels, Arc<T>>,
+        encode: impl Fn(&T, &mut MetricEncoder) -> fmt::Result,
+    ) -> fmt::Result {
+        let mut metric = encoder.encode_descriptor(name, help, None, metric_type)?;
+        for (labels, cell) in series {
+            if labels.is_empty() {
+                encode(cell, &mut metric)?;
+            } else {
+                encode(cell, &mut metric.encode_family(labels)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Collector for FacadeCollector {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> fmt::Result {
+        let descriptions = self.0.descriptions.read();
+        let help = |name: &str| descriptions.get(name).map_or("", String::as_str);
+
+        for (name, series) in self.0.counters.read().iter() {
+            // The encoder adds the suffix back
+            let short = name.strip_suffix("_total").unwrap_or(name);
+            Self::encode_family(
+                &mut encoder,
+                short,
+                help(name),
+                MetricType::Counter,
+                series,
+                |cell, metric| {
+                    metric.encode_counter::<(), _, u64>(
+                        &cell.0.load(Ordering::Relaxed),
+                        None::<&Exemplar<(), u64>>,
+                    )
+                },
+            )?;
+        }
+        for (name, series) in self.0.gauges.read().iter() {
+            Self::encode_family(
+                &mut encoder,
+                name,
+                help(name),
+                MetricType::Gauge,
+                series,
+                |cell, metric| metric.encode_gauge(&cell.get()),
+            )?;
+        }
+        for (name, series) in self.0.histograms.read().iter() {
+            Self::encode_family(
+                &mut encoder,
+                name,
+                help(name),
+                MetricType::Histogram,
+                series,
+                |cell, metric| {
+                    let data = cell.0.lock().clone();
+                    let buckets: Vec<(f64, u64)> = LATENCY_BUCKETS
+                        .iter()
+                        .copied()
+                        .chain([f64::MAX])
+                        .zip(data.buckets)
+                        .collect();
+                    metric.encode_histogram::<()>(data.sum, data.count, &buckets, None)
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Registry of the metrics served on one endpoint
+///
+/// Cheap to clone; clones share the registry.
+#[derive(Clone)]
+pub struct MetricsExporter {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl fmt::Debug for MetricsExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsExporter").finish_non_exhaustive()
+    }
+}
+
+impl Default for MetricsExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsExporter {
+    /// Create an exporter of the `qudag_` metrics
+    pub fn new() -> Self {
+        let mut registry = Registry::default();
+        registry.register_collector(Box::new(FacadeCollector(FacadeMetrics::global())));
+        Self {
+            registry: Arc::new(Mutex::new(registry)),
+        }
+    }
+
+    /// Register libp2p swarm and protocol metrics for one swarm
+    pub fn libp2p_metrics(&self) -> Libp2pMetrics {
+        Libp2pMetrics::new(&mut self.registry.lock())
+    }
+
+    /// Register a metric of another source
+    pub fn register(
+        &self,
+        name: &str,
+        help: &str,
+        metric: impl prometheus_client::registry::Metric,
+    ) {
+        self.registry.lock().register(name, help, metric);
+    }
+
+    /// Encode every metric in the OpenMetrics text format
+    pub fn render(&self) -> Result<String, MetricsError> {
+        let mut text = String::new();
+        prometheus_client::encoding::text::encode(&mut text, &self.registry.lock())?;
+        Ok(text)
+    }
+
+    /// Serve [`METRICS_PATH`] over HTTP until the listener fails
+    pub async fn serve(self, listener: TcpListener) -> Result<(), MetricsError> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let exporter = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = exporter.respond(stream).await {
+                    debug!("Metrics request from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    /// Answer one HTTP request, then close the connection
+    async fn respond(&self, mut stream: TcpStream) -> Result<(), MetricsError> {
+        let head = tokio::time::timeout(REQUEST_TIMEOUT, read_request_head(&mut stream))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        let mut parts = head.split_whitespace();
+        let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        let path = target.split('?').next().unwrap_or("");
+
+        let (status, content_type, body) = match (method, path) {
+            ("GET", METRICS_PATH) => ("200 OK", OPENMETRICS_CONTENT_TYPE, self.render()?),
+            (_, METRICS_PATH) => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "Method not allowed\n".to_string(),
+            ),
+            _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Read up to the end of the request head and return its first line
+async fn read_request_head(stream: &mut TcpStream) -> Result<String, MetricsError> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_REQUEST_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large").into());
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    Ok(head.lines().next().unwrap_or("").to_string())
+}
@@ -2,7 +2,7 @@
 //!
 //! This module implements comprehensive NAT traversal capabilities including:
 //! - STUN/TURN protocol support for NAT detection and relay
-//! - UPnP and NAT-PMP for automatic port mapping
+//! - UPnP IGD, NAT-PMP and PCP for automatic port mapping, see [`crate::port_mapping`]
 //! - Hole punching techniques for direct peer connections
 //! - AutoNAT protocol from libp2p for NAT detection
 //! - Relay functionality for unreachable peers
@@ -10,6 +10,10 @@
 //! - Connection upgrade paths from relay to direct connections
 
 use crate::connection::ConnectionManager;
+use crate::port_mapping::{
+    default_gateway, ssdp_search, IgdGateway, PmpGateway, PmpVersion, PortMappingError,
+    NAT_PMP_PORT, SSDP_MULTICAST,
+};
 use crate::types::{ConnectionStatus, NetworkError, PeerId};
 use dashmap::DashMap;
 use libp2p::core::Multiaddr;
@@ -29,6 +33,12 @@ use tracing::{debug, error, info, warn};
 /// STUN transaction ID type (12 bytes as per RFC 5389)
 type TransactionId = [u8; 12];
 
+/// How long UPnP discovery waits for gateways to answer
+const SSDP_WAIT: Duration = Duration::from_secs(2);
+
+/// Requests sent to each NAT-PMP gateway candidate before giving up
+const NAT_PMP_ATTEMPTS: u32 = 3;
+
 /// STUN message structure
 #[derive(Debug, Clone)]
 pub struct Message {
@@ -99,6 +109,10 @@ pub enum NatTraversalError {
     #[error("NAT-PMP error: {0}")]
     NatPmpError(String),
 
+    /// Port mapping protocol failed
+    #[error("Port mapping error: {0}")]
+    PortMappingError(#[from] PortMappingError),
+
     /// Hole punching failed
     #[error("Hole punching failed: {0}")]
     HolePunchError(String),
@@ -248,6 +262,10 @@ pub struct NatTraversalConfig {
     pub upgrade_interval: Duration,
     /// Port mapping lifetime (for UPnP/NAT-PMP)
     pub port_mapping_lifetime: Duration,
+    /// Where UPnP gateways are searched for over SSDP
+    pub ssdp_target: SocketAddr,
+    /// NAT-PMP/PCP gateway, searched for when unset
+    pub nat_pmp_gateway: Option<SocketAddr>,
 }
 
 impl Default for NatTraversalConfig {
@@ -272,6 +290,8 @@ impl Default for NatTraversalConfig {
             detection_interval: Duration::from_secs(300), // 5 minutes
             upgrade_interval: Duration::from_secs(60),    // 1 minute
             port_mapping_lifetime: Duration::from_secs(3600), // 1 hour
+            ssdp_target: SSDP_MULTICAST,
+            nat_pmp_gateway: None,
         }
     }
 }
@@ -302,6 +322,8 @@ pub struct NatTraversalManager {
     port_mappings: Arc<DashMap<u16, PortMapping>>,
     /// NAT detection task handle
     detection_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Port mapping renewal task handle
+    renewal_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
     /// Statistics
     stats: Arc<NatTraversalStats>,
 }
@@ -339,6 +361,8 @@ pub enum PortMappingMethod {
     Upnp,
     /// NAT-PMP mapping
     NatPmp,
+    /// PCP mapping
+    Pcp,
     /// Manual mapping
     Manual,
 }
@@ -636,19 +660,12 @@ impl TurnClient {
     }
 }
 
-/// Simple UPnP gateway representation
-#[derive(Debug, Clone)]
-pub struct SimpleGateway {
-    /// Gateway address
-    pub address: SocketAddr,
-    /// Friendly name
-    pub name: String,
-}
-
 /// UPnP manager for automatic port mapping
 pub struct UpnpManager {
+    /// Where SSDP searches are sent
+    ssdp_target: SocketAddr,
     /// Gateway device
-    gateway: Arc<Mutex<Option<SimpleGateway>>>,
+    gateway: Arc<Mutex<Option<IgdGateway>>>,
     /// Active mappings
     mappings: Arc<DashMap<u16, UpnpMapping>>,
     /// Mapping refresh interval
@@ -667,48 +684,67 @@ pub struct UpnpMapping {
     pub protocol: PortMappingProtocol,
     /// Description
     pub description: String,
-    /// Lease duration
+    /// Lease duration, zero for a permanent mapping
     pub lease_duration: Duration,
     /// Created timestamp
     pub created_at: Instant,
 }
 
+impl UpnpMapping {
+    /// Whether half of the lease has passed
+    fn needs_renewal(&self) -> bool {
+        !self.lease_duration.is_zero() && self.created_at.elapsed() >= self.lease_duration / 2
+    }
+}
+
 impl UpnpManager {
     /// Create a new UPnP manager
     pub fn new(refresh_interval: Duration) -> Self {
         Self {
+            ssdp_target: SSDP_MULTICAST,
             gateway: Arc::new(Mutex::new(None)),
             mappings: Arc::new(DashMap::new()),
             refresh_interval,
         }
     }
 
+    /// Send SSDP searches to `target` instead of the multicast group
+    pub fn with_ssdp_target(mut self, target: SocketAddr) -> Self {
+        self.ssdp_target = target;
+        self
+    }
+
     /// Discover UPnP gateway
     pub async fn discover_gateway(&self) -> Result<(), NatTraversalError> {
-        // Simple UPnP discovery simulation
-        // In a real implementation, this would use SSDP multicast discovery
-        let potential_gateways = vec!["192.168.1.1:1900", "192.168.0.1:1900", "10.0.0.1:1900"];
-
-        for gateway_addr in potential_gateways {
-            if let Ok(addr) = gateway_addr.parse::<SocketAddr>() {
-                // Test if gateway responds
-                if let Ok(socket) = UdpSocket::bind("0.0.0.0:0").await {
-                    if socket.send_to(b"M-SEARCH", addr).await.is_ok() {
-                        info!("Discovered UPnP gateway at: {}", addr);
-                        let gateway = SimpleGateway {
-                            address: addr,
-                            name: "UPnP Gateway".to_string(),
-                        };
-                        *self.gateway.lock().await = Some(gateway);
-                        return Ok(());
-                    }
+        let locations = ssdp_search(self.ssdp_target, SSDP_WAIT).await?;
+        let mut last_error = PortMappingError::NoGateway;
+        for location in locations {
+            match IgdGateway::from_location(&location).await {
+                Ok(gateway) => {
+                    info!(
+                        "Discovered UPnP gateway {:?} at {}",
+                        gateway.friendly_name(),
+                        gateway.control_url()
+                    );
+                    *self.gateway.lock().await = Some(gateway);
+                    return Ok(());
+                }
+                Err(e) => {
+                    debug!("Unusable UPnP device at {}: {}", location, e);
+                    last_error = e;
                 }
             }
         }
+        Err(last_error.into())
+    }
 
-        Err(NatTraversalError::UpnpError(
-            "No UPnP gateway found".to_string(),
-        ))
+    /// The discovered gateway
+    async fn gateway(&self) -> Result<IgdGateway, NatTraversalError> {
+        self.gateway
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| NatTraversalError::UpnpError("No gateway discovered".to_string()))
     }
 
     /// Create port mapping
@@ -720,12 +756,24 @@ impl UpnpManager {
         description: &str,
         lease_duration: Duration,
     ) -> Result<UpnpMapping, NatTraversalError> {
-        // Simulate UPnP port mapping
-        // In a real implementation, this would send UPnP control messages
+        let gateway = self.gateway().await?;
         info!(
-            "Creating UPnP port mapping: {}:{} -> {} ({})",
-            local_port, external_port, protocol as u8, description
+            "Creating UPnP port mapping: {}:{} -> {} ({:?}, {})",
+            gateway.local_ip(),
+            local_port,
+            external_port,
+            protocol,
+            description
         );
+        let lease_duration = gateway
+            .add_port_mapping(
+                protocol,
+                external_port,
+                local_port,
+                description,
+                lease_duration,
+            )
+            .await?;
 
         let mapping = UpnpMapping {
             local_port,
@@ -740,21 +788,83 @@ impl UpnpManager {
         Ok(mapping)
     }
 
-    /// Get local IP address
-    #[allow(dead_code)]
-    async fn get_local_ip(&self) -> Result<IpAddr, NatTraversalError> {
-        // Try to get local IP by connecting to a public address
-        let socket = UdpSocket::bind("0.0.0.0:0").await?;
-        socket.connect("8.8.8.8:80").await?;
-        let local_addr = socket.local_addr()?;
-        Ok(local_addr.ip())
+    /// Remove the mapping of a local port
+    pub async fn remove_mapping(&self, local_port: u16) -> Result<(), NatTraversalError> {
+        let Some((_, mapping)) = self.mappings.remove(&local_port) else {
+            return Ok(());
+        };
+        self.gateway()
+            .await?
+            .delete_port_mapping(mapping.protocol, mapping.external_port)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove every mapping made through this manager
+    pub async fn remove_all_mappings(&self) -> Result<(), NatTraversalError> {
+        let ports: Vec<u16> = self.mappings.iter().map(|entry| *entry.key()).collect();
+        let mut result = Ok(());
+        for port in ports {
+            if let Err(e) = self.remove_mapping(port).await {
+                warn!("Failed to remove UPnP mapping of port {}: {}", port, e);
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Renew the mappings past half of their lease
+    pub async fn renew_mappings(&self) -> Vec<UpnpMapping> {
+        let due: Vec<UpnpMapping> = self
+            .mappings
+            .iter()
+            .filter(|entry| entry.needs_renewal())
+            .map(|entry| entry.value().clone())
+            .collect();
+        let mut renewed = Vec::with_capacity(due.len());
+        for mapping in due {
+            match self
+                .create_mapping(
+                    mapping.local_port,
+                    mapping.external_port,
+                    mapping.protocol,
+                    &mapping.description,
+                    mapping.lease_duration,
+                )
+                .await
+            {
+                Ok(mapping) => renewed.push(mapping),
+                Err(e) => warn!(
+                    "Failed to renew UPnP mapping of port {}: {}",
+                    mapping.local_port, e
+                ),
+            }
+        }
+        renewed
+    }
+
+    /// Active mappings
+    pub fn mappings(&self) -> Vec<UpnpMapping> {
+        self.mappings
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Address of the gateway on the Internet
+    pub async fn external_ip(&self) -> Result<IpAddr, NatTraversalError> {
+        Ok(self.gateway().await?.external_ip().await?)
     }
 }
 
 /// NAT-PMP client for port mapping
+///
+/// Speaks PCP to gateways that support it.
 pub struct NatPmpClient {
+    /// Gateway to use instead of searching for one
+    gateway_address: Option<SocketAddr>,
     /// Gateway address
-    gateway: Arc<Mutex<Option<IpAddr>>>,
+    gateway: Arc<Mutex<Option<PmpGateway>>>,
     /// Active mappings
     mappings: Arc<DashMap<u16, NatPmpMapping>>,
 }
@@ -772,31 +882,70 @@ pub struct NatPmpMapping {
     pub lifetime: Duration,
     /// Created timestamp
     pub created_at: Instant,
+    /// Protocol the mapping was made with
+    pub version: PmpVersion,
+    /// Identifies the mapping to PCP gateways
+    nonce: [u8; 12],
+}
+
+impl NatPmpMapping {
+    fn protocol(&self) -> PortMappingProtocol {
+        if self.is_tcp {
+            PortMappingProtocol::TCP
+        } else {
+            PortMappingProtocol::UDP
+        }
+    }
+
+    /// Whether half of the lifetime has passed
+    fn needs_renewal(&self) -> bool {
+        self.created_at.elapsed() >= self.lifetime / 2
+    }
 }
 
 impl NatPmpClient {
     /// Create a new NAT-PMP client
     pub fn new() -> Self {
         Self {
+            gateway_address: None,
             gateway: Arc::new(Mutex::new(None)),
             mappings: Arc::new(DashMap::new()),
         }
     }
 
+    /// Use the gateway at `address` instead of searching for one
+    pub fn with_gateway(mut self, address: SocketAddr) -> Self {
+        self.gateway_address = Some(address);
+        self
+    }
+
     /// Discover NAT-PMP gateway
     pub async fn discover_gateway(&self) -> Result<(), NatTraversalError> {
-        // TODO: Implement gateway discovery
-        // For now, try common gateway addresses
-        let common_gateways = vec!["192.168.1.1", "192.168.0.1", "10.0.0.1"];
-
-        for gateway_str in common_gateways {
-            if let Ok(gateway) = gateway_str.parse::<IpAddr>() {
-                // Test if gateway responds to NAT-PMP
-                if self.test_gateway(&gateway).await {
+        let candidates: Vec<SocketAddr> = match self.gateway_address {
+            Some(address) => vec![address],
+            None => default_gateway()
+                .into_iter()
+                .chain([
+                    Ipv4Addr::new(192, 168, 1, 1),
+                    Ipv4Addr::new(192, 168, 0, 1),
+                    Ipv4Addr::new(10, 0, 0, 1),
+                ])
+                .map(|ip| SocketAddr::new(IpAddr::V4(ip), NAT_PMP_PORT))
+                .collect(),
+        };
+
+        for candidate in candidates {
+            match PmpGateway::probe(candidate, NAT_PMP_ATTEMPTS).await {
+                Ok(gateway) => {
+                    info!(
+                        "Discovered {:?} gateway: {}",
+                        gateway.version(),
+                        gateway.address()
+                    );
                     *self.gateway.lock().await = Some(gateway);
-                    info!("Discovered NAT-PMP gateway: {}", gateway);
                     return Ok(());
                 }
+                Err(e) => debug!("No NAT-PMP gateway at {}: {}", candidate, e),
             }
         }
 
@@ -805,11 +954,13 @@ impl NatPmpClient {
         ))
     }
 
-    /// Test if an address responds to NAT-PMP
-    async fn test_gateway(&self, _gateway: &IpAddr) -> bool {
-        // TODO: Implement actual NAT-PMP protocol test
-        // For now, return false
-        false
+    /// The discovered gateway
+    async fn gateway(&self) -> Result<PmpGateway, NatTraversalError> {
+        self.gateway
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| NatTraversalError::NatPmpError("No gateway discovered".to_string()))
     }
 
     /// Create port mapping
@@ -820,24 +971,119 @@ impl NatPmpClient {
         is_tcp: bool,
         lifetime: Duration,
     ) -> Result<NatPmpMapping, NatTraversalError> {
-        let gateway = self.gateway.lock().await;
-        let _gateway_addr = gateway
-            .as_ref()
-            .ok_or_else(|| NatTraversalError::NatPmpError("No gateway discovered".to_string()))?;
+        let gateway = self.gateway().await?;
+        let mut nonce = [0u8; 12];
+        thread_rng().fill(&mut nonce);
+        self.map(&gateway, local_port, external_port, is_tcp, lifetime, nonce)
+            .await
+    }
 
-        // TODO: Implement actual NAT-PMP mapping protocol
+    /// Ask the gateway for a mapping and record what it granted
+    async fn map(
+        &self,
+        gateway: &PmpGateway,
+        local_port: u16,
+        external_port: u16,
+        is_tcp: bool,
+        lifetime: Duration,
+        nonce: [u8; 12],
+    ) -> Result<NatPmpMapping, NatTraversalError> {
+        let protocol = if is_tcp {
+            PortMappingProtocol::TCP
+        } else {
+            PortMappingProtocol::UDP
+        };
+        let grant = gateway
+            .map(protocol, local_port, external_port, lifetime, nonce)
+            .await?;
 
         let mapping = NatPmpMapping {
             local_port,
-            external_port,
+            external_port: grant.external_port,
             is_tcp,
-            lifetime,
+            lifetime: grant.lifetime,
             created_at: Instant::now(),
+            version: gateway.version(),
+            nonce,
         };
 
         self.mappings.insert(local_port, mapping.clone());
         Ok(mapping)
     }
+
+    /// Remove the mapping of a local port
+    pub async fn remove_mapping(&self, local_port: u16) -> Result<(), NatTraversalError> {
+        let Some((_, mapping)) = self.mappings.remove(&local_port) else {
+            return Ok(());
+        };
+        self.gateway()
+            .await?
+            .unmap(mapping.protocol(), local_port, mapping.nonce)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove every mapping made through this client
+    pub async fn remove_all_mappings(&self) -> Result<(), NatTraversalError> {
+        let ports: Vec<u16> = self.mappings.iter().map(|entry| *entry.key()).collect();
+        let mut result = Ok(());
+        for port in ports {
+            if let Err(e) = self.remove_mapping(port).await {
+                warn!("Failed to remove NAT-PMP mapping of port {}: {}", port, e);
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Renew the mappings past half of their lifetime
+    ///
+    /// Renewals ask for the external port already granted.
+    pub async fn renew_mappings(&self) -> Vec<NatPmpMapping> {
+        let due: Vec<NatPmpMapping> = self
+            .mappings
+            .iter()
+            .filter(|entry| entry.needs_renewal())
+            .map(|entry| entry.value().clone())
+            .collect();
+        let Ok(gateway) = self.gateway().await else {
+            return Vec::new();
+        };
+        let mut renewed = Vec::with_capacity(due.len());
+        for mapping in due {
+            match self
+                .map(
+                    &gateway,
+                    mapping.local_port,
+                    mapping.external_port,
+                    mapping.is_tcp,
+                    mapping.lifetime,
+                    mapping.nonce,
+                )
+                .await
+            {
+                Ok(mapping) => renewed.push(mapping),
+                Err(e) => warn!(
+                    "Failed to renew NAT-PMP mapping of port {}: {}",
+                    mapping.local_port, e
+                ),
+            }
+        }
+        renewed
+    }
+
+    /// Active mappings
+    pub fn mappings(&self) -> Vec<NatPmpMapping> {
+        self.mappings
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Address of the gateway on the Internet
+    pub async fn external_ip(&self) -> Result<IpAddr, NatTraversalError> {
+        Ok(self.gateway().await?.external_ip().await?)
+    }
 }
 
 /// Hole punch coordinator for establishing direct connections
@@ -1269,13 +1515,19 @@ impl NatTraversalManager {
                 config.turn_servers.clone(),
                 config.max_relay_connections,
             )),
-            upnp_manager: Arc::new(UpnpManager::new(config.port_mapping_lifetime)),
-            nat_pmp_client: Arc::new(NatPmpClient::new()),
+            upnp_manager: Arc::new(
+                UpnpManager::new(config.port_mapping_lifetime).with_ssdp_target(config.ssdp_target),
+            ),
+            nat_pmp_client: Arc::new(match config.nat_pmp_gateway {
+                Some(gateway) => NatPmpClient::new().with_gateway(gateway),
+                None => NatPmpClient::new(),
+            }),
             hole_punch_coordinator: Arc::new(HolePunchCoordinator::new(config.hole_punch_timeout)),
             relay_manager: Arc::new(RelayManager::new(config.max_relay_connections)),
             upgrade_manager: Arc::new(ConnectionUpgradeManager::new(config.upgrade_interval)),
             port_mappings: Arc::new(DashMap::new()),
             detection_handle: Arc::new(Mutex::new(None)),
+            renewal_handle: Arc::new(Mutex::new(None)),
             stats,
         }
     }
@@ -1352,6 +1604,33 @@ impl NatTraversalManager {
         });
 
         *self.detection_handle.lock().await = Some(detection_task);
+
+        // Port mapping renewal task
+        let upnp_manager = Arc::clone(&self.upnp_manager);
+        let nat_pmp_client = Arc::clone(&self.nat_pmp_client);
+        let port_mappings = Arc::clone(&self.port_mappings);
+        let renewal_interval = (self.config.port_mapping_lifetime / 4).max(Duration::from_secs(1));
+
+        let renewal_task = tokio::spawn(async move {
+            let mut interval = interval(renewal_interval);
+            loop {
+                interval.tick().await;
+
+                for mapping in upnp_manager.renew_mappings().await {
+                    if let Some(mut entry) = port_mappings.get_mut(&mapping.local_port) {
+                        entry.expires_at = mapping.created_at + mapping.lease_duration;
+                    }
+                }
+                for mapping in nat_pmp_client.renew_mappings().await {
+                    if let Some(mut entry) = port_mappings.get_mut(&mapping.local_port) {
+                        entry.external_port = mapping.external_port;
+                        entry.expires_at = mapping.created_at + mapping.lifetime;
+                    }
+                }
+            }
+        });
+
+        *self.renewal_handle.lock().await = Some(renewal_task);
     }
 
     /// Get current NAT information
@@ -1419,7 +1698,10 @@ impl NatTraversalManager {
                         local_port,
                         external_port: mapping.external_port,
                         protocol,
-                        method: PortMappingMethod::NatPmp,
+                        method: match mapping.version {
+                            PmpVersion::NatPmp => PortMappingMethod::NatPmp,
+                            PmpVersion::Pcp => PortMappingMethod::Pcp,
+                        },
                         created_at: Instant::now(),
                         expires_at: Instant::now() + mapping.lifetime,
                     };
@@ -1444,6 +1726,28 @@ impl NatTraversalManager {
         ))
     }
 
+    /// Remove the port mapping of a local port from the gateway
+    pub async fn remove_port_mapping(&self, local_port: u16) -> Result<(), NatTraversalError> {
+        let Some((_, mapping)) = self.port_mappings.remove(&local_port) else {
+            return Ok(());
+        };
+        match mapping.method {
+            PortMappingMethod::Upnp => self.upnp_manager.remove_mapping(local_port).await,
+            PortMappingMethod::NatPmp | PortMappingMethod::Pcp => {
+                self.nat_pmp_client.remove_mapping(local_port).await
+            }
+            PortMappingMethod::Manual => Ok(()),
+        }
+    }
+
+    /// Active port mappings
+    pub fn get_port_mappings(&self) -> Vec<PortMapping> {
+        self.port_mappings
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
     /// Establish connection to a peer with NAT traversal
     pub async fn connect_peer(&self, peer_id: PeerId) -> Result<(), NatTraversalError> {
         // Try direct connection first
@@ -1670,10 +1974,15 @@ impl NatTraversalManager {
             self.relay_manager.close_relay(&peer_id).await;
         }
 
-        // Remove port mappings
-        // TODO: Implement port mapping cleanup
+        // Stop renewing port mappings and remove them from the gateway
+        if let Some(handle) = self.renewal_handle.lock().await.take() {
+            handle.abort();
+        }
+        let upnp_cleanup = self.upnp_manager.remove_all_mappings().await;
+        let nat_pmp_cleanup = self.nat_pmp_client.remove_all_mappings().await;
+        self.port_mappings.clear();
 
-        Ok(())
+        upnp_cleanup.and(nat_pmp_cleanup)
     }
 }
 
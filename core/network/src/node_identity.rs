@@ -0,0 +1,390 @@
+//! Persistent node identity.
+//!
+//! A node is identified by its ML-DSA-65 key pair. The libp2p ed25519 key
+//! (and so the `PeerId`) is derived from the ML-DSA secret key, so only that
+//! key has to be kept secret, either in a key file (optionally
+//! password-protected) or, with the `vault` feature, in a `qudag-vault`
+//! vault. The traffic obfuscation key is shared by the whole network and is
+//! not part of the identity.
+//!
+//! The two identities are bound by an [`IdentityCertificate`] in which each
+//! key signs the other. It is stored next to the key, so its issue time stays
+//! the same across restarts. identify messages are capped at 4 KiB, too small for
+//! ML-DSA material, so nodes advertise the certificate digest in their
+//! identify agent version and serve the certificate itself over
+//! [`IDENTITY_PROTOCOL`].
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hkdf::Hkdf;
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use qudag_crypto::key_encoding::{EncodedKey, KeyEncodingError, KeyFormat};
+use qudag_crypto::ml_dsa::{MlDsaError, MlDsaKeyPair, MlDsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::info;
+use zeroize::Zeroizing;
+
+/// Request-response protocol serving identity certificates
+pub const IDENTITY_PROTOCOL: &str = "/qudag/identity/1";
+
+/// Conventional file name of the identity key inside a node data directory
+pub const IDENTITY_FILE: &str = "node_identity.key";
+
+/// Marker in the identify agent version that precedes the certificate digest
+const AGENT_DIGEST_MARKER: &str = "idcert=";
+
+/// Domain separator for the cross-signed certificate message
+const CERTIFICATE_DOMAIN: &[u8] = b"qudag-node-identity/1";
+
+const LIBP2P_KEY_INFO: &[u8] = b"qudag-node-identity/libp2p-ed25519";
+
+/// Extension of the certificate file stored next to an identity key file
+const CERTIFICATE_EXTENSION: &str = "cert";
+
+/// Suffix of the vault label the certificate is stored under
+#[cfg(feature = "vault")]
+const CERTIFICATE_LABEL_SUFFIX: &str = ".certificate";
+
+/// Errors that can occur while loading or verifying node identities
+#[derive(Debug, Error)]
+pub enum NodeIdentityError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Key encoding error: {0}")]
+    KeyEncoding(#[from] KeyEncodingError),
+    #[error("ML-DSA error: {0}")]
+    MlDsa(#[from] MlDsaError),
+    #[error("Key derivation failed: {0}")]
+    Derivation(String),
+    #[error("Invalid identity certificate: {0}")]
+    InvalidCertificate(&'static str),
+    #[error("Certificate encoding error: {0}")]
+    Encoding(String),
+    #[cfg(feature = "vault")]
+    #[error("Vault error: {0}")]
+    Vault(#[from] qudag_vault_core::VaultError),
+}
+
+/// Cross-signed binding between a libp2p identity and an ML-DSA key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityCertificate {
+    /// libp2p public key in protobuf encoding
+    pub libp2p_public_key: Vec<u8>,
+    /// ML-DSA-65 public key
+    pub ml_dsa_public_key: Vec<u8>,
+    /// Issue time in seconds since the Unix epoch
+    pub issued_at: u64,
+    /// libp2p signature over the certificate message
+    pub libp2p_signature: Vec<u8>,
+    /// ML-DSA signature over the certificate message
+    pub ml_dsa_signature: Vec<u8>,
+}
+
+impl IdentityCertificate {
+    /// Issue a certificate in which `libp2p_key` and `ml_dsa` sign each other
+    pub fn issue(
+        libp2p_key: &Keypair,
+        ml_dsa: &MlDsaKeyPair,
+        issued_at: u64,
+    ) -> Result<Self, NodeIdentityError> {
+        let mut certificate = Self {
+            libp2p_public_key: libp2p_key.public().encode_protobuf(),
+            ml_dsa_public_key: ml_dsa.public_key().to_vec(),
+            issued_at,
+            libp2p_signature: Vec::new(),
+            ml_dsa_signature: Vec::new(),
+        };
+        let message = certificate.signed_message();
+        certificate.libp2p_signature = libp2p_key
+            .sign(&message)
+            .map_err(|e| NodeIdentityError::Derivation(e.to_string()))?;
+        certificate.ml_dsa_signature = ml_dsa.sign(&message, &mut rand::thread_rng())?;
+        Ok(certificate)
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(
+            CERTIFICATE_DOMAIN.len()
+                + 2
+                + self.libp2p_public_key.len()
+                + self.ml_dsa_public_key.len()
+                + 8,
+        );
+        message.extend_from_slice(CERTIFICATE_DOMAIN);
+        message.extend_from_slice(&(self.libp2p_public_key.len() as u16).to_be_bytes());
+        message.extend_from_slice(&self.libp2p_public_key);
+        message.extend_from_slice(&self.ml_dsa_public_key);
+        message.extend_from_slice(&self.issued_at.to_be_bytes());
+        message
+    }
+
+    /// Check both signatures and return the `PeerId` the certificate binds
+    pub fn verify(&self) -> Result<PeerId, NodeIdentityError> {
+        let libp2p_key = PublicKey::try_decode_protobuf(&self.libp2p_public_key)
+            .map_err(|_| NodeIdentityError::InvalidCertificate("bad libp2p public key"))?;
+        let message = self.signed_message();
+        if !libp2p_key.verify(&message, &self.libp2p_signature) {
+            return Err(NodeIdentityError::InvalidCertificate(
+                "libp2p signature does not verify",
+            ));
+        }
+        MlDsaPublicKey::from_bytes(&self.ml_dsa_public_key)
+            .and_then(|key| key.verify(&message, &self.ml_dsa_signature))
+            .map_err(|_| {
+                NodeIdentityError::InvalidCertificate("ML-DSA signature does not verify")
+            })?;
+        Ok(libp2p_key.to_peer_id())
+    }
+
+    /// Whether the certificate binds exactly these two keys
+    fn binds(&self, libp2p_key: &Keypair, ml_dsa: &MlDsaKeyPair) -> bool {
+        self.libp2p_public_key == libp2p_key.public().encode_protobuf()
+            && self.ml_dsa_public_key == ml_dsa.public_key()
+            && self.verify().is_ok()
+    }
+
+    /// Verify the certificate for a specific peer and advertised digest
+    pub fn verify_for(&self, peer_id: &PeerId, digest: &[u8; 32]) -> Result<(), NodeIdentityError> {
+        if &self.digest() != digest {
+            return Err(NodeIdentityError::InvalidCertificate(
+                "digest does not match advertisement",
+            ));
+        }
+        if &self.verify()? != peer_id {
+            return Err(NodeIdentityError::InvalidCertificate(
+                "certificate belongs to another peer",
+            ));
+        }
+        Ok(())
+    }
+
+    /// SHA-256 digest of the encoded certificate
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+
+    /// Encode the certificate
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("certificate serialization cannot fail")
+    }
+
+    /// Decode a certificate produced by [`IdentityCertificate::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NodeIdentityError> {
+        bincode::deserialize(bytes).map_err(|e| NodeIdentityError::Encoding(e.to_string()))
+    }
+}
+
+/// Request for a peer's identity certificate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityRequest;
+
+/// Build the identify agent version advertising `certificate`
+pub fn agent_version(certificate: &IdentityCertificate) -> String {
+    format!(
+        "qudag/{} {}{}",
+        env!("CARGO_PKG_VERSION"),
+        AGENT_DIGEST_MARKER,
+        hex::encode(certificate.digest())
+    )
+}
+
+/// Extract the certificate digest from an identify agent version
+pub fn advertised_digest(agent_version: &str) -> Option<[u8; 32]> {
+    let encoded = agent_version
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix(AGENT_DIGEST_MARKER))?;
+    hex::decode(encoded).ok()?.try_into().ok()
+}
+
+/// Long-lived identity of a node
+#[derive(Clone)]
+pub struct NodeIdentity {
+    keypair: Keypair,
+    ml_dsa: Arc<MlDsaKeyPair>,
+    certificate: IdentityCertificate,
+}
+
+impl std::fmt::Debug for NodeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeIdentity")
+            .field("peer_id", &self.peer_id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl NodeIdentity {
+    /// Generate a fresh identity
+    pub fn generate() -> Result<Self, NodeIdentityError> {
+        Self::from_ml_dsa(MlDsaKeyPair::generate(&mut rand::thread_rng())?)
+    }
+
+    /// Build the identity belonging to an ML-DSA key pair
+    pub fn from_ml_dsa(ml_dsa: MlDsaKeyPair) -> Result<Self, NodeIdentityError> {
+        Self::with_certificate(ml_dsa, None)
+    }
+
+    /// Build the identity, keeping `stored` if it certifies the same keys
+    ///
+    /// A new certificate is issued when there is none or it does not match.
+    fn with_certificate(
+        ml_dsa: MlDsaKeyPair,
+        stored: Option<IdentityCertificate>,
+    ) -> Result<Self, NodeIdentityError> {
+        let hkdf = Hkdf::<Sha256>::new(None, ml_dsa.secret_key());
+        let mut seed = Zeroizing::new([0u8; 32]);
+        hkdf.expand(LIBP2P_KEY_INFO, seed.as_mut())
+            .map_err(|e| NodeIdentityError::Derivation(e.to_string()))?;
+        let keypair = Keypair::ed25519_from_bytes(*seed)
+            .map_err(|e| NodeIdentityError::Derivation(e.to_string()))?;
+
+        let certificate = match stored {
+            Some(certificate) if certificate.binds(&keypair, &ml_dsa) => certificate,
+            _ => {
+                let issued_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                IdentityCertificate::issue(&keypair, &ml_dsa, issued_at)?
+            }
+        };
+
+        Ok(Self {
+            keypair,
+            ml_dsa: Arc::new(ml_dsa),
+            certificate,
+        })
+    }
+
+    /// Load the identity key at `path`, creating it if it does not exist
+    ///
+    /// With a `password` the key file is encrypted. The certificate is kept
+    /// beside it with a `.cert` extension and re-issued if missing.
+    pub fn load_or_create(
+        path: impl AsRef<Path>,
+        password: Option<&str>,
+    ) -> Result<Self, NodeIdentityError> {
+        let path = path.as_ref();
+        let certificate_path = path.with_extension(CERTIFICATE_EXTENSION);
+        if path.exists() {
+            let key = EncodedKey::decode(&fs::read(path)?, password)?;
+            let stored = fs::read(&certificate_path)
+                .ok()
+                .and_then(|bytes| IdentityCertificate::from_bytes(&bytes).ok());
+            let identity = Self::with_certificate(MlDsaKeyPair::try_from(&key)?, stored.clone())?;
+            if stored.as_ref() != Some(&identity.certificate) {
+                fs::write(&certificate_path, identity.certificate.to_bytes())?;
+            }
+            info!(
+                "Loaded node identity {} from {:?}",
+                identity.peer_id(),
+                path
+            );
+            return Ok(identity);
+        }
+
+        let identity = Self::generate()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let encoded =
+            EncodedKey::from(identity.ml_dsa.as_ref()).encode(KeyFormat::Binary, password)?;
+        write_private(path, &encoded)?;
+        fs::write(&certificate_path, identity.certificate.to_bytes())?;
+        info!("Created node identity {} at {:?}", identity.peer_id(), path);
+        Ok(identity)
+    }
+
+    /// Load the identity stored under `label`, creating it if it is missing
+    ///
+    /// The certificate is stored under `label` with a `.certificate` suffix.
+    #[cfg(feature = "vault")]
+    pub fn load_or_create_in_vault(
+        vault: &mut qudag_vault_core::Vault,
+        label: &str,
+    ) -> Result<Self, NodeIdentityError> {
+        let certificate_label = format!("{}{}", label, CERTIFICATE_LABEL_SUFFIX);
+        match vault.get_key(label) {
+            Ok(key) => {
+                let stored = vault
+                    .get_secret(&certificate_label)
+                    .ok()
+                    .and_then(|secret| hex::decode(secret.password.as_str()).ok())
+                    .and_then(|bytes| IdentityCertificate::from_bytes(&bytes).ok());
+                let identity =
+                    Self::with_certificate(MlDsaKeyPair::try_from(&key)?, stored.clone())?;
+                if stored.as_ref() != Some(&identity.certificate) {
+                    // Replace a missing, unreadable or mismatched certificate
+                    if vault.get_secret(&certificate_label).is_ok() {
+                        vault.delete_secret(&certificate_label)?;
+                    }
+                    identity.store_certificate_in_vault(vault, &certificate_label)?;
+                }
+                Ok(identity)
+            }
+            Err(qudag_vault_core::VaultError::SecretNotFound(_)) => {
+                let identity = Self::generate()?;
+                vault.add_key(label, &EncodedKey::from(identity.ml_dsa.as_ref()))?;
+                identity.store_certificate_in_vault(vault, &certificate_label)?;
+                info!("Stored node identity {} in vault", identity.peer_id());
+                Ok(identity)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[cfg(feature = "vault")]
+    fn store_certificate_in_vault(
+        &self,
+        vault: &mut qudag_vault_core::Vault,
+        label: &str,
+    ) -> Result<(), NodeIdentityError> {
+        let encoded = hex::encode(self.certificate.to_bytes());
+        vault.add_secret(label, "identity-certificate", Some(&encoded))?;
+        Ok(())
+    }
+
+    /// libp2p key pair
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
+    /// Stable `PeerId`
+    pub fn peer_id(&self) -> PeerId {
+        self.keypair.public().to_peer_id()
+    }
+
+    /// ML-DSA identity key
+    pub fn ml_dsa(&self) -> &Arc<MlDsaKeyPair> {
+        &self.ml_dsa
+    }
+
+    /// Certificate binding the libp2p and ML-DSA keys
+    pub fn certificate(&self) -> &IdentityCertificate {
+        &self.certificate
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    fs::write(path, data)
+}
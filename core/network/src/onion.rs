@@ -1,6 +1,8 @@
+use crate::onion_relay::{OnionRelayError, RelayDescriptor};
+use libp2p::{Multiaddr, PeerId};
 use qudag_crypto::kem::{PublicKey as KEMPublicKey, SecretKey as KEMSecretKey};
 use qudag_crypto::ml_kem::MlKem768;
-use rand::{thread_rng, Rng, RngCore};
+use rand::{seq::IteratorRandom, thread_rng, Rng, RngCore};
 use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
 use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
@@ -998,7 +1000,7 @@ pub struct CircuitManager {
     /// Circuit creation rate limiter
     creation_rate: f64,
     /// Last circuit creation time
-    last_creation: Instant,
+    last_creation: Option<Instant>,
     /// Maximum concurrent circuits
     max_circuits: usize,
     /// Circuit lifetime
@@ -1012,7 +1014,7 @@ pub struct CircuitManager {
 pub struct Circuit {
     /// Circuit ID
     pub id: u64,
-    /// Circuit hops (node public keys)
+    /// Circuit hops (node IDs)
     pub hops: Vec<Vec<u8>>,
     /// Circuit state
     pub state: CircuitState,
@@ -1047,7 +1049,7 @@ impl CircuitManager {
         Self {
             circuits: HashMap::new(),
             creation_rate: 1.0, // 1 circuit per second max
-            last_creation: Instant::now(),
+            last_creation: None,
             max_circuits: 100,
             circuit_lifetime: Duration::from_secs(600), // 10 minutes
             rotation_interval: Duration::from_secs(300), // 5 minutes
@@ -1060,27 +1062,46 @@ impl CircuitManager {
         hops: usize,
         directory: &DirectoryClient,
     ) -> Result<u64, OnionError> {
-        // Rate limiting
-        let elapsed = self.last_creation.elapsed().as_secs_f64();
+        self.check_creation_allowed()?;
+
+        // Select random nodes for the circuit
+        let nodes = directory.select_random_nodes(hops).await?;
+        Ok(self.insert_circuit(nodes))
+    }
+
+    /// Build a circuit of `hops` relays ending at the relay `last_hop`
+    pub async fn build_circuit_to(
+        &mut self,
+        hops: usize,
+        last_hop: &[u8],
+        directory: &DirectoryClient,
+    ) -> Result<u64, OnionError> {
+        self.check_creation_allowed()?;
+        let nodes = directory.select_path_to(hops, last_hop).await?;
+        Ok(self.insert_circuit(nodes))
+    }
+
+    /// Apply the creation rate and circuit limits
+    fn check_creation_allowed(&mut self) -> Result<(), OnionError> {
+        let elapsed = self
+            .last_creation
+            .map_or(f64::INFINITY, |last| last.elapsed().as_secs_f64());
         if elapsed < 1.0 / self.creation_rate {
             return Err(OnionError::RouteError(
                 "Circuit creation rate limit exceeded".into(),
             ));
         }
-
-        // Check circuit limit
         if self.circuits.len() >= self.max_circuits {
-            // Remove oldest inactive circuit
             self.cleanup_inactive_circuits();
             if self.circuits.len() >= self.max_circuits {
                 return Err(OnionError::RouteError("Maximum circuits reached".into()));
             }
         }
+        Ok(())
+    }
 
-        // Select random nodes for the circuit
-        let nodes = directory.select_random_nodes(hops).await?;
-
-        // Create circuit
+    /// Record a circuit being built through `nodes`
+    fn insert_circuit(&mut self, nodes: Vec<Vec<u8>>) -> u64 {
         let circuit_id = thread_rng().next_u64();
         let circuit = Circuit {
             id: circuit_id,
@@ -1093,9 +1114,14 @@ impl CircuitManager {
         };
 
         self.circuits.insert(circuit_id, circuit);
-        self.last_creation = Instant::now();
+        self.last_creation = Some(Instant::now());
+
+        circuit_id
+    }
 
-        Ok(circuit_id)
+    /// Get a circuit by ID
+    pub fn get_circuit(&self, circuit_id: u64) -> Option<&Circuit> {
+        self.circuits.get(&circuit_id)
     }
 
     /// Activate a circuit after successful building
@@ -1125,8 +1151,7 @@ impl CircuitManager {
 
         circuit.state = CircuitState::TearingDown;
 
-        // In a real implementation, this would send teardown messages to all hops
-        // For now, just mark as closed
+        // Destroy cells are sent to the hops by the node carrying the circuit
         circuit.state = CircuitState::Closed;
 
         Ok(())
@@ -1217,29 +1242,27 @@ pub struct CircuitStats {
     pub average_quality: f64,
 }
 
-/// Directory authority client for node discovery
-#[derive(Debug)]
+/// Directory of relays for node discovery
+///
+/// Entries come from signed relay descriptors gossiped over the swarm, see
+/// [`crate::onion_relay`].
+#[derive(Debug, Default)]
 pub struct DirectoryClient {
-    /// Known nodes with their public keys
+    /// Known nodes keyed by node ID (`PeerId` bytes)
     nodes: Arc<TokioMutex<HashMap<Vec<u8>, NodeInfo>>>,
-    /// Directory servers
-    #[allow(dead_code)]
-    directory_servers: Vec<String>,
-    /// Last directory update
-    last_update: Arc<TokioMutex<Instant>>,
-    /// Update interval
-    update_interval: Duration,
+    /// Descriptors the nodes were learned from, served to other nodes
+    descriptors: Arc<TokioMutex<HashMap<Vec<u8>, RelayDescriptor>>>,
 }
 
 /// Node information in directory
 #[derive(Debug, Clone)]
 pub struct NodeInfo {
-    /// Node ID
+    /// Node ID (`PeerId` bytes)
     pub id: Vec<u8>,
     /// ML-KEM public key
     pub public_key: KEMPublicKey,
-    /// Node address
-    pub address: String,
+    /// Node addresses
+    pub addresses: Vec<Multiaddr>,
     /// Node bandwidth (bytes/sec)
     pub bandwidth: u64,
     /// Node uptime
@@ -1248,10 +1271,12 @@ pub struct NodeInfo {
     pub flags: NodeFlags,
     /// Last seen timestamp
     pub last_seen: Instant,
+    /// Descriptor publication time in seconds since the Unix epoch
+    pub published_at: u64,
 }
 
 /// Node capability flags
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NodeFlags {
     /// Node can be used as entry guard
     pub guard: bool,
@@ -1268,16 +1293,79 @@ pub struct NodeFlags {
 impl DirectoryClient {
     /// Create new directory client
     pub fn new() -> Self {
-        Self {
-            nodes: Arc::new(TokioMutex::new(HashMap::new())),
-            directory_servers: vec![
-                "dir1.qudag.net:9030".to_string(),
-                "dir2.qudag.net:9030".to_string(),
-                "dir3.qudag.net:9030".to_string(),
-            ],
-            last_update: Arc::new(TokioMutex::new(Instant::now())),
-            update_interval: Duration::from_secs(3600), // 1 hour
+        Self::default()
+    }
+
+    /// Add or refresh a node from its relay descriptor
+    ///
+    /// The descriptor is verified first; one older than the known entry for
+    /// the same node is ignored.
+    pub async fn insert_descriptor(
+        &self,
+        descriptor: &RelayDescriptor,
+    ) -> Result<PeerId, OnionRelayError> {
+        let info = descriptor.to_node_info()?;
+        let peer_id = PeerId::from_bytes(&info.id).expect("node ID taken from a verified PeerId");
+        let mut nodes = self.nodes.lock().await;
+        match nodes.get(&info.id) {
+            Some(known) if known.published_at >= info.published_at => {}
+            _ => {
+                self.descriptors
+                    .lock()
+                    .await
+                    .insert(info.id.clone(), descriptor.clone());
+                nodes.insert(info.id.clone(), info);
+            }
         }
+        Ok(peer_id)
+    }
+
+    /// Up to `limit` known descriptors, chosen at random
+    pub async fn descriptors(&self, limit: usize) -> Vec<RelayDescriptor> {
+        let descriptors = self.descriptors.lock().await;
+        descriptors
+            .values()
+            .filter(|descriptor| !descriptor.is_expired())
+            .cloned()
+            .choose_multiple(&mut thread_rng(), limit)
+    }
+
+    /// IDs of all known nodes
+    pub async fn node_ids(&self) -> Vec<Vec<u8>> {
+        self.nodes.lock().await.keys().cloned().collect()
+    }
+
+    /// Get a node's directory entry
+    pub async fn node(&self, node_id: &[u8]) -> Option<NodeInfo> {
+        self.nodes.lock().await.get(node_id).cloned()
+    }
+
+    /// Number of known nodes
+    pub async fn len(&self) -> usize {
+        self.nodes.lock().await.len()
+    }
+
+    /// Whether no node is known
+    pub async fn is_empty(&self) -> bool {
+        self.nodes.lock().await.is_empty()
+    }
+
+    /// Remove a node, e.g. after it failed to extend a circuit
+    pub async fn remove(&self, node_id: &[u8]) -> Option<NodeInfo> {
+        self.descriptors.lock().await.remove(node_id);
+        self.nodes.lock().await.remove(node_id)
+    }
+
+    /// Drop nodes whose descriptors have expired
+    pub async fn remove_stale(&self) -> usize {
+        let mut nodes = self.nodes.lock().await;
+        let before = nodes.len();
+        nodes.retain(|_, info| info.last_seen.elapsed() < crate::onion_relay::DESCRIPTOR_LIFETIME);
+        self.descriptors
+            .lock()
+            .await
+            .retain(|id, _| nodes.contains_key(id));
+        before - nodes.len()
     }
 
     /// Get public key for a node
@@ -1289,18 +1377,62 @@ impl DirectoryClient {
             .ok_or_else(|| "Node not found in directory".to_string())
     }
 
-    /// Select random nodes for circuit building
+    /// Select random nodes for circuit building, returning their node IDs
     pub async fn select_random_nodes(&self, count: usize) -> Result<Vec<Vec<u8>>, OnionError> {
-        // Update directory if needed
-        self.update_directory_if_needed().await?;
+        self.select_nodes_excluding(count, &[]).await
+    }
+
+    /// Select random nodes for circuit building, skipping `excluded` node IDs
+    pub async fn select_nodes_excluding(
+        &self,
+        count: usize,
+        excluded: &[Vec<u8>],
+    ) -> Result<Vec<Vec<u8>>, OnionError> {
+        self.select_weighted(count, excluded, true).await
+    }
+
+    /// Select a path of `count` nodes whose last hop is `last_hop`
+    ///
+    /// The last hop only has to be in the directory; it need not be an exit
+    /// since the circuit ends there.
+    pub async fn select_path_to(
+        &self,
+        count: usize,
+        last_hop: &[u8],
+    ) -> Result<Vec<Vec<u8>>, OnionError> {
+        if count == 0 {
+            return Err(OnionError::RouteError(
+                "A path needs at least one hop".into(),
+            ));
+        }
+        if !self.nodes.lock().await.contains_key(last_hop) {
+            return Err(OnionError::RouteError("Last hop not in directory".into()));
+        }
+        let mut path = self
+            .select_weighted(count - 1, &[last_hop.to_vec()], false)
+            .await?;
+        path.push(last_hop.to_vec());
+        Ok(path)
+    }
+
+    async fn select_weighted(
+        &self,
+        count: usize,
+        excluded: &[Vec<u8>],
+        require_exit: bool,
+    ) -> Result<Vec<Vec<u8>>, OnionError> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
 
         let nodes = self.nodes.lock().await;
 
         // Filter active nodes
         let active_nodes: Vec<_> = nodes
             .values()
-            .filter(|n| n.last_seen.elapsed() < Duration::from_secs(3600))
+            .filter(|n| n.last_seen.elapsed() < crate::onion_relay::DESCRIPTOR_LIFETIME)
             .filter(|n| n.flags.stable)
+            .filter(|n| !excluded.contains(&n.id))
             .collect();
 
         if active_nodes.len() < count {
@@ -1312,6 +1444,10 @@ impl DirectoryClient {
         let mut available = active_nodes.clone();
 
         for i in 0..count {
+            if available.is_empty() {
+                return Err(OnionError::RouteError("Not enough active nodes".into()));
+            }
+
             // For entry guard, prefer nodes with guard flag
             if i == 0 {
                 let guards: Vec<_> = available
@@ -1326,72 +1462,37 @@ impl DirectoryClient {
             }
 
             // For exit node, require exit flag
-            if i == count - 1 {
+            if require_exit && i == count - 1 {
                 available.retain(|n| n.flags.exit);
                 if available.is_empty() {
                     return Err(OnionError::RouteError("No exit nodes available".into()));
                 }
             }
 
-            // Select node weighted by bandwidth
-            let total_bandwidth: u64 = available.iter().map(|n| n.bandwidth).sum();
+            // Select node weighted by bandwidth, every node counting at least 1
+            let total_bandwidth: u64 = available.iter().map(|n| n.bandwidth.max(1)).sum();
             let mut target = thread_rng().gen_range(0..total_bandwidth);
 
             for (idx, node) in available.iter().enumerate() {
-                if target < node.bandwidth {
-                    selected.push(node.public_key.as_bytes().to_vec());
+                if target < node.bandwidth.max(1) {
+                    selected.push(node.id.clone());
                     available.remove(idx);
                     break;
                 }
-                target -= node.bandwidth;
+                target -= node.bandwidth.max(1);
             }
-        }
 
-        Ok(selected)
-    }
-
-    /// Update directory from directory servers
-    async fn update_directory_if_needed(&self) -> Result<(), OnionError> {
-        let last_update = *self.last_update.lock().await;
-
-        if last_update.elapsed() < self.update_interval {
-            return Ok(());
+            // Guard filtering only applies to the first hop
+            if i == 0 {
+                available = active_nodes
+                    .iter()
+                    .filter(|n| !selected.contains(&n.id))
+                    .copied()
+                    .collect();
+            }
         }
 
-        // In a real implementation, this would fetch from directory servers
-        // For now, we'll simulate with some dummy nodes
-        self.populate_dummy_nodes().await;
-
-        *self.last_update.lock().await = Instant::now();
-        Ok(())
-    }
-
-    /// Populate dummy nodes for testing
-    async fn populate_dummy_nodes(&self) {
-        let mut nodes = self.nodes.lock().await;
-
-        for i in 0..20 {
-            let (public_key, _) = MlKem768::keygen().unwrap();
-            let node_id = vec![i as u8; 32];
-
-            let info = NodeInfo {
-                id: node_id.clone(),
-                public_key,
-                address: format!("node{}.qudag.net:9001", i),
-                bandwidth: 1_000_000 * (i as u64 + 1), // 1-20 MB/s
-                uptime: Duration::from_secs(3600 * (i as u64 + 1)),
-                flags: NodeFlags {
-                    guard: i < 5,
-                    exit: i >= 15,
-                    directory: i % 5 == 0,
-                    fast: i % 2 == 0,
-                    stable: true,
-                },
-                last_seen: Instant::now(),
-            };
-
-            nodes.insert(node_id, info);
-        }
+        Ok(selected)
     }
 
     /// Measure bandwidth to a node
@@ -1408,7 +1509,7 @@ impl DirectoryClient {
     /// Get load balancing weights for nodes
     pub async fn get_load_balancing_weights(&self) -> HashMap<Vec<u8>, f64> {
         let nodes = self.nodes.lock().await;
-        let total_bandwidth: u64 = nodes.values().map(|n| n.bandwidth).sum();
+        let total_bandwidth: u64 = nodes.values().map(|n| n.bandwidth).sum::<u64>().max(1);
 
         nodes
             .iter()
@@ -0,0 +1,1047 @@
+//! Onion routing across the P2P swarm.
+//!
+//! Relays announce themselves with a [`RelayDescriptor`] gossiped on
+//! [`RELAY_DIRECTORY_TOPIC`]. A descriptor carries the relay's ML-KEM-768
+//! onion key, listen addresses and capability flags, is signed with the
+//! relay's ML-DSA identity key and includes the [`IdentityCertificate`]
+//! binding that key to the relay's `PeerId`.
+//!
+//! Circuits are built one hop at a time over [`ONION_PROTOCOL`], a
+//! request-response protocol whose requests are [`OnionCell`]s and whose
+//! responses travel the circuit backwards:
+//!
+//! * `Create` encapsulates a shared secret to a hop's onion key. The hop
+//!   answers `Created` with a confirmation only the key holder can compute.
+//! * `Relay` carries a [`RelayCommand`] sealed once for every hop up to its
+//!   target. Each hop removes its layer and either forwards the rest along
+//!   the circuit or acts on the command: `Extend` the circuit by one hop or
+//!   deliver `Data`. Each hop seals the answer again on its way back.
+//! * `Destroy` tears a circuit down hop by hop.
+//! * `Backward` carries a [`RelayResponse`] the last hop pushes towards the
+//!   circuit's origin without being asked, each hop adding its layer.
+//! * `Padding` is link cover traffic from [`crate::cover_traffic`].
+//!
+//! The last hop of a circuit can also act as an introduction point or a
+//! rendezvous point for hidden services, see [`crate::hidden_service`]. A
+//! rendezvous point joins two circuits ending at it and pushes whatever
+//! arrives on one of them down the other.
+//!
+//! Gossip only reaches nodes already subscribed, so a node also fetches the
+//! descriptors known to each peer that joins the directory topic with a
+//! `FetchDirectory` cell.
+//!
+//! The state kept by relays is in [`OnionRelay`] and that of circuit owners
+//! in [`ClientCircuit`]; `P2PNode` moves the cells between them.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use libp2p::{Multiaddr, PeerId};
+use qudag_crypto::kem::{
+    Ciphertext as KEMCiphertext, PublicKey as KEMPublicKey, SecretKey as KEMSecretKey,
+};
+use qudag_crypto::ml_dsa::MlDsaPublicKey;
+use qudag_crypto::MlKem768;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::debug;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::node_identity::{IdentityCertificate, NodeIdentity, NodeIdentityError};
+use crate::onion::{NodeFlags, NodeInfo};
+
+/// Request-response protocol carrying onion cells
+pub const ONION_PROTOCOL: &str = "/qudag/onion/1";
+
+/// Gossipsub topic carrying relay descriptors
+pub const RELAY_DIRECTORY_TOPIC: &str = "qudag/relay-directory/1";
+
+/// Relay descriptors older than this are ignored
+pub const DESCRIPTOR_LIFETIME: Duration = Duration::from_secs(3 * 3600);
+
+/// Largest payload carried by a `Data` command
+pub const MAX_ONION_PAYLOAD: usize = 64 * 1024;
+
+/// Upper bound on an encoded descriptor
+pub const MAX_DESCRIPTOR_SIZE: usize = 32 * 1024;
+
+/// Most descriptors sent in answer to a `FetchDirectory` cell
+pub const MAX_DIRECTORY_FETCH: usize = 64;
+
+/// Domain separator for descriptor signatures
+const DESCRIPTOR_DOMAIN: &[u8] = b"qudag-relay-descriptor/1";
+
+/// Domain separator for hop key derivation
+const HOP_KEY_DOMAIN: &[u8] = b"qudag-onion-hop/1";
+
+const NONCE_SIZE: usize = 12;
+
+/// Errors that can occur while relaying onion cells
+#[derive(Debug, Error)]
+pub enum OnionRelayError {
+    #[error("Invalid relay descriptor: {0}")]
+    InvalidDescriptor(&'static str),
+    #[error("Identity error: {0}")]
+    Identity(#[from] NodeIdentityError),
+    #[error("Key exchange failed: {0}")]
+    KeyExchange(String),
+    #[error("Cell authentication failed")]
+    Crypto,
+    #[error("Malformed cell: {0}")]
+    Malformed(String),
+    #[error("Unknown circuit {0}")]
+    UnknownCircuit(u64),
+    #[error("Circuit failed: {0}")]
+    CircuitFailed(String),
+}
+
+/// Relay behaviour of a node
+#[derive(Debug, Clone)]
+pub struct OnionRelayConfig {
+    /// Announce this node as a relay and carry other nodes' circuits
+    pub enabled: bool,
+    /// Capabilities advertised in the descriptor
+    ///
+    /// Exit relays deliver payloads to other QuDAG peers only, so exit is on
+    /// by default.
+    pub flags: NodeFlags,
+    /// Advertised bandwidth in bytes per second, used to weight selection
+    pub bandwidth: u64,
+    /// Circuits carried at once
+    pub max_circuits: usize,
+    /// Relayed circuits without traffic for this long are dropped
+    pub circuit_idle_timeout: Duration,
+    /// How often the descriptor is published again
+    pub republish_interval: Duration,
+}
+
+impl Default for OnionRelayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            flags: NodeFlags {
+                guard: true,
+                exit: true,
+                directory: false,
+                fast: false,
+                stable: true,
+            },
+            bandwidth: 1_000_000,
+            max_circuits: 1024,
+            circuit_idle_timeout: Duration::from_secs(600),
+            republish_interval: Duration::from_secs(1800),
+        }
+    }
+}
+
+/// Signed announcement of a relay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayDescriptor {
+    /// Binding of the relay's ML-DSA key to its `PeerId`
+    pub certificate: IdentityCertificate,
+    /// ML-KEM-768 onion key
+    pub onion_key: Vec<u8>,
+    /// Listen addresses in binary multiaddr encoding
+    pub addresses: Vec<Vec<u8>>,
+    /// Relay capabilities
+    pub flags: NodeFlags,
+    /// Advertised bandwidth in bytes per second
+    pub bandwidth: u64,
+    /// Publication time in seconds since the Unix epoch
+    pub published_at: u64,
+    /// ML-DSA signature over all other fields
+    pub signature: Vec<u8>,
+}
+
+impl RelayDescriptor {
+    /// Create a descriptor signed by `identity`
+    pub fn new(
+        identity: &NodeIdentity,
+        onion_key: &KEMPublicKey,
+        addresses: &[Multiaddr],
+        config: &OnionRelayConfig,
+    ) -> Result<Self, OnionRelayError> {
+        let mut descriptor = Self {
+            certificate: identity.certificate().clone(),
+            onion_key: onion_key.as_bytes().to_vec(),
+            addresses: addresses.iter().map(|addr| addr.to_vec()).collect(),
+            flags: config.flags.clone(),
+            bandwidth: config.bandwidth,
+            published_at: unix_now(),
+            signature: Vec::new(),
+        };
+        descriptor.signature = identity
+            .ml_dsa()
+            .sign(&descriptor.signed_message(), &mut thread_rng())
+            .map_err(NodeIdentityError::from)?;
+        Ok(descriptor)
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        let fields = (
+            &self.certificate,
+            &self.onion_key,
+            &self.addresses,
+            &self.flags,
+            self.bandwidth,
+            self.published_at,
+        );
+        let mut message = DESCRIPTOR_DOMAIN.to_vec();
+        message.extend(bincode::serialize(&fields).expect("descriptor serialization cannot fail"));
+        message
+    }
+
+    /// Check the certificate and signature and return the relay's `PeerId`
+    pub fn verify(&self) -> Result<PeerId, OnionRelayError> {
+        let peer_id = self.certificate.verify()?;
+        MlDsaPublicKey::from_bytes(&self.certificate.ml_dsa_public_key)
+            .and_then(|key| key.verify(&self.signed_message(), &self.signature))
+            .map_err(|_| OnionRelayError::InvalidDescriptor("signature does not verify"))?;
+        if self.onion_key.len() != MlKem768::PUBLIC_KEY_SIZE {
+            return Err(OnionRelayError::InvalidDescriptor("bad onion key"));
+        }
+        if self.published_at > unix_now() + 300 {
+            return Err(OnionRelayError::InvalidDescriptor(
+                "published in the future",
+            ));
+        }
+        Ok(peer_id)
+    }
+
+    /// Whether the descriptor is too old to use
+    pub fn is_expired(&self) -> bool {
+        unix_now().saturating_sub(self.published_at) > DESCRIPTOR_LIFETIME.as_secs()
+    }
+
+    /// Decoded listen addresses, skipping any that do not parse
+    pub fn multiaddrs(&self) -> Vec<Multiaddr> {
+        self.addresses
+            .iter()
+            .filter_map(|addr| Multiaddr::try_from(addr.clone()).ok())
+            .collect()
+    }
+
+    /// Verify the descriptor and turn it into a directory entry
+    pub fn to_node_info(&self) -> Result<NodeInfo, OnionRelayError> {
+        let peer_id = self.verify()?;
+        if self.is_expired() {
+            return Err(OnionRelayError::InvalidDescriptor("expired"));
+        }
+        let age = Duration::from_secs(unix_now().saturating_sub(self.published_at));
+        Ok(NodeInfo {
+            id: peer_id.to_bytes(),
+            public_key: KEMPublicKey::from_bytes(&self.onion_key)
+                .map_err(|_| OnionRelayError::InvalidDescriptor("bad onion key"))?,
+            addresses: self.multiaddrs(),
+            bandwidth: self.bandwidth,
+            uptime: Duration::ZERO,
+            flags: self.flags.clone(),
+            last_seen: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+            published_at: self.published_at,
+        })
+    }
+
+    /// Encode the descriptor for gossip
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("descriptor serialization cannot fail")
+    }
+
+    /// Decode a descriptor received over gossip
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OnionRelayError> {
+        if bytes.len() > MAX_DESCRIPTOR_SIZE {
+            return Err(OnionRelayError::InvalidDescriptor("too large"));
+        }
+        bincode::deserialize(bytes).map_err(|e| OnionRelayError::Malformed(e.to_string()))
+    }
+}
+
+/// Cell sent between adjacent nodes of a circuit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OnionCell {
+    /// Open a circuit to the receiving relay
+    Create {
+        circuit_id: u64,
+        kem_ciphertext: Vec<u8>,
+    },
+    /// Layered payload for the receiving relay or those after it
+    Relay { circuit_id: u64, payload: Vec<u8> },
+    /// Close a circuit
+    Destroy { circuit_id: u64 },
+    /// Payload delivered by an exit relay to its destination
+    Deliver { payload: Vec<u8> },
+    /// Ask for the relay descriptors known to the receiver
+    FetchDirectory,
+    /// Layered push towards the origin of a circuit, sent to the previous hop
+    Backward { circuit_id: u64, payload: Vec<u8> },
+    /// Link cover traffic, dropped unread
+    Padding(Vec<u8>),
+}
+
+/// Answer to an [`OnionCell`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OnionReply {
+    /// Circuit opened, with the key confirmation
+    Created { confirmation: [u8; 32] },
+    /// Layered answer to a `Relay` cell
+    Relay { payload: Vec<u8> },
+    /// Circuit closed
+    Destroyed,
+    /// `Deliver` payload accepted
+    Delivered,
+    /// Known relay descriptors
+    Directory(Vec<RelayDescriptor>),
+    /// The cell could not be handled
+    Failed(String),
+}
+
+/// Command for a single hop, found under that hop's layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayCommand {
+    /// Layers for the hops after this one
+    Forward(Vec<u8>),
+    /// Add a hop after this one
+    Extend {
+        peer_id: Vec<u8>,
+        addresses: Vec<Vec<u8>>,
+        kem_ciphertext: Vec<u8>,
+    },
+    /// Deliver a payload, to this node when there is no destination
+    Data {
+        destination: Option<Vec<u8>>,
+        addresses: Vec<Vec<u8>>,
+        payload: Vec<u8>,
+    },
+    /// Keep the circuit alive or cover it; the filler is dropped unread
+    Padding(Vec<u8>),
+    /// Make this hop an introduction point for a hidden service
+    EstablishIntro { intro_id: [u8; 32] },
+    /// Pass an introduction to the hidden service behind `intro_id`
+    Introduce {
+        intro_id: [u8; 32],
+        payload: Vec<u8>,
+    },
+    /// Make this hop a rendezvous point waiting for `cookie`
+    EstablishRendezvous { cookie: [u8; 32] },
+    /// Join the circuit waiting for `cookie`, passing it `payload`
+    Rendezvous { cookie: [u8; 32], payload: Vec<u8> },
+    /// Payload for the circuit joined at this rendezvous point
+    Stream(Vec<u8>),
+}
+
+/// Answer from a single hop, sealed under that hop's layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayResponse {
+    /// Layers added by the hops after this one
+    Forward(Vec<u8>),
+    /// Hop added, with its key confirmation
+    Extended { confirmation: [u8; 32] },
+    /// Payload delivered
+    Delivered,
+    /// Introduction or rendezvous point established
+    Established,
+    /// Introduction pushed to a hidden service
+    Introduction(Vec<u8>),
+    /// The hidden service joined a rendezvous circuit, with its payload
+    Joined(Vec<u8>),
+    /// Payload pushed from the joined circuit
+    Stream(Vec<u8>),
+    /// The command failed
+    Failed(String),
+}
+
+/// Symmetric keys shared by a circuit owner and one hop
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct HopKeys {
+    forward: [u8; 32],
+    backward: [u8; 32],
+}
+
+impl HopKeys {
+    /// Derive the hop keys and key confirmation from a KEM exchange
+    fn derive(shared_secret: &[u8], kem_ciphertext: &[u8]) -> (Self, [u8; 32]) {
+        Self::derive_in(HOP_KEY_DOMAIN, shared_secret, kem_ciphertext)
+    }
+
+    /// Derive keys and a key confirmation bound to `domain` and `transcript`
+    pub(crate) fn derive_in(
+        domain: &[u8],
+        shared_secret: &[u8],
+        transcript: &[u8],
+    ) -> (Self, [u8; 32]) {
+        let salt = Sha256::new()
+            .chain_update(domain)
+            .chain_update(transcript)
+            .finalize();
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared_secret);
+        let mut keys = Self {
+            forward: [0u8; 32],
+            backward: [0u8; 32],
+        };
+        let mut confirmation = [0u8; 32];
+        hkdf.expand(b"forward", &mut keys.forward)
+            .expect("32 bytes is a valid HKDF output length");
+        hkdf.expand(b"backward", &mut keys.backward)
+            .expect("32 bytes is a valid HKDF output length");
+        hkdf.expand(b"confirm", &mut confirmation)
+            .expect("32 bytes is a valid HKDF output length");
+        (keys, confirmation)
+    }
+
+    pub(crate) fn seal_forward(&self, plaintext: &[u8]) -> Result<Vec<u8>, OnionRelayError> {
+        seal(&self.forward, plaintext)
+    }
+
+    pub(crate) fn open_forward(&self, sealed: &[u8]) -> Result<Vec<u8>, OnionRelayError> {
+        open(&self.forward, sealed)
+    }
+
+    pub(crate) fn seal_backward(&self, plaintext: &[u8]) -> Result<Vec<u8>, OnionRelayError> {
+        seal(&self.backward, plaintext)
+    }
+
+    pub(crate) fn open_backward(&self, sealed: &[u8]) -> Result<Vec<u8>, OnionRelayError> {
+        open(&self.backward, sealed)
+    }
+}
+
+fn seal(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, OnionRelayError> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| OnionRelayError::Crypto)?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+fn open(key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, OnionRelayError> {
+    if sealed.len() < NONCE_SIZE {
+        return Err(OnionRelayError::Crypto);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| OnionRelayError::Crypto)
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, OnionRelayError> {
+    bincode::serialize(value).map_err(|e| OnionRelayError::Malformed(e.to_string()))
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(bytes: &[u8]) -> Result<T, OnionRelayError> {
+    bincode::deserialize(bytes).map_err(|e| OnionRelayError::Malformed(e.to_string()))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Start a key exchange with a hop whose onion key is `onion_key`
+///
+/// Returns the ciphertext for the hop, the resulting keys and the
+/// confirmation the hop must answer with.
+pub fn client_handshake(
+    onion_key: &KEMPublicKey,
+) -> Result<(Vec<u8>, HopKeys, [u8; 32]), OnionRelayError> {
+    let (ciphertext, shared_secret) = MlKem768::encapsulate(onion_key)
+        .map_err(|e| OnionRelayError::KeyExchange(e.to_string()))?;
+    let (keys, confirmation) = HopKeys::derive(shared_secret.as_bytes(), ciphertext.as_bytes());
+    Ok((ciphertext.as_bytes().to_vec(), keys, confirmation))
+}
+
+/// Keys of a circuit owned by this node
+pub struct ClientCircuit {
+    /// Hops in circuit order
+    pub hops: Vec<PeerId>,
+    keys: Vec<HopKeys>,
+}
+
+impl ClientCircuit {
+    /// Start a circuit whose first hop has been created
+    pub fn new(first_hop: PeerId, keys: HopKeys) -> Self {
+        Self {
+            hops: vec![first_hop],
+            keys: vec![keys],
+        }
+    }
+
+    /// Record a hop added by `Extend`
+    pub fn push_hop(&mut self, peer_id: PeerId, keys: HopKeys) {
+        self.hops.push(peer_id);
+        self.keys.push(keys);
+    }
+
+    /// Number of hops built so far
+    pub fn len(&self) -> usize {
+        self.hops.len()
+    }
+
+    /// Whether no hop has been built
+    pub fn is_empty(&self) -> bool {
+        self.hops.is_empty()
+    }
+
+    /// Layer `command` for the hop at `hop`, giving the first hop's payload
+    pub fn seal_for(&self, hop: usize, command: &RelayCommand) -> Result<Vec<u8>, OnionRelayError> {
+        let keys = self
+            .keys
+            .get(..=hop)
+            .ok_or_else(|| OnionRelayError::CircuitFailed(format!("no hop {}", hop)))?;
+        let (target, before) = keys.split_last().expect("range includes the target hop");
+        let mut payload = target.seal_forward(&encode(command)?)?;
+        for keys in before.iter().rev() {
+            payload = keys.seal_forward(&encode(&RelayCommand::Forward(payload))?)?;
+        }
+        Ok(payload)
+    }
+
+    /// Remove the layers of an answer, returning the answering hop and its response
+    pub fn open_reply(&self, payload: &[u8]) -> Result<(usize, RelayResponse), OnionRelayError> {
+        let mut payload = payload.to_vec();
+        for (hop, keys) in self.keys.iter().enumerate() {
+            match decode(&keys.open_backward(&payload)?)? {
+                RelayResponse::Forward(inner) => payload = inner,
+                response => return Ok((hop, response)),
+            }
+        }
+        Err(OnionRelayError::Malformed(
+            "answer layered beyond the last hop".into(),
+        ))
+    }
+}
+
+/// State of a circuit passing through this relay
+struct RelayedCircuit {
+    keys: HopKeys,
+    /// Next hop and the circuit ID used with it
+    next: Option<(PeerId, u64)>,
+    /// What the circuit is for when it ends here
+    role: CircuitRole,
+    last_used: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitRole {
+    Relay,
+    IntroPoint([u8; 32]),
+    RendezvousPoint([u8; 32]),
+    /// Joined at this rendezvous point with another circuit
+    Joined((PeerId, u64)),
+}
+
+/// A forwarded cell whose answer is still awaited
+#[derive(Debug, Clone)]
+pub struct PendingCell {
+    /// Previous hop and its circuit ID
+    circuit: (PeerId, u64),
+    kind: PendingKind,
+}
+
+#[derive(Debug, Clone)]
+enum PendingKind {
+    Extend { next: (PeerId, u64) },
+    Forward,
+    Deliver,
+}
+
+/// What the node should do with a received cell
+#[derive(Debug)]
+pub enum RelayAction {
+    /// Answer the cell
+    Reply(OnionReply),
+    /// Send a cell onwards; answer once [`OnionRelay::complete`] has its answer
+    Forward {
+        peer_id: PeerId,
+        addresses: Vec<Multiaddr>,
+        cell: OnionCell,
+        pending: Box<PendingCell>,
+    },
+    /// Answer `Destroyed` and tear down the next leg, if any
+    Teardown { next: Option<(PeerId, u64)> },
+    /// Payload addressed to this node; answer with `reply`
+    Deliver { payload: Vec<u8>, reply: OnionReply },
+    /// Answer with the known relay descriptors
+    ServeDirectory,
+    /// Answer the cell and push `cell` to the previous hop of another circuit
+    Push {
+        reply: OnionReply,
+        peer_id: PeerId,
+        cell: OnionCell,
+    },
+}
+
+/// Circuits carried by this node for other nodes
+pub struct OnionRelay {
+    local_peer_id: PeerId,
+    onion_public: KEMPublicKey,
+    onion_secret: KEMSecretKey,
+    config: OnionRelayConfig,
+    circuits: HashMap<(PeerId, u64), RelayedCircuit>,
+    /// Previous leg of each extended circuit, keyed by its next leg
+    previous: HashMap<(PeerId, u64), (PeerId, u64)>,
+    /// Circuits of hidden services introduced here
+    intro_points: HashMap<[u8; 32], (PeerId, u64)>,
+    /// Circuits waiting here for a hidden service
+    rendezvous_points: HashMap<[u8; 32], (PeerId, u64)>,
+}
+
+impl OnionRelay {
+    /// Create a relay with a fresh onion key
+    pub fn new(local_peer_id: PeerId, config: OnionRelayConfig) -> Result<Self, OnionRelayError> {
+        let (onion_public, onion_secret) =
+            MlKem768::keygen().map_err(|e| OnionRelayError::KeyExchange(e.to_string()))?;
+        Ok(Self {
+            local_peer_id,
+            onion_public,
+            onion_secret,
+            config,
+            circuits: HashMap::new(),
+            previous: HashMap::new(),
+            intro_points: HashMap::new(),
+            rendezvous_points: HashMap::new(),
+        })
+    }
+
+    /// Onion key advertised in this relay's descriptor
+    pub fn onion_key(&self) -> &KEMPublicKey {
+        &self.onion_public
+    }
+
+    /// Relay configuration
+    pub fn config(&self) -> &OnionRelayConfig {
+        &self.config
+    }
+
+    /// Number of circuits carried
+    pub fn circuit_count(&self) -> usize {
+        self.circuits.len()
+    }
+
+    /// Handle a cell received from `from`
+    pub fn handle_cell(&mut self, from: PeerId, cell: OnionCell) -> RelayAction {
+        match cell {
+            OnionCell::Create {
+                circuit_id,
+                kem_ciphertext,
+            } => RelayAction::Reply(self.create(from, circuit_id, &kem_ciphertext)),
+            OnionCell::Relay {
+                circuit_id,
+                payload,
+            } => self.relay(from, circuit_id, &payload),
+            OnionCell::Destroy { circuit_id } => RelayAction::Teardown {
+                next: self.remove_circuit(&(from, circuit_id)),
+            },
+            OnionCell::Deliver { payload } => {
+                if payload.len() > MAX_ONION_PAYLOAD {
+                    return RelayAction::Reply(OnionReply::Failed("payload too large".into()));
+                }
+                RelayAction::Deliver {
+                    payload,
+                    reply: OnionReply::Delivered,
+                }
+            }
+            OnionCell::FetchDirectory => RelayAction::ServeDirectory,
+            OnionCell::Backward {
+                circuit_id,
+                payload,
+            } => self.backward(from, circuit_id, payload),
+            OnionCell::Padding(_) => RelayAction::Reply(OnionReply::Delivered),
+        }
+    }
+
+    /// Forget a circuit and its links, returning its next leg
+    fn remove_circuit(&mut self, key: &(PeerId, u64)) -> Option<(PeerId, u64)> {
+        let circuit = self.circuits.remove(key)?;
+        match circuit.role {
+            CircuitRole::Relay => {}
+            CircuitRole::IntroPoint(intro_id) => {
+                self.intro_points.remove(&intro_id);
+            }
+            CircuitRole::RendezvousPoint(cookie) => {
+                self.rendezvous_points.remove(&cookie);
+            }
+            CircuitRole::Joined(other) => {
+                if let Some(other) = self.circuits.get_mut(&other) {
+                    other.role = CircuitRole::Relay;
+                }
+            }
+        }
+        if let Some(next) = circuit.next {
+            self.previous.remove(&next);
+        }
+        circuit.next
+    }
+
+    /// Add this hop's layer to a push from the next hop and pass it on
+    fn backward(&mut self, from: PeerId, circuit_id: u64, payload: Vec<u8>) -> RelayAction {
+        let Some(previous) = self.previous.get(&(from, circuit_id)).copied() else {
+            return RelayAction::Reply(OnionReply::Failed(
+                OnionRelayError::UnknownCircuit(circuit_id).to_string(),
+            ));
+        };
+        match self.push(previous, &RelayResponse::Forward(payload)) {
+            Some(cell) => RelayAction::Push {
+                reply: OnionReply::Delivered,
+                peer_id: previous.0,
+                cell,
+            },
+            None => RelayAction::Reply(OnionReply::Failed("push failed".into())),
+        }
+    }
+
+    /// Seal `response` for the origin of `circuit`, giving the cell to send
+    /// to its previous hop
+    fn push(&mut self, circuit: (PeerId, u64), response: &RelayResponse) -> Option<OnionCell> {
+        let relayed = self.circuits.get_mut(&circuit)?;
+        relayed.last_used = Instant::now();
+        let payload = encode(response)
+            .and_then(|plaintext| relayed.keys.seal_backward(&plaintext))
+            .ok()?;
+        Some(OnionCell::Backward {
+            circuit_id: circuit.1,
+            payload,
+        })
+    }
+
+    /// Answer a circuit's command and push `response` down another circuit
+    fn answer_and_push(
+        &mut self,
+        from: PeerId,
+        circuit_id: u64,
+        target: (PeerId, u64),
+        response: RelayResponse,
+    ) -> RelayAction {
+        let Some(cell) = self.push(target, &response) else {
+            return self.answer(
+                from,
+                circuit_id,
+                RelayResponse::Failed("circuit closed".into()),
+            );
+        };
+        RelayAction::Push {
+            reply: self.seal_response(&(from, circuit_id), &RelayResponse::Delivered),
+            peer_id: target.0,
+            cell,
+        }
+    }
+
+    fn create(&mut self, from: PeerId, circuit_id: u64, kem_ciphertext: &[u8]) -> OnionReply {
+        if !self.config.enabled {
+            return OnionReply::Failed("not a relay".into());
+        }
+        if self.circuits.contains_key(&(from, circuit_id)) {
+            return OnionReply::Failed("circuit exists".into());
+        }
+        if self.circuits.len() >= self.config.max_circuits {
+            return OnionReply::Failed("too many circuits".into());
+        }
+        let shared_secret = match KEMCiphertext::from_bytes(kem_ciphertext)
+            .map_err(|e| e.to_string())
+            .and_then(|ciphertext| {
+                MlKem768::decapsulate(&self.onion_secret, &ciphertext).map_err(|e| e.to_string())
+            }) {
+            Ok(shared_secret) => shared_secret,
+            Err(e) => return OnionReply::Failed(format!("key exchange failed: {}", e)),
+        };
+        let (keys, confirmation) = HopKeys::derive(shared_secret.as_bytes(), kem_ciphertext);
+        self.circuits.insert(
+            (from, circuit_id),
+            RelayedCircuit {
+                keys,
+                next: None,
+                role: CircuitRole::Relay,
+                last_used: Instant::now(),
+            },
+        );
+        debug!("Created relayed circuit {} from {}", circuit_id, from);
+        OnionReply::Created { confirmation }
+    }
+
+    fn relay(&mut self, from: PeerId, circuit_id: u64, payload: &[u8]) -> RelayAction {
+        let Some(circuit) = self.circuits.get_mut(&(from, circuit_id)) else {
+            return RelayAction::Reply(OnionReply::Failed(
+                OnionRelayError::UnknownCircuit(circuit_id).to_string(),
+            ));
+        };
+        circuit.last_used = Instant::now();
+        let command = match circuit
+            .keys
+            .open_forward(payload)
+            .and_then(|plaintext| decode(&plaintext))
+        {
+            Ok(command) => command,
+            Err(e) => return RelayAction::Reply(OnionReply::Failed(e.to_string())),
+        };
+        let next = circuit.next;
+        let role = circuit.role;
+        let pending = |kind| {
+            Box::new(PendingCell {
+                circuit: (from, circuit_id),
+                kind,
+            })
+        };
+
+        match command {
+            RelayCommand::Forward(inner) => match next {
+                Some((peer_id, next_id)) => RelayAction::Forward {
+                    peer_id,
+                    addresses: Vec::new(),
+                    cell: OnionCell::Relay {
+                        circuit_id: next_id,
+                        payload: inner,
+                    },
+                    pending: pending(PendingKind::Forward),
+                },
+                None => self.answer(
+                    from,
+                    circuit_id,
+                    RelayResponse::Failed("no next hop".into()),
+                ),
+            },
+            RelayCommand::Extend {
+                peer_id,
+                addresses,
+                kem_ciphertext,
+            } => {
+                let peer_id = match PeerId::from_bytes(&peer_id) {
+                    Ok(peer_id)
+                        if next.is_none()
+                            && role == CircuitRole::Relay
+                            && peer_id != self.local_peer_id =>
+                    {
+                        peer_id
+                    }
+                    _ => {
+                        return self.answer(
+                            from,
+                            circuit_id,
+                            RelayResponse::Failed("cannot extend".into()),
+                        )
+                    }
+                };
+                let next_id = thread_rng().next_u64();
+                RelayAction::Forward {
+                    peer_id,
+                    addresses: decode_addresses(&addresses),
+                    cell: OnionCell::Create {
+                        circuit_id: next_id,
+                        kem_ciphertext,
+                    },
+                    pending: pending(PendingKind::Extend {
+                        next: (peer_id, next_id),
+                    }),
+                }
+            }
+            RelayCommand::Data {
+                destination,
+                addresses,
+                payload,
+            } => {
+                if payload.len() > MAX_ONION_PAYLOAD {
+                    return self.answer(
+                        from,
+                        circuit_id,
+                        RelayResponse::Failed("payload too large".into()),
+                    );
+                }
+                let destination = match destination.as_deref().map(PeerId::from_bytes) {
+                    None => None,
+                    Some(Ok(peer_id)) if peer_id == self.local_peer_id => None,
+                    Some(Ok(peer_id)) => Some(peer_id),
+                    Some(Err(_)) => {
+                        return self.answer(
+                            from,
+                            circuit_id,
+                            RelayResponse::Failed("bad destination".into()),
+                        )
+                    }
+                };
+                match destination {
+                    None => {
+                        let RelayAction::Reply(reply) =
+                            self.answer(from, circuit_id, RelayResponse::Delivered)
+                        else {
+                            unreachable!("answer always replies")
+                        };
+                        RelayAction::Deliver { payload, reply }
+                    }
+                    Some(_) if !self.config.flags.exit => self.answer(
+                        from,
+                        circuit_id,
+                        RelayResponse::Failed("not an exit".into()),
+                    ),
+                    Some(peer_id) => RelayAction::Forward {
+                        peer_id,
+                        addresses: decode_addresses(&addresses),
+                        cell: OnionCell::Deliver { payload },
+                        pending: pending(PendingKind::Deliver),
+                    },
+                }
+            }
+            RelayCommand::Padding(_) => self.answer(from, circuit_id, RelayResponse::Delivered),
+            command => self.rendezvous_command(from, circuit_id, next, role, command),
+        }
+    }
+
+    /// Handle the hidden service commands of a circuit ending here
+    fn rendezvous_command(
+        &mut self,
+        from: PeerId,
+        circuit_id: u64,
+        next: Option<(PeerId, u64)>,
+        role: CircuitRole,
+        command: RelayCommand,
+    ) -> RelayAction {
+        let key = (from, circuit_id);
+        let fail = |relay: &Self, reason: &str| {
+            relay.answer(from, circuit_id, RelayResponse::Failed(reason.into()))
+        };
+        if next.is_some() {
+            return fail(self, "circuit continues past this hop");
+        }
+        let too_large = match &command {
+            RelayCommand::Introduce { payload, .. }
+            | RelayCommand::Rendezvous { payload, .. }
+            | RelayCommand::Stream(payload) => payload.len() > MAX_ONION_PAYLOAD,
+            _ => false,
+        };
+        if too_large {
+            return fail(self, "payload too large");
+        }
+
+        match command {
+            RelayCommand::EstablishIntro { intro_id } => {
+                if role != CircuitRole::Relay {
+                    return fail(self, "circuit already in use");
+                }
+                // The first live circuit keeps an introduction ID
+                if self.intro_points.contains_key(&intro_id) {
+                    return fail(self, "introduction ID in use");
+                }
+                self.intro_points.insert(intro_id, key);
+                self.set_role(&key, CircuitRole::IntroPoint(intro_id));
+                self.answer(from, circuit_id, RelayResponse::Established)
+            }
+            RelayCommand::Introduce { intro_id, payload } => {
+                match self.intro_points.get(&intro_id).copied() {
+                    Some(service) => self.answer_and_push(
+                        from,
+                        circuit_id,
+                        service,
+                        RelayResponse::Introduction(payload),
+                    ),
+                    None => fail(self, "unknown introduction ID"),
+                }
+            }
+            RelayCommand::EstablishRendezvous { cookie } => {
+                if role != CircuitRole::Relay {
+                    return fail(self, "circuit already in use");
+                }
+                if self.rendezvous_points.contains_key(&cookie) {
+                    return fail(self, "rendezvous cookie in use");
+                }
+                self.rendezvous_points.insert(cookie, key);
+                self.set_role(&key, CircuitRole::RendezvousPoint(cookie));
+                self.answer(from, circuit_id, RelayResponse::Established)
+            }
+            RelayCommand::Rendezvous { cookie, payload } => {
+                if role != CircuitRole::Relay {
+                    return fail(self, "circuit already in use");
+                }
+                let Some(client) = self.rendezvous_points.remove(&cookie) else {
+                    return fail(self, "unknown rendezvous cookie");
+                };
+                self.set_role(&client, CircuitRole::Joined(key));
+                self.set_role(&key, CircuitRole::Joined(client));
+                self.answer_and_push(from, circuit_id, client, RelayResponse::Joined(payload))
+            }
+            RelayCommand::Stream(payload) => match role {
+                CircuitRole::Joined(other) => {
+                    self.answer_and_push(from, circuit_id, other, RelayResponse::Stream(payload))
+                }
+                _ => fail(self, "circuit not joined"),
+            },
+            _ => fail(self, "unexpected command"),
+        }
+    }
+
+    fn set_role(&mut self, key: &(PeerId, u64), role: CircuitRole) {
+        if let Some(circuit) = self.circuits.get_mut(key) {
+            circuit.role = role;
+        }
+    }
+
+    /// Seal a response for the previous hop of a circuit
+    fn answer(&self, from: PeerId, circuit_id: u64, response: RelayResponse) -> RelayAction {
+        RelayAction::Reply(self.seal_response(&(from, circuit_id), &response))
+    }
+
+    fn seal_response(&self, circuit: &(PeerId, u64), response: &RelayResponse) -> OnionReply {
+        let Some(relayed) = self.circuits.get(circuit) else {
+            return OnionReply::Failed(OnionRelayError::UnknownCircuit(circuit.1).to_string());
+        };
+        match encode(response).and_then(|plaintext| relayed.keys.seal_backward(&plaintext)) {
+            Ok(payload) => OnionReply::Relay { payload },
+            Err(e) => OnionReply::Failed(e.to_string()),
+        }
+    }
+
+    /// Build the answer to a forwarded cell from the next node's answer
+    pub fn complete(
+        &mut self,
+        pending: PendingCell,
+        result: Result<OnionReply, String>,
+    ) -> OnionReply {
+        let response = match (pending.kind, result) {
+            (PendingKind::Extend { next }, Ok(OnionReply::Created { confirmation })) => {
+                match self.circuits.get_mut(&pending.circuit) {
+                    Some(circuit) if circuit.role == CircuitRole::Relay => {
+                        circuit.next = Some(next);
+                    }
+                    _ => return OnionReply::Destroyed,
+                }
+                self.previous.insert(next, pending.circuit);
+                RelayResponse::Extended { confirmation }
+            }
+            (PendingKind::Forward, Ok(OnionReply::Relay { payload })) => {
+                RelayResponse::Forward(payload)
+            }
+            (PendingKind::Deliver, Ok(OnionReply::Delivered)) => RelayResponse::Delivered,
+            (_, Ok(OnionReply::Failed(e))) | (_, Err(e)) => RelayResponse::Failed(e),
+            (_, Ok(reply)) => RelayResponse::Failed(format!("unexpected answer {:?}", reply)),
+        };
+        self.seal_response(&pending.circuit, &response)
+    }
+
+    /// Drop circuits that carried no traffic for the idle timeout
+    ///
+    /// Returns the next hops of dropped circuits, to be torn down as well.
+    pub fn expire_idle(&mut self) -> Vec<(PeerId, u64)> {
+        let timeout = self.config.circuit_idle_timeout;
+        let idle: Vec<_> = self
+            .circuits
+            .iter()
+            .filter(|(_, circuit)| circuit.last_used.elapsed() >= timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        idle.iter()
+            .filter_map(|key| self.remove_circuit(key))
+            .collect()
+    }
+}
+
+fn decode_addresses(addresses: &[Vec<u8>]) -> Vec<Multiaddr> {
+    addresses
+        .iter()
+        .filter_map(|addr| Multiaddr::try_from(addr.clone()).ok())
+        .collect()
+}
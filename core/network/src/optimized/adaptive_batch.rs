@@ -57,6 +57,10 @@ struct QueuePressureMetrics {
     average_queue_length: f64,
     queue_growth_rate: f64,
     last_update: Instant,
+    /// Arrival time of the previous message
+    last_arrival: Option<Instant>,
+    /// Smoothed gap between message arrivals, in seconds
+    arrival_interval: Option<f64>,
 }
 
 /// Adaptive threshold calculations
@@ -95,10 +99,13 @@ impl<T> AdaptiveBatcher<T> {
                 average_queue_length: 0.0,
                 queue_growth_rate: 0.0,
                 last_update: Instant::now(),
+                last_arrival: None,
+                arrival_interval: None,
             },
             adaptive_thresholds: AdaptiveThresholds {
                 current_latency_target: Duration::from_micros(config.base_latency_micros),
-                current_batch_size_target: config.min_batch_size,
+                // Until the arrival rate is known only the latency bounds flush
+                current_batch_size_target: config.max_batch_size,
                 load_factor: 0.0,
                 pressure_coefficient: 1.0,
             },
@@ -113,7 +120,11 @@ impl<T> AdaptiveBatcher<T> {
 
     /// Add a message to the current batch
     pub fn add_message(&mut self, message: T) -> Option<Vec<T>> {
+        if self.current_batch.is_empty() {
+            self.batch_start_time = Instant::now();
+        }
         self.current_batch.push_back(message);
+        self.update_arrival_rate();
         self.update_queue_metrics();
 
         if self.should_flush() {
@@ -180,6 +191,48 @@ impl<T> AdaptiveBatcher<T> {
         false
     }
 
+    /// Time left before the current batch must be flushed, if any is pending
+    pub fn time_until_flush(&self) -> Option<Duration> {
+        if self.current_batch.is_empty() {
+            return None;
+        }
+        let deadline = self
+            .adaptive_thresholds
+            .current_latency_target
+            .min(Duration::from_micros(self.config.max_latency_micros));
+        Some(deadline.saturating_sub(self.batch_start_time.elapsed()))
+    }
+
+    /// Track the message arrival rate and size batches to match it
+    ///
+    /// A batch is worth waiting for only when more messages are expected
+    /// within the latency target; at low rates messages go out on their own.
+    fn update_arrival_rate(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.queue_metrics.last_arrival {
+            let gap = now.duration_since(last).as_secs_f64();
+            let interval = match self.queue_metrics.arrival_interval {
+                Some(interval) => {
+                    interval * (1.0 - self.config.smoothing_factor)
+                        + gap * self.config.smoothing_factor
+                }
+                None => gap,
+            };
+            self.queue_metrics.arrival_interval = Some(interval);
+
+            let expected = self
+                .adaptive_thresholds
+                .current_latency_target
+                .as_secs_f64()
+                / interval.max(f64::EPSILON);
+            self.adaptive_thresholds.current_batch_size_target = (expected.ceil() as usize).clamp(
+                self.config.min_batch_size.max(1),
+                self.config.max_batch_size,
+            );
+        }
+        self.queue_metrics.last_arrival = Some(now);
+    }
+
     /// Update queue pressure metrics
     fn update_queue_metrics(&mut self) {
         let now = Instant::now();
@@ -276,29 +329,6 @@ impl<T> AdaptiveBatcher<T> {
             .current_latency_target
             .max(Duration::from_micros(self.config.base_latency_micros / 10))
             .min(Duration::from_micros(self.config.max_latency_micros));
-
-        // Adjust batch size target based on load and performance
-        let optimal_batch_size = self.find_optimal_batch_size();
-        self.adaptive_thresholds.current_batch_size_target = optimal_batch_size
-            .max(self.config.min_batch_size)
-            .min(self.config.max_batch_size);
-    }
-
-    /// Find optimal batch size based on performance history
-    fn find_optimal_batch_size(&self) -> usize {
-        if self.performance_history.batch_performance_map.is_empty() {
-            return self.config.min_batch_size;
-        }
-
-        // Find batch size with best performance score
-        let (best_batch_size, _score) = self
-            .performance_history
-            .batch_performance_map
-            .iter()
-            .max_by(|(_, score_a), (_, score_b)| score_a.partial_cmp(score_b).unwrap())
-            .unwrap();
-
-        *best_batch_size
     }
 
     /// Calculate average latency from recent samples
@@ -0,0 +1,34 @@
+//! Wire types for chunked payloads
+//!
+//! These are part of the request frame format and are compiled regardless of
+//! the `message-chunking` feature, so every build decodes the same frames.
+
+use serde::{Deserialize, Serialize};
+
+/// Message chunk header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkHeader {
+    /// Message ID
+    pub message_id: String,
+    /// Total number of chunks
+    pub total_chunks: u32,
+    /// Current chunk index
+    pub chunk_index: u32,
+    /// Chunk size
+    pub chunk_size: usize,
+    /// Message hash (for verification)
+    pub message_hash: [u8; 32],
+    /// Original message size
+    pub original_size: usize,
+    /// Whether the chunked data is zstd-compressed
+    pub compressed: bool,
+}
+
+/// Chunked message wrapper
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkedMessage {
+    /// Chunk header
+    pub header: ChunkHeader,
+    /// Chunk data
+    pub data: Vec<u8>,
+}
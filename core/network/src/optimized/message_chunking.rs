@@ -1,8 +1,7 @@
 #![deny(unsafe_code)]
 
-use crate::types::{MessagePriority, NetworkError, NetworkMessage, PeerId};
-use blake3::Hash;
-use serde::{Deserialize, Serialize};
+pub use super::chunk_frame::{ChunkHeader, ChunkedMessage};
+use crate::types::{NetworkError, NetworkMessage};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -18,32 +17,6 @@ const MAX_CHUNKS: usize = 10000;
 /// Chunk timeout duration
 const CHUNK_TIMEOUT: Duration = Duration::from_secs(30);
 
-/// Message chunk header
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChunkHeader {
-    /// Message ID
-    pub message_id: String,
-    /// Total number of chunks
-    pub total_chunks: u32,
-    /// Current chunk index
-    pub chunk_index: u32,
-    /// Chunk size
-    pub chunk_size: usize,
-    /// Message hash (for verification)
-    pub message_hash: [u8; 32],
-    /// Original message size
-    pub original_size: usize,
-}
-
-/// Chunked message wrapper
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChunkedMessage {
-    /// Chunk header
-    pub header: ChunkHeader,
-    /// Chunk data
-    pub data: Vec<u8>,
-}
-
 /// Streaming message chunk
 #[derive(Debug)]
 pub struct StreamingChunk {
@@ -64,6 +37,10 @@ pub struct ReassemblyState {
     pub original_size: usize,
     /// Message hash for verification
     pub message_hash: [u8; 32],
+    /// Whether the reassembled data must be decompressed
+    pub compressed: bool,
+    /// Bytes buffered for this message so far
+    pub received_bytes: usize,
     /// First chunk received time
     pub started_at: Instant,
     /// Last activity time
@@ -72,10 +49,10 @@ pub struct ReassemblyState {
 
 /// Message chunking and reassembly system
 pub struct MessageChunker {
-    /// Reassembly states for incoming messages
+    /// Reassembly states for incoming messages, keyed by sender and message ID
     reassembly_states: Arc<RwLock<HashMap<String, ReassemblyState>>>,
-    /// Chunk cache for optimization
-    chunk_cache: Arc<Mutex<lru::LruCache<String, Vec<u8>>>>,
+    /// Recently completed messages, so retransmitted chunks are not delivered twice
+    chunk_cache: Arc<Mutex<lru::LruCache<String, ()>>>,
     /// Configuration
     config: ChunkerConfig,
 }
@@ -93,6 +70,10 @@ pub struct ChunkerConfig {
     pub compression_threshold: usize,
     /// Cache size
     pub cache_size: usize,
+    /// Largest message accepted, before compression
+    pub max_message_size: usize,
+    /// Cap on chunk data buffered across all incomplete messages
+    pub max_reassembly_bytes: usize,
 }
 
 impl Default for ChunkerConfig {
@@ -103,6 +84,8 @@ impl Default for ChunkerConfig {
             enable_compression: true,
             compression_threshold: 1024,
             cache_size: 1000,
+            max_message_size: 16 * 1024 * 1024,
+            max_reassembly_bytes: 64 * 1024 * 1024,
         }
     }
 }
@@ -113,46 +96,57 @@ impl MessageChunker {
         Self {
             reassembly_states: Arc::new(RwLock::new(HashMap::new())),
             chunk_cache: Arc::new(Mutex::new(lru::LruCache::new(
-                std::num::NonZeroUsize::new(config.cache_size).unwrap(),
+                std::num::NonZeroUsize::new(config.cache_size.max(1)).unwrap(),
             ))),
             config,
         }
     }
 
     /// Chunk a message for transmission
+    ///
+    /// Returns no chunks when the payload fits in a single chunk.
     pub async fn chunk_message(
         &self,
         message: &NetworkMessage,
     ) -> Result<Vec<ChunkedMessage>, NetworkError> {
         let payload = &message.payload;
-        
+
+        if payload.len() > self.config.max_message_size {
+            return Err(NetworkError::ContentTooLarge);
+        }
+
         // Check if chunking is needed
         if payload.len() <= self.config.max_chunk_size {
             return Ok(vec![]); // No chunking needed
         }
 
-        // Optionally compress the payload
-        let data = if self.config.enable_compression && payload.len() > self.config.compression_threshold {
-            self.compress_data(payload)?
+        // Compress the payload when that makes it smaller
+        let compressed = if self.config.enable_compression
+            && payload.len() > self.config.compression_threshold
+        {
+            Some(self.compress_data(payload)?).filter(|data| data.len() < payload.len())
         } else {
-            payload.clone()
+            None
         };
+        let is_compressed = compressed.is_some();
+        let data = compressed.as_deref().unwrap_or(payload);
 
         // Calculate message hash
-        let message_hash = blake3::hash(&data);
+        let message_hash = blake3::hash(data);
 
         // Calculate number of chunks
-        let total_chunks = ((data.len() + self.config.max_chunk_size - 1) / self.config.max_chunk_size) as u32;
-        
+        let total_chunks = data.len().div_ceil(self.config.max_chunk_size) as u32;
+
         if total_chunks > MAX_CHUNKS as u32 {
-            return Err(NetworkError::ValidationError(
-                format!("Message too large: {} chunks exceeds maximum {}", total_chunks, MAX_CHUNKS)
-            ));
+            return Err(NetworkError::MessageError(format!(
+                "Message too large: {} chunks exceeds maximum {}",
+                total_chunks, MAX_CHUNKS
+            )));
         }
 
         // Create chunks
         let mut chunks = Vec::with_capacity(total_chunks as usize);
-        
+
         for (index, chunk_data) in data.chunks(self.config.max_chunk_size).enumerate() {
             let header = ChunkHeader {
                 message_id: message.id.clone(),
@@ -161,6 +155,7 @@ impl MessageChunker {
                 chunk_size: chunk_data.len(),
                 message_hash: *message_hash.as_bytes(),
                 original_size: payload.len(),
+                compressed: is_compressed,
             };
 
             chunks.push(ChunkedMessage {
@@ -185,61 +180,90 @@ impl MessageChunker {
         &self,
         chunk: ChunkedMessage,
     ) -> Result<Option<Vec<u8>>, NetworkError> {
-        let message_id = chunk.header.message_id.clone();
-        
+        self.process_chunk_from("", chunk).await
+    }
+
+    /// Process an incoming chunk from `sender`
+    ///
+    /// Message IDs are only unique per sender, so reassembly is keyed by
+    /// both. Returns the original payload once the last chunk arrives.
+    pub async fn process_chunk_from(
+        &self,
+        sender: &str,
+        chunk: ChunkedMessage,
+    ) -> Result<Option<Vec<u8>>, NetworkError> {
+        let message_id = format!("{}/{}", sender, chunk.header.message_id);
+
         // Validate chunk
         self.validate_chunk(&chunk)?;
 
-        // Check cache first
-        if let Some(cached) = self.chunk_cache.lock().await.get(&message_id) {
-            return Ok(Some(cached.clone()));
+        // Ignore retransmissions of messages already delivered
+        if self.chunk_cache.lock().await.contains(&message_id) {
+            return Ok(None);
         }
 
         let mut states = self.reassembly_states.write().await;
-        
+
+        // Stay within the reassembly memory cap
+        let buffered: usize = states.values().map(|state| state.received_bytes).sum();
+        if buffered + chunk.data.len() > self.config.max_reassembly_bytes {
+            warn!(
+                "Dropping chunk for message {}: reassembly buffer full ({} bytes)",
+                message_id, buffered
+            );
+            return Err(NetworkError::ContentTooLarge);
+        }
+
         // Get or create reassembly state
-        let state = states.entry(message_id.clone()).or_insert_with(|| {
-            ReassemblyState {
+        let state = states
+            .entry(message_id.clone())
+            .or_insert_with(|| ReassemblyState {
                 chunks: HashMap::new(),
                 total_chunks: chunk.header.total_chunks,
                 original_size: chunk.header.original_size,
                 message_hash: chunk.header.message_hash,
+                compressed: chunk.header.compressed,
+                received_bytes: 0,
                 started_at: Instant::now(),
                 last_activity: Instant::now(),
-            }
-        });
+            });
 
         // Update last activity
         state.last_activity = Instant::now();
 
         // Validate consistency
-        if state.total_chunks != chunk.header.total_chunks {
-            return Err(NetworkError::ValidationError(
-                "Inconsistent chunk count".into()
+        if state.total_chunks != chunk.header.total_chunks
+            || state.original_size != chunk.header.original_size
+            || state.message_hash != chunk.header.message_hash
+            || state.compressed != chunk.header.compressed
+        {
+            return Err(NetworkError::MessageError(
+                "Inconsistent chunk header".into(),
             ));
         }
 
         // Add chunk to state
-        state.chunks.insert(
+        state.received_bytes += chunk.data.len();
+        if let Some(replaced) = state.chunks.insert(
             chunk.header.chunk_index,
             StreamingChunk {
                 data: chunk.data,
                 received_at: Instant::now(),
             },
-        );
+        ) {
+            state.received_bytes -= replaced.data.len();
+        }
 
         // Check if all chunks received
         if state.chunks.len() == state.total_chunks as usize {
             // Reassemble message
-            let reassembled = self.reassemble_message(state)?;
-            
-            // Cache the result
-            self.chunk_cache.lock().await.put(message_id.clone(), reassembled.clone());
-            
-            // Clean up state
+            let reassembled = self.reassemble_message(state);
+
+            // Clean up state and remember the message as delivered
             states.remove(&message_id);
-            
-            Ok(Some(reassembled))
+            self.chunk_cache.lock().await.put(message_id, ());
+
+            reassembled.map(Some)
         } else {
             debug!(
                 "Received chunk {}/{} for message {}",
@@ -254,64 +278,77 @@ impl MessageChunker {
     /// Validate a chunk
     fn validate_chunk(&self, chunk: &ChunkedMessage) -> Result<(), NetworkError> {
         if chunk.header.chunk_index >= chunk.header.total_chunks {
-            return Err(NetworkError::ValidationError(
-                "Invalid chunk index".into()
-            ));
+            return Err(NetworkError::MessageError("Invalid chunk index".into()));
+        }
+
+        if chunk.header.total_chunks as usize > MAX_CHUNKS {
+            return Err(NetworkError::MessageError("Too many chunks".into()));
         }
 
         if chunk.data.len() != chunk.header.chunk_size {
-            return Err(NetworkError::ValidationError(
-                "Chunk size mismatch".into()
-            ));
+            return Err(NetworkError::MessageError("Chunk size mismatch".into()));
         }
 
         if chunk.header.chunk_size > self.config.max_chunk_size {
-            return Err(NetworkError::ValidationError(
-                "Chunk size exceeds maximum".into()
+            return Err(NetworkError::MessageError(
+                "Chunk size exceeds maximum".into(),
             ));
         }
 
+        if chunk.header.original_size > self.config.max_message_size {
+            return Err(NetworkError::ContentTooLarge);
+        }
+
         Ok(())
     }
 
     /// Reassemble chunks into original message
     fn reassemble_message(&self, state: &ReassemblyState) -> Result<Vec<u8>, NetworkError> {
-        let mut data = Vec::with_capacity(state.original_size);
-        
+        let mut data = Vec::with_capacity(state.received_bytes);
+
         // Reassemble in order
         for i in 0..state.total_chunks {
-            let chunk = state.chunks.get(&i)
-                .ok_or_else(|| NetworkError::ValidationError(
-                    format!("Missing chunk {}", i)
-                ))?;
-            
+            let chunk = state
+                .chunks
+                .get(&i)
+                .ok_or_else(|| NetworkError::MessageError(format!("Missing chunk {}", i)))?;
+
             data.extend_from_slice(&chunk.data);
         }
 
         // Verify hash
         let computed_hash = blake3::hash(&data);
         if *computed_hash.as_bytes() != state.message_hash {
-            return Err(NetworkError::ValidationError(
-                "Message hash verification failed".into()
+            return Err(NetworkError::MessageError(
+                "Message hash verification failed".into(),
             ));
         }
 
         // Decompress if needed
-        if self.config.enable_compression && data.len() < state.original_size {
-            self.decompress_data(&data)
+        let data = if state.compressed {
+            self.decompress_data(&data, state.original_size)?
         } else {
-            Ok(data)
+            data
+        };
+        if data.len() != state.original_size {
+            return Err(NetworkError::MessageError(
+                "Reassembled size mismatch".into(),
+            ));
         }
+        Ok(data)
     }
 
     /// Clean up expired reassembly states
-    pub async fn cleanup_expired(&self) {
+    ///
+    /// Returns the number of incomplete messages dropped.
+    pub async fn cleanup_expired(&self) -> usize {
         let mut states = self.reassembly_states.write().await;
         let now = Instant::now();
-        
+        let before = states.len();
+
         states.retain(|id, state| {
-            let expired = now.duration_since(state.last_activity) < self.config.chunk_timeout;
-            if !expired {
+            let active = now.duration_since(state.last_activity) < self.config.chunk_timeout;
+            if !active {
                 warn!(
                     "Cleaning up expired message reassembly for {} ({}/{} chunks received)",
                     id,
@@ -319,8 +356,9 @@ impl MessageChunker {
                     state.total_chunks
                 );
             }
-            expired
+            active
         });
+        before - states.len()
     }
 
     /// Compress data using zstd
@@ -329,9 +367,9 @@ impl MessageChunker {
             .map_err(|e| NetworkError::Internal(format!("Compression failed: {}", e)))
     }
 
-    /// Decompress data using zstd
-    fn decompress_data(&self, data: &[u8]) -> Result<Vec<u8>, NetworkError> {
-        zstd::decode_all(data)
+    /// Decompress data using zstd, refusing output larger than `max_size`
+    fn decompress_data(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, NetworkError> {
+        zstd::bulk::decompress(data, max_size)
             .map_err(|e| NetworkError::Internal(format!("Decompression failed: {}", e)))
     }
 
@@ -339,13 +377,12 @@ impl MessageChunker {
     pub async fn get_stats(&self) -> ChunkerStats {
         let states = self.reassembly_states.read().await;
         let cache = self.chunk_cache.lock().await;
-        
+
         ChunkerStats {
             active_reassemblies: states.len(),
             cache_size: cache.len(),
-            total_chunks_waiting: states.values()
-                .map(|s| s.chunks.len())
-                .sum(),
+            total_chunks_waiting: states.values().map(|s| s.chunks.len()).sum(),
+            buffered_bytes: states.values().map(|s| s.received_bytes).sum(),
         }
     }
 }
@@ -359,13 +396,15 @@ pub struct ChunkerStats {
     pub cache_size: usize,
     /// Total chunks waiting
     pub total_chunks_waiting: usize,
+    /// Chunk data buffered for incomplete messages
+    pub buffered_bytes: usize,
 }
 
 /// Extension trait for NetworkMessage to support chunking
 pub trait ChunkableMessage {
     /// Check if message needs chunking
     fn needs_chunking(&self, max_size: usize) -> bool;
-    
+
     /// Create chunked variant of the message
     fn into_chunked(self) -> ChunkedNetworkMessage;
 }
@@ -374,7 +413,7 @@ impl ChunkableMessage for NetworkMessage {
     fn needs_chunking(&self, max_size: usize) -> bool {
         self.payload.len() > max_size
     }
-    
+
     fn into_chunked(self) -> ChunkedNetworkMessage {
         ChunkedNetworkMessage {
             base: self,
@@ -395,6 +434,7 @@ pub struct ChunkedNetworkMessage {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::MessagePriority;
     use uuid::Uuid;
 
     #[tokio::test]
@@ -448,7 +488,7 @@ mod tests {
 
         // Chunk and reassemble
         let chunks = chunker.chunk_message(&message).await.unwrap();
-        
+
         let mut reassembled_data = None;
         for chunk in chunks {
             if let Some(data) = chunker.process_chunk(chunk).await.unwrap() {
@@ -479,18 +519,18 @@ mod tests {
         };
 
         let chunks = chunker.chunk_message(&message).await.unwrap();
-        
+
         // Process chunks out of order
         let mut reassembled_data = None;
-        
+
         // Process chunk 2, then 0, then 1
         chunker.process_chunk(chunks[2].clone()).await.unwrap();
         chunker.process_chunk(chunks[0].clone()).await.unwrap();
-        
+
         if let Some(data) = chunker.process_chunk(chunks[1].clone()).await.unwrap() {
             reassembled_data = Some(data);
         }
 
         assert_eq!(reassembled_data.unwrap(), original_data);
     }
-}
\ No newline at end of file
+}
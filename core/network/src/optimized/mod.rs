@@ -8,16 +8,22 @@
 //! - Message chunking for large payloads
 
 // pub mod zero_copy;
+#[cfg(feature = "adaptive-batching")]
 pub mod adaptive_batch;
+pub mod chunk_frame;
+#[cfg(feature = "message-chunking")]
 pub mod message_chunking;
 // pub mod lock_free;
 // pub mod numa_aware;
 
 // pub use zero_copy::ZeroCopyConnection;
-pub use adaptive_batch::AdaptiveBatcher;
+#[cfg(feature = "adaptive-batching")]
+pub use adaptive_batch::{AdaptiveBatcher, BatchConfig, BatcherStats};
+pub use chunk_frame::{ChunkHeader, ChunkedMessage};
+#[cfg(feature = "message-chunking")]
 pub use message_chunking::{
-    ChunkHeader, ChunkedMessage, ChunkedNetworkMessage, ChunkableMessage,
-    MessageChunker, ChunkerConfig, ChunkerStats, StreamingChunk, ReassemblyState
+    ChunkableMessage, ChunkedNetworkMessage, ChunkerConfig, ChunkerStats, MessageChunker,
+    ReassemblyState, StreamingChunk,
 };
 // pub use lock_free::LockFreeMessageQueue;
 // pub use numa_aware::NumaAllocator;
@@ -1,8 +1,12 @@
 use either::Either;
 use libp2p::{
+    autonat,
     core::{
         multiaddr::{Multiaddr, Protocol},
-        transport::{Boxed, MemoryTransport, Transport as LibP2PTransport},
+        transport::{
+            Boxed, ListenerId, MemoryTransport, OptionalTransport, Transport as LibP2PTransport,
+            TransportError, TransportEvent,
+        },
         upgrade::{self},
     },
     dcutr,
@@ -11,14 +15,19 @@ use libp2p::{
         MessageAuthenticity, ValidationMode,
     },
     identify::{self},
-    identity::{self, Keypair},
-    kad::{self, store::MemoryStore, QueryResult},
+    identity::Keypair,
+    kad::{self, store::RecordStore, QueryId, QueryResult, Quorum, Record, RecordKey},
     mdns::{self},
+    metrics::{Metrics, Recorder},
     noise,
     ping::{self},
-    relay,
+    quic, relay,
     request_response::{self, ProtocolSupport},
-    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
+    swarm::{
+        behaviour::toggle::Toggle,
+        dial_opts::{DialOpts, PeerCondition},
+        ConnectionId, NetworkBehaviour, SwarmEvent,
+    },
     tcp, websocket, yamux, PeerId as LibP2PPeerId, StreamProtocol,
 };
 use void;
@@ -26,17 +35,40 @@ use void;
 /// Combined network behaviour event
 #[derive(Debug)]
 pub enum NetworkBehaviourEvent {
+    PeerGate(PeerScoringEvent),
     Kademlia(kad::Event),
     Gossipsub(gossipsub::Event),
     Mdns(mdns::Event),
     Ping(ping::Event),
     Identify(identify::Event),
     Relay(relay::Event),
+    RelayClient(relay::client::Event),
     Dcutr(dcutr::Event),
+    AutoNat(autonat::Event),
     RequestResponse(request_response::Event<QuDagRequest, QuDagResponse>),
+    IdentityExchange(request_response::Event<IdentityRequest, IdentityCertificate>),
+    Onion(request_response::Event<OnionCell, OnionReply>),
+}
+
+impl From<request_response::Event<OnionCell, OnionReply>> for NetworkBehaviourEvent {
+    fn from(event: request_response::Event<OnionCell, OnionReply>) -> Self {
+        NetworkBehaviourEvent::Onion(event)
+    }
 }
 
 // Implement From traits for all event types
+impl From<PeerScoringEvent> for NetworkBehaviourEvent {
+    fn from(event: PeerScoringEvent) -> Self {
+        NetworkBehaviourEvent::PeerGate(event)
+    }
+}
+
+impl From<void::Void> for NetworkBehaviourEvent {
+    fn from(event: void::Void) -> Self {
+        void::unreachable(event)
+    }
+}
+
 impl From<kad::Event> for NetworkBehaviourEvent {
     fn from(event: kad::Event) -> Self {
         NetworkBehaviourEvent::Kademlia(event)
@@ -83,37 +115,99 @@ impl From<relay::Event> for NetworkBehaviourEvent {
     }
 }
 
+impl From<relay::client::Event> for NetworkBehaviourEvent {
+    fn from(event: relay::client::Event) -> Self {
+        NetworkBehaviourEvent::RelayClient(event)
+    }
+}
+
 impl From<dcutr::Event> for NetworkBehaviourEvent {
     fn from(event: dcutr::Event) -> Self {
         NetworkBehaviourEvent::Dcutr(event)
     }
 }
 
+impl From<autonat::Event> for NetworkBehaviourEvent {
+    fn from(event: autonat::Event) -> Self {
+        NetworkBehaviourEvent::AutoNat(event)
+    }
+}
+
 impl From<request_response::Event<QuDagRequest, QuDagResponse>> for NetworkBehaviourEvent {
     fn from(event: request_response::Event<QuDagRequest, QuDagResponse>) -> Self {
         NetworkBehaviourEvent::RequestResponse(event)
     }
 }
 
+impl From<request_response::Event<IdentityRequest, IdentityCertificate>> for NetworkBehaviourEvent {
+    fn from(event: request_response::Event<IdentityRequest, IdentityCertificate>) -> Self {
+        NetworkBehaviourEvent::IdentityExchange(event)
+    }
+}
+
+use async_trait::async_trait;
 use chacha20poly1305::{
     aead::{Aead, KeyInit},
     ChaCha20Poly1305, Key, Nonce,
 };
-use futures::{channel::oneshot, prelude::*};
+use futures::{channel::oneshot, future::BoxFuture, prelude::*, stream::FuturesUnordered};
 use rand::{thread_rng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
-    sync::Arc,
-    time::Duration,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, info, warn};
 
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+
+use crate::address_book::{AddressBook, TransportKind, HAPPY_EYEBALLS_DELAY};
+use crate::circuit_relay::{
+    circuit_listen_addr, is_relayed, RelayConfig, Reservations, RESERVATION_RETRY_INTERVAL,
+};
+use crate::connection::ConnectionManager;
+use crate::cover_traffic::{CoverLink, CoverScheduler, CoverStats, CoverTrafficConfig};
+use crate::dark_resolver::{DarkRecordValidator, DarkResolverError, DhtClient};
+use crate::gossip_topics::{
+    GossipMessage, GossipTopic, GossipValidator, GossipValidators, Validation, Verdict,
+};
+use crate::metrics_exporter::MetricsExporter;
+use crate::nat_traversal::NatInfo;
+use crate::node_identity::{
+    advertised_digest, agent_version, IdentityCertificate, IdentityRequest, NodeIdentity,
+    IDENTITY_PROTOCOL,
+};
+use crate::onion::{CircuitManager, DirectoryClient, NodeInfo};
+use crate::onion_relay::{
+    client_handshake, ClientCircuit, HopKeys, OnionCell, OnionRelay, OnionRelayConfig,
+    OnionRelayError, OnionReply, PendingCell, RelayAction, RelayCommand, RelayDescriptor,
+    RelayResponse, MAX_DIRECTORY_FETCH, MAX_ONION_PAYLOAD, ONION_PROTOCOL, RELAY_DIRECTORY_TOPIC,
+};
+use crate::optimized::ChunkedMessage;
+#[cfg(feature = "adaptive-batching")]
+use crate::optimized::{AdaptiveBatcher, BatchConfig};
+#[cfg(feature = "message-chunking")]
+use crate::optimized::{ChunkerConfig, MessageChunker};
+use crate::peer_scoring::{
+    Ban, PeerEvent, PeerGate, PeerScoring, PeerScoringConfig, PeerScoringEvent, PEER_SCORES_FILE,
+};
+use crate::pluggable_transport::{ObfuscatedTransport, PluggableTransportConfig};
+use crate::pooled_transport::with_connection_pool;
+use crate::pq_noise::{PqNoiseConfig, SecurityProtocol, SelectSecurity};
+use crate::reachability::{AutoNatConfig, Reachability, ReachabilityState};
+use crate::record_store::{PersistentRecordStore, RecordStoreConfig, RECORD_STORE_DIR};
+use crate::resource_manager::{
+    ip_of, ResourceConfig, ResourceLimiter, ResourceManager, ResourceProtocol, StreamPermit,
+};
 use crate::routing::Router;
-// Optimization features disabled for initial release
-// use crate::optimized::message_chunking::{MessageChunker, ChunkerConfig, ChunkedMessage};
+#[cfg(feature = "message-chunking")]
 use crate::types::{MessagePriority, NetworkMessage};
 
 /// Configuration for the P2P network node
@@ -127,27 +221,77 @@ pub struct NetworkConfig {
     pub timeout: Duration,
     /// Maximum number of concurrent connections
     pub max_connections: usize,
-    /// Traffic obfuscation key
+    /// Traffic obfuscation key, shared by all nodes of the network
+    ///
+    /// Defaults to the public network's [`default_obfuscation_key`].
     pub obfuscation_key: [u8; 32],
     /// Enable MDNS for local peer discovery
     pub enable_mdns: bool,
     /// Enable relay for NAT traversal
     pub enable_relay: bool,
+    /// Circuit relay server quotas and the relays to reserve on
+    pub relay: RelayConfig,
+    /// AutoNAT reachability probing
+    pub autonat: AutoNatConfig,
+    /// Enable TCP transport
+    pub enable_tcp: bool,
     /// Enable QUIC transport
     pub enable_quic: bool,
     /// Enable WebSocket transport
     pub enable_websocket: bool,
+    /// Pluggable transport disguising TCP and memory connections
+    pub pluggable_transport: Option<PluggableTransportConfig>,
     /// Gossipsub configuration
     pub gossipsub_config: Option<GossipsubConfig>,
     /// Kademlia replication factor
     pub kad_replication_factor: usize,
+    /// Connection security upgrade
+    pub security: SecurityProtocol,
+    /// Persistent node identity; a throwaway one is generated when unset
+    pub identity: Option<NodeIdentity>,
+    /// Node data directory; DHT records are persisted below it when set
+    pub data_dir: Option<PathBuf>,
+    /// Quotas for the Kademlia record store
+    pub record_store: RecordStoreConfig,
+    /// Onion relaying for other nodes' circuits
+    pub onion: OnionRelayConfig,
+    /// Dummy cells on idle connections and circuits
+    pub cover_traffic: CoverTrafficConfig,
+    /// Peer scoring, bans and allowlist; bans are persisted below
+    /// `data_dir` when set
+    pub peer_scoring: PeerScoringConfig,
+    /// Rate, stream, memory and connection limits
+    ///
+    /// The lower of its `max_connections` and the one above applies.
+    pub resources: ResourceConfig,
+    /// Capacity of the event channel
+    ///
+    /// Once the application falls this far behind, further events are
+    /// dropped and counted in [`P2PHandle::dropped_events`].
+    pub event_buffer: usize,
+    /// Exporter to record libp2p swarm and protocol metrics into
+    pub metrics: Option<MetricsExporter>,
+    /// Chunking and reassembly of large requests
+    #[cfg(feature = "message-chunking")]
+    pub chunker: ChunkerConfig,
+    /// Adaptive batching of published gossip messages
+    #[cfg(feature = "adaptive-batching")]
+    pub gossip_batching: BatchConfig,
+}
+
+/// Context the public network's obfuscation key is derived from
+const DEFAULT_OBFUSCATION_KEY_CONTEXT: &str = "QuDAG 2024 public network gossip obfuscation key";
+
+/// Obfuscation key of the public network
+///
+/// Deterministic, so nodes left on the default configuration read each
+/// other's gossip. Private networks set their own `obfuscation_key`.
+pub fn default_obfuscation_key() -> [u8; 32] {
+    blake3::derive_key(DEFAULT_OBFUSCATION_KEY_CONTEXT, &[])
 }
 
 impl Default for NetworkConfig {
     fn default() -> Self {
-        let mut key = [0u8; 32];
-        thread_rng().fill_bytes(&mut key);
-
         Self {
             listen_addrs: vec![
                 "/ip4/0.0.0.0/tcp/0".to_string(),
@@ -156,14 +300,93 @@ impl Default for NetworkConfig {
             bootstrap_peers: vec![],
             timeout: Duration::from_secs(20),
             max_connections: 50,
-            obfuscation_key: key,
+            obfuscation_key: default_obfuscation_key(),
             enable_mdns: true,
             enable_relay: true,
+            relay: RelayConfig::default(),
+            autonat: AutoNatConfig::default(),
+            enable_tcp: true,
             enable_quic: false,
             enable_websocket: true,
+            pluggable_transport: None,
             gossipsub_config: None,
             kad_replication_factor: 20,
+            security: SecurityProtocol::default(),
+            identity: None,
+            data_dir: None,
+            record_store: RecordStoreConfig::default(),
+            onion: OnionRelayConfig::default(),
+            cover_traffic: CoverTrafficConfig::default(),
+            peer_scoring: PeerScoringConfig::default(),
+            resources: ResourceConfig::default(),
+            event_buffer: 1024,
+            metrics: None,
+            #[cfg(feature = "message-chunking")]
+            chunker: ChunkerConfig::default(),
+            #[cfg(feature = "adaptive-batching")]
+            gossip_batching: BatchConfig::default(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Whether an enabled transport can dial `addr`
+    pub fn supports(&self, addr: &Multiaddr) -> bool {
+        match TransportKind::of(addr) {
+            Some(TransportKind::Memory) => true,
+            Some(TransportKind::Tcp) => self.enable_tcp,
+            Some(TransportKind::Quic) => self.enable_quic,
+            Some(TransportKind::WebSocket) => self.enable_websocket,
+            Some(TransportKind::Relayed) => self.enable_relay,
+            None => false,
+        }
+    }
+
+    /// Addresses to listen on for every enabled transport
+    ///
+    /// Each plain `/ip4|ip6/<ip>/tcp/<port>` address stands for all enabled
+    /// transports on that IP: TCP on the port, QUIC on the same UDP port and,
+    /// for port 0 only, WebSocket on another TCP port. Other addresses are
+    /// used as given if their transport is enabled.
+    pub fn listen_multiaddrs(&self) -> Result<Vec<Multiaddr>, libp2p::multiaddr::Error> {
+        let mut addrs = Vec::new();
+        for addr_str in &self.listen_addrs {
+            let addr: Multiaddr = addr_str.parse()?;
+            let components: Vec<_> = addr.iter().collect();
+            let (ip, port) = match components.as_slice() {
+                [ip @ (Protocol::Ip4(_) | Protocol::Ip6(_)), Protocol::Tcp(port)] => {
+                    (ip.clone(), *port)
+                }
+                _ => {
+                    if self.supports(&addr) {
+                        addrs.push(addr);
+                    } else {
+                        warn!("No enabled transport listens on {}", addr);
+                    }
+                    continue;
+                }
+            };
+            if self.enable_tcp {
+                addrs.push(addr.clone());
+            }
+            if self.enable_quic {
+                addrs.push(
+                    Multiaddr::empty()
+                        .with(ip.clone())
+                        .with(Protocol::Udp(port))
+                        .with(Protocol::QuicV1),
+                );
+            }
+            if self.enable_websocket && port == 0 {
+                addrs.push(
+                    Multiaddr::empty()
+                        .with(ip)
+                        .with(Protocol::Tcp(0))
+                        .with(Protocol::Ws("/".into())),
+                );
+            }
         }
+        Ok(addrs)
     }
 }
 
@@ -180,12 +403,109 @@ pub struct QuDagResponse {
     pub payload: Vec<u8>,
 }
 
+/// Framing of `QuDagRequest` payloads on the wire
+///
+/// The layout is the same in every build; a node built without
+/// `message-chunking` decodes chunks but refuses them.
+#[derive(Serialize, Deserialize)]
+enum RequestFrame {
+    /// Payload small enough for a single request
+    Whole(Vec<u8>),
+    /// One chunk of a large payload
+    Chunk(ChunkedMessage),
+}
+
+/// Framing of `QuDagResponse` payloads on the wire
+#[derive(Serialize, Deserialize)]
+enum ResponseFrame {
+    /// Chunk received, more are expected
+    Ack,
+    /// Application reply to a complete request
+    Reply(Vec<u8>),
+}
+
+/// First byte of a gossip message carrying one application message
+const GOSSIP_FRAME_SINGLE: u8 = 1;
+
+/// First byte of a gossip message carrying a batch of application messages
+const GOSSIP_FRAME_BATCH: u8 = 2;
+
+/// Largest batch published at once, below gossipsub's 64 KiB transmit limit
+#[cfg(feature = "adaptive-batching")]
+const MAX_GOSSIP_BATCH_BYTES: usize = 48 * 1024;
+
+/// Chunks of one request in flight before waiting for acknowledgements
+#[cfg(feature = "message-chunking")]
+const CHUNK_SEND_WINDOW: usize = 16;
+
+/// How often stalled chunk reassemblies are dropped
+const REASSEMBLY_CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Frame one application message as a gossip message
+fn encode_gossip_single(message: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(1 + message.len());
+    frame.push(GOSSIP_FRAME_SINGLE);
+    frame.extend_from_slice(message);
+    frame
+}
+
+/// Frame application messages as one gossip batch
+#[cfg(feature = "adaptive-batching")]
+fn encode_gossip_batch(messages: &[Vec<u8>]) -> Result<Vec<u8>, bincode::Error> {
+    let mut frame = vec![GOSSIP_FRAME_BATCH];
+    frame.extend(bincode::serialize(messages)?);
+    Ok(frame)
+}
+
+/// Decode the application messages of a gossip message
+///
+/// The framing byte says whether one message or a batch follows, so
+/// nothing is inferred from the contents. `None` for an unknown framing or
+/// a malformed batch.
+fn decode_gossip_frame(data: &[u8]) -> Option<Vec<Vec<u8>>> {
+    match data.split_first()? {
+        (&GOSSIP_FRAME_SINGLE, message) => Some(vec![message.to_vec()]),
+        (&GOSSIP_FRAME_BATCH, batch) => bincode::deserialize(batch).ok(),
+        _ => None,
+    }
+}
+
+/// Split a request payload into the frames sent for it
+#[cfg(feature = "message-chunking")]
+async fn chunk_request(
+    chunker: &MessageChunker,
+    local_peer_id: LibP2PPeerId,
+    ttl: Duration,
+    request: QuDagRequest,
+) -> Result<Vec<RequestFrame>, Box<dyn Error + Send + Sync>> {
+    let message = NetworkMessage {
+        id: request.request_id,
+        source: local_peer_id.to_bytes(),
+        destination: vec![],
+        payload: request.payload,
+        priority: MessagePriority::Normal,
+        ttl,
+    };
+    let chunks = chunker
+        .chunk_message(&message)
+        .await
+        .map_err(|e| format!("Chunking error: {}", e))?;
+    if chunks.is_empty() {
+        return Ok(vec![RequestFrame::Whole(message.payload)]);
+    }
+    Ok(chunks.into_iter().map(RequestFrame::Chunk).collect())
+}
+
 /// Combined network behaviour for the P2P node
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "NetworkBehaviourEvent")]
 pub struct NetworkBehaviourImpl {
+    /// Refuses banned peers, ahead of every other protocol
+    pub peer_gate: PeerGate,
+    /// Caps connections overall, per peer, per IP address and per subnet
+    pub resource_limiter: ResourceLimiter,
     /// Kademlia DHT for peer discovery and content routing
-    pub kademlia: kad::Behaviour<MemoryStore>,
+    pub kademlia: kad::Behaviour<PersistentRecordStore>,
     /// Gossipsub for pub/sub messaging
     pub gossipsub: gossipsub::Behaviour,
     /// MDNS for local peer discovery
@@ -194,14 +514,75 @@ pub struct NetworkBehaviourImpl {
     pub ping: ping::Behaviour,
     /// Identify protocol for peer identification
     pub identify: identify::Behaviour,
-    /// Relay for NAT traversal
-    pub relay: relay::Behaviour,
+    /// Relay server offering reservations and circuits to other peers
+    pub relay: Toggle<relay::Behaviour>,
+    /// Relay client reserving on relays and dialing through them
+    pub relay_client: Toggle<relay::client::Behaviour>,
     /// Direct connection upgrade through relay
     pub dcutr: dcutr::Behaviour,
+    /// Reachability probes through peers, and answers to theirs
+    pub autonat: Toggle<autonat::Behaviour>,
     /// Request-response protocol for custom messages
     pub request_response: request_response::cbor::Behaviour<QuDagRequest, QuDagResponse>,
+    /// Exchange of identity certificates advertised over identify
+    pub identity_exchange: request_response::cbor::Behaviour<IdentityRequest, IdentityCertificate>,
+    /// Onion cells for circuits and relay directory fetches
+    pub onion: request_response::cbor::Behaviour<OnionCell, OnionReply>,
 }
 
+/// How often expired DHT entries are dropped and due records republished
+const RECORD_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often peer scores decay and are exchanged with gossipsub
+const PEER_SCORING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long connections without open streams stay up, so that one
+/// hole-punched by DCUtR outlives the relayed connection it replaces
+const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often reservations on the configured relays are renewed if lost
+const RELAY_MAINTENANCE_INTERVAL: Duration = RESERVATION_RETRY_INTERVAL;
+
+/// How often the resource usage of idle peers is forgotten
+const RESOURCE_PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reply channel for a request sent to a peer
+type SendRequestResponse = oneshot::Sender<Result<QuDagResponse, Box<dyn Error + Send + Sync>>>;
+
+/// Application reply to an inbound request, once it is ready
+///
+/// The future holds the request's [`StreamPermit`] until the reply is ready.
+type PendingReply = BoxFuture<
+    'static,
+    (
+        request_response::ResponseChannel<QuDagResponse>,
+        String,
+        Option<QuDagResponse>,
+    ),
+>;
+
+/// Verdict on a gossip message received on a typed topic, once it is ready
+type PendingValidation = BoxFuture<'static, (gossipsub::MessageId, LibP2PPeerId, Validation)>;
+
+/// How often relay circuits, the relay directory and this node's relay
+/// descriptor are maintained
+const ONION_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reply channel for a circuit build
+type BuildCircuitResponse = oneshot::Sender<Result<u64, Box<dyn Error + Send + Sync>>>;
+
+/// Reply channel for a command sent to the last hop of a circuit
+type CircuitCommandResponse = oneshot::Sender<Result<RelayResponse, Box<dyn Error + Send + Sync>>>;
+
+/// Reply channel for a DHT put
+type PutRecordResponse = oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>;
+
+/// Reply channel for a DHT get
+type GetRecordResponse = oneshot::Sender<Result<Vec<Vec<u8>>, Box<dyn Error + Send + Sync>>>;
+
+/// Reply channel for a dial of an address, with the peer that answered
+type ConnectResponse = oneshot::Sender<Result<LibP2PPeerId, Box<dyn Error + Send + Sync>>>;
+
 /// Commands that can be sent to the P2P node
 #[derive(Debug)]
 pub enum P2PCommand {
@@ -225,13 +606,28 @@ pub enum P2PCommand {
     SendRequest {
         peer_id: LibP2PPeerId,
         request: QuDagRequest,
-        response: oneshot::Sender<Result<QuDagResponse, Box<dyn Error + Send + Sync>>>,
+        response: SendRequestResponse,
     },
     /// Dial a peer
     Dial {
         addr: Multiaddr,
         response: oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
     },
+    /// Dial a peer at its known addresses, best first
+    DialPeer {
+        peer_id: LibP2PPeerId,
+        response: oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
+    },
+    /// Dial an address and wait for the connection
+    Connect {
+        addr: Multiaddr,
+        response: ConnectResponse,
+    },
+    /// Close every connection to a peer
+    Disconnect {
+        peer_id: LibP2PPeerId,
+        response: oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
+    },
     /// Get connected peers
     GetConnectedPeers {
         response: oneshot::Sender<Vec<LibP2PPeerId>>,
@@ -244,6 +640,70 @@ pub enum P2PCommand {
     GetListeners {
         response: oneshot::Sender<Vec<Multiaddr>>,
     },
+    /// Get the addresses the node advertises as reachable
+    GetExternalAddresses {
+        response: oneshot::Sender<Vec<Multiaddr>>,
+    },
+    /// Get the relays holding a reservation for this node
+    GetRelayReservations {
+        response: oneshot::Sender<Vec<LibP2PPeerId>>,
+    },
+    /// Get whether peers can dial the node directly
+    GetReachability {
+        response: oneshot::Sender<Reachability>,
+    },
+    /// Combine a STUN detection result into the node's reachability
+    ReportNatInfo { info: NatInfo },
+    /// Get the verified ML-DSA identity key of a peer
+    GetPeerIdentity {
+        peer_id: LibP2PPeerId,
+        response: oneshot::Sender<Option<Vec<u8>>>,
+    },
+    /// Store a record in the Kademlia DHT
+    PutRecord {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        ttl: Duration,
+        response: PutRecordResponse,
+    },
+    /// Collect every record stored under a key in the Kademlia DHT
+    GetRecord {
+        key: Vec<u8>,
+        response: GetRecordResponse,
+    },
+    /// Build an onion circuit through relays from the directory
+    BuildCircuit {
+        hops: usize,
+        /// Relay the circuit must end at
+        last_hop: Option<LibP2PPeerId>,
+        response: BuildCircuitResponse,
+    },
+    /// Send a payload over an onion circuit
+    SendOnionMessage {
+        circuit_id: u64,
+        destination: Option<LibP2PPeerId>,
+        payload: Vec<u8>,
+        response: CircuitCommandResponse,
+    },
+    /// Send a command to the last hop of an onion circuit
+    SendCircuitCommand {
+        circuit_id: u64,
+        command: RelayCommand,
+        response: CircuitCommandResponse,
+    },
+    /// Tear down an onion circuit
+    DestroyCircuit {
+        circuit_id: u64,
+        response: oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
+    },
+    /// Get the relays in the onion directory
+    GetKnownRelays {
+        response: oneshot::Sender<Vec<LibP2PPeerId>>,
+    },
+    /// Get the real and cover traffic counters
+    GetCoverStats {
+        response: oneshot::Sender<CoverStats>,
+    },
 }
 
 /// Events emitted by the P2P network
@@ -255,12 +715,17 @@ pub enum P2PEvent {
     PeerConnected(LibP2PPeerId),
     /// Peer disconnected
     PeerDisconnected(LibP2PPeerId),
-    /// Message received via gossipsub
+    /// Message received via gossipsub on an untyped topic
     MessageReceived {
         peer_id: LibP2PPeerId,
         topic: String,
         data: Vec<u8>,
     },
+    /// Message on a typed topic that passed validation
+    GossipMessageReceived {
+        peer_id: LibP2PPeerId,
+        message: GossipMessage,
+    },
     /// Request received
     RequestReceived {
         peer_id: LibP2PPeerId,
@@ -274,6 +739,30 @@ pub enum P2PEvent {
     },
     /// Routing table updated
     RoutingTableUpdated,
+    /// Peer proved ownership of an ML-DSA identity key
+    PeerIdentityVerified {
+        peer_id: LibP2PPeerId,
+        ml_dsa_public_key: Vec<u8>,
+    },
+    /// Payload delivered over an onion circuit; the sender is unknown
+    OnionMessageReceived { payload: Vec<u8> },
+    /// The last hop of one of this node's circuits pushed a response
+    CircuitPush {
+        circuit_id: u64,
+        response: RelayResponse,
+    },
+    /// Peer was banned and disconnected
+    PeerBanned(Ban),
+    /// Ban of a peer was lifted or ran out
+    PeerUnbanned(LibP2PPeerId),
+    /// A relay accepted a reservation; the node is reachable through it
+    RelayReservationAccepted { relay: LibP2PPeerId },
+    /// A reservation on a relay was refused or ended
+    RelayReservationClosed { relay: LibP2PPeerId },
+    /// A connection through a relay was replaced by a direct one
+    DirectConnectionUpgraded(LibP2PPeerId),
+    /// Whether peers can dial the node directly changed
+    ReachabilityChanged(Reachability),
 }
 
 /// Main P2P network node implementation
@@ -287,29 +776,300 @@ pub struct P2PNode {
     /// Traffic obfuscation cipher
     cipher: ChaCha20Poly1305,
     /// Event channel sender
-    event_tx: mpsc::UnboundedSender<P2PEvent>,
+    event_tx: mpsc::Sender<P2PEvent>,
+    /// Events dropped because the application fell behind
+    dropped_events: Arc<AtomicU64>,
     /// Command channel receiver
     command_rx: mpsc::UnboundedReceiver<P2PCommand>,
     /// Connected peers
     connected_peers: HashSet<LibP2PPeerId>,
-    /// Pending requests
-    pending_requests: HashMap<String, oneshot::Sender<QuDagResponse>>,
+    /// Requests awaiting a reply, keyed by request ID
+    pending_requests: HashMap<String, PendingRequest>,
+    /// Request-response exchanges in flight and the request they carry
+    outbound_requests: HashMap<request_response::OutboundRequestId, String>,
+    /// Inbound requests waiting for the application to reply
+    pending_replies: FuturesUnordered<PendingReply>,
+    /// Validators of the typed gossip topics shared with handles
+    validators: GossipValidators,
+    /// Gossip messages held by gossipsub until they are validated
+    pending_validations: FuturesUnordered<PendingValidation>,
+    /// Node identity
+    identity: NodeIdentity,
+    /// Certificate digests advertised by peers whose certificate is requested
+    pending_identities: HashMap<LibP2PPeerId, [u8; 32]>,
+    /// Verified ML-DSA identity keys and the digest of their certificate
+    verified_identities: HashMap<LibP2PPeerId, ([u8; 32], Vec<u8>)>,
+    /// DHT puts awaiting their quorum
+    pending_puts: HashMap<QueryId, PutRecordResponse>,
+    /// DHT gets and the distinct values found so far
+    pending_gets: HashMap<QueryId, PendingGet>,
     /// Metrics recorder
     #[allow(dead_code)]
-    metrics: Option<()>, // TODO: Use proper metrics type
+    /// libp2p metrics, when the node has an exporter
+    metrics: Option<Metrics>,
     /// Network configuration
     config: NetworkConfig,
-    // Message chunker for large messages (disabled for initial release)
-    // message_chunker: MessageChunker,
+    /// Chunking and reassembly of large requests
+    #[cfg(feature = "message-chunking")]
+    message_chunker: MessageChunker,
+    /// Gossip messages waiting to be published, per topic
+    #[cfg(feature = "adaptive-batching")]
+    gossip_batches: HashMap<String, GossipBatch>,
+    /// Circuits carried for other nodes
+    onion_relay: OnionRelay,
+    /// Relays learned from descriptors
+    directory: Arc<DirectoryClient>,
+    /// Circuits owned by this node
+    circuit_manager: CircuitManager,
+    /// Keys of this node's built circuits
+    client_circuits: HashMap<u64, ClientCircuit>,
+    /// Circuits of this node being extended
+    circuit_builds: HashMap<u64, CircuitBuild>,
+    /// Onion cells sent and what their answer is for
+    onion_requests: HashMap<request_response::OutboundRequestId, OnionExchange>,
+    /// This node's relay descriptor and when it was signed
+    local_descriptor: Option<(RelayDescriptor, Instant)>,
+    /// Whether `local_descriptor` has been gossiped
+    descriptor_published: bool,
+    /// Peers whose directory has been fetched
+    directory_fetched: HashSet<LibP2PPeerId>,
+    /// Dummy cells for idle connections and circuits
+    cover: CoverScheduler,
+    /// Peer scores, bans and allowlist shared with handles
+    scoring: PeerScoring,
+    /// Rate, stream, memory and connection limits shared with handles
+    resources: ResourceManager,
+    /// Addresses of known peers shared with handles
+    address_book: AddressBook,
+    /// Remote address of each connection this node dialed
+    dialed_addrs: HashMap<ConnectionId, Multiaddr>,
+    /// Peers being dialed at their known addresses
+    dial_races: HashMap<LibP2PPeerId, DialRace>,
+    /// Race attempts still in flight after their race was decided
+    stale_dials: HashSet<ConnectionId>,
+    /// Dials of addresses waiting for their connection
+    address_dials: HashMap<ConnectionId, ConnectResponse>,
+    /// Reservations on relays held or requested by this node
+    reservations: Reservations,
+    /// Connections to peers through a relay
+    relayed_connections: HashMap<LibP2PPeerId, HashSet<ConnectionId>>,
+    /// Connected peers offering relaying, at the address to reserve on
+    relay_candidates: HashMap<LibP2PPeerId, Multiaddr>,
+    /// Whether peers can dial the node, from AutoNAT and STUN
+    reachability: ReachabilityState,
+}
+
+/// Addresses of a peer dialed one after another until one connects
+struct DialRace {
+    /// Addresses not dialed yet, best first
+    remaining: VecDeque<Multiaddr>,
+    /// Dials in flight and their address
+    attempts: HashMap<ConnectionId, Multiaddr>,
+    /// When the next address is dialed though no attempt failed yet
+    next_attempt: Instant,
+    /// Callers waiting for the outcome
+    waiters: Vec<oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>>,
+}
+
+/// A circuit of this node being extended one hop at a time
+struct CircuitBuild {
+    /// Planned hops
+    relays: Vec<NodeInfo>,
+    /// Hops built so far
+    circuit: Option<ClientCircuit>,
+    /// Keys and confirmation expected from the hop being added
+    handshake: Option<(HopKeys, [u8; 32])>,
+    response: BuildCircuitResponse,
+}
+
+impl CircuitBuild {
+    fn built(&self) -> usize {
+        self.circuit.as_ref().map_or(0, ClientCircuit::len)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.built() == self.relays.len()
+    }
+
+    /// Cell adding the next planned hop, its recipient and addresses to reach it
+    fn next_cell(
+        &mut self,
+        circuit_id: u64,
+    ) -> Result<(LibP2PPeerId, Vec<Multiaddr>, OnionCell), String> {
+        let built = self.built();
+        let relay = &self.relays[built];
+        let peer_id = LibP2PPeerId::from_bytes(&relay.id).map_err(|e| e.to_string())?;
+        let (kem_ciphertext, keys, confirmation) =
+            client_handshake(&relay.public_key).map_err(|e| e.to_string())?;
+        self.handshake = Some((keys, confirmation));
+
+        match &self.circuit {
+            None => Ok((
+                peer_id,
+                relay.addresses.clone(),
+                OnionCell::Create {
+                    circuit_id,
+                    kem_ciphertext,
+                },
+            )),
+            Some(circuit) => {
+                let command = RelayCommand::Extend {
+                    peer_id: relay.id.clone(),
+                    addresses: relay.addresses.iter().map(|addr| addr.to_vec()).collect(),
+                    kem_ciphertext,
+                };
+                let payload = circuit
+                    .seal_for(built - 1, &command)
+                    .map_err(|e| e.to_string())?;
+                Ok((
+                    circuit.hops[0],
+                    Vec::new(),
+                    OnionCell::Relay {
+                        circuit_id,
+                        payload,
+                    },
+                ))
+            }
+        }
+    }
+
+    /// Check the answer to the last cell and record the hop it added
+    fn add_hop(&mut self, reply: Result<OnionReply, String>) -> Result<(), String> {
+        let (keys, expected) = self.handshake.take().ok_or("No hop being added")?;
+        let built = self.built();
+        let peer_id =
+            LibP2PPeerId::from_bytes(&self.relays[built].id).map_err(|e| e.to_string())?;
+        let confirmation = match (&self.circuit, reply?) {
+            (None, OnionReply::Created { confirmation }) => confirmation,
+            (None, OnionReply::Failed(e)) => return Err(e),
+            (None, reply) => return Err(format!("Unexpected answer {:?}", reply)),
+            (Some(circuit), reply) => match open_circuit_reply(circuit, Ok(reply))? {
+                (hop, RelayResponse::Extended { confirmation }) if hop + 1 == built => confirmation,
+                (hop, RelayResponse::Failed(e)) => return Err(format!("Hop {}: {}", hop, e)),
+                (hop, response) => {
+                    return Err(format!(
+                        "Unexpected answer from hop {}: {:?}",
+                        hop, response
+                    ))
+                }
+            },
+        };
+        if confirmation != expected {
+            return Err(format!("Hop {} failed key confirmation", built));
+        }
+        match &mut self.circuit {
+            None => self.circuit = Some(ClientCircuit::new(peer_id, keys)),
+            Some(circuit) => circuit.push_hop(peer_id, keys),
+        }
+        Ok(())
+    }
+}
+
+/// What an onion cell sent to a peer is waiting for
+enum OnionExchange {
+    /// A step of building one of this node's circuits
+    Build(u64),
+    /// A command sent over one of this node's circuits
+    Send {
+        circuit_id: u64,
+        bytes: u64,
+        response: CircuitCommandResponse,
+    },
+    /// A cell relayed for a circuit through this node
+    Forward {
+        channel: request_response::ResponseChannel<OnionReply>,
+        pending: Box<PendingCell>,
+    },
+    /// A relay directory fetch
+    Fetch,
+    /// A teardown or push, whose answer needs no action
+    Notify,
+}
+
+/// Remove the layers of an answer received over a circuit
+fn open_circuit_reply(
+    circuit: &ClientCircuit,
+    reply: Result<OnionReply, String>,
+) -> Result<(usize, RelayResponse), String> {
+    match reply? {
+        OnionReply::Relay { payload } => circuit.open_reply(&payload).map_err(|e| e.to_string()),
+        OnionReply::Failed(e) => Err(e),
+        reply => Err(format!("Unexpected answer {:?}", reply)),
+    }
+}
+
+/// A request sent to a peer, possibly as several chunks
+struct PendingRequest {
+    peer_id: LibP2PPeerId,
+    /// Frames not sent yet
+    queued: VecDeque<RequestFrame>,
+    /// Frames sent and not yet acknowledged
+    in_flight: usize,
+    response: SendRequestResponse,
+}
+
+/// Gossip messages for one topic waiting to be published together
+#[cfg(feature = "adaptive-batching")]
+struct GossipBatch {
+    batcher: AdaptiveBatcher<Vec<u8>>,
+    bytes: usize,
+}
+
+#[cfg(feature = "adaptive-batching")]
+impl GossipBatch {
+    fn new(config: BatchConfig) -> Self {
+        Self {
+            batcher: AdaptiveBatcher::new(config),
+            bytes: 0,
+        }
+    }
+
+    /// Queue a message, returning a batch if one is ready
+    fn add(&mut self, data: Vec<u8>) -> Option<Vec<Vec<u8>>> {
+        self.bytes += data.len();
+        let batch = self.batcher.add_message(data);
+        if batch.is_some() {
+            self.bytes = 0;
+        }
+        batch
+    }
+
+    fn flush(&mut self) -> Vec<Vec<u8>> {
+        self.bytes = 0;
+        self.batcher.flush_batch()
+    }
+}
+
+/// A DHT lookup collecting values from every peer that answers
+struct PendingGet {
+    values: Vec<Vec<u8>>,
+    response: GetRecordResponse,
 }
 
 /// Handle for sending commands to the P2P node
+///
+/// Every clone shares the node's single event stream. Events are read either
+/// through [`P2PHandle::next_event`] or, once [`P2PHandle::take_events`] was
+/// called on any clone, only through the taken receiver; from then on
+/// `next_event` returns `None` immediately on every clone.
 #[derive(Clone)]
 pub struct P2PHandle {
     /// Command channel sender
     command_tx: mpsc::UnboundedSender<P2PCommand>,
-    /// Event channel receiver (cloned for each handle)
-    event_rx: Arc<Mutex<mpsc::UnboundedReceiver<P2PEvent>>>,
+    /// Event channel receiver shared by every handle, until taken
+    event_rx: Arc<Mutex<Option<mpsc::Receiver<P2PEvent>>>>,
+    /// Events dropped because the application fell behind
+    dropped_events: Arc<AtomicU64>,
+    /// Peer scoring of the node
+    scoring: PeerScoring,
+    /// Resource limits of the node
+    resources: ResourceManager,
+    /// Validators of the typed gossip topics
+    validators: GossipValidators,
+    /// Addresses of known peers
+    address_book: AddressBook,
+    /// Pool of the node's transport connections
+    connection_manager: Arc<ConnectionManager>,
 }
 
 impl P2PHandle {
@@ -337,6 +1097,44 @@ impl P2PHandle {
         rx.await.map_err(|_| "Command failed")?
     }
 
+    /// Subscribe to a typed topic
+    pub async fn subscribe_topic(
+        &self,
+        topic: GossipTopic,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.subscribe(&topic.topic()).await
+    }
+
+    /// Unsubscribe from a typed topic
+    pub async fn unsubscribe_topic(
+        &self,
+        topic: GossipTopic,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.unsubscribe(&topic.topic()).await
+    }
+
+    /// Publish a message on its typed topic
+    pub async fn publish_message(
+        &self,
+        message: &GossipMessage,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.publish(&message.topic().topic(), message.encode()?)
+            .await
+    }
+
+    /// Validate the messages received on `topic` with `validator`
+    ///
+    /// Messages are held back from the application and from other peers
+    /// until the validator accepts them.
+    pub fn set_validator(&self, topic: GossipTopic, validator: Arc<dyn GossipValidator>) {
+        self.validators.set(topic, validator);
+    }
+
+    /// Validators of the typed topics
+    pub fn gossip_validators(&self) -> &GossipValidators {
+        &self.validators
+    }
+
     /// Publish a message to a topic
     pub async fn publish(
         &self,
@@ -380,6 +1178,54 @@ impl P2PHandle {
         rx.await.map_err(|_| "Command failed")?
     }
 
+    /// Dial a known peer, racing its addresses
+    ///
+    /// Addresses come from the [`AddressBook`], best first, filtered to the
+    /// enabled transports. The next address is dialed when an attempt fails
+    /// or after [`HAPPY_EYEBALLS_DELAY`]. Resolves once any connection to the
+    /// peer is up, or fails when every address did.
+    pub async fn dial_peer(
+        &self,
+        peer_id: LibP2PPeerId,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::DialPeer {
+                peer_id,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
+    /// Dial an address, resolving to the peer that answered once the
+    /// connection is up
+    pub async fn connect(
+        &self,
+        addr: Multiaddr,
+    ) -> Result<LibP2PPeerId, Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::Connect { addr, response: tx })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
+    /// Close every connection to a peer
+    pub async fn disconnect(
+        &self,
+        peer_id: LibP2PPeerId,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::Disconnect {
+                peer_id,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
     /// Get connected peers
     pub async fn connected_peers(&self) -> Vec<LibP2PPeerId> {
         let (tx, rx) = oneshot::channel();
@@ -394,62 +1240,380 @@ impl P2PHandle {
         }
     }
 
-    /// Get local peer ID
-    pub async fn local_peer_id(&self) -> LibP2PPeerId {
+    /// Get the ML-DSA identity key a peer proved ownership of
+    pub async fn peer_identity(&self, peer_id: LibP2PPeerId) -> Option<Vec<u8>> {
         let (tx, rx) = oneshot::channel();
-        if self
-            .command_tx
-            .send(P2PCommand::GetLocalPeerId { response: tx })
-            .is_ok()
-        {
-            rx.await.unwrap_or_else(|_| LibP2PPeerId::random())
-        } else {
-            LibP2PPeerId::random()
-        }
+        self.command_tx
+            .send(P2PCommand::GetPeerIdentity {
+                peer_id,
+                response: tx,
+            })
+            .ok()?;
+        rx.await.ok().flatten()
     }
 
-    /// Get listeners
-    pub async fn listeners(&self) -> Vec<Multiaddr> {
+    /// Store a record in the DHT, replicated to the closest peers for `ttl`
+    pub async fn put_record(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let (tx, rx) = oneshot::channel();
-        if self
-            .command_tx
-            .send(P2PCommand::GetListeners { response: tx })
-            .is_ok()
-        {
-            rx.await.unwrap_or_default()
-        } else {
-            Vec::new()
-        }
+        self.command_tx
+            .send(P2PCommand::PutRecord {
+                key,
+                value,
+                ttl,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
     }
 
-    /// Get the next network event
-    pub async fn next_event(&self) -> Option<P2PEvent> {
-        let mut event_rx = self.event_rx.lock().await;
-        event_rx.recv().await
+    /// Get the distinct values stored under `key`, locally and on remote peers
+    pub async fn get_records(
+        &self,
+        key: Vec<u8>,
+    ) -> Result<Vec<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::GetRecord { key, response: tx })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
     }
-}
 
-impl P2PNode {
-    /// Creates a new P2P network node with the given configuration
-    /// Returns the node and a handle for sending commands
+    /// Build an onion circuit through `hops` relays, returning its ID
+    pub async fn build_circuit(&self, hops: usize) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::BuildCircuit {
+                hops,
+                last_hop: None,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
+    /// Build an onion circuit through `hops` relays ending at `last_hop`
+    ///
+    /// The last hop must be in the relay directory.
+    pub async fn build_circuit_to(
+        &self,
+        hops: usize,
+        last_hop: LibP2PPeerId,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::BuildCircuit {
+                hops,
+                last_hop: Some(last_hop),
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
+    /// Send a payload over an onion circuit
+    ///
+    /// The last hop delivers it to `destination`, or keeps it when unset.
+    pub async fn send_onion_message(
+        &self,
+        circuit_id: u64,
+        destination: Option<LibP2PPeerId>,
+        payload: Vec<u8>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::SendOnionMessage {
+                circuit_id,
+                destination,
+                payload,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        match rx.await.map_err(|_| "Command failed")?? {
+            RelayResponse::Delivered => Ok(()),
+            response => Err(format!("Unexpected answer {:?}", response).into()),
+        }
+    }
+
+    /// Send a command to the last hop of an onion circuit
+    ///
+    /// Responses the last hop pushes later arrive as
+    /// [`P2PEvent::CircuitPush`].
+    pub async fn send_relay_command(
+        &self,
+        circuit_id: u64,
+        command: RelayCommand,
+    ) -> Result<RelayResponse, Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::SendCircuitCommand {
+                circuit_id,
+                command,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
+    /// Tear down an onion circuit
+    pub async fn destroy_circuit(
+        &self,
+        circuit_id: u64,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(P2PCommand::DestroyCircuit {
+                circuit_id,
+                response: tx,
+            })
+            .map_err(|_| "P2P node offline")?;
+        rx.await.map_err(|_| "Command failed")?
+    }
+
+    /// Get the relays in the onion directory
+    pub async fn known_relays(&self) -> Vec<LibP2PPeerId> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetKnownRelays { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Get the real and cover traffic counters
+    pub async fn cover_stats(&self) -> CoverStats {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetCoverStats { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or_default()
+        } else {
+            CoverStats::default()
+        }
+    }
+
+    /// Get local peer ID
+    pub async fn local_peer_id(&self) -> LibP2PPeerId {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetLocalPeerId { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or_else(|_| LibP2PPeerId::random())
+        } else {
+            LibP2PPeerId::random()
+        }
+    }
+
+    /// Get listeners
+    pub async fn listeners(&self) -> Vec<Multiaddr> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetListeners { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Get the addresses the node advertises as reachable, relayed ones
+    /// included
+    pub async fn external_addresses(&self) -> Vec<Multiaddr> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetExternalAddresses { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Get the relays holding a reservation for this node
+    pub async fn relay_reservations(&self) -> Vec<LibP2PPeerId> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetRelayReservations { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Get whether peers can dial the node directly
+    pub async fn reachability(&self) -> Reachability {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(P2PCommand::GetReachability { response: tx })
+            .is_ok()
+        {
+            rx.await.unwrap_or(Reachability::Unknown)
+        } else {
+            Reachability::Unknown
+        }
+    }
+
+    /// Combine a STUN detection result into the node's reachability
+    ///
+    /// AutoNAT's verdict takes precedence once it has one.
+    pub fn report_nat_info(&self, info: NatInfo) {
+        let _ = self.command_tx.send(P2PCommand::ReportNatInfo { info });
+    }
+
+    /// Peer scores, bans and allowlist of the node
+    ///
+    /// Bans take effect on the node at once.
+    pub fn peer_scoring(&self) -> &PeerScoring {
+        &self.scoring
+    }
+
+    /// Resource limits and usage of the node
+    pub fn resources(&self) -> &ResourceManager {
+        &self.resources
+    }
+
+    /// Addresses the node knows peers at, with their measured latency
+    pub fn address_book(&self) -> &AddressBook {
+        &self.address_book
+    }
+
+    /// Pool tracking the node's transport connections
+    pub fn connection_manager(&self) -> &Arc<ConnectionManager> {
+        &self.connection_manager
+    }
+
+    /// Report something a peer did, returning the ban it caused if any
+    pub fn report_peer(&self, peer_id: LibP2PPeerId, event: PeerEvent) -> Option<Ban> {
+        self.scoring.report(peer_id, event)
+    }
+
+    /// Ban a peer for `duration`, disconnecting it
+    pub fn ban_peer(
+        &self,
+        peer_id: LibP2PPeerId,
+        duration: Duration,
+        reason: impl Into<String>,
+    ) -> Ban {
+        self.scoring.ban(peer_id, duration, reason)
+    }
+
+    /// Lift the ban of a peer, returning whether it was banned
+    pub fn unban_peer(&self, peer_id: &LibP2PPeerId) -> bool {
+        self.scoring.unban(peer_id)
+    }
+
+    /// Get the next network event
+    ///
+    /// Returns `None` once the node stopped or its events were taken with
+    /// [`P2PHandle::take_events`].
+    pub async fn next_event(&self) -> Option<P2PEvent> {
+        let mut event_rx = self.event_rx.lock().await;
+        event_rx.as_mut()?.recv().await
+    }
+
+    /// Take the node's event stream for exclusive use
+    ///
+    /// Afterwards [`P2PHandle::next_event`] returns `None` on every handle of
+    /// the node. Returns `None` if the events were already taken.
+    pub async fn take_events(&self) -> Option<mpsc::Receiver<P2PEvent>> {
+        self.event_rx.lock().await.take()
+    }
+
+    /// Number of events dropped because the application fell behind
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl DhtClient for P2PHandle {
+    async fn put(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<(), DarkResolverError> {
+        self.put_record(key.to_vec(), value.to_vec(), ttl)
+            .await
+            .map_err(|e| DarkResolverError::DhtError(e.to_string()))
+    }
+
+    async fn get(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, DarkResolverError> {
+        self.get_records(key.to_vec())
+            .await
+            .map_err(|e| DarkResolverError::DhtError(e.to_string()))
+    }
+}
+
+impl P2PNode {
+    /// Creates a new P2P network node with the given configuration
+    /// Returns the node and a handle for sending commands
     pub async fn new(config: NetworkConfig) -> Result<(Self, P2PHandle), Box<dyn Error>> {
-        // Generate node identity
-        let local_key = identity::Keypair::generate_ed25519();
-        let local_peer_id = LibP2PPeerId::from(local_key.public());
+        // Load or generate node identity
+        let node_identity = match &config.identity {
+            Some(node_identity) => node_identity.clone(),
+            None => NodeIdentity::generate()?,
+        };
+        let local_key = node_identity.keypair().clone();
+        let local_peer_id = node_identity.peer_id();
 
         info!("Local peer ID: {}", local_peer_id);
 
-        // Build the transport
-        let transport = build_transport(&local_key, &config)?;
+        // Build the transport, dialing and listening through relays if enabled
+        let (relay_transport, relay_client) = if config.enable_relay {
+            let (transport, behaviour) = relay::client::new(local_peer_id);
+            (Some(transport), Some(behaviour))
+        } else {
+            (None, None)
+        };
+        let connection_manager = Arc::new(ConnectionManager::new(config.max_connections));
+        let transport = build_transport(
+            &local_key,
+            node_identity.ml_dsa().clone(),
+            &config,
+            relay_transport,
+            connection_manager.clone(),
+        )?;
 
         // Set up Kademlia DHT
-        let store = MemoryStore::new(local_peer_id);
+        let store = match &config.data_dir {
+            Some(dir) => PersistentRecordStore::open(
+                dir.join(RECORD_STORE_DIR),
+                local_peer_id,
+                config.record_store.clone(),
+            )?,
+            None => PersistentRecordStore::in_memory(local_peer_id, config.record_store.clone()),
+        }
+        .with_validator(DarkRecordValidator);
         let mut kad_config = kad::Config::default();
+        // Local records are republished from the store, which remembers
+        // publication times across restarts
+        kad_config.set_publication_interval(None);
+        // Records and provider records are stored by the node, once the
+        // sender's DHT rate limit allows it
+        kad_config.set_record_filtering(kad::StoreInserts::FilterBoth);
         kad_config.set_replication_factor(
             std::num::NonZeroUsize::new(config.kad_replication_factor)
                 .expect("Replication factor must be > 0"),
         );
-        let kademlia = kad::Behaviour::with_config(local_peer_id, store, kad_config);
+        let mut kademlia = kad::Behaviour::with_config(local_peer_id, store, kad_config);
+        // Serve records even before an external address is confirmed
+        kademlia.set_mode(Some(kad::Mode::Server));
 
         // Set up Gossipsub
         let gossipsub_config = config.gossipsub_config.clone().unwrap_or_else(|| {
@@ -460,10 +1624,31 @@ impl P2PNode {
                 .expect("Valid gossipsub config")
         });
 
-        let gossipsub = gossipsub::Behaviour::new(
+        // Typed topics are only forwarded once validated
+        let gossipsub_config = GossipsubConfigBuilder::from(gossipsub_config)
+            .validate_messages()
+            .build()?;
+        let mut gossipsub = gossipsub::Behaviour::new(
             MessageAuthenticity::Signed(local_key.clone()),
             gossipsub_config,
         )?;
+        let validators = GossipValidators::default();
+        if let Some((params, thresholds)) = config.peer_scoring.gossipsub.clone() {
+            gossipsub.with_peer_score(params, thresholds)?;
+        }
+
+        // Set up peer scoring
+        let scoring = match &config.data_dir {
+            Some(dir) => {
+                PeerScoring::open(dir.join(PEER_SCORES_FILE), config.peer_scoring.clone())?
+            }
+            None => PeerScoring::new(config.peer_scoring.clone()),
+        };
+        let resources = ResourceManager::new(ResourceConfig {
+            max_connections: config.resources.max_connections.min(config.max_connections),
+            ..config.resources.clone()
+        })
+        .with_scoring(scoring.clone());
 
         // Set up MDNS
         let mdns = if config.enable_mdns {
@@ -477,44 +1662,80 @@ impl P2PNode {
 
         // Set up other protocols
         let ping = ping::Behaviour::new(ping::Config::new());
-        let identify = identify::Behaviour::new(identify::Config::new(
-            "/qudag/1.0.0".to_string(),
-            local_key.public(),
-        ));
+        let identify = identify::Behaviour::new(
+            identify::Config::new("/qudag/1.0.0".to_string(), local_key.public())
+                .with_agent_version(agent_version(node_identity.certificate())),
+        );
 
-        let relay = relay::Behaviour::new(local_peer_id, Default::default());
+        let relay = Toggle::from(
+            (config.enable_relay && config.relay.server)
+                .then(|| relay::Behaviour::new(local_peer_id, config.relay.server_config())),
+        );
+        let relay_client = Toggle::from(relay_client);
         let dcutr = dcutr::Behaviour::new(local_peer_id);
+        let autonat =
+            Toggle::from(config.autonat.enabled.then(|| {
+                autonat::Behaviour::new(local_peer_id, config.autonat.behaviour_config())
+            }));
 
         // Set up request-response protocol
         let protocols = std::iter::once((
             StreamProtocol::new("/qudag/req/1.0.0"),
             ProtocolSupport::Full,
         ));
-        let request_response =
-            request_response::cbor::Behaviour::new(protocols, request_response::Config::default());
+        let request_response = request_response::cbor::Behaviour::new(
+            protocols,
+            request_response::Config::default().with_request_timeout(config.timeout),
+        );
+        let identity_exchange = request_response::cbor::Behaviour::new(
+            std::iter::once((
+                StreamProtocol::new(IDENTITY_PROTOCOL),
+                ProtocolSupport::Full,
+            )),
+            request_response::Config::default(),
+        );
+
+        let onion = request_response::cbor::Behaviour::new(
+            std::iter::once((StreamProtocol::new(ONION_PROTOCOL), ProtocolSupport::Full)),
+            request_response::Config::default().with_request_timeout(config.timeout),
+        );
 
         // Create the network behaviour
         let behaviour = NetworkBehaviourImpl {
+            peer_gate: PeerGate::new(scoring.clone()),
+            resource_limiter: ResourceLimiter::new(resources.clone()),
             kademlia,
             gossipsub,
             mdns,
             ping,
             identify,
             relay,
+            relay_client,
             dcutr,
+            autonat,
             request_response,
+            identity_exchange,
+            onion,
         };
 
         // Build the swarm
-        let swarm = libp2p::Swarm::new(
+        let mut swarm = libp2p::Swarm::new(
             transport,
             behaviour,
             local_peer_id,
-            libp2p::swarm::Config::with_tokio_executor(),
+            libp2p::swarm::Config::with_tokio_executor()
+                .with_idle_connection_timeout(IDLE_CONNECTION_TIMEOUT),
         );
+        swarm
+            .behaviour_mut()
+            .gossipsub
+            .subscribe(&IdentTopic::new(RELAY_DIRECTORY_TOPIC))?;
+        let onion_relay = OnionRelay::new(local_peer_id, config.onion.clone())?;
+        let cover = CoverScheduler::new(config.cover_traffic.clone());
 
         // Set up channels and state
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::channel(config.event_buffer.max(1));
+        let dropped_events = Arc::new(AtomicU64::new(0));
         let (command_tx, command_rx) = mpsc::unbounded_channel();
         let (router_tx, _) = mpsc::channel(1024);
         let router = Router::new(router_tx);
@@ -522,27 +1743,24 @@ impl P2PNode {
         // Initialize traffic obfuscation
         let cipher = ChaCha20Poly1305::new(Key::from_slice(&config.obfuscation_key));
 
-        // Initialize metrics if enabled
-        let metrics = if std::env::var("QUDAG_METRICS").is_ok() {
-            Some(()) // TODO: Initialize proper metrics
-        } else {
-            None
-        };
+        let metrics = config.metrics.as_ref().map(MetricsExporter::libp2p_metrics);
+
+        let address_book = AddressBook::new();
 
         // Create the handle
         let handle = P2PHandle {
             command_tx,
-            event_rx: Arc::new(Mutex::new(event_rx)),
+            event_rx: Arc::new(Mutex::new(Some(event_rx))),
+            dropped_events: dropped_events.clone(),
+            scoring: scoring.clone(),
+            resources: resources.clone(),
+            validators: validators.clone(),
+            address_book: address_book.clone(),
+            connection_manager,
         };
 
-        // Initialize message chunker (disabled for initial release)
-        // let chunker_config = ChunkerConfig {
-        //     max_chunk_size: 65536, // 64KB chunks
-        //     enable_compression: true,
-        //     compression_threshold: 1024, // Compress messages larger than 1KB
-        //     ..Default::default()
-        // };
-        // let message_chunker = MessageChunker::new(chunker_config);
+        #[cfg(feature = "message-chunking")]
+        let message_chunker = MessageChunker::new(config.chunker.clone());
 
         let node = Self {
             local_peer_id,
@@ -550,12 +1768,46 @@ impl P2PNode {
             router,
             cipher,
             event_tx,
+            dropped_events,
             command_rx,
             connected_peers: HashSet::new(),
             pending_requests: HashMap::new(),
+            outbound_requests: HashMap::new(),
+            pending_replies: FuturesUnordered::new(),
+            validators: validators.clone(),
+            pending_validations: FuturesUnordered::new(),
+            identity: node_identity,
+            pending_identities: HashMap::new(),
+            verified_identities: HashMap::new(),
+            pending_puts: HashMap::new(),
+            pending_gets: HashMap::new(),
             metrics,
             config,
-            // message_chunker,
+            #[cfg(feature = "message-chunking")]
+            message_chunker,
+            #[cfg(feature = "adaptive-batching")]
+            gossip_batches: HashMap::new(),
+            onion_relay,
+            directory: Arc::new(DirectoryClient::new()),
+            circuit_manager: CircuitManager::new(),
+            client_circuits: HashMap::new(),
+            circuit_builds: HashMap::new(),
+            onion_requests: HashMap::new(),
+            local_descriptor: None,
+            descriptor_published: false,
+            directory_fetched: HashSet::new(),
+            cover,
+            scoring,
+            resources,
+            address_book,
+            dialed_addrs: HashMap::new(),
+            dial_races: HashMap::new(),
+            stale_dials: HashSet::new(),
+            address_dials: HashMap::new(),
+            reservations: Reservations::default(),
+            relayed_connections: HashMap::new(),
+            relay_candidates: HashMap::new(),
+            reachability: ReachabilityState::default(),
         };
 
         Ok((node, handle))
@@ -563,9 +1815,8 @@ impl P2PNode {
 
     /// Starts the network node and begins listening on configured addresses
     pub async fn start(&mut self) -> Result<(), Box<dyn Error>> {
-        // Listen on all configured addresses
-        for addr_str in &self.config.listen_addrs {
-            let addr: Multiaddr = addr_str.parse()?;
+        // Listen on all configured addresses with every enabled transport
+        for addr in self.config.listen_multiaddrs()? {
             self.swarm.listen_on(addr)?;
         }
 
@@ -573,6 +1824,7 @@ impl P2PNode {
         for peer_addr_str in &self.config.bootstrap_peers {
             let peer_addr: Multiaddr = peer_addr_str.parse()?;
             if let Some(peer_id) = extract_peer_id(&peer_addr) {
+                self.address_book.add(peer_id, peer_addr.clone());
                 self.swarm
                     .behaviour_mut()
                     .kademlia
@@ -585,14 +1837,68 @@ impl P2PNode {
             warn!("Kademlia bootstrap failed: {}", e);
         }
 
+        self.maintain_reservations();
+
         info!("P2P node started");
         Ok(())
     }
 
     /// Main event loop for the P2P node
     pub async fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut record_maintenance = tokio::time::interval(RECORD_MAINTENANCE_INTERVAL);
+        let mut reassembly_cleanup = tokio::time::interval(REASSEMBLY_CLEANUP_INTERVAL);
+        let mut onion_maintenance = tokio::time::interval(ONION_MAINTENANCE_INTERVAL);
+        let mut scoring_maintenance = tokio::time::interval(PEER_SCORING_INTERVAL);
+        let mut resource_pruning = tokio::time::interval(RESOURCE_PRUNE_INTERVAL);
+        let mut relay_maintenance = tokio::time::interval(RELAY_MAINTENANCE_INTERVAL);
         loop {
+            let gossip_flush = self.next_gossip_flush();
+            let cover_due = self.cover.next_deadline();
+            let dial_due = self.next_dial_attempt();
             tokio::select! {
+                _ = record_maintenance.tick() => {
+                    self.maintain_records();
+                }
+                _ = reassembly_cleanup.tick() => {
+                    self.cleanup_reassembly().await;
+                }
+                _ = onion_maintenance.tick() => {
+                    self.maintain_onion().await;
+                }
+                _ = scoring_maintenance.tick() => {
+                    self.maintain_scoring();
+                }
+                _ = resource_pruning.tick() => {
+                    self.resources.prune();
+                }
+                _ = relay_maintenance.tick() => {
+                    self.maintain_reservations();
+                }
+                _ = tokio::time::sleep_until(
+                    tokio::time::Instant::from_std(gossip_flush.unwrap_or_else(Instant::now)),
+                ), if gossip_flush.is_some() => {
+                    self.flush_due_gossip();
+                }
+                _ = tokio::time::sleep_until(
+                    tokio::time::Instant::from_std(cover_due.unwrap_or_else(Instant::now)),
+                ), if cover_due.is_some() => {
+                    self.send_due_cover();
+                }
+                _ = tokio::time::sleep_until(
+                    tokio::time::Instant::from_std(dial_due.unwrap_or_else(Instant::now)),
+                ), if dial_due.is_some() => {
+                    self.advance_dial_races();
+                }
+                Some((channel, request_id, reply)) = self.pending_replies.next(),
+                    if !self.pending_replies.is_empty() =>
+                {
+                    self.send_reply(channel, request_id, reply);
+                }
+                Some((message_id, source, validation)) = self.pending_validations.next(),
+                    if !self.pending_validations.is_empty() =>
+                {
+                    self.finish_validation(message_id, source, validation)?;
+                }
                 swarm_event = self.swarm.next() => {
                     if let Some(event) = swarm_event {
                         self.handle_swarm_event(event).await?;
@@ -611,63 +1917,338 @@ impl P2PNode {
         Ok(())
     }
 
-    /// Handle swarm events
-    async fn handle_swarm_event(
-        &mut self,
-        event: SwarmEvent<NetworkBehaviourEvent>,
-    ) -> Result<(), Box<dyn Error>> {
-        match event {
-            SwarmEvent::NewListenAddr { address, .. } => {
-                info!("Listening on {}", address);
+    /// Drop chunk reassemblies that stopped making progress
+    async fn cleanup_reassembly(&mut self) {
+        #[cfg(feature = "message-chunking")]
+        {
+            let dropped = self.message_chunker.cleanup_expired().await;
+            if dropped > 0 {
+                debug!("Dropped {} stalled chunk reassemblies", dropped);
             }
-            SwarmEvent::ConnectionEstablished {
-                peer_id,
-                endpoint,
-                num_established,
-                ..
-            } => {
-                info!(
-                    "Connection established with {} at {} ({} total connections)",
-                    peer_id,
-                    endpoint.get_remote_address(),
-                    num_established
-                );
-                self.connected_peers.insert(peer_id);
-                self.event_tx.send(P2PEvent::PeerConnected(peer_id))?;
+        }
+    }
 
-                // Update router
-                if let Ok(socket_addr) = endpoint.get_remote_address().to_string().parse() {
-                    self.router
-                        .add_discovered_peer(
-                            peer_id,
-                            crate::discovery::DiscoveredPeer::new(
-                                peer_id,
-                                socket_addr,
-                                crate::discovery::DiscoveryMethod::Kademlia,
-                            ),
-                        )
-                        .await;
-                }
-            }
-            SwarmEvent::ConnectionClosed {
-                peer_id,
-                num_established,
-                ..
-            } => {
-                info!(
-                    "Connection closed with {} ({} remaining connections)",
-                    peer_id, num_established
-                );
-                if num_established == 0 {
-                    self.connected_peers.remove(&peer_id);
-                    self.event_tx.send(P2PEvent::PeerDisconnected(peer_id))?;
+    /// When the earliest pending gossip batch is due
+    fn next_gossip_flush(&self) -> Option<Instant> {
+        #[cfg(feature = "adaptive-batching")]
+        {
+            self.gossip_batches
+                .values()
+                .filter_map(|batch| batch.batcher.time_until_flush())
+                .min()
+                .map(|wait| Instant::now() + wait)
+        }
+        #[cfg(not(feature = "adaptive-batching"))]
+        None
+    }
 
-                    // Update router
-                    self.router.remove_discovered_peer(peer_id).await;
+    /// Publish the gossip batches whose latency target has passed
+    fn flush_due_gossip(&mut self) {
+        #[cfg(feature = "adaptive-batching")]
+        {
+            let due: Vec<_> = self
+                .gossip_batches
+                .iter_mut()
+                .filter(|(_, batch)| batch.batcher.time_until_flush() == Some(Duration::ZERO))
+                .map(|(topic, batch)| (topic.clone(), batch.flush()))
+                .collect();
+            for (topic, messages) in due {
+                if let Err(e) = self.publish_batch(&topic, &messages) {
+                    warn!(
+                        "Failed to publish {} messages to {}: {}",
+                        messages.len(),
+                        topic,
+                        e
+                    );
                 }
             }
-            SwarmEvent::Behaviour(behaviour_event) => {
-                self.handle_behaviour_event(behaviour_event).await?;
+        }
+    }
+
+    /// Expire idle relay circuits and stale relays, and publish this node's
+    /// relay descriptor when it changed or is due again
+    async fn maintain_onion(&mut self) {
+        for (peer_id, circuit_id) in self.onion_relay.expire_idle() {
+            self.send_onion_cell(
+                peer_id,
+                OnionCell::Destroy { circuit_id },
+                OnionExchange::Notify,
+            );
+        }
+        let removed = self.directory.remove_stale().await;
+        if removed > 0 {
+            debug!("Dropped {} stale relays from the directory", removed);
+        }
+        if self.config.onion.enabled {
+            self.publish_descriptor();
+        }
+    }
+
+    /// Sign a fresh relay descriptor if needed and gossip it until that succeeds
+    fn publish_descriptor(&mut self) {
+        let addresses: Vec<Multiaddr> = self.swarm.listeners().cloned().collect();
+        if addresses.is_empty() {
+            return;
+        }
+        let republish = self.config.onion.republish_interval;
+        if self
+            .local_descriptor
+            .as_ref()
+            .is_none_or(|(_, signed)| signed.elapsed() >= republish)
+        {
+            match RelayDescriptor::new(
+                &self.identity,
+                self.onion_relay.onion_key(),
+                &addresses,
+                &self.config.onion,
+            ) {
+                Ok(descriptor) => {
+                    self.local_descriptor = Some((descriptor, Instant::now()));
+                    self.descriptor_published = false;
+                }
+                Err(e) => {
+                    warn!("Failed to sign relay descriptor: {}", e);
+                    return;
+                }
+            }
+        }
+        if self.descriptor_published {
+            return;
+        }
+        let Some((descriptor, _)) = &self.local_descriptor else {
+            return;
+        };
+        // Without subscribed peers this fails and is retried on the next tick
+        let frame = encode_gossip_single(&descriptor.to_bytes());
+        match self.publish_frame(RELAY_DIRECTORY_TOPIC, &frame) {
+            Ok(()) => {
+                debug!("Published relay descriptor");
+                self.descriptor_published = true;
+            }
+            Err(e) => debug!("Relay descriptor not published yet: {}", e),
+        }
+    }
+
+    /// Decay peer scores and exchange them with gossipsub
+    ///
+    /// The local score becomes the gossipsub application score, and the
+    /// rest of the gossipsub score counts towards bans.
+    fn maintain_scoring(&mut self) {
+        self.scoring.maintain();
+        let Some((params, _)) = &self.config.peer_scoring.gossipsub else {
+            return;
+        };
+        let app_weight = params.app_specific_weight;
+        let gossipsub = &mut self.swarm.behaviour_mut().gossipsub;
+        for peer_id in &self.connected_peers {
+            let local = self.scoring.score(peer_id).local;
+            gossipsub.set_application_score(peer_id, local);
+            if let Some(score) = gossipsub.peer_score(peer_id) {
+                self.scoring
+                    .set_gossip_score(*peer_id, score - app_weight * local);
+            }
+        }
+    }
+
+    /// Apply bans to gossipsub and the DHT and tell the application
+    fn handle_scoring_event(&mut self, event: PeerScoringEvent) -> Result<(), Box<dyn Error>> {
+        let behaviour = self.swarm.behaviour_mut();
+        match event {
+            PeerScoringEvent::Banned(ban) => {
+                behaviour.gossipsub.blacklist_peer(&ban.peer);
+                behaviour.kademlia.remove_peer(&ban.peer);
+                self.emit(P2PEvent::PeerBanned(ban))?;
+            }
+            PeerScoringEvent::Unbanned(peer_id) => {
+                behaviour.gossipsub.remove_blacklisted_peer(&peer_id);
+                self.emit(P2PEvent::PeerUnbanned(peer_id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a record or provider record a peer put, within its DHT rate
+    fn store_inbound_record(&mut self, request: kad::InboundRequest) {
+        match request {
+            kad::InboundRequest::PutRecord {
+                source,
+                record: Some(record),
+                ..
+            } => {
+                if self
+                    .resources
+                    .admit(source, ResourceProtocol::Dht, record.value.len())
+                    .is_err()
+                {
+                    return;
+                }
+                let store = self.swarm.behaviour_mut().kademlia.store_mut();
+                if let Err(e) = store.put(record) {
+                    debug!("Rejected DHT record from {}: {:?}", source, e);
+                }
+            }
+            kad::InboundRequest::AddProvider {
+                record: Some(record),
+            } => {
+                if self
+                    .resources
+                    .admit(record.provider, ResourceProtocol::Dht, 0)
+                    .is_err()
+                {
+                    return;
+                }
+                let store = self.swarm.behaviour_mut().kademlia.store_mut();
+                if let Err(e) = store.add_provider(record) {
+                    debug!("Rejected provider record: {:?}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Drop expired DHT entries and republish local records that are due
+    fn maintain_records(&mut self) {
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        kademlia.store_mut().remove_expired();
+        for record in kademlia.store_mut().due_for_republish() {
+            debug!("Republishing DHT record {:?}", record.key);
+            if let Err(e) = kademlia.put_record(record, Quorum::One) {
+                warn!("Failed to republish DHT record: {:?}", e);
+            }
+        }
+    }
+
+    /// Handle swarm events
+    async fn handle_swarm_event(
+        &mut self,
+        event: SwarmEvent<NetworkBehaviourEvent>,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(&event);
+        }
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => {
+                info!("Listening on {}", address);
+                // A relay is publicly reachable at its own addresses
+                if self.config.relay.server && !is_relayed(&address) {
+                    self.swarm.add_external_address(address);
+                }
+                // Announce the new address in a fresh relay descriptor
+                self.local_descriptor = None;
+            }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } => {
+                if let Some(relay) = self.reservations.close(listener_id) {
+                    match reason {
+                        Ok(()) => info!("Reservation on relay {} ended", relay),
+                        Err(e) => warn!("Reservation on relay {} failed: {}", relay, e),
+                    }
+                    self.emit(P2PEvent::RelayReservationClosed { relay })?;
+                }
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
+                if endpoint.is_relayed() {
+                    self.relayed_connections
+                        .entry(peer_id)
+                        .or_default()
+                        .insert(connection_id);
+                }
+                if endpoint.is_dialer() {
+                    let addr = endpoint.get_remote_address().clone();
+                    self.address_book.add(peer_id, addr.clone());
+                    self.address_book.record_success(peer_id, &addr);
+                    self.dialed_addrs.insert(connection_id, addr);
+                }
+                if self.stale_dials.remove(&connection_id) && num_established.get() > 1 {
+                    // Lost a race another address already won
+                    self.swarm.close_connection(connection_id);
+                }
+                if let Some(race) = self.dial_races.get_mut(&peer_id) {
+                    race.attempts.remove(&connection_id);
+                }
+                self.finish_dial_race(peer_id, Ok(()));
+                if let Some(response) = self.address_dials.remove(&connection_id) {
+                    let _ = response.send(Ok(peer_id));
+                }
+                info!(
+                    "Connection established with {} at {} ({} total connections)",
+                    peer_id,
+                    endpoint.get_remote_address(),
+                    num_established
+                );
+                self.connected_peers.insert(peer_id);
+                metrics::gauge!("qudag_connected_peers", self.connected_peers.len() as f64);
+                self.cover.add_link(CoverLink::Connection(peer_id));
+                self.emit(P2PEvent::PeerConnected(peer_id))?;
+
+                // Update router
+                if let Ok(socket_addr) = endpoint.get_remote_address().to_string().parse() {
+                    self.router
+                        .add_discovered_peer(
+                            peer_id,
+                            crate::discovery::DiscoveredPeer::new(
+                                peer_id,
+                                socket_addr,
+                                crate::discovery::DiscoveryMethod::Kademlia,
+                            ),
+                        )
+                        .await;
+                }
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                connection_id,
+                num_established,
+                ..
+            } => {
+                self.dialed_addrs.remove(&connection_id);
+                if let Some(relayed) = self.relayed_connections.get_mut(&peer_id) {
+                    relayed.remove(&connection_id);
+                    if relayed.is_empty() {
+                        self.relayed_connections.remove(&peer_id);
+                    }
+                }
+                info!(
+                    "Connection closed with {} ({} remaining connections)",
+                    peer_id, num_established
+                );
+                if num_established == 0 {
+                    self.connected_peers.remove(&peer_id);
+                    metrics::gauge!("qudag_connected_peers", self.connected_peers.len() as f64);
+                    self.directory_fetched.remove(&peer_id);
+                    self.relay_candidates.remove(&peer_id);
+                    self.cover.remove_link(&CoverLink::Connection(peer_id));
+                    self.emit(P2PEvent::PeerDisconnected(peer_id))?;
+
+                    // Update router
+                    self.router.remove_discovered_peer(peer_id).await;
+                }
+            }
+            SwarmEvent::OutgoingConnectionError {
+                connection_id,
+                peer_id,
+                error,
+            } => {
+                debug!("Dial of {:?} failed: {}", peer_id, error);
+                self.stale_dials.remove(&connection_id);
+                if let Some(response) = self.address_dials.remove(&connection_id) {
+                    let _ = response.send(Err(format!("Dial error: {}", error).into()));
+                }
+                if let Some(peer_id) = peer_id {
+                    self.dial_attempt_failed(peer_id, connection_id);
+                }
+            }
+            SwarmEvent::Behaviour(behaviour_event) => {
+                self.handle_behaviour_event(behaviour_event).await?;
             }
             _ => {}
         }
@@ -679,7 +2260,21 @@ impl P2PNode {
         &mut self,
         event: NetworkBehaviourEvent,
     ) -> Result<(), Box<dyn Error>> {
+        if let Some(metrics) = &self.metrics {
+            match &event {
+                NetworkBehaviourEvent::Kademlia(e) => metrics.record(e),
+                NetworkBehaviourEvent::Gossipsub(e) => metrics.record(e),
+                NetworkBehaviourEvent::Ping(e) => metrics.record(e),
+                NetworkBehaviourEvent::Identify(e) => metrics.record(e),
+                NetworkBehaviourEvent::Relay(e) => metrics.record(e),
+                NetworkBehaviourEvent::Dcutr(e) => metrics.record(e),
+                _ => {}
+            }
+        }
         match event {
+            NetworkBehaviourEvent::PeerGate(scoring_event) => {
+                self.handle_scoring_event(scoring_event)?;
+            }
             NetworkBehaviourEvent::Kademlia(kad_event) => {
                 self.handle_kademlia_event(kad_event).await?;
             }
@@ -698,12 +2293,24 @@ impl P2PNode {
             NetworkBehaviourEvent::RequestResponse(req_res_event) => {
                 self.handle_request_response_event(req_res_event).await?;
             }
+            NetworkBehaviourEvent::IdentityExchange(identity_event) => {
+                self.handle_identity_exchange_event(identity_event)?;
+            }
+            NetworkBehaviourEvent::Onion(onion_event) => {
+                self.handle_onion_event(onion_event).await?;
+            }
             NetworkBehaviourEvent::Relay(relay_event) => {
                 self.handle_relay_event(relay_event).await?;
             }
+            NetworkBehaviourEvent::RelayClient(client_event) => {
+                self.handle_relay_client_event(client_event)?;
+            }
             NetworkBehaviourEvent::Dcutr(dcutr_event) => {
                 self.handle_dcutr_event(dcutr_event).await?;
             }
+            NetworkBehaviourEvent::AutoNat(autonat_event) => {
+                self.handle_autonat_event(autonat_event)?;
+            }
         }
         Ok(())
     }
@@ -716,29 +2323,66 @@ impl P2PNode {
             } => {
                 debug!("Kademlia routing updated for peer {}", peer);
                 for addr in addresses.iter() {
+                    self.address_book.add(peer, addr.clone());
                     self.swarm
                         .behaviour_mut()
                         .kademlia
                         .add_address(&peer, addr.clone());
                 }
-                self.event_tx.send(P2PEvent::RoutingTableUpdated)?;
+                self.emit(P2PEvent::RoutingTableUpdated)?;
             }
             kad::Event::UnroutablePeer { peer } => {
                 warn!("Peer {} is unroutable", peer);
             }
             kad::Event::InboundRequest { request } => {
                 debug!("Kademlia inbound request: {:?}", request);
+                self.store_inbound_record(request);
             }
-            kad::Event::OutboundQueryProgressed { result, .. } => match result {
+            kad::Event::OutboundQueryProgressed {
+                id, result, step, ..
+            } => match result {
                 QueryResult::GetClosestPeers(result) => match result {
                     Ok(ok) => {
                         for peer in ok.peers {
                             debug!("Found closest peer: {}", peer);
-                            self.event_tx.send(P2PEvent::PeerDiscovered(peer))?;
+                            self.emit(P2PEvent::PeerDiscovered(peer))?;
                         }
                     }
                     Err(e) => warn!("Get closest peers error: {:?}", e),
                 },
+                QueryResult::PutRecord(result) => {
+                    if let Some(response) = self.pending_puts.remove(&id) {
+                        let result = result.map(|_| ()).map_err(|e| {
+                            Box::<dyn Error + Send + Sync>::from(format!(
+                                "Put record error: {:?}",
+                                e
+                            ))
+                        });
+                        let _ = response.send(result);
+                    }
+                }
+                QueryResult::GetRecord(result) => {
+                    let finished = match result {
+                        Ok(kad::GetRecordOk::FoundRecord(found)) => {
+                            if let Some(pending) = self.pending_gets.get_mut(&id) {
+                                if !pending.values.contains(&found.record.value) {
+                                    pending.values.push(found.record.value);
+                                }
+                            }
+                            step.last
+                        }
+                        Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => true,
+                        Err(e) => {
+                            debug!("Get record query ended: {:?}", e);
+                            true
+                        }
+                    };
+                    if finished {
+                        if let Some(pending) = self.pending_gets.remove(&id) {
+                            let _ = pending.response.send(Ok(pending.values));
+                        }
+                    }
+                }
                 _ => {}
             },
             _ => {}
@@ -754,26 +2398,80 @@ impl P2PNode {
         match event {
             gossipsub::Event::Message {
                 propagation_source,
+                message_id,
                 message,
-                ..
             } => {
+                if self
+                    .resources
+                    .admit(
+                        propagation_source,
+                        ResourceProtocol::Gossip,
+                        message.data.len(),
+                    )
+                    .is_err()
+                {
+                    self.report_validation(&message_id, &propagation_source, Verdict::Ignore);
+                    return Ok(());
+                }
                 let topic = message.topic.to_string();
                 let data = message.data;
 
-                // Deobfuscate if needed
+                // Every node obfuscates what it publishes, so data that
+                // does not open was sealed with another network's key. That
+                // is a configuration mismatch, not misbehaviour, so the
+                // sender is not penalized.
                 let decrypted_data = match self.deobfuscate_traffic(&data) {
                     Ok(d) => d,
-                    Err(_) => data, // Assume not obfuscated
+                    Err(e) => {
+                        debug!("Dropping gossip from {}: {}", propagation_source, e);
+                        self.report_validation(&message_id, &propagation_source, Verdict::Ignore);
+                        return Ok(());
+                    }
                 };
 
-                self.event_tx.send(P2PEvent::MessageReceived {
-                    peer_id: propagation_source,
-                    topic,
-                    data: decrypted_data,
-                })?;
+                let Some(messages) = decode_gossip_frame(&decrypted_data) else {
+                    debug!("Dropping malformed gossip from {}", propagation_source);
+                    self.report_validation(&message_id, &propagation_source, Verdict::Reject);
+                    self.scoring
+                        .report(propagation_source, PeerEvent::InvalidMessage);
+                    return Ok(());
+                };
+                if let Some(topic) = GossipTopic::from_topic(&topic) {
+                    let validators = self.validators.clone();
+                    self.pending_validations.push(
+                        async move {
+                            let validation = validators
+                                .validate(propagation_source, topic, messages)
+                                .await;
+                            (message_id, propagation_source, validation)
+                        }
+                        .boxed(),
+                    );
+                    return Ok(());
+                }
+                self.report_validation(&message_id, &propagation_source, Verdict::Accept);
+                if topic == RELAY_DIRECTORY_TOPIC {
+                    for data in messages {
+                        self.ingest_descriptor(propagation_source, &data).await;
+                    }
+                    return Ok(());
+                }
+                for data in messages {
+                    self.emit(P2PEvent::MessageReceived {
+                        peer_id: propagation_source,
+                        topic: topic.clone(),
+                        data,
+                    })?;
+                }
             }
             gossipsub::Event::Subscribed { peer_id, topic } => {
                 debug!("Peer {} subscribed to topic {}", peer_id, topic);
+                // Gossip only carries descriptors published from now on
+                if topic == IdentTopic::new(RELAY_DIRECTORY_TOPIC).hash()
+                    && self.directory_fetched.insert(peer_id)
+                {
+                    self.send_onion_cell(peer_id, OnionCell::FetchDirectory, OnionExchange::Fetch);
+                }
             }
             gossipsub::Event::Unsubscribed { peer_id, topic } => {
                 debug!("Peer {} unsubscribed from topic {}", peer_id, topic);
@@ -783,6 +2481,51 @@ impl P2PNode {
         Ok(())
     }
 
+    /// Report a verdict to gossipsub, which forwards accepted messages
+    fn report_validation(
+        &mut self,
+        message_id: &gossipsub::MessageId,
+        source: &LibP2PPeerId,
+        verdict: Verdict,
+    ) {
+        metrics::counter!("qudag_gossip_validation_total", 1, "verdict" => match verdict {
+            Verdict::Accept => "accept",
+            Verdict::Reject => "reject",
+            Verdict::Ignore => "ignore",
+        });
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(message_id, source, verdict.into());
+    }
+
+    /// Act on the verdict for a gossip message on a typed topic
+    fn finish_validation(
+        &mut self,
+        message_id: gossipsub::MessageId,
+        source: LibP2PPeerId,
+        validation: Validation,
+    ) -> Result<(), Box<dyn Error>> {
+        self.report_validation(&message_id, &source, validation.verdict);
+        if validation.verdict == Verdict::Reject {
+            warn!("Rejected gossip message from {}", source);
+            self.scoring.report(source, PeerEvent::InvalidMessage);
+        } else if validation.rejected > 0 {
+            debug!(
+                "Dropped {} invalid messages of a batch from {}",
+                validation.rejected, source
+            );
+        }
+        for message in validation.accepted {
+            self.emit(P2PEvent::GossipMessageReceived {
+                peer_id: source,
+                message,
+            })?;
+        }
+        Ok(())
+    }
+
     /// Handle MDNS events
     async fn handle_mdns_event(&mut self, event: mdns::Event) -> Result<(), Box<dyn Error>> {
         match event {
@@ -793,7 +2536,7 @@ impl P2PNode {
                         .behaviour_mut()
                         .kademlia
                         .add_address(&peer_id, addr);
-                    self.event_tx.send(P2PEvent::PeerDiscovered(peer_id))?;
+                    self.emit(P2PEvent::PeerDiscovered(peer_id))?;
                 }
             }
             mdns::Event::Expired(peers) => {
@@ -810,9 +2553,15 @@ impl P2PNode {
         match event.result {
             Ok(duration) => {
                 debug!("Ping to {} successful: {:?}", event.peer, duration);
+                if let Some(addr) = self.dialed_addrs.get(&event.connection) {
+                    self.address_book.record_rtt(event.peer, addr, duration);
+                }
             }
             Err(e) => {
                 debug!("Ping to {} failed: {}", event.peer, e);
+                if matches!(e, ping::Failure::Timeout) {
+                    self.scoring.report(event.peer, PeerEvent::Timeout);
+                }
             }
         }
         Ok(())
@@ -830,12 +2579,45 @@ impl P2PNode {
                     peer_id, info.protocols, info.agent_version
                 );
 
-                // Add observed addresses to Kademlia
+                // Remember relays and reserve on them while short of
+                // reservations
+                if info.protocols.contains(&relay::HOP_PROTOCOL_NAME) {
+                    let relay_addr = info
+                        .listen_addrs
+                        .iter()
+                        .find(|addr| !is_relayed(addr) && self.config.supports(addr))
+                        .cloned();
+                    if let Some(relay_addr) = relay_addr {
+                        self.reserve_on(peer_id, &relay_addr);
+                        self.relay_candidates.insert(peer_id, relay_addr);
+                    }
+                }
+
+                // Add observed addresses to Kademlia if the peer serves the
+                // DHT, which it does not while privately reachable
+                let serves_dht = info.protocols.contains(&kad::PROTOCOL_NAME);
                 for addr in info.listen_addrs {
-                    self.swarm
-                        .behaviour_mut()
-                        .kademlia
-                        .add_address(&peer_id, addr);
+                    self.address_book.add(peer_id, addr.clone());
+                    if serves_dht {
+                        self.swarm
+                            .behaviour_mut()
+                            .kademlia
+                            .add_address(&peer_id, addr);
+                    }
+                }
+
+                // Fetch the advertised identity certificate unless already verified
+                if let Some(digest) = advertised_digest(&info.agent_version) {
+                    let known = self
+                        .verified_identities
+                        .get(&peer_id)
+                        .is_some_and(|(verified, _)| *verified == digest);
+                    if !known && self.pending_identities.insert(peer_id, digest).is_none() {
+                        self.swarm
+                            .behaviour_mut()
+                            .identity_exchange
+                            .send_request(&peer_id, IdentityRequest);
+                    }
                 }
             }
             identify::Event::Sent { .. } => {}
@@ -909,6 +2691,166 @@ impl P2PNode {
         Ok(())
     }
 
+    /// Handle relay client events
+    fn handle_relay_client_event(
+        &mut self,
+        event: relay::client::Event,
+    ) -> Result<(), Box<dyn Error>> {
+        match event {
+            relay::client::Event::ReservationReqAccepted {
+                relay_peer_id,
+                renewal,
+                ..
+            } => {
+                debug!(
+                    "Reservation on relay {} accepted: renewal={}",
+                    relay_peer_id, renewal
+                );
+                if self.reservations.accept(&relay_peer_id) {
+                    info!("Reachable through relay {}", relay_peer_id);
+                    self.emit(P2PEvent::RelayReservationAccepted {
+                        relay: relay_peer_id,
+                    })?;
+                }
+            }
+            relay::client::Event::OutboundCircuitEstablished { relay_peer_id, .. } => {
+                debug!("Circuit established through relay {}", relay_peer_id);
+            }
+            relay::client::Event::InboundCircuitEstablished { src_peer_id, .. } => {
+                debug!("Relayed circuit from {} accepted", src_peer_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the node reserves on relays: when configured to, or while
+    /// it is privately reachable
+    fn wants_reservations(&self) -> bool {
+        self.config.enable_relay
+            && (self.config.relay.reserve
+                || self.reachability.reachability() == Reachability::Private)
+    }
+
+    /// Request reservations on the configured relays, then on connected
+    /// relays, that hold none
+    fn maintain_reservations(&mut self) {
+        if !self.wants_reservations() {
+            return;
+        }
+        for relay_addr in self.config.relay.relays.clone() {
+            match extract_peer_id(&relay_addr) {
+                Some(relay) => self.reserve_on(relay, &relay_addr),
+                None => warn!("Relay address {} lacks a peer ID", relay_addr),
+            }
+        }
+        let candidates: Vec<_> = self
+            .relay_candidates
+            .iter()
+            .map(|(relay, addr)| (*relay, addr.clone()))
+            .collect();
+        for (relay, relay_addr) in candidates {
+            self.reserve_on(relay, &relay_addr);
+        }
+    }
+
+    /// Request a reservation on `relay` reached at `relay_addr`, unless one
+    /// is held already or enough relays hold one
+    fn reserve_on(&mut self, relay: LibP2PPeerId, relay_addr: &Multiaddr) {
+        if !self.wants_reservations()
+            || relay == self.local_peer_id
+            || self.reservations.contains(&relay)
+            || self.reservations.len() >= self.config.relay.max_relays
+        {
+            return;
+        }
+        let addr = circuit_listen_addr(relay_addr, relay);
+        match self.swarm.listen_on(addr.clone()) {
+            Ok(listener) => {
+                debug!("Requesting a reservation at {}", addr);
+                self.reservations.request(relay, listener);
+            }
+            Err(e) => warn!("Cannot reserve at {}: {}", addr, e),
+        }
+    }
+
+    /// Handle AutoNAT events
+    fn handle_autonat_event(&mut self, event: autonat::Event) -> Result<(), Box<dyn Error>> {
+        match event {
+            autonat::Event::StatusChanged { old, new } => {
+                info!("AutoNAT status changed from {:?} to {:?}", old, new);
+                if let Some(reachability) = self.reachability.on_autonat(&new) {
+                    self.apply_reachability(reachability)?;
+                }
+            }
+            autonat::Event::OutboundProbe(probe) => {
+                debug!("AutoNAT probe: {:?}", probe);
+            }
+            autonat::Event::InboundProbe(probe) => {
+                debug!("AutoNAT probe served: {:?}", probe);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adapt the node to a change of its reachability
+    ///
+    /// Publicly reachable nodes serve the DHT at their direct addresses;
+    /// privately reachable ones advertise relayed addresses only and use
+    /// the DHT as clients.
+    fn apply_reachability(&mut self, reachability: Reachability) -> Result<(), Box<dyn Error>> {
+        info!("Reachability is now {:?}", reachability);
+        match reachability {
+            Reachability::Public => {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .set_mode(Some(kad::Mode::Server));
+                // AutoNAT confirms the addresses it probed; STUN vouches
+                // for the listen addresses on the public IP it saw
+                if let Some(public_ip) = self.reachability.stun_public_ip() {
+                    let addresses: Vec<Multiaddr> = self
+                        .swarm
+                        .listeners()
+                        .filter(|addr| !is_relayed(addr) && ip_of(addr) == Some(public_ip))
+                        .cloned()
+                        .collect();
+                    for address in addresses {
+                        self.swarm.add_external_address(address);
+                    }
+                }
+                if !self.config.relay.reserve {
+                    for listener in self.reservations.listeners() {
+                        self.swarm.remove_listener(listener);
+                    }
+                }
+            }
+            Reachability::Private => {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .set_mode(Some(kad::Mode::Client));
+                let direct: Vec<Multiaddr> = self
+                    .swarm
+                    .external_addresses()
+                    .filter(|addr| !is_relayed(addr))
+                    .cloned()
+                    .collect();
+                for address in direct {
+                    self.swarm.remove_external_address(&address);
+                }
+                self.maintain_reservations();
+            }
+            Reachability::Unknown => {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .set_mode(Some(kad::Mode::Server));
+            }
+        }
+        self.emit(P2PEvent::ReachabilityChanged(reachability))?;
+        Ok(())
+    }
+
     /// Handle DCUTR events
     async fn handle_dcutr_event(&mut self, event: dcutr::Event) -> Result<(), Box<dyn Error>> {
         match event {
@@ -921,6 +2863,15 @@ impl P2PNode {
                         "Direct connection upgrade succeeded with peer {} (connection: {:?})",
                         remote_peer_id, connection_id
                     );
+                    // The direct connection replaces the relayed ones
+                    for relayed in self
+                        .relayed_connections
+                        .remove(&remote_peer_id)
+                        .unwrap_or_default()
+                    {
+                        self.swarm.close_connection(relayed);
+                    }
+                    self.emit(P2PEvent::DirectConnectionUpgraded(remote_peer_id))?;
                 }
                 Err(error) => {
                     warn!(
@@ -930,72 +2881,726 @@ impl P2PNode {
                 }
             },
         }
-        Ok(())
+        Ok(())
+    }
+
+    /// Handle request-response events
+    async fn handle_request_response_event(
+        &mut self,
+        event: request_response::Event<QuDagRequest, QuDagResponse>,
+    ) -> Result<(), Box<dyn Error>> {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    let QuDagRequest {
+                        request_id,
+                        payload,
+                    } = request;
+                    // Dropping the channel fails the request at the sender
+                    if self
+                        .resources
+                        .admit(peer, ResourceProtocol::Request, payload.len())
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                    let payload = match bincode::deserialize::<RequestFrame>(&payload) {
+                        Ok(RequestFrame::Whole(payload)) => payload,
+                        #[cfg(feature = "message-chunking")]
+                        Ok(RequestFrame::Chunk(chunk)) => {
+                            match self
+                                .message_chunker
+                                .process_chunk_from(&peer.to_string(), chunk)
+                                .await
+                            {
+                                Ok(Some(payload)) => payload,
+                                Ok(None) => {
+                                    self.send_response_frame(
+                                        channel,
+                                        request_id,
+                                        ResponseFrame::Ack,
+                                    );
+                                    return Ok(());
+                                }
+                                Err(e) => {
+                                    // Dropping the channel fails the request at the sender
+                                    warn!(
+                                        "Rejected chunk of request {} from {}: {}",
+                                        request_id, peer, e
+                                    );
+                                    return Ok(());
+                                }
+                            }
+                        }
+                        #[cfg(not(feature = "message-chunking"))]
+                        Ok(RequestFrame::Chunk(_)) => {
+                            // Dropping the channel fails the request at the sender
+                            debug!(
+                                "Refused chunked request {} from {}: chunking is disabled",
+                                request_id, peer
+                            );
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            // Dropping the channel fails the request at the sender
+                            warn!(
+                                "Rejected malformed request {} from {}: {}",
+                                request_id, peer, e
+                            );
+                            self.scoring.report(peer, PeerEvent::InvalidMessage);
+                            return Ok(());
+                        }
+                    };
+
+                    // Hold a stream and the payload's memory until the
+                    // application answers
+                    let permit: StreamPermit = match self.resources.open_stream(
+                        peer,
+                        ResourceProtocol::Request,
+                        payload.len(),
+                    ) {
+                        Ok(permit) => permit,
+                        Err(_) => return Ok(()),
+                    };
+
+                    // Hand the request to the application and reply once it answers
+                    let (tx, rx) = oneshot::channel();
+                    let delivered = self.emit(P2PEvent::RequestReceived {
+                        peer_id: peer,
+                        request: QuDagRequest {
+                            request_id: request_id.clone(),
+                            payload,
+                        },
+                        channel: tx,
+                    })?;
+                    if !delivered {
+                        // Dropping the channel fails the request at the sender
+                        return Ok(());
+                    }
+                    self.pending_replies.push(
+                        async move {
+                            let reply = rx.await.ok();
+                            drop(permit);
+                            (channel, request_id, reply)
+                        }
+                        .boxed(),
+                    );
+                }
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => {
+                    let Some(id) = self.outbound_requests.remove(&request_id) else {
+                        return Ok(());
+                    };
+                    match bincode::deserialize::<ResponseFrame>(&response.payload) {
+                        Ok(ResponseFrame::Ack) => self.continue_request(&id),
+                        Ok(ResponseFrame::Reply(payload)) => self.complete_request(
+                            &id,
+                            Ok(QuDagResponse {
+                                request_id: id.clone(),
+                                payload,
+                            }),
+                        ),
+                        // Unframed response
+                        Err(_) => self.complete_request(&id, Ok(response)),
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                warn!(
+                    "Request to {} failed (id: {}): {:?}",
+                    peer, request_id, error
+                );
+                if matches!(error, request_response::OutboundFailure::Timeout) {
+                    self.scoring.report(peer, PeerEvent::Timeout);
+                }
+                if let Some(id) = self.outbound_requests.remove(&request_id) {
+                    self.complete_request(
+                        &id,
+                        Err(format!("Request to {} failed: {:?}", peer, error).into()),
+                    );
+                }
+            }
+            request_response::Event::InboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                warn!(
+                    "Inbound request from {} failed (id: {}): {:?}",
+                    peer, request_id, error
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle identity certificate requests and responses
+    fn handle_identity_exchange_event(
+        &mut self,
+        event: request_response::Event<IdentityRequest, IdentityCertificate>,
+    ) -> Result<(), Box<dyn Error>> {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request { channel, .. } => {
+                    if self
+                        .resources
+                        .admit(peer, ResourceProtocol::Identity, 0)
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                    let certificate = self.identity.certificate().clone();
+                    self.swarm
+                        .behaviour_mut()
+                        .identity_exchange
+                        .send_response(channel, certificate)
+                        .map_err(|_| "Failed to send identity certificate")?;
+                }
+                request_response::Message::Response { response, .. } => {
+                    let Some(digest) = self.pending_identities.remove(&peer) else {
+                        return Ok(());
+                    };
+                    match response.verify_for(&peer, &digest) {
+                        Ok(()) => {
+                            debug!("Verified ML-DSA identity of {}", peer);
+                            let ml_dsa_public_key = response.ml_dsa_public_key;
+                            self.verified_identities
+                                .insert(peer, (digest, ml_dsa_public_key.clone()));
+                            self.emit(P2PEvent::PeerIdentityVerified {
+                                peer_id: peer,
+                                ml_dsa_public_key,
+                            })?;
+                        }
+                        Err(e) => {
+                            warn!("Rejected identity certificate from {}: {}", peer, e);
+                            self.scoring.report(peer, PeerEvent::InvalidMessage);
+                        }
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure { peer, error, .. } => {
+                debug!(
+                    "Identity certificate request to {} failed: {:?}",
+                    peer, error
+                );
+                self.pending_identities.remove(&peer);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Add a relay descriptor gossiped by `source` to the directory
+    async fn ingest_descriptor(&mut self, source: LibP2PPeerId, data: &[u8]) {
+        let result = match RelayDescriptor::from_bytes(data) {
+            Ok(descriptor) => self.directory.insert_descriptor(&descriptor).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(peer_id) => debug!("Relay {} in directory", peer_id),
+            Err(e) => {
+                debug!("Rejected relay descriptor from {}: {}", source, e);
+                // Expired descriptors may still be in flight; forged or
+                // garbled ones are not
+                if matches!(
+                    e,
+                    OnionRelayError::Malformed(_) | OnionRelayError::Identity(_)
+                ) {
+                    self.scoring.report(source, PeerEvent::InvalidMessage);
+                }
+            }
+        }
+    }
+
+    /// Handle onion cells and their answers
+    async fn handle_onion_event(
+        &mut self,
+        event: request_response::Event<OnionCell, OnionReply>,
+    ) -> Result<(), Box<dyn Error>> {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    let size = bincode::serialized_size(&request).map_or(usize::MAX, |size| {
+                        usize::try_from(size).unwrap_or(usize::MAX)
+                    });
+                    if self
+                        .resources
+                        .admit(peer, ResourceProtocol::Onion, size)
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                    self.handle_onion_cell(peer, request, channel).await?;
+                }
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => {
+                    if let Some(exchange) = self.onion_requests.remove(&request_id) {
+                        self.handle_onion_reply(exchange, Ok(response)).await;
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                debug!("Onion cell to {} failed: {:?}", peer, error);
+                if matches!(error, request_response::OutboundFailure::Timeout) {
+                    self.scoring.report(peer, PeerEvent::Timeout);
+                }
+                if let Some(exchange) = self.onion_requests.remove(&request_id) {
+                    let reason = format!("Cell to {} failed: {:?}", peer, error);
+                    self.handle_onion_reply(exchange, Err(reason)).await;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Act on a cell received from a peer
+    async fn handle_onion_cell(
+        &mut self,
+        peer: LibP2PPeerId,
+        cell: OnionCell,
+        channel: request_response::ResponseChannel<OnionReply>,
+    ) -> Result<(), Box<dyn Error>> {
+        if let OnionCell::Backward {
+            circuit_id,
+            payload,
+        } = &cell
+        {
+            if let Some(circuit) = self
+                .client_circuits
+                .get(circuit_id)
+                .filter(|circuit| circuit.hops[0] == peer)
+            {
+                let reply = match circuit.open_reply(payload) {
+                    Ok((hop, response)) if hop + 1 == circuit.len() => {
+                        self.emit(P2PEvent::CircuitPush {
+                            circuit_id: *circuit_id,
+                            response,
+                        })?;
+                        OnionReply::Delivered
+                    }
+                    Ok(_) => OnionReply::Failed("Push from a middle hop".into()),
+                    Err(e) => OnionReply::Failed(e.to_string()),
+                };
+                self.send_onion_reply(channel, reply);
+                return Ok(());
+            }
+        }
+
+        match self.onion_relay.handle_cell(peer, cell) {
+            RelayAction::Reply(reply) => self.send_onion_reply(channel, reply),
+            RelayAction::Push {
+                reply,
+                peer_id,
+                cell,
+            } => {
+                self.send_onion_cell(peer_id, cell, OnionExchange::Notify);
+                self.send_onion_reply(channel, reply);
+            }
+            RelayAction::Forward {
+                peer_id,
+                addresses,
+                cell,
+                pending,
+            } => {
+                for address in addresses {
+                    self.swarm.add_peer_address(peer_id, address);
+                }
+                self.send_onion_cell(peer_id, cell, OnionExchange::Forward { channel, pending });
+            }
+            RelayAction::Teardown { next } => {
+                if let Some((peer_id, circuit_id)) = next {
+                    self.send_onion_cell(
+                        peer_id,
+                        OnionCell::Destroy { circuit_id },
+                        OnionExchange::Notify,
+                    );
+                }
+                self.send_onion_reply(channel, OnionReply::Destroyed);
+            }
+            RelayAction::Deliver { payload, reply } => {
+                self.emit(P2PEvent::OnionMessageReceived { payload })?;
+                self.send_onion_reply(channel, reply);
+            }
+            RelayAction::ServeDirectory => {
+                let mut descriptors = self.directory.descriptors(MAX_DIRECTORY_FETCH - 1).await;
+                descriptors.extend(
+                    self.local_descriptor
+                        .as_ref()
+                        .map(|(descriptor, _)| descriptor.clone()),
+                );
+                self.send_onion_reply(channel, OnionReply::Directory(descriptors));
+            }
+        }
+        Ok(())
+    }
+
+    /// Act on the answer to a cell this node sent
+    async fn handle_onion_reply(
+        &mut self,
+        exchange: OnionExchange,
+        reply: Result<OnionReply, String>,
+    ) {
+        match exchange {
+            OnionExchange::Build(circuit_id) => self.continue_circuit(circuit_id, reply).await,
+            OnionExchange::Send {
+                circuit_id,
+                bytes,
+                response,
+            } => {
+                let outcome = match self.client_circuits.get(&circuit_id) {
+                    None => Err("Circuit destroyed".to_string()),
+                    Some(circuit) => match open_circuit_reply(circuit, reply) {
+                        Ok((hop, RelayResponse::Failed(e))) => {
+                            Err(format!("Hop {} failed: {}", hop, e))
+                        }
+                        Ok((hop, response)) if hop + 1 == circuit.len() => Ok(response),
+                        Ok((hop, response)) => Err(format!(
+                            "Unexpected answer from hop {}: {:?}",
+                            hop, response
+                        )),
+                        Err(e) => Err(e),
+                    },
+                };
+                self.circuit_manager
+                    .update_circuit_metrics(circuit_id, bytes, outcome.is_ok());
+                let _ = response.send(outcome.map_err(Into::into));
+            }
+            OnionExchange::Forward { channel, pending } => {
+                let reply = self.onion_relay.complete(*pending, reply);
+                self.send_onion_reply(channel, reply);
+            }
+            OnionExchange::Fetch => {
+                if let Ok(OnionReply::Directory(descriptors)) = reply {
+                    for descriptor in descriptors.iter().take(MAX_DIRECTORY_FETCH) {
+                        if let Err(e) = self.directory.insert_descriptor(descriptor).await {
+                            debug!("Rejected fetched relay descriptor: {}", e);
+                        }
+                    }
+                }
+            }
+            OnionExchange::Notify => {}
+        }
+    }
+
+    /// Send an onion cell and remember what its answer is for
+    fn send_onion_cell(&mut self, peer_id: LibP2PPeerId, cell: OnionCell, exchange: OnionExchange) {
+        let bytes = match &cell {
+            OnionCell::Create { kem_ciphertext, .. } => kem_ciphertext.len(),
+            OnionCell::Relay { payload, .. }
+            | OnionCell::Backward { payload, .. }
+            | OnionCell::Deliver { payload }
+            | OnionCell::Padding(payload) => payload.len(),
+            OnionCell::Destroy { .. } | OnionCell::FetchDirectory => 0,
+        };
+        self.cover
+            .record_real(CoverLink::Connection(peer_id), bytes);
+        self.queue_onion_cell(peer_id, cell, exchange);
+    }
+
+    /// Send an onion cell without counting it as real traffic
+    fn queue_onion_cell(
+        &mut self,
+        peer_id: LibP2PPeerId,
+        cell: OnionCell,
+        exchange: OnionExchange,
+    ) {
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .onion
+            .send_request(&peer_id, cell);
+        self.onion_requests.insert(request_id, exchange);
+    }
+
+    /// Answer an onion cell
+    fn send_onion_reply(
+        &mut self,
+        channel: request_response::ResponseChannel<OnionReply>,
+        reply: OnionReply,
+    ) {
+        if self
+            .swarm
+            .behaviour_mut()
+            .onion
+            .send_response(channel, reply)
+            .is_err()
+        {
+            debug!("Previous hop went away before the onion reply was sent");
+        }
+    }
+
+    /// Pick relays for a new circuit and create its first hop
+    async fn build_circuit_internal(
+        &mut self,
+        hops: usize,
+        last_hop: Option<LibP2PPeerId>,
+        response: BuildCircuitResponse,
+    ) {
+        if hops == 0 {
+            let _ = response.send(Err("A circuit needs at least one hop".into()));
+            return;
+        }
+        let built = match last_hop {
+            Some(last_hop) => {
+                self.circuit_manager
+                    .build_circuit_to(hops, &last_hop.to_bytes(), &self.directory)
+                    .await
+            }
+            None => {
+                self.circuit_manager
+                    .build_circuit(hops, &self.directory)
+                    .await
+            }
+        };
+        let circuit_id = match built {
+            Ok(circuit_id) => circuit_id,
+            Err(e) => {
+                let _ = response.send(Err(e.into()));
+                return;
+            }
+        };
+        let node_ids = self
+            .circuit_manager
+            .get_circuit(circuit_id)
+            .map(|circuit| circuit.hops.clone())
+            .unwrap_or_default();
+        let mut relays = Vec::with_capacity(node_ids.len());
+        for node_id in &node_ids {
+            relays.extend(self.directory.node(node_id).await);
+        }
+        self.circuit_builds.insert(
+            circuit_id,
+            CircuitBuild {
+                relays,
+                circuit: None,
+                handshake: None,
+                response,
+            },
+        );
+        if node_ids.len() != hops {
+            self.fail_circuit(circuit_id, "Relay left the directory".into())
+                .await;
+            return;
+        }
+        self.extend_circuit(circuit_id).await;
+    }
+
+    /// Send the cell adding the next hop to a circuit being built
+    async fn extend_circuit(&mut self, circuit_id: u64) {
+        let Some(build) = self.circuit_builds.get_mut(&circuit_id) else {
+            return;
+        };
+        match build.next_cell(circuit_id) {
+            Ok((peer_id, addresses, cell)) => {
+                for address in addresses {
+                    self.swarm.add_peer_address(peer_id, address);
+                }
+                self.send_onion_cell(peer_id, cell, OnionExchange::Build(circuit_id));
+            }
+            Err(e) => self.fail_circuit(circuit_id, e).await,
+        }
+    }
+
+    /// Handle the answer to a circuit building step
+    async fn continue_circuit(&mut self, circuit_id: u64, reply: Result<OnionReply, String>) {
+        let Some(build) = self.circuit_builds.get_mut(&circuit_id) else {
+            return;
+        };
+        if let Err(e) = build.add_hop(reply) {
+            self.fail_circuit(circuit_id, e).await;
+            return;
+        }
+        if !build.is_complete() {
+            self.extend_circuit(circuit_id).await;
+            return;
+        }
+
+        let build = self
+            .circuit_builds
+            .remove(&circuit_id)
+            .expect("build checked above");
+        let circuit = build.circuit.expect("complete build has hops");
+        let result = self
+            .circuit_manager
+            .activate_circuit(circuit_id)
+            .map(|()| circuit_id);
+        match &result {
+            Ok(_) => {
+                info!("Built {}-hop onion circuit {}", circuit.len(), circuit_id);
+                self.client_circuits.insert(circuit_id, circuit);
+                self.cover.add_link(CoverLink::Circuit(circuit_id));
+            }
+            Err(_) => self.send_onion_cell(
+                circuit.hops[0],
+                OnionCell::Destroy { circuit_id },
+                OnionExchange::Notify,
+            ),
+        }
+        let _ = build.response.send(result.map_err(Into::into));
     }
 
-    /// Handle request-response events
-    async fn handle_request_response_event(
+    /// Abandon a circuit being built, tearing down the hops built so far
+    async fn fail_circuit(&mut self, circuit_id: u64, reason: String) {
+        let Some(build) = self.circuit_builds.remove(&circuit_id) else {
+            return;
+        };
+        warn!("Building onion circuit {} failed: {}", circuit_id, reason);
+        if let Some(circuit) = &build.circuit {
+            self.send_onion_cell(
+                circuit.hops[0],
+                OnionCell::Destroy { circuit_id },
+                OnionExchange::Notify,
+            );
+        }
+        let _ = self.circuit_manager.teardown_circuit(circuit_id).await;
+        let _ = build
+            .response
+            .send(Err(format!("Circuit build failed: {}", reason).into()));
+    }
+
+    /// Send a payload to the last hop of a circuit
+    async fn send_onion_message_internal(
         &mut self,
-        event: request_response::Event<QuDagRequest, QuDagResponse>,
-    ) -> Result<(), Box<dyn Error>> {
-        match event {
-            request_response::Event::Message { peer, message } => match message {
-                request_response::Message::Request {
-                    request, channel, ..
-                } => {
-                    // Handle the request and prepare response
-                    let response = QuDagResponse {
-                        request_id: request.request_id.clone(),
-                        payload: vec![], // TODO: Process request and generate actual response
-                    };
+        circuit_id: u64,
+        destination: Option<LibP2PPeerId>,
+        payload: Vec<u8>,
+        response: CircuitCommandResponse,
+    ) {
+        if payload.len() > MAX_ONION_PAYLOAD {
+            let _ = response.send(Err("Onion payload too large".into()));
+            return;
+        }
+        // Tell the exit where the destination listens, if it is a known relay
+        let addresses = match destination {
+            Some(peer_id) => self
+                .directory
+                .node(&peer_id.to_bytes())
+                .await
+                .map(|node| node.addresses.iter().map(|addr| addr.to_vec()).collect())
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let command = RelayCommand::Data {
+            destination: destination.map(|peer_id| peer_id.to_bytes()),
+            addresses,
+            payload,
+        };
+        self.send_circuit_command(circuit_id, command, response);
+    }
 
-                    // Send the response back directly
-                    self.swarm
-                        .behaviour_mut()
-                        .request_response
-                        .send_response(channel, response)
-                        .map_err(|_| "Failed to send response")?;
+    /// Seal a command for the last hop of a circuit and send it
+    fn send_circuit_command(
+        &mut self,
+        circuit_id: u64,
+        command: RelayCommand,
+        response: CircuitCommandResponse,
+    ) {
+        let Some(circuit) = self.client_circuits.get(&circuit_id) else {
+            let _ = response.send(Err(format!("Unknown circuit {}", circuit_id).into()));
+            return;
+        };
+        let bytes = match &command {
+            RelayCommand::Data { payload, .. }
+            | RelayCommand::Introduce { payload, .. }
+            | RelayCommand::Rendezvous { payload, .. }
+            | RelayCommand::Stream(payload) => payload.len() as u64,
+            _ => 0,
+        };
+        match circuit.seal_for(circuit.len() - 1, &command) {
+            Ok(sealed) => {
+                let first_hop = circuit.hops[0];
+                self.cover
+                    .record_real(CoverLink::Circuit(circuit_id), sealed.len());
+                self.send_onion_cell(
+                    first_hop,
+                    OnionCell::Relay {
+                        circuit_id,
+                        payload: sealed,
+                    },
+                    OnionExchange::Send {
+                        circuit_id,
+                        bytes,
+                        response,
+                    },
+                );
+            }
+            Err(e) => {
+                let _ = response.send(Err(e.into()));
+            }
+        }
+    }
 
-                    // Also emit event for the application layer
-                    let (tx, _rx) = oneshot::channel();
-                    self.event_tx.send(P2PEvent::RequestReceived {
-                        peer_id: peer,
-                        request,
-                        channel: tx,
-                    })?;
+    /// Send the dummy cells that are due
+    fn send_due_cover(&mut self) {
+        for link in self.cover.poll_due(Instant::now()) {
+            let filler = self.cover.filler();
+            match link {
+                CoverLink::Connection(peer_id) => {
+                    self.queue_onion_cell(
+                        peer_id,
+                        OnionCell::Padding(filler),
+                        OnionExchange::Notify,
+                    );
                 }
-                request_response::Message::Response {
-                    request_id,
-                    response,
-                } => {
-                    if let Some(tx) = self.pending_requests.remove(&request_id.to_string()) {
-                        let _ = tx.send(response);
+                CoverLink::Circuit(circuit_id) => {
+                    let Some(circuit) = self.client_circuits.get(&circuit_id) else {
+                        self.cover.remove_link(&link);
+                        continue;
+                    };
+                    let first_hop = circuit.hops[0];
+                    match circuit.seal_for(circuit.len() - 1, &RelayCommand::Padding(filler)) {
+                        Ok(payload) => {
+                            self.cover.fill_slot(&CoverLink::Connection(first_hop));
+                            self.queue_onion_cell(
+                                first_hop,
+                                OnionCell::Relay {
+                                    circuit_id,
+                                    payload,
+                                },
+                                OnionExchange::Notify,
+                            );
+                        }
+                        Err(e) => debug!("Failed to seal cover for circuit {}: {}", circuit_id, e),
                     }
                 }
-            },
-            request_response::Event::OutboundFailure {
-                peer,
-                request_id,
-                error,
-            } => {
-                warn!(
-                    "Request to {} failed (id: {}): {:?}",
-                    peer, request_id, error
-                );
-                self.pending_requests.remove(&request_id.to_string());
-            }
-            request_response::Event::InboundFailure {
-                peer,
-                request_id,
-                error,
-            } => {
-                warn!(
-                    "Inbound request from {} failed (id: {}): {:?}",
-                    peer, request_id, error
-                );
             }
-            _ => {}
         }
+    }
+
+    /// Tear down one of this node's circuits
+    async fn destroy_circuit_internal(
+        &mut self,
+        circuit_id: u64,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let circuit = self
+            .client_circuits
+            .remove(&circuit_id)
+            .ok_or_else(|| format!("Unknown circuit {}", circuit_id))?;
+        self.cover.remove_link(&CoverLink::Circuit(circuit_id));
+        self.send_onion_cell(
+            circuit.hops[0],
+            OnionCell::Destroy { circuit_id },
+            OnionExchange::Notify,
+        );
+        self.circuit_manager.teardown_circuit(circuit_id).await?;
         Ok(())
     }
 
@@ -1023,13 +3628,37 @@ impl P2PNode {
                 request,
                 response,
             } => {
-                let result = self.send_request_internal(peer_id, request).await;
-                let _ = response.send(result);
+                self.send_request_internal(peer_id, request, response).await;
             }
             P2PCommand::Dial { addr, response } => {
                 let result = self.dial_internal(addr).await;
                 let _ = response.send(result);
             }
+            P2PCommand::DialPeer { peer_id, response } => {
+                self.start_dial_race(peer_id, response);
+            }
+            P2PCommand::Connect { addr, response } => {
+                if let Some(peer_id) = extract_peer_id(&addr) {
+                    self.address_book.add(peer_id, addr.clone());
+                }
+                let opts = DialOpts::from(addr);
+                let connection_id = opts.connection_id();
+                match self.swarm.dial(opts) {
+                    Ok(()) => {
+                        self.address_dials.insert(connection_id, response);
+                    }
+                    Err(e) => {
+                        let _ = response.send(Err(format!("Dial error: {}", e).into()));
+                    }
+                }
+            }
+            P2PCommand::Disconnect { peer_id, response } => {
+                let result = self
+                    .swarm
+                    .disconnect_peer_id(peer_id)
+                    .map_err(|()| format!("Not connected to {}", peer_id).into());
+                let _ = response.send(result);
+            }
             P2PCommand::GetConnectedPeers { response } => {
                 let peers = self.connected_peers.iter().copied().collect();
                 let _ = response.send(peers);
@@ -1041,6 +3670,109 @@ impl P2PNode {
                 let listeners = self.swarm.listeners().cloned().collect();
                 let _ = response.send(listeners);
             }
+            P2PCommand::GetExternalAddresses { response } => {
+                let addresses = self.swarm.external_addresses().cloned().collect();
+                let _ = response.send(addresses);
+            }
+            P2PCommand::GetRelayReservations { response } => {
+                let _ = response.send(self.reservations.active());
+            }
+            P2PCommand::GetReachability { response } => {
+                let _ = response.send(self.reachability.reachability());
+            }
+            P2PCommand::ReportNatInfo { info } => {
+                debug!("STUN detected {:?} NAT", info.nat_type);
+                if let Some(reachability) = self.reachability.on_stun(&info) {
+                    if let Err(e) = self.apply_reachability(reachability) {
+                        warn!("Failed to apply reachability: {}", e);
+                    }
+                }
+            }
+            P2PCommand::GetPeerIdentity { peer_id, response } => {
+                let key = self
+                    .verified_identities
+                    .get(&peer_id)
+                    .map(|(_, key)| key.clone());
+                let _ = response.send(key);
+            }
+            P2PCommand::PutRecord {
+                key,
+                value,
+                ttl,
+                response,
+            } => {
+                let mut record = Record::new(RecordKey::new(&key), value);
+                record.publisher = Some(self.local_peer_id);
+                record.expires = Some(Instant::now() + ttl);
+                match self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .put_record(record, Quorum::One)
+                {
+                    Ok(query_id) => {
+                        self.pending_puts.insert(query_id, response);
+                    }
+                    Err(e) => {
+                        let _ = response.send(Err(format!("Put record error: {:?}", e).into()));
+                    }
+                }
+            }
+            P2PCommand::GetRecord { key, response } => {
+                let query_id = self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .get_record(RecordKey::new(&key));
+                self.pending_gets.insert(
+                    query_id,
+                    PendingGet {
+                        values: Vec::new(),
+                        response,
+                    },
+                );
+            }
+            P2PCommand::BuildCircuit {
+                hops,
+                last_hop,
+                response,
+            } => {
+                self.build_circuit_internal(hops, last_hop, response).await;
+            }
+            P2PCommand::SendOnionMessage {
+                circuit_id,
+                destination,
+                payload,
+                response,
+            } => {
+                self.send_onion_message_internal(circuit_id, destination, payload, response)
+                    .await;
+            }
+            P2PCommand::SendCircuitCommand {
+                circuit_id,
+                command,
+                response,
+            } => self.send_circuit_command(circuit_id, command, response),
+            P2PCommand::DestroyCircuit {
+                circuit_id,
+                response,
+            } => {
+                let result = self.destroy_circuit_internal(circuit_id).await;
+                let _ = response.send(result);
+            }
+            P2PCommand::GetKnownRelays { response } => {
+                let relays = self
+                    .directory
+                    .node_ids()
+                    .await
+                    .iter()
+                    .filter_map(|id| LibP2PPeerId::from_bytes(id).ok())
+                    .collect();
+                let _ = response.send(relays);
+            }
+            P2PCommand::GetCoverStats { response } => {
+                let _ = response.send(self.cover.stats());
+            }
         }
     }
 
@@ -1075,16 +3807,61 @@ impl P2PNode {
     }
 
     /// Internal publish method
+    ///
+    /// Messages are queued per topic and published in adaptively sized
+    /// batches.
     async fn publish_internal(
         &mut self,
         topic: &str,
         data: Vec<u8>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        #[cfg(feature = "adaptive-batching")]
+        {
+            let config = self.config.gossip_batching.clone();
+            let batch = self
+                .gossip_batches
+                .entry(topic.to_string())
+                .or_insert_with(|| GossipBatch::new(config));
+
+            let mut ready = Vec::new();
+            if batch.bytes > 0 && batch.bytes + data.len() > MAX_GOSSIP_BATCH_BYTES {
+                ready.push(batch.flush());
+            }
+            ready.extend(batch.add(data));
+            for messages in ready {
+                self.publish_batch(topic, &messages)?;
+            }
+            Ok(())
+        }
+        #[cfg(not(feature = "adaptive-batching"))]
+        self.publish_frame(topic, &encode_gossip_single(&data))
+    }
+
+    /// Publish queued messages as one gossip message
+    #[cfg(feature = "adaptive-batching")]
+    fn publish_batch(
+        &mut self,
+        topic: &str,
+        messages: &[Vec<u8>],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let frame =
+            encode_gossip_batch(messages).map_err(|e| format!("Serialization error: {}", e))?;
+        self.publish_frame(topic, &frame)?;
+        debug!("Published {} messages to topic: {}", messages.len(), topic);
+        Ok(())
+    }
+
+    /// Obfuscate and publish a framed gossip message
+    fn publish_frame(
+        &mut self,
+        topic: &str,
+        data: &[u8],
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let topic = IdentTopic::new(topic);
 
         // Obfuscate traffic if configured
         let message_data = self
-            .obfuscate_traffic(&data)
+            .obfuscate_traffic(data)
             .map_err(|e| format!("Obfuscation error: {}", e))?;
 
         self.swarm
@@ -1098,51 +3875,176 @@ impl P2PNode {
     }
 
     /// Internal send request method with chunking support
+    ///
+    /// Large payloads are sent as a window of chunks, the next one going
+    /// out as each is acknowledged. The reply arrives on `response`.
     async fn send_request_internal(
         &mut self,
         peer_id: LibP2PPeerId,
         request: QuDagRequest,
-    ) -> Result<QuDagResponse, Box<dyn Error + Send + Sync>> {
+        response: SendRequestResponse,
+    ) {
         let request_id = request.request_id.clone();
+        if self.pending_requests.contains_key(&request_id) {
+            let _ = response.send(Err(
+                format!("Request {} already in flight", request_id).into()
+            ));
+            return;
+        }
 
-        // Check if message needs chunking
-        let network_message = NetworkMessage {
-            id: request.request_id.clone(),
-            source: vec![0],      // Placeholder source
-            destination: vec![0], // Placeholder destination
-            payload: request.payload.clone(),
-            priority: MessagePriority::Normal,
-            ttl: Duration::from_secs(60),
+        #[cfg(feature = "message-chunking")]
+        let frames = chunk_request(
+            &self.message_chunker,
+            self.local_peer_id,
+            self.config.timeout,
+            request,
+        )
+        .await;
+        #[cfg(not(feature = "message-chunking"))]
+        let frames = Ok(vec![RequestFrame::Whole(request.payload)]);
+        let queued = match frames {
+            Ok(frames) => VecDeque::from(frames),
+            Err(e) => {
+                let _ = response.send(Err(e));
+                return;
+            }
         };
+        self.pending_requests.insert(
+            request_id.clone(),
+            PendingRequest {
+                peer_id,
+                queued,
+                in_flight: 0,
+                response,
+            },
+        );
+
+        #[cfg(feature = "message-chunking")]
+        let window = CHUNK_SEND_WINDOW;
+        #[cfg(not(feature = "message-chunking"))]
+        let window = 1;
+        for _ in 0..window {
+            self.send_next_frame(&request_id);
+        }
+    }
 
-        // Chunking disabled for initial release - send message directly
-        // let chunks = self.message_chunker.chunk_message(&network_message).await
-        //     .map_err(|e| format!("Chunking error: {:?}", e))?;
+    /// Send the next queued frame of a request, if any
+    fn send_next_frame(&mut self, request_id: &str) {
+        let Some(pending) = self.pending_requests.get_mut(request_id) else {
+            return;
+        };
+        let Some(frame) = pending.queued.pop_front() else {
+            return;
+        };
+        let payload = match bincode::serialize(&frame) {
+            Ok(payload) => payload,
+            Err(e) => {
+                self.complete_request(
+                    request_id,
+                    Err(format!("Serialization error: {}", e).into()),
+                );
+                return;
+            }
+        };
+        pending.in_flight += 1;
+        let peer_id = pending.peer_id;
+        let outbound_id = self.swarm.behaviour_mut().request_response.send_request(
+            &peer_id,
+            QuDagRequest {
+                request_id: request_id.to_string(),
+                payload,
+            },
+        );
+        self.outbound_requests
+            .insert(outbound_id, request_id.to_string());
+    }
 
-        // Send message directly without chunking
-        let request = QuDagRequest {
-            request_id: request_id.clone(),
-            payload: bincode::serialize(&network_message)
-                .map_err(|e| format!("Serialization error: {}", e))?,
+    /// Handle a chunk acknowledgement by sending the next one
+    fn continue_request(&mut self, request_id: &str) {
+        let Some(pending) = self.pending_requests.get_mut(request_id) else {
+            return;
         };
+        pending.in_flight -= 1;
+        if pending.queued.is_empty() && pending.in_flight == 0 {
+            self.complete_request(
+                request_id,
+                Err("Peer acknowledged every chunk without replying".into()),
+            );
+        } else {
+            self.send_next_frame(request_id);
+        }
+    }
 
-        // Setup response handling
-        let (tx, rx) = oneshot::channel();
-        self.pending_requests.insert(request_id.clone(), tx);
+    /// Deliver the outcome of a request and forget its remaining exchanges
+    fn complete_request(
+        &mut self,
+        request_id: &str,
+        result: Result<QuDagResponse, Box<dyn Error + Send + Sync>>,
+    ) {
+        if let Some(pending) = self.pending_requests.remove(request_id) {
+            let _ = pending.response.send(result);
+            self.outbound_requests.retain(|_, id| id != request_id);
+        }
+    }
 
-        self.swarm
-            .behaviour_mut()
-            .request_response
-            .send_request(&peer_id, request);
+    /// Answer an inbound request with the application's reply
+    ///
+    /// An empty reply is sent if the application dropped the request.
+    fn send_reply(
+        &mut self,
+        channel: request_response::ResponseChannel<QuDagResponse>,
+        request_id: String,
+        reply: Option<QuDagResponse>,
+    ) {
+        let payload = reply.map(|reply| reply.payload).unwrap_or_default();
+        self.send_response_frame(channel, request_id, ResponseFrame::Reply(payload));
+    }
+
+    /// Hand an event to the application, returning whether it was delivered
+    ///
+    /// The event channel is bounded. Rather than stalling the swarm while the
+    /// application falls behind, events that don't fit are dropped and
+    /// counted. Fails once the application dropped the event stream.
+    fn emit(&self, event: P2PEvent) -> Result<bool, Box<dyn Error>> {
+        match self.event_tx.try_send(event) {
+            Ok(()) => Ok(true),
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                self.dropped_events.fetch_add(1, Ordering::Relaxed);
+                debug!("Event channel full, dropped {:?}", event);
+                Ok(false)
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => Err("Event stream closed".into()),
+        }
+    }
 
-        // Wait for response with timeout
-        match tokio::time::timeout(self.config.timeout, rx).await {
-            Ok(Ok(response)) => Ok(response),
-            Ok(Err(_)) => Err("Response channel closed".into()),
-            Err(_) => {
-                self.pending_requests.remove(&request_id);
-                Err("Request timeout".into())
+    /// Send a framed response on a request-response channel
+    fn send_response_frame(
+        &mut self,
+        channel: request_response::ResponseChannel<QuDagResponse>,
+        request_id: String,
+        frame: ResponseFrame,
+    ) {
+        let payload = match bincode::serialize(&frame) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to encode response to {}: {}", request_id, e);
+                return;
             }
+        };
+        if self
+            .swarm
+            .behaviour_mut()
+            .request_response
+            .send_response(
+                channel,
+                QuDagResponse {
+                    request_id,
+                    payload,
+                },
+            )
+            .is_err()
+        {
+            debug!("Requester went away before the response was sent");
         }
     }
 
@@ -1151,12 +4053,130 @@ impl P2PNode {
         &mut self,
         peer_addr: Multiaddr,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(peer_id) = extract_peer_id(&peer_addr) {
+            self.address_book.add(peer_id, peer_addr.clone());
+        }
         self.swarm
             .dial(peer_addr)
             .map_err(|e| format!("Dial error: {}", e))?;
         Ok(())
     }
 
+    /// Start dialing the known addresses of a peer, best first
+    fn start_dial_race(
+        &mut self,
+        peer_id: LibP2PPeerId,
+        response: oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
+    ) {
+        if self.connected_peers.contains(&peer_id) {
+            let _ = response.send(Ok(()));
+            return;
+        }
+        if let Some(race) = self.dial_races.get_mut(&peer_id) {
+            race.waiters.push(response);
+            return;
+        }
+        let remaining: VecDeque<_> = self
+            .address_book
+            .addresses(&peer_id)
+            .into_iter()
+            .map(|info| info.address)
+            .filter(|addr| self.config.supports(addr))
+            .collect();
+        if remaining.is_empty() {
+            let _ = response.send(Err(format!(
+                "No address of {} on an enabled transport",
+                peer_id
+            )
+            .into()));
+            return;
+        }
+        self.dial_races.insert(
+            peer_id,
+            DialRace {
+                remaining,
+                attempts: HashMap::new(),
+                next_attempt: Instant::now(),
+                waiters: vec![response],
+            },
+        );
+        self.dial_next_address(peer_id);
+    }
+
+    /// Dial the next address of a race, skipping those that fail at once
+    fn dial_next_address(&mut self, peer_id: LibP2PPeerId) {
+        let Some(race) = self.dial_races.get_mut(&peer_id) else {
+            return;
+        };
+        while let Some(addr) = race.remaining.pop_front() {
+            let opts = DialOpts::peer_id(peer_id)
+                .addresses(vec![addr.clone()])
+                .condition(PeerCondition::Always)
+                .build();
+            let connection_id = opts.connection_id();
+            match self.swarm.dial(opts) {
+                Ok(()) => {
+                    debug!("Dialing {} at {}", peer_id, addr);
+                    race.attempts.insert(connection_id, addr);
+                    race.next_attempt = Instant::now() + HAPPY_EYEBALLS_DELAY;
+                    return;
+                }
+                Err(e) => {
+                    debug!("Dial of {} at {} failed: {}", peer_id, addr, e);
+                    self.address_book.record_failure(peer_id, &addr);
+                }
+            }
+        }
+        if race.attempts.is_empty() {
+            self.finish_dial_race(peer_id, Err(format!("Every address of {} failed", peer_id)));
+        }
+    }
+
+    /// Fall back to the next address after a failed attempt
+    fn dial_attempt_failed(&mut self, peer_id: LibP2PPeerId, connection_id: ConnectionId) {
+        let Some(race) = self.dial_races.get_mut(&peer_id) else {
+            return;
+        };
+        if let Some(addr) = race.attempts.remove(&connection_id) {
+            self.address_book.record_failure(peer_id, &addr);
+            self.dial_next_address(peer_id);
+        }
+    }
+
+    /// Decide a race and stop dialing its remaining addresses
+    fn finish_dial_race(&mut self, peer_id: LibP2PPeerId, result: Result<(), String>) {
+        let Some(race) = self.dial_races.remove(&peer_id) else {
+            return;
+        };
+        self.stale_dials.extend(race.attempts.into_keys());
+        for waiter in race.waiters {
+            let _ = waiter.send(result.clone().map_err(Into::into));
+        }
+    }
+
+    /// When the next address of a race is due
+    fn next_dial_attempt(&self) -> Option<Instant> {
+        self.dial_races
+            .values()
+            .filter(|race| !race.remaining.is_empty())
+            .map(|race| race.next_attempt)
+            .min()
+    }
+
+    /// Dial the next address of every race that has waited long enough
+    fn advance_dial_races(&mut self) {
+        let now = Instant::now();
+        let due: Vec<_> = self
+            .dial_races
+            .iter()
+            .filter(|(_, race)| !race.remaining.is_empty() && race.next_attempt <= now)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        for peer_id in due {
+            self.dial_next_address(peer_id);
+        }
+    }
+
     /// Obfuscates traffic using ChaCha20-Poly1305
     fn obfuscate_traffic(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
         let mut nonce = [0u8; 12];
@@ -1190,47 +4210,207 @@ impl P2PNode {
 }
 
 /// Build the transport layer with multiple protocol support
+///
+/// Circuits through relays are secured like any other connection. Every
+/// connection passes through the connection pool layer on top, which
+/// registers it with `connection_manager`.
 fn build_transport(
     local_key: &Keypair,
+    ml_dsa_key: Arc<MlDsaKeyPair>,
     config: &NetworkConfig,
+    relay_transport: Option<relay::client::Transport>,
+    connection_manager: Arc<ConnectionManager>,
 ) -> Result<Boxed<(LibP2PPeerId, StreamMuxerBox)>, Box<dyn Error>> {
-    let noise = noise::Config::new(local_key)?;
-
-    let yamux_config = yamux::Config::default();
-
-    // Build base TCP transport
-    let tcp = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true));
+    // Build base TCP transport; with relaying, dials leave from the listen
+    // port so that peers observe an address hole punching can reach
+    let tcp = if config.enable_tcp {
+        OptionalTransport::some(PortReuseTcp(tcp::tokio::Transport::new(
+            tcp::Config::default()
+                .nodelay(true)
+                .port_reuse(config.enable_relay),
+        )))
+    } else {
+        OptionalTransport::none()
+    };
 
     // Memory transport for testing
     let memory = MemoryTransport::default();
 
     // Combine transports
     let base_transport = tcp.or_transport(memory);
+    let relay = match relay_transport {
+        Some(transport) => OptionalTransport::some(transport),
+        None => OptionalTransport::none(),
+    };
+
+    // Disguise them if a pluggable transport is configured; relayed
+    // circuits already travel inside a connection to the relay
+    let secured = match &config.pluggable_transport {
+        Some(pluggable) => with_websocket(
+            relay.or_transport(ObfuscatedTransport::new(base_transport, pluggable.clone())),
+            local_key,
+            ml_dsa_key,
+            config,
+        )?,
+        None => with_websocket(
+            relay.or_transport(base_transport),
+            local_key,
+            ml_dsa_key,
+            config,
+        )?,
+    };
+
+    // QUIC brings its own TLS 1.3 security and stream multiplexing
+    if !config.enable_quic {
+        return Ok(with_connection_pool(secured, connection_manager));
+    }
+    let quic = quic::tokio::Transport::new(quic::Config::new(local_key));
+    let combined = secured.or_transport(quic).map(|output, _| match output {
+        future::Either::Left((peer_id, muxer)) => (peer_id, muxer),
+        future::Either::Right((peer_id, connection)) => (peer_id, StreamMuxerBox::new(connection)),
+    });
+    Ok(with_connection_pool(combined, connection_manager))
+}
 
-    // Add WebSocket support if enabled
-    let transport: Boxed<(LibP2PPeerId, StreamMuxerBox)> = if config.enable_websocket {
+/// Add WebSocket support if enabled, then secure the transport
+fn with_websocket<T>(
+    base: T,
+    local_key: &Keypair,
+    ml_dsa_key: Arc<MlDsaKeyPair>,
+    config: &NetworkConfig,
+) -> Result<Boxed<(LibP2PPeerId, StreamMuxerBox)>, Box<dyn Error>>
+where
+    T: LibP2PTransport + Send + Unpin + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Error: Send + Sync + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    if config.enable_websocket {
         let ws = websocket::WsConfig::new(tcp::tokio::Transport::new(
             tcp::Config::default().nodelay(true),
         ));
-        base_transport
-            .or_transport(ws)
+        secure_transport(base.or_transport(ws), local_key, ml_dsa_key, config)
+    } else {
+        secure_transport(base, local_key, ml_dsa_key, config)
+    }
+}
+
+/// Apply the configured security upgrade and multiplexer to a base transport
+fn secure_transport<T>(
+    base: T,
+    local_key: &Keypair,
+    ml_dsa_key: Arc<MlDsaKeyPair>,
+    config: &NetworkConfig,
+) -> Result<Boxed<(LibP2PPeerId, StreamMuxerBox)>, Box<dyn Error>>
+where
+    T: LibP2PTransport + Send + Unpin + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Error: Send + Sync + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    let yamux_config = yamux::Config::default();
+    let pq_noise = PqNoiseConfig::new(local_key, ml_dsa_key);
+
+    let transport = match config.security {
+        SecurityProtocol::Noise => base
             .upgrade(upgrade::Version::V1)
-            .authenticate(noise)
+            .authenticate(noise::Config::new(local_key)?)
             .multiplex(yamux_config)
             .timeout(Duration::from_secs(20))
-            .boxed()
-    } else {
-        base_transport
+            .boxed(),
+        SecurityProtocol::PqNoise => base
             .upgrade(upgrade::Version::V1)
-            .authenticate(noise)
+            .authenticate(pq_noise)
             .multiplex(yamux_config)
             .timeout(Duration::from_secs(20))
-            .boxed()
+            .boxed(),
+        SecurityProtocol::PqNoiseWithFallback => base
+            .upgrade(upgrade::Version::V1)
+            .authenticate(SelectSecurity(pq_noise, noise::Config::new(local_key)?))
+            .multiplex(yamux_config)
+            .timeout(Duration::from_secs(20))
+            .boxed(),
     };
 
     Ok(transport)
 }
 
+/// TCP transport falling back to a fresh port when a dial from the listen
+/// port fails
+///
+/// A peer that connected from its listen port to ours cannot be dialed
+/// back from ours, as that connection holds the same pair of ports. AutoNAT
+/// dial-backs are such dials.
+struct PortReuseTcp(tcp::tokio::Transport);
+
+impl LibP2PTransport for PortReuseTcp {
+    type Output = tcp::tokio::TcpStream;
+    type Error = std::io::Error;
+    type ListenerUpgrade = <tcp::tokio::Transport as LibP2PTransport>::ListenerUpgrade;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(
+        &mut self,
+        id: ListenerId,
+        addr: Multiaddr,
+    ) -> Result<(), TransportError<Self::Error>> {
+        self.0.listen_on(id, addr)
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.0.remove_listener(id)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let dial = self.0.dial(addr.clone())?;
+        Ok(dial_from_fresh_port_on_conflict(dial, addr))
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let dial = self.0.dial_as_listener(addr.clone())?;
+        Ok(dial_from_fresh_port_on_conflict(dial, addr))
+    }
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        std::pin::Pin::new(&mut self.0).poll(cx)
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.0.address_translation(listen, observed)
+    }
+}
+
+/// Retry a TCP dial from an ephemeral port if its port pair is taken
+fn dial_from_fresh_port_on_conflict(
+    dial: <tcp::tokio::Transport as LibP2PTransport>::Dial,
+    addr: Multiaddr,
+) -> BoxFuture<'static, std::io::Result<tcp::tokio::TcpStream>> {
+    async move {
+        match dial.await {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => {
+                let (Some(ip), Some(Protocol::Tcp(port))) = (ip_of(&addr), addr.iter().nth(1))
+                else {
+                    return Err(e);
+                };
+                debug!("Dialing {} from a fresh port", addr);
+                let stream = tokio::net::TcpStream::connect((ip, port)).await?;
+                stream.set_nodelay(true)?;
+                Ok(tcp::tokio::TcpStream(stream))
+            }
+            result => result,
+        }
+    }
+    .boxed()
+}
+
 /// Extract peer ID from multiaddr if present
 fn extract_peer_id(addr: &Multiaddr) -> Option<LibP2PPeerId> {
     addr.iter().find_map(|p| match p {
@@ -0,0 +1,696 @@
+//! Peer scoring and banning shared by every subsystem.
+//!
+//! [`PeerScoring`] is the one place a node keeps its opinion of other peers.
+//! It is a cheap handle to shared state, so the DAG, consensus, gossip and
+//! connection code all report to the same book:
+//!
+//! * Subsystems report [`PeerEvent`]s such as invalid vertices, equivocation,
+//!   invalid gossip messages and timeouts. Each event moves the peer's local
+//!   score by a configured amount, and scores decay towards zero over time.
+//! * The gossipsub router scores peers on its own. [`crate::p2p::P2PNode`]
+//!   feeds the local score into gossipsub as the application-specific score
+//!   and copies the rest of the gossipsub score back, so both views agree.
+//! * A peer whose combined score falls below the ban threshold is banned for
+//!   a limited time, doubling with every repeated ban. Equivocation bans at
+//!   once. Bans can also be set and lifted by hand.
+//! * Allowlisted peers are never banned automatically. In allowlist-only mode
+//!   no other peer may connect at all.
+//!
+//! [`PeerGate`] enforces bans and the allowlist on every dial and every
+//! accepted connection and closes connections to peers as they are banned.
+//! Bans and the allowlist are persisted to a file when the scoring is opened
+//! with a path, so they survive restarts. The file is written on a background
+//! thread, so reporting from the swarm loop never waits on the disk.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libp2p::core::Endpoint;
+use libp2p::gossipsub::{PeerScoreParams, PeerScoreThresholds};
+use libp2p::swarm::{
+    dummy, CloseConnection, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::disk_writer::DiskWriter;
+
+/// File below the node data directory holding bans and the allowlist
+pub const PEER_SCORES_FILE: &str = "peer_scores.json";
+
+/// Lowest and highest local score
+const SCORE_BOUND: f64 = 100.0;
+
+/// Errors from peer scoring
+#[derive(Debug, Error)]
+pub enum PeerScoringError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] serde_json::Error),
+    #[error("Invalid peer ID: {0}")]
+    InvalidPeerId(String),
+    #[error("Peer {0} is banned")]
+    Banned(PeerId),
+    #[error("Peer {0} is not in the allowlist")]
+    NotAllowed(PeerId),
+}
+
+/// Something a peer did that affects its score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerEvent {
+    /// Sent a DAG vertex that failed validation
+    InvalidVertex,
+    /// Signed conflicting vertices or votes
+    Equivocation,
+    /// Sent a gossip message or certificate that failed validation
+    InvalidMessage,
+    /// Let a request or ping time out
+    Timeout,
+    /// Broke a rate, stream or memory limit
+    LimitExceeded,
+    /// Delivered something valid
+    Useful,
+}
+
+/// Penalties, thresholds and lists for [`PeerScoring`]
+///
+/// Not to be confused with [`crate::kademlia::PeerScoringConfig`], which
+/// only ranks peers for DHT queries.
+#[derive(Debug, Clone)]
+pub struct PeerScoringConfig {
+    /// Score lost for an invalid vertex
+    pub invalid_vertex_penalty: f64,
+    /// Score lost for an invalid gossip message
+    pub invalid_message_penalty: f64,
+    /// Score lost for a timeout
+    pub timeout_penalty: f64,
+    /// Score lost for breaking a resource limit
+    pub limit_penalty: f64,
+    /// Score gained for valid data
+    pub useful_reward: f64,
+    /// Ban peers at once when they equivocate
+    pub ban_on_equivocation: bool,
+    /// Combined score below which a peer is banned
+    pub ban_threshold: f64,
+    /// Length of a first ban; every further ban of the peer doubles it
+    pub ban_duration: Duration,
+    /// Longest ban imposed automatically
+    pub max_ban_duration: Duration,
+    /// Time in which a local score decays to half
+    pub score_half_life: Duration,
+    /// Peers never banned automatically
+    pub allowlist: Vec<PeerId>,
+    /// Refuse every peer outside the allowlist
+    pub allowlist_only: bool,
+    /// Gossipsub peer scoring, disabled when unset
+    pub gossipsub: Option<(PeerScoreParams, PeerScoreThresholds)>,
+}
+
+impl Default for PeerScoringConfig {
+    fn default() -> Self {
+        Self {
+            invalid_vertex_penalty: 20.0,
+            invalid_message_penalty: 10.0,
+            timeout_penalty: 2.0,
+            limit_penalty: 5.0,
+            useful_reward: 0.5,
+            ban_on_equivocation: true,
+            ban_threshold: -50.0,
+            ban_duration: Duration::from_secs(60 * 60),
+            max_ban_duration: Duration::from_secs(7 * 24 * 60 * 60),
+            score_half_life: Duration::from_secs(60 * 60),
+            allowlist: Vec::new(),
+            allowlist_only: false,
+            gossipsub: Some((
+                PeerScoreParams {
+                    // The application score is the local score, already on
+                    // the scale of the gossipsub thresholds
+                    app_specific_weight: 1.0,
+                    ..Default::default()
+                },
+                PeerScoreThresholds::default(),
+            )),
+        }
+    }
+}
+
+/// A ban in force
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ban {
+    pub peer: PeerId,
+    /// When the ban is lifted
+    pub until: SystemTime,
+    pub reason: String,
+}
+
+/// Scores of one peer
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeerScore {
+    /// Score from reported events
+    pub local: f64,
+    /// Gossipsub score without the application-specific part
+    pub gossip: f64,
+}
+
+impl PeerScore {
+    /// The score bans are decided on
+    pub fn combined(&self) -> f64 {
+        self.local + self.gossip
+    }
+}
+
+/// Bans and allowlist changes for [`PeerGate`] to act on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerScoringEvent {
+    /// A peer was banned; its connections are closed
+    Banned(Ban),
+    /// A ban was lifted or ran out
+    Unbanned(PeerId),
+}
+
+/// A ban and how many bans the peer had before
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BanEntry {
+    until: u64,
+    reason: String,
+    count: u32,
+}
+
+/// On-disk form of bans and the allowlist
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedScoring {
+    bans: HashMap<String, BanEntry>,
+    allowlist: Vec<String>,
+}
+
+#[derive(Debug)]
+struct ScoreBook {
+    config: PeerScoringConfig,
+    scores: HashMap<PeerId, PeerScore>,
+    bans: HashMap<PeerId, BanEntry>,
+    /// Number of bans each peer has had, for escalation
+    ban_counts: HashMap<PeerId, u32>,
+    allowlist: HashSet<PeerId>,
+    path: Option<PathBuf>,
+    /// Writes the ban file off the caller's thread
+    writer: Option<DiskWriter>,
+    last_decay: Instant,
+    events: VecDeque<PeerScoringEvent>,
+    waker: Option<Waker>,
+}
+
+impl ScoreBook {
+    fn is_banned(&self, peer: &PeerId) -> bool {
+        self.bans
+            .get(peer)
+            .is_some_and(|ban| ban.until > unix_now())
+    }
+
+    fn check(&self, peer: &PeerId) -> Result<(), PeerScoringError> {
+        if self.is_banned(peer) {
+            return Err(PeerScoringError::Banned(*peer));
+        }
+        if self.config.allowlist_only && !self.allowlist.contains(peer) {
+            return Err(PeerScoringError::NotAllowed(*peer));
+        }
+        Ok(())
+    }
+
+    fn ban(&mut self, peer: PeerId, duration: Duration, reason: String) -> Ban {
+        let count = self
+            .bans
+            .get(&peer)
+            .map(|ban| ban.count)
+            .or_else(|| self.ban_counts.get(&peer).copied())
+            .unwrap_or(0)
+            + 1;
+        let until = unix_now().saturating_add(duration.as_secs());
+        self.bans.insert(
+            peer,
+            BanEntry {
+                until,
+                reason: reason.clone(),
+                count,
+            },
+        );
+        self.ban_counts.insert(peer, count);
+        let ban = Ban {
+            peer,
+            until: UNIX_EPOCH + Duration::from_secs(until),
+            reason,
+        };
+        info!("Banned peer {} until {}: {}", peer, until, ban.reason);
+        metrics::counter!("qudag_peer_bans_total", 1);
+        self.notify(PeerScoringEvent::Banned(ban.clone()));
+        self.persist();
+        ban
+    }
+
+    /// Ban a peer whose score fell below the threshold, escalating repeats
+    fn ban_if_due(&mut self, peer: PeerId, reason: &str) -> Option<Ban> {
+        if self.allowlist.contains(&peer) || self.is_banned(&peer) {
+            return None;
+        }
+        let score = self.scores.get(&peer).copied().unwrap_or_default();
+        if score.combined() >= self.config.ban_threshold {
+            return None;
+        }
+        let previous = self.ban_counts.get(&peer).copied().unwrap_or(0).min(16);
+        let duration = self
+            .config
+            .ban_duration
+            .saturating_mul(1 << previous)
+            .min(self.config.max_ban_duration);
+        Some(self.ban(peer, duration, reason.to_string()))
+    }
+
+    fn adjust(&mut self, peer: PeerId, delta: f64) -> f64 {
+        let score = self.scores.entry(peer).or_default();
+        score.local = (score.local + delta).clamp(-SCORE_BOUND, SCORE_BOUND);
+        score.local
+    }
+
+    fn notify(&mut self, event: PeerScoringEvent) {
+        self.events.push_back(event);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Queue the bans and allowlist for writing to the ban file
+    fn persist(&self) {
+        let (Some(path), Some(writer)) = (&self.path, &self.writer) else {
+            return;
+        };
+        let state = PersistedScoring {
+            bans: self
+                .bans
+                .iter()
+                .map(|(peer, ban)| (peer.to_string(), ban.clone()))
+                .collect(),
+            allowlist: self.allowlist.iter().map(PeerId::to_string).collect(),
+        };
+        let bytes = match serde_json::to_vec_pretty(&state) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Failed to encode peer bans: {}", e);
+                return;
+            }
+        };
+        let path = path.clone();
+        writer.submit(move || {
+            if let Err(e) = write_atomically(&path, &bytes) {
+                warn!("Failed to persist peer bans to {}: {}", path.display(), e);
+            }
+        });
+    }
+}
+
+/// Shared peer scores, bans and allowlist
+///
+/// Clones refer to the same book.
+#[derive(Debug, Clone)]
+pub struct PeerScoring {
+    book: Arc<Mutex<ScoreBook>>,
+}
+
+impl Default for PeerScoring {
+    fn default() -> Self {
+        Self::new(PeerScoringConfig::default())
+    }
+}
+
+impl PeerScoring {
+    /// Create scoring that keeps bans in memory only
+    pub fn new(config: PeerScoringConfig) -> Self {
+        let allowlist = config.allowlist.iter().copied().collect();
+        Self {
+            book: Arc::new(Mutex::new(ScoreBook {
+                config,
+                scores: HashMap::new(),
+                bans: HashMap::new(),
+                ban_counts: HashMap::new(),
+                allowlist,
+                path: None,
+                writer: None,
+                last_decay: Instant::now(),
+                events: VecDeque::new(),
+                waker: None,
+            })),
+        }
+    }
+
+    /// Create scoring that persists bans and the allowlist to `path`
+    ///
+    /// Bans and allowlist entries already in the file are loaded; the
+    /// configured allowlist is added to them.
+    pub fn open(
+        path: impl AsRef<Path>,
+        config: PeerScoringConfig,
+    ) -> Result<Self, PeerScoringError> {
+        let path = path.as_ref().to_path_buf();
+        let persisted: PersistedScoring = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => PersistedScoring::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let scoring = Self::new(config);
+        {
+            let mut book = scoring.lock();
+            for (peer, ban) in persisted.bans {
+                let peer = parse_peer_id(&peer)?;
+                book.ban_counts.insert(peer, ban.count);
+                book.bans.insert(peer, ban);
+            }
+            for peer in persisted.allowlist {
+                book.allowlist.insert(parse_peer_id(&peer)?);
+            }
+            book.path = Some(path);
+            book.writer = Some(DiskWriter::new("peer-scores-writer")?);
+            book.persist();
+        }
+        Ok(scoring)
+    }
+
+    /// The configuration in use
+    pub fn config(&self) -> PeerScoringConfig {
+        self.lock().config.clone()
+    }
+
+    /// Record something a peer did, banning it if its score fell too low
+    pub fn report(&self, peer: PeerId, event: PeerEvent) -> Option<Ban> {
+        let mut book = self.lock();
+        let delta = match event {
+            PeerEvent::InvalidVertex => -book.config.invalid_vertex_penalty,
+            PeerEvent::Equivocation => {
+                if book.config.ban_on_equivocation && !book.allowlist.contains(&peer) {
+                    book.adjust(peer, -SCORE_BOUND);
+                    if book.is_banned(&peer) {
+                        return None;
+                    }
+                    let duration = book.config.max_ban_duration;
+                    return Some(book.ban(peer, duration, "equivocation".to_string()));
+                }
+                -SCORE_BOUND
+            }
+            PeerEvent::InvalidMessage => -book.config.invalid_message_penalty,
+            PeerEvent::Timeout => -book.config.timeout_penalty,
+            PeerEvent::LimitExceeded => -book.config.limit_penalty,
+            PeerEvent::Useful => book.config.useful_reward,
+        };
+        book.adjust(peer, delta);
+        if delta < 0.0 {
+            metrics::counter!("qudag_peer_penalties_total", 1);
+        }
+        book.ban_if_due(peer, &format!("score below threshold after {:?}", event))
+    }
+
+    /// Move a peer's local score by `delta`
+    pub fn adjust(&self, peer: PeerId, delta: f64) -> Option<Ban> {
+        let mut book = self.lock();
+        book.adjust(peer, delta);
+        book.ban_if_due(peer, "score below threshold")
+    }
+
+    /// Store the gossipsub score of a peer, without its application part
+    pub fn set_gossip_score(&self, peer: PeerId, score: f64) -> Option<Ban> {
+        let mut book = self.lock();
+        book.scores.entry(peer).or_default().gossip = score;
+        book.ban_if_due(peer, "gossipsub score below threshold")
+    }
+
+    /// Scores of a peer
+    pub fn score(&self, peer: &PeerId) -> PeerScore {
+        self.lock().scores.get(peer).copied().unwrap_or_default()
+    }
+
+    /// Scores of every peer with one
+    pub fn scores(&self) -> Vec<(PeerId, PeerScore)> {
+        self.lock()
+            .scores
+            .iter()
+            .map(|(peer, score)| (*peer, *score))
+            .collect()
+    }
+
+    /// Ban a peer for `duration`, even when it is allowlisted
+    pub fn ban(&self, peer: PeerId, duration: Duration, reason: impl Into<String>) -> Ban {
+        self.lock().ban(peer, duration, reason.into())
+    }
+
+    /// Lift a ban, returning whether the peer was banned
+    pub fn unban(&self, peer: &PeerId) -> bool {
+        let mut book = self.lock();
+        if book.bans.remove(peer).is_none() {
+            return false;
+        }
+        // A lifted ban is not held against the peer's score
+        if let Some(score) = book.scores.get_mut(peer) {
+            score.local = score.local.max(0.0);
+        }
+        info!("Unbanned peer {}", peer);
+        book.notify(PeerScoringEvent::Unbanned(*peer));
+        book.persist();
+        true
+    }
+
+    /// Whether a ban on the peer is in force
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.lock().is_banned(peer)
+    }
+
+    /// Bans in force
+    pub fn bans(&self) -> Vec<Ban> {
+        let now = unix_now();
+        self.lock()
+            .bans
+            .iter()
+            .filter(|(_, ban)| ban.until > now)
+            .map(|(peer, ban)| Ban {
+                peer: *peer,
+                until: UNIX_EPOCH + Duration::from_secs(ban.until),
+                reason: ban.reason.clone(),
+            })
+            .collect()
+    }
+
+    /// Add a peer to the allowlist
+    pub fn allow(&self, peer: PeerId) {
+        let mut book = self.lock();
+        if book.allowlist.insert(peer) {
+            book.persist();
+        }
+    }
+
+    /// Remove a peer from the allowlist
+    ///
+    /// In allowlist-only mode its connections are closed.
+    pub fn disallow(&self, peer: &PeerId) {
+        let mut book = self.lock();
+        if !book.allowlist.remove(peer) {
+            return;
+        }
+        if book.config.allowlist_only {
+            book.notify(PeerScoringEvent::Banned(Ban {
+                peer: *peer,
+                until: SystemTime::now(),
+                reason: "removed from allowlist".to_string(),
+            }));
+        }
+        book.persist();
+    }
+
+    /// Whether a peer is allowlisted
+    pub fn is_allowlisted(&self, peer: &PeerId) -> bool {
+        self.lock().allowlist.contains(peer)
+    }
+
+    /// Allowlisted peers
+    pub fn allowlist(&self) -> Vec<PeerId> {
+        self.lock().allowlist.iter().copied().collect()
+    }
+
+    /// Whether a connection with the peer may be kept
+    pub fn check(&self, peer: &PeerId) -> Result<(), PeerScoringError> {
+        self.lock().check(peer)
+    }
+
+    /// Decay local scores and drop bans that ran out
+    pub fn maintain(&self) {
+        let mut book = self.lock();
+        let now = Instant::now();
+        let elapsed = now.duration_since(book.last_decay);
+        book.last_decay = now;
+        let half_life = book.config.score_half_life.as_secs_f64();
+        if half_life > 0.0 {
+            let factor = 0.5f64.powf(elapsed.as_secs_f64() / half_life);
+            for score in book.scores.values_mut() {
+                score.local *= factor;
+            }
+        }
+        book.scores
+            .retain(|_, score| score.local.abs() > 0.01 || score.gossip != 0.0);
+
+        let unix = unix_now();
+        let expired: Vec<PeerId> = book
+            .bans
+            .iter()
+            .filter(|(_, ban)| ban.until <= unix)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in &expired {
+            book.bans.remove(peer);
+            // The peer starts over once its ban has been served
+            book.scores.remove(peer);
+            book.notify(PeerScoringEvent::Unbanned(*peer));
+        }
+        if !expired.is_empty() {
+            book.persist();
+        }
+        metrics::gauge!("qudag_peer_bans", book.bans.len() as f64);
+    }
+
+    /// Wait until bans and allowlist changes made so far are on disk
+    pub fn flush(&self) {
+        if let Some(writer) = &self.lock().writer {
+            writer.flush();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ScoreBook> {
+        self.book.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Network behaviour enforcing [`PeerScoring`] bans and the allowlist
+///
+/// Refuses dials to and connections from refused peers and closes the
+/// connections of peers as they are banned.
+pub struct PeerGate {
+    scoring: PeerScoring,
+    pending: VecDeque<ToSwarm<PeerScoringEvent, THandlerInEvent<Self>>>,
+}
+
+impl PeerGate {
+    /// Enforce the given scoring
+    pub fn new(scoring: PeerScoring) -> Self {
+        Self {
+            scoring,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The scoring enforced
+    pub fn scoring(&self) -> &PeerScoring {
+        &self.scoring
+    }
+
+    fn enforce(&self, peer: &PeerId) -> Result<(), ConnectionDenied> {
+        self.scoring.check(peer).map_err(ConnectionDenied::new)
+    }
+}
+
+impl NetworkBehaviour for PeerGate {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = PeerScoringEvent;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.enforce(&peer)?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: Option<PeerId>,
+        _: &[Multiaddr],
+        _: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        if let Some(peer) = peer {
+            self.enforce(&peer)?;
+        }
+        Ok(Vec::new())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.enforce(&peer)?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, _event: FromSwarm) {}
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        void::unreachable(event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        if let Some(action) = self.pending.pop_front() {
+            return Poll::Ready(action);
+        }
+        let mut book = self.scoring.lock();
+        let Some(event) = book.events.pop_front() else {
+            book.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        };
+        drop(book);
+        if let PeerScoringEvent::Banned(ban) = &event {
+            self.pending
+                .push_back(ToSwarm::GenerateEvent(event.clone()));
+            return Poll::Ready(ToSwarm::CloseConnection {
+                peer_id: ban.peer,
+                connection: CloseConnection::All,
+            });
+        }
+        Poll::Ready(ToSwarm::GenerateEvent(event))
+    }
+}
+
+fn parse_peer_id(peer: &str) -> Result<PeerId, PeerScoringError> {
+    peer.parse()
+        .map_err(|_| PeerScoringError::InvalidPeerId(peer.to_string()))
+}
+
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
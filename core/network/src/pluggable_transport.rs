@@ -0,0 +1,1310 @@
+//! Pluggable transports that disguise connections as other protocols.
+//!
+//! [`TrafficObfuscator`](crate::traffic_obfuscation::TrafficObfuscator) pads
+//! and delays messages but leaves the wire format recognisable. The transports
+//! here sit below the libp2p security upgrade and change what a middlebox
+//! parses the connection as:
+//!
+//! - [`PluggableTransport::HttpLongPoll`]: HTTP/1.1 long polling. The client
+//!   sends its data in pipelined `POST` requests and keeps one request
+//!   outstanding, which the server holds until it has data to answer with.
+//! - [`PluggableTransport::WebSocket`]: an RFC 6455 upgrade followed by binary
+//!   frames, masked from the client and unmasked from the server.
+//! - [`PluggableTransport::Obfs4`]: no plaintext structure at all. Handshake
+//!   messages and frames look like random bytes and are padded to random
+//!   lengths, with frame lengths masked by a keyed stream.
+//!
+//! Each transport authenticates the client against a secret shared by the
+//! network. The client proves knowledge of it in its first message. A server
+//! that cannot verify the proof answers like an ordinary web server, or for
+//! obfs4 closes the connection, instead of revealing itself. Payloads are
+//! sealed with ChaCha20-Poly1305 under per-connection keys so that the
+//! multistream-select negotiation preceding the security upgrade never
+//! appears in the clear. Confidentiality against holders of the shared
+//! secret is left to that upgrade.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use either::Either;
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures::ready;
+use hkdf::Hkdf;
+use libp2p::core::transport::{ListenerId, TransportError, TransportEvent};
+use libp2p::core::Transport;
+use libp2p::Multiaddr;
+use parking_lot::Mutex;
+use rand::{thread_rng, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::debug;
+use zeroize::Zeroizing;
+
+/// Largest plaintext sealed into one record
+const MAX_RECORD: usize = 16 * 1024;
+
+/// Largest padding added to one obfs4 frame
+pub const MAX_FRAME_PADDING: usize = 4096;
+
+/// Largest padding in an obfs4 handshake message
+const MAX_HANDSHAKE_PADDING: usize = 1024;
+
+/// Largest HTTP request or response head accepted
+const MAX_HTTP_HEAD: usize = 8 * 1024;
+
+/// Largest WebSocket or HTTP body accepted
+const MAX_BODY: usize = MAX_RECORD + 1024;
+
+const NONCE_SIZE: usize = 32;
+const PROOF_SIZE: usize = 16;
+
+/// Handshake proofs are bound to the hour, and accepted one hour either side
+const EPOCH_SECS: u64 = 3600;
+
+/// Client nonces remembered to reject replayed handshakes
+const REPLAY_CAPACITY: usize = 8192;
+
+const SESSION_COOKIE: &str = "sid";
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36";
+const NOT_FOUND_BODY: &str =
+    "<html><head><title>404 Not Found</title></head><body><h1>Not Found</h1></body></html>";
+
+const WS_OPCODE_BINARY: u8 = 0x2;
+const WS_OPCODE_CLOSE: u8 = 0x8;
+const WS_OPCODE_PING: u8 = 0x9;
+const WS_OPCODE_PONG: u8 = 0xA;
+
+/// Errors that can occur while establishing a disguised connection
+#[derive(Debug, Error)]
+pub enum PluggableTransportError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed handshake: {0}")]
+    Malformed(&'static str),
+    #[error("Peer failed to prove the shared secret")]
+    Unauthenticated,
+    #[error("Replayed handshake")]
+    Replay,
+    #[error("Peer rejected the handshake")]
+    Rejected,
+}
+
+/// Protocol a connection is disguised as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PluggableTransport {
+    /// HTTP/1.1 long polling
+    HttpLongPoll,
+    /// WebSocket binary frames
+    WebSocket,
+    /// Randomized obfs4-style stream
+    Obfs4,
+}
+
+impl PluggableTransport {
+    fn label(self) -> &'static [u8] {
+        match self {
+            Self::HttpLongPoll => b"http-long-poll",
+            Self::WebSocket => b"websocket",
+            Self::Obfs4 => b"obfs4",
+        }
+    }
+}
+
+/// Configuration of a pluggable transport
+#[derive(Clone)]
+pub struct PluggableTransportConfig {
+    /// Protocol to disguise connections as
+    pub transport: PluggableTransport,
+    /// Secret shared by every node of the network
+    pub shared_secret: [u8; 32],
+    /// `Host` header sent by HTTP and WebSocket clients
+    pub host: String,
+    /// Request path used by HTTP and WebSocket clients
+    pub path: String,
+    /// Largest random padding added to an obfs4 frame, capped at
+    /// [`MAX_FRAME_PADDING`]
+    pub max_padding: usize,
+}
+
+impl PluggableTransportConfig {
+    /// Configuration for `transport` with default HTTP settings
+    pub fn new(transport: PluggableTransport, shared_secret: [u8; 32]) -> Self {
+        Self {
+            transport,
+            shared_secret,
+            host: "cdn.example.com".to_string(),
+            path: "/".to_string(),
+            max_padding: 256,
+        }
+    }
+}
+
+impl fmt::Debug for PluggableTransportConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluggableTransportConfig")
+            .field("transport", &self.transport)
+            .field("shared_secret", &"<redacted>")
+            .field("host", &self.host)
+            .field("path", &self.path)
+            .field("max_padding", &self.max_padding)
+            .finish()
+    }
+}
+
+/// Client nonces of recent handshakes
+///
+/// Bounded, so a nonce can be replayed once it has been evicted; by then the
+/// proof it came with has usually expired as well.
+#[derive(Default)]
+pub(crate) struct ReplayFilter {
+    seen: Mutex<SeenNonces>,
+}
+
+#[derive(Default)]
+struct SeenNonces {
+    set: HashSet<[u8; NONCE_SIZE]>,
+    order: VecDeque<[u8; NONCE_SIZE]>,
+}
+
+impl ReplayFilter {
+    /// Record `nonce`, returning `false` if it was already seen
+    fn insert(&self, nonce: [u8; NONCE_SIZE]) -> bool {
+        let mut seen = self.seen.lock();
+        if !seen.set.insert(nonce) {
+            return false;
+        }
+        seen.order.push_back(nonce);
+        if seen.order.len() > REPLAY_CAPACITY {
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.set.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// libp2p transport wrapper disguising every connection of `inner`
+pub struct ObfuscatedTransport<T> {
+    inner: T,
+    config: Arc<PluggableTransportConfig>,
+    replay: Arc<ReplayFilter>,
+}
+
+impl<T> ObfuscatedTransport<T> {
+    /// Wrap `inner` in the configured pluggable transport
+    pub fn new(inner: T, config: PluggableTransportConfig) -> Self {
+        Self {
+            inner,
+            config: Arc::new(config),
+            replay: Arc::new(ReplayFilter::default()),
+        }
+    }
+}
+
+impl<T> Transport for ObfuscatedTransport<T>
+where
+    T: Transport + Unpin,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Error: Send + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    type Output = DisguisedStream<T::Output>;
+    type Error = Either<T::Error, PluggableTransportError>;
+    type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(
+        &mut self,
+        id: ListenerId,
+        addr: Multiaddr,
+    ) -> Result<(), TransportError<Self::Error>> {
+        self.inner
+            .listen_on(id, addr)
+            .map_err(|e| e.map(Either::Left))
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.inner.remove_listener(id)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let dial = self.inner.dial(addr).map_err(|e| e.map(Either::Left))?;
+        let config = self.config.clone();
+        Ok(async move {
+            let io = dial.await.map_err(Either::Left)?;
+            dial_handshake(io, &config).await.map_err(Either::Right)
+        }
+        .boxed())
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        // The remote dials normally, so this side answers its handshake
+        let dial = self
+            .inner
+            .dial_as_listener(addr)
+            .map_err(|e| e.map(Either::Left))?;
+        let config = self.config.clone();
+        let replay = self.replay.clone();
+        Ok(async move {
+            let io = dial.await.map_err(Either::Left)?;
+            accept_handshake(io, &config, &replay)
+                .await
+                .map_err(Either::Right)
+        }
+        .boxed())
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        let this = self.get_mut();
+        let event = ready!(Pin::new(&mut this.inner).poll(cx));
+        let config = this.config.clone();
+        let replay = this.replay.clone();
+        Poll::Ready(
+            event
+                .map_upgrade(|upgrade| {
+                    async move {
+                        let io = upgrade.await.map_err(Either::Left)?;
+                        accept_handshake(io, &config, &replay)
+                            .await
+                            .map_err(Either::Right)
+                    }
+                    .boxed()
+                })
+                .map_err(Either::Left),
+        )
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(listen, observed)
+    }
+}
+
+/// Run the client side of the configured handshake over `io`
+pub(crate) async fn dial_handshake<S>(
+    mut io: S,
+    config: &PluggableTransportConfig,
+) -> Result<DisguisedStream<S>, PluggableTransportError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let client_nonce = random_nonce();
+    let hour = current_hour();
+
+    if config.transport == PluggableTransport::Obfs4 {
+        let padding = random_padding(MAX_HANDSHAKE_PADDING);
+        let mut hello = client_nonce.to_vec();
+        hello.extend_from_slice(&padding);
+        hello.extend_from_slice(&client_mark(config, &client_nonce));
+        hello.extend_from_slice(&client_proof(config, &client_nonce, &padding, hour));
+        io.write_all(&hello).await?;
+        io.flush().await?;
+
+        let (server_nonce, padding, proof, rest) =
+            read_marked(&mut io, |nonce| server_mark(config, nonce)).await?;
+        if !ct_eq(
+            &proof,
+            &server_proof(config, &client_nonce, &server_nonce, &padding),
+        ) {
+            return Err(PluggableTransportError::Unauthenticated);
+        }
+        let (send, recv) = session_ciphers(config, &client_nonce, &server_nonce, true);
+        return Ok(DisguisedStream::new(
+            io,
+            Framing::Obfs4 {
+                max_padding: config.max_padding.min(MAX_FRAME_PADDING),
+            },
+            send,
+            recv,
+            rest,
+        ));
+    }
+
+    let mut token = client_nonce.to_vec();
+    token.extend_from_slice(&client_proof(config, &client_nonce, &[], hour));
+    let cookie = general_purpose::URL_SAFE_NO_PAD.encode(token);
+    let websocket_key = general_purpose::STANDARD.encode(thread_rng().gen::<[u8; 16]>());
+    let request = match config.transport {
+        PluggableTransport::WebSocket => format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\
+             Cookie: {}={}\r\n\r\n",
+            config.path, config.host, USER_AGENT, websocket_key, SESSION_COOKIE, cookie
+        ),
+        _ => format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\n\
+             Cookie: {}={}\r\nConnection: keep-alive\r\n\r\n",
+            config.path, config.host, USER_AGENT, SESSION_COOKIE, cookie
+        ),
+    };
+    io.write_all(request.as_bytes()).await?;
+    io.flush().await?;
+
+    let (head, rest) = read_http_head(&mut io).await?;
+    let expected_status = match config.transport {
+        PluggableTransport::WebSocket => "101",
+        _ => "200",
+    };
+    if head.start_line.split(' ').nth(1) != Some(expected_status) {
+        return Err(PluggableTransportError::Rejected);
+    }
+    if config.transport == PluggableTransport::WebSocket
+        && head.header("sec-websocket-accept") != Some(websocket_accept(&websocket_key).as_str())
+    {
+        return Err(PluggableTransportError::Malformed(
+            "wrong Sec-WebSocket-Accept",
+        ));
+    }
+    if config.transport == PluggableTransport::HttpLongPoll && head.content_length()? != 0 {
+        return Err(PluggableTransportError::Malformed(
+            "handshake response has a body",
+        ));
+    }
+
+    let token = head
+        .header("set-cookie")
+        .and_then(|value| cookie_value(value.split(';').next().unwrap_or_default()))
+        .and_then(|value| general_purpose::URL_SAFE_NO_PAD.decode(value).ok())
+        .filter(|token| token.len() == NONCE_SIZE + PROOF_SIZE)
+        .ok_or(PluggableTransportError::Malformed("missing session cookie"))?;
+    let (server_nonce, proof) = token.split_at(NONCE_SIZE);
+    let server_nonce: [u8; NONCE_SIZE] = server_nonce.try_into().expect("checked length");
+    if !ct_eq(
+        proof,
+        &server_proof(config, &client_nonce, &server_nonce, &[]),
+    ) {
+        return Err(PluggableTransportError::Unauthenticated);
+    }
+
+    let (send, recv) = session_ciphers(config, &client_nonce, &server_nonce, true);
+    let framing = match config.transport {
+        PluggableTransport::WebSocket => Framing::WebSocket { dialer: true },
+        _ => Framing::Http(HttpState {
+            dialer: true,
+            host: config.host.clone(),
+            path: config.path.clone(),
+            in_flight: 0,
+        }),
+    };
+    let mut stream = DisguisedStream::new(io, framing, send, recv, rest);
+    // Keep a request outstanding for the server to answer
+    stream.queue_poll();
+    Ok(stream)
+}
+
+/// Run the server side of the configured handshake over `io`
+pub(crate) async fn accept_handshake<S>(
+    mut io: S,
+    config: &PluggableTransportConfig,
+    replay: &ReplayFilter,
+) -> Result<DisguisedStream<S>, PluggableTransportError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let server_nonce = random_nonce();
+
+    if config.transport == PluggableTransport::Obfs4 {
+        let (client_nonce, padding, proof, rest) =
+            read_marked(&mut io, |nonce| client_mark(config, nonce)).await?;
+        if !verify_client_proof(config, &client_nonce, &padding, &proof) {
+            return Err(PluggableTransportError::Unauthenticated);
+        }
+        if !replay.insert(client_nonce) {
+            return Err(PluggableTransportError::Replay);
+        }
+
+        let padding = random_padding(MAX_HANDSHAKE_PADDING);
+        let mut reply = server_nonce.to_vec();
+        reply.extend_from_slice(&padding);
+        reply.extend_from_slice(&server_mark(config, &server_nonce));
+        reply.extend_from_slice(&server_proof(
+            config,
+            &client_nonce,
+            &server_nonce,
+            &padding,
+        ));
+        io.write_all(&reply).await?;
+        io.flush().await?;
+
+        let (send, recv) = session_ciphers(config, &client_nonce, &server_nonce, false);
+        return Ok(DisguisedStream::new(
+            io,
+            Framing::Obfs4 {
+                max_padding: config.max_padding.min(MAX_FRAME_PADDING),
+            },
+            send,
+            recv,
+            rest,
+        ));
+    }
+
+    let (head, rest) = read_http_head(&mut io).await?;
+    let client_nonce = match verify_http_request(config, &head) {
+        Ok(client_nonce) if replay.insert(client_nonce) => client_nonce,
+        result => {
+            debug!("Rejected disguised handshake: {:?}", result.as_ref().err());
+            // Look like a web server without the requested page
+            let response = format!(
+                "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                NOT_FOUND_BODY.len(),
+                NOT_FOUND_BODY
+            );
+            io.write_all(response.as_bytes()).await?;
+            io.close().await?;
+            return Err(result.err().unwrap_or(PluggableTransportError::Replay));
+        }
+    };
+
+    let mut token = server_nonce.to_vec();
+    token.extend_from_slice(&server_proof(config, &client_nonce, &server_nonce, &[]));
+    let cookie = general_purpose::URL_SAFE_NO_PAD.encode(token);
+    let response = match config.transport {
+        PluggableTransport::WebSocket => format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\nSet-Cookie: {}={}; Path=/; HttpOnly\r\n\r\n",
+            websocket_accept(head.header("sec-websocket-key").unwrap_or_default()),
+            SESSION_COOKIE,
+            cookie
+        ),
+        _ => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n\
+             Cache-Control: no-store\r\nSet-Cookie: {}={}; Path=/; HttpOnly\r\n\
+             Content-Length: 0\r\n\r\n",
+            SESSION_COOKIE, cookie
+        ),
+    };
+    io.write_all(response.as_bytes()).await?;
+    io.flush().await?;
+
+    let (send, recv) = session_ciphers(config, &client_nonce, &server_nonce, false);
+    let framing = match config.transport {
+        PluggableTransport::WebSocket => Framing::WebSocket { dialer: false },
+        _ => Framing::Http(HttpState {
+            dialer: false,
+            host: config.host.clone(),
+            path: config.path.clone(),
+            in_flight: 0,
+        }),
+    };
+    Ok(DisguisedStream::new(io, framing, send, recv, rest))
+}
+
+/// Check the upgrade or poll request opening a connection
+fn verify_http_request(
+    config: &PluggableTransportConfig,
+    head: &HttpHead,
+) -> Result<[u8; NONCE_SIZE], PluggableTransportError> {
+    let mut start = head.start_line.split(' ');
+    if start.next() != Some("GET") || start.next() != Some(config.path.as_str()) {
+        return Err(PluggableTransportError::Malformed("unexpected request"));
+    }
+    if config.transport == PluggableTransport::WebSocket
+        && (!head
+            .header("upgrade")
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+            || head.header("sec-websocket-key").is_none())
+    {
+        return Err(PluggableTransportError::Malformed(
+            "not a WebSocket upgrade",
+        ));
+    }
+    let token = head
+        .header("cookie")
+        .and_then(|value| value.split(';').find_map(cookie_value))
+        .and_then(|value| general_purpose::URL_SAFE_NO_PAD.decode(value).ok())
+        .filter(|token| token.len() == NONCE_SIZE + PROOF_SIZE)
+        .ok_or(PluggableTransportError::Unauthenticated)?;
+    let (client_nonce, proof) = token.split_at(NONCE_SIZE);
+    let client_nonce: [u8; NONCE_SIZE] = client_nonce.try_into().expect("checked length");
+    if !verify_client_proof(config, &client_nonce, &[], proof) {
+        return Err(PluggableTransportError::Unauthenticated);
+    }
+    Ok(client_nonce)
+}
+
+/// Value of the session cookie in one `name=value` pair
+fn cookie_value(pair: &str) -> Option<&str> {
+    let (name, value) = pair.trim().split_once('=')?;
+    (name == SESSION_COOKIE).then_some(value)
+}
+
+fn websocket_accept(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+fn random_nonce() -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+fn random_padding(max: usize) -> Vec<u8> {
+    let mut padding = vec![0u8; thread_rng().gen_range(0..=max)];
+    thread_rng().fill_bytes(&mut padding);
+    padding
+}
+
+fn current_hour() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / EPOCH_SECS
+}
+
+/// Keyed hash of `parts` under the shared secret, truncated to a proof
+fn keyed_proof(config: &PluggableTransportConfig, parts: &[&[u8]]) -> [u8; PROOF_SIZE] {
+    let mut hasher = blake3::Hasher::new_keyed(&config.shared_secret);
+    hasher.update(config.transport.label());
+    for part in parts {
+        hasher.update(&(part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    let mut proof = [0u8; PROOF_SIZE];
+    proof.copy_from_slice(&hasher.finalize().as_bytes()[..PROOF_SIZE]);
+    proof
+}
+
+fn client_mark(config: &PluggableTransportConfig, nonce: &[u8]) -> [u8; PROOF_SIZE] {
+    keyed_proof(config, &[b"client-mark", nonce])
+}
+
+fn server_mark(config: &PluggableTransportConfig, nonce: &[u8]) -> [u8; PROOF_SIZE] {
+    keyed_proof(config, &[b"server-mark", nonce])
+}
+
+fn client_proof(
+    config: &PluggableTransportConfig,
+    nonce: &[u8],
+    padding: &[u8],
+    hour: u64,
+) -> [u8; PROOF_SIZE] {
+    keyed_proof(config, &[b"client", nonce, padding, &hour.to_be_bytes()])
+}
+
+fn verify_client_proof(
+    config: &PluggableTransportConfig,
+    nonce: &[u8],
+    padding: &[u8],
+    proof: &[u8],
+) -> bool {
+    let hour = current_hour();
+    [hour.saturating_sub(1), hour, hour + 1]
+        .iter()
+        .any(|&hour| ct_eq(proof, &client_proof(config, nonce, padding, hour)))
+}
+
+fn server_proof(
+    config: &PluggableTransportConfig,
+    client_nonce: &[u8],
+    server_nonce: &[u8],
+    padding: &[u8],
+) -> [u8; PROOF_SIZE] {
+    keyed_proof(config, &[b"server", client_nonce, server_nonce, padding])
+}
+
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Derive the sending and receiving ciphers of one side
+fn session_ciphers(
+    config: &PluggableTransportConfig,
+    client_nonce: &[u8; NONCE_SIZE],
+    server_nonce: &[u8; NONCE_SIZE],
+    dialer: bool,
+) -> (RecordCipher, RecordCipher) {
+    let mut salt = client_nonce.to_vec();
+    salt.extend_from_slice(server_nonce);
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), &config.shared_secret);
+    let derive = |direction: &[u8]| {
+        let mut okm = Zeroizing::new([0u8; 64]);
+        let info = [b"qudag-pt ".as_slice(), config.transport.label(), direction].concat();
+        hkdf.expand(&info, okm.as_mut())
+            .expect("64 bytes is a valid HKDF-SHA256 output length");
+        RecordCipher::new(&okm)
+    };
+    let client_to_server = derive(b" client-to-server");
+    let server_to_client = derive(b" server-to-client");
+    if dialer {
+        (client_to_server, server_to_client)
+    } else {
+        (server_to_client, client_to_server)
+    }
+}
+
+/// Read an obfs4 handshake message: nonce, padding, mark and proof
+///
+/// The mark, derived from the nonce, is the only way to find where the
+/// random-length padding ends.
+async fn read_marked<S, F>(
+    io: &mut S,
+    mark_for: F,
+) -> Result<([u8; NONCE_SIZE], Vec<u8>, [u8; PROOF_SIZE], Vec<u8>), PluggableTransportError>
+where
+    S: AsyncRead + Unpin,
+    F: Fn(&[u8]) -> [u8; PROOF_SIZE],
+{
+    let limit = NONCE_SIZE + MAX_HANDSHAKE_PADDING + 2 * PROOF_SIZE;
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let mut mark = None;
+    loop {
+        if buf.len() >= NONCE_SIZE {
+            let mark = mark.get_or_insert_with(|| mark_for(&buf[..NONCE_SIZE]));
+            let search = &buf[NONCE_SIZE..buf.len().min(limit - PROOF_SIZE)];
+            if let Some(position) = search
+                .windows(PROOF_SIZE)
+                .position(|window| window == mark.as_slice())
+            {
+                let mark_start = NONCE_SIZE + position;
+                let proof_start = mark_start + PROOF_SIZE;
+                if buf.len() >= proof_start + PROOF_SIZE {
+                    let mut nonce = [0u8; NONCE_SIZE];
+                    nonce.copy_from_slice(&buf[..NONCE_SIZE]);
+                    let padding = buf[NONCE_SIZE..mark_start].to_vec();
+                    let mut proof = [0u8; PROOF_SIZE];
+                    proof.copy_from_slice(&buf[proof_start..proof_start + PROOF_SIZE]);
+                    let rest = buf.split_off(proof_start + PROOF_SIZE);
+                    return Ok((nonce, padding, proof, rest));
+                }
+            } else if buf.len() >= limit {
+                return Err(PluggableTransportError::Unauthenticated);
+            }
+        }
+        let n = io.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Parsed HTTP request or response head
+struct HttpHead {
+    start_line: String,
+    headers: Vec<(String, String)>,
+}
+
+impl HttpHead {
+    /// Parse a complete head from the front of `buf`, returning its length
+    fn parse(buf: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") else {
+            if buf.len() > MAX_HTTP_HEAD {
+                return Err(invalid_data("HTTP head too large"));
+            }
+            return Ok(None);
+        };
+        let text = std::str::from_utf8(&buf[..end]).map_err(|_| invalid_data("non-UTF-8 head"))?;
+        let mut lines = text.split("\r\n");
+        let start_line = lines.next().unwrap_or_default().to_string();
+        let headers = lines
+            .map(|line| {
+                line.split_once(':')
+                    .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().into()))
+                    .ok_or_else(|| invalid_data("malformed header"))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Some((
+            Self {
+                start_line,
+                headers,
+            },
+            end + 4,
+        )))
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn content_length(&self) -> io::Result<usize> {
+        let length = match self.header("content-length") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| invalid_data("malformed Content-Length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid_data("HTTP body too large"));
+        }
+        Ok(length)
+    }
+}
+
+/// Read an HTTP head, returning it with any bytes read past it
+async fn read_http_head<S: AsyncRead + Unpin>(
+    io: &mut S,
+) -> Result<(HttpHead, Vec<u8>), PluggableTransportError> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        if let Some((head, len)) = HttpHead::parse(&buf)? {
+            return Ok((head, buf.split_off(len)));
+        }
+        let n = io.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// One direction of a disguised connection's encryption
+struct RecordCipher {
+    cipher: ChaCha20Poly1305,
+    /// Keys the obfs4 length masks
+    mask_key: [u8; 32],
+    counter: u64,
+}
+
+impl RecordCipher {
+    fn new(okm: &[u8; 64]) -> Self {
+        let mut mask_key = [0u8; 32];
+        mask_key.copy_from_slice(&okm[32..]);
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&okm[..32])),
+            mask_key,
+            counter: 0,
+        }
+    }
+
+    fn nonce(&self) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.counter.to_le_bytes());
+        nonce
+    }
+
+    fn advance(&mut self) -> io::Result<()> {
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("nonce space exhausted"))?;
+        Ok(())
+    }
+
+    /// Mask hiding the length of the next frame
+    fn length_mask(&self) -> u16 {
+        let hash = blake3::keyed_hash(&self.mask_key, &self.counter.to_le_bytes());
+        u16::from_be_bytes([hash.as_bytes()[0], hash.as_bytes()[1]])
+    }
+
+    fn seal(&mut self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&self.nonce()), plaintext)
+            .map_err(|_| io::Error::other("record encryption failed"))?;
+        self.advance()?;
+        Ok(sealed)
+    }
+
+    fn open(&mut self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&self.nonce()), sealed)
+            .map_err(|_| invalid_data("record failed to decrypt"))?;
+        self.advance()?;
+        Ok(plaintext)
+    }
+}
+
+/// Long-poll bookkeeping of an HTTP connection
+struct HttpState {
+    dialer: bool,
+    host: String,
+    path: String,
+    /// Dialer: requests awaiting a response. Listener: requests held for an
+    /// answer.
+    in_flight: usize,
+}
+
+/// Wire format of a disguised connection
+enum Framing {
+    Http(HttpState),
+    WebSocket { dialer: bool },
+    Obfs4 { max_padding: usize },
+}
+
+/// Connection carried by a pluggable transport
+pub struct DisguisedStream<S> {
+    io: S,
+    framing: Framing,
+    send: RecordCipher,
+    recv: RecordCipher,
+    /// Bytes read but not yet decoded
+    read_buf: Vec<u8>,
+    read_eof: bool,
+    /// The peer closed its side (WebSocket close frame)
+    remote_closed: bool,
+    close_sent: bool,
+    /// An HTTP end-of-stream record waits for a request to answer
+    fin_pending: bool,
+    /// Decoded bytes not yet returned to the reader
+    plaintext: Vec<u8>,
+    plaintext_read: usize,
+    /// Plaintext waiting to be sealed into a record
+    send_plaintext: Vec<u8>,
+    /// Encoded bytes not yet written
+    write_buf: Vec<u8>,
+    write_buf_written: usize,
+}
+
+impl<S> DisguisedStream<S> {
+    fn new(
+        io: S,
+        framing: Framing,
+        send: RecordCipher,
+        recv: RecordCipher,
+        read_buf: Vec<u8>,
+    ) -> Self {
+        Self {
+            io,
+            framing,
+            send,
+            recv,
+            read_buf,
+            read_eof: false,
+            remote_closed: false,
+            close_sent: false,
+            fin_pending: false,
+            plaintext: Vec::new(),
+            plaintext_read: 0,
+            send_plaintext: Vec::new(),
+            write_buf: Vec::new(),
+            write_buf_written: 0,
+        }
+    }
+
+    /// Underlying connection
+    pub fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    /// Queue an empty long-poll request
+    fn queue_poll(&mut self) {
+        if let Framing::Http(state) = &mut self.framing {
+            state.in_flight += 1;
+            let request = http_request(&state.host, &state.path, &[]);
+            self.write_buf.extend_from_slice(&request);
+        }
+    }
+
+    /// Whether a record can be sent now
+    fn can_send(&self) -> bool {
+        match &self.framing {
+            Framing::Http(state) => state.dialer || state.in_flight > 0,
+            _ => true,
+        }
+    }
+
+    /// Seal up to one record of buffered plaintext into `write_buf`
+    ///
+    /// Over HTTP an empty record marks the end of the stream, as the client
+    /// cannot half-close a connection it still polls on.
+    fn encode_record(&mut self) -> io::Result<()> {
+        let len = self.send_plaintext.len().min(MAX_RECORD);
+        let chunk: Vec<u8> = self.send_plaintext.drain(..len).collect();
+        if chunk.is_empty() {
+            self.fin_pending = false;
+        }
+        match &mut self.framing {
+            Framing::Obfs4 { max_padding } => {
+                let mut inner = Vec::with_capacity(2 + chunk.len() + *max_padding);
+                inner.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+                inner.extend_from_slice(&chunk);
+                inner.resize(inner.len() + thread_rng().gen_range(0..=*max_padding), 0);
+                let mask = self.send.length_mask();
+                let sealed = self.send.seal(&inner)?;
+                self.write_buf
+                    .extend_from_slice(&(sealed.len() as u16 ^ mask).to_be_bytes());
+                self.write_buf.extend_from_slice(&sealed);
+            }
+            Framing::WebSocket { dialer } => {
+                let sealed = self.send.seal(&chunk)?;
+                websocket_frame(&mut self.write_buf, WS_OPCODE_BINARY, &sealed, *dialer);
+            }
+            Framing::Http(state) => {
+                let sealed = self.send.seal(&chunk)?;
+                if state.dialer {
+                    self.write_buf.extend_from_slice(&http_request(
+                        &state.host,
+                        &state.path,
+                        &sealed,
+                    ));
+                } else {
+                    self.write_buf.extend_from_slice(&http_response(&sealed));
+                }
+                if state.dialer {
+                    state.in_flight += 1;
+                } else {
+                    state.in_flight -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode one unit from `read_buf`, returning whether it made progress
+    fn decode(&mut self) -> io::Result<bool> {
+        match &mut self.framing {
+            Framing::Obfs4 { .. } => {
+                if self.read_buf.len() < 2 {
+                    return Ok(false);
+                }
+                let len = (u16::from_be_bytes([self.read_buf[0], self.read_buf[1]])
+                    ^ self.recv.length_mask()) as usize;
+                if self.read_buf.len() < 2 + len {
+                    return Ok(false);
+                }
+                let inner = self.recv.open(&self.read_buf[2..2 + len])?;
+                self.read_buf.drain(..2 + len);
+                if inner.len() < 2 {
+                    return Err(invalid_data("short frame"));
+                }
+                let payload_len = u16::from_be_bytes([inner[0], inner[1]]) as usize;
+                let payload = inner
+                    .get(2..2 + payload_len)
+                    .ok_or_else(|| invalid_data("frame payload exceeds frame"))?;
+                self.plaintext.extend_from_slice(payload);
+                Ok(true)
+            }
+            Framing::WebSocket { dialer } => {
+                let dialer = *dialer;
+                let Some((opcode, payload, len)) = parse_websocket_frame(&self.read_buf, !dialer)?
+                else {
+                    return Ok(false);
+                };
+                self.read_buf.drain(..len);
+                match opcode {
+                    WS_OPCODE_BINARY => {
+                        let plaintext = self.recv.open(&payload)?;
+                        self.plaintext.extend_from_slice(&plaintext);
+                    }
+                    WS_OPCODE_PING => {
+                        websocket_frame(&mut self.write_buf, WS_OPCODE_PONG, &payload, dialer)
+                    }
+                    WS_OPCODE_PONG => {}
+                    // Answered when this side closes, after its remaining data
+                    WS_OPCODE_CLOSE => self.remote_closed = true,
+                    _ => return Err(invalid_data("unexpected WebSocket opcode")),
+                }
+                Ok(true)
+            }
+            Framing::Http(state) => {
+                let Some((head, head_len)) = HttpHead::parse(&self.read_buf)? else {
+                    return Ok(false);
+                };
+                let body_len = head.content_length()?;
+                if self.read_buf.len() < head_len + body_len {
+                    return Ok(false);
+                }
+                let mut start = head.start_line.split(' ');
+                let expected = if state.dialer {
+                    start.next() == Some("HTTP/1.1") && start.next() == Some("200")
+                } else {
+                    start.next() == Some("POST") && start.next() == Some(state.path.as_str())
+                };
+                if !expected {
+                    return Err(invalid_data("unexpected HTTP message"));
+                }
+                if body_len > 0 {
+                    let plaintext = self
+                        .recv
+                        .open(&self.read_buf[head_len..head_len + body_len])?;
+                    self.remote_closed |= plaintext.is_empty();
+                    self.plaintext.extend_from_slice(&plaintext);
+                }
+                self.read_buf.drain(..head_len + body_len);
+
+                if state.dialer {
+                    state.in_flight = state
+                        .in_flight
+                        .checked_sub(1)
+                        .ok_or_else(|| invalid_data("unsolicited HTTP response"))?;
+                    if state.in_flight == 0 && !self.remote_closed {
+                        state.in_flight += 1;
+                        let request = http_request(&state.host, &state.path, &[]);
+                        self.write_buf.extend_from_slice(&request);
+                    }
+                } else {
+                    state.in_flight += 1;
+                    // Hold the newest request; answer older ones empty
+                    while state.in_flight > 1 {
+                        state.in_flight -= 1;
+                        self.write_buf.extend_from_slice(&http_response(&[]));
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> DisguisedStream<S> {
+    /// Read more bytes from the underlying stream into `read_buf`
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut chunk = [0u8; 8 * 1024];
+        let n = ready!(Pin::new(&mut self.io).poll_read(cx, &mut chunk))?;
+        if n == 0 {
+            self.read_eof = true;
+        }
+        self.read_buf.extend_from_slice(&chunk[..n]);
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write out every encoded byte
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.write_buf_written < self.write_buf.len() {
+            let n =
+                ready!(Pin::new(&mut self.io)
+                    .poll_write(cx, &self.write_buf[self.write_buf_written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_buf_written += n;
+        }
+        self.write_buf.clear();
+        self.write_buf_written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Encode buffered plaintext and write out every pending byte
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            ready!(self.poll_write_buf(cx))?;
+            if self.send_plaintext.is_empty() && !self.fin_pending {
+                return Poll::Ready(Ok(()));
+            }
+            if self.can_send() {
+                self.encode_record()?;
+                continue;
+            }
+            // A long-poll server can only answer a request
+            if self.decode()? {
+                continue;
+            }
+            if self.read_eof {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            ready!(self.poll_fill(cx))?;
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for DisguisedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            // Polls, pongs and empty answers queued while decoding
+            if let Poll::Ready(Err(e)) = this.poll_write_buf(cx) {
+                return Poll::Ready(Err(e));
+            }
+            let available = &this.plaintext[this.plaintext_read..];
+            if !available.is_empty() {
+                let n = available.len().min(buf.len());
+                buf[..n].copy_from_slice(&available[..n]);
+                this.plaintext_read += n;
+                if this.plaintext_read == this.plaintext.len() {
+                    this.plaintext.clear();
+                    this.plaintext_read = 0;
+                }
+                return Poll::Ready(Ok(n));
+            }
+            if this.remote_closed {
+                return Poll::Ready(Ok(0));
+            }
+            if this.decode()? {
+                continue;
+            }
+            if this.read_eof {
+                if this.read_buf.is_empty() {
+                    return Poll::Ready(Ok(0));
+                }
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            ready!(this.poll_fill(cx))?;
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for DisguisedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.send_plaintext.len() >= MAX_RECORD {
+            ready!(this.poll_drain(cx))?;
+        }
+        let n = buf.len().min(MAX_RECORD - this.send_plaintext.len());
+        this.send_plaintext.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if matches!(this.framing, Framing::Http(_)) && !this.close_sent {
+            this.close_sent = true;
+            this.fin_pending = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        if let Framing::WebSocket { dialer } = this.framing {
+            if !this.close_sent {
+                this.close_sent = true;
+                websocket_frame(&mut this.write_buf, WS_OPCODE_CLOSE, &[], dialer);
+                ready!(this.poll_write_buf(cx))?;
+            }
+        }
+        ready!(Pin::new(&mut this.io).poll_flush(cx))?;
+        // A long-poll client keeps polling for the rest of the server's data
+        if let Framing::Http(HttpState { dialer: true, .. }) = this.framing {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.io).poll_close(cx)
+    }
+}
+
+fn http_request(host: &str, path: &str, body: &[u8]) -> Vec<u8> {
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/octet-stream\r\n\
+         Content-Length: {}\r\n\r\n",
+        path,
+        host,
+        body.len()
+    )
+    .into_bytes();
+    request.extend_from_slice(body);
+    request
+}
+
+fn http_response(body: &[u8]) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n\
+         Cache-Control: no-store\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+/// Append a single-fragment WebSocket frame; clients mask theirs
+fn websocket_frame(out: &mut Vec<u8>, opcode: u8, payload: &[u8], masked: bool) {
+    out.push(0x80 | opcode);
+    let mask_bit = if masked { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => out.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    if masked {
+        let mask: [u8; 4] = thread_rng().gen();
+        out.extend_from_slice(&mask);
+        out.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    } else {
+        out.extend_from_slice(payload);
+    }
+}
+
+/// Parse one WebSocket frame from the front of `buf`
+///
+/// Returns the opcode, unmasked payload and frame length.
+fn parse_websocket_frame(
+    buf: &[u8],
+    expect_masked: bool,
+) -> io::Result<Option<(u8, Vec<u8>, usize)>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x80 == 0 || buf[0] & 0x70 != 0 {
+        return Err(invalid_data("fragmented or extended WebSocket frame"));
+    }
+    let opcode = buf[0] & 0x0f;
+    if (buf[1] & 0x80 != 0) != expect_masked {
+        return Err(invalid_data("wrong WebSocket masking"));
+    }
+    let (len, mut offset) = match buf[1] & 0x7f {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as usize, 4),
+        127 if buf.len() >= 10 => {
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&buf[2..10]);
+            let len = usize::try_from(u64::from_be_bytes(len))
+                .map_err(|_| invalid_data("WebSocket frame too large"))?;
+            (len, 10)
+        }
+        126 | 127 => return Ok(None),
+        len => (len as usize, 2),
+    };
+    if len > MAX_BODY {
+        return Err(invalid_data("WebSocket frame too large"));
+    }
+    let mut mask = None;
+    if expect_masked {
+        if buf.len() < offset + 4 {
+            return Ok(None);
+        }
+        mask = Some([
+            buf[offset],
+            buf[offset + 1],
+            buf[offset + 2],
+            buf[offset + 3],
+        ]);
+        offset += 4;
+    }
+    if buf.len() < offset + len {
+        return Ok(None);
+    }
+    let mut payload = buf[offset..offset + len].to_vec();
+    if let Some(mask) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok(Some((opcode, payload, offset + len)))
+}
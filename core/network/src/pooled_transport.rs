@@ -0,0 +1,103 @@
+//! Connection pool layer of the libp2p transport.
+//!
+//! [`crate::p2p`] builds one transport: the base transports, the
+//! `/qudag/pq-noise` security upgrade (ML-KEM key exchange and
+//! [`crate::transport::SecureFrame`] records), yamux, and this layer on top.
+//! Every connection handed to the swarm is registered with a
+//! [`ConnectionManager`] until its muxer is dropped, so the manager's pool,
+//! quality scores and circuit breakers describe the node's real connections.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use libp2p::core::muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent, SubstreamBox};
+use libp2p::core::transport::{Boxed, Transport};
+use libp2p::core::ConnectedPoint;
+use libp2p::PeerId;
+
+use crate::connection::ConnectionManager;
+use crate::types::PeerId as PoolPeerId;
+
+/// Register the connections of `transport` with `manager`
+pub fn with_connection_pool<T>(
+    transport: T,
+    manager: Arc<ConnectionManager>,
+) -> Boxed<(PeerId, StreamMuxerBox)>
+where
+    T: Transport<Output = (PeerId, StreamMuxerBox)> + Send + Unpin + 'static,
+    T::Error: Send + Sync + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    transport
+        .map(move |(peer_id, muxer), endpoint| {
+            let muxer = PooledMuxer::new(manager.clone(), &peer_id, &endpoint, muxer);
+            (peer_id, StreamMuxerBox::new(muxer))
+        })
+        .boxed()
+}
+
+/// Muxer of a connection registered with a [`ConnectionManager`]
+///
+/// Releases the connection when dropped, which the swarm does once the
+/// connection is closed.
+struct PooledMuxer {
+    inner: StreamMuxerBox,
+    manager: Arc<ConnectionManager>,
+    peer_id: PoolPeerId,
+}
+
+impl PooledMuxer {
+    fn new(
+        manager: Arc<ConnectionManager>,
+        peer_id: &PeerId,
+        endpoint: &ConnectedPoint,
+        inner: StreamMuxerBox,
+    ) -> Self {
+        let peer_id = PoolPeerId::from(peer_id);
+        manager.connection_opened(peer_id, endpoint.get_remote_address().to_string());
+        Self {
+            inner,
+            manager,
+            peer_id,
+        }
+    }
+}
+
+impl Drop for PooledMuxer {
+    fn drop(&mut self) {
+        self.manager.connection_closed(&self.peer_id);
+    }
+}
+
+impl StreamMuxer for PooledMuxer {
+    type Substream = SubstreamBox;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        Pin::new(&mut self.inner).poll_inbound(cx)
+    }
+
+    fn poll_outbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        Pin::new(&mut self.inner).poll_outbound(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
@@ -0,0 +1,750 @@
+//! Port mapping protocols spoken to home gateways.
+//!
+//! * UPnP IGD: a gateway answers an SSDP `M-SEARCH` with the location of its
+//!   device description. The description names the control URL of its
+//!   `WANIPConnection` or `WANPPPConnection` service, which takes SOAP calls
+//!   such as `AddPortMapping`. See [`ssdp_search`] and [`IgdGateway`].
+//! * NAT-PMP (RFC 6886) and PCP (RFC 6887) are UDP request-response
+//!   protocols on port [`NAT_PMP_PORT`] of the gateway. [`PmpGateway`] tries
+//!   PCP first; gateways that only speak NAT-PMP answer a PCP request with an
+//!   unsupported version result.
+//!
+//! Mappings made here are kept, renewed and removed on shutdown by
+//! [`crate::nat_traversal::UpnpManager`] and
+//! [`crate::nat_traversal::NatPmpClient`].
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::Url;
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::nat_traversal::PortMappingProtocol;
+
+/// Multicast address SSDP searches are sent to
+pub const SSDP_MULTICAST: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+
+/// Gateway port of NAT-PMP and PCP
+pub const NAT_PMP_PORT: u16 = 5351;
+
+/// Device type searched for over SSDP
+const IGD_DEVICE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+
+/// Services able to map ports, in order of preference
+const WAN_SERVICES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// UPnP error code of gateways refusing leases other than permanent ones
+const ONLY_PERMANENT_LEASES: u16 = 725;
+
+/// How long SSDP keeps listening once a gateway answered
+const SSDP_GRACE: Duration = Duration::from_millis(250);
+
+/// Timeout of SOAP calls
+const SOAP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First NAT-PMP retransmission delay, doubled on each retry (RFC 6886 3.1)
+const PMP_INITIAL_RETRY: Duration = Duration::from_millis(250);
+
+/// NAT-PMP result code of an unsupported version
+const NAT_PMP_UNSUPPORTED_VERSION: u16 = 1;
+
+/// PCP result codes of a request the gateway cannot parse in this version
+const PCP_UNSUPP_VERSION: u8 = 1;
+
+/// Errors of the port mapping protocols
+#[derive(Debug, Error)]
+pub enum PortMappingError {
+    /// Socket error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// HTTP request to the gateway failed
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The gateway answered with an unexpected HTTP status
+    #[error("Gateway answered HTTP {0}")]
+    Status(u16),
+
+    /// The gateway did not answer
+    #[error("Gateway did not answer")]
+    Timeout,
+
+    /// No gateway answered discovery
+    #[error("No gateway found")]
+    NoGateway,
+
+    /// The gateway's answer could not be parsed
+    #[error("Malformed gateway answer: {0}")]
+    Malformed(&'static str),
+
+    /// A SOAP call failed
+    #[error("UPnP error {code}: {description}")]
+    Upnp {
+        /// UPnP error code
+        code: u16,
+        /// Error description given by the gateway
+        description: String,
+    },
+
+    /// A NAT-PMP request failed
+    #[error("NAT-PMP result code {0}")]
+    NatPmp(u16),
+
+    /// A PCP request failed
+    #[error("PCP result code {0}")]
+    Pcp(u8),
+}
+
+/// Search for Internet gateway devices and return their description URLs
+///
+/// Listens for `wait` at most, and for a short grace period once the first
+/// gateway answered.
+pub async fn ssdp_search(
+    target: SocketAddr,
+    wait: Duration,
+) -> Result<Vec<String>, PortMappingError> {
+    let socket = UdpSocket::bind(unspecified(&target.ip())).await?;
+    let request = format!(
+        "M-SEARCH * HTTP/1.1\r\n\
+         HOST: {}\r\n\
+         ST: {}\r\n\
+         MAN: \"ssdp:discover\"\r\n\
+         MX: {}\r\n\r\n",
+        target,
+        IGD_DEVICE,
+        wait.as_secs().clamp(1, 5)
+    );
+    socket.send_to(request.as_bytes(), target).await?;
+
+    let mut locations = Vec::new();
+    let mut deadline = Instant::now() + wait;
+    let mut buf = [0u8; 2048];
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received?;
+        let Ok(answer) = std::str::from_utf8(&buf[..len]) else {
+            continue;
+        };
+        let Some(location) = header(answer, "location") else {
+            debug!("SSDP answer from {} without a location", from);
+            continue;
+        };
+        if !locations.iter().any(|known| known == location) {
+            locations.push(location.to_string());
+        }
+        deadline = deadline.min(Instant::now() + SSDP_GRACE);
+    }
+    if locations.is_empty() {
+        return Err(PortMappingError::NoGateway);
+    }
+    Ok(locations)
+}
+
+/// An Internet gateway device reached through its SOAP control URL
+#[derive(Debug, Clone)]
+pub struct IgdGateway {
+    /// Name the device gives itself
+    friendly_name: String,
+    /// Connection service to call
+    service_type: String,
+    /// Where SOAP calls are posted
+    control_url: Url,
+    /// Address of this host on the gateway's network
+    local_ip: IpAddr,
+    http: reqwest::Client,
+}
+
+impl IgdGateway {
+    /// Fetch a device description and find its connection service
+    pub async fn from_location(location: &str) -> Result<Self, PortMappingError> {
+        let location = Url::parse(location).map_err(|_| PortMappingError::Malformed("location"))?;
+        let http = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(SOAP_TIMEOUT)
+            .build()?;
+        let response = http.get(location.clone()).send().await?;
+        if !response.status().is_success() {
+            return Err(PortMappingError::Status(response.status().as_u16()));
+        }
+        let description = response.text().await?;
+
+        let (service_type, control) = WAN_SERVICES
+            .iter()
+            .find_map(|wanted| {
+                elements(&description, "service")
+                    .into_iter()
+                    .find_map(|service| {
+                        let service_type = element(service, "serviceType")?;
+                        (service_type == *wanted)
+                            .then(|| (service_type, element(service, "controlURL")))
+                    })
+            })
+            .ok_or(PortMappingError::Malformed("no connection service"))?;
+        let control = control.ok_or(PortMappingError::Malformed("no control URL"))?;
+        let base = match element(&description, "URLBase").filter(|base| !base.is_empty()) {
+            Some(base) => Url::parse(&base).map_err(|_| PortMappingError::Malformed("URL base"))?,
+            None => location,
+        };
+        let control_url = base
+            .join(&control)
+            .map_err(|_| PortMappingError::Malformed("control URL"))?;
+        let host = control_url
+            .host_str()
+            .ok_or(PortMappingError::Malformed("control URL"))?
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_string();
+        let port = control_url.port_or_known_default().unwrap_or(80);
+        let local_ip = local_ip_towards(&host, port).await?;
+
+        Ok(Self {
+            friendly_name: element(&description, "friendlyName").unwrap_or_default(),
+            service_type,
+            control_url,
+            local_ip,
+            http,
+        })
+    }
+
+    /// Name the device gives itself
+    pub fn friendly_name(&self) -> &str {
+        &self.friendly_name
+    }
+
+    /// Where SOAP calls are posted
+    pub fn control_url(&self) -> &Url {
+        &self.control_url
+    }
+
+    /// Address of this host on the gateway's network
+    pub fn local_ip(&self) -> IpAddr {
+        self.local_ip
+    }
+
+    /// Address of the gateway on the Internet
+    pub async fn external_ip(&self) -> Result<IpAddr, PortMappingError> {
+        let answer = self.call("GetExternalIPAddress", &[]).await?;
+        element(&answer, "NewExternalIPAddress")
+            .and_then(|ip| ip.parse().ok())
+            .ok_or(PortMappingError::Malformed("external address"))
+    }
+
+    /// Forward an external port to a port of this host
+    ///
+    /// Returns the lease granted, zero for a permanent mapping on gateways
+    /// that refuse others.
+    pub async fn add_port_mapping(
+        &self,
+        protocol: PortMappingProtocol,
+        external_port: u16,
+        internal_port: u16,
+        description: &str,
+        lease: Duration,
+    ) -> Result<Duration, PortMappingError> {
+        match self
+            .add(protocol, external_port, internal_port, description, lease)
+            .await
+        {
+            Err(PortMappingError::Upnp { code, .. })
+                if code == ONLY_PERMANENT_LEASES && !lease.is_zero() =>
+            {
+                self.add(
+                    protocol,
+                    external_port,
+                    internal_port,
+                    description,
+                    Duration::ZERO,
+                )
+                .await?;
+                Ok(Duration::ZERO)
+            }
+            result => result.map(|()| lease),
+        }
+    }
+
+    async fn add(
+        &self,
+        protocol: PortMappingProtocol,
+        external_port: u16,
+        internal_port: u16,
+        description: &str,
+        lease: Duration,
+    ) -> Result<(), PortMappingError> {
+        self.call(
+            "AddPortMapping",
+            &[
+                ("NewRemoteHost", String::new()),
+                ("NewExternalPort", external_port.to_string()),
+                ("NewProtocol", protocol_name(protocol).to_string()),
+                ("NewInternalPort", internal_port.to_string()),
+                ("NewInternalClient", self.local_ip.to_string()),
+                ("NewEnabled", "1".to_string()),
+                ("NewPortMappingDescription", description.to_string()),
+                ("NewLeaseDuration", lease.as_secs().to_string()),
+            ],
+        )
+        .await
+        .map(drop)
+    }
+
+    /// Remove the forwarding of an external port
+    pub async fn delete_port_mapping(
+        &self,
+        protocol: PortMappingProtocol,
+        external_port: u16,
+    ) -> Result<(), PortMappingError> {
+        self.call(
+            "DeletePortMapping",
+            &[
+                ("NewRemoteHost", String::new()),
+                ("NewExternalPort", external_port.to_string()),
+                ("NewProtocol", protocol_name(protocol).to_string()),
+            ],
+        )
+        .await
+        .map(drop)
+    }
+
+    /// Post a SOAP action and return the answer
+    async fn call(
+        &self,
+        action: &str,
+        args: &[(&str, String)],
+    ) -> Result<String, PortMappingError> {
+        let mut body = format!(
+            "<?xml version=\"1.0\"?>\r\n\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{} xmlns:u=\"{}\">",
+            action, self.service_type
+        );
+        for (name, value) in args {
+            body.push_str(&format!("<{}>{}</{}>", name, escape(value), name));
+        }
+        body.push_str(&format!("</u:{}></s:Body></s:Envelope>\r\n", action));
+
+        let response = self
+            .http
+            .post(self.control_url.clone())
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header(
+                "SOAPAction",
+                format!("\"{}#{}\"", self.service_type, action),
+            )
+            .body(body)
+            .send()
+            .await?;
+        let status = response.status();
+        let answer = response.text().await?;
+        if status.is_success() {
+            return Ok(answer);
+        }
+        match element(&answer, "errorCode").and_then(|code| code.parse().ok()) {
+            Some(code) => Err(PortMappingError::Upnp {
+                code,
+                description: element(&answer, "errorDescription").unwrap_or_default(),
+            }),
+            None => Err(PortMappingError::Status(status.as_u16())),
+        }
+    }
+}
+
+/// Protocol a NAT-PMP gateway speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmpVersion {
+    /// NAT-PMP, RFC 6886
+    NatPmp,
+    /// Port Control Protocol, RFC 6887
+    Pcp,
+}
+
+/// A mapping granted by a NAT-PMP or PCP gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PmpGrant {
+    /// External port assigned
+    pub external_port: u16,
+    /// External address assigned, only reported by PCP
+    pub external_ip: Option<IpAddr>,
+    /// Lifetime granted
+    pub lifetime: Duration,
+}
+
+/// A gateway speaking NAT-PMP or PCP
+#[derive(Debug, Clone)]
+pub struct PmpGateway {
+    address: SocketAddr,
+    version: PmpVersion,
+    /// Address of this host on the gateway's network
+    local_ip: IpAddr,
+    /// Requests sent before giving up
+    attempts: u32,
+}
+
+impl PmpGateway {
+    /// Find out which protocol a gateway speaks, if any
+    ///
+    /// Each request is sent `attempts` times, the delay doubling from 250ms.
+    pub async fn probe(address: SocketAddr, attempts: u32) -> Result<Self, PortMappingError> {
+        let socket = UdpSocket::bind(unspecified(&address.ip())).await?;
+        socket.connect(address).await?;
+        let mut gateway = Self {
+            address,
+            version: PmpVersion::Pcp,
+            local_ip: socket.local_addr()?.ip(),
+            attempts,
+        };
+        drop(socket);
+
+        // A PCP ANNOUNCE is answered by PCP gateways, and refused with an
+        // unsupported version by NAT-PMP ones
+        let announce = gateway.pcp_header(0, 0);
+        match gateway
+            .exchange(&announce, |answer| {
+                (answer.first() == Some(&2) && answer.get(1) == Some(&0x80))
+                    || answer.first() == Some(&0)
+            })
+            .await
+        {
+            Ok(answer) if answer[0] == 2 && answer.get(3) == Some(&0) => return Ok(gateway),
+            Ok(answer) if answer[0] == 2 => {
+                return Err(PortMappingError::Pcp(answer.get(3).copied().unwrap_or(0)))
+            }
+            Ok(_) | Err(PortMappingError::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+        gateway.version = PmpVersion::NatPmp;
+        gateway.external_ip().await?;
+        Ok(gateway)
+    }
+
+    /// Gateway address
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Protocol the gateway speaks
+    pub fn version(&self) -> PmpVersion {
+        self.version
+    }
+
+    /// Address of the gateway on the Internet
+    ///
+    /// PCP has no request for it, so this asks in NAT-PMP, which PCP
+    /// gateways keep answering.
+    pub async fn external_ip(&self) -> Result<IpAddr, PortMappingError> {
+        let answer = self
+            .exchange(&[0, 0], |answer| {
+                answer.len() >= 4 && answer[..2] == [0, 128]
+            })
+            .await?;
+        let result = u16::from_be_bytes([answer[2], answer[3]]);
+        if result != 0 {
+            return Err(PortMappingError::NatPmp(result));
+        }
+        let ip: [u8; 4] = answer
+            .get(8..12)
+            .and_then(|ip| ip.try_into().ok())
+            .ok_or(PortMappingError::Malformed("external address"))?;
+        Ok(IpAddr::V4(Ipv4Addr::from(ip)))
+    }
+
+    /// Map a port of this host, or renew the mapping of the same nonce
+    ///
+    /// The nonce identifies the mapping to PCP gateways and is ignored by
+    /// NAT-PMP ones.
+    pub async fn map(
+        &self,
+        protocol: PortMappingProtocol,
+        internal_port: u16,
+        external_port: u16,
+        lifetime: Duration,
+        nonce: [u8; 12],
+    ) -> Result<PmpGrant, PortMappingError> {
+        let lifetime = lifetime.as_secs().min(u32::MAX as u64) as u32;
+        match self.version {
+            PmpVersion::NatPmp => {
+                self.nat_pmp_map(protocol, internal_port, external_port, lifetime)
+                    .await
+            }
+            PmpVersion::Pcp => {
+                self.pcp_map(protocol, internal_port, external_port, lifetime, nonce)
+                    .await
+            }
+        }
+    }
+
+    /// Remove the mapping of a port of this host
+    pub async fn unmap(
+        &self,
+        protocol: PortMappingProtocol,
+        internal_port: u16,
+        nonce: [u8; 12],
+    ) -> Result<(), PortMappingError> {
+        self.map(protocol, internal_port, 0, Duration::ZERO, nonce)
+            .await
+            .map(drop)
+    }
+
+    async fn nat_pmp_map(
+        &self,
+        protocol: PortMappingProtocol,
+        internal_port: u16,
+        external_port: u16,
+        lifetime: u32,
+    ) -> Result<PmpGrant, PortMappingError> {
+        let opcode = match protocol {
+            PortMappingProtocol::UDP => 1,
+            PortMappingProtocol::TCP => 2,
+        };
+        let mut request = vec![0, opcode, 0, 0];
+        request.extend_from_slice(&internal_port.to_be_bytes());
+        request.extend_from_slice(&external_port.to_be_bytes());
+        request.extend_from_slice(&lifetime.to_be_bytes());
+
+        let answer = self
+            .exchange(&request, |answer| {
+                answer.len() >= 4
+                    && answer[..2] == [0, 128 + opcode]
+                    && (answer.len() < 10 || answer[8..10] == internal_port.to_be_bytes())
+            })
+            .await?;
+        let result = u16::from_be_bytes([answer[2], answer[3]]);
+        if result != 0 {
+            return Err(PortMappingError::NatPmp(result));
+        }
+        if answer.len() < 16 {
+            return Err(PortMappingError::Malformed("short NAT-PMP mapping"));
+        }
+        Ok(PmpGrant {
+            external_port: u16::from_be_bytes([answer[10], answer[11]]),
+            external_ip: None,
+            lifetime: Duration::from_secs(u32::from_be_bytes([
+                answer[12], answer[13], answer[14], answer[15],
+            ]) as u64),
+        })
+    }
+
+    async fn pcp_map(
+        &self,
+        protocol: PortMappingProtocol,
+        internal_port: u16,
+        external_port: u16,
+        lifetime: u32,
+        nonce: [u8; 12],
+    ) -> Result<PmpGrant, PortMappingError> {
+        let mut request = self.pcp_header(1, lifetime);
+        request.extend_from_slice(&nonce);
+        request.push(match protocol {
+            PortMappingProtocol::TCP => 6,
+            PortMappingProtocol::UDP => 17,
+        });
+        request.extend_from_slice(&[0; 3]);
+        request.extend_from_slice(&internal_port.to_be_bytes());
+        request.extend_from_slice(&external_port.to_be_bytes());
+        let any = match self.local_ip {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.to_ipv6_mapped(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED,
+        };
+        request.extend_from_slice(&any.octets());
+
+        let answer = self
+            .exchange(&request, |answer| {
+                answer.len() >= 4
+                    && answer[..2] == [2, 0x81]
+                    && (answer.len() < 36 || answer[24..36] == nonce)
+            })
+            .await?;
+        if answer[3] != 0 {
+            return Err(PortMappingError::Pcp(answer[3]));
+        }
+        if answer.len() < 60 {
+            return Err(PortMappingError::Malformed("short PCP mapping"));
+        }
+        let ip: [u8; 16] = answer[44..60].try_into().expect("length checked above");
+        let ip = Ipv6Addr::from(ip);
+        Ok(PmpGrant {
+            external_port: u16::from_be_bytes([answer[42], answer[43]]),
+            external_ip: Some(match ip.to_ipv4_mapped() {
+                Some(ip) => IpAddr::V4(ip),
+                None => IpAddr::V6(ip),
+            }),
+            lifetime: Duration::from_secs(u32::from_be_bytes([
+                answer[4], answer[5], answer[6], answer[7],
+            ]) as u64),
+        })
+    }
+
+    /// Common header of PCP requests
+    fn pcp_header(&self, opcode: u8, lifetime: u32) -> Vec<u8> {
+        let client = match self.local_ip {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        };
+        let mut header = vec![2, opcode, 0, 0];
+        header.extend_from_slice(&lifetime.to_be_bytes());
+        header.extend_from_slice(&client.octets());
+        header
+    }
+
+    /// Send a request until an answer it accepts arrives
+    ///
+    /// Answers with an unsupported version are accepted whatever their
+    /// opcode, so that the caller can fall back.
+    async fn exchange(
+        &self,
+        request: &[u8],
+        accept: impl Fn(&[u8]) -> bool,
+    ) -> Result<Vec<u8>, PortMappingError> {
+        let socket = UdpSocket::bind(unspecified(&self.address.ip())).await?;
+        socket.connect(self.address).await?;
+        let mut delay = PMP_INITIAL_RETRY;
+        let mut buf = [0u8; 1100];
+        for _ in 0..self.attempts {
+            socket.send(request).await?;
+            let deadline = Instant::now() + delay;
+            while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await
+            {
+                let answer = &buf[..received?];
+                if accept(answer) {
+                    return Ok(answer.to_vec());
+                }
+                if unsupported_version(answer) {
+                    return Err(match answer[0] {
+                        0 => PortMappingError::NatPmp(NAT_PMP_UNSUPPORTED_VERSION),
+                        _ => PortMappingError::Pcp(PCP_UNSUPP_VERSION),
+                    });
+                }
+            }
+            delay *= 2;
+        }
+        Err(PortMappingError::Timeout)
+    }
+}
+
+/// The gateway of this host's default IPv4 route, read from the kernel
+pub fn default_gateway() -> Option<Ipv4Addr> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        // The kernel prints the address as a host-order integer
+        Some(Ipv4Addr::from(gateway.to_ne_bytes())).filter(|gateway| !gateway.is_unspecified())
+    })
+}
+
+/// Whether an answer refuses the version of the request
+fn unsupported_version(answer: &[u8]) -> bool {
+    match answer {
+        [0, _, result_high, result_low, ..] => {
+            u16::from_be_bytes([*result_high, *result_low]) == NAT_PMP_UNSUPPORTED_VERSION
+        }
+        [2, _, _, result, ..] => *result == PCP_UNSUPP_VERSION,
+        _ => false,
+    }
+}
+
+/// Local address the system would use to reach a host
+async fn local_ip_towards(host: &str, port: u16) -> Result<IpAddr, PortMappingError> {
+    let remote = tokio::net::lookup_host((host, port))
+        .await?
+        .next()
+        .ok_or(PortMappingError::Malformed("gateway host"))?;
+    let socket = UdpSocket::bind(unspecified(&remote.ip())).await?;
+    socket.connect(remote).await?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Wildcard address of the family of `ip`, port zero
+fn unspecified(ip: &IpAddr) -> SocketAddr {
+    match ip {
+        IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    }
+}
+
+fn protocol_name(protocol: PortMappingProtocol) -> &'static str {
+    match protocol {
+        PortMappingProtocol::TCP => "TCP",
+        PortMappingProtocol::UDP => "UDP",
+    }
+}
+
+/// Value of an HTTP header, matched case-insensitively
+fn header<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+    message.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Text of the first element named `name`, whatever its namespace prefix
+fn element(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name)
+        .first()
+        .map(|content| unescape(content.trim()))
+}
+
+/// Contents of the elements named `name`, whatever their namespace prefix
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('/') || tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        let tag_name = tag.split_whitespace().next().unwrap_or_default();
+        if local_name(tag_name) != name || tag.ends_with('/') {
+            continue;
+        }
+        // Find the matching closing tag
+        let mut search = 0;
+        while let Some(close) = rest[search..].find("</") {
+            let close = search + close;
+            let Some(close_end) = rest[close..].find('>') else {
+                break;
+            };
+            if local_name(rest[close + 2..close + close_end].trim()) == name {
+                found.push(&rest[..close]);
+                rest = &rest[close + close_end + 1..];
+                break;
+            }
+            search = close + 2;
+        }
+    }
+    found
+}
+
+/// Element name without its namespace prefix
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
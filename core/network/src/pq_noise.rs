@@ -0,0 +1,788 @@
+//! Post-quantum libp2p security upgrade (`/qudag/pq-noise/2`).
+//!
+//! A Noise-style handshake that replaces libp2p's classical Noise/TLS upgrade:
+//!
+//! 1. The initiator sends an ephemeral X25519 key and an ephemeral ML-KEM-768
+//!    encapsulation key.
+//! 2. The responder answers with its own X25519 key, an ML-KEM ciphertext and
+//!    its identity, encrypted under keys derived from both shared secrets.
+//! 3. The initiator sends its identity under the same keys.
+//!
+//! An identity carries the libp2p public key (which fixes the `PeerId`), an
+//! ML-DSA-65 public key bound to it by a libp2p signature, and an ML-DSA
+//! signature over the handshake transcript. Session keys are only
+//! recoverable by breaking both X25519 and ML-KEM, and peers are authenticated
+//! with ML-DSA.
+//!
+//! After the handshake the connection carries [`SecureFrame`]s: the payload
+//! is ChaCha20-Poly1305 ciphertext under per-direction keys, the frame's
+//! length, type and sequence number are authenticated with it, and the
+//! sequence number is the nonce counter, so frames cannot be replayed or
+//! reordered. Version 1 of the protocol used bare `u16` length prefixes.
+
+use std::io;
+use std::iter;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use chacha20poly1305::aead::{Aead, AeadInPlace, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use futures::future::{self, BoxFuture};
+use futures::prelude::*;
+use futures::ready;
+use hkdf::Hkdf;
+use libp2p::core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade, UpgradeInfo};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use qudag_crypto::key_certificate::{KeyCertificateError, RevocationList};
+use qudag_crypto::ml_dsa::{
+    MlDsaKeyPair, MlDsaPublicKey, ML_DSA_PUBLIC_KEY_SIZE, ML_DSA_SIGNATURE_SIZE,
+};
+use qudag_crypto::{kem, MlKem768};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::debug;
+use zeroize::Zeroizing;
+
+use crate::transport::SecureFrame;
+
+/// Protocol name negotiated through multistream-select
+pub const PROTOCOL_NAME: &str = "/qudag/pq-noise/2";
+
+/// Handshake version carried in the first message
+pub const HANDSHAKE_VERSION: u8 = 1;
+
+/// Largest handshake message accepted from a peer
+const MAX_HANDSHAKE_MESSAGE: usize = 64 * 1024;
+
+/// Largest plaintext carried in one frame, sent or accepted
+const MAX_FRAME_PLAINTEXT: usize = 64 * 1024;
+
+const X25519_KEY_SIZE: usize = 32;
+
+/// Prefix of the message the libp2p key signs to vouch for an ML-DSA key
+const BINDING_PREFIX: &[u8] = b"qudag-pq-noise-ml-dsa:";
+
+const INITIATOR_ROLE: &[u8] = b"initiator";
+const RESPONDER_ROLE: &[u8] = b"responder";
+
+/// Errors that can occur during the post-quantum handshake
+#[derive(Debug, Error)]
+pub enum PqNoiseError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unsupported handshake version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Malformed handshake message: {0}")]
+    Malformed(&'static str),
+    #[error("Key exchange failed: {0}")]
+    KeyExchange(String),
+    #[error("Handshake message failed to decrypt")]
+    Decryption,
+    #[error("Invalid peer identity: {0}")]
+    InvalidIdentity(&'static str),
+    #[error("Peer ML-DSA key rejected: {0}")]
+    KeyRejected(#[from] KeyCertificateError),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+}
+
+/// Security upgrade selected by [`crate::p2p::NetworkConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SecurityProtocol {
+    /// Classical libp2p Noise (X25519) only
+    Noise,
+    /// `/qudag/pq-noise/2` only; classical peers are refused
+    #[default]
+    PqNoise,
+    /// Prefer `/qudag/pq-noise/2`, accept classical Noise from older peers
+    ///
+    /// An active attacker can strip the post-quantum option during
+    /// negotiation, so this mode only suits migrations.
+    PqNoiseWithFallback,
+}
+
+/// Configuration of the `/qudag/pq-noise/2` upgrade
+#[derive(Clone)]
+pub struct PqNoiseConfig {
+    identity: Keypair,
+    ml_dsa: Arc<MlDsaKeyPair>,
+    revocations: Option<RevocationList>,
+}
+
+impl PqNoiseConfig {
+    /// Authenticate as `identity`, vouching for the ML-DSA key `ml_dsa`
+    pub fn new(identity: &Keypair, ml_dsa: Arc<MlDsaKeyPair>) -> Self {
+        Self {
+            identity: identity.clone(),
+            ml_dsa,
+            revocations: None,
+        }
+    }
+
+    /// Reject peers whose ML-DSA key has been rotated away or revoked
+    pub fn with_revocation_list(mut self, revocations: RevocationList) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// Identity section sent to the peer
+    fn identity_payload(&self, transcript: &[u8], role: &[u8]) -> Result<Vec<u8>, PqNoiseError> {
+        let libp2p_key = self.identity.public().encode_protobuf();
+        let ml_dsa_key = self.ml_dsa.public_key();
+        let binding = self
+            .identity
+            .sign(&[BINDING_PREFIX, ml_dsa_key].concat())
+            .map_err(|e| PqNoiseError::Signing(e.to_string()))?;
+        let signature = self
+            .ml_dsa
+            .sign(&[transcript, role].concat(), &mut rand::thread_rng())
+            .map_err(|e| PqNoiseError::Signing(e.to_string()))?;
+
+        let mut out = Vec::with_capacity(
+            4 + libp2p_key.len() + ml_dsa_key.len() + binding.len() + signature.len(),
+        );
+        write_u16_prefixed(&mut out, &libp2p_key)?;
+        out.extend_from_slice(ml_dsa_key);
+        write_u16_prefixed(&mut out, &binding)?;
+        out.extend_from_slice(&signature);
+        Ok(out)
+    }
+
+    /// Check a peer identity section and return its `PeerId` and ML-DSA key
+    fn verify_identity(
+        &self,
+        payload: &[u8],
+        transcript: &[u8],
+        role: &[u8],
+    ) -> Result<(PeerId, Vec<u8>), PqNoiseError> {
+        let mut reader = Reader(payload);
+        let libp2p_key = PublicKey::try_decode_protobuf(reader.u16_prefixed()?)
+            .map_err(|_| PqNoiseError::InvalidIdentity("bad libp2p public key"))?;
+        let ml_dsa_key = reader.take(ML_DSA_PUBLIC_KEY_SIZE)?;
+        let binding = reader.u16_prefixed()?;
+        let signature = reader.take(ML_DSA_SIGNATURE_SIZE)?;
+        if !reader.0.is_empty() {
+            return Err(PqNoiseError::Malformed("trailing identity data"));
+        }
+
+        if !libp2p_key.verify(&[BINDING_PREFIX, ml_dsa_key].concat(), binding) {
+            return Err(PqNoiseError::InvalidIdentity(
+                "ML-DSA key not bound to libp2p key",
+            ));
+        }
+        MlDsaPublicKey::from_bytes(ml_dsa_key)
+            .and_then(|key| key.verify(&[transcript, role].concat(), signature))
+            .map_err(|_| PqNoiseError::InvalidIdentity("bad transcript signature"))?;
+        if let Some(revocations) = &self.revocations {
+            revocations.check(ml_dsa_key)?;
+        }
+
+        Ok((libp2p_key.to_peer_id(), ml_dsa_key.to_vec()))
+    }
+}
+
+impl UpgradeInfo for PqNoiseConfig {
+    type Info = &'static str;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<T> InboundConnectionUpgrade<T> for PqNoiseConfig
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = (PeerId, PqNoiseOutput<T>);
+    type Error = PqNoiseError;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: T, _: Self::Info) -> Self::Future {
+        self.respond(socket).boxed()
+    }
+}
+
+impl<T> OutboundConnectionUpgrade<T> for PqNoiseConfig
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = (PeerId, PqNoiseOutput<T>);
+    type Error = PqNoiseError;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_outbound(self, socket: T, _: Self::Info) -> Self::Future {
+        self.initiate(socket).boxed()
+    }
+}
+
+impl PqNoiseConfig {
+    async fn initiate<T>(self, mut socket: T) -> Result<(PeerId, PqNoiseOutput<T>), PqNoiseError>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut transcript = Transcript::new();
+
+        // -> version, e, ek
+        let x25519_secret = x25519_dalek::EphemeralSecret::random_from_rng(rand::thread_rng());
+        let x25519_public = x25519_dalek::PublicKey::from(&x25519_secret);
+        let (kem_public, kem_secret) =
+            MlKem768::keygen().map_err(|e| PqNoiseError::KeyExchange(e.to_string()))?;
+        let mut first = vec![HANDSHAKE_VERSION];
+        first.extend_from_slice(x25519_public.as_bytes());
+        first.extend_from_slice(kem_public.as_bytes());
+        write_message(&mut socket, &first).await?;
+        transcript.absorb(&first);
+
+        // <- e, ct, {identity}
+        let second = read_message(&mut socket).await?;
+        let mut reader = Reader(&second);
+        let remote_x25519 = reader.array::<X25519_KEY_SIZE>()?;
+        let ciphertext = reader.take(MlKem768::CIPHERTEXT_SIZE)?;
+        let sealed_identity = reader.0;
+        transcript.absorb(&second[..second.len() - sealed_identity.len()]);
+
+        let dh = x25519_secret.diffie_hellman(&remote_x25519.into());
+        if !dh.was_contributory() {
+            return Err(PqNoiseError::KeyExchange(
+                "non-contributory X25519 key".to_string(),
+            ));
+        }
+        let kem_secret_shared = MlKem768::decapsulate(
+            &kem_secret,
+            &kem::Ciphertext::from_bytes(ciphertext)
+                .map_err(|e| PqNoiseError::KeyExchange(e.to_string()))?,
+        )
+        .map_err(|e| PqNoiseError::KeyExchange(e.to_string()))?;
+        let secrets = SharedSecrets::new(dh.as_bytes(), kem_secret_shared.as_bytes());
+
+        let responder_hash = transcript.hash();
+        let handshake = secrets.handshake_keys(&responder_hash);
+        let identity = open(&handshake.responder, &responder_hash, sealed_identity)?;
+        let (peer_id, remote_ml_dsa) =
+            self.verify_identity(&identity, &responder_hash, RESPONDER_ROLE)?;
+        transcript.absorb(sealed_identity);
+
+        // -> {identity}
+        let initiator_hash = transcript.hash();
+        let identity = self.identity_payload(&initiator_hash, INITIATOR_ROLE)?;
+        let third = seal(&handshake.initiator, &initiator_hash, &identity)?;
+        write_message(&mut socket, &third).await?;
+        transcript.absorb(&third);
+
+        let traffic = secrets.traffic_keys(&transcript.hash());
+        debug!("Completed {} handshake with {}", PROTOCOL_NAME, peer_id);
+        Ok((
+            peer_id,
+            PqNoiseOutput::new(socket, traffic.initiator, traffic.responder, remote_ml_dsa),
+        ))
+    }
+
+    async fn respond<T>(self, mut socket: T) -> Result<(PeerId, PqNoiseOutput<T>), PqNoiseError>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut transcript = Transcript::new();
+
+        // <- version, e, ek
+        let first = read_message(&mut socket).await?;
+        let mut reader = Reader(&first);
+        let version = reader.array::<1>()?[0];
+        if version != HANDSHAKE_VERSION {
+            return Err(PqNoiseError::UnsupportedVersion(version));
+        }
+        let remote_x25519 = reader.array::<X25519_KEY_SIZE>()?;
+        let remote_kem = reader.take(MlKem768::PUBLIC_KEY_SIZE)?;
+        if !reader.0.is_empty() {
+            return Err(PqNoiseError::Malformed("trailing key exchange data"));
+        }
+        transcript.absorb(&first);
+
+        // -> e, ct, {identity}
+        let x25519_secret = x25519_dalek::EphemeralSecret::random_from_rng(rand::thread_rng());
+        let x25519_public = x25519_dalek::PublicKey::from(&x25519_secret);
+        let dh = x25519_secret.diffie_hellman(&remote_x25519.into());
+        if !dh.was_contributory() {
+            return Err(PqNoiseError::KeyExchange(
+                "non-contributory X25519 key".to_string(),
+            ));
+        }
+        let (ciphertext, kem_secret_shared) = MlKem768::encapsulate(
+            &kem::PublicKey::from_bytes(remote_kem)
+                .map_err(|e| PqNoiseError::KeyExchange(e.to_string()))?,
+        )
+        .map_err(|e| PqNoiseError::KeyExchange(e.to_string()))?;
+        let secrets = SharedSecrets::new(dh.as_bytes(), kem_secret_shared.as_bytes());
+
+        let mut second = x25519_public.as_bytes().to_vec();
+        second.extend_from_slice(ciphertext.as_bytes());
+        transcript.absorb(&second);
+
+        let responder_hash = transcript.hash();
+        let handshake = secrets.handshake_keys(&responder_hash);
+        let identity = self.identity_payload(&responder_hash, RESPONDER_ROLE)?;
+        let sealed_identity = seal(&handshake.responder, &responder_hash, &identity)?;
+        second.extend_from_slice(&sealed_identity);
+        write_message(&mut socket, &second).await?;
+        transcript.absorb(&sealed_identity);
+
+        // <- {identity}
+        let initiator_hash = transcript.hash();
+        let third = read_message(&mut socket).await?;
+        let identity = open(&handshake.initiator, &initiator_hash, &third)?;
+        let (peer_id, remote_ml_dsa) =
+            self.verify_identity(&identity, &initiator_hash, INITIATOR_ROLE)?;
+        transcript.absorb(&third);
+
+        let traffic = secrets.traffic_keys(&transcript.hash());
+        debug!("Completed {} handshake with {}", PROTOCOL_NAME, peer_id);
+        Ok((
+            peer_id,
+            PqNoiseOutput::new(socket, traffic.responder, traffic.initiator, remote_ml_dsa),
+        ))
+    }
+}
+
+/// Running hash over every handshake message
+struct Transcript(Sha256);
+
+impl Transcript {
+    fn new() -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(PROTOCOL_NAME.as_bytes());
+        Self(hasher)
+    }
+
+    fn absorb(&mut self, message: &[u8]) {
+        self.0.update((message.len() as u32).to_be_bytes());
+        self.0.update(message);
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        self.0.clone().finalize().into()
+    }
+}
+
+/// Concatenated X25519 and ML-KEM shared secrets
+struct SharedSecrets(Zeroizing<Vec<u8>>);
+
+/// One key per direction
+struct DirectionalKeys {
+    initiator: Zeroizing<[u8; 32]>,
+    responder: Zeroizing<[u8; 32]>,
+}
+
+impl SharedSecrets {
+    fn new(dh: &[u8], kem: &[u8]) -> Self {
+        Self(Zeroizing::new([dh, kem].concat()))
+    }
+
+    fn derive(&self, salt: &[u8], label: &str) -> DirectionalKeys {
+        let hkdf = Hkdf::<Sha256>::new(Some(salt), &self.0);
+        let mut initiator = Zeroizing::new([0u8; 32]);
+        let mut responder = Zeroizing::new([0u8; 32]);
+        // 32-byte outputs are always within HKDF-SHA256 limits
+        hkdf.expand(
+            format!("{} {} initiator", PROTOCOL_NAME, label).as_bytes(),
+            initiator.as_mut(),
+        )
+        .expect("valid HKDF output length");
+        hkdf.expand(
+            format!("{} {} responder", PROTOCOL_NAME, label).as_bytes(),
+            responder.as_mut(),
+        )
+        .expect("valid HKDF output length");
+        DirectionalKeys {
+            initiator,
+            responder,
+        }
+    }
+
+    fn handshake_keys(&self, transcript: &[u8]) -> DirectionalKeys {
+        self.derive(transcript, "handshake")
+    }
+
+    fn traffic_keys(&self, transcript: &[u8]) -> DirectionalKeys {
+        self.derive(transcript, "traffic")
+    }
+}
+
+/// Encrypt a handshake payload; each handshake key seals exactly one message
+fn seal(key: &[u8; 32], transcript: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, PqNoiseError> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            Nonce::from_slice(&[0u8; 12]),
+            Payload {
+                msg: plaintext,
+                aad: transcript,
+            },
+        )
+        .map_err(|_| PqNoiseError::KeyExchange("handshake encryption failed".to_string()))
+}
+
+fn open(key: &[u8; 32], transcript: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, PqNoiseError> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(
+            Nonce::from_slice(&[0u8; 12]),
+            Payload {
+                msg: ciphertext,
+                aad: transcript,
+            },
+        )
+        .map_err(|_| PqNoiseError::Decryption)
+}
+
+async fn write_message<T: AsyncWrite + Unpin>(
+    socket: &mut T,
+    message: &[u8],
+) -> Result<(), PqNoiseError> {
+    socket
+        .write_all(&(message.len() as u32).to_be_bytes())
+        .await?;
+    socket.write_all(message).await?;
+    socket.flush().await?;
+    Ok(())
+}
+
+async fn read_message<T: AsyncRead + Unpin>(socket: &mut T) -> Result<Vec<u8>, PqNoiseError> {
+    let mut len = [0u8; 4];
+    socket.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_HANDSHAKE_MESSAGE {
+        return Err(PqNoiseError::Malformed("handshake message too large"));
+    }
+    let mut message = vec![0u8; len];
+    socket.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+fn write_u16_prefixed(out: &mut Vec<u8>, field: &[u8]) -> Result<(), PqNoiseError> {
+    let len = u16::try_from(field.len()).map_err(|_| PqNoiseError::Malformed("field too long"))?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(field);
+    Ok(())
+}
+
+/// Bounds-checked reader over a handshake message
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PqNoiseError> {
+        if self.0.len() < len {
+            return Err(PqNoiseError::Malformed("truncated handshake message"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], PqNoiseError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn u16_prefixed(&mut self) -> Result<&'a [u8], PqNoiseError> {
+        let len = u16::from_be_bytes(self.array::<2>()?) as usize;
+        self.take(len)
+    }
+}
+
+/// One direction of the encrypted channel
+struct CipherState {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl CipherState {
+    fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            counter: 0,
+        }
+    }
+
+    /// Take the next sequence number, which is also the frame's nonce
+    fn next_sequence(&mut self) -> io::Result<u64> {
+        let sequence = self.counter;
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("nonce space exhausted"))?;
+        Ok(sequence)
+    }
+}
+
+fn frame_nonce(sequence: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&sequence.to_le_bytes());
+    nonce
+}
+
+fn invalid_frame(reason: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.into())
+}
+
+/// Encrypted connection produced by the `/qudag/pq-noise/2` upgrade
+pub struct PqNoiseOutput<T> {
+    io: T,
+    remote_ml_dsa_key: Vec<u8>,
+    send: CipherState,
+    recv: CipherState,
+    /// Plaintext waiting to be sealed into a frame
+    send_plaintext: Vec<u8>,
+    /// Sealed frame bytes not yet written
+    send_frame: Vec<u8>,
+    send_frame_written: usize,
+    /// Raw bytes of the frame being received
+    recv_frame: Vec<u8>,
+    recv_frame_filled: usize,
+    /// Decrypted bytes not yet returned to the reader
+    recv_plaintext: Vec<u8>,
+    recv_plaintext_read: usize,
+}
+
+impl<T> PqNoiseOutput<T> {
+    fn new(
+        io: T,
+        send_key: Zeroizing<[u8; 32]>,
+        recv_key: Zeroizing<[u8; 32]>,
+        remote_ml_dsa_key: Vec<u8>,
+    ) -> Self {
+        Self {
+            io,
+            remote_ml_dsa_key,
+            send: CipherState::new(&send_key),
+            recv: CipherState::new(&recv_key),
+            send_plaintext: Vec::new(),
+            send_frame: Vec::new(),
+            send_frame_written: 0,
+            recv_frame: vec![0u8; SecureFrame::PREFIX_SIZE],
+            recv_frame_filled: 0,
+            recv_plaintext: Vec::new(),
+            recv_plaintext_read: 0,
+        }
+    }
+
+    /// ML-DSA public key the peer authenticated with
+    pub fn remote_ml_dsa_key(&self) -> &[u8] {
+        &self.remote_ml_dsa_key
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> PqNoiseOutput<T> {
+    /// Read and decrypt the next frame; `false` on clean end of stream
+    fn poll_next_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        loop {
+            let target = if self.recv_frame_filled < SecureFrame::PREFIX_SIZE {
+                SecureFrame::PREFIX_SIZE
+            } else {
+                let (length, _, _) = SecureFrame::parse_prefix(&self.recv_frame)
+                    .map_err(|e| invalid_frame(e.to_string()))?;
+                if length as usize > MAX_FRAME_PLAINTEXT {
+                    return Poll::Ready(Err(invalid_frame("frame too large")));
+                }
+                SecureFrame::HEADER_SIZE + length as usize
+            };
+
+            if self.recv_frame_filled == target && target > SecureFrame::PREFIX_SIZE {
+                let mut frame = SecureFrame::from_bytes(&self.recv_frame[..target])
+                    .map_err(|e| invalid_frame(e.to_string()))?;
+                self.recv_frame_filled = 0;
+                if frame.frame_type != SecureFrame::DATA {
+                    return Poll::Ready(Err(invalid_frame(format!(
+                        "unknown frame type {}",
+                        frame.frame_type
+                    ))));
+                }
+                let expected = self.recv.next_sequence()?;
+                if frame.sequence != expected {
+                    return Poll::Ready(Err(invalid_frame(format!(
+                        "frame {} out of sequence, expected {}",
+                        frame.sequence, expected
+                    ))));
+                }
+                self.recv
+                    .cipher
+                    .decrypt_in_place_detached(
+                        Nonce::from_slice(&frame_nonce(frame.sequence)),
+                        &frame.prefix(),
+                        &mut frame.payload,
+                        (&frame.auth_tag).into(),
+                    )
+                    .map_err(|_| invalid_frame("frame failed to decrypt"))?;
+                self.recv_plaintext = frame.payload;
+                self.recv_plaintext_read = 0;
+                return Poll::Ready(Ok(true));
+            }
+
+            if self.recv_frame.len() < target {
+                self.recv_frame.resize(target, 0);
+            }
+            let filled = self.recv_frame_filled;
+            let n =
+                ready!(Pin::new(&mut self.io).poll_read(cx, &mut self.recv_frame[filled..target]))?;
+            if n == 0 {
+                if self.recv_frame_filled == 0 {
+                    return Poll::Ready(Ok(false));
+                }
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            self.recv_frame_filled += n;
+        }
+    }
+
+    /// Seal buffered plaintext and write out every pending frame byte
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            while self.send_frame_written < self.send_frame.len() {
+                let n = ready!(Pin::new(&mut self.io)
+                    .poll_write(cx, &self.send_frame[self.send_frame_written..]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                self.send_frame_written += n;
+            }
+
+            if self.send_plaintext.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+
+            let sequence = self.send.next_sequence()?;
+            let mut frame = SecureFrame::new(
+                SecureFrame::DATA,
+                sequence,
+                std::mem::take(&mut self.send_plaintext),
+            );
+            let tag = self
+                .send
+                .cipher
+                .encrypt_in_place_detached(
+                    Nonce::from_slice(&frame_nonce(sequence)),
+                    &frame.prefix(),
+                    &mut frame.payload,
+                )
+                .map_err(|_| io::Error::other("frame encryption failed"))?;
+            frame.auth_tag.copy_from_slice(&tag);
+            self.send_frame = frame.to_bytes();
+            self.send_frame_written = 0;
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for PqNoiseOutput<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let available = &this.recv_plaintext[this.recv_plaintext_read..];
+            if !available.is_empty() {
+                let n = available.len().min(buf.len());
+                buf[..n].copy_from_slice(&available[..n]);
+                this.recv_plaintext_read += n;
+                return Poll::Ready(Ok(n));
+            }
+            if !ready!(this.poll_next_frame(cx))? {
+                return Poll::Ready(Ok(0));
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for PqNoiseOutput<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.send_plaintext.len() >= MAX_FRAME_PLAINTEXT {
+            ready!(this.poll_drain(cx))?;
+        }
+        let n = buf
+            .len()
+            .min(MAX_FRAME_PLAINTEXT - this.send_plaintext.len());
+        this.send_plaintext.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        ready!(Pin::new(&mut this.io).poll_flush(cx))?;
+        Pin::new(&mut this.io).poll_close(cx)
+    }
+}
+
+/// Offers two security upgrades, preferring the first
+///
+/// libp2p's equivalent is private to its swarm builder.
+#[derive(Clone)]
+pub struct SelectSecurity<A, B>(pub A, pub B);
+
+impl<A, B> UpgradeInfo for SelectSecurity<A, B>
+where
+    A: UpgradeInfo<Info = &'static str>,
+    B: UpgradeInfo<Info = &'static str>,
+{
+    type Info = &'static str;
+    type InfoIter = Vec<&'static str>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.0
+            .protocol_info()
+            .into_iter()
+            .chain(self.1.protocol_info())
+            .collect()
+    }
+}
+
+macro_rules! select_security_upgrade {
+    ($trait:ident, $method:ident) => {
+        impl<C, A, B, TA, TB, EA, EB> $trait<C> for SelectSecurity<A, B>
+        where
+            A: $trait<C, Output = (PeerId, TA), Error = EA> + UpgradeInfo<Info = &'static str>,
+            B: $trait<C, Output = (PeerId, TB), Error = EB> + UpgradeInfo<Info = &'static str>,
+            A::Future: Send + 'static,
+            B::Future: Send + 'static,
+            TA: Send + 'static,
+            TB: Send + 'static,
+            EA: Send + 'static,
+            EB: Send + 'static,
+        {
+            type Output = (PeerId, future::Either<TA, TB>);
+            type Error = either::Either<EA, EB>;
+            type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+            fn $method(self, socket: C, info: Self::Info) -> Self::Future {
+                if self.0.protocol_info().into_iter().any(|p| p == info) {
+                    self.0
+                        .$method(socket, info)
+                        .map_ok(|(peer, io)| (peer, future::Either::Left(io)))
+                        .map_err(either::Either::Left)
+                        .boxed()
+                } else {
+                    self.1
+                        .$method(socket, info)
+                        .map_ok(|(peer, io)| (peer, future::Either::Right(io)))
+                        .map_err(either::Either::Right)
+                        .boxed()
+                }
+            }
+        }
+    };
+}
+
+select_security_upgrade!(InboundConnectionUpgrade, upgrade_inbound);
+select_security_upgrade!(OutboundConnectionUpgrade, upgrade_outbound);
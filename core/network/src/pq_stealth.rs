@@ -0,0 +1,360 @@
+//! Post-quantum stealth addresses, shadow address version 3.
+//!
+//! Version 2 stealth addresses derive their one-time keys from an X25519
+//! exchange with the recipient's view key, which a quantum adversary can
+//! undo. Version 3 replaces the exchange with ML-KEM-768 and authorizes
+//! spending with ML-DSA:
+//!
+//! * The recipient publishes a meta-address, a version 3 [`ShadowAddress`]
+//!   whose view key is an ML-KEM-768 public key and whose spend key is an
+//!   ML-DSA public key. [`PqStealthKeys`] holds the matching secrets.
+//! * A sender encapsulates to the view key. The one-time address carries the
+//!   ciphertext as its view key, a commitment to the recipient's spend key
+//!   as its spend key, and a stealth prefix derived from the shared secret.
+//!   The address is its own announcement.
+//! * The recipient's [`StealthScanner`] decapsulates every announcement and
+//!   compares the 4-byte prefix before deriving anything else, so a foreign
+//!   announcement costs one decapsulation and one HMAC.
+//! * Spending takes a [`SpendAuthorization`]: the opening of the commitment
+//!   and an ML-DSA signature by the spend key. The sender knows the opening
+//!   but not the signing key, so it cannot spend what it sent.
+//!
+//! Verifying a spend reveals the recipient's spend key to the verifier;
+//! unspent one-time addresses stay unlinkable to each other and to the
+//! meta-address.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hkdf::Hkdf;
+use qudag_crypto::kem::{
+    Ciphertext as KEMCiphertext, PublicKey as KEMPublicKey, SecretKey as KEMSecretKey,
+};
+use qudag_crypto::ml_dsa::{MlDsaKeyPair, MlDsaPublicKey, ML_DSA_PUBLIC_KEY_SIZE};
+use qudag_crypto::MlKem768;
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::shadow_address::{
+    NetworkType, ShadowAddress, ShadowAddressError, ShadowFeatures, ShadowMetadata,
+    SHADOW_VERSION_STEALTH_ML_KEM,
+};
+
+/// HKDF salt of the secrets derived from an encapsulation
+const STEALTH_DOMAIN: &[u8] = b"qudag-stealth/3";
+
+/// Domain of the commitment to the recipient's spend key
+const COMMITMENT_DOMAIN: &[u8] = b"qudag-stealth-commitment/3";
+
+/// Domain of spend authorization signatures
+const SPEND_DOMAIN: &[u8] = b"qudag-stealth-spend/3";
+
+/// Metadata flag of stealth addresses, shared with version 2
+const STEALTH_FLAG: u32 = 0x02;
+
+/// Keys of a stealth recipient
+///
+/// The ML-KEM view key finds payments; only the ML-DSA spend key can
+/// authorize spending them.
+pub struct PqStealthKeys {
+    pub view_public: KEMPublicKey,
+    pub view_secret: KEMSecretKey,
+    pub spend: MlDsaKeyPair,
+}
+
+impl PqStealthKeys {
+    /// Generate fresh keys
+    pub fn generate() -> Result<Self, ShadowAddressError> {
+        let (view_public, view_secret) =
+            MlKem768::keygen().map_err(|e| ShadowAddressError::CryptoError(e.to_string()))?;
+        let spend = MlDsaKeyPair::generate(&mut thread_rng())
+            .map_err(|e| ShadowAddressError::CryptoError(e.to_string()))?;
+        Ok(Self {
+            view_public,
+            view_secret,
+            spend,
+        })
+    }
+
+    /// The meta-address senders pay to
+    pub fn meta_address(&self, network: NetworkType) -> ShadowAddress {
+        ShadowAddress {
+            view_key: self.view_public.as_bytes().to_vec(),
+            spend_key: self.spend.public_key().to_vec(),
+            payment_id: None,
+            metadata: ShadowMetadata {
+                version: SHADOW_VERSION_STEALTH_ML_KEM,
+                network,
+                expires_at: None,
+                created_at: current_timestamp(),
+                last_used: None,
+                flags: 0,
+                ttl: None,
+                usage_count: 0,
+                max_uses: None,
+            },
+            shadow_features: ShadowFeatures {
+                is_temporary: false,
+                derivation_index: None,
+                stealth_prefix: None,
+                mixing_enabled: false,
+                pool_id: None,
+            },
+        }
+    }
+
+    /// A scanner that finds payments to these keys
+    pub fn scanner(&self) -> StealthScanner {
+        StealthScanner::new(self.view_secret.clone(), self.spend.public_key())
+    }
+
+    /// Authorize spending a found payment, binding `message`
+    pub fn authorize_spend(
+        &self,
+        found: &StealthMatch,
+        message: &[u8],
+    ) -> Result<SpendAuthorization, ShadowAddressError> {
+        let signed = spend_message(&found.address.spend_key, message);
+        let signature = self
+            .spend
+            .sign(&signed, &mut thread_rng())
+            .map_err(|e| ShadowAddressError::CryptoError(e.to_string()))?;
+        Ok(SpendAuthorization {
+            spend_public_key: self.spend.public_key().to_vec(),
+            opening: found.opening,
+            signature,
+        })
+    }
+}
+
+/// Generate a version 3 one-time address for a recipient's keys
+pub fn generate_stealth_address(
+    network: NetworkType,
+    recipient_view_key: &[u8],
+    recipient_spend_key: &[u8],
+) -> Result<ShadowAddress, ShadowAddressError> {
+    if recipient_spend_key.len() != ML_DSA_PUBLIC_KEY_SIZE {
+        return Err(ShadowAddressError::InvalidKeyFormat(
+            "Invalid spend key length".into(),
+        ));
+    }
+    let view_key = KEMPublicKey::from_bytes(recipient_view_key)
+        .map_err(|e| ShadowAddressError::InvalidKeyFormat(e.to_string()))?;
+    let (ciphertext, shared_secret) = MlKem768::encapsulate(&view_key)
+        .map_err(|e| ShadowAddressError::CryptoError(e.to_string()))?;
+
+    let secrets = Hkdf::<Sha256>::new(Some(STEALTH_DOMAIN), shared_secret.as_bytes());
+    let opening = opening(&secrets);
+
+    Ok(ShadowAddress {
+        view_key: ciphertext.as_bytes().to_vec(),
+        spend_key: spend_commitment(&opening, recipient_spend_key).to_vec(),
+        payment_id: None,
+        metadata: ShadowMetadata {
+            version: SHADOW_VERSION_STEALTH_ML_KEM,
+            network,
+            expires_at: None,
+            created_at: current_timestamp(),
+            last_used: None,
+            flags: STEALTH_FLAG,
+            ttl: None,
+            usage_count: 0,
+            max_uses: Some(1),
+        },
+        shadow_features: ShadowFeatures {
+            is_temporary: false,
+            derivation_index: None,
+            stealth_prefix: Some(stealth_prefix(&secrets)),
+            mixing_enabled: true,
+            pool_id: None,
+        },
+    })
+}
+
+/// Whether an address has the shape of a version 3 one-time address
+pub fn is_one_time_address(address: &ShadowAddress) -> bool {
+    address.metadata.version == SHADOW_VERSION_STEALTH_ML_KEM
+        && address.metadata.flags & STEALTH_FLAG != 0
+        && address.view_key.len() == MlKem768::CIPHERTEXT_SIZE
+        && address.spend_key.len() == 32
+        && address.shadow_features.stealth_prefix.is_some()
+}
+
+/// Whether an address has the shape of a version 3 meta-address
+pub fn is_meta_address(address: &ShadowAddress) -> bool {
+    address.metadata.version == SHADOW_VERSION_STEALTH_ML_KEM
+        && address.view_key.len() == MlKem768::PUBLIC_KEY_SIZE
+        && address.spend_key.len() == ML_DSA_PUBLIC_KEY_SIZE
+}
+
+/// A payment found by a [`StealthScanner`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StealthMatch {
+    /// Position of the announcement in the scanned batch
+    pub index: usize,
+    /// The one-time address
+    pub address: ShadowAddress,
+    /// Opening of the address's spend key commitment
+    pub opening: [u8; 32],
+}
+
+/// Counters of the announcements a scanner looked at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanStats {
+    /// Announcements looked at
+    pub scanned: u64,
+    /// Announcements that are not version 3 one-time addresses
+    pub skipped: u64,
+    /// Announcements whose prefix matched after decapsulation
+    pub prefix_hits: u64,
+    /// Announcements paying to this recipient
+    pub matched: u64,
+}
+
+/// Finds the one-time addresses paying to a recipient
+///
+/// Holds the view secret and the spend public key only, so a watch-only
+/// node can scan without being able to spend.
+pub struct StealthScanner {
+    view_secret: KEMSecretKey,
+    spend_public: Vec<u8>,
+    stats: ScanStats,
+}
+
+impl StealthScanner {
+    /// Create a scanner from a recipient's view secret and spend public key
+    pub fn new(view_secret: KEMSecretKey, spend_public: &[u8]) -> Self {
+        Self {
+            view_secret,
+            spend_public: spend_public.to_vec(),
+            stats: ScanStats::default(),
+        }
+    }
+
+    /// Check a batch of announcements, returning those paying to this recipient
+    pub fn scan<'a, I>(&mut self, announcements: I) -> Vec<StealthMatch>
+    where
+        I: IntoIterator<Item = &'a ShadowAddress>,
+    {
+        announcements
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, address)| {
+                self.check(address).map(|opening| StealthMatch {
+                    index,
+                    address: address.clone(),
+                    opening,
+                })
+            })
+            .collect()
+    }
+
+    /// Check one announcement, returning the commitment opening on a match
+    pub fn check(&mut self, address: &ShadowAddress) -> Option<[u8; 32]> {
+        self.stats.scanned += 1;
+        if !is_one_time_address(address) {
+            self.stats.skipped += 1;
+            return None;
+        }
+        let ciphertext = KEMCiphertext::from_bytes(&address.view_key).ok()?;
+        // A ciphertext for another recipient decapsulates to a pseudorandom
+        // secret, which the prefix rejects with probability 1 - 2^-32
+        let shared_secret = MlKem768::decapsulate(&self.view_secret, &ciphertext).ok()?;
+        let secrets = Hkdf::<Sha256>::new(Some(STEALTH_DOMAIN), shared_secret.as_bytes());
+        if address.shadow_features.stealth_prefix != Some(stealth_prefix(&secrets)) {
+            return None;
+        }
+        self.stats.prefix_hits += 1;
+
+        let opening = opening(&secrets);
+        if spend_commitment(&opening, &self.spend_public)[..] != address.spend_key[..] {
+            return None;
+        }
+        self.stats.matched += 1;
+        Some(opening)
+    }
+
+    /// Counters so far
+    pub fn stats(&self) -> ScanStats {
+        self.stats
+    }
+}
+
+/// Proof that the owner of a one-time address authorized spending it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendAuthorization {
+    /// The recipient's ML-DSA spend key
+    pub spend_public_key: Vec<u8>,
+    /// Opening of the address's spend key commitment
+    pub opening: [u8; 32],
+    /// Signature over the address commitment and the spent message
+    pub signature: Vec<u8>,
+}
+
+impl SpendAuthorization {
+    /// Check that the authorization spends `address` and signs `message`
+    pub fn verify(
+        &self,
+        address: &ShadowAddress,
+        message: &[u8],
+    ) -> Result<(), ShadowAddressError> {
+        if !is_one_time_address(address) {
+            return Err(ShadowAddressError::UnsupportedVersion(
+                address.metadata.version,
+            ));
+        }
+        if spend_commitment(&self.opening, &self.spend_public_key)[..] != address.spend_key[..] {
+            return Err(ShadowAddressError::CryptoError(
+                "Spend key does not open the address commitment".into(),
+            ));
+        }
+        MlDsaPublicKey::from_bytes(&self.spend_public_key)
+            .and_then(|key| {
+                key.verify(&spend_message(&address.spend_key, message), &self.signature)
+            })
+            .map_err(|e| ShadowAddressError::CryptoError(e.to_string()))
+    }
+}
+
+/// Scanning prefix derived from an encapsulated secret
+fn stealth_prefix(secrets: &Hkdf<Sha256>) -> [u8; 4] {
+    let mut prefix = [0u8; 4];
+    secrets
+        .expand(b"prefix", &mut prefix)
+        .expect("4 bytes is a valid HKDF-SHA256 length");
+    prefix
+}
+
+/// Commitment opening derived from an encapsulated secret
+fn opening(secrets: &Hkdf<Sha256>) -> [u8; 32] {
+    let mut opening = [0u8; 32];
+    secrets
+        .expand(b"opening", &mut opening)
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    opening
+}
+
+/// Commitment to a spend key, the spend key of a one-time address
+fn spend_commitment(opening: &[u8; 32], spend_public_key: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(COMMITMENT_DOMAIN);
+    hasher.update(opening);
+    hasher.update(spend_public_key);
+    hasher.finalize().into()
+}
+
+/// Message signed to spend a one-time address
+fn spend_message(commitment: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut signed = Vec::with_capacity(SPEND_DOMAIN.len() + commitment.len() + message.len());
+    signed.extend_from_slice(SPEND_DOMAIN);
+    signed.extend_from_slice(commitment);
+    signed.extend_from_slice(message);
+    signed
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,180 @@
+//! Reachability detection from AutoNAT probes and STUN results.
+//!
+//! A node learns whether peers can dial it from two sources:
+//!
+//! * AutoNAT probes among peers: a connected peer is asked to dial the
+//!   node's listen and observed addresses back. This needs no public
+//!   servers, so it works offline and between in-process peers.
+//! * STUN detection by the [`NatTraversalManager`], reported to the node
+//!   through `P2PHandle::report_nat_info`.
+//!
+//! AutoNAT tests the node's own addresses, so its verdict wins once it has
+//! one; STUN decides until then. The combined [`Reachability`] drives the
+//! node:
+//!
+//! * [`Reachability::Public`]: the addresses confirmed by AutoNAT, or the
+//!   listen addresses on the public IP found by STUN, are external
+//!   addresses, which identify advertises. Kademlia runs in server mode, so
+//!   peers add the node to their routing tables. Reservations on relays are
+//!   released unless [`RelayConfig::reserve`] asks for them.
+//! * [`Reachability::Private`]: only relayed addresses remain external.
+//!   Kademlia runs in client mode, so the node queries the DHT without
+//!   being advertised in it. The node reserves on the configured relays and
+//!   on connected peers offering relaying.
+//! * [`Reachability::Unknown`]: Kademlia serves as before, and nothing else
+//!   changes.
+//!
+//! [`NatTraversalManager`]: crate::nat_traversal::NatTraversalManager
+//! [`RelayConfig::reserve`]: crate::circuit_relay::RelayConfig::reserve
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use libp2p::autonat::{self, NatStatus};
+use serde::{Deserialize, Serialize};
+
+use crate::nat_traversal::{NatInfo, NatType};
+
+/// Whether peers can dial the node directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Reachability {
+    /// Not determined yet
+    Unknown,
+    /// Dialable at its external addresses
+    Public,
+    /// Behind a NAT or firewall, reachable through relays only
+    Private,
+}
+
+impl From<&NatStatus> for Reachability {
+    fn from(status: &NatStatus) -> Self {
+        match status {
+            NatStatus::Public(_) => Reachability::Public,
+            NatStatus::Private => Reachability::Private,
+            NatStatus::Unknown => Reachability::Unknown,
+        }
+    }
+}
+
+impl From<&NatInfo> for Reachability {
+    fn from(info: &NatInfo) -> Self {
+        match info.nat_type {
+            NatType::None => Reachability::Public,
+            NatType::Unknown => Reachability::Unknown,
+            _ => Reachability::Private,
+        }
+    }
+}
+
+/// AutoNAT probing configuration
+#[derive(Debug, Clone)]
+pub struct AutoNatConfig {
+    /// Probe reachability through peers and answer their probes
+    pub enabled: bool,
+    /// Delay before the first probe
+    pub boot_delay: Duration,
+    /// How often to probe while reachability is unknown or unconfirmed
+    pub retry_interval: Duration,
+    /// How often to probe once enough probes agree
+    pub refresh_interval: Duration,
+    /// Timeout of a probe
+    pub timeout: Duration,
+    /// How many agreeing probes confirm reachability; as many contrary
+    /// ones flip it
+    pub confidence_max: usize,
+    /// Only probe through and for peers at global IP addresses
+    ///
+    /// Disable for local networks and in-process peers.
+    pub only_global_ips: bool,
+}
+
+impl Default for AutoNatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            boot_delay: Duration::from_secs(15),
+            retry_interval: Duration::from_secs(90),
+            refresh_interval: Duration::from_secs(15 * 60),
+            timeout: Duration::from_secs(30),
+            confidence_max: 3,
+            only_global_ips: true,
+        }
+    }
+}
+
+impl AutoNatConfig {
+    /// AutoNAT behaviour configuration with these intervals
+    ///
+    /// libp2p's default throttling of probes served applies on top.
+    pub fn behaviour_config(&self) -> autonat::Config {
+        autonat::Config {
+            timeout: self.timeout,
+            boot_delay: self.boot_delay,
+            retry_interval: self.retry_interval,
+            refresh_interval: self.refresh_interval,
+            confidence_max: self.confidence_max,
+            only_global_ips: self.only_global_ips,
+            ..Default::default()
+        }
+    }
+}
+
+/// Reachability combined from AutoNAT and STUN
+#[derive(Debug, Clone)]
+pub struct ReachabilityState {
+    autonat: Reachability,
+    stun: Reachability,
+    stun_public_ip: Option<IpAddr>,
+}
+
+impl Default for ReachabilityState {
+    fn default() -> Self {
+        Self {
+            autonat: Reachability::Unknown,
+            stun: Reachability::Unknown,
+            stun_public_ip: None,
+        }
+    }
+}
+
+impl ReachabilityState {
+    /// Combined reachability: AutoNAT's if known, STUN's otherwise
+    pub fn reachability(&self) -> Reachability {
+        match self.autonat {
+            Reachability::Unknown => self.stun,
+            known => known,
+        }
+    }
+
+    /// Public IP address reported by STUN for a node without NAT
+    pub fn stun_public_ip(&self) -> Option<IpAddr> {
+        self.stun_public_ip
+    }
+
+    /// Record the status AutoNAT settled on
+    ///
+    /// Returns the new reachability if it changed.
+    pub fn on_autonat(&mut self, status: &NatStatus) -> Option<Reachability> {
+        let before = self.reachability();
+        self.autonat = status.into();
+        self.changed(before)
+    }
+
+    /// Record the result of STUN detection
+    ///
+    /// Returns the new reachability if it changed.
+    pub fn on_stun(&mut self, info: &NatInfo) -> Option<Reachability> {
+        let before = self.reachability();
+        self.stun = info.into();
+        self.stun_public_ip = match self.stun {
+            Reachability::Public => info.public_ip,
+            _ => None,
+        };
+        self.changed(before)
+    }
+
+    fn changed(&self, before: Reachability) -> Option<Reachability> {
+        let after = self.reachability();
+        (after != before).then_some(after)
+    }
+}
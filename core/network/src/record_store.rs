@@ -0,0 +1,583 @@
+//! Disk-backed Kademlia record store.
+//!
+//! [`PersistentRecordStore`] keeps records and provider entries in memory for
+//! lookups and mirrors every change to one file per key, so a node keeps
+//! serving its share of the DHT across restarts. Files are written on a
+//! background thread, in order, so the swarm loop never waits on the disk. It enforces count, size and
+//! TTL quotas, drops expired entries, tracks when locally published records
+//! are due for republishing and runs [`RecordValidator`] hooks before
+//! anything is stored.
+
+use libp2p::kad::{
+    store::{
+        Error as StoreError, MemoryStore, MemoryStoreConfig, RecordStore, Result as StoreResult,
+    },
+    ProviderRecord, Record, RecordKey, K_VALUE,
+};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::disk_writer::DiskWriter;
+
+/// Directory below the node data directory holding the record store
+pub const RECORD_STORE_DIR: &str = "dht";
+
+const RECORDS_DIR: &str = "records";
+const PROVIDERS_DIR: &str = "providers";
+
+/// Errors raised by the record store and its validators
+#[derive(Debug, Error)]
+pub enum RecordStoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Encoding error: {0}")]
+    Encoding(String),
+    #[error("Record rejected: {0}")]
+    Rejected(String),
+}
+
+/// Hook deciding whether a record may be stored
+///
+/// Validators run on every put, including records replicated to this node
+/// by other peers, and on records loaded from disk.
+pub trait RecordValidator: Send + Sync {
+    /// Accept `record` or explain why it is refused
+    fn validate(&self, record: &Record) -> Result<(), RecordStoreError>;
+}
+
+/// Quotas and timing for [`PersistentRecordStore`]
+#[derive(Debug, Clone)]
+pub struct RecordStoreConfig {
+    /// Maximum number of records
+    pub max_records: usize,
+    /// Maximum size of a single record value
+    pub max_value_bytes: usize,
+    /// Maximum combined size of all record values
+    pub max_total_bytes: usize,
+    /// Longest lifetime a record or provider entry is kept for
+    pub max_ttl: Duration,
+    /// Maximum number of providers kept per key
+    pub max_providers_per_key: usize,
+    /// Maximum number of keys with provider entries
+    pub max_provided_keys: usize,
+    /// How long after publishing a local record it is published again
+    pub republish_interval: Duration,
+}
+
+impl Default for RecordStoreConfig {
+    fn default() -> Self {
+        Self {
+            max_records: 1024,
+            max_value_bytes: 65 * 1024,
+            max_total_bytes: 16 * 1024 * 1024,
+            max_ttl: Duration::from_secs(48 * 60 * 60),
+            max_providers_per_key: K_VALUE.get(),
+            max_provided_keys: 1024,
+            republish_interval: Duration::from_secs(12 * 60 * 60),
+        }
+    }
+}
+
+/// On-disk form of a record; instants are stored as UNIX seconds
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    publisher: Option<Vec<u8>>,
+    expires_at: Option<u64>,
+    published_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredProvider {
+    provider: Vec<u8>,
+    addresses: Vec<Vec<u8>>,
+    expires_at: Option<u64>,
+}
+
+/// On-disk form of all provider entries for one key
+#[derive(Serialize, Deserialize)]
+struct StoredProviders {
+    key: Vec<u8>,
+    providers: Vec<StoredProvider>,
+}
+
+/// Kademlia record store persisted to a directory
+pub struct PersistentRecordStore {
+    /// Local peer, whose records are tracked for republishing
+    local_id: PeerId,
+    /// In-memory view used for lookups and provider ordering
+    inner: MemoryStore,
+    config: RecordStoreConfig,
+    /// Store directory; `None` keeps everything in memory only
+    dir: Option<PathBuf>,
+    /// Writes entries to `dir` off the swarm loop
+    writer: Option<DiskWriter>,
+    validators: Vec<Box<dyn RecordValidator>>,
+    /// Combined size of all record values
+    total_bytes: usize,
+    /// When each locally published record was last published
+    published_at: HashMap<RecordKey, SystemTime>,
+    /// Keys with at least one provider entry
+    provider_keys: HashSet<RecordKey>,
+}
+
+impl PersistentRecordStore {
+    /// Create a store that keeps nothing on disk
+    pub fn in_memory(local_id: PeerId, config: RecordStoreConfig) -> Self {
+        let inner = MemoryStore::with_config(
+            local_id,
+            MemoryStoreConfig {
+                max_records: config.max_records,
+                // Value size is checked by `put` itself
+                max_value_bytes: usize::MAX,
+                max_providers_per_key: config.max_providers_per_key,
+                max_provided_keys: config.max_provided_keys,
+            },
+        );
+        Self {
+            local_id,
+            inner,
+            config,
+            dir: None,
+            writer: None,
+            validators: Vec::new(),
+            total_bytes: 0,
+            published_at: HashMap::new(),
+            provider_keys: HashSet::new(),
+        }
+    }
+
+    /// Open the store in `dir`, creating it if needed and loading its entries
+    ///
+    /// Expired and unreadable entries are removed from disk while loading.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        local_id: PeerId,
+        config: RecordStoreConfig,
+    ) -> Result<Self, RecordStoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(RECORDS_DIR))?;
+        fs::create_dir_all(dir.join(PROVIDERS_DIR))?;
+
+        let mut store = Self::in_memory(local_id, config);
+        store.load_records(&dir)?;
+        store.load_providers(&dir)?;
+        store.dir = Some(dir);
+        store.writer = Some(DiskWriter::new("dht-store-writer")?);
+        info!(
+            "Loaded {} DHT records and providers for {} keys",
+            store.len(),
+            store.provider_keys.len()
+        );
+        Ok(store)
+    }
+
+    /// Add a validator; stored records it refuses are dropped
+    pub fn with_validator(mut self, validator: impl RecordValidator + 'static) -> Self {
+        let rejected: Vec<RecordKey> = self
+            .inner
+            .records()
+            .filter(|record| validator.validate(record).is_err())
+            .map(|record| record.key.clone())
+            .collect();
+        for key in rejected {
+            debug!("Dropping stored record refused by validator");
+            self.remove(&key);
+        }
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> usize {
+        self.inner.records().count()
+    }
+
+    /// Whether no records are stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Combined size of all record values
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Remove expired records and provider entries
+    ///
+    /// Returns the number of entries removed.
+    pub fn remove_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<RecordKey> = self
+            .inner
+            .records()
+            .filter(|record| record.is_expired(now))
+            .map(|record| record.key.clone())
+            .collect();
+        let mut removed = expired.len();
+        for key in expired {
+            self.remove(&key);
+        }
+
+        let keys: Vec<RecordKey> = self.provider_keys.iter().cloned().collect();
+        for key in keys {
+            let expired: Vec<PeerId> = self
+                .inner
+                .providers(&key)
+                .into_iter()
+                .filter(|provider| provider.is_expired(now))
+                .map(|provider| provider.provider)
+                .collect();
+            removed += expired.len();
+            for provider in expired {
+                self.remove_provider(&key, &provider);
+            }
+        }
+
+        if removed > 0 {
+            debug!("Removed {} expired DHT entries", removed);
+        }
+        removed
+    }
+
+    /// Locally published records whose republish interval has elapsed
+    pub fn due_for_republish(&self) -> Vec<Record> {
+        let now = SystemTime::now();
+        let instant = Instant::now();
+        self.published_at
+            .iter()
+            .filter(|(_, published)| {
+                now.duration_since(**published).unwrap_or_default()
+                    >= self.config.republish_interval
+            })
+            .filter_map(|(key, _)| self.inner.get(key))
+            .filter(|record| !record.is_expired(instant))
+            .map(Cow::into_owned)
+            .collect()
+    }
+
+    fn validate(&self, record: &Record) -> Result<(), RecordStoreError> {
+        if record.value.len() > self.config.max_value_bytes {
+            return Err(RecordStoreError::Rejected(format!(
+                "value of {} bytes exceeds {} bytes",
+                record.value.len(),
+                self.config.max_value_bytes
+            )));
+        }
+        self.validators
+            .iter()
+            .try_for_each(|validator| validator.validate(record))
+    }
+
+    /// Cap `expires` at the configured maximum TTL
+    fn clamp_expiry(&self, expires: Option<Instant>) -> Option<Instant> {
+        let limit = Instant::now() + self.config.max_ttl;
+        Some(expires.map_or(limit, |expires| expires.min(limit)))
+    }
+
+    fn load_records(&mut self, dir: &Path) -> Result<(), RecordStoreError> {
+        for path in entry_paths(&dir.join(RECORDS_DIR))? {
+            let stored = match read_entry::<StoredRecord>(&path) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    warn!("Discarding unreadable DHT record {:?}: {}", path, e);
+                    remove_entry(&path);
+                    continue;
+                }
+            };
+            let published_at = stored.published_at.map(unix_to_system);
+            let mut record = Record::new(stored.key, stored.value);
+            record.publisher = stored
+                .publisher
+                .and_then(|bytes| PeerId::from_bytes(&bytes).ok());
+            record.expires = stored.expires_at.map(unix_to_instant);
+            if record.is_expired(Instant::now()) {
+                remove_entry(&path);
+                continue;
+            }
+            let key = record.key.clone();
+            if self.put(record).is_err() {
+                remove_entry(&path);
+                continue;
+            }
+            // Keep the original publication time rather than the load time
+            match published_at {
+                Some(at) if self.published_at.contains_key(&key) => {
+                    self.published_at.insert(key, at);
+                }
+                _ => {
+                    self.published_at.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_providers(&mut self, dir: &Path) -> Result<(), RecordStoreError> {
+        for path in entry_paths(&dir.join(PROVIDERS_DIR))? {
+            let stored = match read_entry::<StoredProviders>(&path) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    warn!("Discarding unreadable DHT providers {:?}: {}", path, e);
+                    remove_entry(&path);
+                    continue;
+                }
+            };
+            let key = RecordKey::from(stored.key);
+            for stored in stored.providers {
+                let Ok(provider) = PeerId::from_bytes(&stored.provider) else {
+                    continue;
+                };
+                let addresses = stored
+                    .addresses
+                    .into_iter()
+                    .filter_map(|bytes| Multiaddr::try_from(bytes).ok())
+                    .collect();
+                let mut record = ProviderRecord::new(key.clone(), provider, addresses);
+                record.expires = stored.expires_at.map(unix_to_instant);
+                if !record.is_expired(Instant::now()) {
+                    let _ = self.add_provider(record);
+                }
+            }
+            if self.inner.providers(&key).is_empty() {
+                remove_entry(&path);
+            }
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, kind: &str, key: &RecordKey) -> Option<PathBuf> {
+        let name = hex::encode(blake3::hash(key.as_ref()).as_bytes());
+        self.dir.as_ref().map(|dir| dir.join(kind).join(name))
+    }
+
+    fn persist_record(&self, record: &Record) {
+        let Some(path) = self.entry_path(RECORDS_DIR, &record.key) else {
+            return;
+        };
+        let stored = StoredRecord {
+            key: record.key.to_vec(),
+            value: record.value.clone(),
+            publisher: record.publisher.map(|peer| peer.to_bytes()),
+            expires_at: record.expires.map(instant_to_unix),
+            published_at: self.published_at.get(&record.key).map(system_to_unix),
+        };
+        self.queue_write(path, &stored);
+    }
+
+    fn persist_providers(&self, key: &RecordKey) {
+        let Some(path) = self.entry_path(PROVIDERS_DIR, key) else {
+            return;
+        };
+        let providers = self.inner.providers(key);
+        if providers.is_empty() {
+            self.queue_remove(path);
+            return;
+        }
+        let stored = StoredProviders {
+            key: key.to_vec(),
+            providers: providers
+                .into_iter()
+                .map(|record| StoredProvider {
+                    provider: record.provider.to_bytes(),
+                    addresses: record.addresses.iter().map(|addr| addr.to_vec()).collect(),
+                    expires_at: record.expires.map(instant_to_unix),
+                })
+                .collect(),
+        };
+        self.queue_write(path, &stored);
+    }
+
+    /// Queue writing an entry behind earlier changes to the store
+    fn queue_write<T: Serialize>(&self, path: PathBuf, value: &T) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let bytes = match bincode::serialize(value) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Failed to encode DHT entry: {}", e);
+                return;
+            }
+        };
+        writer.submit(move || {
+            if let Err(e) = write_entry(&path, &bytes) {
+                warn!("Failed to persist DHT entry {:?}: {}", path, e);
+            }
+        });
+    }
+
+    /// Queue removing an entry behind earlier changes to the store
+    fn queue_remove(&self, path: PathBuf) {
+        if let Some(writer) = &self.writer {
+            writer.submit(move || remove_entry(&path));
+        }
+    }
+
+    /// Wait until every change made so far is on disk
+    pub fn flush(&self) {
+        if let Some(writer) = &self.writer {
+            writer.flush();
+        }
+    }
+}
+
+impl RecordStore for PersistentRecordStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, key: &RecordKey) -> Option<Cow<'_, Record>> {
+        self.inner
+            .get(key)
+            .filter(|record| !record.is_expired(Instant::now()))
+    }
+
+    /// Store a record after quota and validator checks
+    ///
+    /// libp2p's store error has no variant for invalid records, so records
+    /// refused by a validator are reported as [`StoreError::ValueTooLarge`].
+    fn put(&mut self, mut record: Record) -> StoreResult<()> {
+        if let Err(e) = self.validate(&record) {
+            debug!("Refusing DHT record: {}", e);
+            return Err(StoreError::ValueTooLarge);
+        }
+
+        let previous = self
+            .inner
+            .get(&record.key)
+            .map_or(0, |existing| existing.value.len());
+        let total = self.total_bytes - previous + record.value.len();
+        if total > self.config.max_total_bytes {
+            return Err(StoreError::MaxRecords);
+        }
+
+        record.expires = self.clamp_expiry(record.expires);
+        self.inner.put(record.clone())?;
+        self.total_bytes = total;
+
+        if record.publisher == Some(self.local_id) {
+            self.published_at
+                .insert(record.key.clone(), SystemTime::now());
+        } else {
+            self.published_at.remove(&record.key);
+        }
+        self.persist_record(&record);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &RecordKey) {
+        if let Some(record) = self.inner.get(key) {
+            self.total_bytes -= record.value.len();
+        }
+        self.inner.remove(key);
+        self.published_at.remove(key);
+        if let Some(path) = self.entry_path(RECORDS_DIR, key) {
+            self.queue_remove(path);
+        }
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.inner.records()
+    }
+
+    fn add_provider(&mut self, mut record: ProviderRecord) -> StoreResult<()> {
+        record.expires = self.clamp_expiry(record.expires);
+        let key = record.key.clone();
+        self.inner.add_provider(record)?;
+        self.provider_keys.insert(key.clone());
+        self.persist_providers(&key);
+        Ok(())
+    }
+
+    fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+        let now = Instant::now();
+        self.inner
+            .providers(key)
+            .into_iter()
+            .filter(|record| !record.is_expired(now))
+            .collect()
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.inner.provided()
+    }
+
+    fn remove_provider(&mut self, key: &RecordKey, provider: &PeerId) {
+        self.inner.remove_provider(key, provider);
+        if self.inner.providers(key).is_empty() {
+            self.provider_keys.remove(key);
+        }
+        self.persist_providers(key);
+    }
+}
+
+fn entry_paths(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "tmp") {
+            remove_entry(&path);
+        } else if path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+fn read_entry<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, RecordStoreError> {
+    let bytes = fs::read(path)?;
+    bincode::deserialize(&bytes).map_err(|e| RecordStoreError::Encoding(e.to_string()))
+}
+
+/// Write through a temporary file so a crash never leaves a partial entry
+fn write_entry(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path)
+}
+
+fn remove_entry(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            warn!("Failed to remove DHT entry {:?}: {}", path, e);
+        }
+    }
+}
+
+fn system_to_unix(at: &SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn unix_to_system(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn instant_to_unix(at: Instant) -> u64 {
+    let now = Instant::now();
+    let unix_now = system_to_unix(&SystemTime::now());
+    if at >= now {
+        unix_now + (at - now).as_secs()
+    } else {
+        unix_now.saturating_sub((now - at).as_secs())
+    }
+}
+
+fn unix_to_instant(secs: u64) -> Instant {
+    let now = Instant::now();
+    let unix_now = system_to_unix(&SystemTime::now());
+    if secs >= unix_now {
+        now + Duration::from_secs(secs - unix_now)
+    } else {
+        now.checked_sub(Duration::from_secs(unix_now - secs))
+            .unwrap_or(now)
+    }
+}
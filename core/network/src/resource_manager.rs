@@ -0,0 +1,738 @@
+//! Resource limits for the P2P node.
+//!
+//! Apart from the 1 MB check in `SecureConnection::send`, nothing used to
+//! stop a peer from flooding a node. The [`ResourceManager`] keeps limits
+//! per peer, per protocol and for the node as a whole:
+//!
+//! * Token buckets cap the rate of gossip messages, requests, DHT queries,
+//!   onion cells and identity requests, for each peer and overall.
+//! * Inbound requests hold a stream until the application answers them;
+//!   streams are capped per peer and overall.
+//! * Request payloads waiting for an answer count against a memory budget,
+//!   again per peer and overall.
+//! * [`ResourceLimiter`] caps connections overall, per peer, per IP address
+//!   and per subnet.
+//!
+//! Messages over a limit are dropped. Violations of per-peer limits are
+//! reported to [`PeerScoring`] as [`PeerEvent::LimitExceeded`], so peers
+//! that keep flooding end up banned.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use libp2p::core::{multiaddr::Protocol, Endpoint};
+use libp2p::swarm::behaviour::{ConnectionClosed, ConnectionEstablished};
+use libp2p::swarm::{
+    dummy, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use libp2p::{Multiaddr, PeerId};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::peer_scoring::{PeerEvent, PeerScoring};
+
+/// Errors from resource limits
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ResourceError {
+    #[error("Peer {peer} exceeded the {protocol:?} rate limit")]
+    RateLimited {
+        peer: PeerId,
+        protocol: ResourceProtocol,
+    },
+    #[error("Node-wide {0:?} rate limit exceeded")]
+    GlobalRateLimited(ResourceProtocol),
+    #[error("{protocol:?} message of {size} bytes exceeds the limit of {limit}")]
+    TooLarge {
+        protocol: ResourceProtocol,
+        size: usize,
+        limit: usize,
+    },
+    #[error("Too many {protocol:?} streams open")]
+    TooManyStreams {
+        peer: Option<PeerId>,
+        protocol: ResourceProtocol,
+    },
+    #[error("Memory budget exhausted")]
+    MemoryExhausted { peer: Option<PeerId> },
+    #[error("Connection limit reached: {0}")]
+    TooManyConnections(String),
+}
+
+impl ResourceError {
+    /// The peer that broke a per-peer limit, if it was one
+    pub fn offender(&self) -> Option<PeerId> {
+        match self {
+            ResourceError::RateLimited { peer, .. } => Some(*peer),
+            ResourceError::TooManyStreams { peer, .. } => *peer,
+            ResourceError::MemoryExhausted { peer } => *peer,
+            _ => None,
+        }
+    }
+}
+
+/// Protocols with their own limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceProtocol {
+    /// Gossipsub messages
+    Gossip,
+    /// Application requests
+    Request,
+    /// Kademlia queries
+    Dht,
+    /// Onion cells
+    Onion,
+    /// Identity certificate requests
+    Identity,
+}
+
+impl ResourceProtocol {
+    fn label(&self) -> &'static str {
+        match self {
+            ResourceProtocol::Gossip => "gossip",
+            ResourceProtocol::Request => "request",
+            ResourceProtocol::Dht => "dht",
+            ResourceProtocol::Onion => "onion",
+            ResourceProtocol::Identity => "identity",
+        }
+    }
+}
+
+/// Sustained rate and burst of a token bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Messages per second
+    pub per_second: f64,
+    /// Messages allowed at once
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub const fn new(per_second: f64, burst: u32) -> Self {
+        Self { per_second, burst }
+    }
+}
+
+/// Limits of one protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolLimits {
+    /// Rate of messages from each peer
+    pub peer_rate: RateLimit,
+    /// Rate of messages from all peers together
+    pub global_rate: RateLimit,
+    /// Streams each peer may hold open
+    pub max_streams_per_peer: usize,
+    /// Streams all peers together may hold open
+    pub max_streams: usize,
+    /// Largest message accepted
+    pub max_message_size: usize,
+}
+
+impl ProtocolLimits {
+    fn new(peer_rate: RateLimit, global_rate: RateLimit, max_message_size: usize) -> Self {
+        Self {
+            peer_rate,
+            global_rate,
+            max_streams_per_peer: usize::MAX,
+            max_streams: usize::MAX,
+            max_message_size,
+        }
+    }
+}
+
+/// Limits enforced by [`ResourceManager`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceConfig {
+    pub gossip: ProtocolLimits,
+    pub request: ProtocolLimits,
+    pub dht: ProtocolLimits,
+    pub onion: ProtocolLimits,
+    pub identity: ProtocolLimits,
+    /// Connections overall
+    pub max_connections: usize,
+    /// Connections to one peer
+    pub max_connections_per_peer: usize,
+    /// Inbound connections from one IP address
+    pub max_connections_per_ip: usize,
+    /// Inbound connections from one subnet
+    pub max_connections_per_subnet: usize,
+    /// Prefix length of an IPv4 subnet
+    pub ipv4_subnet_prefix: u8,
+    /// Prefix length of an IPv6 subnet
+    pub ipv6_subnet_prefix: u8,
+    /// Bytes of unanswered requests held for one peer
+    pub memory_per_peer: usize,
+    /// Bytes of unanswered requests held overall
+    pub memory_total: usize,
+}
+
+impl Default for ResourceConfig {
+    fn default() -> Self {
+        Self {
+            gossip: ProtocolLimits::new(
+                RateLimit::new(100.0, 200),
+                RateLimit::new(1000.0, 2000),
+                64 * 1024,
+            ),
+            request: ProtocolLimits {
+                max_streams_per_peer: 32,
+                max_streams: 256,
+                ..ProtocolLimits::new(
+                    RateLimit::new(50.0, 100),
+                    RateLimit::new(500.0, 1000),
+                    1024 * 1024,
+                )
+            },
+            dht: ProtocolLimits::new(
+                RateLimit::new(50.0, 100),
+                RateLimit::new(500.0, 1000),
+                64 * 1024,
+            ),
+            onion: ProtocolLimits::new(
+                RateLimit::new(200.0, 400),
+                RateLimit::new(2000.0, 4000),
+                256 * 1024,
+            ),
+            identity: ProtocolLimits::new(RateLimit::new(1.0, 5), RateLimit::new(50.0, 100), 1024),
+            max_connections: 50,
+            max_connections_per_peer: 4,
+            max_connections_per_ip: 8,
+            max_connections_per_subnet: 16,
+            ipv4_subnet_prefix: 24,
+            ipv6_subnet_prefix: 64,
+            memory_per_peer: 16 * 1024 * 1024,
+            memory_total: 128 * 1024 * 1024,
+        }
+    }
+}
+
+impl ResourceConfig {
+    /// Limits of a protocol
+    pub fn limits(&self, protocol: ResourceProtocol) -> &ProtocolLimits {
+        match protocol {
+            ResourceProtocol::Gossip => &self.gossip,
+            ResourceProtocol::Request => &self.request,
+            ResourceProtocol::Dht => &self.dht,
+            ResourceProtocol::Onion => &self.onion,
+            ResourceProtocol::Identity => &self.identity,
+        }
+    }
+}
+
+/// Usage and drops since the manager was created
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    /// Open connections
+    pub connections: usize,
+    /// Open inbound request streams
+    pub streams: usize,
+    /// Bytes of unanswered requests
+    pub memory: usize,
+    /// Messages dropped over a limit
+    pub throttled: u64,
+    /// Connections refused over a limit
+    pub denied_connections: u64,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            refilled: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.refilled = now;
+    }
+
+    fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.limit.burst as f64
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerUsage {
+    buckets: HashMap<ResourceProtocol, TokenBucket>,
+    streams: HashMap<ResourceProtocol, usize>,
+    memory: usize,
+    connections: usize,
+}
+
+impl PeerUsage {
+    fn is_idle(&mut self, now: Instant) -> bool {
+        self.connections == 0
+            && self.memory == 0
+            && self.streams.values().all(|streams| *streams == 0)
+            && self.buckets.values_mut().all(|bucket| bucket.is_full(now))
+    }
+}
+
+#[derive(Debug)]
+struct Usage {
+    config: ResourceConfig,
+    peers: HashMap<PeerId, PeerUsage>,
+    global_buckets: HashMap<ResourceProtocol, TokenBucket>,
+    streams: HashMap<ResourceProtocol, usize>,
+    memory: usize,
+    /// Open connections and the IP address of inbound ones
+    connections: HashMap<ConnectionId, (PeerId, Option<IpAddr>)>,
+    throttled: u64,
+    denied_connections: u64,
+}
+
+impl Usage {
+    fn admit(
+        &mut self,
+        peer: PeerId,
+        protocol: ResourceProtocol,
+        size: usize,
+        now: Instant,
+    ) -> Result<(), ResourceError> {
+        let limits = self.config.limits(protocol).clone();
+        if size > limits.max_message_size {
+            return Err(ResourceError::TooLarge {
+                protocol,
+                size,
+                limit: limits.max_message_size,
+            });
+        }
+        let peer_bucket = self
+            .peers
+            .entry(peer)
+            .or_default()
+            .buckets
+            .entry(protocol)
+            .or_insert_with(|| TokenBucket::new(limits.peer_rate, now));
+        if !peer_bucket.has_token(now) {
+            return Err(ResourceError::RateLimited { peer, protocol });
+        }
+        let global_bucket = self
+            .global_buckets
+            .entry(protocol)
+            .or_insert_with(|| TokenBucket::new(limits.global_rate, now));
+        if !global_bucket.has_token(now) {
+            return Err(ResourceError::GlobalRateLimited(protocol));
+        }
+        global_bucket.tokens -= 1.0;
+        if let Some(bucket) = self
+            .peers
+            .get_mut(&peer)
+            .and_then(|usage| usage.buckets.get_mut(&protocol))
+        {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    fn open_stream(
+        &mut self,
+        peer: PeerId,
+        protocol: ResourceProtocol,
+        memory: usize,
+    ) -> Result<(), ResourceError> {
+        let limits = self.config.limits(protocol);
+        let (max_streams_per_peer, max_streams) = (limits.max_streams_per_peer, limits.max_streams);
+        let usage = self.peers.entry(peer).or_default();
+        let peer_streams = usage.streams.get(&protocol).copied().unwrap_or(0);
+        if peer_streams >= max_streams_per_peer {
+            return Err(ResourceError::TooManyStreams {
+                peer: Some(peer),
+                protocol,
+            });
+        }
+        if usage.memory + memory > self.config.memory_per_peer {
+            return Err(ResourceError::MemoryExhausted { peer: Some(peer) });
+        }
+        if self.streams.get(&protocol).copied().unwrap_or(0) >= max_streams {
+            return Err(ResourceError::TooManyStreams {
+                peer: None,
+                protocol,
+            });
+        }
+        if self.memory + memory > self.config.memory_total {
+            return Err(ResourceError::MemoryExhausted { peer: None });
+        }
+        *usage.streams.entry(protocol).or_default() += 1;
+        usage.memory += memory;
+        *self.streams.entry(protocol).or_default() += 1;
+        self.memory += memory;
+        Ok(())
+    }
+
+    fn close_stream(&mut self, peer: PeerId, protocol: ResourceProtocol, memory: usize) {
+        if let Some(usage) = self.peers.get_mut(&peer) {
+            if let Some(streams) = usage.streams.get_mut(&protocol) {
+                *streams = streams.saturating_sub(1);
+            }
+            usage.memory = usage.memory.saturating_sub(memory);
+        }
+        if let Some(streams) = self.streams.get_mut(&protocol) {
+            *streams = streams.saturating_sub(1);
+        }
+        self.memory = self.memory.saturating_sub(memory);
+    }
+
+    fn subnet(&self, ip: IpAddr) -> IpAddr {
+        match ip {
+            IpAddr::V4(ip) => {
+                let prefix = self.config.ipv4_subnet_prefix.min(32) as u32;
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                IpAddr::V4((u32::from(ip) & mask).into())
+            }
+            IpAddr::V6(ip) => {
+                let prefix = self.config.ipv6_subnet_prefix.min(128) as u32;
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                IpAddr::V6((u128::from(ip) & mask).into())
+            }
+        }
+    }
+
+    /// Check whether one more connection, from `ip` if inbound, fits
+    fn check_connection(&self, ip: Option<IpAddr>) -> Result<(), ResourceError> {
+        if self.connections.len() >= self.config.max_connections {
+            return Err(ResourceError::TooManyConnections(format!(
+                "{} connections open",
+                self.connections.len()
+            )));
+        }
+        let Some(ip) = ip else {
+            return Ok(());
+        };
+        let from_ip = self
+            .connections
+            .values()
+            .filter(|(_, other)| *other == Some(ip))
+            .count();
+        if from_ip >= self.config.max_connections_per_ip {
+            return Err(ResourceError::TooManyConnections(format!(
+                "{} connections from {}",
+                from_ip, ip
+            )));
+        }
+        let subnet = self.subnet(ip);
+        let from_subnet = self
+            .connections
+            .values()
+            .filter(|(_, other)| other.is_some_and(|other| self.subnet(other) == subnet))
+            .count();
+        if from_subnet >= self.config.max_connections_per_subnet {
+            return Err(ResourceError::TooManyConnections(format!(
+                "{} connections from subnet {}",
+                from_subnet, subnet
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_peer_connection(&self, peer: &PeerId) -> Result<(), ResourceError> {
+        let open = self.peers.get(peer).map_or(0, |usage| usage.connections);
+        if open >= self.config.max_connections_per_peer {
+            return Err(ResourceError::TooManyConnections(format!(
+                "{} connections to {}",
+                open, peer
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Shared per-peer, per-protocol and global resource usage
+///
+/// Clones refer to the same usage.
+#[derive(Debug, Clone)]
+pub struct ResourceManager {
+    usage: Arc<Mutex<Usage>>,
+    scoring: Option<PeerScoring>,
+}
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        Self::new(ResourceConfig::default())
+    }
+}
+
+impl ResourceManager {
+    /// Create a manager enforcing `config`
+    pub fn new(config: ResourceConfig) -> Self {
+        Self {
+            usage: Arc::new(Mutex::new(Usage {
+                config,
+                peers: HashMap::new(),
+                global_buckets: HashMap::new(),
+                streams: HashMap::new(),
+                memory: 0,
+                connections: HashMap::new(),
+                throttled: 0,
+                denied_connections: 0,
+            })),
+            scoring: None,
+        }
+    }
+
+    /// Report violations of per-peer limits to `scoring`
+    pub fn with_scoring(mut self, scoring: PeerScoring) -> Self {
+        self.scoring = Some(scoring);
+        self
+    }
+
+    /// The limits enforced
+    pub fn config(&self) -> ResourceConfig {
+        self.lock().config.clone()
+    }
+
+    /// Take a message of `size` bytes from a peer if the limits allow it
+    pub fn admit(
+        &self,
+        peer: PeerId,
+        protocol: ResourceProtocol,
+        size: usize,
+    ) -> Result<(), ResourceError> {
+        let result = self.lock().admit(peer, protocol, size, Instant::now());
+        self.settle(protocol, result)
+    }
+
+    /// Hold a stream for a peer, and `memory` bytes of its request, until
+    /// the returned permit is dropped
+    ///
+    /// The messages making up the request are [admitted](Self::admit)
+    /// separately.
+    pub fn open_stream(
+        &self,
+        peer: PeerId,
+        protocol: ResourceProtocol,
+        memory: usize,
+    ) -> Result<StreamPermit, ResourceError> {
+        let result = self.lock().open_stream(peer, protocol, memory);
+        self.settle(protocol, result)?;
+        Ok(StreamPermit {
+            usage: self.usage.clone(),
+            peer,
+            protocol,
+            memory,
+        })
+    }
+
+    /// Current usage and drops
+    pub fn stats(&self) -> ResourceStats {
+        let usage = self.lock();
+        ResourceStats {
+            connections: usage.connections.len(),
+            streams: usage.streams.values().sum(),
+            memory: usage.memory,
+            throttled: usage.throttled,
+            denied_connections: usage.denied_connections,
+        }
+    }
+
+    /// Forget idle peers whose buckets have refilled
+    pub fn prune(&self) {
+        let mut usage = self.lock();
+        let now = Instant::now();
+        usage.peers.retain(|_, peer| !peer.is_idle(now));
+        metrics::gauge!("qudag_resource_memory_bytes", usage.memory as f64);
+        metrics::gauge!(
+            "qudag_resource_streams",
+            usage.streams.values().sum::<usize>() as f64
+        );
+    }
+
+    /// Count a drop and report the peer that caused it
+    fn settle(
+        &self,
+        protocol: ResourceProtocol,
+        result: Result<(), ResourceError>,
+    ) -> Result<(), ResourceError> {
+        let Err(e) = result else {
+            return Ok(());
+        };
+        self.lock().throttled += 1;
+        metrics::counter!("qudag_resource_throttled_total", 1, "protocol" => protocol.label());
+        debug!("Dropped {:?} message: {}", protocol, e);
+        if let (Some(peer), Some(scoring)) = (e.offender(), &self.scoring) {
+            scoring.report(peer, PeerEvent::LimitExceeded);
+        }
+        Err(e)
+    }
+
+    fn deny_connection(&self, e: ResourceError) -> ConnectionDenied {
+        self.lock().denied_connections += 1;
+        metrics::counter!("qudag_resource_denied_connections_total", 1);
+        debug!("Refused connection: {}", e);
+        ConnectionDenied::new(e)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Usage> {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A stream and memory held for an inbound request
+///
+/// Released when dropped.
+#[derive(Debug)]
+pub struct StreamPermit {
+    usage: Arc<Mutex<Usage>>,
+    peer: PeerId,
+    protocol: ResourceProtocol,
+    memory: usize,
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        self.usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .close_stream(self.peer, self.protocol, self.memory);
+    }
+}
+
+/// Network behaviour enforcing the connection limits of a
+/// [`ResourceManager`]
+pub struct ResourceLimiter {
+    resources: ResourceManager,
+}
+
+impl ResourceLimiter {
+    /// Enforce the limits of `resources`
+    pub fn new(resources: ResourceManager) -> Self {
+        Self { resources }
+    }
+
+    /// The manager whose limits are enforced
+    pub fn resources(&self) -> &ResourceManager {
+        &self.resources
+    }
+
+    fn check(&self, peer: Option<&PeerId>, ip: Option<IpAddr>) -> Result<(), ConnectionDenied> {
+        let result = {
+            let usage = self.resources.lock();
+            usage.check_connection(ip).and_then(|()| match peer {
+                Some(peer) => usage.check_peer_connection(peer),
+                None => Ok(()),
+            })
+        };
+        result.map_err(|e| self.resources.deny_connection(e))
+    }
+}
+
+impl NetworkBehaviour for ResourceLimiter {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = void::Void;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.check(None, ip_of(remote_addr))
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.check(Some(&peer), ip_of(remote_addr))?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: Option<PeerId>,
+        _: &[Multiaddr],
+        _: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.check(None, None)?;
+        Ok(Vec::new())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        peer: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.check(Some(&peer), None)?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                ..
+            }) => {
+                // Only inbound connections count against IP and subnet caps
+                let ip = endpoint
+                    .is_listener()
+                    .then(|| ip_of(endpoint.get_remote_address()))
+                    .flatten();
+                let mut usage = self.resources.lock();
+                usage.connections.insert(connection_id, (peer_id, ip));
+                usage.peers.entry(peer_id).or_default().connections += 1;
+            }
+            FromSwarm::ConnectionClosed(ConnectionClosed {
+                peer_id,
+                connection_id,
+                ..
+            }) => {
+                let mut usage = self.resources.lock();
+                if usage.connections.remove(&connection_id).is_some() {
+                    if let Some(peer) = usage.peers.get_mut(&peer_id) {
+                        peer.connections = peer.connections.saturating_sub(1);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        void::unreachable(event)
+    }
+
+    fn poll(&mut self, _: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        Poll::Pending
+    }
+}
+
+/// IP address a multiaddr starts with
+pub(crate) fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.iter().next()? {
+        Protocol::Ip4(ip) => Some(ip.into()),
+        Protocol::Ip6(ip) => Some(ip.into()),
+        _ => None,
+    }
+}
@@ -3,6 +3,7 @@
 //! This module implements a stealth address system that allows generating
 //! one-time addresses for anonymous communication.
 
+use qudag_crypto::MlKem768;
 use rand::{thread_rng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -15,6 +16,17 @@ use tokio::sync::{Mutex, RwLock};
 use tokio::time::interval;
 use x25519_dalek::{EphemeralSecret, PublicKey};
 
+use crate::pq_stealth;
+
+/// Version of plain shadow addresses
+pub const SHADOW_VERSION_PLAIN: u8 = 1;
+
+/// Version of legacy stealth addresses derived with X25519
+pub const SHADOW_VERSION_STEALTH_X25519: u8 = 2;
+
+/// Version of stealth addresses derived with ML-KEM and spent with ML-DSA
+pub const SHADOW_VERSION_STEALTH_ML_KEM: u8 = 3;
+
 /// Errors that can occur during shadow address operations.
 #[derive(Debug, Error)]
 pub enum ShadowAddressError {
@@ -33,6 +45,10 @@ pub enum ShadowAddressError {
     /// Cryptographic operation failed
     #[error("Cryptographic error: {0}")]
     CryptoError(String),
+
+    /// Address version not supported or refused
+    #[error("Unsupported address version: {0}")]
+    UnsupportedVersion(u8),
 }
 
 /// Shadow address components for stealth address generation.
@@ -237,6 +253,9 @@ pub struct DefaultShadowAddressHandler {
     /// Current derivation counter
     #[allow(dead_code)]
     derivation_counter: Mutex<u32>,
+
+    /// Lowest stealth version this handler pays with
+    min_stealth_version: u8,
 }
 
 impl DefaultShadowAddressHandler {
@@ -246,7 +265,102 @@ impl DefaultShadowAddressHandler {
             network,
             master_seed,
             derivation_counter: Mutex::new(0),
+            min_stealth_version: SHADOW_VERSION_STEALTH_X25519,
+        }
+    }
+
+    /// Refuse to pay with stealth versions below `version`
+    ///
+    /// Set it to [`SHADOW_VERSION_STEALTH_ML_KEM`] to stop falling back to
+    /// X25519 for recipients with legacy keys.
+    pub fn with_min_stealth_version(mut self, version: u8) -> Self {
+        self.min_stealth_version = version;
+        self
+    }
+
+    /// Stealth version to pay a recipient's address with
+    ///
+    /// Version 3 meta-addresses carry ML-KEM and ML-DSA keys; older
+    /// addresses carry 32-byte keys and get a legacy X25519 address.
+    pub fn negotiate_stealth_version(
+        &self,
+        recipient: &ShadowAddress,
+    ) -> Result<u8, ShadowAddressError> {
+        let version = match recipient.metadata.version {
+            SHADOW_VERSION_STEALTH_ML_KEM => SHADOW_VERSION_STEALTH_ML_KEM,
+            SHADOW_VERSION_PLAIN | SHADOW_VERSION_STEALTH_X25519 => SHADOW_VERSION_STEALTH_X25519,
+            other => return Err(ShadowAddressError::UnsupportedVersion(other)),
+        };
+        if version < self.min_stealth_version {
+            return Err(ShadowAddressError::UnsupportedVersion(version));
         }
+        Ok(version)
+    }
+
+    /// Generate a one-time address for a recipient's address
+    ///
+    /// The version is negotiated from the recipient's metadata and recorded
+    /// in the metadata of the result.
+    pub fn generate_stealth_address_for(
+        &self,
+        recipient: &ShadowAddress,
+    ) -> Result<ShadowAddress, ShadowAddressError> {
+        let network = recipient.metadata.network;
+        match self.negotiate_stealth_version(recipient)? {
+            SHADOW_VERSION_STEALTH_ML_KEM => pq_stealth::generate_stealth_address(
+                network,
+                &recipient.view_key,
+                &recipient.spend_key,
+            ),
+            _ => self.generate_legacy_stealth_address(
+                network,
+                &recipient.view_key,
+                &recipient.spend_key,
+            ),
+        }
+    }
+
+    /// Generate a version 2 stealth address from X25519 keys.
+    fn generate_legacy_stealth_address(
+        &self,
+        network: NetworkType,
+        recipient_view_key: &[u8],
+        recipient_spend_key: &[u8],
+    ) -> Result<ShadowAddress, ShadowAddressError> {
+        let (stealth_view_key, stealth_spend_key, ephemeral_pubkey) =
+            self.generate_stealth_keys(recipient_view_key, recipient_spend_key)?;
+
+        let current_time = Self::current_timestamp();
+
+        // Generate stealth prefix for efficient scanning
+        let mut hasher = Sha256::new();
+        hasher.update(ephemeral_pubkey);
+        let hash = hasher.finalize();
+        let stealth_prefix = [hash[0], hash[1], hash[2], hash[3]];
+
+        Ok(ShadowAddress {
+            view_key: stealth_view_key,
+            spend_key: stealth_spend_key,
+            payment_id: Some(ephemeral_pubkey),
+            metadata: ShadowMetadata {
+                version: SHADOW_VERSION_STEALTH_X25519,
+                network,
+                expires_at: None,
+                created_at: current_time,
+                last_used: None,
+                flags: 0x02, // Stealth flag
+                ttl: None,
+                usage_count: 0,
+                max_uses: Some(1), // One-time use
+            },
+            shadow_features: ShadowFeatures {
+                is_temporary: false,
+                derivation_index: None,
+                stealth_prefix: Some(stealth_prefix),
+                mixing_enabled: true,
+                pool_id: None,
+            },
+        })
     }
 
     /// Generate a random 32-byte seed.
@@ -335,7 +449,7 @@ impl ShadowAddressGenerator for DefaultShadowAddressHandler {
             spend_key,
             payment_id: None,
             metadata: ShadowMetadata {
-                version: 1,
+                version: SHADOW_VERSION_PLAIN,
                 network,
                 expires_at: None,
                 created_at: current_time,
@@ -370,7 +484,7 @@ impl ShadowAddressGenerator for DefaultShadowAddressHandler {
             spend_key,
             payment_id: None,
             metadata: ShadowMetadata {
-                version: 1,
+                version: SHADOW_VERSION_PLAIN,
                 network,
                 expires_at: Some(expires_at),
                 created_at: current_time,
@@ -396,40 +510,23 @@ impl ShadowAddressGenerator for DefaultShadowAddressHandler {
         recipient_view_key: &[u8],
         recipient_spend_key: &[u8],
     ) -> Result<ShadowAddress, ShadowAddressError> {
-        let (stealth_view_key, stealth_spend_key, ephemeral_pubkey) =
-            self.generate_stealth_keys(recipient_view_key, recipient_spend_key)?;
-
-        let current_time = Self::current_timestamp();
-
-        // Generate stealth prefix for efficient scanning
-        let mut hasher = Sha256::new();
-        hasher.update(&ephemeral_pubkey);
-        let hash = hasher.finalize();
-        let stealth_prefix = [hash[0], hash[1], hash[2], hash[3]];
-
-        Ok(ShadowAddress {
-            view_key: stealth_view_key,
-            spend_key: stealth_spend_key,
-            payment_id: Some(ephemeral_pubkey),
-            metadata: ShadowMetadata {
-                version: 2, // Version 2 for stealth addresses
+        // ML-KEM view keys select version 3, anything else the legacy scheme
+        let version = if recipient_view_key.len() == MlKem768::PUBLIC_KEY_SIZE {
+            SHADOW_VERSION_STEALTH_ML_KEM
+        } else {
+            SHADOW_VERSION_STEALTH_X25519
+        };
+        if version < self.min_stealth_version {
+            return Err(ShadowAddressError::UnsupportedVersion(version));
+        }
+        if version == SHADOW_VERSION_STEALTH_ML_KEM {
+            return pq_stealth::generate_stealth_address(
                 network,
-                expires_at: None,
-                created_at: current_time,
-                last_used: None,
-                flags: 0x02, // Stealth flag
-                ttl: None,
-                usage_count: 0,
-                max_uses: Some(1), // One-time use
-            },
-            shadow_features: ShadowFeatures {
-                is_temporary: false,
-                derivation_index: None,
-                stealth_prefix: Some(stealth_prefix),
-                mixing_enabled: true,
-                pool_id: None,
-            },
-        })
+                recipient_view_key,
+                recipient_spend_key,
+            );
+        }
+        self.generate_legacy_stealth_address(network, recipient_view_key, recipient_spend_key)
     }
 
     fn derive_address(&self, base: &ShadowAddress) -> Result<ShadowAddress, ShadowAddressError> {
@@ -477,7 +574,7 @@ impl ShadowAddressGenerator for DefaultShadowAddressHandler {
             spend_key,
             payment_id: None,
             metadata: ShadowMetadata {
-                version: 1,
+                version: SHADOW_VERSION_PLAIN,
                 network: self.network,
                 expires_at: None,
                 created_at: current_time,
@@ -499,7 +596,12 @@ impl ShadowAddressGenerator for DefaultShadowAddressHandler {
 
     fn validate_address(&self, address: &ShadowAddress) -> Result<bool, ShadowAddressError> {
         // Check key lengths
-        if address.view_key.len() != 32 || address.spend_key.len() != 32 {
+        let keys_valid = if address.metadata.version == SHADOW_VERSION_STEALTH_ML_KEM {
+            pq_stealth::is_one_time_address(address) || pq_stealth::is_meta_address(address)
+        } else {
+            address.view_key.len() == 32 && address.spend_key.len() == 32
+        };
+        if !keys_valid {
             return Ok(false);
         }
 
@@ -624,6 +726,15 @@ impl ShadowAddressManager {
         Ok(address)
     }
 
+    /// Create a stealth address for a recipient's address, negotiating the version.
+    pub async fn create_stealth_address_for(
+        &self,
+        recipient: &ShadowAddress,
+    ) -> Result<ShadowAddress, ShadowAddressError> {
+        let generator = self.generator.read().await;
+        generator.generate_stealth_address_for(recipient)
+    }
+
     /// Create an address pool for rotation.
     pub async fn create_address_pool(
         &self,
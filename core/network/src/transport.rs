@@ -1,31 +1,19 @@
-//! Network transport layer implementation with TLS 1.3 and post-quantum cryptography.
+//! Transport configuration and the record format of the secure transport.
 //!
-//! This module provides a production-ready transport layer featuring:
-//! - TLS 1.3 with post-quantum key exchange (ML-KEM)
-//! - Post-quantum authentication (ML-DSA)
-//! - Secure connection management with graceful degradation
-//! - Comprehensive error handling and logging
-//! - Performance optimizations for high-throughput scenarios
-//! - Integration with libp2p networking stack
-
-use crate::quantum_crypto::{MlKemSecurityLevel, QuantumKeyExchange, SharedSecret};
-use crate::traffic_obfuscation::{TrafficObfuscationConfig, TrafficObfuscator};
-use crate::types::{ConnectionStatus, NetworkError, PeerId};
-// use crate::p2p::{P2PNode, NetworkConfig as P2PConfig, P2PEvent};
-use dashmap::DashMap;
-use parking_lot::RwLock as ParkingRwLock;
-use quinn::Endpoint;
-use rustls::{ClientConfig, ServerConfig};
-use std::net::SocketAddr;
-use std::pin::Pin;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+//! Nodes connect through the single libp2p transport built by [`crate::p2p`]:
+//! the base transports, the `/qudag/pq-noise` upgrade whose records are
+//! [`SecureFrame`]s, yamux, and the connection pool layer of
+//! [`crate::pooled_transport`]. [`utils::to_p2p_config`] maps a
+//! [`TransportConfig`] onto a node's configuration.
+
+use crate::p2p::NetworkConfig as P2PConfig;
+use crate::pluggable_transport::PluggableTransportConfig;
+use crate::pq_noise::SecurityProtocol;
+use crate::quantum_crypto::MlKemSecurityLevel;
+use crate::traffic_obfuscation::TrafficObfuscationConfig;
+use crate::types::NetworkError;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
-use tokio::time::timeout;
-use tracing::{debug, error, info, warn};
 
 /// Errors that can occur during transport operations.
 #[derive(Debug, Error)]
@@ -141,6 +129,10 @@ pub struct TransportConfig {
 
     /// Traffic obfuscation configuration
     pub traffic_obfuscation_config: TrafficObfuscationConfig,
+
+    /// Pluggable transport disguising TCP connections while traffic
+    /// obfuscation is enabled
+    pub pluggable_transport: Option<PluggableTransportConfig>,
 }
 
 impl Default for TransportConfig {
@@ -162,620 +154,17 @@ impl Default for TransportConfig {
             buffer_size: 64 * 1024, // 64KB
             enable_traffic_obfuscation: true,
             traffic_obfuscation_config: TrafficObfuscationConfig::default(),
+            pluggable_transport: None,
         }
     }
 }
 
-/// Async transport stream trait with additional metadata.
-pub trait AsyncTransport: AsyncRead + AsyncWrite + Send + Sync + Unpin {
-    /// Get the remote peer address
-    fn peer_addr(&self) -> Result<SocketAddr, TransportError>;
-
-    /// Get the local address
-    fn local_addr(&self) -> Result<SocketAddr, TransportError>;
-
-    /// Check if the connection is secure (TLS/QUIC)
-    fn is_secure(&self) -> bool;
-
-    /// Get connection metadata
-    fn metadata(&self) -> ConnectionMetadata;
-
-    /// Gracefully close the connection (sync version for trait object safety)
-    fn close_sync(&mut self) -> Result<(), TransportError>;
-}
-
-/// Connection metadata
-#[derive(Debug, Clone)]
-pub struct ConnectionMetadata {
-    /// Connection ID
-    pub connection_id: String,
-    /// Peer ID
-    pub peer_id: Option<PeerId>,
-    /// Connection status
-    pub status: ConnectionStatus,
-    /// Connection establishment time
-    pub established_at: Instant,
-    /// Last activity time
-    pub last_activity: Instant,
-    /// Bytes sent
-    pub bytes_sent: u64,
-    /// Bytes received
-    pub bytes_received: u64,
-    /// Is post-quantum secure
-    pub is_post_quantum: bool,
-    /// TLS version
-    pub tls_version: Option<String>,
-}
-
-/// Network transport trait defining the interface for transport operations.
-#[async_trait::async_trait]
-pub trait Transport: Send + Sync {
-    /// Initialize transport with configuration.
-    async fn init(&mut self, config: TransportConfig) -> Result<(), TransportError>;
-
-    /// Start listening on the specified address.
-    async fn listen(&mut self, addr: SocketAddr) -> Result<(), TransportError>;
-
-    /// Create a new connection to a remote peer.
-    async fn connect(
-        &mut self,
-        addr: SocketAddr,
-    ) -> Result<Box<dyn AsyncTransport + Send + Sync>, TransportError>;
-
-    /// Accept an incoming connection.
-    async fn accept(&mut self) -> Result<Box<dyn AsyncTransport + Send + Sync>, TransportError>;
-
-    /// Close a specific connection.
-    async fn close_connection(&mut self, connection_id: &str) -> Result<(), TransportError>;
-
-    /// Get active connections.
-    fn get_connections(&self) -> Vec<ConnectionMetadata>;
-
-    /// Get transport statistics.
-    fn get_stats(&self) -> TransportStats;
-
-    /// Shutdown the transport.
-    async fn shutdown(&mut self) -> Result<(), TransportError>;
-}
-
-/// Transport statistics
-#[derive(Debug, Clone, Default)]
-pub struct TransportStats {
-    /// Total connections established
-    pub total_connections: u64,
-    /// Current active connections
-    pub active_connections: usize,
-    /// Total bytes sent
-    pub total_bytes_sent: u64,
-    /// Total bytes received
-    pub total_bytes_received: u64,
-    /// Connection errors
-    pub connection_errors: u64,
-    /// Handshake failures
-    pub handshake_failures: u64,
-    /// Post-quantum handshakes
-    pub post_quantum_handshakes: u64,
-    /// Average connection duration
-    pub avg_connection_duration: Duration,
-}
-
-/// Secure transport implementation with TLS 1.3 and post-quantum crypto
-pub struct SecureTransport {
-    /// Transport configuration
-    config: TransportConfig,
-    /// TCP listener for incoming connections
-    listener: Option<Arc<Mutex<TcpListener>>>,
-    /// QUIC endpoint for QUIC connections
-    quic_endpoint: Option<Arc<Mutex<Endpoint>>>,
-    /// Active connections
-    connections: Arc<DashMap<String, Arc<Mutex<Box<dyn AsyncTransport + Send + Sync>>>>>,
-    /// Connection metadata
-    connection_metadata: Arc<DashMap<String, ConnectionMetadata>>,
-    /// TLS client configuration
-    tls_client_config: Option<Arc<ClientConfig>>,
-    /// TLS server configuration
-    #[allow(dead_code)]
-    tls_server_config: Option<Arc<ServerConfig>>,
-    /// Quantum key exchange instance
-    quantum_kex: Arc<Mutex<QuantumKeyExchange>>,
-    /// Transport statistics
-    stats: Arc<ParkingRwLock<TransportStats>>,
-    /// Connection ID counter
-    connection_counter: Arc<std::sync::atomic::AtomicU64>,
-    /// P2P node for libp2p integration (temporarily disabled for compilation)
-    // p2p_node: Option<Arc<Mutex<P2PNode>>>,
-    /// Traffic obfuscator
-    traffic_obfuscator: Option<Arc<TrafficObfuscator>>,
-}
-
-// Ensure SecureTransport is Send + Sync
-unsafe impl Send for SecureTransport {}
-unsafe impl Sync for SecureTransport {}
-
-impl SecureTransport {
-    /// Create a new secure transport instance
-    pub fn new() -> Self {
-        Self {
-            config: TransportConfig::default(),
-            listener: None,
-            quic_endpoint: None,
-            connections: Arc::new(DashMap::new()),
-            connection_metadata: Arc::new(DashMap::new()),
-            tls_client_config: None,
-            tls_server_config: None,
-            quantum_kex: Arc::new(Mutex::new(QuantumKeyExchange::with_security_level(
-                MlKemSecurityLevel::Level768,
-            ))),
-            stats: Arc::new(ParkingRwLock::new(TransportStats::default())),
-            connection_counter: Arc::new(std::sync::atomic::AtomicU64::new(0)),
-            // p2p_node: None,
-            traffic_obfuscator: None,
-        }
-    }
-
-    /// Create a new secure transport with custom configuration
-    pub fn with_config(config: TransportConfig) -> Self {
-        let mut transport = Self::new();
-        transport.config = config.clone();
-        transport.quantum_kex = Arc::new(Mutex::new(QuantumKeyExchange::with_security_level(
-            config.ml_kem_security_level,
-        )));
-
-        // Initialize traffic obfuscator if enabled
-        if config.enable_traffic_obfuscation {
-            transport.traffic_obfuscator = Some(Arc::new(TrafficObfuscator::new(
-                config.traffic_obfuscation_config.clone(),
-            )));
-        }
-
-        transport
-    }
-
-    /// Setup TLS configuration
-    async fn setup_tls_config(&mut self) -> Result<(), TransportError> {
-        if !self.config.use_tls {
-            return Ok(());
-        }
-
-        info!("Setting up TLS configuration");
-
-        // Setup client configuration
-        let client_config = ClientConfig::builder()
-            .with_root_certificates(self.load_ca_certificates()?)
-            .with_no_client_auth();
-
-        // Enable post-quantum cipher suites if available
-        if self.config.use_post_quantum {
-            debug!("Enabling post-quantum cipher suites");
-            // Note: This would require custom cipher suite implementation
-            // For now, we'll use standard TLS 1.3 with post-quantum key exchange
-        }
-
-        self.tls_client_config = Some(Arc::new(client_config));
-
-        // For now, skip server certificate setup
-        // In production, this would load actual certificates
-
-        info!("TLS configuration completed successfully");
-        Ok(())
-    }
-
-    /// Load CA certificates
-    fn load_ca_certificates(&self) -> Result<rustls::RootCertStore, TransportError> {
-        let mut root_store = rustls::RootCertStore::empty();
-
-        // Use system root certificates from webpki_roots
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-
-        Ok(root_store)
-    }
-
-    /// Load certificate chain from file (placeholder)
-    #[allow(dead_code)]
-    fn load_certificate_chain(
-        &self,
-        _cert_path: &str,
-    ) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>, TransportError> {
-        // For now, return an error since we don't have actual certificates
-        Err(TransportError::ConfigurationError(
-            "Certificate loading not implemented".to_string(),
-        ))
-    }
-
-    /// Load private key from file (placeholder)
-    #[allow(dead_code)]
-    fn load_private_key(
-        &self,
-        _key_path: &str,
-    ) -> Result<rustls::pki_types::PrivateKeyDer<'static>, TransportError> {
-        // For now, return an error since we don't have actual private keys
-        Err(TransportError::ConfigurationError(
-            "Private key loading not implemented".to_string(),
-        ))
-    }
-
-    /// Setup QUIC endpoint if enabled
-    async fn setup_quic_endpoint(&mut self) -> Result<(), TransportError> {
-        if !self.config.use_quic {
-            return Ok(());
-        }
-
-        info!("Setting up QUIC endpoint");
-
-        // QUIC setup would go here
-        // For now, we'll skip QUIC implementation
-        warn!("QUIC support not yet implemented");
-
-        Ok(())
-    }
-
-    /// Generate a unique connection ID
-    fn generate_connection_id(&self) -> String {
-        let id = self
-            .connection_counter
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        format!("conn_{}", id)
-    }
-
-    /// Perform post-quantum key exchange (placeholder)
-    #[allow(dead_code)]
-    async fn perform_post_quantum_handshake(&self) -> Result<SharedSecret, TransportError> {
-        if !self.config.use_post_quantum {
-            return Err(TransportError::PostQuantumError(
-                "Post-quantum crypto disabled".to_string(),
-            ));
-        }
-
-        debug!("Performing post-quantum handshake (placeholder)");
-
-        // For now, return a dummy shared secret
-        // In production, this would perform the full ML-KEM exchange
-        let dummy_secret = crate::quantum_crypto::SharedSecret {
-            secret: vec![0u8; 32],
-        };
-
-        Ok(dummy_secret)
-    }
-
-    /// Update connection statistics
-    #[allow(dead_code)]
-    fn update_stats(&self, bytes_sent: u64, bytes_received: u64) {
-        let mut stats = self.stats.write();
-        stats.total_bytes_sent += bytes_sent;
-        stats.total_bytes_received += bytes_received;
-    }
-
-    /// Clean up inactive connections
-    #[allow(dead_code)]
-    async fn cleanup_connections(&self) {
-        let now = Instant::now();
-        let timeout = Duration::from_secs(300); // 5 minutes
-
-        let mut to_remove = Vec::new();
-
-        for entry in self.connection_metadata.iter() {
-            let metadata = entry.value();
-            if now.duration_since(metadata.last_activity) > timeout {
-                to_remove.push(entry.key().clone());
-            }
-        }
-
-        for conn_id in to_remove {
-            debug!("Cleaning up inactive connection: {}", conn_id);
-            self.connections.remove(&conn_id);
-            self.connection_metadata.remove(&conn_id);
-        }
-    }
-
-    // Enable P2P networking layer (temporarily disabled)
-    // pub async fn enable_p2p(&mut self) -> Result<(), TransportError> {
-    //     let p2p_config = utils::to_p2p_config(&self.config);
-    //     let mut p2p_node = P2PNode::new(p2p_config).await
-    //         .map_err(|e| TransportError::ConfigurationError(format!("P2P setup failed: {}", e)))?;
-    //
-    //     // Start the P2P node
-    //     p2p_node.start().await
-    //         .map_err(|e| TransportError::ConfigurationError(format!("P2P start failed: {}", e)))?;
-    //
-    //     self.p2p_node = Some(Arc::new(Mutex::new(p2p_node)));
-    //
-    //     info!("P2P networking layer enabled");
-    //     Ok(())
-    // }
-    //
-    // /// Get P2P node reference
-    // pub fn p2p_node(&self) -> Option<Arc<Mutex<P2PNode>>> {
-    //     self.p2p_node.clone()
-    // }
-}
-
-#[async_trait::async_trait]
-impl Transport for SecureTransport {
-    async fn init(&mut self, config: TransportConfig) -> Result<(), TransportError> {
-        info!("Initializing secure transport with config: {:?}", config);
-
-        self.config = config.clone();
-
-        // Setup TLS configuration
-        self.setup_tls_config().await?;
-
-        // Setup QUIC endpoint if enabled
-        self.setup_quic_endpoint().await?;
-
-        // Initialize quantum key exchange
-        if self.config.use_post_quantum {
-            let mut quantum_kex = self.quantum_kex.lock().await;
-            quantum_kex.initialize().map_err(|e| {
-                TransportError::PostQuantumError(format!("Failed to initialize quantum KEX: {}", e))
-            })?;
-        }
-
-        // Initialize traffic obfuscator
-        if config.enable_traffic_obfuscation {
-            let obfuscator = Arc::new(TrafficObfuscator::new(
-                config.traffic_obfuscation_config.clone(),
-            ));
-            obfuscator.start().await;
-            self.traffic_obfuscator = Some(obfuscator);
-            info!("Traffic obfuscation enabled");
-        }
-
-        info!("Secure transport initialized successfully");
-        Ok(())
-    }
-
-    async fn listen(&mut self, addr: SocketAddr) -> Result<(), TransportError> {
-        info!("Starting to listen on address: {}", addr);
-
-        let listener = TcpListener::bind(addr).await.map_err(|e| {
-            TransportError::ConnectionFailed(format!("Failed to bind to {}: {}", addr, e))
-        })?;
-
-        self.listener = Some(Arc::new(Mutex::new(listener)));
-
-        info!("Successfully listening on {}", addr);
-        Ok(())
-    }
-
-    async fn connect(
-        &mut self,
-        addr: SocketAddr,
-    ) -> Result<Box<dyn AsyncTransport + Send + Sync>, TransportError> {
-        debug!("Connecting to {}", addr);
-
-        // Check connection limit
-        if self.connections.len() >= self.config.max_connections {
-            return Err(TransportError::ConnectionLimitExceeded {
-                current: self.connections.len(),
-                max: self.config.max_connections,
-            });
-        }
-
-        // Establish TCP connection with timeout
-        let tcp_stream = timeout(self.config.connection_timeout, TcpStream::connect(addr))
-            .await
-            .map_err(|_| TransportError::HandshakeTimeout(self.config.connection_timeout))?
-            .map_err(|e| {
-                TransportError::ConnectionFailed(format!("TCP connection failed: {}", e))
-            })?;
-
-        // For now, return a simple TCP transport
-        // In a full implementation, this would handle TLS and post-quantum setup
-        let transport = TcpTransport::new(tcp_stream, self.generate_connection_id());
-
-        // Register the connection
-        let conn_id = transport.metadata().connection_id.clone();
-        let metadata = transport.metadata();
-
-        self.connection_metadata.insert(conn_id.clone(), metadata);
-
-        // Update stats
-        let mut stats = self.stats.write();
-        stats.total_connections += 1;
-        stats.active_connections = self.connections.len();
-
-        info!("Successfully connected to {} (conn_id: {})", addr, conn_id);
-
-        Ok(Box::new(transport))
-    }
-
-    async fn accept(&mut self) -> Result<Box<dyn AsyncTransport + Send + Sync>, TransportError> {
-        let listener = self.listener.as_ref().ok_or_else(|| {
-            TransportError::ConfigurationError("Transport not listening".to_string())
-        })?;
-
-        // Accept incoming connection
-        let (tcp_stream, peer_addr) = listener.lock().await.accept().await.map_err(|e| {
-            TransportError::ConnectionFailed(format!("Failed to accept connection: {}", e))
-        })?;
-
-        debug!("Accepted connection from {}", peer_addr);
-
-        // Check connection limit
-        if self.connections.len() >= self.config.max_connections {
-            return Err(TransportError::ConnectionLimitExceeded {
-                current: self.connections.len(),
-                max: self.config.max_connections,
-            });
-        }
-
-        // For now, return a simple TCP transport
-        let transport = TcpTransport::new(tcp_stream, self.generate_connection_id());
-
-        // Register the connection
-        let conn_id = transport.metadata().connection_id.clone();
-        let metadata = transport.metadata();
-
-        self.connection_metadata.insert(conn_id.clone(), metadata);
-
-        // Update stats
-        let mut stats = self.stats.write();
-        stats.total_connections += 1;
-        stats.active_connections = self.connections.len();
-
-        info!(
-            "Successfully accepted connection from {} (conn_id: {})",
-            peer_addr, conn_id
-        );
-
-        Ok(Box::new(transport))
-    }
-
-    async fn close_connection(&mut self, connection_id: &str) -> Result<(), TransportError> {
-        debug!("Closing connection: {}", connection_id);
-
-        if let Some((_, transport)) = self.connections.remove(connection_id) {
-            let mut transport = transport.lock().await;
-            transport.close_sync()?;
-        }
-
-        self.connection_metadata.remove(connection_id);
-
-        // Update stats
-        let mut stats = self.stats.write();
-        stats.active_connections = self.connections.len();
-
-        info!("Connection {} closed successfully", connection_id);
-        Ok(())
-    }
-
-    fn get_connections(&self) -> Vec<ConnectionMetadata> {
-        self.connection_metadata
-            .iter()
-            .map(|entry| entry.value().clone())
-            .collect()
-    }
-
-    fn get_stats(&self) -> TransportStats {
-        self.stats.read().clone()
-    }
-
-    async fn shutdown(&mut self) -> Result<(), TransportError> {
-        info!("Shutting down secure transport");
-
-        // Close all connections
-        let connection_ids: Vec<String> = self
-            .connections
-            .iter()
-            .map(|entry| entry.key().clone())
-            .collect();
-
-        for conn_id in connection_ids.iter() {
-            if let Err(e) = self.close_connection(conn_id).await {
-                warn!("Error closing connection {}: {}", conn_id, e);
-            }
-        }
-
-        // Close listener
-        self.listener = None;
-
-        // Close QUIC endpoint
-        if let Some(endpoint) = self.quic_endpoint.take() {
-            endpoint.lock().await.close(0u32.into(), b"shutdown");
-        }
-
-        info!("Secure transport shutdown completed");
-        Ok(())
-    }
-}
-
-/// TCP transport implementation
-struct TcpTransport {
-    stream: TcpStream,
-    #[allow(dead_code)]
-    connection_id: String,
-    metadata: ConnectionMetadata,
-}
-
-// Ensure TcpTransport is Send + Sync (TcpStream already is)
-unsafe impl Send for TcpTransport {}
-unsafe impl Sync for TcpTransport {}
-
-impl TcpTransport {
-    fn new(stream: TcpStream, connection_id: String) -> Self {
-        let metadata = ConnectionMetadata {
-            connection_id: connection_id.clone(),
-            peer_id: None,
-            status: ConnectionStatus::Connected,
-            established_at: Instant::now(),
-            last_activity: Instant::now(),
-            bytes_sent: 0,
-            bytes_received: 0,
-            is_post_quantum: false,
-            tls_version: None,
-        };
-
-        Self {
-            stream,
-            connection_id,
-            metadata,
-        }
-    }
-}
-
-impl AsyncRead for TcpTransport {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &mut tokio::io::ReadBuf<'_>,
-    ) -> std::task::Poll<std::io::Result<()>> {
-        Pin::new(&mut self.stream).poll_read(cx, buf)
-    }
-}
-
-impl AsyncWrite for TcpTransport {
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &[u8],
-    ) -> std::task::Poll<Result<usize, std::io::Error>> {
-        Pin::new(&mut self.stream).poll_write(cx, buf)
-    }
-
-    fn poll_flush(
-        mut self: Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), std::io::Error>> {
-        Pin::new(&mut self.stream).poll_flush(cx)
-    }
-
-    fn poll_shutdown(
-        mut self: Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), std::io::Error>> {
-        Pin::new(&mut self.stream).poll_shutdown(cx)
-    }
-}
-
-impl AsyncTransport for TcpTransport {
-    fn peer_addr(&self) -> Result<SocketAddr, TransportError> {
-        self.stream.peer_addr().map_err(|e| {
-            TransportError::ConnectionFailed(format!("Failed to get peer address: {}", e))
-        })
-    }
-
-    fn local_addr(&self) -> Result<SocketAddr, TransportError> {
-        self.stream.local_addr().map_err(|e| {
-            TransportError::ConnectionFailed(format!("Failed to get local address: {}", e))
-        })
-    }
-
-    fn is_secure(&self) -> bool {
-        false
-    }
-
-    fn metadata(&self) -> ConnectionMetadata {
-        self.metadata.clone()
-    }
-
-    fn close_sync(&mut self) -> Result<(), TransportError> {
-        // For sync version, we can't use async shutdown
-        // In a real implementation, this might use blocking I/O or store state for later cleanup
-        Ok(())
-    }
-}
-
 /// Message framing for secure transport
+///
+/// This is the record format of the `/qudag/pq-noise` upgrade
+/// ([`crate::pq_noise`]): the payload is ChaCha20-Poly1305 ciphertext, the
+/// header is authenticated with it, and the sequence number doubles as the
+/// nonce counter of its direction.
 #[derive(Debug, Clone)]
 pub struct SecureFrame {
     /// Frame length
@@ -797,6 +186,12 @@ impl SecureFrame {
     /// Frame header size
     pub const HEADER_SIZE: usize = 4 + 1 + 8 + 16; // length + type + sequence + tag
 
+    /// Size of the fields in front of the payload
+    pub const PREFIX_SIZE: usize = 4 + 1 + 8; // length + type + sequence
+
+    /// Frame carrying application data
+    pub const DATA: u8 = 0;
+
     /// Create a new secure frame
     pub fn new(frame_type: u8, sequence: u64, payload: Vec<u8>) -> Self {
         Self {
@@ -808,37 +203,55 @@ impl SecureFrame {
         }
     }
 
-    /// Serialize frame to bytes
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(Self::HEADER_SIZE + self.payload.len());
-        bytes.extend_from_slice(&self.length.to_be_bytes());
-        bytes.push(self.frame_type);
-        bytes.extend_from_slice(&self.sequence.to_be_bytes());
-        bytes.extend_from_slice(&self.payload);
-        bytes.extend_from_slice(&self.auth_tag);
-        bytes
+    /// Length, type and sequence number as they precede the payload
+    pub fn prefix(&self) -> [u8; Self::PREFIX_SIZE] {
+        let mut prefix = [0u8; Self::PREFIX_SIZE];
+        prefix[..4].copy_from_slice(&self.length.to_be_bytes());
+        prefix[4] = self.frame_type;
+        prefix[5..].copy_from_slice(&self.sequence.to_be_bytes());
+        prefix
     }
 
-    /// Deserialize frame from bytes
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TransportError> {
-        if bytes.len() < Self::HEADER_SIZE {
+    /// Parse the fields in front of the payload into length, type and
+    /// sequence number
+    pub fn parse_prefix(bytes: &[u8]) -> Result<(u32, u8, u64), TransportError> {
+        if bytes.len() < Self::PREFIX_SIZE {
             return Err(TransportError::InvalidMessageFormat(
                 "Frame too short".to_string(),
             ));
         }
 
         let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-
         if length > Self::MAX_FRAME_SIZE {
             return Err(TransportError::InvalidMessageFormat(
                 "Frame too large".to_string(),
             ));
         }
 
-        let frame_type = bytes[4];
         let sequence = u64::from_be_bytes([
             bytes[5], bytes[6], bytes[7], bytes[8], bytes[9], bytes[10], bytes[11], bytes[12],
         ]);
+        Ok((length, bytes[4], sequence))
+    }
+
+    /// Serialize frame to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::HEADER_SIZE + self.payload.len());
+        bytes.extend_from_slice(&self.prefix());
+        bytes.extend_from_slice(&self.payload);
+        bytes.extend_from_slice(&self.auth_tag);
+        bytes
+    }
+
+    /// Deserialize frame from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TransportError> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(TransportError::InvalidMessageFormat(
+                "Frame too short".to_string(),
+            ));
+        }
+
+        let (length, frame_type, sequence) = Self::parse_prefix(bytes)?;
 
         let payload_end = 13 + length as usize;
         if bytes.len() < payload_end + 16 {
@@ -898,43 +311,35 @@ pub mod utils {
         }
     }
 
-    // Convert transport config to P2P config (temporarily disabled)
-    // pub fn to_p2p_config(transport_config: &TransportConfig) -> P2PConfig {
-    //     let mut obfuscation_key = [0u8; 32];
-    //     thread_rng().fill_bytes(&mut obfuscation_key);
-    //
-    //     P2PConfig {
-    //         listen_addrs: vec![
-    //             "/ip4/0.0.0.0/tcp/0".to_string(),
-    //             "/ip6/::/tcp/0".to_string(),
-    //         ],
-    //         bootstrap_peers: vec![],
-    //         timeout: transport_config.connection_timeout,
-    //         max_connections: transport_config.max_connections,
-    //         obfuscation_key,
-    //         enable_mdns: true,
-    //         enable_relay: true,
-    //         enable_quic: transport_config.use_quic,
-    //         enable_websocket: true,
-    //         gossipsub_config: None,
-    //         kad_replication_factor: 20,
-    //     }
-    // }
+    /// Configuration of a [`crate::p2p::P2PNode`] equivalent to a transport
+    /// configuration
+    ///
+    /// The node secures connections with the `/qudag/pq-noise` upgrade when
+    /// post-quantum cryptography is enabled, and disguises them with the
+    /// pluggable transport when traffic obfuscation is enabled.
+    pub fn to_p2p_config(transport_config: &TransportConfig) -> P2PConfig {
+        P2PConfig {
+            timeout: transport_config.connection_timeout,
+            max_connections: transport_config.max_connections,
+            enable_quic: transport_config.use_quic,
+            security: if transport_config.use_post_quantum {
+                SecurityProtocol::PqNoise
+            } else {
+                SecurityProtocol::Noise
+            },
+            pluggable_transport: transport_config
+                .pluggable_transport
+                .clone()
+                .filter(|_| transport_config.enable_traffic_obfuscation),
+            ..Default::default()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_transport_initialization() {
-        let mut transport = SecureTransport::new();
-        let config = utils::test_config();
-
-        let result = transport.init(config).await;
-        assert!(result.is_ok());
-    }
-
     #[tokio::test]
     async fn test_secure_frame() {
         let payload = b"test payload".to_vec();
@@ -955,23 +360,4 @@ mod tests {
         assert!(config.use_post_quantum);
         assert_eq!(config.max_connections, 1000);
     }
-
-    #[test]
-    fn test_connection_metadata() {
-        let metadata = ConnectionMetadata {
-            connection_id: "test_conn".to_string(),
-            peer_id: Some(PeerId::random()),
-            status: ConnectionStatus::Connected,
-            established_at: Instant::now(),
-            last_activity: Instant::now(),
-            bytes_sent: 1024,
-            bytes_received: 2048,
-            is_post_quantum: true,
-            tls_version: Some("TLS 1.3".to_string()),
-        };
-
-        assert_eq!(metadata.connection_id, "test_conn");
-        assert!(metadata.is_post_quantum);
-        assert_eq!(metadata.tls_version, Some("TLS 1.3".to_string()));
-    }
 }
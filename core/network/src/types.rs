@@ -218,6 +218,13 @@ impl PeerId {
     }
 }
 
+impl From<&libp2p::PeerId> for PeerId {
+    /// Digest of a libp2p peer ID, whose encoding can exceed 32 bytes
+    fn from(peer_id: &libp2p::PeerId) -> Self {
+        Self(*blake3::hash(&peer_id.to_bytes()).as_bytes())
+    }
+}
+
 impl std::fmt::Display for PeerId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Format as truncated hex string for readability (first 8 bytes)
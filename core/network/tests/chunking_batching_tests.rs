@@ -0,0 +1,320 @@
+//! Tests for chunked requests and adaptive gossip batching, on their own
+//! and through the P2P node.
+
+use std::time::Duration;
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId as LibP2PPeerId};
+use qudag_network::optimized::{AdaptiveBatcher, BatchConfig, ChunkerConfig, MessageChunker};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode, QuDagRequest};
+use qudag_network::types::{MessagePriority, NetworkError, NetworkMessage};
+use rand::{thread_rng, Rng, RngCore};
+
+fn message(id: &str, payload: Vec<u8>) -> NetworkMessage {
+    NetworkMessage {
+        id: id.to_string(),
+        source: vec![1],
+        destination: vec![2],
+        payload,
+        priority: MessagePriority::Normal,
+        ttl: Duration::from_secs(60),
+    }
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    thread_rng().fill_bytes(&mut data);
+    data
+}
+
+fn low_entropy_bytes(len: usize) -> Vec<u8> {
+    random_bytes(len).into_iter().map(|b| b % 4).collect()
+}
+
+fn small_chunks() -> ChunkerConfig {
+    ChunkerConfig {
+        max_chunk_size: 1024,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_chunks_reassemble_per_sender() {
+    let sender = MessageChunker::new(small_chunks());
+    let receiver = MessageChunker::new(small_chunks());
+
+    // Incompressible data is sent as is, compressible data compressed
+    for (id, payload) in [
+        ("random", random_bytes(5000)),
+        ("low-entropy", low_entropy_bytes(50_000)),
+    ] {
+        let chunks = sender
+            .chunk_message(&message(id, payload.clone()))
+            .await
+            .unwrap();
+        assert_eq!(chunks[0].header.compressed, id == "low-entropy");
+
+        // Two senders may use the same message ID
+        let (last, rest) = chunks.split_last().unwrap();
+        for chunk in rest {
+            assert!(receiver
+                .process_chunk_from("a", chunk.clone())
+                .await
+                .unwrap()
+                .is_none());
+            receiver
+                .process_chunk_from("b", chunk.clone())
+                .await
+                .unwrap();
+        }
+        let reassembled = receiver
+            .process_chunk_from("a", last.clone())
+            .await
+            .unwrap();
+        assert_eq!(reassembled.unwrap(), payload);
+
+        // A retransmitted chunk does not deliver the message again
+        assert!(receiver
+            .process_chunk_from("a", last.clone())
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(receiver.get_stats().await.active_reassemblies, 1);
+        let reassembled = receiver
+            .process_chunk_from("b", last.clone())
+            .await
+            .unwrap();
+        assert_eq!(reassembled.unwrap(), payload);
+
+        let stats = receiver.get_stats().await;
+        assert_eq!(stats.active_reassemblies, 0);
+        assert_eq!(stats.buffered_bytes, 0);
+    }
+
+    // Small payloads need no chunks
+    assert!(sender
+        .chunk_message(&message("small", vec![1; 100]))
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_reassembly_limits() {
+    let sender = MessageChunker::new(small_chunks());
+    let receiver = MessageChunker::new(ChunkerConfig {
+        max_reassembly_bytes: 4096,
+        max_message_size: 8192,
+        ..small_chunks()
+    });
+
+    // Buffered chunk data is capped across messages
+    for id in ["one", "two"] {
+        let chunks = sender
+            .chunk_message(&message(id, random_bytes(4000)))
+            .await
+            .unwrap();
+        for chunk in &chunks[..2] {
+            receiver
+                .process_chunk_from("peer", chunk.clone())
+                .await
+                .unwrap();
+        }
+    }
+    let chunks = sender
+        .chunk_message(&message("three", random_bytes(4000)))
+        .await
+        .unwrap();
+    assert!(matches!(
+        receiver.process_chunk_from("peer", chunks[0].clone()).await,
+        Err(NetworkError::ContentTooLarge)
+    ));
+
+    // Messages over the size limit are refused by sender and receiver
+    let chunks = sender
+        .chunk_message(&message("huge", random_bytes(10_000)))
+        .await
+        .unwrap();
+    assert!(matches!(
+        receiver.process_chunk_from("peer", chunks[0].clone()).await,
+        Err(NetworkError::ContentTooLarge)
+    ));
+    assert!(matches!(
+        receiver
+            .chunk_message(&message("huge", random_bytes(10_000)))
+            .await,
+        Err(NetworkError::ContentTooLarge)
+    ));
+
+    // Chunks must agree with the first one seen
+    let mut forged = chunks[1].clone();
+    forged.header.message_id = "one".to_string();
+    assert!(receiver.process_chunk_from("peer", forged).await.is_err());
+}
+
+#[tokio::test]
+async fn test_stalled_reassembly_expires() {
+    let sender = MessageChunker::new(small_chunks());
+    let receiver = MessageChunker::new(ChunkerConfig {
+        chunk_timeout: Duration::from_millis(50),
+        ..small_chunks()
+    });
+    let chunks = sender
+        .chunk_message(&message("stalled", random_bytes(3000)))
+        .await
+        .unwrap();
+    receiver
+        .process_chunk_from("peer", chunks[0].clone())
+        .await
+        .unwrap();
+
+    assert_eq!(receiver.cleanup_expired().await, 0);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(receiver.cleanup_expired().await, 1);
+    assert_eq!(receiver.get_stats().await.buffered_bytes, 0);
+}
+
+#[test]
+fn test_batch_size_follows_arrival_rate() {
+    let config = BatchConfig {
+        base_latency_micros: 20_000,
+        max_latency_micros: 50_000,
+        ..Default::default()
+    };
+
+    // Messages arriving far apart go out on their own
+    let mut batcher = AdaptiveBatcher::new(config.clone());
+    assert!(batcher.add_message(0).is_none());
+    std::thread::sleep(batcher.time_until_flush().unwrap());
+    assert_eq!(batcher.flush_batch().len(), 1);
+    std::thread::sleep(Duration::from_millis(60));
+    for i in 1..4 {
+        assert_eq!(batcher.add_message(i).map(|batch| batch.len()), Some(1));
+        std::thread::sleep(Duration::from_millis(60));
+    }
+    assert_eq!(batcher.time_until_flush(), None);
+
+    // A burst is held until the latency target
+    let mut batcher = AdaptiveBatcher::new(config);
+    for i in 0..10 {
+        assert!(batcher.add_message(i).is_none());
+    }
+    assert!(batcher.time_until_flush().unwrap() <= Duration::from_millis(50));
+    std::thread::sleep(batcher.time_until_flush().unwrap());
+    assert!(batcher.should_flush());
+    assert_eq!(batcher.flush_batch().len(), 10);
+}
+
+async fn spawn_node(obfuscation_key: [u8; 32]) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key,
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+async fn connected_pair() -> (P2PHandle, P2PHandle, LibP2PPeerId) {
+    let key = rand::random();
+    let (server, server_addr) = spawn_node(key).await;
+    let (client, _) = spawn_node(key).await;
+    let server_id = server.local_peer_id().await;
+
+    client.dial(server_addr).await.unwrap();
+    for _ in 0..50 {
+        if client.connected_peers().await.contains(&server_id) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    (server, client, server_id)
+}
+
+#[tokio::test]
+async fn test_large_request_round_trip() {
+    let (server, client, server_id) = connected_pair().await;
+
+    // Reply with the size and hash of whatever arrives
+    tokio::spawn(async move {
+        while let Some(event) = server.next_event().await {
+            if let P2PEvent::RequestReceived {
+                request, channel, ..
+            } = event
+            {
+                let mut reply = (request.payload.len() as u64).to_le_bytes().to_vec();
+                reply.extend(blake3::hash(&request.payload).as_bytes());
+                let _ = channel.send(qudag_network::p2p::QuDagResponse {
+                    request_id: request.request_id,
+                    payload: reply,
+                });
+            }
+        }
+    });
+
+    // Well above the 1 MB request-response frame limit
+    for (id, payload) in [
+        ("small", b"ping".to_vec()),
+        ("large", random_bytes(3 << 20)),
+    ] {
+        let response = tokio::time::timeout(
+            Duration::from_secs(30),
+            client.send_request(
+                server_id,
+                QuDagRequest {
+                    request_id: id.to_string(),
+                    payload: payload.clone(),
+                },
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(response.request_id, id);
+        assert_eq!(response.payload[..8], (payload.len() as u64).to_le_bytes());
+        assert_eq!(response.payload[8..], blake3::hash(&payload).as_bytes()[..]);
+    }
+}
+
+#[tokio::test]
+async fn test_gossip_burst_is_batched() {
+    let (server, client, _) = connected_pair().await;
+    server.subscribe("batched").await.unwrap();
+    client.subscribe("batched").await.unwrap();
+    // Let subscriptions propagate
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let sent: Vec<Vec<u8>> = (0..50u32).map(|i| i.to_le_bytes().to_vec()).collect();
+    for data in &sent {
+        client.publish("batched", data.clone()).await.unwrap();
+    }
+
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        let mut received = Vec::new();
+        while received.len() < sent.len() {
+            if let Some(P2PEvent::MessageReceived { topic, data, .. }) = server.next_event().await {
+                assert_eq!(topic, "batched");
+                received.push(data);
+            }
+        }
+        received
+    })
+    .await
+    .unwrap();
+    assert_eq!(received, sent);
+}
@@ -0,0 +1,207 @@
+//! Tests for circuit relay v2: reservations on a relay with quotas, relayed
+//! addresses, and DCUtR replacing a relayed connection with a direct one.
+//!
+//! NAT is simulated by what the nodes know of each other: peers behind the
+//! NAT are only ever given relayed addresses, and the direct addresses
+//! DCUtR hole-punches to are the ones the relay observed them dialing from.
+//! Dials leave from the listen port, as through a NAT with endpoint
+//! independent mapping.
+
+use std::time::Duration;
+
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::circuit_relay::{circuit_listen_addr, is_relayed, RelayConfig};
+use qudag_network::p2p::{
+    NetworkConfig, P2PEvent, P2PHandle, P2PNode, QuDagRequest, QuDagResponse,
+};
+
+async fn spawn_node(relay: RelayConfig) -> P2PHandle {
+    let config = NetworkConfig {
+        listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".to_string()],
+        enable_mdns: false,
+        enable_websocket: false,
+        relay,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    handle
+}
+
+/// A relay serving reservations, and its address
+async fn spawn_relay(relay: RelayConfig) -> (P2PHandle, Multiaddr) {
+    let handle = spawn_node(RelayConfig {
+        server: true,
+        ..relay
+    })
+    .await;
+    let peer_id = handle.local_peer_id().await;
+    for _ in 0..50 {
+        if let Some(addr) = handle.listeners().await.into_iter().next() {
+            return (handle, addr.with(Protocol::P2p(peer_id)));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("relay is not listening");
+}
+
+/// A node behind a NAT reserving on `relays`
+async fn spawn_natted(relays: Vec<Multiaddr>) -> P2PHandle {
+    spawn_node(RelayConfig {
+        reserve: true,
+        relays,
+        ..Default::default()
+    })
+    .await
+}
+
+async fn wait_for(handle: &P2PHandle, mut matches: impl FnMut(&P2PEvent) -> bool) -> P2PEvent {
+    tokio::time::timeout(Duration::from_secs(20), async {
+        loop {
+            match handle.next_event().await {
+                Some(event) if matches(&event) => break event,
+                Some(_) => continue,
+                None => panic!("node stopped"),
+            }
+        }
+    })
+    .await
+    .expect("event not seen")
+}
+
+#[test]
+fn test_circuit_addresses() {
+    let relay = PeerId::random();
+    let tcp: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+    let expected: Multiaddr = format!("/ip4/1.2.3.4/tcp/4001/p2p/{}/p2p-circuit", relay)
+        .parse()
+        .unwrap();
+    assert_eq!(circuit_listen_addr(&tcp, relay), expected);
+    // A trailing peer ID is replaced by the relay's
+    assert_eq!(
+        circuit_listen_addr(&tcp.clone().with(Protocol::P2p(relay)), relay),
+        expected
+    );
+    assert!(is_relayed(&expected));
+    assert!(!is_relayed(&tcp));
+    assert!(NetworkConfig::default().supports(&expected));
+    assert!(!NetworkConfig {
+        enable_relay: false,
+        ..Default::default()
+    }
+    .supports(&expected));
+}
+
+#[tokio::test]
+async fn test_relay_enforces_reservation_quota() {
+    let (relay, relay_addr) = spawn_relay(RelayConfig {
+        max_reservations: 1,
+        ..Default::default()
+    })
+    .await;
+    let relay_id = relay.local_peer_id().await;
+
+    // A configured relay is reserved on at start
+    let first = spawn_natted(vec![relay_addr.clone()]).await;
+    let first_id = first.local_peer_id().await;
+    wait_for(
+        &first,
+        |e| matches!(e, P2PEvent::RelayReservationAccepted { relay } if *relay == relay_id),
+    )
+    .await;
+    assert_eq!(first.relay_reservations().await, vec![relay_id]);
+
+    // The relayed address is advertised as reachable
+    let relayed = first.external_addresses().await;
+    assert!(relayed.contains(
+        &relay_addr
+            .clone()
+            .with(Protocol::P2pCircuit)
+            .with(Protocol::P2p(first_id))
+    ));
+
+    // Without configured relays, a peer offering relaying is reserved on
+    // once identified; the relay's quota is used up, so it refuses
+    let second = spawn_natted(Vec::new()).await;
+    second.dial(relay_addr.clone()).await.unwrap();
+    wait_for(
+        &second,
+        |e| matches!(e, P2PEvent::RelayReservationClosed { relay } if *relay == relay_id),
+    )
+    .await;
+    assert!(second.relay_reservations().await.is_empty());
+    assert!(second
+        .external_addresses()
+        .await
+        .iter()
+        .all(|addr| !is_relayed(addr)));
+}
+
+#[tokio::test]
+async fn test_relayed_connection_is_upgraded_to_direct() {
+    let (relay, relay_addr) = spawn_relay(RelayConfig::default()).await;
+    let relay_id = relay.local_peer_id().await;
+
+    let natted = spawn_natted(vec![relay_addr.clone()]).await;
+    let natted_id = natted.local_peer_id().await;
+    wait_for(
+        &natted,
+        |e| matches!(e, P2PEvent::RelayReservationAccepted { relay } if *relay == relay_id),
+    )
+    .await;
+    // Only the relayed address is advertised as reachable
+    let advertised = natted.external_addresses().await;
+    assert!(!advertised.is_empty());
+    assert!(advertised.iter().all(is_relayed));
+
+    // Another peer learns the address it dials from through the relay
+    let dialer = spawn_node(RelayConfig::default()).await;
+    dialer.dial(relay_addr.clone()).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // It reaches the NATed node through the relay, then hole-punches to it
+    // and drops the circuit
+    let circuit = relay_addr
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(natted_id));
+    assert_eq!(dialer.connect(circuit).await.unwrap(), natted_id);
+    wait_for(
+        &dialer,
+        |e| matches!(e, P2PEvent::DirectConnectionUpgraded(peer) if *peer == natted_id),
+    )
+    .await;
+    assert!(dialer
+        .address_book()
+        .addresses(&natted_id)
+        .iter()
+        .any(|info| !is_relayed(&info.address)));
+
+    tokio::spawn(async move {
+        while let Some(event) = natted.next_event().await {
+            if let P2PEvent::RequestReceived {
+                request, channel, ..
+            } = event
+            {
+                let _ = channel.send(QuDagResponse {
+                    request_id: request.request_id,
+                    payload: request.payload,
+                });
+            }
+        }
+    });
+    let response = dialer
+        .send_request(
+            natted_id,
+            QuDagRequest {
+                request_id: "direct".to_string(),
+                payload: b"after the upgrade".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.payload, b"after the upgrade");
+}
@@ -0,0 +1,215 @@
+//! Tests for cover traffic: slot filling, Poisson timing and the bandwidth
+//! budget of the scheduler, and dummy cells between P2P nodes.
+
+use std::time::{Duration, Instant};
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::cover_traffic::{CoverLink, CoverMode, CoverScheduler, CoverTrafficConfig};
+use qudag_network::p2p::{NetworkConfig, P2PHandle, P2PNode};
+use rand::{thread_rng, Rng};
+
+fn constant_rate(interval: Duration) -> CoverTrafficConfig {
+    CoverTrafficConfig {
+        connections: Some(CoverMode::ConstantRate { interval }),
+        circuits: Some(CoverMode::ConstantRate { interval }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_constant_rate_fills_idle_slots() {
+    let mut scheduler = CoverScheduler::new(constant_rate(Duration::from_millis(50)));
+    let peer = CoverLink::Connection(PeerId::random());
+    let circuit = CoverLink::Circuit(7);
+    assert!(scheduler.add_link(peer));
+    assert!(scheduler.add_link(circuit));
+    assert!(scheduler.poll_due(Instant::now()).is_empty());
+
+    // An idle slot gets a dummy on every link
+    let slot_end = scheduler.next_deadline().unwrap() + Duration::from_millis(1);
+    let mut due = scheduler.poll_due(slot_end);
+    due.sort_by_key(|link| matches!(link, CoverLink::Circuit(_)));
+    assert_eq!(due, vec![peer, circuit]);
+
+    // A slot with real traffic needs none
+    scheduler.record_real(peer, 300);
+    let slot_end = slot_end + Duration::from_millis(51);
+    assert_eq!(scheduler.poll_due(slot_end), vec![circuit]);
+    let slot_end = slot_end + Duration::from_millis(51);
+    assert_eq!(scheduler.poll_due(slot_end).len(), 2);
+
+    let stats = scheduler.stats();
+    assert_eq!(stats.real_cells, 1);
+    assert_eq!(stats.real_bytes, 300);
+    assert_eq!(stats.cover_cells, 5);
+    assert_eq!(stats.cover_bytes, 5 * 512);
+    assert_eq!(stats.links, 2);
+    assert_eq!(stats.real_to_cover_ratio(), Some(300.0 / 2560.0));
+
+    // Removed links and unknown links are not scheduled
+    scheduler.remove_link(&peer);
+    scheduler.record_real(peer, 100);
+    assert_eq!(scheduler.stats().real_cells, 1);
+    assert_eq!(
+        scheduler.poll_due(slot_end + Duration::from_millis(51)),
+        vec![circuit]
+    );
+}
+
+#[test]
+fn test_unconfigured_links_get_no_cover() {
+    let config = CoverTrafficConfig {
+        circuits: Some(CoverMode::Poisson {
+            mean_interval: Duration::from_millis(10),
+        }),
+        ..Default::default()
+    };
+    let mut scheduler = CoverScheduler::new(config);
+    assert!(!scheduler.add_link(CoverLink::Connection(PeerId::random())));
+    assert!(scheduler.add_link(CoverLink::Circuit(1)));
+    assert_eq!(scheduler.stats().links, 1);
+    assert_eq!(scheduler.stats().real_to_cover_ratio(), None);
+
+    let disabled = CoverScheduler::new(CoverTrafficConfig::default());
+    assert!(!disabled.config().is_enabled());
+    assert_eq!(disabled.next_deadline(), None);
+}
+
+#[test]
+fn test_poisson_dummies_ignore_real_traffic() {
+    let mean = Duration::from_millis(100);
+    let config = CoverTrafficConfig {
+        connections: Some(CoverMode::Poisson {
+            mean_interval: mean,
+        }),
+        bandwidth_budget: u64::MAX / 2,
+        burst: u64::MAX / 2,
+        ..Default::default()
+    };
+    let mut scheduler = CoverScheduler::new(config);
+    let link = CoverLink::Connection(PeerId::random());
+    scheduler.add_link(link);
+
+    let start = scheduler.next_deadline().unwrap();
+    let mut now = start;
+    let mut intervals = Vec::new();
+    for _ in 0..2000 {
+        scheduler.record_real(link, 64);
+        assert_eq!(scheduler.poll_due(now), vec![link]);
+        let next = scheduler.next_deadline().unwrap();
+        intervals.push(next - now);
+        now = next;
+    }
+
+    // Exponential intervals: the mean is close to the configured one and
+    // the spread is as wide as the mean
+    let mean_ms = intervals.iter().map(Duration::as_secs_f64).sum::<f64>() * 1000.0 / 2000.0;
+    assert!((80.0..120.0).contains(&mean_ms), "mean {}ms", mean_ms);
+    let short = intervals
+        .iter()
+        .filter(|interval| **interval < mean / 10)
+        .count();
+    assert!(short > 100, "only {} short intervals", short);
+    assert_eq!(scheduler.stats().cover_cells, 2000);
+}
+
+#[test]
+fn test_budget_suppresses_cover() {
+    let config = CoverTrafficConfig {
+        cell_size: 100,
+        bandwidth_budget: 1000,
+        burst: 250,
+        ..constant_rate(Duration::from_millis(10))
+    };
+    let mut scheduler = CoverScheduler::new(config);
+    for _ in 0..4 {
+        scheduler.add_link(CoverLink::Connection(PeerId::random()));
+    }
+
+    // The burst pays for two dummies; the other two links stay silent
+    let slot_end = Instant::now() + Duration::from_millis(11);
+    assert_eq!(scheduler.poll_due(slot_end).len(), 2);
+    assert_eq!(scheduler.stats().suppressed, 2);
+
+    // 1000 bytes per second refill one dummy every 100ms
+    let later = slot_end + Duration::from_millis(105);
+    assert_eq!(scheduler.poll_due(later).len(), 1);
+    let stats = scheduler.stats();
+    assert_eq!(stats.cover_bytes, 300);
+    assert_eq!(stats.suppressed, 5);
+}
+
+async fn spawn_node(cover_traffic: CoverTrafficConfig) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key: [7u8; 32],
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        cover_traffic,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+#[tokio::test]
+async fn test_cover_between_nodes() {
+    let (client, _) = spawn_node(constant_rate(Duration::from_millis(50))).await;
+    let (relay, relay_addr) = spawn_node(CoverTrafficConfig::default()).await;
+    let relay_id = relay.local_peer_id().await;
+    client.dial(relay_addr).await.unwrap();
+
+    // Idle connections carry dummies
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let stats = client.cover_stats().await;
+    assert_eq!(stats.links, 1);
+    assert!(stats.cover_cells >= 5, "{:?}", stats);
+    assert_eq!(relay.cover_stats().await.cover_cells, 0);
+
+    for _ in 0..100 {
+        if client.known_relays().await.contains(&relay_id) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let circuit_id = tokio::time::timeout(
+        Duration::from_secs(30),
+        client.build_circuit_to(1, relay_id),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(client.cover_stats().await.links, 2);
+
+    // The relay drops circuit dummies and still delivers real payloads
+    let before = client.cover_stats().await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        client.send_onion_message(circuit_id, None, b"real".to_vec()),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let after = client.cover_stats().await;
+    assert!(after.cover_cells > before.cover_cells);
+    assert!(after.real_bytes > before.real_bytes);
+    assert!(after.real_to_cover_ratio().unwrap() > 0.0);
+
+    client.destroy_circuit(circuit_id).await.unwrap();
+    assert_eq!(client.cover_stats().await.links, 1);
+}
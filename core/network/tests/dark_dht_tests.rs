@@ -0,0 +1,398 @@
+//! Tests for resolving `.dark` domains through the DHT: choosing among
+//! conflicting records, expiry and lifetime bounds, republishing and
+//! resolution across nodes.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use libp2p::Multiaddr;
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+use qudag_network::dark_resolver::{
+    DarkDomainRecord, DarkResolver, DarkResolverError, DhtClient, IntroductionPoint,
+    DARK_RECORD_VERSION_LEGACY, MAX_RECORD_TTL,
+};
+use qudag_network::p2p::{NetworkConfig, P2PHandle, P2PNode};
+use qudag_network::types::{NetworkAddress, PeerId};
+use rand::{thread_rng, Rng};
+
+/// DHT keeping every value put under a key, like peers holding diverging copies
+#[derive(Default)]
+struct MemoryDht {
+    values: Mutex<HashMap<Vec<u8>, Vec<Vec<u8>>>>,
+    puts: Mutex<usize>,
+}
+
+impl MemoryDht {
+    fn seed(&self, domain: &str, record: &DarkDomainRecord) {
+        self.values
+            .lock()
+            .unwrap()
+            .entry(DarkResolver::dht_key(domain))
+            .or_default()
+            .push(bincode::serialize(record).unwrap());
+    }
+}
+
+#[async_trait]
+impl DhtClient for MemoryDht {
+    async fn put(&self, key: &[u8], value: &[u8], _ttl: Duration) -> Result<(), DarkResolverError> {
+        *self.puts.lock().unwrap() += 1;
+        self.values
+            .lock()
+            .unwrap()
+            .entry(key.to_vec())
+            .or_default()
+            .push(value.to_vec());
+        Ok(())
+    }
+
+    async fn get(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, DarkResolverError> {
+        Ok(self
+            .values
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+fn keypair() -> MlDsaKeyPair {
+    MlDsaKeyPair::generate(&mut thread_rng()).unwrap()
+}
+
+fn record(signer: &MlDsaKeyPair, domain: &str, sequence: u64, port: u16) -> DarkDomainRecord {
+    let mut record = DarkDomainRecord::new(
+        signer,
+        vec![0u8; 1184],
+        vec![NetworkAddress::new([10, 0, 0, 1], port)],
+        None,
+        3600,
+        PeerId::random(),
+    )
+    .unwrap();
+    record.bind_to_domain(signer, domain, sequence).unwrap();
+    record
+}
+
+fn derived_domain(keypair: &MlDsaKeyPair) -> String {
+    DarkResolver::generate_dark_address(keypair.public_key(), None)
+        .unwrap()
+        .domain
+}
+
+#[tokio::test]
+async fn test_owner_latest_record_wins() {
+    let owner = keypair();
+    let attacker = keypair();
+    let domain = derived_domain(&owner);
+    let dht = Arc::new(MemoryDht::default());
+
+    dht.seed(&domain, &record(&owner, &domain, 1, 1));
+    dht.seed(&domain, &record(&owner, &domain, 3, 3));
+    dht.seed(&domain, &record(&owner, &domain, 2, 2));
+    // Higher sequence, but not the key the name is derived from
+    dht.seed(&domain, &record(&attacker, &domain, 99, 99));
+    // Tampered after signing
+    let mut forged = record(&owner, &domain, 5, 5);
+    forged.addresses = vec![NetworkAddress::new([6, 6, 6, 6], 666)];
+    dht.seed(&domain, &forged);
+    // Valid record for another name replayed under this one
+    dht.seed(&domain, &record(&owner, "other.dark", 7, 7));
+
+    let resolver = DarkResolver::with_dht(dht);
+    let found = resolver.resolve_domain(&domain).await.unwrap();
+    assert_eq!(found.signing_public_key, owner.public_key());
+    assert_eq!(found.sequence, 3);
+    assert_eq!(found.addresses[0].port, 3);
+
+    // Cached for local lookups afterwards
+    assert_eq!(resolver.lookup_domain(&domain).unwrap().sequence, 3);
+}
+
+#[tokio::test]
+async fn test_first_seen_owner_keeps_custom_name() {
+    let first = keypair();
+    let squatter = keypair();
+    let domain = "shared.dark";
+    let dht = Arc::new(MemoryDht::default());
+
+    // The resolver saw the first owner's record, which has since expired
+    let mut original = record(&first, domain, 0, 1);
+    original.registered_at -= 7200;
+    original.expires_at = original.registered_at + 3600;
+    original.bind_to_domain(&first, domain, 0).unwrap();
+    let resolver = DarkResolver::with_dht(dht.clone());
+    resolver.register_record(original).unwrap();
+
+    // A squatter claims an earlier registration than the owner's update
+    let update = record(&first, domain, 4, 2);
+    let mut squat = record(&squatter, domain, 10, 3);
+    squat.registered_at -= 3000;
+    squat.bind_to_domain(&squatter, domain, 10).unwrap();
+    dht.seed(domain, &update);
+    dht.seed(domain, &squat);
+
+    let found = resolver.resolve_domain(domain).await.unwrap();
+    assert_eq!(found.signing_public_key, first.public_key());
+    assert_eq!(found.sequence, 4);
+
+    // A resolver without a pin does not let self-reported times decide
+    let fresh = DarkResolver::with_dht(dht);
+    assert!(matches!(
+        fresh.resolve_domain(domain).await,
+        Err(DarkResolverError::ContestedDomain)
+    ));
+}
+
+#[tokio::test]
+async fn test_overlong_lifetimes_rejected() {
+    let owner = keypair();
+    let domain = derived_domain(&owner);
+
+    // Claims to stay valid for far longer than records may
+    let mut long_lived = record(&owner, &domain, 0, 1);
+    long_lived.expires_at = long_lived.registered_at + MAX_RECORD_TTL + 1;
+    long_lived.bind_to_domain(&owner, &domain, 0).unwrap();
+    let dht = Arc::new(MemoryDht::default());
+    dht.seed(&domain, &long_lived);
+    assert!(matches!(
+        DarkResolver::with_dht(dht).resolve_domain(&domain).await,
+        Err(DarkResolverError::TtlTooLong { .. })
+    ));
+
+    // Dated in the future so that a short lifetime ends late
+    let mut future = record(&owner, &domain, 0, 1);
+    future.registered_at += 10 * MAX_RECORD_TTL;
+    future.expires_at = future.registered_at + 3600;
+    future.bind_to_domain(&owner, &domain, 0).unwrap();
+    assert!(matches!(
+        DarkResolver::new().register_record(future),
+        Err(DarkResolverError::RegisteredInFuture)
+    ));
+
+    assert!(matches!(
+        DarkDomainRecord::new(
+            &owner,
+            vec![0u8; 1184],
+            vec![],
+            None,
+            u32::MAX,
+            PeerId::random(),
+        ),
+        Err(DarkResolverError::TtlTooLong { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_expired_records_rejected() {
+    let owner = keypair();
+    let domain = derived_domain(&owner);
+    let dht = Arc::new(MemoryDht::default());
+
+    let mut expired = record(&owner, &domain, 0, 1);
+    expired.registered_at -= 7200;
+    expired.expires_at = expired.registered_at + 3600;
+    expired.bind_to_domain(&owner, &domain, 0).unwrap();
+    dht.seed(&domain, &expired);
+
+    let resolver = DarkResolver::with_dht(dht);
+    assert!(matches!(
+        resolver.resolve_domain(&domain).await,
+        Err(DarkResolverError::DomainExpired)
+    ));
+
+    assert!(matches!(
+        resolver.resolve_domain("missing.dark").await,
+        Err(DarkResolverError::DomainNotFound)
+    ));
+}
+
+/// Record as stored before records were versioned and bound to a domain,
+/// signed over the fields of that layout
+fn legacy_record_json(signer: &MlDsaKeyPair) -> serde_json::Value {
+    let record = DarkDomainRecord::new(
+        signer,
+        vec![0u8; 1184],
+        vec![NetworkAddress::new([10, 0, 0, 1], 9000)],
+        Some("legacy".to_string()),
+        3600,
+        PeerId::random(),
+    )
+    .unwrap();
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&record.signing_public_key);
+    hasher.update(&record.encryption_public_key);
+    for addr in &record.addresses {
+        hasher.update(&bincode::serialize(addr).unwrap());
+    }
+    hasher.update(record.alias.as_ref().unwrap().as_bytes());
+    hasher.update(&record.ttl.to_le_bytes());
+    hasher.update(&record.registered_at.to_le_bytes());
+    hasher.update(&record.expires_at.to_le_bytes());
+    hasher.update(&bincode::serialize(&record.owner_id).unwrap());
+    let signature = signer
+        .sign(hasher.finalize().as_bytes(), &mut thread_rng())
+        .unwrap();
+
+    let mut json = serde_json::to_value(&record).unwrap();
+    let fields = json.as_object_mut().unwrap();
+    for field in ["domain", "sequence", "introduction_points", "version"] {
+        fields.remove(field);
+    }
+    fields.insert("signature".to_string(), serde_json::json!(signature));
+    json
+}
+
+#[test]
+fn test_records_stored_before_versioning_load() {
+    let owner = keypair();
+    let json = legacy_record_json(&owner);
+
+    let record: DarkDomainRecord = serde_json::from_str(&json.to_string()).unwrap();
+    assert_eq!(record.version, DARK_RECORD_VERSION_LEGACY);
+    assert!(record.domain.is_empty());
+    assert!(record.introduction_points.is_empty());
+    record.verify_signature().unwrap();
+
+    // Fields the legacy signature does not cover cannot be added to it
+    let mut bound = record.clone();
+    bound.domain = "legacy.dark".to_string();
+    assert!(bound.verify_signature().is_err());
+    let mut introduced = record;
+    introduced.introduction_points = vec![IntroductionPoint {
+        relay: vec![1, 2, 3],
+        intro_id: [7u8; 32],
+    }];
+    assert!(introduced.verify_signature().is_err());
+}
+
+#[tokio::test]
+async fn test_republish_only_published_domains() {
+    let dht = Arc::new(MemoryDht::default());
+    let resolver = DarkResolver::with_dht(dht.clone());
+    let addresses = vec![NetworkAddress::new([10, 0, 0, 1], 8000)];
+
+    let published = resolver
+        .register_domain(
+            Some("published"),
+            addresses.clone(),
+            None,
+            3600,
+            PeerId::random(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+    resolver
+        .register_domain(
+            Some("private"),
+            addresses,
+            None,
+            3600,
+            PeerId::random(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+    assert_eq!(*dht.puts.lock().unwrap(), 0);
+
+    resolver.publish_domain(&published.domain).await.unwrap();
+    assert_eq!(*dht.puts.lock().unwrap(), 1);
+    assert_eq!(resolver.republish().await.unwrap(), 1);
+    assert_eq!(*dht.puts.lock().unwrap(), 2);
+
+    // Another resolver sharing the DHT sees the published name only
+    let remote = DarkResolver::with_dht(dht);
+    remote.resolve_domain(&published.domain).await.unwrap();
+    assert!(remote.resolve_domain("private.dark").await.is_err());
+}
+
+#[tokio::test]
+async fn test_republisher_stops_with_resolver() {
+    let dht = Arc::new(MemoryDht::default());
+    let resolver = Arc::new(DarkResolver::with_dht(dht.clone()));
+    let address = resolver
+        .register_domain(
+            None,
+            vec![NetworkAddress::new([10, 0, 0, 1], 8000)],
+            None,
+            3600,
+            PeerId::random(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+    resolver.publish_domain(&address.domain).await.unwrap();
+
+    let task = resolver.spawn_republisher(Duration::from_millis(50));
+    tokio::time::sleep(Duration::from_millis(180)).await;
+    assert!(*dht.puts.lock().unwrap() >= 3);
+
+    drop(resolver);
+    tokio::time::timeout(Duration::from_secs(1), task)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+async fn spawn_node() -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+#[tokio::test]
+async fn test_domain_resolves_across_nodes() {
+    let (publisher, publisher_addr) = spawn_node().await;
+    let (client, _) = spawn_node().await;
+    let publisher_id = publisher.local_peer_id().await;
+
+    client.dial(publisher_addr).await.unwrap();
+    for _ in 0..50 {
+        if client.connected_peers().await.contains(&publisher_id) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // Let identify populate both routing tables
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let publishing = DarkResolver::with_dht(Arc::new(publisher.clone()));
+    let address = publishing
+        .register_domain(
+            Some("across"),
+            vec![NetworkAddress::new([10, 0, 0, 7], 9000)],
+            None,
+            3600,
+            PeerId::random(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+    publishing.publish_domain(&address.domain).await.unwrap();
+
+    let resolving = DarkResolver::with_dht(Arc::new(client.clone()));
+    let found = resolving.resolve_domain(&address.domain).await.unwrap();
+    assert_eq!(
+        found.addresses,
+        vec![NetworkAddress::new([10, 0, 0, 7], 9000)]
+    );
+    assert_eq!(
+        found.signing_public_key,
+        publishing
+            .lookup_domain(&address.domain)
+            .unwrap()
+            .signing_public_key
+    );
+}
@@ -0,0 +1,381 @@
+//! Tests for DNS seed discovery against a local stub DNS server.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hickory_resolver::proto::op::{Message, MessageType, ResponseCode};
+use hickory_resolver::proto::rr::rdata::{A, SRV, TXT};
+use hickory_resolver::proto::rr::{Name, RData, Record, RecordType as DnsRecordType};
+use libp2p::{Multiaddr, PeerId};
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+use qudag_network::discovery::{DiscoveryConfig, DiscoveryMethod, KademliaPeerDiscovery};
+use qudag_network::dns::{DnsError, DnsProvider, DnsRecord};
+use qudag_network::dns_seed::{
+    publish_seed_list, DnsSeedConfig, DnsSeedError, DnsSeedResolver, SeedList,
+};
+use rand::thread_rng;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Records served by the stub, keyed by lowercase name without trailing dot
+#[derive(Clone, Default)]
+struct Zone {
+    records: Arc<Mutex<HashMap<String, Vec<RData>>>>,
+}
+
+impl Zone {
+    fn add(&self, name: &str, rdata: RData) {
+        self.records
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .push(rdata);
+    }
+
+    fn answer(&self, request: &[u8], max_size: Option<usize>) -> Vec<u8> {
+        let request = Message::from_vec(request).unwrap();
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .set_response_code(ResponseCode::NoError);
+        let query = request.queries()[0].clone();
+        response.add_query(query.clone());
+
+        let name = query.name().to_utf8().trim_end_matches('.').to_lowercase();
+        let mut answered = response.clone();
+        for rdata in self
+            .records
+            .lock()
+            .unwrap()
+            .get(&name)
+            .into_iter()
+            .flatten()
+        {
+            if rdata.record_type() == query.query_type() {
+                answered.add_answer(Record::from_rdata(query.name().clone(), 60, rdata.clone()));
+            }
+        }
+        let bytes = answered.to_vec().unwrap();
+        match max_size {
+            // Too large for UDP: the client has to retry over TCP
+            Some(max_size) if bytes.len() > max_size => {
+                response.set_truncated(true);
+                response.to_vec().unwrap()
+            }
+            _ => bytes,
+        }
+    }
+}
+
+/// Serve `zone` over UDP and TCP on one local port
+async fn spawn_stub(zone: Zone) -> SocketAddr {
+    let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = tcp.local_addr().unwrap();
+    let udp = UdpSocket::bind(addr).await.unwrap();
+
+    let udp_zone = zone.clone();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 4096];
+        loop {
+            let Ok((len, from)) = udp.recv_from(&mut buf).await else {
+                return;
+            };
+            let answer = udp_zone.answer(&buf[..len], Some(512));
+            let _ = udp.send_to(&answer, from).await;
+        }
+    });
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = tcp.accept().await else {
+                return;
+            };
+            let zone = zone.clone();
+            tokio::spawn(async move {
+                loop {
+                    let Ok(len) = stream.read_u16().await else {
+                        return;
+                    };
+                    let mut request = vec![0u8; len as usize];
+                    if stream.read_exact(&mut request).await.is_err() {
+                        return;
+                    }
+                    let answer = zone.answer(&request, None);
+                    let _ = stream.write_u16(answer.len() as u16).await;
+                    let _ = stream.write_all(&answer).await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Provider keeping records in the stub's zone
+#[derive(Clone, Default)]
+struct ZoneProvider {
+    zone: Zone,
+    records: Arc<Mutex<Vec<DnsRecord>>>,
+}
+
+impl ZoneProvider {
+    /// Push the TXT records to the stub
+    fn sync(&self) {
+        let mut served = self.zone.records.lock().unwrap();
+        for rdatas in served.values_mut() {
+            rdatas.retain(|rdata| rdata.record_type() != DnsRecordType::TXT);
+        }
+        for record in self.records.lock().unwrap().iter() {
+            served
+                .entry(record.name.clone())
+                .or_default()
+                .push(RData::TXT(TXT::new(vec![record.content.clone()])));
+        }
+    }
+}
+
+#[async_trait]
+impl DnsProvider for ZoneProvider {
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, DnsError> {
+        Ok(self.records.lock().unwrap().clone())
+    }
+
+    async fn create_record(&self, mut record: DnsRecord) -> Result<DnsRecord, DnsError> {
+        record.id = Some(uuid::Uuid::new_v4().to_string());
+        self.records.lock().unwrap().push(record.clone());
+        self.sync();
+        Ok(record)
+    }
+
+    async fn update_record(
+        &self,
+        record_id: &str,
+        record: DnsRecord,
+    ) -> Result<DnsRecord, DnsError> {
+        self.delete_record(record_id).await?;
+        self.create_record(record).await
+    }
+
+    async fn delete_record(&self, record_id: &str) -> Result<(), DnsError> {
+        let mut records = self.records.lock().unwrap();
+        let before = records.len();
+        records.retain(|record| record.id.as_deref() != Some(record_id));
+        if records.len() == before {
+            return Err(DnsError::NotFound(record_id.to_string()));
+        }
+        drop(records);
+        self.sync();
+        Ok(())
+    }
+}
+
+fn keypair() -> MlDsaKeyPair {
+    MlDsaKeyPair::generate(&mut thread_rng()).unwrap()
+}
+
+fn seed_addr(host: &str, port: u16) -> (PeerId, Multiaddr) {
+    let peer_id = PeerId::random();
+    let addr = format!("{}/tcp/{}/p2p/{}", host, port, peer_id)
+        .parse()
+        .unwrap();
+    (peer_id, addr)
+}
+
+fn txt_values(list: &SeedList) -> Vec<String> {
+    list.to_txt_records().unwrap()
+}
+
+#[test]
+fn test_seed_list_signing() {
+    let signer = keypair();
+    let (_, addr) = seed_addr("/ip4/192.0.2.1", 4001);
+    let list = SeedList::new(
+        &signer,
+        "Seeds.Example.org.",
+        std::slice::from_ref(&addr),
+        1,
+        Duration::from_secs(3600),
+    )
+    .unwrap();
+    list.verify(signer.public_key(), "seeds.example.org")
+        .unwrap();
+
+    // Chunks reassemble in any order, other TXT values are ignored
+    let mut values = txt_values(&list);
+    assert!(values.len() > 1);
+    assert!(values.iter().all(|value| value.len() <= 255));
+    values.reverse();
+    values.push("v=spf1 -all".to_string());
+    let decoded = SeedList::from_txt_records(&values);
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].as_ref().unwrap(), &list);
+
+    // A missing chunk loses the publication
+    assert!(SeedList::from_txt_records(&values[1..]).is_empty());
+
+    assert!(matches!(
+        list.verify(keypair().public_key(), "seeds.example.org"),
+        Err(DnsSeedError::MlDsa(_))
+    ));
+    assert!(matches!(
+        list.verify(signer.public_key(), "other.example.org"),
+        Err(DnsSeedError::WrongDomain { .. })
+    ));
+    let mut tampered = list.clone();
+    tampered
+        .peers
+        .push(seed_addr("/ip4/198.51.100.7", 4001).1.to_string());
+    assert!(tampered
+        .verify(signer.public_key(), "seeds.example.org")
+        .is_err());
+
+    // Every entry must name its peer
+    let no_peer: Multiaddr = "/ip4/192.0.2.1/tcp/4001".parse().unwrap();
+    assert!(SeedList::new(&signer, "seeds.example.org", &[no_peer], 1, Duration::ZERO).is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_signed_seeds_from_stub_server() {
+    let signer = keypair();
+    let domain = "seeds.example.org";
+    let zone = Zone::default();
+
+    let (direct, direct_addr) = seed_addr("/ip4/192.0.2.10", 4001);
+    let (named, named_addr) = seed_addr("/dns4/node.example.org", 4002);
+    let (delegated, delegated_addr) = seed_addr("/ip4/192.0.2.30", 4003);
+    zone.add(
+        "node.example.org",
+        RData::A(A(Ipv4Addr::new(192, 0, 2, 20))),
+    );
+
+    let list = SeedList::new(
+        &signer,
+        domain,
+        &[direct_addr, named_addr],
+        1,
+        Duration::from_secs(3600),
+    )
+    .unwrap();
+    for value in txt_values(&list) {
+        zone.add(domain, RData::TXT(TXT::new(vec![value])));
+    }
+
+    // A second zone, reached through SRV, serves another list for the domain
+    zone.add(
+        "_qudag._tcp.seeds.example.org",
+        RData::SRV(SRV::new(
+            10,
+            0,
+            4001,
+            Name::from_str("mirror.example.net.").unwrap(),
+        )),
+    );
+    let mirrored = SeedList::new(
+        &signer,
+        domain,
+        std::slice::from_ref(&delegated_addr),
+        2,
+        Duration::from_secs(3600),
+    )
+    .unwrap();
+    for value in txt_values(&mirrored) {
+        zone.add("mirror.example.net", RData::TXT(TXT::new(vec![value])));
+    }
+
+    // Lists signed by another key are ignored
+    let (forged, forged_addr) = seed_addr("/ip4/203.0.113.66", 4001);
+    let forgery = SeedList::new(
+        &keypair(),
+        domain,
+        &[forged_addr],
+        3,
+        Duration::from_secs(3600),
+    )
+    .unwrap();
+    for value in txt_values(&forgery) {
+        zone.add(domain, RData::TXT(TXT::new(vec![value])));
+    }
+
+    let server = spawn_stub(zone).await;
+    let mut config = DnsSeedConfig::new(domain, signer.public_key().to_vec());
+    config.nameservers = vec![server];
+    let resolver = DnsSeedResolver::new(config).unwrap();
+
+    let lists = resolver.resolve_lists().await.unwrap();
+    assert_eq!(lists.len(), 2);
+    let peers = resolver.resolve().await.unwrap();
+    let addrs_of = |peer_id: PeerId| {
+        peers
+            .iter()
+            .find(|peer| peer.peer_id == peer_id)
+            .map(|peer| peer.socket_addrs.clone())
+    };
+    assert_eq!(
+        addrs_of(direct),
+        Some(vec!["192.0.2.10:4001".parse().unwrap()])
+    );
+    assert_eq!(
+        addrs_of(named),
+        Some(vec!["192.0.2.20:4002".parse().unwrap()])
+    );
+    assert_eq!(
+        addrs_of(delegated),
+        Some(vec!["192.0.2.30:4003".parse().unwrap()])
+    );
+    assert_eq!(addrs_of(forged), None);
+
+    // Without a valid list there is nothing to bootstrap from
+    let mut config = DnsSeedConfig::new(domain, keypair().public_key().to_vec());
+    config.nameservers = vec![server];
+    let untrusted = DnsSeedResolver::new(config).unwrap();
+    assert!(matches!(
+        untrusted.resolve().await,
+        Err(DnsSeedError::NoSeeds(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_published_seeds_feed_discovery() {
+    let signer = keypair();
+    let domain = "bootstrap.example.org";
+    let provider = ZoneProvider::default();
+    let server = spawn_stub(provider.zone.clone()).await;
+
+    let (old_peer, old_addr) = seed_addr("/ip4/192.0.2.1", 4001);
+    let old = SeedList::new(&signer, domain, &[old_addr], 1, Duration::from_secs(3600)).unwrap();
+    publish_seed_list(&provider, &old, 300).await.unwrap();
+    let published = provider.records.lock().unwrap().len();
+
+    // Republishing replaces the previous chunks
+    let (peer, addr) = seed_addr("/ip4/192.0.2.2", 4001);
+    let list = SeedList::new(&signer, domain, &[addr], 2, Duration::from_secs(3600)).unwrap();
+    publish_seed_list(&provider, &list, 300).await.unwrap();
+    assert_eq!(provider.records.lock().unwrap().len(), published);
+
+    let mut seeds = DnsSeedConfig::new(domain, signer.public_key().to_vec());
+    seeds.nameservers = vec![server];
+    let config = DiscoveryConfig {
+        methods: vec![DiscoveryMethod::DNS],
+        dns_seeds: Some(seeds),
+        ..Default::default()
+    };
+    let mut discovery = KademliaPeerDiscovery::new(config);
+    discovery.start().await.unwrap();
+
+    let discovered = discovery.get_discovered_peers().await;
+    let seed = discovered
+        .iter()
+        .find(|discovered| discovered.peer_id == peer)
+        .expect("seed peer discovered");
+    assert_eq!(seed.discovery_method, DiscoveryMethod::DNS);
+    assert_eq!(seed.addresses, vec!["192.0.2.2:4001".parse().unwrap()]);
+    assert!(discovered.iter().all(|peer| peer.peer_id != old_peer));
+    discovery.stop().await.unwrap();
+}
@@ -0,0 +1,334 @@
+//! Tests for typed gossip topics: schemas, validation verdicts, that
+//! rejected messages are neither delivered nor forwarded between P2P nodes,
+//! and that the network-wide obfuscation key decides who can read gossip.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::gossip_topics::{
+    GossipMessage, GossipTopic, GossipTopicError, GossipValidator, GossipValidators,
+    PeerAnnouncement, Verdict, VertexMessage, VoteMessage,
+};
+use qudag_network::node_identity::{NodeIdentity, IDENTITY_FILE};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode};
+use rand::{thread_rng, Rng};
+
+fn vertex(payload: &[u8]) -> GossipMessage {
+    GossipMessage::Vertex(VertexMessage {
+        id: payload.to_vec(),
+        parents: vec![vec![0; 32]],
+        payload: payload.to_vec(),
+        timestamp: 1_700_000_000,
+        signature: vec![7; 64],
+    })
+}
+
+/// Rejects vertices whose payload starts with "bad", ignores "dup"
+struct PayloadValidator;
+
+#[async_trait]
+impl GossipValidator for PayloadValidator {
+    async fn validate(&self, _source: PeerId, message: &GossipMessage) -> Verdict {
+        match message {
+            GossipMessage::Vertex(v) if v.payload.starts_with(b"bad") => Verdict::Reject,
+            GossipMessage::Vertex(v) if v.payload.starts_with(b"dup") => Verdict::Ignore,
+            _ => Verdict::Accept,
+        }
+    }
+}
+
+#[test]
+fn test_topics_and_schemas() {
+    assert_eq!(GossipTopic::Vertices.topic(), "/qudag/vertices/1");
+    assert_eq!(
+        GossipTopic::ExchangeTxs.to_string(),
+        "/qudag/exchange-txs/1"
+    );
+    for topic in GossipTopic::ALL {
+        assert_eq!(GossipTopic::from_topic(&topic.topic()), Some(topic));
+        assert_eq!(topic.name().parse::<GossipTopic>().unwrap(), topic);
+    }
+    // Other schema versions are other topics
+    assert_eq!(GossipTopic::from_topic("/qudag/vertices/2"), None);
+    assert!(matches!(
+        "blocks".parse::<GossipTopic>(),
+        Err(GossipTopicError::UnknownTopic(_))
+    ));
+
+    let messages = [
+        vertex(b"hello"),
+        GossipMessage::Vote(VoteMessage {
+            vertex: vec![1; 32],
+            round: 3,
+            accept: true,
+            voter: vec![2; 32],
+            signature: vec![3; 64],
+        }),
+        GossipMessage::PeerAnnouncement(PeerAnnouncement {
+            peer_id: PeerId::random().to_bytes(),
+            addresses: vec!["/memory/1".parse::<Multiaddr>().unwrap().to_vec()],
+            timestamp: 1_700_000_000,
+        }),
+    ];
+    for message in messages {
+        let data = message.encode().unwrap();
+        assert_eq!(
+            GossipMessage::decode(message.topic(), &data).unwrap(),
+            message
+        );
+    }
+    assert!(matches!(
+        GossipMessage::decode(GossipTopic::Votes, &[1, 2, 3]),
+        Err(GossipTopicError::Malformed {
+            topic: GossipTopic::Votes,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_batch_verdicts() {
+    let validators = GossipValidators::default();
+    let source = PeerId::random();
+    let encode = |payload: &[u8]| vertex(payload).encode().unwrap();
+
+    // Without a validator only the schema is checked
+    let validation = validators
+        .validate(source, GossipTopic::Vertices, vec![encode(b"bad")])
+        .await;
+    assert_eq!(validation.verdict, Verdict::Accept);
+    let validation = validators
+        .validate(source, GossipTopic::Vertices, vec![vec![0xff]])
+        .await;
+    assert_eq!(validation.verdict, Verdict::Reject);
+
+    validators.set(GossipTopic::Vertices, Arc::new(PayloadValidator));
+    let validation = validators
+        .validate(
+            source,
+            GossipTopic::Vertices,
+            vec![encode(b"good"), encode(b"dup"), encode(b"fine")],
+        )
+        .await;
+    assert_eq!(validation.verdict, Verdict::Accept);
+    assert_eq!(validation.accepted, vec![vertex(b"good"), vertex(b"fine")]);
+
+    // Messages of a batch are judged one by one; a batch with rejected
+    // messages is not forwarded, but its valid ones are still delivered
+    let validation = validators
+        .validate(
+            source,
+            GossipTopic::Vertices,
+            vec![encode(b"good"), encode(b"bad"), vec![0xff]],
+        )
+        .await;
+    assert_eq!(validation.verdict, Verdict::Ignore);
+    assert_eq!(validation.accepted, vec![vertex(b"good")]);
+    assert_eq!(validation.rejected, 2);
+
+    // Only a batch of nothing but invalid messages is rejected
+    let validation = validators
+        .validate(
+            source,
+            GossipTopic::Vertices,
+            vec![encode(b"bad"), encode(b"bad too")],
+        )
+        .await;
+    assert_eq!(validation.verdict, Verdict::Reject);
+    assert!(validation.accepted.is_empty());
+
+    let validation = validators
+        .validate(source, GossipTopic::Vertices, vec![encode(b"dup")])
+        .await;
+    assert_eq!(validation.verdict, Verdict::Ignore);
+
+    assert!(validators.remove(GossipTopic::Vertices));
+    assert!(validators.get(GossipTopic::Vertices).is_none());
+}
+
+/// Obfuscation key of the test network
+const NETWORK_KEY: [u8; 32] = [5u8; 32];
+
+async fn spawn_node() -> (P2PHandle, Multiaddr) {
+    spawn_node_with(None, NETWORK_KEY).await
+}
+
+async fn spawn_node_with(
+    identity: Option<NodeIdentity>,
+    obfuscation_key: [u8; 32],
+) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key,
+        identity,
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        ..Default::default()
+    };
+    (start(config).await, addr.parse().unwrap())
+}
+
+async fn start(config: NetworkConfig) -> P2PHandle {
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    handle
+}
+
+async fn next_vertex(handle: &P2PHandle, wait: Duration) -> Option<(PeerId, VertexMessage)> {
+    tokio::time::timeout(wait, async {
+        loop {
+            match handle.next_event().await {
+                Some(P2PEvent::GossipMessageReceived {
+                    peer_id,
+                    message: GossipMessage::Vertex(vertex),
+                }) => break (peer_id, vertex),
+                Some(_) => continue,
+                None => panic!("node stopped"),
+            }
+        }
+    })
+    .await
+    .ok()
+}
+
+#[tokio::test]
+async fn test_rejected_messages_are_not_forwarded() {
+    // Line topology: publisher -> validator -> receiver
+    let (publisher, _) = spawn_node().await;
+    let (validator, validator_addr) = spawn_node().await;
+    let (receiver, _) = spawn_node().await;
+    let publisher_id = publisher.local_peer_id().await;
+    let validator_id = validator.local_peer_id().await;
+
+    validator.set_validator(GossipTopic::Vertices, Arc::new(PayloadValidator));
+    publisher.dial(validator_addr.clone()).await.unwrap();
+    receiver.dial(validator_addr).await.unwrap();
+    for handle in [&publisher, &validator, &receiver] {
+        handle.subscribe_topic(GossipTopic::Vertices).await.unwrap();
+    }
+    // Let subscriptions and the mesh form
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Spaced out so each goes out in a gossip message of its own
+    let pause = || tokio::time::sleep(Duration::from_millis(100));
+    publisher
+        .publish_message(&vertex(b"bad vertex"))
+        .await
+        .unwrap();
+    pause().await;
+    // Untyped data on a typed topic fails its schema
+    publisher
+        .publish(&GossipTopic::Vertices.topic(), b"not a vertex".to_vec())
+        .await
+        .unwrap();
+    pause().await;
+    publisher
+        .publish_message(&vertex(b"good vertex"))
+        .await
+        .unwrap();
+
+    // The receiver only hears of the accepted vertex, through the validator
+    let (from, received) = next_vertex(&receiver, Duration::from_secs(10))
+        .await
+        .expect("accepted vertex not forwarded");
+    assert_eq!(from, validator_id);
+    assert_eq!(received.payload, b"good vertex");
+    assert!(next_vertex(&receiver, Duration::from_secs(1))
+        .await
+        .is_none());
+
+    let (from, received) = next_vertex(&validator, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(from, publisher_id);
+    assert_eq!(received.payload, b"good vertex");
+
+    // The sender of invalid messages lost score
+    assert!(validator.peer_scoring().score(&publisher_id).local < 0.0);
+}
+
+#[tokio::test]
+async fn test_persistent_identities_share_gossip() {
+    let dir = tempfile::tempdir().unwrap();
+    let identity = |name: &str| {
+        NodeIdentity::load_or_create(dir.path().join(name).join(IDENTITY_FILE), None).unwrap()
+    };
+
+    // Distinct identities on the same network read each other's gossip
+    let (publisher, _) = spawn_node_with(Some(identity("publisher")), NETWORK_KEY).await;
+    let (receiver, receiver_addr) = spawn_node_with(Some(identity("receiver")), NETWORK_KEY).await;
+    // A node of another network hears the gossip but cannot open it
+    let (outsider, _) = spawn_node_with(Some(identity("outsider")), [6u8; 32]).await;
+    let publisher_id = publisher.local_peer_id().await;
+
+    publisher.dial(receiver_addr.clone()).await.unwrap();
+    outsider.dial(receiver_addr).await.unwrap();
+    for handle in [&publisher, &receiver, &outsider] {
+        handle.subscribe_topic(GossipTopic::Vertices).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    publisher
+        .publish_message(&vertex(b"shared vertex"))
+        .await
+        .unwrap();
+    let (from, received) = next_vertex(&receiver, Duration::from_secs(10))
+        .await
+        .expect("vertex not delivered between persistent identities");
+    assert_eq!(from, publisher_id);
+    assert_eq!(received.payload, b"shared vertex");
+
+    assert!(next_vertex(&outsider, Duration::from_secs(2))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_default_configs_share_gossip() {
+    // Nothing but local addresses set: both nodes keep the default key
+    let config = || {
+        let port: u64 = thread_rng().gen_range(1..u64::MAX);
+        NetworkConfig {
+            listen_addrs: vec![format!("/memory/{}", port)],
+            enable_mdns: false,
+            ..Default::default()
+        }
+    };
+    let receiver_config = config();
+    let receiver_addr: Multiaddr = receiver_config.listen_addrs[0].parse().unwrap();
+    let publisher = start(config()).await;
+    let receiver = start(receiver_config).await;
+    let publisher_id = publisher.local_peer_id().await;
+
+    publisher.dial(receiver_addr).await.unwrap();
+    for handle in [&publisher, &receiver] {
+        handle.subscribe_topic(GossipTopic::Vertices).await.unwrap();
+    }
+    // Default heartbeat of one second
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    publisher
+        .publish_message(&vertex(b"default vertex"))
+        .await
+        .unwrap();
+    let (from, received) = next_vertex(&receiver, Duration::from_secs(10))
+        .await
+        .expect("vertex not delivered between default configs");
+    assert_eq!(from, publisher_id);
+    assert_eq!(received.payload, b"default vertex");
+}
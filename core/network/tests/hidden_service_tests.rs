@@ -0,0 +1,183 @@
+//! Tests for hidden services reached through introduction and rendezvous
+//! points.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::Multiaddr;
+use qudag_network::dark_resolver::DarkResolver;
+use qudag_network::hidden_service::{
+    HiddenServiceConfig, HiddenServiceError, HiddenServiceEvent, HiddenServiceKeys, HiddenServices,
+};
+use qudag_network::onion_relay::OnionRelayConfig;
+use qudag_network::p2p::{NetworkConfig, P2PHandle, P2PNode};
+use qudag_network::types::{NetworkAddress, PeerId};
+use rand::{thread_rng, Rng};
+
+async fn spawn_node(obfuscation_key: [u8; 32], relay: bool) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key,
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        onion: OnionRelayConfig {
+            enabled: relay,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+async fn wait_for_relays(node: &P2PHandle, count: usize) {
+    for _ in 0..100 {
+        if node.known_relays().await.len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("directory never reached {} relays", count);
+}
+
+fn hidden_services(handle: &P2PHandle) -> (HiddenServices, Arc<DarkResolver>) {
+    let resolver = Arc::new(DarkResolver::with_dht(Arc::new(handle.clone())));
+    let config = HiddenServiceConfig {
+        introduction_points: 2,
+        circuit_hops: 2,
+        ..Default::default()
+    };
+    (
+        HiddenServices::new(handle.clone(), resolver.clone(), config),
+        resolver,
+    )
+}
+
+async fn next_hidden_event(services: &HiddenServices) -> HiddenServiceEvent {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            match services.next_event().await {
+                Some(HiddenServiceEvent::Network(_)) => continue,
+                Some(event) => return event,
+                None => panic!("event stream closed"),
+            }
+        }
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_rendezvous_with_hidden_service() {
+    let key = rand::random();
+    let (hub, hub_addr) = spawn_node(key, true).await;
+    let mut relays = Vec::new();
+    for _ in 0..3 {
+        let (relay, _) = spawn_node(key, true).await;
+        relay.dial(hub_addr.clone()).await.unwrap();
+        relays.push(relay);
+    }
+    wait_for_relays(&hub, 3).await;
+
+    // Neither end relays traffic for others
+    let (service_node, _) = spawn_node(key, false).await;
+    let (client_node, _) = spawn_node(key, false).await;
+    for node in [&service_node, &client_node] {
+        node.dial(hub_addr.clone()).await.unwrap();
+        wait_for_relays(node, 4).await;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let (service, _) = hidden_services(&service_node);
+    let address = tokio::time::timeout(
+        Duration::from_secs(60),
+        service.host(HiddenServiceKeys::generate().unwrap(), None),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    // The published record locates the service by relays only
+    let (client, client_resolver) = hidden_services(&client_node);
+    let record = client_resolver
+        .resolve_domain(&address.domain)
+        .await
+        .unwrap();
+    assert!(record.addresses.is_empty());
+    assert_eq!(record.introduction_points.len(), 2);
+
+    let session = tokio::time::timeout(Duration::from_secs(60), client.connect(&address.domain))
+        .await
+        .unwrap()
+        .unwrap();
+    let HiddenServiceEvent::IncomingSession {
+        domain,
+        session: incoming,
+    } = next_hidden_event(&service).await
+    else {
+        panic!("expected an incoming session");
+    };
+    assert_eq!(domain, address.domain);
+
+    client.send(session, b"ping".to_vec()).await.unwrap();
+    match next_hidden_event(&service).await {
+        HiddenServiceEvent::Data {
+            session: received_on,
+            payload,
+        } => {
+            assert_eq!(received_on, incoming);
+            assert_eq!(payload, b"ping");
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    service.send(incoming, b"pong".to_vec()).await.unwrap();
+    match next_hidden_event(&client).await {
+        HiddenServiceEvent::Data {
+            session: received_on,
+            payload,
+        } => {
+            assert_eq!(received_on, session);
+            assert_eq!(payload, b"pong");
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    client.close(session).await.unwrap();
+    assert!(client.send(session, b"late".to_vec()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_connect_requires_hidden_service() {
+    let key = rand::random();
+    let (node, _) = spawn_node(key, false).await;
+    let (client, resolver) = hidden_services(&node);
+    let address = resolver
+        .register_domain(
+            None,
+            vec![NetworkAddress::new([10, 0, 0, 1], 8000)],
+            None,
+            3600,
+            PeerId::random(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+    assert!(matches!(
+        client.connect(&address.domain).await,
+        Err(HiddenServiceError::NotHidden(_))
+    ));
+}
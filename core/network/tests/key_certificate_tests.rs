@@ -0,0 +1,242 @@
+//! Tests for key rotation/revocation enforcement in the dark resolver and
+//! for certificate gossip encoding.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use qudag_crypto::key_certificate::{
+    KeyCertificate, KeyCertificateError, KeyRevocation, KeyRotation, RevocationList,
+    RevocationReason,
+};
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+use qudag_network::dark_resolver::{DarkDomainRecord, DarkResolver, DarkResolverError, DhtClient};
+use qudag_network::key_certificates::{
+    decode_certificate, encode_certificate, ingest_certificate, KeyCertificateGossipError,
+    MAX_CERTIFICATE_SIZE,
+};
+use qudag_network::types::{NetworkAddress, PeerId};
+
+const DOMAIN: &str = "rotating.dark";
+
+/// DHT that stores one value per key, so records signed by test keys can be seeded
+#[derive(Default)]
+struct MemoryDht {
+    values: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+#[async_trait]
+impl DhtClient for MemoryDht {
+    async fn put(&self, key: &[u8], value: &[u8], _ttl: Duration) -> Result<(), DarkResolverError> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    async fn get(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, DarkResolverError> {
+        Ok(self
+            .values
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .into_iter()
+            .collect())
+    }
+}
+
+fn keypair() -> MlDsaKeyPair {
+    MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap()
+}
+
+/// Record for `DOMAIN` at update `sequence`
+fn record(signer: &MlDsaKeyPair, sequence: u64) -> DarkDomainRecord {
+    let mut record = DarkDomainRecord::new(
+        signer,
+        vec![0u8; 1184],
+        vec![NetworkAddress::new([10, 0, 0, 1], 8000)],
+        None,
+        3600,
+        PeerId::random(),
+    )
+    .unwrap();
+    record.bind_to_domain(signer, DOMAIN, sequence).unwrap();
+    record
+}
+
+/// Resolver whose DHT serves a record for `DOMAIN` signed by `signer`
+fn resolver_with_record(
+    signer: &MlDsaKeyPair,
+    revocations: &RevocationList,
+) -> (DarkResolver, Arc<MemoryDht>) {
+    let dht = Arc::new(MemoryDht::default());
+    dht.values.lock().unwrap().insert(
+        DarkResolver::dht_key(DOMAIN),
+        bincode::serialize(&record(signer, 0)).unwrap(),
+    );
+    let resolver = DarkResolver::with_dht(dht.clone()).with_revocation_list(revocations.clone());
+    (resolver, dht)
+}
+
+#[tokio::test]
+async fn test_resolve_accepts_active_key() {
+    let owner = keypair();
+    let (resolver, _dht) = resolver_with_record(&owner, &RevocationList::new());
+
+    let found = resolver.resolve_domain(DOMAIN).await.unwrap();
+    assert_eq!(found.signing_public_key, owner.public_key());
+}
+
+#[tokio::test]
+async fn test_resolve_rejects_revoked_key() {
+    let owner = keypair();
+    let revocations = RevocationList::new();
+    let (resolver, _dht) = resolver_with_record(&owner, &revocations);
+    resolver.resolve_domain(DOMAIN).await.unwrap();
+
+    // Revocation learned after the record was cached still takes effect
+    let revocation = KeyRevocation::new(&owner, RevocationReason::KeyCompromise, 1).unwrap();
+    revocations.insert(revocation.into()).unwrap();
+
+    assert!(matches!(
+        resolver.lookup_domain(DOMAIN),
+        Err(DarkResolverError::KeyCertificate(
+            KeyCertificateError::Revoked { .. }
+        ))
+    ));
+    match resolver.resolve_domain(DOMAIN).await {
+        Err(DarkResolverError::KeyCertificate(KeyCertificateError::Revoked { reason })) => {
+            assert_eq!(reason, RevocationReason::KeyCompromise);
+        }
+        other => panic!("expected revoked key error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_resolve_rejects_rotated_key() {
+    let old = keypair();
+    let new = keypair();
+    let revocations = RevocationList::new();
+    revocations
+        .insert(KeyRotation::now(&old, &new).unwrap().into())
+        .unwrap();
+    let (resolver, _dht) = resolver_with_record(&old, &revocations);
+
+    match resolver.resolve_domain(DOMAIN).await {
+        Err(DarkResolverError::KeyCertificate(KeyCertificateError::Rotated { new_key })) => {
+            assert_eq!(new_key, new.public_key());
+        }
+        other => panic!("expected rotated key error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_update_after_rotation() {
+    let old = keypair();
+    let new = keypair();
+    let revocations = RevocationList::new();
+    let (resolver, _dht) = resolver_with_record(&old, &revocations);
+    resolver.resolve_domain(DOMAIN).await.unwrap();
+
+    // Without a rotation certificate the new key is not the owner
+    assert!(matches!(
+        resolver.update_domain(DOMAIN, record(&new, 1)),
+        Err(DarkResolverError::InvalidSignature)
+    ));
+
+    revocations
+        .insert(KeyRotation::now(&old, &new).unwrap().into())
+        .unwrap();
+    resolver.update_domain(DOMAIN, record(&new, 1)).unwrap();
+
+    let found = resolver.lookup_domain(DOMAIN).unwrap();
+    assert_eq!(found.signing_public_key, new.public_key());
+
+    // The retired key can no longer publish
+    assert!(matches!(
+        resolver.update_domain(DOMAIN, record(&old, 2)),
+        Err(DarkResolverError::KeyCertificate(
+            KeyCertificateError::Rotated { .. }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_update_rejected_after_revocation() {
+    let owner = keypair();
+    let revocations = RevocationList::new();
+    let (resolver, _dht) = resolver_with_record(&owner, &revocations);
+    resolver.resolve_domain(DOMAIN).await.unwrap();
+    revocations
+        .insert(
+            KeyRevocation::new(&owner, RevocationReason::Retired, 1)
+                .unwrap()
+                .into(),
+        )
+        .unwrap();
+
+    assert!(matches!(
+        resolver.update_domain(DOMAIN, record(&owner, 1)),
+        Err(DarkResolverError::KeyCertificate(
+            KeyCertificateError::Revoked { .. }
+        ))
+    ));
+}
+
+#[test]
+fn test_gossip_encoding_roundtrip() {
+    let old = keypair();
+    let new = keypair();
+    let certificate: KeyCertificate = KeyRotation::now(&old, &new).unwrap().into();
+
+    let encoded = encode_certificate(&certificate).unwrap();
+    assert!(encoded.len() <= MAX_CERTIFICATE_SIZE);
+    assert_eq!(decode_certificate(&encoded).unwrap(), certificate);
+
+    assert!(matches!(
+        decode_certificate(&vec![0u8; MAX_CERTIFICATE_SIZE + 1]),
+        Err(KeyCertificateGossipError::TooLarge(_))
+    ));
+}
+
+#[test]
+fn test_ingest_certificate() {
+    let owner = keypair();
+    let list = RevocationList::new();
+    let certificate: KeyCertificate = KeyRevocation::new(&owner, RevocationReason::Superseded, 1)
+        .unwrap()
+        .into();
+    let encoded = encode_certificate(&certificate).unwrap();
+
+    assert_eq!(
+        ingest_certificate(&list, &encoded).unwrap(),
+        Some(certificate)
+    );
+    assert!(list.is_revoked(owner.public_key()));
+
+    // Duplicates are accepted but not reported as new
+    assert_eq!(ingest_certificate(&list, &encoded).unwrap(), None);
+}
+
+#[test]
+fn test_ingest_rejects_forged_certificate() {
+    let owner = keypair();
+    let list = RevocationList::new();
+    let mut revocation = KeyRevocation::new(&owner, RevocationReason::Unspecified, 1).unwrap();
+    revocation.issued_at += 1;
+    let encoded = encode_certificate(&revocation.into()).unwrap();
+
+    assert!(matches!(
+        ingest_certificate(&list, &encoded),
+        Err(KeyCertificateGossipError::Certificate(_))
+    ));
+    assert!(list.is_empty());
+
+    assert!(matches!(
+        ingest_certificate(&list, b"not a certificate"),
+        Err(KeyCertificateGossipError::Encoding(_))
+    ));
+}
@@ -0,0 +1,118 @@
+//! Tests for the OpenMetrics exporter: facade metrics in the scrape, the
+//! HTTP endpoint, and libp2p swarm metrics of a running node.
+
+use std::time::Duration;
+
+use libp2p::Multiaddr;
+use qudag_network::metrics_exporter::{MetricsExporter, METRICS_PATH, OPENMETRICS_CONTENT_TYPE};
+use qudag_network::p2p::{NetworkConfig, P2PHandle, P2PNode};
+use rand::{thread_rng, Rng};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[test]
+fn test_facade_metrics_rendered() {
+    let exporter = MetricsExporter::new();
+    metrics::counter!("qudag_test_events_total", 3, "kind" => "a");
+    metrics::counter!("qudag_test_events_total", 1, "kind" => "b");
+    metrics::gauge!("qudag_test_level", 2.5);
+    metrics::histogram!("qudag_test_latency_seconds", 0.003);
+    metrics::histogram!("qudag_test_latency_seconds", 7.0);
+
+    let text = exporter.render().unwrap();
+    assert!(text.contains("# TYPE qudag_test_events counter"));
+    assert!(text.contains("qudag_test_events_total{kind=\"a\"} 3"));
+    assert!(text.contains("qudag_test_events_total{kind=\"b\"} 1"));
+    assert!(text.contains("# TYPE qudag_test_level gauge"));
+    assert!(text.contains("qudag_test_level 2.5"));
+    assert!(text.contains("# TYPE qudag_test_latency_seconds histogram"));
+    assert!(text.contains("qudag_test_latency_seconds_count 2"));
+    // Buckets are cumulative
+    assert!(text.contains("qudag_test_latency_seconds_bucket{le=\"0.0025\"} 0"));
+    assert!(text.contains("qudag_test_latency_seconds_bucket{le=\"0.005\"} 1"));
+    assert!(text.contains("qudag_test_latency_seconds_bucket{le=\"+Inf\"} 2"));
+    assert!(text.ends_with("# EOF\n"));
+
+    // Crypto operations report their latency
+    let (pk, _) = qudag_crypto::ml_kem::MlKem768::keygen().unwrap();
+    qudag_crypto::ml_kem::MlKem768::encapsulate(&pk).unwrap();
+    let text = exporter.render().unwrap();
+    assert!(text.contains("# HELP qudag_crypto_operation_seconds Latency"));
+    assert!(text.contains("qudag_crypto_operation_seconds_count{operation=\"ml_kem_encapsulate\"}"));
+}
+
+async fn get(addr: std::net::SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn test_http_endpoint() {
+    let exporter = MetricsExporter::new();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(exporter.serve(listener));
+    metrics::gauge!("qudag_test_http_gauge", 1.0);
+
+    let response = get(
+        addr,
+        &format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", METRICS_PATH),
+    )
+    .await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains(&format!("Content-Type: {}", OPENMETRICS_CONTENT_TYPE)));
+    assert!(response.contains("qudag_test_http_gauge 1.0"));
+    assert!(response.ends_with("# EOF\n"));
+
+    let response = get(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    let response = get(addr, &format!("POST {} HTTP/1.1\r\n\r\n", METRICS_PATH)).await;
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+}
+
+async fn spawn_node(metrics: Option<MetricsExporter>) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key: [3u8; 32],
+        metrics,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+#[tokio::test]
+async fn test_swarm_metrics_exported() {
+    let exporter = MetricsExporter::new();
+    let (node, addr) = spawn_node(Some(exporter.clone())).await;
+    let (other, _) = spawn_node(None).await;
+
+    other.dial(addr).await.unwrap();
+    let mut connected = false;
+    for _ in 0..50 {
+        if node.connected_peers().await.len() == 1 {
+            connected = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(connected);
+    // Let identify and ping run
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let text = exporter.render().unwrap();
+    assert!(text.contains("libp2p_swarm_connections_established_total"));
+    assert!(text.contains("libp2p_identify_"));
+    assert!(text.contains("qudag_connected_peers"));
+}
@@ -0,0 +1,267 @@
+//! Tests for TCP, QUIC and WebSocket side by side: address ranking, listen
+//! addresses per transport, happy-eyeballs dialing with fallback, and nodes
+//! with disjoint transports meeting through a third node.
+
+use std::time::{Duration, Instant};
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::address_book::{
+    AddressBook, TransportKind, HAPPY_EYEBALLS_DELAY, MAX_ADDRESSES_PER_PEER,
+};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode};
+
+fn addr(s: &str) -> Multiaddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_address_ranking() {
+    assert_eq!(
+        TransportKind::of(&addr("/ip4/1.2.3.4/tcp/1")),
+        Some(TransportKind::Tcp)
+    );
+    assert_eq!(
+        TransportKind::of(&addr("/ip4/1.2.3.4/udp/1/quic-v1")),
+        Some(TransportKind::Quic)
+    );
+    assert_eq!(
+        TransportKind::of(&addr("/ip4/1.2.3.4/tcp/1/ws")),
+        Some(TransportKind::WebSocket)
+    );
+    assert_eq!(TransportKind::of(&addr("/ip4/1.2.3.4/udp/1")), None);
+
+    let book = AddressBook::new();
+    let peer = PeerId::random();
+    let tcp = addr("/ip4/1.2.3.4/tcp/1");
+    let quic = addr("/ip4/1.2.3.4/udp/1/quic-v1");
+    let ws = addr("/ip4/1.2.3.4/tcp/2/ws");
+    assert!(book.add(peer, ws.clone()));
+    assert!(book.add(peer, tcp.clone()));
+    // The peer ID suffix is dropped
+    assert!(book.add(
+        peer,
+        quic.clone().with(libp2p::multiaddr::Protocol::P2p(peer))
+    ));
+    assert!(!book.add(peer, quic.clone()));
+    assert!(!book.add(peer, addr("/ip4/1.2.3.4/udp/3")));
+
+    // Unmeasured addresses go by transport
+    let ranked: Vec<_> = book
+        .addresses(&peer)
+        .into_iter()
+        .map(|i| i.address)
+        .collect();
+    assert_eq!(ranked, vec![quic.clone(), tcp.clone(), ws.clone()]);
+
+    // Measured ones go first, fastest first
+    book.record_rtt(peer, &ws, Duration::from_millis(5));
+    book.record_rtt(peer, &tcp, Duration::from_millis(20));
+    let ranked: Vec<_> = book
+        .addresses(&peer)
+        .into_iter()
+        .map(|i| i.address)
+        .collect();
+    assert_eq!(ranked, vec![ws.clone(), tcp.clone(), quic.clone()]);
+
+    // Failed dials push an address back until it succeeds again
+    book.record_failure(peer, &ws);
+    assert_eq!(book.best(&peer), Some(tcp.clone()));
+    book.record_success(peer, &ws);
+    assert_eq!(book.best(&peer), Some(ws.clone()));
+
+    // Round-trip times are smoothed
+    book.record_rtt(peer, &ws, Duration::from_millis(45));
+    let info = book.addresses(&peer).remove(0);
+    assert_eq!(info.address, ws);
+    assert_eq!(info.rtt, Some(Duration::from_millis(15)));
+
+    for port in 0..MAX_ADDRESSES_PER_PEER as u16 {
+        book.add(peer, addr(&format!("/ip4/5.6.7.8/tcp/{}", port + 10)));
+    }
+    assert_eq!(book.addresses(&peer).len(), MAX_ADDRESSES_PER_PEER);
+    assert!(book.addresses(&peer).iter().any(|i| i.address == ws));
+}
+
+#[test]
+fn test_listen_addresses_per_transport() {
+    let config = NetworkConfig {
+        listen_addrs: vec![
+            "/ip4/127.0.0.1/tcp/0".to_string(),
+            "/ip4/127.0.0.1/tcp/4001".to_string(),
+            "/ip4/127.0.0.1/udp/4002/quic-v1".to_string(),
+            "/memory/7".to_string(),
+        ],
+        enable_tcp: false,
+        enable_quic: true,
+        enable_websocket: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        config.listen_multiaddrs().unwrap(),
+        vec![
+            addr("/ip4/127.0.0.1/udp/0/quic-v1"),
+            addr("/ip4/127.0.0.1/tcp/0/ws"),
+            addr("/ip4/127.0.0.1/udp/4001/quic-v1"),
+            addr("/ip4/127.0.0.1/udp/4002/quic-v1"),
+            addr("/memory/7"),
+        ]
+    );
+    assert!(!config.supports(&addr("/ip4/127.0.0.1/tcp/1")));
+    assert!(config.supports(&addr("/ip4/127.0.0.1/tcp/1/ws")));
+    assert!(config.supports(&addr("/ip4/127.0.0.1/udp/1/quic-v1")));
+}
+
+/// Start a node on loopback with the given transports
+async fn spawn_node(tcp: bool, quic: bool, websocket: bool) -> (P2PHandle, Vec<Multiaddr>) {
+    let config = NetworkConfig {
+        listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".to_string()],
+        enable_mdns: false,
+        enable_tcp: tcp,
+        enable_quic: quic,
+        enable_websocket: websocket,
+        obfuscation_key: [4u8; 32],
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        ..Default::default()
+    };
+    let expected = config.listen_multiaddrs().unwrap().len();
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    for _ in 0..50 {
+        let listeners = handle.listeners().await;
+        if listeners.len() >= expected {
+            return (handle, listeners);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("node did not start listening");
+}
+
+async fn next_message(handle: &P2PHandle, wait: Duration) -> Option<Vec<u8>> {
+    tokio::time::timeout(wait, async {
+        loop {
+            match handle.next_event().await {
+                Some(P2PEvent::MessageReceived { data, .. }) => break data,
+                Some(_) => continue,
+                None => panic!("node stopped"),
+            }
+        }
+    })
+    .await
+    .ok()
+}
+
+#[tokio::test]
+async fn test_disjoint_transports_meet_through_third_node() {
+    let (quic_only, quic_addrs) = spawn_node(false, true, false).await;
+    let (ws_only, ws_addrs) = spawn_node(false, false, true).await;
+    let (hub, hub_addrs) = spawn_node(true, true, true).await;
+    let hub_id = hub.local_peer_id().await;
+    let ws_id = ws_only.local_peer_id().await;
+
+    assert!(quic_addrs
+        .iter()
+        .all(|a| TransportKind::of(a) == Some(TransportKind::Quic)));
+    assert!(ws_addrs
+        .iter()
+        .all(|a| TransportKind::of(a) == Some(TransportKind::WebSocket)));
+    for kind in [
+        TransportKind::Tcp,
+        TransportKind::Quic,
+        TransportKind::WebSocket,
+    ] {
+        assert!(hub_addrs.iter().any(|a| TransportKind::of(a) == Some(kind)));
+    }
+
+    // Each picks the hub address its own transport can dial
+    for node in [&quic_only, &ws_only] {
+        for a in &hub_addrs {
+            node.address_book().add(hub_id, a.clone());
+        }
+        node.dial_peer(hub_id).await.unwrap();
+    }
+
+    // No direct path between the two
+    for a in &ws_addrs {
+        quic_only.address_book().add(ws_id, a.clone());
+    }
+    assert!(quic_only.dial_peer(ws_id).await.is_err());
+
+    for handle in [&quic_only, &ws_only, &hub] {
+        handle.subscribe("transport-test").await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    quic_only
+        .publish("transport-test", b"over quic and websocket".to_vec())
+        .await
+        .unwrap();
+    let received = next_message(&ws_only, Duration::from_secs(10))
+        .await
+        .expect("message not relayed by the hub");
+    assert_eq!(received, b"over quic and websocket");
+    assert!(!ws_only
+        .connected_peers()
+        .await
+        .contains(&quic_only.local_peer_id().await));
+}
+
+#[tokio::test]
+async fn test_happy_eyeballs_falls_back_from_stalled_address() {
+    let (server, server_addrs) = spawn_node(true, true, false).await;
+    let (client, _) = spawn_node(true, true, false).await;
+    let server_id = server.local_peer_id().await;
+
+    // Accepts TCP connections but never completes a handshake
+    let stalled = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let stalled_addr = addr(&format!(
+        "/ip4/127.0.0.1/tcp/{}",
+        stalled.local_addr().unwrap().port()
+    ));
+    let book = client.address_book();
+    book.add(server_id, stalled_addr.clone());
+    book.record_rtt(server_id, &stalled_addr, Duration::from_millis(1));
+    for a in &server_addrs {
+        book.add(server_id, a.clone());
+    }
+    assert_eq!(book.best(&server_id), Some(stalled_addr.clone()));
+
+    let started = Instant::now();
+    client.dial_peer(server_id).await.unwrap();
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed >= HAPPY_EYEBALLS_DELAY,
+        "raced too early: {:?}",
+        elapsed
+    );
+    assert!(
+        elapsed < Duration::from_secs(5),
+        "no fallback: {:?}",
+        elapsed
+    );
+    assert!(client.connected_peers().await.contains(&server_id));
+
+    // The address that connected gets a measured round-trip time
+    let mut measured = false;
+    for _ in 0..50 {
+        if book
+            .addresses(&server_id)
+            .iter()
+            .any(|i| i.address != stalled_addr && i.rtt.is_some())
+        {
+            measured = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(measured);
+}
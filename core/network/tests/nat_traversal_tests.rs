@@ -1,5 +1,6 @@
 //! Comprehensive tests for NAT traversal functionality
 
+use qudag_network::port_mapping::SSDP_MULTICAST;
 use qudag_network::{
     ConnectionManager, ConnectionUpgradeManager, HolePunchCoordinator, NatInfo, NatTraversalConfig,
     NatTraversalManager, NatType, PortMapping, PortMappingProtocol, RelayManager, StunClient,
@@ -266,6 +267,8 @@ async fn test_nat_traversal_integration() {
         detection_interval: Duration::from_secs(60),
         upgrade_interval: Duration::from_secs(30),
         port_mapping_lifetime: Duration::from_secs(300),
+        ssdp_target: SSDP_MULTICAST,
+        nat_pmp_gateway: None,
     };
 
     let connection_manager = Arc::new(ConnectionManager::new(10));
@@ -411,5 +414,7 @@ fn create_test_nat_config() -> NatTraversalConfig {
         detection_interval: Duration::from_secs(60),
         upgrade_interval: Duration::from_secs(30),
         port_mapping_lifetime: Duration::from_secs(300),
+        ssdp_target: SSDP_MULTICAST,
+        nat_pmp_gateway: None,
     }
 }
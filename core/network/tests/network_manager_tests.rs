@@ -0,0 +1,278 @@
+//! Tests for the single layered transport: `SecureFrame` records on the wire
+//! of the pq-noise upgrade, the connection pool layer, and `NetworkManager`
+//! as a façade over a P2P node.
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::poll_fn;
+use futures::prelude::*;
+use libp2p::core::transport::{ListenerId, MemoryTransport, Transport, TransportEvent};
+use libp2p::core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade};
+use libp2p::identity::Keypair;
+use libp2p::Multiaddr;
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+use qudag_network::p2p::{NetworkConfig as P2PConfig, P2PHandle, P2PNode};
+use qudag_network::pq_noise::{PqNoiseConfig, PROTOCOL_NAME};
+use qudag_network::transport::SecureFrame;
+use qudag_network::types::{ConnectionStatus, PeerId as PoolPeerId};
+use qudag_network::{NetworkConfig, NetworkEvent, NetworkManager, RequestReply};
+use rand::{thread_rng, Rng};
+
+/// Socket recording every byte written to it
+struct Tap<T> {
+    inner: T,
+    written: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Tap<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Tap<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let n = futures::ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.written.lock().unwrap().extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+fn random_memory_addr() -> Multiaddr {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    format!("/memory/{}", port).parse().unwrap()
+}
+
+fn pq_config() -> PqNoiseConfig {
+    let identity = Keypair::generate_ed25519();
+    let ml_dsa = Arc::new(MlDsaKeyPair::generate(&mut thread_rng()).unwrap());
+    PqNoiseConfig::new(&identity, ml_dsa)
+}
+
+#[tokio::test]
+async fn test_pq_noise_records_are_secure_frames() {
+    let addr = random_memory_addr();
+    let mut listener = MemoryTransport::default();
+    listener
+        .listen_on(ListenerId::next(), addr.clone())
+        .unwrap();
+    let dial = MemoryTransport::default().dial(addr).unwrap();
+    let accept = async {
+        loop {
+            let event = poll_fn(|cx| Pin::new(&mut listener).poll(cx)).await;
+            if let TransportEvent::Incoming { upgrade, .. } = event {
+                return upgrade.await.unwrap();
+            }
+        }
+    };
+    let (outbound, inbound) = futures::join!(dial, accept);
+
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let outbound = Tap {
+        inner: outbound.unwrap(),
+        written: written.clone(),
+    };
+    let (out, inc) = futures::join!(
+        pq_config().upgrade_outbound(outbound, PROTOCOL_NAME),
+        pq_config().upgrade_inbound(inbound, PROTOCOL_NAME),
+    );
+    let (_, mut out) = out.unwrap();
+    let (_, mut inc) = inc.unwrap();
+    written.lock().unwrap().clear();
+
+    for (sequence, message) in [b"first".as_slice(), b"second"].into_iter().enumerate() {
+        out.write_all(message).await.unwrap();
+        out.flush().await.unwrap();
+        let mut received = vec![0u8; message.len()];
+        inc.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, message);
+
+        let bytes = std::mem::take(&mut *written.lock().unwrap());
+        let frame = SecureFrame::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes.len(), SecureFrame::HEADER_SIZE + message.len());
+        assert_eq!(frame.frame_type, SecureFrame::DATA);
+        assert_eq!(frame.sequence, sequence as u64);
+        assert_eq!(frame.length as usize, message.len());
+        // Only ciphertext goes over the wire
+        assert_ne!(frame.payload, message);
+    }
+}
+
+async fn spawn_node() -> (P2PHandle, Multiaddr) {
+    let addr = random_memory_addr();
+    let config = P2PConfig {
+        listen_addrs: vec![addr.to_string()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key: [6u8; 32],
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr)
+}
+
+async fn eventually(mut condition: impl FnMut() -> bool) -> bool {
+    for _ in 0..50 {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_connection_pool_tracks_transport_connections() {
+    let (server, server_addr) = spawn_node().await;
+    let (client, _) = spawn_node().await;
+    let server_id = server.local_peer_id().await;
+    let client_id = client.local_peer_id().await;
+
+    assert_eq!(
+        client.connect(server_addr.clone()).await.unwrap(),
+        server_id
+    );
+
+    let pool = client.connection_manager();
+    let pooled_server = PoolPeerId::from(&server_id);
+    assert!(pool.has_live_connection(&pooled_server));
+    assert_eq!(
+        pool.get_status(&pooled_server),
+        Some(ConnectionStatus::Connected)
+    );
+    let info = pool.get_connection_info(&pooled_server).unwrap();
+    assert_eq!(info.metadata["remote_addr"], server_addr.to_string());
+    // The peer's pool sees the inbound side
+    let server_pool = server.connection_manager().clone();
+    assert!(eventually(|| server_pool.has_live_connection(&PoolPeerId::from(&client_id))).await);
+
+    // Reusing the open connection needs no new dial
+    pool.connect(pooled_server).await.unwrap();
+
+    client.disconnect(server_id).await.unwrap();
+    assert!(eventually(|| !pool.has_live_connection(&pooled_server)).await);
+    assert_eq!(pool.connection_count(), 0);
+    assert!(client.disconnect(server_id).await.is_err());
+}
+
+async fn start_manager() -> NetworkManager {
+    let config = NetworkConfig {
+        enable_nat_traversal: false,
+        p2p: P2PConfig {
+            listen_addrs: vec![random_memory_addr().to_string()],
+            enable_mdns: false,
+            enable_websocket: false,
+            obfuscation_key: [7u8; 32],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut manager = NetworkManager::with_config(config);
+    manager.initialize().await.unwrap();
+    manager
+}
+
+async fn next_request(manager: &NetworkManager) -> (libp2p::PeerId, Vec<u8>, RequestReply) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match manager.next_event().await {
+                Some(NetworkEvent::RequestReceived { from, data, reply }) => {
+                    break (from, data, reply)
+                }
+                Some(_) => continue,
+                None => panic!("node stopped"),
+            }
+        }
+    })
+    .await
+    .expect("no request received")
+}
+
+#[tokio::test]
+async fn test_network_manager_is_a_facade_over_the_node() {
+    let mut server = start_manager().await;
+    let mut client = start_manager().await;
+    let server_id = server.local_peer_id().unwrap();
+    let client_id = client.local_peer_id().unwrap();
+    assert_eq!(server.node().unwrap().local_peer_id().await, server_id);
+
+    let mut listeners = Vec::new();
+    for _ in 0..50 {
+        listeners = server.node().unwrap().listeners().await;
+        if !listeners.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let address = listeners[0].to_string();
+
+    assert_eq!(client.connect_peer(&address).await.unwrap(), server_id);
+    assert!(client.get_connected_peers().await.contains(&server_id));
+    assert_eq!(client.get_network_stats().await.connected_peers, 1);
+    let metadata = client.get_peer_metadata(&server_id).await.unwrap();
+    assert_eq!(metadata.address, address);
+
+    // The manager is the only reader of the node's events
+    assert!(server.node().unwrap().take_events().await.is_none());
+
+    // Requests reach the server's application, which answers them
+    let (response, ()) = tokio::join!(
+        client.send_request(&server_id, b"through the facade".to_vec()),
+        async {
+            let (from, data, reply) = next_request(&server).await;
+            assert_eq!(from, client_id);
+            assert_eq!(data, b"through the facade");
+            reply.send(b"answered".to_vec()).unwrap();
+            assert!(reply.send(b"twice".to_vec()).is_err());
+        }
+    );
+    assert_eq!(response.unwrap(), b"answered");
+
+    // A request dropped unanswered gets an empty reply
+    let (response, ()) = tokio::join!(
+        client.send_request(&server_id, b"unanswered".to_vec()),
+        async {
+            next_request(&server).await;
+        }
+    );
+    assert!(response.unwrap().is_empty());
+
+    // Blacklisting goes through the node's peer scoring, which refuses
+    // dials to the peer
+    client.blacklist_peer(server_id).await;
+    assert!(!client.get_connected_peers().await.contains(&server_id));
+    assert!(client
+        .connect_peer(&format!("{}/p2p/{}", address, server_id))
+        .await
+        .is_err());
+
+    assert!(client.connect_peer("not an address").await.is_err());
+
+    client.shutdown().await.unwrap();
+    server.shutdown().await.unwrap();
+    assert!(client.get_connected_peers().await.is_empty());
+    assert!(client.send_message(&server_id, Vec::new()).await.is_err());
+}
@@ -0,0 +1,180 @@
+//! Tests for persistent node identities and the identity certificate
+//! exchanged after identify.
+
+use std::time::Duration;
+
+use libp2p::Multiaddr;
+use qudag_network::node_identity::{
+    advertised_digest, agent_version, IdentityCertificate, NodeIdentity, NodeIdentityError,
+    IDENTITY_FILE,
+};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode};
+use rand::{thread_rng, Rng};
+
+#[test]
+fn test_identity_survives_reload() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("node").join(IDENTITY_FILE);
+
+    let created = NodeIdentity::load_or_create(&path, None).unwrap();
+    let loaded = NodeIdentity::load_or_create(&path, None).unwrap();
+
+    assert_eq!(created.peer_id(), loaded.peer_id());
+    assert_eq!(created.ml_dsa().public_key(), loaded.ml_dsa().public_key());
+    // The stored certificate is kept rather than re-issued
+    assert_eq!(created.certificate(), loaded.certificate());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn test_certificate_stored_beside_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(IDENTITY_FILE);
+    let certificate_path = path.with_extension("cert");
+
+    let created = NodeIdentity::load_or_create(&path, None).unwrap();
+    let stored = IdentityCertificate::from_bytes(&std::fs::read(&certificate_path).unwrap());
+    assert_eq!(&stored.unwrap(), created.certificate());
+
+    // Another node's certificate is not taken over, and a fresh one replaces it
+    let other = NodeIdentity::generate().unwrap();
+    std::fs::write(&certificate_path, other.certificate().to_bytes()).unwrap();
+    let loaded = NodeIdentity::load_or_create(&path, None).unwrap();
+    assert_eq!(loaded.certificate().verify().unwrap(), created.peer_id());
+    assert_ne!(loaded.certificate(), other.certificate());
+
+    // The replacement is kept from then on
+    let reloaded = NodeIdentity::load_or_create(&path, None).unwrap();
+    assert_eq!(reloaded.certificate(), loaded.certificate());
+}
+
+#[test]
+fn test_encrypted_identity_requires_password() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(IDENTITY_FILE);
+
+    let created = NodeIdentity::load_or_create(&path, Some("hunter2")).unwrap();
+    let loaded = NodeIdentity::load_or_create(&path, Some("hunter2")).unwrap();
+    assert_eq!(created.peer_id(), loaded.peer_id());
+
+    assert!(matches!(
+        NodeIdentity::load_or_create(&path, Some("wrong")),
+        Err(NodeIdentityError::KeyEncoding(_))
+    ));
+    assert!(matches!(
+        NodeIdentity::load_or_create(&path, None),
+        Err(NodeIdentityError::KeyEncoding(_))
+    ));
+}
+
+#[test]
+fn test_certificate_binds_both_keys() {
+    let identity = NodeIdentity::generate().unwrap();
+    let certificate = identity.certificate();
+
+    assert_eq!(certificate.verify().unwrap(), identity.peer_id());
+    let decoded = IdentityCertificate::from_bytes(&certificate.to_bytes()).unwrap();
+    assert_eq!(&decoded, certificate);
+
+    let digest = advertised_digest(&agent_version(certificate)).unwrap();
+    assert_eq!(digest, certificate.digest());
+    certificate
+        .verify_for(&identity.peer_id(), &digest)
+        .unwrap();
+
+    // Claiming another node's ML-DSA key breaks both signatures
+    let other = NodeIdentity::generate().unwrap();
+    let mut forged = certificate.clone();
+    forged.ml_dsa_public_key = other.ml_dsa().public_key().to_vec();
+    assert!(forged.verify().is_err());
+
+    // A valid certificate presented by the wrong peer is rejected
+    assert!(certificate
+        .verify_for(&other.peer_id(), &certificate.digest())
+        .is_err());
+    assert!(certificate
+        .verify_for(&identity.peer_id(), &[0u8; 32])
+        .is_err());
+}
+
+#[test]
+fn test_agent_version_without_digest() {
+    assert_eq!(advertised_digest("rust-libp2p/0.44.2"), None);
+    assert_eq!(advertised_digest("qudag/0.4.3 idcert=zz"), None);
+}
+
+async fn spawn_node(identity: NodeIdentity) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        identity: Some(identity),
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+#[tokio::test]
+async fn test_peers_verify_identity_over_identify() {
+    let listener_identity = NodeIdentity::generate().unwrap();
+    let dialer_identity = NodeIdentity::generate().unwrap();
+
+    let (listen_handle, addr) = spawn_node(listener_identity.clone()).await;
+    let (dial_handle, _) = spawn_node(dialer_identity.clone()).await;
+    assert_eq!(
+        listen_handle.local_peer_id().await,
+        listener_identity.peer_id()
+    );
+
+    dial_handle.dial(addr).await.unwrap();
+
+    let verified = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(P2PEvent::PeerIdentityVerified {
+                peer_id,
+                ml_dsa_public_key,
+            }) = dial_handle.next_event().await
+            {
+                return (peer_id, ml_dsa_public_key);
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(verified.0, listener_identity.peer_id());
+    assert_eq!(verified.1, listener_identity.ml_dsa().public_key());
+    assert_eq!(
+        dial_handle
+            .peer_identity(listener_identity.peer_id())
+            .await
+            .unwrap(),
+        listener_identity.ml_dsa().public_key()
+    );
+
+    // The listener verifies the dialer the same way
+    let mut dialer_key = None;
+    for _ in 0..50 {
+        dialer_key = listen_handle.peer_identity(dialer_identity.peer_id()).await;
+        if dialer_key.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        dialer_key.as_deref(),
+        Some(dialer_identity.ml_dsa().public_key())
+    );
+}
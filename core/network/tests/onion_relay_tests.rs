@@ -0,0 +1,334 @@
+//! Tests for onion relaying: relay descriptors, cell handling by a chain of
+//! relays, and circuits between P2P nodes.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::onion::DirectoryClient;
+use qudag_network::onion_relay::{
+    client_handshake, ClientCircuit, OnionCell, OnionRelay, OnionRelayConfig, OnionReply,
+    RelayAction, RelayCommand, RelayDescriptor, RelayResponse,
+};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode};
+use qudag_network::NodeIdentity;
+use rand::{thread_rng, Rng};
+
+#[tokio::test]
+async fn test_relay_descriptor_verification() {
+    let identity = NodeIdentity::generate().unwrap();
+    let config = OnionRelayConfig::default();
+    let relay = OnionRelay::new(identity.peer_id(), config.clone()).unwrap();
+    let addr: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+
+    let descriptor = RelayDescriptor::new(
+        &identity,
+        relay.onion_key(),
+        std::slice::from_ref(&addr),
+        &config,
+    )
+    .unwrap();
+    let decoded = RelayDescriptor::from_bytes(&descriptor.to_bytes()).unwrap();
+    assert_eq!(decoded.verify().unwrap(), identity.peer_id());
+    assert_eq!(decoded.multiaddrs(), vec![addr]);
+
+    // Every signed field is covered
+    let mut tampered = descriptor.clone();
+    tampered.bandwidth += 1;
+    assert!(tampered.verify().is_err());
+    let mut tampered = descriptor.clone();
+    tampered.addresses = vec!["/ip4/10.0.0.1/tcp/4001"
+        .parse::<Multiaddr>()
+        .unwrap()
+        .to_vec()];
+    assert!(tampered.verify().is_err());
+
+    // Another identity cannot claim the descriptor
+    let mut tampered = descriptor.clone();
+    tampered.certificate = NodeIdentity::generate().unwrap().certificate().clone();
+    assert!(tampered.verify().is_err());
+
+    let directory = DirectoryClient::new();
+    assert!(directory.insert_descriptor(&tampered).await.is_err());
+    assert_eq!(
+        directory.insert_descriptor(&descriptor).await.unwrap(),
+        identity.peer_id()
+    );
+    let node = directory
+        .node(&identity.peer_id().to_bytes())
+        .await
+        .unwrap();
+    assert_eq!(node.public_key.as_bytes(), relay.onion_key().as_bytes());
+    assert_eq!(
+        directory.select_random_nodes(1).await.unwrap(),
+        vec![identity.peer_id().to_bytes()]
+    );
+    assert!(directory.select_random_nodes(2).await.is_err());
+}
+
+/// Hand a cell to a relay and follow it along the chain, as the swarm would
+fn send(
+    relays: &mut HashMap<PeerId, OnionRelay>,
+    from: PeerId,
+    to: PeerId,
+    cell: OnionCell,
+    delivered: &mut Vec<(PeerId, Vec<u8>)>,
+) -> OnionReply {
+    let action = relays.get_mut(&to).unwrap().handle_cell(from, cell);
+    match action {
+        RelayAction::Reply(reply) => reply,
+        RelayAction::Forward {
+            peer_id,
+            cell,
+            pending,
+            ..
+        } => {
+            let reply = send(relays, to, peer_id, cell, delivered);
+            relays.get_mut(&to).unwrap().complete(*pending, Ok(reply))
+        }
+        RelayAction::Teardown { next } => {
+            if let Some((peer_id, circuit_id)) = next {
+                send(
+                    relays,
+                    to,
+                    peer_id,
+                    OnionCell::Destroy { circuit_id },
+                    delivered,
+                );
+            }
+            OnionReply::Destroyed
+        }
+        RelayAction::Deliver { payload, reply } => {
+            delivered.push((to, payload));
+            reply
+        }
+        RelayAction::ServeDirectory => OnionReply::Directory(Vec::new()),
+        RelayAction::Push {
+            reply,
+            peer_id,
+            cell,
+        } => {
+            if relays.contains_key(&peer_id) {
+                send(relays, to, peer_id, cell, delivered);
+            }
+            reply
+        }
+    }
+}
+
+#[test]
+fn test_cells_through_relay_chain() {
+    let client = PeerId::random();
+    let hops: Vec<PeerId> = (0..3).map(|_| PeerId::random()).collect();
+    let destination = PeerId::random();
+    let mut relays: HashMap<PeerId, OnionRelay> = hops
+        .iter()
+        .chain([&destination])
+        .map(|&peer_id| {
+            (
+                peer_id,
+                OnionRelay::new(peer_id, OnionRelayConfig::default()).unwrap(),
+            )
+        })
+        .collect();
+    let mut delivered = Vec::new();
+    let circuit_id = 7;
+
+    // Create the first hop directly
+    let (kem_ciphertext, keys, expected) = client_handshake(relays[&hops[0]].onion_key()).unwrap();
+    let reply = send(
+        &mut relays,
+        client,
+        hops[0],
+        OnionCell::Create {
+            circuit_id,
+            kem_ciphertext,
+        },
+        &mut delivered,
+    );
+    assert!(matches!(reply, OnionReply::Created { confirmation } if confirmation == expected));
+    let mut circuit = ClientCircuit::new(hops[0], keys);
+
+    // Extend through the hops built so far
+    for &hop in &hops[1..] {
+        let (kem_ciphertext, keys, expected) = client_handshake(relays[&hop].onion_key()).unwrap();
+        let command = RelayCommand::Extend {
+            peer_id: hop.to_bytes(),
+            addresses: Vec::new(),
+            kem_ciphertext,
+        };
+        let payload = circuit.seal_for(circuit.len() - 1, &command).unwrap();
+        let reply = send(
+            &mut relays,
+            client,
+            hops[0],
+            OnionCell::Relay {
+                circuit_id,
+                payload,
+            },
+            &mut delivered,
+        );
+        let OnionReply::Relay { payload } = reply else {
+            panic!("unexpected reply {:?}", reply);
+        };
+        let (answered_by, response) = circuit.open_reply(&payload).unwrap();
+        assert_eq!(answered_by, circuit.len() - 1);
+        assert!(
+            matches!(response, RelayResponse::Extended { confirmation } if confirmation == expected)
+        );
+        circuit.push_hop(hop, keys);
+    }
+    assert_eq!(circuit.hops, hops);
+
+    // The exit delivers to the destination, which only sees the exit
+    let data = |destination: Option<PeerId>| RelayCommand::Data {
+        destination: destination.map(|peer_id| peer_id.to_bytes()),
+        addresses: Vec::new(),
+        payload: b"hello".to_vec(),
+    };
+    for (target, receiver) in [(Some(destination), destination), (None, hops[2])] {
+        let payload = circuit.seal_for(2, &data(target)).unwrap();
+        let OnionReply::Relay { payload } = send(
+            &mut relays,
+            client,
+            hops[0],
+            OnionCell::Relay {
+                circuit_id,
+                payload,
+            },
+            &mut delivered,
+        ) else {
+            panic!("data not relayed");
+        };
+        let (answered_by, response) = circuit.open_reply(&payload).unwrap();
+        assert_eq!(answered_by, 2);
+        assert!(matches!(response, RelayResponse::Delivered));
+        assert_eq!(delivered.pop(), Some((receiver, b"hello".to_vec())));
+    }
+
+    // Tampered cells and cells on other circuits are refused
+    let mut payload = circuit.seal_for(2, &data(None)).unwrap();
+    *payload.last_mut().unwrap() ^= 1;
+    let reply = send(
+        &mut relays,
+        client,
+        hops[0],
+        OnionCell::Relay {
+            circuit_id,
+            payload,
+        },
+        &mut delivered,
+    );
+    assert!(matches!(reply, OnionReply::Failed(_)));
+    let payload = circuit.seal_for(2, &data(None)).unwrap();
+    let reply = send(
+        &mut relays,
+        PeerId::random(),
+        hops[0],
+        OnionCell::Relay {
+            circuit_id,
+            payload,
+        },
+        &mut delivered,
+    );
+    assert!(matches!(reply, OnionReply::Failed(_)));
+    assert!(delivered.is_empty());
+
+    // Teardown reaches every hop
+    let reply = send(
+        &mut relays,
+        client,
+        hops[0],
+        OnionCell::Destroy { circuit_id },
+        &mut delivered,
+    );
+    assert!(matches!(reply, OnionReply::Destroyed));
+    assert!(hops.iter().all(|hop| relays[hop].circuit_count() == 0));
+}
+
+async fn spawn_node(obfuscation_key: [u8; 32]) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key,
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+async fn wait_for_relays(node: &P2PHandle, count: usize) {
+    for _ in 0..100 {
+        if node.known_relays().await.len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("directory never reached {} relays", count);
+}
+
+#[tokio::test]
+async fn test_circuit_between_nodes() {
+    let key = rand::random();
+    let (hub, hub_addr) = spawn_node(key).await;
+    let mut relays = Vec::new();
+    for _ in 0..3 {
+        let (relay, _) = spawn_node(key).await;
+        relay.dial(hub_addr.clone()).await.unwrap();
+        relays.push(relay);
+    }
+    // Relays announce themselves over gossip
+    wait_for_relays(&hub, 3).await;
+
+    // A late joiner fetches the directory from its peer
+    let (client, _) = spawn_node(key).await;
+    client.dial(hub_addr).await.unwrap();
+    wait_for_relays(&client, 4).await;
+
+    let circuit_id = tokio::time::timeout(Duration::from_secs(30), client.build_circuit(3))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let destination = relays.pop().unwrap();
+    let destination_id = destination.local_peer_id().await;
+    tokio::time::timeout(
+        Duration::from_secs(30),
+        client.send_onion_message(circuit_id, Some(destination_id), b"anonymous".to_vec()),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let payload = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(P2PEvent::OnionMessageReceived { payload }) = destination.next_event().await
+            {
+                return payload;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(payload, b"anonymous");
+
+    client.destroy_circuit(circuit_id).await.unwrap();
+    assert!(client
+        .send_onion_message(circuit_id, None, b"late".to_vec())
+        .await
+        .is_err());
+}
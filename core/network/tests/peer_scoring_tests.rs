@@ -0,0 +1,219 @@
+//! Tests for peer scoring: penalties and bans, the allowlist, persisted
+//! bans, and enforcement on dial and accept between P2P nodes.
+
+use std::time::Duration;
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode};
+use qudag_network::peer_scoring::{
+    PeerEvent, PeerScoring, PeerScoringConfig, PeerScoringError, PEER_SCORES_FILE,
+};
+use rand::{thread_rng, Rng};
+
+#[test]
+fn test_penalties_ban_below_threshold() {
+    let scoring = PeerScoring::default();
+    let peer = PeerId::random();
+
+    assert!(scoring.report(peer, PeerEvent::InvalidVertex).is_none());
+    assert!(scoring.report(peer, PeerEvent::InvalidMessage).is_none());
+    assert!(scoring.report(peer, PeerEvent::InvalidVertex).is_none());
+    assert_eq!(scoring.score(&peer).local, -50.0);
+    assert!(scoring.check(&peer).is_ok());
+
+    let ban = scoring.report(peer, PeerEvent::Timeout).unwrap();
+    assert_eq!(ban.peer, peer);
+    assert!(scoring.is_banned(&peer));
+    assert!(matches!(
+        scoring.check(&peer),
+        Err(PeerScoringError::Banned(banned)) if banned == peer
+    ));
+    // A banned peer is not banned again
+    assert!(scoring.report(peer, PeerEvent::InvalidVertex).is_none());
+
+    // The gossipsub score counts towards bans as well
+    let spammer = PeerId::random();
+    scoring.adjust(spammer, -30.0);
+    assert!(scoring.set_gossip_score(spammer, -25.0).is_some());
+}
+
+#[test]
+fn test_equivocation_and_allowlist() {
+    let scoring = PeerScoring::new(PeerScoringConfig::default());
+    let peer = PeerId::random();
+    let ban = scoring.report(peer, PeerEvent::Equivocation).unwrap();
+    assert_eq!(ban.reason, "equivocation");
+    assert_eq!(scoring.score(&peer).local, -100.0);
+
+    // Allowlisted peers lose score but are not banned automatically
+    let friend = PeerId::random();
+    scoring.allow(friend);
+    assert!(scoring.report(friend, PeerEvent::Equivocation).is_none());
+    assert!(scoring.report(friend, PeerEvent::InvalidVertex).is_none());
+    assert!(!scoring.is_banned(&friend));
+
+    // Manual bans apply to them all the same
+    scoring.ban(friend, Duration::from_secs(60), "operator");
+    assert!(scoring.check(&friend).is_err());
+    assert!(scoring.unban(&friend));
+    assert!(!scoring.unban(&friend));
+    assert!(scoring.check(&friend).is_ok());
+
+    // Allowlist-only mode refuses everyone else
+    let strict = PeerScoring::new(PeerScoringConfig {
+        allowlist: vec![friend],
+        allowlist_only: true,
+        ..Default::default()
+    });
+    assert!(strict.check(&friend).is_ok());
+    assert!(matches!(
+        strict.check(&peer),
+        Err(PeerScoringError::NotAllowed(_))
+    ));
+}
+
+#[test]
+fn test_bans_persist_and_expire() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(PEER_SCORES_FILE);
+    let banned = PeerId::random();
+    let expired = PeerId::random();
+    let friend = PeerId::random();
+    {
+        let scoring = PeerScoring::open(&path, PeerScoringConfig::default()).unwrap();
+        scoring.ban(banned, Duration::from_secs(3600), "spam");
+        scoring.ban(expired, Duration::ZERO, "brief");
+        scoring.allow(friend);
+        scoring.report(PeerId::random(), PeerEvent::Timeout);
+    }
+
+    let scoring = PeerScoring::open(&path, PeerScoringConfig::default()).unwrap();
+    assert!(scoring.is_banned(&banned));
+    assert!(!scoring.is_banned(&expired));
+    assert!(scoring.is_allowlisted(&friend));
+    let bans = scoring.bans();
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].reason, "spam");
+    // Scores are not persisted
+    assert!(scoring.scores().is_empty());
+
+    // Expired bans are dropped from the file on maintenance
+    scoring.maintain();
+    scoring.unban(&banned);
+    scoring.flush();
+    let reopened = PeerScoring::open(&path, PeerScoringConfig::default()).unwrap();
+    assert!(reopened.bans().is_empty());
+    assert!(reopened.is_allowlisted(&friend));
+
+    // Repeated bans last longer
+    let scoring = PeerScoring::new(PeerScoringConfig {
+        ban_duration: Duration::from_secs(100),
+        ..Default::default()
+    });
+    let peer = PeerId::random();
+    let first = scoring.adjust(peer, -60.0).unwrap();
+    scoring.unban(&peer);
+    let second = scoring.adjust(peer, -60.0).unwrap();
+    let extra = second.until.duration_since(first.until).unwrap();
+    assert!(extra >= Duration::from_secs(99), "{:?}", extra);
+}
+
+async fn spawn_node(scoring: PeerScoringConfig) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key: [7u8; 32],
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        peer_scoring: scoring,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+async fn wait_for_peers(handle: &P2PHandle, count: usize) -> bool {
+    for _ in 0..50 {
+        if handle.connected_peers().await.len() == count {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_bans_enforced_between_nodes() {
+    let (alice, alice_addr) = spawn_node(PeerScoringConfig::default()).await;
+    let (bob, bob_addr) = spawn_node(PeerScoringConfig::default()).await;
+    let alice_id = alice.local_peer_id().await;
+    let bob_id = bob.local_peer_id().await;
+
+    bob.dial(alice_addr.clone()).await.unwrap();
+    assert!(wait_for_peers(&alice, 1).await);
+
+    // Banning a connected peer disconnects it
+    alice.ban_peer(bob_id, Duration::from_secs(3600), "test");
+    let banned = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match alice.next_event().await {
+                Some(P2PEvent::PeerBanned(ban)) => break ban,
+                Some(_) => continue,
+                None => panic!("node stopped"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(banned.peer, bob_id);
+    assert!(wait_for_peers(&alice, 0).await);
+    assert!(wait_for_peers(&bob, 0).await);
+
+    // The banned peer is refused on accept, and not dialled either
+    let _ = bob.dial(alice_addr.clone()).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(alice.connected_peers().await.is_empty());
+    assert!(alice
+        .dial(format!("{}/p2p/{}", bob_addr, bob_id).parse().unwrap())
+        .await
+        .is_err());
+
+    // Once unbanned it may connect again
+    assert!(alice.unban_peer(&bob_id));
+    bob.dial(alice_addr).await.unwrap();
+    assert!(wait_for_peers(&alice, 1).await);
+    assert_eq!(bob.connected_peers().await, vec![alice_id]);
+}
+
+#[tokio::test]
+async fn test_allowlist_only_node() {
+    let (outsider, _) = spawn_node(PeerScoringConfig::default()).await;
+    let (member, _) = spawn_node(PeerScoringConfig::default()).await;
+    let member_id = member.local_peer_id().await;
+    let (gated, gated_addr) = spawn_node(PeerScoringConfig {
+        allowlist: vec![member_id],
+        allowlist_only: true,
+        ..Default::default()
+    })
+    .await;
+
+    let _ = outsider.dial(gated_addr.clone()).await;
+    member.dial(gated_addr).await.unwrap();
+    assert!(wait_for_peers(&gated, 1).await);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(gated.connected_peers().await, vec![member_id]);
+    assert!(gated.peer_scoring().is_allowlisted(&member_id));
+}
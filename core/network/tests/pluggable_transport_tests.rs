@@ -0,0 +1,297 @@
+//! Loopback tests for the pluggable transports, on their own and below the
+//! P2P node.
+
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::future::poll_fn;
+use futures::{AsyncReadExt, AsyncWriteExt};
+use libp2p::core::transport::{ListenerId, Transport as _, TransportEvent};
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{tcp, Multiaddr};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PNode, QuDagRequest, QuDagResponse};
+use qudag_network::pluggable_transport::{
+    DisguisedStream, ObfuscatedTransport, PluggableTransport, PluggableTransportConfig,
+};
+use rand::{thread_rng, RngCore};
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::{TcpListener, TcpStream};
+
+const TRANSPORTS: [PluggableTransport; 3] = [
+    PluggableTransport::HttpLongPoll,
+    PluggableTransport::WebSocket,
+    PluggableTransport::Obfs4,
+];
+
+type Tcp = ObfuscatedTransport<tcp::tokio::Transport>;
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    thread_rng().fill_bytes(&mut data);
+    data
+}
+
+fn obfuscated(config: &PluggableTransportConfig) -> Tcp {
+    ObfuscatedTransport::new(
+        tcp::tokio::Transport::new(tcp::Config::default().nodelay(true)),
+        config.clone(),
+    )
+}
+
+/// Listen on loopback, returning the transport and its address
+async fn listen(config: &PluggableTransportConfig) -> (Tcp, Multiaddr) {
+    let mut transport = obfuscated(config);
+    transport
+        .listen_on(ListenerId::next(), "/ip4/127.0.0.1/tcp/0".parse().unwrap())
+        .unwrap();
+    match poll_fn(|cx| Pin::new(&mut transport).poll(cx)).await {
+        TransportEvent::NewAddress { listen_addr, .. } => (transport, listen_addr),
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+/// Accept one connection and run its handshake
+async fn accept(transport: &mut Tcp) -> DisguisedStream<tcp::tokio::TcpStream> {
+    loop {
+        if let TransportEvent::Incoming { upgrade, .. } =
+            poll_fn(|cx| Pin::new(&mut *transport).poll(cx)).await
+        {
+            return upgrade.await.unwrap();
+        }
+    }
+}
+
+async fn dial(
+    config: &PluggableTransportConfig,
+    addr: Multiaddr,
+) -> Result<DisguisedStream<tcp::tokio::TcpStream>, String> {
+    obfuscated(config)
+        .dial(addr)
+        .unwrap()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Exchange request-sized messages and a bulk transfer in both directions
+async fn exchange(transport: PluggableTransport) {
+    let config = PluggableTransportConfig::new(transport, rand::random());
+    let (mut listener, addr) = listen(&config).await;
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&mut listener).await;
+        // Answer each small message, then echo the bulk transfer
+        for _ in 0..10 {
+            let mut message = [0u8; 64];
+            stream.read_exact(&mut message).await.unwrap();
+            message.reverse();
+            stream.write_all(&message).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+        let (mut reader, mut writer) = stream.split();
+        futures::io::copy(&mut reader, &mut writer).await.unwrap();
+        writer.close().await.unwrap();
+    });
+
+    let mut stream = dial(&config, addr).await.unwrap();
+    for _ in 0..10 {
+        let message = random_bytes(64);
+        stream.write_all(&message).await.unwrap();
+        stream.flush().await.unwrap();
+        let mut reply = [0u8; 64];
+        stream.read_exact(&mut reply).await.unwrap();
+        reply.reverse();
+        assert_eq!(reply.to_vec(), message);
+    }
+
+    let bulk = random_bytes(300 * 1024);
+    let (mut reader, mut writer) = stream.split();
+    let sent = bulk.clone();
+    let send = tokio::spawn(async move {
+        writer.write_all(&sent).await.unwrap();
+        writer.close().await.unwrap();
+    });
+    let mut echoed = Vec::new();
+    tokio::time::timeout(Duration::from_secs(30), reader.read_to_end(&mut echoed))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(echoed, bulk);
+    send.await.unwrap();
+    server.await.unwrap();
+}
+
+/// Bytes a dialer sends to a plain TCP listener before giving up
+async fn sniff_handshake(transport: PluggableTransport) -> Vec<u8> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = PluggableTransportConfig::new(transport, rand::random());
+    let dialer = tokio::spawn(async move {
+        let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", addr.port())
+            .parse()
+            .unwrap();
+        let _ = dial(&config, addr).await;
+    });
+    let (mut socket, _) = listener.accept().await.unwrap();
+    let mut sent = vec![0u8; 4096];
+    let n = socket.read(&mut sent).await.unwrap();
+    sent.truncate(n);
+    drop(socket);
+    dialer.await.unwrap();
+    sent
+}
+
+#[tokio::test]
+async fn test_http_long_poll_loopback() {
+    exchange(PluggableTransport::HttpLongPoll).await;
+
+    let hello = String::from_utf8(sniff_handshake(PluggableTransport::HttpLongPoll).await).unwrap();
+    assert!(hello.starts_with("GET / HTTP/1.1\r\nHost: cdn.example.com\r\n"));
+    assert!(hello.contains("\r\nCookie: sid="));
+    assert!(hello.ends_with("\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn test_websocket_loopback() {
+    exchange(PluggableTransport::WebSocket).await;
+
+    let hello = String::from_utf8(sniff_handshake(PluggableTransport::WebSocket).await).unwrap();
+    assert!(hello.starts_with("GET / HTTP/1.1\r\n"));
+    assert!(hello.contains("\r\nUpgrade: websocket\r\n"));
+    assert!(hello.contains("\r\nSec-WebSocket-Version: 13\r\n"));
+}
+
+#[tokio::test]
+async fn test_obfs4_loopback() {
+    exchange(PluggableTransport::Obfs4).await;
+
+    // Random bytes of random length
+    let first = sniff_handshake(PluggableTransport::Obfs4).await;
+    let second = sniff_handshake(PluggableTransport::Obfs4).await;
+    assert!(first.len() >= 64 && second.len() >= 64);
+    assert_ne!(first[..32], second[..32]);
+    assert!(!first.starts_with(b"GET") && !first.starts_with(b"/multistream"));
+}
+
+#[tokio::test]
+async fn test_probes_do_not_reveal_listener() {
+    for transport in TRANSPORTS {
+        let config = PluggableTransportConfig::new(transport, rand::random());
+        let (mut listener, addr) = listen(&config).await;
+        tokio::spawn(async move {
+            loop {
+                if let TransportEvent::Incoming { upgrade, .. } =
+                    poll_fn(|cx| Pin::new(&mut listener).poll(cx)).await
+                {
+                    tokio::spawn(upgrade);
+                }
+            }
+        });
+
+        // Dialers without the shared secret are refused; obfs4 never answers
+        // them, leaving the dial to time out
+        let stranger = PluggableTransportConfig::new(transport, rand::random());
+        let dialed = tokio::time::timeout(Duration::from_secs(2), dial(&stranger, addr.clone()));
+        assert!(!matches!(dialed.await, Ok(Ok(_))));
+
+        // An ordinary HTTP client gets a 404 from the HTTP transports, and
+        // obfs4 closes without answering
+        let port = match addr.iter().nth(1) {
+            Some(libp2p::multiaddr::Protocol::Tcp(port)) => port,
+            _ => unreachable!(),
+        };
+        let mut probe = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut request = b"GET / HTTP/1.1\r\nHost: cdn.example.com\r\n\r\n".to_vec();
+        if transport == PluggableTransport::Obfs4 {
+            request = random_bytes(2048);
+        }
+        probe.write_all(&request).await.unwrap();
+        let mut reply = Vec::new();
+        let _ = tokio::time::timeout(Duration::from_secs(5), probe.read_to_end(&mut reply)).await;
+        if transport == PluggableTransport::Obfs4 {
+            assert!(reply.is_empty());
+        } else {
+            assert!(reply.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_p2p_nodes_over_pluggable_transports() {
+    for transport in TRANSPORTS {
+        let key: [u8; 32] = rand::random();
+        let spawn = || async move {
+            let config = NetworkConfig {
+                listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".to_string()],
+                enable_mdns: false,
+                enable_websocket: false,
+                obfuscation_key: key,
+                pluggable_transport: Some(PluggableTransportConfig::new(transport, key)),
+                gossipsub_config: Some(
+                    GossipsubConfigBuilder::default()
+                        .heartbeat_interval(Duration::from_millis(100))
+                        .validation_mode(ValidationMode::Strict)
+                        .build()
+                        .unwrap(),
+                ),
+                ..Default::default()
+            };
+            let (mut node, handle) = P2PNode::new(config).await.unwrap();
+            node.start().await.unwrap();
+            tokio::spawn(async move {
+                let _ = node.run().await;
+            });
+            handle
+        };
+        let server = spawn().await;
+        let client = spawn().await;
+        let server_id = server.local_peer_id().await;
+        let mut listeners = Vec::new();
+        for _ in 0..50 {
+            listeners = server.listeners().await;
+            if !listeners.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        client.dial(listeners[0].clone()).await.unwrap();
+
+        tokio::spawn(async move {
+            while let Some(event) = server.next_event().await {
+                if let P2PEvent::RequestReceived {
+                    request, channel, ..
+                } = event
+                {
+                    let _ = channel.send(QuDagResponse {
+                        request_id: request.request_id,
+                        payload: blake3::hash(&request.payload).as_bytes().to_vec(),
+                    });
+                }
+            }
+        });
+
+        let payload = random_bytes(200 * 1024);
+        let mut response = None;
+        for _ in 0..50 {
+            if client.connected_peers().await.contains(&server_id) {
+                response = Some(
+                    tokio::time::timeout(
+                        Duration::from_secs(30),
+                        client.send_request(
+                            server_id,
+                            QuDagRequest {
+                                request_id: format!("{:?}", transport),
+                                payload: payload.clone(),
+                            },
+                        ),
+                    )
+                    .await
+                    .unwrap()
+                    .unwrap(),
+                );
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let response = response.expect("nodes never connected");
+        assert_eq!(response.payload, blake3::hash(&payload).as_bytes().to_vec());
+    }
+}
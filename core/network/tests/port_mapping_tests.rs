@@ -0,0 +1,597 @@
+//! Tests for UPnP IGD, NAT-PMP and PCP port mapping against an in-process
+//! gateway speaking SSDP, SOAP, NAT-PMP and PCP over loopback.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use qudag_network::port_mapping::{PmpVersion, PortMappingError};
+use qudag_network::{
+    ConnectionManager, NatPmpClient, NatTraversalConfig, NatTraversalError, NatTraversalManager,
+    PortMappingMethod, PortMappingProtocol, UpnpManager,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+const EXTERNAL_IP: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 7);
+
+/// A UPnP forwarding: internal client, internal port, lease and description
+type UpnpEntry = (String, u16, u64, String);
+
+/// A NAT-PMP or PCP forwarding: external port, lifetime and PCP nonce
+type PmpEntry = (u16, u32, Option<[u8; 12]>);
+
+#[derive(Default)]
+struct GatewayState {
+    /// UPnP forwardings by protocol and external port
+    upnp: HashMap<(String, u16), UpnpEntry>,
+    /// NAT-PMP and PCP forwardings by protocol number and internal port
+    pmp: HashMap<(u8, u16), PmpEntry>,
+    /// Mapping requests received per internal port
+    pmp_requests: HashMap<u16, u32>,
+    /// Refuse UPnP leases other than permanent ones
+    permanent_only: bool,
+}
+
+struct FakeGateway {
+    ssdp: SocketAddr,
+    pmp: SocketAddr,
+    state: Arc<Mutex<GatewayState>>,
+}
+
+impl FakeGateway {
+    async fn spawn(pcp: bool, permanent_only: bool) -> Self {
+        let state = Arc::new(Mutex::new(GatewayState {
+            permanent_only,
+            ..Default::default()
+        }));
+        let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http_addr = http.local_addr().unwrap();
+        let ssdp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let ssdp_addr = ssdp.local_addr().unwrap();
+        let pmp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let pmp_addr = pmp.local_addr().unwrap();
+
+        tokio::spawn(serve_ssdp(ssdp, http_addr));
+        let http_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = http.accept().await {
+                tokio::spawn(serve_http(stream, Arc::clone(&http_state)));
+            }
+        });
+        tokio::spawn(serve_pmp(pmp, Arc::clone(&state), pcp));
+
+        Self {
+            ssdp: ssdp_addr,
+            pmp: pmp_addr,
+            state,
+        }
+    }
+}
+
+/// Answer M-SEARCH with a broken device first, then the gateway
+async fn serve_ssdp(socket: UdpSocket, http: SocketAddr) {
+    let mut buf = [0u8; 2048];
+    while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+        let request = String::from_utf8_lossy(&buf[..len]);
+        if !request.starts_with("M-SEARCH")
+            || !request.contains("InternetGatewayDevice:1")
+            || !request.contains("ssdp:discover")
+        {
+            continue;
+        }
+        for path in ["/missing.xml", "/rootDesc.xml"] {
+            let answer = format!(
+                "HTTP/1.1 200 OK\r\n\
+                 CACHE-CONTROL: max-age=120\r\n\
+                 ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+                 USN: uuid:fake-gateway::urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+                 Location: http://{}{}\r\n\r\n",
+                http, path
+            );
+            socket.send_to(answer.as_bytes(), from).await.unwrap();
+        }
+    }
+}
+
+const DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+    <friendlyName>Fake Router &amp; Co</friendlyName>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>
+        <controlURL>/ctl/L3F</controlURL>
+      </service>
+    </serviceList>
+    <deviceList>
+      <device>
+        <deviceType>urn:schemas-upnp-org:device:WANDevice:1</deviceType>
+        <deviceList>
+          <device>
+            <deviceType>urn:schemas-upnp-org:device:WANConnectionDevice:1</deviceType>
+            <serviceList>
+              <service>
+                <serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+                <serviceId>urn:upnp-org:serviceId:WANIPConn1</serviceId>
+                <controlURL>/ctl/IPConn</controlURL>
+              </service>
+            </serviceList>
+          </device>
+        </deviceList>
+      </device>
+    </deviceList>
+  </device>
+</root>"#;
+
+async fn serve_http(mut stream: TcpStream, state: Arc<Mutex<GatewayState>>) {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let head_end = loop {
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(len) => data.extend_from_slice(&buf[..len]),
+        }
+    };
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let header = |name: &str| {
+        head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    let length: usize = header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    while data.len() < head_end + length {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(len) => data.extend_from_slice(&buf[..len]),
+        }
+    }
+    let body = String::from_utf8_lossy(&data[head_end..head_end + length]).to_string();
+    let path = head
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+
+    let (status, answer) = match (head.starts_with("GET"), path.as_str()) {
+        (true, "/rootDesc.xml") => ("200 OK", DESCRIPTION.to_string()),
+        (false, "/ctl/IPConn") => {
+            let action = header("soapaction")
+                .and_then(|action| {
+                    let action = action.trim_matches('"');
+                    let (service, action) = action.split_once('#')?;
+                    (service == "urn:schemas-upnp-org:service:WANIPConnection:1")
+                        .then(|| action.to_string())
+                })
+                .unwrap_or_default();
+            soap(&action, &body, &mut state.lock().unwrap())
+        }
+        _ => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        answer.len(),
+        answer
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// Text of an argument of a SOAP call
+fn argument(body: &str, name: &str) -> String {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    body.split_once(&open)
+        .and_then(|(_, rest)| rest.split_once(&close))
+        .map(|(value, _)| value.to_string())
+        .unwrap_or_default()
+}
+
+fn soap(action: &str, body: &str, state: &mut GatewayState) -> (&'static str, String) {
+    let envelope = |inner: String| {
+        format!(
+            "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\"><s:Body>{}</s:Body></s:Envelope>",
+            inner
+        )
+    };
+    let fault = |code: u16, description: &str| {
+        (
+            "500 Internal Server Error",
+            envelope(format!(
+                "<s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns=\"urn:schemas-upnp-org:control-1-0\"><errorCode>{}</errorCode><errorDescription>{}</errorDescription></UPnPError></detail></s:Fault>",
+                code, description
+            )),
+        )
+    };
+    let success = |inner: &str| {
+        (
+            "200 OK",
+            envelope(format!(
+                "<u:{action}Response xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\">{inner}</u:{action}Response>"
+            )),
+        )
+    };
+    let key = || {
+        (
+            argument(body, "NewProtocol"),
+            argument(body, "NewExternalPort").parse::<u16>().unwrap(),
+        )
+    };
+
+    match action {
+        "GetExternalIPAddress" => success(&format!(
+            "<NewExternalIPAddress>{}</NewExternalIPAddress>",
+            EXTERNAL_IP
+        )),
+        "AddPortMapping" => {
+            let client = argument(body, "NewInternalClient");
+            let lease: u64 = argument(body, "NewLeaseDuration").parse().unwrap();
+            if state.permanent_only && lease != 0 {
+                return fault(725, "OnlyPermanentLeasesSupported");
+            }
+            if let Some((owner, ..)) = state.upnp.get(&key()) {
+                if *owner != client {
+                    return fault(718, "ConflictInMappingEntry");
+                }
+            }
+            let entry = (
+                client,
+                argument(body, "NewInternalPort").parse().unwrap(),
+                lease,
+                argument(body, "NewPortMappingDescription"),
+            );
+            state.upnp.insert(key(), entry);
+            success("")
+        }
+        "DeletePortMapping" => match state.upnp.remove(&key()) {
+            Some(_) => success(""),
+            None => fault(714, "NoSuchEntryInArray"),
+        },
+        _ => fault(401, "Invalid Action"),
+    }
+}
+
+async fn serve_pmp(socket: UdpSocket, state: Arc<Mutex<GatewayState>>, pcp: bool) {
+    let mut buf = [0u8; 1100];
+    let epoch = 1000u32.to_be_bytes();
+    while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+        let request = &buf[..len];
+        let answer = match (request[0], pcp) {
+            (0, _) => nat_pmp(request, &epoch, &mut state.lock().unwrap()),
+            (2, true) => pcp_answer(request, from, &epoch, &mut state.lock().unwrap()),
+            // NAT-PMP gateways refuse other versions
+            (_, _) => {
+                let mut answer = vec![0, 128 | request[1], 0, 1];
+                answer.extend_from_slice(&epoch);
+                answer
+            }
+        };
+        socket.send_to(&answer, from).await.unwrap();
+    }
+}
+
+fn nat_pmp(request: &[u8], epoch: &[u8; 4], state: &mut GatewayState) -> Vec<u8> {
+    let opcode = request[1];
+    let mut answer = vec![0, 128 + opcode, 0, 0];
+    answer.extend_from_slice(epoch);
+    if opcode == 0 {
+        answer.extend_from_slice(&EXTERNAL_IP.octets());
+        return answer;
+    }
+    let internal = u16::from_be_bytes([request[4], request[5]]);
+    let suggested = u16::from_be_bytes([request[6], request[7]]);
+    let lifetime = u32::from_be_bytes([request[8], request[9], request[10], request[11]]);
+    let protocol = if opcode == 1 { 17 } else { 6 };
+    *state.pmp_requests.entry(internal).or_default() += 1;
+
+    let external = if lifetime == 0 {
+        state.pmp.remove(&(protocol, internal));
+        0
+    } else {
+        let external = state
+            .pmp
+            .get(&(protocol, internal))
+            .map_or(suggested, |(external, ..)| *external);
+        state
+            .pmp
+            .insert((protocol, internal), (external, lifetime, None));
+        external
+    };
+    answer.extend_from_slice(&internal.to_be_bytes());
+    answer.extend_from_slice(&external.to_be_bytes());
+    answer.extend_from_slice(&lifetime.to_be_bytes());
+    answer
+}
+
+fn pcp_answer(
+    request: &[u8],
+    from: SocketAddr,
+    epoch: &[u8; 4],
+    state: &mut GatewayState,
+) -> Vec<u8> {
+    let opcode = request[1];
+    let lifetime = u32::from_be_bytes([request[4], request[5], request[6], request[7]]);
+    let client: [u8; 16] = request[8..24].try_into().unwrap();
+    let source = match from.ip() {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    };
+    let header = |result: u8, lifetime: u32| {
+        let mut answer = vec![2, 0x80 | opcode, 0, result];
+        answer.extend_from_slice(&lifetime.to_be_bytes());
+        answer.extend_from_slice(epoch);
+        answer.extend_from_slice(&[0; 12]);
+        answer
+    };
+    if client != source {
+        // ADDRESS_MISMATCH
+        return header(12, 0);
+    }
+    if opcode == 0 {
+        return header(0, 0);
+    }
+
+    let nonce: [u8; 12] = request[24..36].try_into().unwrap();
+    let protocol = request[36];
+    let internal = u16::from_be_bytes([request[40], request[41]]);
+    let suggested = u16::from_be_bytes([request[42], request[43]]);
+    *state.pmp_requests.entry(internal).or_default() += 1;
+
+    let existing = state.pmp.get(&(protocol, internal)).copied();
+    if matches!(existing, Some((_, _, Some(owner))) if owner != nonce) {
+        // NOT_AUTHORIZED
+        return header(2, 0);
+    }
+    let external = if lifetime == 0 {
+        state.pmp.remove(&(protocol, internal));
+        0
+    } else {
+        let external = existing.map_or(suggested, |(external, ..)| external);
+        state
+            .pmp
+            .insert((protocol, internal), (external, lifetime, Some(nonce)));
+        external
+    };
+    let mut answer = header(0, lifetime);
+    answer.extend_from_slice(&nonce);
+    answer.extend_from_slice(&[protocol, 0, 0, 0]);
+    answer.extend_from_slice(&internal.to_be_bytes());
+    answer.extend_from_slice(&external.to_be_bytes());
+    answer.extend_from_slice(&EXTERNAL_IP.to_ipv6_mapped().octets());
+    answer
+}
+
+#[tokio::test]
+async fn test_upnp_igd_mapping() {
+    let gateway = FakeGateway::spawn(false, false).await;
+    let upnp = UpnpManager::new(Duration::from_secs(3600)).with_ssdp_target(gateway.ssdp);
+
+    // No mapping before a gateway is found
+    assert!(upnp
+        .create_mapping(
+            4001,
+            4001,
+            PortMappingProtocol::TCP,
+            "QuDAG P2P",
+            Duration::ZERO
+        )
+        .await
+        .is_err());
+    upnp.discover_gateway().await.unwrap();
+    assert_eq!(upnp.external_ip().await.unwrap(), IpAddr::V4(EXTERNAL_IP));
+
+    let mapping = upnp
+        .create_mapping(
+            4001,
+            4001,
+            PortMappingProtocol::TCP,
+            "QuDAG <P2P>",
+            Duration::from_secs(3600),
+        )
+        .await
+        .unwrap();
+    assert_eq!(mapping.lease_duration, Duration::from_secs(3600));
+    assert_eq!(
+        gateway.state.lock().unwrap().upnp[&("TCP".to_string(), 4001)],
+        (
+            "127.0.0.1".to_string(),
+            4001,
+            3600,
+            "QuDAG &lt;P2P&gt;".to_string()
+        )
+    );
+
+    // Ports forwarded to another host are refused
+    gateway.state.lock().unwrap().upnp.insert(
+        ("UDP".to_string(), 4002),
+        ("192.168.1.20".to_string(), 4002, 0, "other".to_string()),
+    );
+    match upnp
+        .create_mapping(
+            4002,
+            4002,
+            PortMappingProtocol::UDP,
+            "QuDAG P2P",
+            Duration::ZERO,
+        )
+        .await
+    {
+        Err(NatTraversalError::PortMappingError(PortMappingError::Upnp { code, description })) => {
+            assert_eq!(code, 718);
+            assert_eq!(description, "ConflictInMappingEntry");
+        }
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+
+    // Nothing is due for renewal yet; removal deletes the forwarding
+    assert!(upnp.renew_mappings().await.is_empty());
+    upnp.remove_all_mappings().await.unwrap();
+    assert!(upnp.mappings().is_empty());
+    let state = gateway.state.lock().unwrap();
+    assert!(!state.upnp.contains_key(&("TCP".to_string(), 4001)));
+    assert!(state.upnp.contains_key(&("UDP".to_string(), 4002)));
+}
+
+#[tokio::test]
+async fn test_upnp_permanent_leases() {
+    let gateway = FakeGateway::spawn(false, true).await;
+    let upnp = UpnpManager::new(Duration::from_secs(3600)).with_ssdp_target(gateway.ssdp);
+    upnp.discover_gateway().await.unwrap();
+
+    let mapping = upnp
+        .create_mapping(
+            4003,
+            4003,
+            PortMappingProtocol::UDP,
+            "QuDAG P2P",
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+    assert_eq!(mapping.lease_duration, Duration::ZERO);
+    assert_eq!(
+        gateway.state.lock().unwrap().upnp[&("UDP".to_string(), 4003)].2,
+        0
+    );
+
+    // Permanent mappings are never renewed
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(upnp.renew_mappings().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_nat_pmp_mapping_and_renewal() {
+    let gateway = FakeGateway::spawn(false, false).await;
+    let client = NatPmpClient::new().with_gateway(gateway.pmp);
+
+    // The gateway refuses PCP, so the client falls back to NAT-PMP
+    client.discover_gateway().await.unwrap();
+    assert_eq!(client.external_ip().await.unwrap(), IpAddr::V4(EXTERNAL_IP));
+    let mapping = client
+        .create_mapping(5001, 6001, true, Duration::from_secs(2))
+        .await
+        .unwrap();
+    assert_eq!(mapping.version, PmpVersion::NatPmp);
+    assert_eq!(mapping.external_port, 6001);
+    assert_eq!(mapping.lifetime, Duration::from_secs(2));
+    assert_eq!(
+        gateway.state.lock().unwrap().pmp[&(6, 5001)],
+        (6001, 2, None)
+    );
+
+    assert!(client.renew_mappings().await.is_empty());
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let renewed = client.renew_mappings().await;
+    assert_eq!(renewed.len(), 1);
+    assert_eq!(renewed[0].external_port, 6001);
+    assert_eq!(gateway.state.lock().unwrap().pmp_requests[&5001], 2);
+
+    client.remove_mapping(5001).await.unwrap();
+    assert!(client.mappings().is_empty());
+    assert!(gateway.state.lock().unwrap().pmp.is_empty());
+}
+
+#[tokio::test]
+async fn test_pcp_mapping() {
+    let gateway = FakeGateway::spawn(true, false).await;
+    let client = NatPmpClient::new().with_gateway(gateway.pmp);
+    client.discover_gateway().await.unwrap();
+
+    let mapping = client
+        .create_mapping(5002, 5002, false, Duration::from_secs(2))
+        .await
+        .unwrap();
+    assert_eq!(mapping.version, PmpVersion::Pcp);
+    assert_eq!(mapping.external_port, 5002);
+    let nonce = gateway.state.lock().unwrap().pmp[&(17, 5002)].2;
+    assert!(nonce.is_some());
+
+    // Renewals carry the nonce the gateway knows the mapping by
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(client.renew_mappings().await.len(), 1);
+    assert_eq!(gateway.state.lock().unwrap().pmp[&(17, 5002)].2, nonce);
+
+    // Another client cannot take the mapping over
+    let intruder = NatPmpClient::new().with_gateway(gateway.pmp);
+    intruder.discover_gateway().await.unwrap();
+    match intruder
+        .create_mapping(5002, 5002, false, Duration::from_secs(2))
+        .await
+    {
+        Err(NatTraversalError::PortMappingError(PortMappingError::Pcp(2))) => {}
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+
+    client.remove_all_mappings().await.unwrap();
+    assert!(gateway.state.lock().unwrap().pmp.is_empty());
+}
+
+fn manager_config(gateway: &FakeGateway, enable_upnp: bool) -> NatTraversalConfig {
+    NatTraversalConfig {
+        enable_stun: false,
+        enable_turn: false,
+        enable_upnp,
+        enable_nat_pmp: true,
+        enable_hole_punching: false,
+        enable_relay: false,
+        enable_ipv6: false,
+        stun_servers: vec![],
+        turn_servers: vec![],
+        max_relay_connections: 5,
+        hole_punch_timeout: Duration::from_secs(5),
+        detection_interval: Duration::from_secs(60),
+        upgrade_interval: Duration::from_secs(30),
+        port_mapping_lifetime: Duration::from_secs(2),
+        ssdp_target: gateway.ssdp,
+        nat_pmp_gateway: Some(gateway.pmp),
+    }
+}
+
+#[tokio::test]
+async fn test_manager_renews_and_cleans_up() {
+    let gateway = FakeGateway::spawn(false, false).await;
+
+    // UPnP is preferred when the gateway speaks it
+    let manager = NatTraversalManager::new(
+        manager_config(&gateway, true),
+        Arc::new(ConnectionManager::new(10)),
+    );
+    manager.initialize().await.unwrap();
+    let mapping = manager
+        .create_port_mapping(7001, 7001, PortMappingProtocol::TCP)
+        .await
+        .unwrap();
+    assert_eq!(mapping.method, PortMappingMethod::Upnp);
+    assert_eq!(gateway.state.lock().unwrap().upnp.len(), 1);
+    manager.shutdown().await.unwrap();
+    assert!(gateway.state.lock().unwrap().upnp.is_empty());
+    assert!(manager.get_port_mappings().is_empty());
+
+    // NAT-PMP mappings are renewed in the background until shutdown
+    let manager = NatTraversalManager::new(
+        manager_config(&gateway, false),
+        Arc::new(ConnectionManager::new(10)),
+    );
+    manager.initialize().await.unwrap();
+    let mapping = manager
+        .create_port_mapping(7002, 7002, PortMappingProtocol::UDP)
+        .await
+        .unwrap();
+    assert_eq!(mapping.method, PortMappingMethod::NatPmp);
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert!(gateway.state.lock().unwrap().pmp_requests[&7002] >= 2);
+    assert!(manager.get_port_mappings()[0].expires_at > mapping.expires_at);
+
+    manager.shutdown().await.unwrap();
+    assert!(gateway.state.lock().unwrap().pmp.is_empty());
+}
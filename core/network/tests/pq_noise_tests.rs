@@ -0,0 +1,203 @@
+//! Tests for the `/qudag/pq-noise/2` security upgrade: the raw handshake and
+//! interop/downgrade behaviour between in-process swarms.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::poll_fn;
+use futures::prelude::*;
+use libp2p::core::transport::{ListenerId, MemoryTransport, Transport, TransportEvent};
+use libp2p::core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade};
+use libp2p::identity::Keypair;
+use libp2p::Multiaddr;
+use qudag_crypto::key_certificate::{
+    KeyCertificate, KeyRevocation, RevocationList, RevocationReason,
+};
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+use qudag_network::p2p::{NetworkConfig, P2PHandle, P2PNode};
+use qudag_network::pq_noise::{PqNoiseConfig, PqNoiseError, SecurityProtocol, PROTOCOL_NAME};
+use rand::{thread_rng, Rng};
+
+fn pq_config() -> (Keypair, Arc<MlDsaKeyPair>, PqNoiseConfig) {
+    let identity = Keypair::generate_ed25519();
+    let ml_dsa = Arc::new(MlDsaKeyPair::generate(&mut thread_rng()).unwrap());
+    let config = PqNoiseConfig::new(&identity, ml_dsa.clone());
+    (identity, ml_dsa, config)
+}
+
+/// Open a connected pair of in-memory sockets
+async fn memory_pair() -> (
+    libp2p::core::transport::memory::Channel<Vec<u8>>,
+    libp2p::core::transport::memory::Channel<Vec<u8>>,
+) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr: Multiaddr = format!("/memory/{}", port).parse().unwrap();
+    let mut listener = MemoryTransport::default();
+    listener
+        .listen_on(ListenerId::next(), addr.clone())
+        .unwrap();
+
+    let dial = MemoryTransport::default().dial(addr).unwrap();
+    let accept = async {
+        loop {
+            let event = poll_fn(|cx| std::pin::Pin::new(&mut listener).poll(cx)).await;
+            if let TransportEvent::Incoming { upgrade, .. } = event {
+                return upgrade.await.unwrap();
+            }
+        }
+    };
+    let (outbound, inbound) = futures::join!(dial, accept);
+    (outbound.unwrap(), inbound)
+}
+
+#[tokio::test]
+async fn test_handshake_authenticates_both_sides() {
+    let (initiator_id, initiator_dsa, initiator) = pq_config();
+    let (responder_id, responder_dsa, responder) = pq_config();
+    let (outbound, inbound) = memory_pair().await;
+
+    let (out, inc) = futures::join!(
+        initiator.upgrade_outbound(outbound, PROTOCOL_NAME),
+        responder.upgrade_inbound(inbound, PROTOCOL_NAME),
+    );
+    let (seen_responder, mut out) = out.unwrap();
+    let (seen_initiator, mut inc) = inc.unwrap();
+
+    assert_eq!(seen_responder, responder_id.public().to_peer_id());
+    assert_eq!(seen_initiator, initiator_id.public().to_peer_id());
+    assert_eq!(out.remote_ml_dsa_key(), responder_dsa.public_key());
+    assert_eq!(inc.remote_ml_dsa_key(), initiator_dsa.public_key());
+
+    // Larger than one frame, so it is split and reassembled
+    let payload: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+    let write = async {
+        out.write_all(&payload).await.unwrap();
+        out.flush().await.unwrap();
+        out
+    };
+    let read = async {
+        let mut received = vec![0u8; payload.len()];
+        inc.read_exact(&mut received).await.unwrap();
+        received
+    };
+    let (mut out, received) = futures::join!(write, read);
+    assert_eq!(received, payload);
+
+    inc.write_all(b"pong").await.unwrap();
+    inc.flush().await.unwrap();
+    let mut reply = [0u8; 4];
+    out.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"pong");
+}
+
+#[tokio::test]
+async fn test_handshake_rejects_revoked_key() {
+    let (_, _, initiator) = pq_config();
+    let (_, responder_dsa, responder) = pq_config();
+
+    let revocation =
+        KeyRevocation::new(&responder_dsa, RevocationReason::KeyCompromise, 1).unwrap();
+    let revocations = RevocationList::new();
+    revocations
+        .insert(KeyCertificate::Revocation(revocation))
+        .unwrap();
+    let initiator = initiator.with_revocation_list(revocations);
+
+    let (outbound, inbound) = memory_pair().await;
+    let (out, _) = futures::join!(
+        initiator.upgrade_outbound(outbound, PROTOCOL_NAME),
+        responder.upgrade_inbound(inbound, PROTOCOL_NAME),
+    );
+    assert!(matches!(out, Err(PqNoiseError::KeyRejected(_))));
+}
+
+async fn spawn_node(security: SecurityProtocol) -> (P2PHandle, Multiaddr) {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    let addr = format!("/memory/{}", port);
+    let config = NetworkConfig {
+        listen_addrs: vec![addr.clone()],
+        enable_mdns: false,
+        enable_websocket: false,
+        security,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr.parse().unwrap())
+}
+
+/// Dial `listener` from `dialer` and report whether they end up connected
+async fn connects(dialer: SecurityProtocol, listener: SecurityProtocol) -> bool {
+    let (listen_handle, addr) = spawn_node(listener).await;
+    let (dial_handle, _) = spawn_node(dialer).await;
+    let listener_id = listen_handle.local_peer_id().await;
+
+    dial_handle.dial(addr).await.unwrap();
+    for _ in 0..50 {
+        if dial_handle.connected_peers().await.contains(&listener_id) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_pq_nodes_connect_and_stay_connected() {
+    let (listen_handle, addr) = spawn_node(SecurityProtocol::PqNoise).await;
+    let (dial_handle, _) = spawn_node(SecurityProtocol::PqNoise).await;
+    let listener_id = listen_handle.local_peer_id().await;
+    let dialer_id = dial_handle.local_peer_id().await;
+
+    dial_handle.dial(addr).await.unwrap();
+    let mut connected = false;
+    for _ in 0..50 {
+        if dial_handle.connected_peers().await.contains(&listener_id)
+            && listen_handle.connected_peers().await.contains(&dialer_id)
+        {
+            connected = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(connected);
+
+    // Identify and ping streams run over the encrypted, multiplexed connection
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(dial_handle.connected_peers().await.contains(&listener_id));
+    assert!(listen_handle.connected_peers().await.contains(&dialer_id));
+}
+
+#[tokio::test]
+async fn test_fallback_interoperates_with_classical_noise() {
+    assert!(
+        connects(
+            SecurityProtocol::PqNoiseWithFallback,
+            SecurityProtocol::Noise
+        )
+        .await
+    );
+    assert!(
+        connects(
+            SecurityProtocol::Noise,
+            SecurityProtocol::PqNoiseWithFallback
+        )
+        .await
+    );
+    assert!(
+        connects(
+            SecurityProtocol::PqNoiseWithFallback,
+            SecurityProtocol::PqNoise
+        )
+        .await
+    );
+}
+
+#[tokio::test]
+async fn test_pq_only_rejects_downgrade() {
+    assert!(!connects(SecurityProtocol::Noise, SecurityProtocol::PqNoise).await);
+    assert!(!connects(SecurityProtocol::PqNoise, SecurityProtocol::Noise).await);
+}
@@ -0,0 +1,143 @@
+//! Tests for version 3 stealth addresses: ML-KEM derivation, prefix-filtered
+//! scanning, ML-DSA spend authorization and negotiation with the legacy
+//! X25519 version.
+
+use qudag_network::pq_stealth::{self, PqStealthKeys};
+use qudag_network::shadow_address::{
+    DefaultShadowAddressHandler, NetworkType, ShadowAddressError, ShadowAddressGenerator,
+    SHADOW_VERSION_STEALTH_ML_KEM, SHADOW_VERSION_STEALTH_X25519,
+};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+fn handler() -> DefaultShadowAddressHandler {
+    DefaultShadowAddressHandler::new(NetworkType::Testnet, [3u8; 32])
+}
+
+#[test]
+fn test_scanner_finds_own_payments() {
+    let alice = PqStealthKeys::generate().unwrap();
+    let bob = PqStealthKeys::generate().unwrap();
+    let alice_meta = alice.meta_address(NetworkType::Testnet);
+    let bob_meta = bob.meta_address(NetworkType::Testnet);
+    let sender = handler();
+    assert!(sender.validate_address(&alice_meta).unwrap());
+
+    let mut announcements = Vec::new();
+    for i in 0..20 {
+        let recipient = if i % 4 == 0 { &alice_meta } else { &bob_meta };
+        let address = sender.generate_stealth_address_for(recipient).unwrap();
+        assert_eq!(address.metadata.version, SHADOW_VERSION_STEALTH_ML_KEM);
+        assert!(sender.validate_address(&address).unwrap());
+        announcements.push(address);
+    }
+    // Legacy announcements are skipped without decapsulating
+    let legacy_recipient = sender.generate_address(NetworkType::Testnet).unwrap();
+    announcements.push(
+        sender
+            .generate_stealth_address_for(&legacy_recipient)
+            .unwrap(),
+    );
+
+    let mut scanner = alice.scanner();
+    let found = scanner.scan(&announcements);
+    let indices: Vec<usize> = found.iter().map(|found| found.index).collect();
+    assert_eq!(indices, vec![0, 4, 8, 12, 16]);
+    assert!(found
+        .iter()
+        .all(|found| found.address == announcements[found.index]));
+
+    // Foreign announcements fail at the prefix, before the commitment check
+    let stats = scanner.stats();
+    assert_eq!(stats.scanned, 21);
+    assert_eq!(stats.skipped, 1);
+    assert_eq!(stats.prefix_hits, 5);
+    assert_eq!(stats.matched, 5);
+
+    // One-time addresses do not reveal the meta-address keys
+    assert!(announcements[..20]
+        .iter()
+        .all(|address| address.spend_key != alice_meta.spend_key
+            && address.view_key != alice_meta.view_key));
+    assert_ne!(announcements[0].spend_key, announcements[4].spend_key);
+}
+
+#[test]
+fn test_spend_authorization() {
+    let alice = PqStealthKeys::generate().unwrap();
+    let mallory = PqStealthKeys::generate().unwrap();
+    let address = pq_stealth::generate_stealth_address(
+        NetworkType::Testnet,
+        alice.view_public.as_bytes(),
+        alice.spend.public_key(),
+    )
+    .unwrap();
+    let found = alice.scanner().scan([&address]).pop().unwrap();
+
+    let authorization = alice.authorize_spend(&found, b"spend 1").unwrap();
+    authorization.verify(&address, b"spend 1").unwrap();
+    assert!(authorization.verify(&address, b"spend 2").is_err());
+
+    let mut tampered = authorization.clone();
+    tampered.signature[10] ^= 1;
+    assert!(tampered.verify(&address, b"spend 1").is_err());
+
+    // Another spend key does not open the commitment, even with a valid
+    // signature and the opening the sender knows
+    let forged = mallory.authorize_spend(&found, b"spend 1").unwrap();
+    assert!(matches!(
+        forged.verify(&address, b"spend 1"),
+        Err(ShadowAddressError::CryptoError(_))
+    ));
+
+    // Nobody else's scanner finds the payment
+    assert!(mallory.scanner().scan([&address]).is_empty());
+}
+
+#[test]
+fn test_version_negotiation() {
+    let sender = handler();
+
+    // Recipients with 32-byte X25519 keys get legacy stealth addresses
+    let view_secret = EphemeralSecret::random_from_rng(rand::thread_rng());
+    let view_public = PublicKey::from(&view_secret);
+    let legacy = sender
+        .generate_stealth_address(NetworkType::Testnet, view_public.as_bytes(), &[0u8; 32])
+        .unwrap();
+    assert_eq!(legacy.metadata.version, SHADOW_VERSION_STEALTH_X25519);
+    assert!(legacy.payment_id.is_some());
+
+    // ML-KEM view keys select version 3 through the trait as well
+    let keys = PqStealthKeys::generate().unwrap();
+    let meta = keys.meta_address(NetworkType::Devnet);
+    assert_eq!(
+        sender.negotiate_stealth_version(&meta).unwrap(),
+        SHADOW_VERSION_STEALTH_ML_KEM
+    );
+    let address = sender
+        .generate_stealth_address(NetworkType::Devnet, &meta.view_key, &meta.spend_key)
+        .unwrap();
+    assert_eq!(address.metadata.version, SHADOW_VERSION_STEALTH_ML_KEM);
+    assert_eq!(keys.scanner().scan([&address]).len(), 1);
+
+    // A handler that refuses the legacy version
+    let strict = handler().with_min_stealth_version(SHADOW_VERSION_STEALTH_ML_KEM);
+    let legacy_recipient = strict.generate_address(NetworkType::Testnet).unwrap();
+    assert!(matches!(
+        strict.negotiate_stealth_version(&legacy_recipient),
+        Err(ShadowAddressError::UnsupportedVersion(
+            SHADOW_VERSION_STEALTH_X25519
+        ))
+    ));
+    assert!(strict
+        .generate_stealth_address(NetworkType::Testnet, view_public.as_bytes(), &[0u8; 32])
+        .is_err());
+    assert!(strict.generate_stealth_address_for(&meta).is_ok());
+
+    // Unknown versions are refused
+    let mut future = meta.clone();
+    future.metadata.version = 9;
+    assert!(matches!(
+        sender.negotiate_stealth_version(&future),
+        Err(ShadowAddressError::UnsupportedVersion(9))
+    ));
+}
@@ -0,0 +1,231 @@
+//! Tests for reachability detection: AutoNAT probes among in-process peers,
+//! STUN results, and how reachability drives relays and advertised
+//! addresses.
+//!
+//! NAT is simulated with loopback addresses: a NATed node listens on
+//! 127.0.0.2, while its dials leave from 127.0.0.1, so the address peers
+//! observe and dial back has nothing listening on it.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+use libp2p::autonat::NatStatus;
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use qudag_network::circuit_relay::{is_relayed, RelayConfig};
+use qudag_network::nat_traversal::{NatInfo, NatType};
+use qudag_network::p2p::{NetworkConfig, P2PEvent, P2PHandle, P2PNode};
+use qudag_network::reachability::{AutoNatConfig, Reachability, ReachabilityState};
+
+/// AutoNAT probing in-process peers quickly
+fn autonat() -> AutoNatConfig {
+    AutoNatConfig {
+        boot_delay: Duration::from_millis(500),
+        retry_interval: Duration::from_secs(1),
+        timeout: Duration::from_secs(5),
+        confidence_max: 1,
+        only_global_ips: false,
+        ..Default::default()
+    }
+}
+
+async fn spawn_node(listen_ip: &str, autonat: AutoNatConfig, relay: RelayConfig) -> P2PHandle {
+    let config = NetworkConfig {
+        listen_addrs: vec![format!("/ip4/{}/tcp/0", listen_ip)],
+        enable_mdns: false,
+        enable_websocket: false,
+        autonat,
+        relay,
+        ..Default::default()
+    };
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    handle
+}
+
+async fn address_of(handle: &P2PHandle) -> Multiaddr {
+    let peer_id = handle.local_peer_id().await;
+    for _ in 0..50 {
+        if let Some(addr) = handle.listeners().await.into_iter().next() {
+            return addr.with(Protocol::P2p(peer_id));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("node is not listening");
+}
+
+async fn wait_for(handle: &P2PHandle, mut matches: impl FnMut(&P2PEvent) -> bool) -> P2PEvent {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            match handle.next_event().await {
+                Some(event) if matches(&event) => break event,
+                Some(_) => continue,
+                None => panic!("node stopped"),
+            }
+        }
+    })
+    .await
+    .expect("event not seen")
+}
+
+fn nat_info(nat_type: NatType, public_ip: Option<IpAddr>) -> NatInfo {
+    NatInfo {
+        nat_type,
+        public_ip,
+        public_port: None,
+        local_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        local_port: 0,
+        hairpinning: false,
+        detected_at: Instant::now(),
+        confidence: 1.0,
+    }
+}
+
+#[test]
+fn test_autonat_takes_precedence_over_stun() {
+    let mut state = ReachabilityState::default();
+    assert_eq!(state.reachability(), Reachability::Unknown);
+
+    // STUN decides while AutoNAT has no verdict
+    let public_ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    assert_eq!(
+        state.on_stun(&nat_info(NatType::None, Some(public_ip))),
+        Some(Reachability::Public)
+    );
+    assert_eq!(state.stun_public_ip(), Some(public_ip));
+
+    // AutoNAT overrides it
+    assert_eq!(
+        state.on_autonat(&NatStatus::Private),
+        Some(Reachability::Private)
+    );
+    assert_eq!(
+        state.on_stun(&nat_info(NatType::None, Some(public_ip))),
+        None
+    );
+    assert_eq!(state.reachability(), Reachability::Private);
+
+    // STUN decides again once AutoNAT loses its verdict
+    assert_eq!(
+        state.on_stun(&nat_info(NatType::Symmetric, Some(public_ip))),
+        None
+    );
+    assert_eq!(state.stun_public_ip(), None);
+    assert_eq!(state.on_autonat(&NatStatus::Unknown), None);
+    assert_eq!(state.reachability(), Reachability::Private);
+    assert_eq!(
+        state.on_stun(&nat_info(NatType::Unknown, None)),
+        Some(Reachability::Unknown)
+    );
+}
+
+#[tokio::test]
+async fn test_stun_result_sets_reachability() {
+    let node = spawn_node(
+        "127.0.0.1",
+        AutoNatConfig {
+            enabled: false,
+            ..Default::default()
+        },
+        RelayConfig::default(),
+    )
+    .await;
+    let listen = address_of(&node).await;
+    assert_eq!(node.reachability().await, Reachability::Unknown);
+
+    // Without NAT, the listen addresses on the public IP are advertised
+    node.report_nat_info(nat_info(
+        NatType::None,
+        Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+    ));
+    wait_for(&node, |e| {
+        matches!(e, P2PEvent::ReachabilityChanged(Reachability::Public))
+    })
+    .await;
+    let mut direct = listen.clone();
+    direct.pop();
+    assert!(node.external_addresses().await.contains(&direct));
+
+    // Behind a NAT, they are not
+    node.report_nat_info(nat_info(NatType::PortRestrictedCone, None));
+    wait_for(&node, |e| {
+        matches!(e, P2PEvent::ReachabilityChanged(Reachability::Private))
+    })
+    .await;
+    assert_eq!(node.reachability().await, Reachability::Private);
+    assert!(node.external_addresses().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_autonat_finds_public_node() {
+    let server = spawn_node("127.0.0.1", autonat(), RelayConfig::default()).await;
+    let server_addr = address_of(&server).await;
+
+    let node = spawn_node("127.0.0.1", autonat(), RelayConfig::default()).await;
+    let listen = address_of(&node).await;
+    node.dial(server_addr).await.unwrap();
+
+    // The server dials the node back at its listen address, which is
+    // confirmed as external
+    wait_for(&node, |e| {
+        matches!(e, P2PEvent::ReachabilityChanged(Reachability::Public))
+    })
+    .await;
+    assert_eq!(node.reachability().await, Reachability::Public);
+    assert!(node.external_addresses().await.contains(&listen));
+    assert!(node.relay_reservations().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_private_node_reserves_on_relay() {
+    let relay = spawn_node(
+        "127.0.0.1",
+        autonat(),
+        RelayConfig {
+            server: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    let relay_id = relay.local_peer_id().await;
+    let relay_addr = address_of(&relay).await;
+
+    // The relay is known, but not reserved on while reachability is unknown
+    let natted = spawn_node(
+        "127.0.0.2",
+        autonat(),
+        RelayConfig {
+            relays: vec![relay_addr.clone()],
+            ..Default::default()
+        },
+    )
+    .await;
+    let natted_id = natted.local_peer_id().await;
+    address_of(&natted).await;
+    natted.dial(relay_addr.clone()).await.unwrap();
+    assert!(natted.relay_reservations().await.is_empty());
+
+    // The relay's dial back fails, so the node finds itself private and
+    // reserves on the relay
+    wait_for(&natted, |e| {
+        matches!(e, P2PEvent::ReachabilityChanged(Reachability::Private))
+    })
+    .await;
+    wait_for(
+        &natted,
+        |e| matches!(e, P2PEvent::RelayReservationAccepted { relay } if *relay == relay_id),
+    )
+    .await;
+
+    // Only the relayed address is advertised
+    let advertised = natted.external_addresses().await;
+    assert!(advertised.contains(
+        &relay_addr
+            .with(Protocol::P2pCircuit)
+            .with(Protocol::P2p(natted_id))
+    ));
+    assert!(advertised.iter().all(is_relayed));
+}
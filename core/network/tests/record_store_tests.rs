@@ -0,0 +1,281 @@
+//! Tests for the disk-backed Kademlia record store: persistence, quotas,
+//! expiry, republish bookkeeping and dark domain record validation.
+
+use std::time::{Duration, Instant};
+
+use libp2p::kad::{
+    store::Error as StoreError, store::RecordStore, ProviderRecord, Record, RecordKey,
+};
+use libp2p::{Multiaddr, PeerId as LibP2PPeerId};
+use qudag_crypto::ml_dsa::MlDsaKeyPair;
+use qudag_network::dark_resolver::{
+    DarkDomainRecord, DarkRecordValidator, DarkResolver, MAX_DARK_RECORD_SIZE,
+};
+use qudag_network::p2p::{NetworkConfig, P2PNode};
+use qudag_network::record_store::{PersistentRecordStore, RecordStoreConfig};
+use qudag_network::types::{NetworkAddress, PeerId};
+use rand::thread_rng;
+
+fn record(key: &[u8], value: &[u8]) -> Record {
+    Record::new(key.to_vec(), value.to_vec())
+}
+
+#[test]
+fn test_records_and_providers_survive_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let local = LibP2PPeerId::random();
+    let remote = LibP2PPeerId::random();
+    let key = RecordKey::new(&b"provided".to_vec());
+    let addr: Multiaddr = "/ip4/10.0.0.1/tcp/8000".parse().unwrap();
+
+    {
+        let mut store =
+            PersistentRecordStore::open(dir.path(), local, RecordStoreConfig::default()).unwrap();
+        store.put(record(b"alpha", b"one")).unwrap();
+        store.put(record(b"beta", b"two")).unwrap();
+        store.remove(&RecordKey::new(&b"beta".to_vec()));
+        store
+            .add_provider(ProviderRecord::new(key.clone(), remote, vec![addr.clone()]))
+            .unwrap();
+    }
+
+    let store =
+        PersistentRecordStore::open(dir.path(), local, RecordStoreConfig::default()).unwrap();
+    assert_eq!(store.len(), 1);
+    assert_eq!(
+        store
+            .get(&RecordKey::new(&b"alpha".to_vec()))
+            .unwrap()
+            .value,
+        b"one"
+    );
+    assert!(store.get(&RecordKey::new(&b"beta".to_vec())).is_none());
+    assert_eq!(store.total_bytes(), 3);
+
+    let providers = store.providers(&key);
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].provider, remote);
+    assert_eq!(providers[0].addresses, vec![addr]);
+}
+
+#[test]
+fn test_quotas() {
+    let config = RecordStoreConfig {
+        max_records: 2,
+        max_value_bytes: 8,
+        max_total_bytes: 12,
+        max_ttl: Duration::from_secs(60),
+        ..Default::default()
+    };
+    let mut store = PersistentRecordStore::in_memory(LibP2PPeerId::random(), config);
+
+    assert!(matches!(
+        store.put(record(b"big", &[0u8; 9])),
+        Err(StoreError::ValueTooLarge)
+    ));
+    store.put(record(b"a", &[0u8; 8])).unwrap();
+    assert!(matches!(
+        store.put(record(b"b", &[0u8; 5])),
+        Err(StoreError::MaxRecords)
+    ));
+    store.put(record(b"b", &[0u8; 4])).unwrap();
+    assert!(matches!(
+        store.put(record(b"c", &[0u8; 1])),
+        Err(StoreError::MaxRecords)
+    ));
+
+    // Replacing a record only counts the size difference
+    store.put(record(b"a", &[0u8; 2])).unwrap();
+    assert_eq!(store.total_bytes(), 6);
+
+    // Lifetimes are capped at the maximum TTL
+    let mut long_lived = record(b"a", b"x");
+    long_lived.expires = Some(Instant::now() + Duration::from_secs(3600));
+    store.put(long_lived).unwrap();
+    let expires = store.get(&RecordKey::new(&b"a".to_vec())).unwrap().expires;
+    assert!(expires.unwrap() <= Instant::now() + Duration::from_secs(60));
+}
+
+#[test]
+fn test_expired_entries_are_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let local = LibP2PPeerId::random();
+    let key = RecordKey::new(&b"provided".to_vec());
+    let config = RecordStoreConfig {
+        max_ttl: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    let mut store = PersistentRecordStore::open(dir.path(), local, config.clone()).unwrap();
+    store.put(record(b"short", b"lived")).unwrap();
+    store
+        .add_provider(ProviderRecord::new(
+            key.clone(),
+            LibP2PPeerId::random(),
+            vec![],
+        ))
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(store.get(&RecordKey::new(&b"short".to_vec())).is_none());
+    assert!(store.providers(&key).is_empty());
+    assert_eq!(store.remove_expired(), 2);
+    assert!(store.is_empty());
+    drop(store);
+
+    let store = PersistentRecordStore::open(dir.path(), local, config).unwrap();
+    assert!(store.is_empty());
+    assert!(store.providers(&key).is_empty());
+}
+
+#[test]
+fn test_republish_bookkeeping() {
+    let dir = tempfile::tempdir().unwrap();
+    let local = LibP2PPeerId::random();
+    let config = RecordStoreConfig {
+        republish_interval: Duration::from_secs(1),
+        ..Default::default()
+    };
+
+    let mut store = PersistentRecordStore::open(dir.path(), local, config.clone()).unwrap();
+    let mut own = record(b"own", b"value");
+    own.publisher = Some(local);
+    store.put(own).unwrap();
+    let mut foreign = record(b"foreign", b"value");
+    foreign.publisher = Some(LibP2PPeerId::random());
+    store.put(foreign).unwrap();
+    assert!(store.due_for_republish().is_empty());
+    drop(store);
+
+    // Publication times are kept across restarts
+    std::thread::sleep(Duration::from_millis(1100));
+    let mut store = PersistentRecordStore::open(dir.path(), local, config).unwrap();
+    let due = store.due_for_republish();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].key, RecordKey::new(&b"own".to_vec()));
+
+    // Publishing again resets the clock
+    store.put(due[0].clone()).unwrap();
+    assert!(store.due_for_republish().is_empty());
+}
+
+fn dark_record(keypair: &MlDsaKeyPair, domain: &str) -> DarkDomainRecord {
+    let mut record = DarkDomainRecord::new(
+        keypair,
+        vec![0u8; 1184],
+        vec![NetworkAddress::new([10, 0, 0, 1], 8000)],
+        None,
+        3600,
+        PeerId::random(),
+    )
+    .unwrap();
+    record.bind_to_domain(keypair, domain, 0).unwrap();
+    record
+}
+
+fn dark_kad_record(domain: &str, record: &DarkDomainRecord) -> Record {
+    Record::new(
+        DarkResolver::dht_key(domain),
+        bincode::serialize(record).unwrap(),
+    )
+}
+
+#[test]
+fn test_dark_records_are_validated() {
+    let keypair = MlDsaKeyPair::generate(&mut thread_rng()).unwrap();
+    let mut store =
+        PersistentRecordStore::in_memory(LibP2PPeerId::random(), RecordStoreConfig::default())
+            .with_validator(DarkRecordValidator);
+    let signed = dark_record(&keypair, "valid.dark");
+
+    store.put(dark_kad_record("valid.dark", &signed)).unwrap();
+
+    // Unsigned
+    let mut unsigned = signed.clone();
+    unsigned.signature.clear();
+    assert!(store.put(dark_kad_record("valid.dark", &unsigned)).is_err());
+
+    // Stored under another domain's key
+    assert!(store.put(dark_kad_record("other.dark", &signed)).is_err());
+
+    // Oversized, even when correctly signed
+    let mut oversized = signed.clone();
+    oversized
+        .metadata
+        .insert("padding".to_string(), "x".repeat(MAX_DARK_RECORD_SIZE));
+    oversized.bind_to_domain(&keypair, "big.dark", 0).unwrap();
+    assert!(store.put(dark_kad_record("big.dark", &oversized)).is_err());
+
+    // Garbage under the dark prefix
+    assert!(store
+        .put(Record::new(
+            DarkResolver::dht_key("junk.dark"),
+            b"junk".to_vec()
+        ))
+        .is_err());
+
+    // Other records are not affected
+    store.put(record(b"plain", b"value")).unwrap();
+    assert_eq!(store.len(), 2);
+}
+
+#[test]
+fn test_validator_drops_invalid_records_on_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let local = LibP2PPeerId::random();
+    {
+        let mut store =
+            PersistentRecordStore::open(dir.path(), local, RecordStoreConfig::default()).unwrap();
+        store
+            .put(Record::new(
+                DarkResolver::dht_key("junk.dark"),
+                b"junk".to_vec(),
+            ))
+            .unwrap();
+        store.put(record(b"plain", b"value")).unwrap();
+    }
+
+    let store = PersistentRecordStore::open(dir.path(), local, RecordStoreConfig::default())
+        .unwrap()
+        .with_validator(DarkRecordValidator);
+    assert_eq!(store.len(), 1);
+    drop(store);
+
+    let store =
+        PersistentRecordStore::open(dir.path(), local, RecordStoreConfig::default()).unwrap();
+    assert_eq!(store.len(), 1);
+}
+
+#[tokio::test]
+async fn test_node_serves_records_after_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = || NetworkConfig {
+        listen_addrs: vec![],
+        enable_mdns: false,
+        enable_websocket: false,
+        data_dir: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let (mut node, handle) = P2PNode::new(config()).await.unwrap();
+    let task = tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    // No peers to replicate to, but the record is stored locally
+    let _ = handle
+        .put_record(
+            b"persisted".to_vec(),
+            b"value".to_vec(),
+            Duration::from_secs(600),
+        )
+        .await;
+    drop(handle);
+    task.await.unwrap();
+
+    let (mut node, handle) = P2PNode::new(config()).await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    let values = handle.get_records(b"persisted".to_vec()).await.unwrap();
+    assert_eq!(values, vec![b"value".to_vec()]);
+}
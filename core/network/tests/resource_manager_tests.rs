@@ -0,0 +1,281 @@
+//! Tests for the resource manager: token buckets, stream and memory caps,
+//! penalties for violations, and a node staying responsive under a flood.
+
+use std::time::Duration;
+
+use libp2p::gossipsub::{ConfigBuilder as GossipsubConfigBuilder, ValidationMode};
+use libp2p::{Multiaddr, PeerId};
+use qudag_network::p2p::{
+    NetworkConfig, P2PEvent, P2PHandle, P2PNode, QuDagRequest, QuDagResponse,
+};
+use qudag_network::peer_scoring::PeerScoring;
+use qudag_network::resource_manager::{
+    RateLimit, ResourceConfig, ResourceError, ResourceManager, ResourceProtocol,
+};
+use rand::{thread_rng, Rng};
+
+#[test]
+fn test_token_buckets() {
+    let mut config = ResourceConfig::default();
+    config.gossip.peer_rate = RateLimit::new(10.0, 3);
+    config.gossip.global_rate = RateLimit::new(100.0, 5);
+    let resources = ResourceManager::new(config);
+    let peer = PeerId::random();
+    let other = PeerId::random();
+
+    for _ in 0..3 {
+        resources.admit(peer, ResourceProtocol::Gossip, 10).unwrap();
+    }
+    assert!(matches!(
+        resources.admit(peer, ResourceProtocol::Gossip, 10),
+        Err(ResourceError::RateLimited { peer: p, .. }) if p == peer
+    ));
+    // Buckets are per protocol
+    resources.admit(peer, ResourceProtocol::Dht, 10).unwrap();
+
+    // The global bucket is shared by all peers
+    resources
+        .admit(other, ResourceProtocol::Gossip, 10)
+        .unwrap();
+    resources
+        .admit(other, ResourceProtocol::Gossip, 10)
+        .unwrap();
+    assert!(matches!(
+        resources.admit(other, ResourceProtocol::Gossip, 10),
+        Err(ResourceError::GlobalRateLimited(ResourceProtocol::Gossip))
+    ));
+
+    // Oversized messages are refused whatever the rate
+    let size = resources.config().gossip.max_message_size + 1;
+    assert!(matches!(
+        resources.admit(PeerId::random(), ResourceProtocol::Gossip, size),
+        Err(ResourceError::TooLarge { .. })
+    ));
+
+    // Buckets refill over time
+    std::thread::sleep(Duration::from_millis(250));
+    resources.admit(peer, ResourceProtocol::Gossip, 10).unwrap();
+    assert_eq!(resources.stats().throttled, 3);
+}
+
+#[test]
+fn test_stream_and_memory_caps() {
+    let mut config = ResourceConfig::default();
+    config.request.max_streams_per_peer = 2;
+    config.request.max_streams = 3;
+    config.memory_per_peer = 1000;
+    config.memory_total = 1500;
+    let resources = ResourceManager::new(config);
+    let peer = PeerId::random();
+    let other = PeerId::random();
+
+    let first = resources
+        .open_stream(peer, ResourceProtocol::Request, 100)
+        .unwrap();
+    let second = resources
+        .open_stream(peer, ResourceProtocol::Request, 100)
+        .unwrap();
+    assert!(matches!(
+        resources.open_stream(peer, ResourceProtocol::Request, 100),
+        Err(ResourceError::TooManyStreams { peer: Some(p), .. }) if p == peer
+    ));
+    let third = resources
+        .open_stream(other, ResourceProtocol::Request, 900)
+        .unwrap();
+    assert!(matches!(
+        resources.open_stream(other, ResourceProtocol::Request, 100),
+        Err(ResourceError::TooManyStreams { peer: None, .. })
+    ));
+    let stats = resources.stats();
+    assert_eq!(stats.streams, 3);
+    assert_eq!(stats.memory, 1100);
+
+    // Permits release their stream and memory when dropped
+    drop(first);
+    drop(second);
+    assert!(matches!(
+        resources.open_stream(other, ResourceProtocol::Request, 200),
+        Err(ResourceError::MemoryExhausted { peer: Some(p) }) if p == other
+    ));
+    assert!(matches!(
+        resources.open_stream(peer, ResourceProtocol::Request, 700),
+        Err(ResourceError::MemoryExhausted { peer: None })
+    ));
+    let _fourth = resources
+        .open_stream(peer, ResourceProtocol::Request, 500)
+        .unwrap();
+    drop(third);
+    let stats = resources.stats();
+    assert_eq!(stats.streams, 1);
+    assert_eq!(stats.memory, 500);
+}
+
+#[test]
+fn test_violations_feed_peer_scoring() {
+    let scoring = PeerScoring::default();
+    let mut config = ResourceConfig::default();
+    config.identity.peer_rate = RateLimit::new(0.001, 1);
+    let resources = ResourceManager::new(config).with_scoring(scoring.clone());
+    let peer = PeerId::random();
+
+    resources
+        .admit(peer, ResourceProtocol::Identity, 0)
+        .unwrap();
+    for _ in 0..10 {
+        assert!(resources
+            .admit(peer, ResourceProtocol::Identity, 0)
+            .is_err());
+    }
+    assert_eq!(scoring.score(&peer).local, -50.0);
+    assert!(!scoring.is_banned(&peer));
+    assert!(resources
+        .admit(peer, ResourceProtocol::Identity, 0)
+        .is_err());
+    assert!(scoring.is_banned(&peer));
+}
+
+fn node_config(resources: ResourceConfig) -> NetworkConfig {
+    let port: u64 = thread_rng().gen_range(1..u64::MAX);
+    NetworkConfig {
+        listen_addrs: vec![format!("/memory/{}", port)],
+        enable_mdns: false,
+        enable_websocket: false,
+        obfuscation_key: [9u8; 32],
+        gossipsub_config: Some(
+            GossipsubConfigBuilder::default()
+                .heartbeat_interval(Duration::from_millis(100))
+                .validation_mode(ValidationMode::Strict)
+                .build()
+                .unwrap(),
+        ),
+        resources,
+        ..Default::default()
+    }
+}
+
+async fn spawn(config: NetworkConfig) -> (P2PHandle, Multiaddr) {
+    let addr = config.listen_addrs[0].parse().unwrap();
+    let (mut node, handle) = P2PNode::new(config).await.unwrap();
+    node.start().await.unwrap();
+    tokio::spawn(async move {
+        let _ = node.run().await;
+    });
+    (handle, addr)
+}
+
+async fn spawn_node(resources: ResourceConfig) -> (P2PHandle, Multiaddr) {
+    spawn(node_config(resources)).await
+}
+
+async fn wait_for_peers(handle: &P2PHandle, count: usize) -> bool {
+    for _ in 0..50 {
+        if handle.connected_peers().await.len() == count {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+fn request(id: usize) -> QuDagRequest {
+    QuDagRequest {
+        request_id: id.to_string(),
+        payload: vec![0u8; 128],
+    }
+}
+
+#[tokio::test]
+async fn test_node_stays_responsive_under_flood() {
+    let mut limits = ResourceConfig::default();
+    limits.request.peer_rate = RateLimit::new(5.0, 10);
+    let (server, server_addr) = spawn_node(limits).await;
+    let (flooder, _) = spawn_node(ResourceConfig::default()).await;
+    let (client, _) = spawn_node(ResourceConfig::default()).await;
+    let server_id = server.local_peer_id().await;
+    let flooder_id = flooder.local_peer_id().await;
+    let resources = server.resources().clone();
+    let scoring = server.peer_scoring().clone();
+
+    // Echo every request
+    tokio::spawn(async move {
+        while let Some(event) = server.next_event().await {
+            if let P2PEvent::RequestReceived {
+                request, channel, ..
+            } = event
+            {
+                let _ = channel.send(QuDagResponse {
+                    request_id: request.request_id,
+                    payload: request.payload,
+                });
+            }
+        }
+    });
+
+    flooder.dial(server_addr.clone()).await.unwrap();
+    client.dial(server_addr).await.unwrap();
+    assert!(wait_for_peers(&client, 1).await);
+    assert!(wait_for_peers(&flooder, 1).await);
+
+    let flood = tokio::spawn(async move {
+        let requests = (0..500).map(|id| flooder.send_request(server_id, request(id)));
+        futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .filter(Result::is_ok)
+            .count()
+    });
+
+    // Another peer is still answered promptly while the flood lasts
+    for id in 0..5 {
+        let response = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.send_request(server_id, request(id)),
+        )
+        .await
+        .expect("server unresponsive under flood")
+        .unwrap();
+        assert_eq!(response.request_id, id.to_string());
+    }
+
+    let answered = tokio::time::timeout(Duration::from_secs(60), flood)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(answered < 500, "{} flood requests answered", answered);
+    assert!(resources.stats().throttled > 0);
+    assert!(scoring.score(&flooder_id).local < 0.0);
+}
+
+#[tokio::test]
+async fn test_slow_application_drops_events() {
+    // The application never reads the server's events
+    let mut config = node_config(ResourceConfig::default());
+    config.event_buffer = 4;
+    let (server, server_addr) = spawn(config).await;
+    let (client, _) = spawn_node(ResourceConfig::default()).await;
+    let server_id = server.local_peer_id().await;
+
+    client.dial(server_addr).await.unwrap();
+    assert!(wait_for_peers(&client, 1).await);
+
+    // Requests that don't fit in the event buffer fail instead of stalling
+    let requests = (0..10).map(|id| {
+        tokio::time::timeout(
+            Duration::from_secs(3),
+            client.send_request(server_id, request(id)),
+        )
+    });
+    let failed = futures::future::join_all(requests)
+        .await
+        .into_iter()
+        .filter(|result| matches!(result, Ok(Err(_))))
+        .count();
+    assert!(failed > 0);
+    assert!(server.dropped_events() >= failed as u64);
+
+    // The swarm keeps running
+    let peers = tokio::time::timeout(Duration::from_secs(1), server.connected_peers())
+        .await
+        .expect("swarm stalled by a full event channel");
+    assert_eq!(peers.len(), 1);
+}
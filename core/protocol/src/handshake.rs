@@ -8,6 +8,7 @@ use tracing::{debug, error, info};
 use uuid::Uuid;
 
 use qudag_crypto::{
+    key_certificate::{KeyCertificateError, RevocationList},
     Ciphertext as KemCiphertext, KeyPair as KemKeyPair, MlDsaKeyPair, MlDsaPublicKey, MlKem768,
     PublicKey as KemPublicKey, SecretKey, SharedSecret,
 };
@@ -61,6 +62,10 @@ pub enum HandshakeError {
     /// Replay attack detected
     #[error("Replay attack detected: timestamp {timestamp} is too old")]
     ReplayAttack { timestamp: u64 },
+
+    /// Peer signing key has been rotated or revoked
+    #[error("Peer signing key rejected: {0}")]
+    KeyRejected(#[from] KeyCertificateError),
 }
 
 /// Handshake configuration
@@ -218,6 +223,8 @@ pub struct HandshakeCoordinator {
     identity_keys: HandshakeKeys,
     /// Protocol state machine
     state_machine: ProtocolStateMachine,
+    /// Rotated and revoked peer signing keys
+    revocations: RevocationList,
 }
 
 impl HandshakeCoordinator {
@@ -232,9 +239,16 @@ impl HandshakeCoordinator {
             sessions: HashMap::new(),
             identity_keys,
             state_machine,
+            revocations: RevocationList::new(),
         }
     }
 
+    /// Reject peers whose signing key appears in a shared revocation list
+    pub fn with_revocation_list(mut self, revocations: RevocationList) -> Self {
+        self.revocations = revocations;
+        self
+    }
+
     /// Generate new handshake keys
     pub fn generate_keys() -> Result<HandshakeKeys, HandshakeError> {
         // Generate ML-DSA keypair
@@ -397,6 +411,9 @@ impl HandshakeCoordinator {
                 return Err(HandshakeError::InvalidCredentials);
             }
 
+            // Reject rotated or revoked identities
+            self.revocations.check(&signature_public_key)?;
+
             // Generate ephemeral keys for this session
             let session_keys = Self::generate_keys()?;
             let session_id = Uuid::new_v4();
@@ -534,6 +551,9 @@ impl HandshakeCoordinator {
                 return Err(HandshakeError::InvalidCredentials);
             }
 
+            // Reject rotated or revoked identities
+            self.revocations.check(&signature_public_key)?;
+
             // Verify protocol version
             if !self.config.supported_versions.contains(&protocol_version) {
                 return Err(HandshakeError::VersionMismatch {
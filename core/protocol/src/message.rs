@@ -308,6 +308,10 @@ impl Message {
 
     /// Sign message with ML-DSA
     pub fn sign(&mut self, keypair: &MlDsaKeyPair) -> Result<(), MessageError> {
+        // Set sender key hash first so it is covered by the signature
+        let public_key_bytes = keypair.public_key();
+        self.sender_key_hash = Some(blake3::hash(public_key_bytes).as_bytes().to_vec());
+
         let signable_data = self.get_signable_data()?;
 
         // Sign using the keypair directly
@@ -317,10 +321,6 @@ impl Message {
 
         self.signature = Some(signature);
 
-        // Set sender key hash for verification
-        let public_key_bytes = keypair.public_key();
-        self.sender_key_hash = Some(blake3::hash(public_key_bytes).as_bytes().to_vec());
-
         Ok(())
     }
 
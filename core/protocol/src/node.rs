@@ -9,7 +9,7 @@ use crate::{
 };
 use qudag_crypto::ml_kem::MlKem768;
 use qudag_dag::Consensus;
-use qudag_network::Transport;
+use qudag_network::P2PHandle;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -72,8 +72,8 @@ pub struct Node {
     events: NodeEvents,
     /// Cryptographic keys
     keys: Option<KeyPair>,
-    /// Network transport
-    transport: Option<Arc<dyn Transport + Send + Sync>>,
+    /// Handle of the P2P node carrying the network transport
+    transport: Option<P2PHandle>,
     /// Consensus engine
     #[allow(dead_code)]
     consensus: Option<Arc<dyn Consensus + Send + Sync>>,
@@ -1,3 +1,7 @@
+use async_trait::async_trait;
+use libp2p::PeerId;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
@@ -6,13 +10,16 @@ use tokio::sync::{mpsc, Mutex, RwLock};
 use tracing::{debug, error, info, warn};
 
 // Import network components
+use qudag_crypto::key_certificate::{KeyCertificate, RevocationList};
 use qudag_network::{
+    dark_resolver::DEFAULT_REPUBLISH_INTERVAL,
     p2p::{NetworkConfig as P2PNetworkConfig, P2PEvent, P2PNode, QuDagResponse},
-    DarkResolver, P2PHandle,
+    DarkResolver, GossipMessage, GossipTopic, GossipValidator, KeyCertificateGossip,
+    MetricsExporter, NodeIdentity, P2PHandle, PeerEvent, Verdict, CERTIFICATE_REPUBLISH_INTERVAL,
 };
 
 // Import DAG components
-use qudag_dag::{Dag, DagMessage, VertexId};
+use qudag_dag::{ConsensusError, Dag, DagMessage, DagModuleError, VertexId};
 
 // Minimal RPC types for NodeRunner integration
 #[derive(Debug, Clone)]
@@ -75,6 +82,7 @@ impl RpcServer {
 }
 
 // Import protocol types
+use crate::persistence::{FileStateStore, MemoryStateStore, StateStore};
 use crate::types::{ProtocolError, ProtocolEvent};
 
 /// Errors that can occur during node operations
@@ -92,6 +100,9 @@ pub enum NodeRunnerError {
     #[error("Protocol error: {0}")]
     ProtocolError(#[from] ProtocolError),
 
+    #[error("Persistence error: {0}")]
+    PersistenceError(String),
+
     #[error("Node already started")]
     AlreadyStarted,
 
@@ -119,6 +130,14 @@ pub struct NodeRunnerConfig {
 
     /// Node shutdown timeout
     pub shutdown_timeout: Duration,
+
+    /// Node identity key file, loaded or created on start
+    ///
+    /// Ignored when `p2p_config.identity` is already set.
+    pub identity_path: Option<PathBuf>,
+
+    /// Address to serve OpenMetrics on at `/metrics`; disabled when unset
+    pub metrics_addr: Option<SocketAddr>,
 }
 
 impl Default for NodeRunnerConfig {
@@ -129,6 +148,8 @@ impl Default for NodeRunnerConfig {
             max_dag_concurrent: 100,
             enable_dark_resolver: true,
             shutdown_timeout: Duration::from_secs(30),
+            identity_path: None,
+            metrics_addr: None,
         }
     }
 }
@@ -160,6 +181,27 @@ pub struct NodeRunner {
     /// Dark resolver for .dark addresses
     dark_resolver: Option<Arc<RwLock<DarkResolver>>>,
 
+    /// Task republishing .dark records before they expire
+    dark_republish_handle: Option<tokio::task::JoinHandle<()>>,
+
+    /// Revocation list shared by the dark resolver and certificate gossip
+    revocations: RevocationList,
+
+    /// Store persisting key certificates learned from peers
+    state_store: Option<Arc<dyn StateStore + Send + Sync>>,
+
+    /// Key certificate gossip
+    key_certificates: Option<KeyCertificateGossip>,
+
+    /// Task republishing known key certificates
+    certificate_republish_handle: Option<tokio::task::JoinHandle<()>>,
+
+    /// Address the metrics endpoint is bound to
+    metrics_addr: Option<SocketAddr>,
+
+    /// Task serving the metrics endpoint
+    metrics_handle: Option<tokio::task::JoinHandle<()>>,
+
     /// Event channel for protocol events
     #[allow(dead_code)]
     event_tx: mpsc::UnboundedSender<ProtocolEvent>,
@@ -188,6 +230,13 @@ impl NodeRunner {
             rpc_server: None,
             rpc_command_rx: None,
             dark_resolver: None,
+            dark_republish_handle: None,
+            revocations: RevocationList::new(),
+            state_store: None,
+            key_certificates: None,
+            certificate_republish_handle: None,
+            metrics_addr: None,
+            metrics_handle: None,
             event_tx,
             event_rx: Some(event_rx),
             shutdown_tx: None,
@@ -199,8 +248,60 @@ impl NodeRunner {
     async fn initialize_components(&mut self) -> Result<(), NodeRunnerError> {
         info!("Initializing node components...");
 
+        // Load the persistent node identity
+        let mut p2p_config = self.config.p2p_config.clone();
+        if p2p_config.identity.is_none() {
+            if let Some(path) = &self.config.identity_path {
+                let path = path.clone();
+                let identity =
+                    tokio::task::spawn_blocking(move || NodeIdentity::load_or_create(path, None))
+                        .await
+                        .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?
+                        .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?;
+                p2p_config.identity = Some(identity);
+            }
+        }
+
+        // Serve swarm, DAG, pool and crypto metrics if enabled
+        if let Some(addr) = self.config.metrics_addr {
+            let exporter = MetricsExporter::new();
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?;
+            let local_addr = listener
+                .local_addr()
+                .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?;
+            info!("Serving metrics on http://{}/metrics", local_addr);
+            p2p_config.metrics = Some(exporter.clone());
+            self.metrics_addr = Some(local_addr);
+            self.metrics_handle = Some(tokio::spawn(async move {
+                if let Err(e) = exporter.serve(listener).await {
+                    error!("Metrics endpoint failed: {}", e);
+                }
+            }));
+        }
+
+        // Restore key certificates persisted below the data directory
+        let state_store: Arc<dyn StateStore + Send + Sync> = match &p2p_config.data_dir {
+            Some(data_dir) => Arc::new(
+                FileStateStore::new(data_dir.clone())
+                    .await
+                    .map_err(|e| NodeRunnerError::PersistenceError(e.to_string()))?,
+            ),
+            None => Arc::new(MemoryStateStore::new()),
+        };
+        let certificates = state_store
+            .load_key_certificates()
+            .await
+            .map_err(|e| NodeRunnerError::PersistenceError(e.to_string()))?;
+        let restored = self.revocations.extend(certificates);
+        if restored > 0 {
+            info!("Restored {} key certificates", restored);
+        }
+        self.state_store = Some(state_store);
+
         // Initialize P2P node
-        let (mut p2p_node, p2p_handle) = P2PNode::new(self.config.p2p_config.clone())
+        let (mut p2p_node, p2p_handle) = P2PNode::new(p2p_config)
             .await
             .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?;
 
@@ -220,6 +321,39 @@ impl NodeRunner {
                 })
         });
 
+        // Vertices are forwarded to other peers only once the DAG took them
+        p2p_handle.set_validator(
+            GossipTopic::Vertices,
+            Arc::new(VertexValidator {
+                dag: self.dag.clone(),
+                p2p_handle: p2p_handle.clone(),
+            }),
+        );
+        p2p_handle
+            .subscribe_topic(GossipTopic::Vertices)
+            .await
+            .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?;
+
+        // Merge certificates gossiped by peers and re-publish known ones
+        let key_certificates =
+            KeyCertificateGossip::new(self.revocations.clone(), p2p_handle.clone());
+        key_certificates
+            .subscribe()
+            .await
+            .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))?;
+        let republished = key_certificates.clone();
+        self.certificate_republish_handle = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(CERTIFICATE_REPUBLISH_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = republished.republish_all().await {
+                    debug!("Key certificate republish failed: {}", e);
+                }
+            }
+        }));
+        self.key_certificates = Some(key_certificates);
+
         self.p2p_handle = Some(p2p_handle);
         self.p2p_task_handle = Some(p2p_task_handle);
 
@@ -238,9 +372,24 @@ impl NodeRunner {
         self.rpc_server = Some(Arc::new(Mutex::new(rpc_server)));
         self.rpc_command_rx = Some(rpc_command_rx);
 
-        // Initialize dark resolver if enabled
-        if self.config.enable_dark_resolver {
-            self.dark_resolver = Some(Arc::new(RwLock::new(DarkResolver::new())));
+        // Initialize dark resolver over the Kademlia DHT if enabled
+        if let (true, Some(p2p_handle)) = (self.config.enable_dark_resolver, &self.p2p_handle) {
+            let dht = Arc::new(p2p_handle.clone());
+            let resolver = Arc::new(RwLock::new(
+                DarkResolver::with_dht(dht).with_revocation_list(self.revocations.clone()),
+            ));
+            let republished = resolver.clone();
+            self.dark_republish_handle = Some(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(DEFAULT_REPUBLISH_INTERVAL);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = republished.read().await.republish().await {
+                        warn!("Dark domain republish failed: {}", e);
+                    }
+                }
+            }));
+            self.dark_resolver = Some(resolver);
         }
 
         info!("All node components initialized successfully");
@@ -331,30 +480,26 @@ impl NodeRunner {
 
     /// Handle P2P network events
     async fn handle_p2p_event(&self, event: P2PEvent) -> Result<(), NodeRunnerError> {
+        // Persist certificates learned from peers so they survive restarts
+        let learned = self
+            .key_certificates
+            .as_ref()
+            .and_then(|gossip| gossip.handle_event(&event));
+        if let (Some(certificate), Some(state_store)) = (learned, &self.state_store) {
+            state_store
+                .save_key_certificate(&certificate)
+                .await
+                .map_err(|e| NodeRunnerError::PersistenceError(e.to_string()))?;
+        }
+
         match event {
-            P2PEvent::MessageReceived {
-                peer_id,
-                topic,
-                data,
-            } => {
+            P2PEvent::MessageReceived { peer_id, topic, .. } => {
                 debug!("Received message from peer {} on topic {}", peer_id, topic);
+            }
 
-                // Convert P2P message to DAG message
-                let dag_message = DagMessage {
-                    id: VertexId::new(),
-                    payload: data,
-                    parents: Default::default(), // TODO: Extract parents from message
-                    timestamp: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                };
-
-                // Submit to DAG
-                let dag = self.dag.write().await;
-                dag.submit_message(dag_message)
-                    .await
-                    .map_err(|e| NodeRunnerError::DagError(e.to_string()))?;
+            // Vertices were processed in the DAG while being validated
+            P2PEvent::GossipMessageReceived { peer_id, message } => {
+                debug!("Accepted {} message from peer {}", message.topic(), peer_id);
             }
 
             P2PEvent::PeerConnected(peer_id) => {
@@ -451,6 +596,21 @@ impl NodeRunner {
                 .map_err(|e| NodeRunnerError::RpcError(e.to_string()))?;
         }
 
+        // Stop republishing .dark records and key certificates
+        if let Some(task_handle) = self.dark_republish_handle.take() {
+            task_handle.abort();
+        }
+        if let Some(task_handle) = self.certificate_republish_handle.take() {
+            task_handle.abort();
+        }
+        self.key_certificates = None;
+
+        // Stop serving metrics
+        if let Some(task_handle) = self.metrics_handle.take() {
+            task_handle.abort();
+        }
+        self.metrics_addr = None;
+
         // Stop P2P node by canceling the task
         if let Some(task_handle) = self.p2p_task_handle.take() {
             task_handle.abort();
@@ -476,6 +636,11 @@ impl NodeRunner {
         &self.p2p_handle
     }
 
+    /// Address the metrics endpoint is bound to, while it is served
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_addr
+    }
+
     /// Get a reference to the DAG
     pub fn dag(&self) -> &Arc<RwLock<Dag>> {
         &self.dag
@@ -491,6 +656,35 @@ impl NodeRunner {
         &self.dark_resolver
     }
 
+    /// Revocation list fed by persisted and gossiped key certificates
+    pub fn revocation_list(&self) -> &RevocationList {
+        &self.revocations
+    }
+
+    /// Persist a local key rotation or revocation and gossip it to peers
+    ///
+    /// The certificate is kept even if publishing fails, and goes out with
+    /// the next republish.
+    pub async fn announce_key_certificate(
+        &self,
+        certificate: KeyCertificate,
+    ) -> Result<(), NodeRunnerError> {
+        let (Some(gossip), Some(state_store)) = (&self.key_certificates, &self.state_store) else {
+            return Err(NodeRunnerError::NotStarted);
+        };
+        self.revocations.insert(certificate.clone()).map_err(|e| {
+            NodeRunnerError::ProtocolError(ProtocolError::CryptoError(e.to_string()))
+        })?;
+        state_store
+            .save_key_certificate(&certificate)
+            .await
+            .map_err(|e| NodeRunnerError::PersistenceError(e.to_string()))?;
+        gossip
+            .announce(certificate)
+            .await
+            .map_err(|e| NodeRunnerError::NetworkError(e.to_string()))
+    }
+
     /// Get a reference to the running state
     pub fn is_running(&self) -> &Arc<RwLock<bool>> {
         &self.is_running
@@ -532,6 +726,72 @@ impl NodeRunner {
     }
 }
 
+/// Validates gossiped vertices by processing them in the DAG
+struct VertexValidator {
+    dag: Arc<RwLock<Dag>>,
+    p2p_handle: P2PHandle,
+}
+
+#[async_trait]
+impl GossipValidator for VertexValidator {
+    /// Accept vertices the DAG took and reject those that are the sender's
+    /// fault; equivocation is reported on top, as it bans at once
+    async fn validate(&self, source: PeerId, message: &GossipMessage) -> Verdict {
+        let GossipMessage::Vertex(vertex) = message else {
+            return Verdict::Reject;
+        };
+        let dag_message = DagMessage {
+            id: VertexId::from_bytes(vertex.id.clone()),
+            payload: vertex.payload.clone(),
+            parents: vertex
+                .parents
+                .iter()
+                .cloned()
+                .map(VertexId::from_bytes)
+                .collect(),
+            timestamp: vertex.timestamp,
+        };
+
+        let dag = self.dag.write().await;
+        let Err(e) = dag.submit_message_and_wait(dag_message).await else {
+            return Verdict::Accept;
+        };
+        match peer_event_for(&e) {
+            Some(peer_event) => {
+                warn!("Rejected vertex from peer {}: {}", source, e);
+                if peer_event == PeerEvent::Equivocation {
+                    self.p2p_handle.report_peer(source, peer_event);
+                }
+                Verdict::Reject
+            }
+            None => {
+                debug!("Ignored vertex from peer {}: {}", source, e);
+                Verdict::Ignore
+            }
+        }
+    }
+}
+
+/// What a DAG rejection says about the peer that sent the message
+///
+/// Conflicting votes and forks are equivocation; other invalid vertices are
+/// penalised less. Local failures say nothing about the peer.
+fn peer_event_for(error: &DagModuleError) -> Option<PeerEvent> {
+    match error {
+        DagModuleError::ConsensusError(
+            ConsensusError::ByzantineBehavior(_)
+            | ConsensusError::ForkDetected(_)
+            | ConsensusError::ConflictingVertices,
+        ) => Some(PeerEvent::Equivocation),
+        DagModuleError::VertexError(_)
+        | DagModuleError::ConflictDetected
+        | DagModuleError::ConsensusError(
+            ConsensusError::InvalidVertex | ConsensusError::ValidationError(_),
+        ) => Some(PeerEvent::InvalidVertex),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
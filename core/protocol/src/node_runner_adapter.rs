@@ -1,9 +1,9 @@
 use crate::node_runner::NodeRunner;
 use crate::rpc_server::{NetworkStats, NodeRunnerTrait, PeerInfo};
-use libp2p::Multiaddr;
+use libp2p::{Multiaddr, PeerId};
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
 /// Adapter that implements NodeRunnerTrait for the actual NodeRunner
@@ -124,6 +124,49 @@ impl NodeRunnerTrait for NodeRunnerAdapter {
         })
     }
 
+    fn ban_peer(
+        &self,
+        peer_id: &str,
+        duration: Duration,
+    ) -> Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>> {
+        let node_runner = self.node_runner.clone();
+        let peer_id = peer_id.to_string();
+        Box::pin(async move {
+            let peer_id: PeerId = peer_id
+                .parse()
+                .map_err(|e| format!("Invalid peer ID: {}", e))?;
+            let runner = node_runner.read().await;
+
+            if let Some(p2p_handle) = runner.p2p_handle() {
+                // The node closes the peer's connections and refuses new ones
+                p2p_handle.ban_peer(peer_id, duration, "banned over RPC");
+                Ok(())
+            } else {
+                Err("P2P handle not available".to_string())
+            }
+        })
+    }
+
+    fn unban_peer(
+        &self,
+        peer_id: &str,
+    ) -> Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>> {
+        let node_runner = self.node_runner.clone();
+        let peer_id = peer_id.to_string();
+        Box::pin(async move {
+            let peer_id: PeerId = peer_id
+                .parse()
+                .map_err(|e| format!("Invalid peer ID: {}", e))?;
+            let runner = node_runner.read().await;
+
+            match runner.p2p_handle() {
+                Some(p2p_handle) if p2p_handle.unban_peer(&peer_id) => Ok(()),
+                Some(_) => Err("Peer is not banned".to_string()),
+                None => Err("P2P handle not available".to_string()),
+            }
+        })
+    }
+
     fn get_network_stats(&self) -> Pin<Box<dyn std::future::Future<Output = NetworkStats> + Send>> {
         let node_runner = self.node_runner.clone();
         let start_time = self.start_time;
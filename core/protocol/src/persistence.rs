@@ -15,6 +15,7 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{debug, error, info, warn};
 
 // Import types from other modules
+use qudag_crypto::key_certificate::KeyCertificate;
 use qudag_dag::vertex::{Vertex, VertexId};
 use qudag_network::dark_resolver::DarkDomainRecord;
 use qudag_network::types::PeerId;
@@ -203,6 +204,12 @@ pub trait StateStore: Send + Sync {
     /// Load all dark domain records from storage
     async fn load_dark_records(&self) -> Result<Vec<DarkDomainRecord>>;
 
+    /// Save a key rotation or revocation certificate to storage
+    async fn save_key_certificate(&self, certificate: &KeyCertificate) -> Result<()>;
+
+    /// Load all key certificates from storage
+    async fn load_key_certificates(&self) -> Result<Vec<KeyCertificate>>;
+
     /// Remove a vertex from storage
     async fn remove_vertex(&self, id: &VertexId) -> Result<()>;
 
@@ -257,6 +264,7 @@ impl FileStateStore {
         let vertices_dir = data_dir.join("vertices");
         let peers_dir = data_dir.join("peers");
         let domains_dir = data_dir.join("domains");
+        let certificates_dir = data_dir.join("certificates");
 
         fs::create_dir_all(&vertices_dir).await.map_err(|e| {
             PersistenceError::DirectoryCreation(format!("Failed to create vertices dir: {}", e))
@@ -270,6 +278,10 @@ impl FileStateStore {
             PersistenceError::DirectoryCreation(format!("Failed to create domains dir: {}", e))
         })?;
 
+        fs::create_dir_all(&certificates_dir).await.map_err(|e| {
+            PersistenceError::DirectoryCreation(format!("Failed to create certificates dir: {}", e))
+        })?;
+
         info!("Initialized file state store at {:?}", data_dir);
 
         Ok(Self {
@@ -306,6 +318,15 @@ impl FileStateStore {
             .join(format!("{}.json", id_hex))
     }
 
+    /// Get path for a key certificate file
+    fn certificate_path(&self, certificate: &KeyCertificate) -> PathBuf {
+        // A key has at most one rotation and one revocation
+        let id_hex = hex::encode(certificate.subject());
+        self.data_dir
+            .join("certificates")
+            .join(format!("{}-{}.json", id_hex, certificate.kind()))
+    }
+
     /// Write data to file atomically
     async fn write_file_atomic<T: Serialize>(&self, path: &Path, data: &T) -> Result<()> {
         let json = serde_json::to_string_pretty(data)
@@ -436,6 +457,36 @@ impl StateStore for FileStateStore {
         Ok(records)
     }
 
+    async fn save_key_certificate(&self, certificate: &KeyCertificate) -> Result<()> {
+        let path = self.certificate_path(certificate);
+        self.write_file_atomic(&path, certificate).await?;
+        debug!("Saved key certificate to {:?}", path);
+        Ok(())
+    }
+
+    async fn load_key_certificates(&self) -> Result<Vec<KeyCertificate>> {
+        let certificates_dir = self.data_dir.join("certificates");
+        let mut certificates = Vec::new();
+
+        // Stores created before certificates existed have no directory
+        if !certificates_dir.exists() {
+            return Ok(certificates);
+        }
+
+        let mut entries = fs::read_dir(&certificates_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                if let Some(certificate) = self.read_file::<KeyCertificate>(&path).await? {
+                    certificates.push(certificate);
+                }
+            }
+        }
+
+        debug!("Loaded {} key certificates from files", certificates.len());
+        Ok(certificates)
+    }
+
     async fn remove_vertex(&self, id: &VertexId) -> Result<()> {
         let path = self.vertex_path(id);
         if path.exists() {
@@ -584,6 +635,8 @@ pub struct MemoryStateStore {
     peers: DashMap<PeerId, PeerInfo>,
     /// Stored dark domain records
     dark_records: DashMap<String, DarkDomainRecord>,
+    /// Stored key certificates
+    key_certificates: DashMap<String, KeyCertificate>,
 }
 
 impl Default for MemoryStateStore {
@@ -599,6 +652,7 @@ impl MemoryStateStore {
             vertices: DashMap::new(),
             peers: DashMap::new(),
             dark_records: DashMap::new(),
+            key_certificates: DashMap::new(),
         }
     }
 
@@ -607,6 +661,7 @@ impl MemoryStateStore {
         self.vertices.clear();
         self.peers.clear();
         self.dark_records.clear();
+        self.key_certificates.clear();
     }
 }
 
@@ -662,6 +717,27 @@ impl StateStore for MemoryStateStore {
         Ok(records)
     }
 
+    async fn save_key_certificate(&self, certificate: &KeyCertificate) -> Result<()> {
+        let key = format!(
+            "{}-{}",
+            hex::encode(certificate.subject()),
+            certificate.kind()
+        );
+        self.key_certificates.insert(key, certificate.clone());
+        debug!("Saved key certificate to memory");
+        Ok(())
+    }
+
+    async fn load_key_certificates(&self) -> Result<Vec<KeyCertificate>> {
+        let certificates: Vec<KeyCertificate> = self
+            .key_certificates
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        debug!("Loaded {} key certificates from memory", certificates.len());
+        Ok(certificates)
+    }
+
     async fn remove_vertex(&self, id: &VertexId) -> Result<()> {
         self.vertices.remove(id);
         debug!("Removed vertex {:?} from memory", id);
@@ -961,6 +1037,10 @@ mod tests {
             owner_id: PeerId::new(),
             signature: vec![9, 10, 11, 12],
             metadata: HashMap::new(),
+            domain: "test.dark".to_string(),
+            sequence: 0,
+            introduction_points: Vec::new(),
+            version: qudag_network::dark_resolver::DARK_RECORD_VERSION,
         }
     }
 
@@ -1,4 +1,5 @@
 use crate::ProtocolError;
+use qudag_crypto::key_certificate::RevocationList;
 use qudag_crypto::ml_dsa::MlDsaPublicKey;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -128,6 +129,9 @@ pub struct NodeStatus {
     pub memory_usage: MemoryStats,
 }
 
+/// How long `ban_peer` bans a peer when no duration is given
+const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
 /// Transport type for RPC server
 #[derive(Debug, Clone)]
 pub enum RpcTransport {
@@ -163,6 +167,23 @@ pub trait NodeRunnerTrait: Send + Sync + std::fmt::Debug {
         peer_id: &str,
     ) -> Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>>;
     fn get_network_stats(&self) -> Pin<Box<dyn std::future::Future<Output = NetworkStats> + Send>>;
+    /// Ban a peer by ID for `duration`
+    ///
+    /// Nodes without peer bans only disconnect the peer.
+    fn ban_peer(
+        &self,
+        peer_id: &str,
+        _duration: Duration,
+    ) -> Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>> {
+        self.disconnect_peer(peer_id)
+    }
+    /// Lift the ban of a peer
+    fn unban_peer(
+        &self,
+        _peer_id: &str,
+    ) -> Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>> {
+        Box::pin(async { Err("Peer is not banned".to_string()) })
+    }
     fn shutdown(
         &self,
     ) -> Pin<
@@ -186,6 +207,8 @@ pub struct RpcServer {
     auth_token: Option<String>,
     rate_limiter: Arc<Mutex<RateLimiter>>,
     auth_keys: Arc<RwLock<HashMap<String, MlDsaPublicKey>>>,
+    /// Rotated and revoked client keys, checked on every request
+    revocations: RevocationList,
     #[allow(dead_code)]
     start_time: SystemTime,
 }
@@ -310,20 +333,16 @@ impl NetworkManager {
         self.mock_peers.values().cloned().collect()
     }
 
-    async fn ban_peer(&mut self, peer_id: &str) -> Result<(), String> {
-        // Get peer address before removing
-        let peer_address = if let Some(node) = &self.node_handle {
+    async fn ban_peer(&mut self, peer_id: &str, duration: Duration) -> Result<(), String> {
+        // A real node bans by peer ID, on dial and accept, whether or not
+        // the peer is connected
+        if let Some(node) = &self.node_handle {
             let node_guard = node.read().await;
-            let connected_peers = node_guard.get_connected_peers().await;
-            connected_peers
-                .into_iter()
-                .find(|p| p.id == peer_id)
-                .map(|p| p.address)
-        } else {
-            self.mock_peers.get(peer_id).map(|p| p.address.clone())
-        };
+            return node_guard.ban_peer(peer_id, duration).await;
+        }
 
-        if let Some(address) = peer_address {
+        // Fall back to mock behavior
+        if let Some(address) = self.mock_peers.get(peer_id).map(|p| p.address.clone()) {
             self.banned_peers.insert(address);
             self.remove_peer(peer_id).await?;
             Ok(())
@@ -332,12 +351,16 @@ impl NetworkManager {
         }
     }
 
-    fn unban_peer(&mut self, address: &str) -> Result<(), String> {
-        if self.banned_peers.remove(address) {
-            Ok(())
-        } else {
-            Err("Peer is not banned".to_string())
+    /// Lift a ban, by address for mock peers or by peer ID for a real node
+    async fn unban_peer(&mut self, peer: &str) -> Result<(), String> {
+        if self.banned_peers.remove(peer) {
+            return Ok(());
         }
+        if let Some(node) = &self.node_handle {
+            let node_guard = node.read().await;
+            return node_guard.unban_peer(peer).await;
+        }
+        Err("Peer is not banned".to_string())
     }
 
     async fn get_network_stats(&mut self) -> NetworkStats {
@@ -456,6 +479,7 @@ impl RpcServer {
             auth_token: std::env::var("RPC_AUTH_TOKEN").ok(),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new(60))), // 60 requests per minute
             auth_keys: Arc::new(RwLock::new(HashMap::new())),
+            revocations: RevocationList::new(),
             start_time: SystemTime::now(),
         };
 
@@ -481,6 +505,7 @@ impl RpcServer {
             auth_token: std::env::var("RPC_AUTH_TOKEN").ok(),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new(60))),
             auth_keys: Arc::new(RwLock::new(HashMap::new())),
+            revocations: RevocationList::new(),
             start_time: SystemTime::now(),
         };
 
@@ -507,6 +532,7 @@ impl RpcServer {
             auth_token: Some(auth_token),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new(60))),
             auth_keys: Arc::new(RwLock::new(HashMap::new())),
+            revocations: RevocationList::new(),
             start_time: SystemTime::now(),
         };
 
@@ -525,10 +551,31 @@ impl RpcServer {
         self.node_shutdown_tx = Some(tx);
     }
 
+    /// Use a shared revocation list for client key authentication
+    pub fn with_revocation_list(mut self, revocations: RevocationList) -> Self {
+        self.revocations = revocations;
+        self
+    }
+
+    /// Revocation list consulted when authenticating clients
+    pub fn revocation_list(&self) -> &RevocationList {
+        &self.revocations
+    }
+
     /// Add an authorized public key for ML-DSA authentication
-    pub async fn add_auth_key(&self, client_id: String, public_key: MlDsaPublicKey) {
+    ///
+    /// Rotated or revoked keys are refused.
+    pub async fn add_auth_key(
+        &self,
+        client_id: String,
+        public_key: MlDsaPublicKey,
+    ) -> Result<(), ProtocolError> {
+        self.revocations
+            .check(public_key.as_bytes())
+            .map_err(|e| ProtocolError::CryptoError(e.to_string()))?;
         let mut keys = self.auth_keys.write().await;
         keys.insert(client_id, public_key);
+        Ok(())
     }
 
     /// Start RPC server
@@ -542,6 +589,7 @@ impl RpcServer {
         // let dag = self.dag.clone();
         let auth_token = self.auth_token.clone();
         let auth_keys = Arc::clone(&self.auth_keys);
+        let revocations = self.revocations.clone();
         let rate_limiter = Arc::clone(&self.rate_limiter);
         let transport = self.transport.clone();
 
@@ -571,6 +619,7 @@ impl RpcServer {
                                 // let dag = dag.clone();
                                 let auth_token = auth_token.clone();
                                 let auth_keys = Arc::clone(&auth_keys);
+                                let revocations = revocations.clone();
                                 let rate_limiter = Arc::clone(&rate_limiter);
                                 let client_ip = addr.ip().to_string();
 
@@ -585,7 +634,7 @@ impl RpcServer {
                                     }
 
                                     if let Err(e) = handle_tcp_connection(
-                                        stream, command_tx, network_manager, auth_token, auth_keys
+                                        stream, command_tx, network_manager, auth_token, auth_keys, revocations
                                     ).await {
                                         error!("Error handling RPC connection: {}", e);
                                     }
@@ -622,10 +671,11 @@ impl RpcServer {
                                 // let dag = dag.clone();
                                 let auth_token = auth_token.clone();
                                 let auth_keys = Arc::clone(&auth_keys);
+                                let revocations = revocations.clone();
 
                                 tokio::spawn(async move {
                                     if let Err(e) = handle_unix_connection(
-                                        stream, command_tx, network_manager, auth_token, auth_keys
+                                        stream, command_tx, network_manager, auth_token, auth_keys, revocations
                                     ).await {
                                         error!("Error handling RPC connection: {}", e);
                                     }
@@ -660,6 +710,7 @@ async fn handle_tcp_connection(
     network_manager: Arc<RwLock<NetworkManager>>,
     auth_token: Option<String>,
     auth_keys: Arc<RwLock<HashMap<String, MlDsaPublicKey>>>,
+    revocations: RevocationList,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Read request with timeout
     let request_len = timeout(Duration::from_secs(30), ReadU32Ext::read_u32(&mut stream))
@@ -675,8 +726,15 @@ async fn handle_tcp_connection(
 
     let request: RpcRequest = serde_json::from_slice(&request_data)?;
 
-    let response =
-        handle_request(request, command_tx, network_manager, auth_token, auth_keys).await;
+    let response = handle_request(
+        request,
+        command_tx,
+        network_manager,
+        auth_token,
+        auth_keys,
+        revocations,
+    )
+    .await;
 
     let response_data = serde_json::to_vec(&response)?;
     stream
@@ -695,6 +753,7 @@ async fn handle_unix_connection(
     network_manager: Arc<RwLock<NetworkManager>>,
     auth_token: Option<String>,
     auth_keys: Arc<RwLock<HashMap<String, MlDsaPublicKey>>>,
+    revocations: RevocationList,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let request_len = timeout(Duration::from_secs(30), ReadU32Ext::read_u32(&mut stream))
         .await??
@@ -709,8 +768,15 @@ async fn handle_unix_connection(
 
     let request: RpcRequest = serde_json::from_slice(&request_data)?;
 
-    let response =
-        handle_request(request, command_tx, network_manager, auth_token, auth_keys).await;
+    let response = handle_request(
+        request,
+        command_tx,
+        network_manager,
+        auth_token,
+        auth_keys,
+        revocations,
+    )
+    .await;
 
     let response_data = serde_json::to_vec(&response)?;
     stream
@@ -727,6 +793,7 @@ async fn authenticate_request(
     request: &RpcRequest,
     auth_token: &Option<String>,
     auth_keys: &Arc<RwLock<HashMap<String, MlDsaPublicKey>>>,
+    revocations: &RevocationList,
 ) -> bool {
     // Check token-based auth first
     if let Some(expected_token) = auth_token {
@@ -747,6 +814,12 @@ async fn authenticate_request(
     ) {
         let keys = auth_keys.read().await;
         if let Some(public_key) = keys.get(client_id) {
+            // Keys rotated or revoked after being authorized are no longer valid
+            if let Err(e) = revocations.check(public_key.as_bytes()) {
+                warn!("Rejecting RPC client {}: {}", client_id, e);
+                return false;
+            }
+
             // Verify signature over the request method and ID
             let message = format!("{}:{}", request.method, request.id);
             if let Ok(sig_bytes) = hex::decode(signature) {
@@ -767,9 +840,10 @@ async fn handle_request(
     network_manager: Arc<RwLock<NetworkManager>>,
     auth_token: Option<String>,
     auth_keys: Arc<RwLock<HashMap<String, MlDsaPublicKey>>>,
+    revocations: RevocationList,
 ) -> RpcResponse {
     // Authenticate request if auth is enabled
-    if !authenticate_request(&request, &auth_token, &auth_keys).await {
+    if !authenticate_request(&request, &auth_token, &auth_keys, &revocations).await {
         return RpcResponse {
             id: request.id,
             result: None,
@@ -912,8 +986,14 @@ async fn handle_request(
                 }
             };
 
+            let duration = request
+                .params
+                .get("duration_secs")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_BAN_DURATION, Duration::from_secs);
+
             let mut manager = network_manager.write().await;
-            match manager.ban_peer(peer_id).await {
+            match manager.ban_peer(peer_id, duration).await {
                 Ok(()) => RpcResponse {
                     id: request.id,
                     result: Some(
@@ -933,7 +1013,12 @@ async fn handle_request(
             }
         }
         "unban_peer" => {
-            let address = match request.params.get("address").and_then(|v| v.as_str()) {
+            let address = match request
+                .params
+                .get("address")
+                .or_else(|| request.params.get("peer_id"))
+                .and_then(|v| v.as_str())
+            {
                 Some(addr) => addr,
                 None => {
                     return RpcResponse {
@@ -941,7 +1026,7 @@ async fn handle_request(
                         result: None,
                         error: Some(RpcError {
                             code: -32602,
-                            message: "Invalid params: address required".to_string(),
+                            message: "Invalid params: address or peer_id required".to_string(),
                             data: None,
                         }),
                     };
@@ -949,7 +1034,7 @@ async fn handle_request(
             };
 
             let mut manager = network_manager.write().await;
-            match manager.unban_peer(address) {
+            match manager.unban_peer(address).await {
                 Ok(()) => RpcResponse {
                     id: request.id,
                     result: Some(
@@ -1175,7 +1260,10 @@ mod tests {
         let peer_id = manager.list_peers().await[0].id.clone();
 
         // Ban the peer
-        assert!(manager.ban_peer(&peer_id).await.is_ok());
+        assert!(manager
+            .ban_peer(&peer_id, DEFAULT_BAN_DURATION)
+            .await
+            .is_ok());
         assert_eq!(manager.list_peers().await.len(), 0); // Should be removed
 
         // Try to add the same address again (should fail)
@@ -1185,7 +1273,7 @@ mod tests {
             .is_err());
 
         // Unban the peer
-        assert!(manager.unban_peer("127.0.0.1:8001").is_ok());
+        assert!(manager.unban_peer("127.0.0.1:8001").await.is_ok());
 
         // Now adding should work again
         assert!(manager.add_peer("127.0.0.1:8001".to_string()).await.is_ok());
@@ -1221,16 +1309,31 @@ mod tests {
         };
 
         let auth_keys = Arc::new(RwLock::new(HashMap::new()));
+        let revocations = RevocationList::new();
 
         // Test with auth enabled
         let auth_token = Some("secret123".to_string());
-        assert!(authenticate_request(&request_with_token, &auth_token, &auth_keys).await);
-        assert!(!authenticate_request(&request_without_token, &auth_token, &auth_keys).await);
+        assert!(
+            authenticate_request(&request_with_token, &auth_token, &auth_keys, &revocations).await
+        );
+        assert!(
+            !authenticate_request(
+                &request_without_token,
+                &auth_token,
+                &auth_keys,
+                &revocations
+            )
+            .await
+        );
 
         // Test with auth disabled
         let no_auth = None;
-        assert!(authenticate_request(&request_with_token, &no_auth, &auth_keys).await);
-        assert!(authenticate_request(&request_without_token, &no_auth, &auth_keys).await);
+        assert!(
+            authenticate_request(&request_with_token, &no_auth, &auth_keys, &revocations).await
+        );
+        assert!(
+            authenticate_request(&request_without_token, &no_auth, &auth_keys, &revocations).await
+        );
     }
 
     #[tokio::test]
@@ -0,0 +1,233 @@
+//! Tests for key certificate enforcement in the handshake and RPC server, for
+//! certificate persistence, and for certificate gossip between node runners.
+
+use qudag_crypto::key_certificate::{
+    KeyCertificate, KeyCertificateError, KeyRevocation, KeyRotation, RevocationList,
+    RevocationReason,
+};
+use qudag_crypto::ml_dsa::{MlDsaKeyPair, MlDsaPublicKey};
+use qudag_network::p2p::NetworkConfig;
+use qudag_protocol::state::HandshakeState;
+use qudag_protocol::{
+    FileStateStore, HandshakeConfig, HandshakeCoordinator, HandshakeError, MemoryStateStore,
+    NodeRunner, NodeRunnerConfig, ProtocolError, ProtocolState, ProtocolStateMachine,
+    ProtocolVersion, RpcServer, StateStore,
+};
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn keypair() -> MlDsaKeyPair {
+    MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap()
+}
+
+fn coordinator() -> HandshakeCoordinator {
+    let mut state_machine = ProtocolStateMachine::new(ProtocolVersion::CURRENT);
+    state_machine
+        .transition_to(
+            ProtocolState::Handshake(HandshakeState::Waiting),
+            "Awaiting peer".to_string(),
+        )
+        .unwrap();
+    HandshakeCoordinator::new(
+        HandshakeConfig::default(),
+        HandshakeCoordinator::generate_keys().unwrap(),
+        state_machine,
+    )
+}
+
+fn sample_certificates() -> Vec<KeyCertificate> {
+    let old = keypair();
+    let new = keypair();
+    vec![
+        KeyRotation::now(&old, &new).unwrap().into(),
+        KeyRevocation::new(&new, RevocationReason::KeyCompromise, 1)
+            .unwrap()
+            .into(),
+    ]
+}
+
+#[test]
+fn test_handshake_rejects_revoked_peer_key() {
+    let mut initiator = coordinator();
+    let (session_id, init) = initiator.initiate_handshake(None).unwrap();
+
+    // Revoke the key the initiator signed with
+    let signing_key = &initiator
+        .get_session(&session_id)
+        .unwrap()
+        .our_keys
+        .signature_keypair;
+    let revocations = RevocationList::new();
+    revocations
+        .insert(
+            KeyRevocation::new(signing_key, RevocationReason::KeyCompromise, 1)
+                .unwrap()
+                .into(),
+        )
+        .unwrap();
+
+    let mut responder = coordinator().with_revocation_list(revocations);
+    match responder.process_handshake_message(&init, None) {
+        Err(HandshakeError::KeyRejected(KeyCertificateError::Revoked { .. })) => {}
+        other => panic!("expected revoked key error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_handshake_accepts_unrevoked_peer_key() {
+    let mut initiator = coordinator();
+    let (_, init) = initiator.initiate_handshake(None).unwrap();
+
+    let mut responder = coordinator().with_revocation_list(RevocationList::new());
+    assert!(responder
+        .process_handshake_message(&init, None)
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn test_rpc_add_auth_key_rejects_retired_keys() {
+    let old = keypair();
+    let new = keypair();
+    let revocations = RevocationList::new();
+    revocations
+        .insert(KeyRotation::now(&old, &new).unwrap().into())
+        .unwrap();
+
+    let (server, _rx) = RpcServer::new_tcp(0);
+    let server = server.with_revocation_list(revocations);
+
+    let old_public = MlDsaPublicKey::from_bytes(old.public_key()).unwrap();
+    let new_public = MlDsaPublicKey::from_bytes(new.public_key()).unwrap();
+    assert!(matches!(
+        server.add_auth_key("client".to_string(), old_public).await,
+        Err(ProtocolError::CryptoError(_))
+    ));
+    server
+        .add_auth_key("client".to_string(), new_public)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_file_store_persists_certificates() {
+    let temp_dir = TempDir::new().unwrap();
+    let certificates = sample_certificates();
+
+    {
+        let store = FileStateStore::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        for certificate in &certificates {
+            store.save_key_certificate(certificate).await.unwrap();
+            // Saving twice overwrites rather than duplicating
+            store.save_key_certificate(certificate).await.unwrap();
+        }
+    }
+
+    let store = FileStateStore::new(temp_dir.path().to_path_buf())
+        .await
+        .unwrap();
+    let loaded = store.load_key_certificates().await.unwrap();
+    assert_eq!(loaded.len(), certificates.len());
+
+    // Reloaded certificates rebuild the same revocation list
+    let list = RevocationList::new();
+    assert_eq!(list.extend(loaded), certificates.len());
+    for certificate in &certificates {
+        assert!(list.certificates().contains(certificate));
+    }
+}
+
+#[tokio::test]
+async fn test_memory_store_persists_certificates() {
+    let store = MemoryStateStore::new();
+    let certificates = sample_certificates();
+    for certificate in &certificates {
+        store.save_key_certificate(certificate).await.unwrap();
+    }
+
+    let loaded = store.load_key_certificates().await.unwrap();
+    assert_eq!(loaded.len(), certificates.len());
+
+    store.clear();
+    assert!(store.load_key_certificates().await.unwrap().is_empty());
+}
+
+fn runner_config(data_dir: &Path) -> NodeRunnerConfig {
+    NodeRunnerConfig {
+        p2p_config: NetworkConfig {
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".to_string()],
+            enable_mdns: false,
+            enable_websocket: false,
+            obfuscation_key: [29u8; 32],
+            data_dir: Some(data_dir.to_path_buf()),
+            ..Default::default()
+        },
+        enable_dark_resolver: false,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_gossiped_certificates_survive_restart() {
+    let announcer_dir = TempDir::new().unwrap();
+    let receiver_dir = TempDir::new().unwrap();
+
+    let mut announcer = NodeRunner::new(runner_config(announcer_dir.path()));
+    announcer.start().await.unwrap();
+    let announcer_handle = announcer.p2p_handle().clone().unwrap();
+    let mut announcer_addr = None;
+    for _ in 0..50 {
+        announcer_addr = announcer_handle.listeners().await.into_iter().next();
+        if announcer_addr.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    // The receiver persists certificates from its event loop
+    let mut receiver = NodeRunner::new(runner_config(receiver_dir.path()));
+    receiver.start().await.unwrap();
+    receiver
+        .p2p_handle()
+        .clone()
+        .unwrap()
+        .dial(announcer_addr.expect("announcer is not listening"))
+        .await
+        .unwrap();
+    let receiver_task = tokio::spawn(async move {
+        let _ = receiver.run().await;
+    });
+
+    // Publishing fails until the gossip mesh has formed
+    let revocation = KeyRevocation::new(&keypair(), RevocationReason::KeyCompromise, 1).unwrap();
+    let store = FileStateStore::new(receiver_dir.path().to_path_buf())
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    for _ in 0..60 {
+        let _ = announcer
+            .announce_key_certificate(revocation.clone().into())
+            .await;
+        received = store.load_key_certificates().await.unwrap();
+        if !received.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert_eq!(received, vec![KeyCertificate::from(revocation.clone())]);
+    receiver_task.abort();
+    announcer.stop().await.unwrap();
+
+    // Both nodes reload the certificate on start
+    for data_dir in [announcer_dir.path(), receiver_dir.path()] {
+        let mut restarted = NodeRunner::new(runner_config(data_dir));
+        restarted.start().await.unwrap();
+        assert!(restarted
+            .revocation_list()
+            .is_revoked(&revocation.public_key));
+        restarted.stop().await.unwrap();
+    }
+}
@@ -64,6 +64,12 @@ impl From<qudag_crypto::CryptoError> for VaultError {
     }
 }
 
+impl From<qudag_crypto::KeyEncodingError> for VaultError {
+    fn from(err: qudag_crypto::KeyEncodingError) -> Self {
+        VaultError::Crypto(err.to_string())
+    }
+}
+
 impl From<bincode::Error> for VaultError {
     fn from(err: bincode::Error) -> Self {
         VaultError::Serialization(err.to_string())
@@ -1,5 +1,6 @@
 //! Main vault implementation combining all components.
 
+use qudag_crypto::key_encoding::{EncodedKey, KeyFormat};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
@@ -198,6 +199,32 @@ impl Vault {
         self.dag.get_secret(label, &self.crypto)
     }
 
+    /// Store a key under `label`.
+    ///
+    /// The key is kept in its armored form with the algorithm name as the
+    /// username, so it can be exported again with [`Vault::get_key`].
+    pub fn add_key(&mut self, label: &str, key: &EncodedKey) -> VaultResult<()> {
+        let armored = String::from_utf8(key.encode(KeyFormat::Armored, None)?)
+            .map_err(|e| VaultError::Serialization(e.to_string()))?;
+        self.add_secret(label, key.algorithm().name(), Some(&armored))
+    }
+
+    /// Retrieve a key stored with [`Vault::add_key`].
+    pub fn get_key(&self, label: &str) -> VaultResult<EncodedKey> {
+        let secret = self.get_secret(label)?;
+        let key = EncodedKey::decode(secret.password.as_str().as_bytes(), None)
+            .map_err(|e| VaultError::InvalidFormat(format!("{}: {}", label, e)))?;
+        if key.algorithm().name() != secret.username {
+            return Err(VaultError::InvalidFormat(format!(
+                "{}: stored as {} but contains a {} key",
+                label,
+                secret.username,
+                key.algorithm()
+            )));
+        }
+        Ok(key)
+    }
+
     /// List all secrets or those in a specific category.
     pub fn list_secrets(&self, category: Option<&str>) -> VaultResult<Vec<String>> {
         self.dag.list_secrets(category)
@@ -419,4 +446,26 @@ mod tests {
         vault.delete_secret("test/secret").unwrap();
         vault.get_secret("test/secret").unwrap_err();
     }
+
+    #[test]
+    fn test_vault_keys() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_vault.qdag");
+
+        let keypair =
+            qudag_crypto::ml_dsa::MlDsaKeyPair::generate(&mut rand::thread_rng()).unwrap();
+        let key = EncodedKey::from(&keypair);
+        {
+            let mut vault = Vault::create(&path, "test_password").unwrap();
+            vault.add_key("keys/identity", &key).unwrap();
+        }
+
+        // Keys survive reopening the vault
+        let vault = Vault::open(&path, "test_password").unwrap();
+        assert_eq!(vault.get_key("keys/identity").unwrap(), key);
+        assert_eq!(
+            vault.get_secret("keys/identity").unwrap().username,
+            "ML-DSA-65"
+        );
+    }
 }
@@ -1,11 +1,13 @@
 //! Example demonstrating the Dark Addressing system with quantum-resistant cryptography
 
 use anyhow::Result;
+use async_trait::async_trait;
 use qudag_network::dark_resolver::{DarkResolver, DarkAddress, AddressBookEntry, DarkDomainRecord, DhtClient, DarkResolverError};
 use qudag_network::types::{NetworkAddress, PeerId};
 use rand::thread_rng;
 use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
+use std::time::Duration;
 use tracing::{info, warn};
 use tracing_subscriber;
 
@@ -22,8 +24,9 @@ impl MockDhtClient {
     }
 }
 
+#[async_trait]
 impl DhtClient for MockDhtClient {
-    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), DarkResolverError> {
+    async fn put(&self, key: &[u8], value: &[u8], _ttl: Duration) -> Result<(), DarkResolverError> {
         let mut storage = self.storage.write()
             .map_err(|_| DarkResolverError::StorageError)?;
         storage.insert(key.to_vec(), value.to_vec());
@@ -31,20 +34,12 @@ impl DhtClient for MockDhtClient {
         Ok(())
     }
     
-    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DarkResolverError> {
+    async fn get(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, DarkResolverError> {
         let storage = self.storage.read()
             .map_err(|_| DarkResolverError::StorageError)?;
         let result = storage.get(key).cloned();
         info!("DHT: Retrieved {:?} bytes from key", result.as_ref().map(|v| v.len()));
-        Ok(result)
-    }
-    
-    fn remove(&self, key: &[u8]) -> Result<(), DarkResolverError> {
-        let mut storage = self.storage.write()
-            .map_err(|_| DarkResolverError::StorageError)?;
-        storage.remove(key);
-        info!("DHT: Removed key");
-        Ok(())
+        Ok(result.into_iter().collect())
     }
 }
 
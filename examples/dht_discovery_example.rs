@@ -1,16 +1,16 @@
 //! Example demonstrating Kademlia DHT for decentralized peer discovery
-//! 
+//!
 //! This example shows how to use the QuDAG network layer with Kademlia DHT
 //! for peer discovery, content routing, and dark addressing support.
 
+use libp2p::PeerId as LibP2PPeerId;
 use qudag_network::{
-    BootstrapConfig, ContentRoutingConfig, KademliaDHT, PeerReputation,
-    DiscoveryConfig, DiscoveryMethod, KademliaPeerDiscovery, DiscoveryEvent,
+    BootstrapConfig, ContentRoutingConfig, DiscoveryConfig, DiscoveryEvent, DiscoveryMethod,
+    KademliaDHT, KademliaPeerDiscovery, PeerReputation,
 };
-use libp2p::PeerId as LibP2PPeerId;
 use std::time::Duration;
 use tokio::sync::mpsc;
-use tracing::{info, error, debug};
+use tracing::{debug, error, info};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -103,6 +103,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         scoring_config: scoring_config.clone(),
         load_balancing_config: Default::default(),
         geo_preferences: Default::default(),
+        dns_seeds: None,
     };
 
     let mut peer_discovery = KademliaPeerDiscovery::new(discovery_config);
@@ -113,60 +114,78 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         while let Some(event) = event_rx.recv().await {
             match event {
                 DiscoveryEvent::PeerDiscovered(peer) => {
-                    info!("Discovered new peer: {} via {:?}", 
-                          peer.peer_id, peer.discovery_method);
-                    debug!("Peer details: reputation={}, addresses={:?}", 
-                           peer.reputation, peer.addresses);
+                    info!(
+                        "Discovered new peer: {} via {:?}",
+                        peer.peer_id, peer.discovery_method
+                    );
+                    debug!(
+                        "Peer details: reputation={}, addresses={:?}",
+                        peer.reputation, peer.addresses
+                    );
                 }
-                DiscoveryEvent::BootstrapCompleted { 
-                    peers_discovered, 
-                    duration, 
-                    success_rate 
+                DiscoveryEvent::BootstrapCompleted {
+                    peers_discovered,
+                    duration,
+                    success_rate,
                 } => {
-                    info!("Bootstrap completed: {} peers discovered in {:?} (success rate: {:.2}%)",
-                          peers_discovered, duration, success_rate * 100.0);
+                    info!(
+                        "Bootstrap completed: {} peers discovered in {:?} (success rate: {:.2}%)",
+                        peers_discovered,
+                        duration,
+                        success_rate * 100.0
+                    );
                 }
                 DiscoveryEvent::BootstrapFailed { reason, .. } => {
                     error!("Bootstrap failed: {}", reason);
                 }
-                DiscoveryEvent::ReputationUpdated { 
-                    peer_id, 
-                    new_reputation, 
-                    reason 
+                DiscoveryEvent::ReputationUpdated {
+                    peer_id,
+                    new_reputation,
+                    reason,
                 } => {
-                    debug!("Peer {} reputation updated to {:.2}: {}", 
-                           peer_id, new_reputation, reason);
+                    debug!(
+                        "Peer {} reputation updated to {:.2}: {}",
+                        peer_id, new_reputation, reason
+                    );
                 }
-                DiscoveryEvent::PeerBlacklisted { peer_id, reason, .. } => {
+                DiscoveryEvent::PeerBlacklisted {
+                    peer_id, reason, ..
+                } => {
                     info!("Peer {} blacklisted: {}", peer_id, reason);
                 }
-                DiscoveryEvent::DarkAddressDiscovered { 
-                    peer_id, 
-                    dark_address, 
-                    resolution_time 
+                DiscoveryEvent::DarkAddressDiscovered {
+                    peer_id,
+                    dark_address,
+                    resolution_time,
                 } => {
-                    info!("Dark address discovered for peer {} in {:?}",
-                          peer_id, resolution_time);
+                    info!(
+                        "Dark address discovered for peer {} in {:?}",
+                        peer_id, resolution_time
+                    );
                     debug!("Dark address details: {:?}", dark_address);
                 }
-                DiscoveryEvent::TopologyUpdated { 
-                    largest_component_size, 
-                    avg_clustering, 
-                    diameter 
+                DiscoveryEvent::TopologyUpdated {
+                    largest_component_size,
+                    avg_clustering,
+                    diameter,
                 } => {
-                    info!("Network topology updated: {} nodes in largest component, clustering={:.3}",
-                          largest_component_size, avg_clustering);
+                    info!(
+                        "Network topology updated: {} nodes in largest component, clustering={:.3}",
+                        largest_component_size, avg_clustering
+                    );
                     if let Some(d) = diameter {
                         debug!("Network diameter: {}", d);
                     }
                 }
-                DiscoveryEvent::DHTBucketUpdated { 
-                    bucket_index, 
-                    peer_count, 
-                    health_score 
+                DiscoveryEvent::DHTBucketUpdated {
+                    bucket_index,
+                    peer_count,
+                    health_score,
                 } => {
-                    debug!("DHT bucket {} updated: {} peers, health={:.2}",
-                           bucket_index, peer_count, health_score);
+                    debug!(
+                        "DHT bucket {} updated: {} peers, health={:.2}",
+                        bucket_index, peer_count, health_score
+                    );
                 }
                 _ => {}
             }
@@ -185,7 +204,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Store some content in the DHT
         let content_key = b"example_content_key".to_vec();
         let content_value = b"This is example content stored in the DHT".to_vec();
-        
+
         if let Err(e) = dht.store_record(content_key.clone(), content_value).await {
             error!("Failed to store content: {:?}", e);
         } else {
@@ -194,7 +213,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // Wait a bit then try to retrieve it
         tokio::time::sleep(Duration::from_secs(2)).await;
-        
+
         if let Err(e) = dht.get_record(content_key).await {
             error!("Failed to retrieve content: {:?}", e);
         } else {
@@ -212,19 +231,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Periodically show DHT metrics
         loop {
             tokio::time::sleep(Duration::from_secs(30)).await;
-            
+
             let metrics = dht.get_metrics();
-            info!("DHT Metrics: {} total queries, {} successful, routing table size: {}",
-                  metrics.total_queries, metrics.successful_queries, metrics.routing_table_size);
-            
+            info!(
+                "DHT Metrics: {} total queries, {} successful, routing table size: {}",
+                metrics.total_queries, metrics.successful_queries, metrics.routing_table_size
+            );
+
             if let Some(top_peers) = dht.get_top_peers(5).await.get(0..5) {
                 info!("Top 5 peers by reputation:");
                 for (i, peer) in top_peers.iter().enumerate() {
-                    info!("  {}. {} (score: {:.2}, interactions: {})",
-                          i + 1, peer.peer_id, peer.score, peer.total_interactions);
+                    info!(
+                        "  {}. {} (score: {:.2}, interactions: {})",
+                        i + 1,
+                        peer.peer_id,
+                        peer.score,
+                        peer.total_interactions
+                    );
                 }
             }
-            
+
             // Perform maintenance
             dht.perform_maintenance().await;
         }
@@ -234,17 +260,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tokio::spawn(async move {
         loop {
             tokio::time::sleep(Duration::from_secs(60)).await;
-            
+
             // Get discovery statistics
             let stats = peer_discovery.get_discovery_stats().await;
-            info!("Discovery Stats: {} total peers, {} connectable, avg reputation: {:.2}",
-                  stats.total_discovered, stats.connectable_peers, stats.average_reputation);
-            
+            info!(
+                "Discovery Stats: {} total peers, {} connectable, avg reputation: {:.2}",
+                stats.total_discovered, stats.connectable_peers, stats.average_reputation
+            );
+
             // Show method breakdown
             for (method, count) in &stats.method_counts {
                 debug!("  {:?}: {} peers", method, count);
             }
-            
+
             // Clean up old peers
             peer_discovery.cleanup_old_peers().await;
         }
@@ -252,7 +280,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Run for demonstration purposes
     info!("DHT discovery example running. Press Ctrl+C to stop.");
-    
+
     // Keep the main task running
     tokio::select! {
         _ = tokio::signal::ctrl_c() => {
@@ -268,4 +296,4 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("DHT discovery example completed");
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -10,6 +10,7 @@ use qudag_network::{
     NetworkManager, NetworkConfig, NatTraversalConfig, NatTraversalManager,
     NatType, PortMappingProtocol, StunServer, TurnServer, ConnectionManager
 };
+use qudag_network::port_mapping::SSDP_MULTICAST;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -41,6 +42,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         detection_interval: Duration::from_secs(300),
         upgrade_interval: Duration::from_secs(60),
         port_mapping_lifetime: Duration::from_secs(3600),
+        ssdp_target: SSDP_MULTICAST,
+        nat_pmp_gateway: None,
     };
     
     // Create network configuration with NAT traversal enabled
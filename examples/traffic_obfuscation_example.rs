@@ -13,7 +13,7 @@ use qudag_network::{
         ObfuscationPattern, TrafficObfuscationConfig, TrafficObfuscator,
         DEFAULT_MESSAGE_SIZE, STANDARD_MESSAGE_SIZES,
     },
-    transport::{SecureTransport, Transport, TransportConfig},
+    transport::{utils::to_p2p_config, TransportConfig},
     types::{MessagePriority, NetworkMessage},
 };
 use std::time::Duration;
@@ -226,17 +226,13 @@ async fn transport_obfuscation_example() -> Result<(), Box<dyn std::error::Error
         ..Default::default()
     };
 
-    // Create secure transport with obfuscation
-    let mut transport = SecureTransport::with_config(transport_config.clone());
-    transport.init(transport_config).await?;
+    // Nodes apply the transport configuration to their libp2p transport
+    let p2p_config = to_p2p_config(&transport_config);
 
-    info!("Transport initialized with traffic obfuscation");
-    
-    // Get transport statistics
-    let stats = transport.get_stats();
-    info!("Transport stats:");
-    info!("  Total connections: {}", stats.total_connections);
-    info!("  Active connections: {}", stats.active_connections);
+    info!("Node configured with traffic obfuscation");
+    info!("Node transport:");
+    info!("  Security: {:?}", p2p_config.security);
+    info!("  Max connections: {}", p2p_config.max_connections);
 
     // Demonstrate message size options
     info!("\nAvailable standard message sizes:");
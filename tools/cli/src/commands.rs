@@ -2,6 +2,7 @@ use crate::peer_manager::{PeerManager, PeerManagerConfig};
 use crate::rpc::{NodeStatus, RpcClient};
 use crate::CliError;
 use anyhow::Result;
+use qudag_crypto::key_encoding::{EncodedKey, KeyAlgorithm, KeyFormat};
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -1704,8 +1705,150 @@ impl CommandRouter {
         Ok(())
     }
 
+    // ===== KEY COMMAND HANDLERS =====
+
+    /// Route and execute key generate command
+    pub async fn handle_key_generate(
+        &self,
+        algorithm: String,
+        output: Option<PathBuf>,
+        public_output: Option<PathBuf>,
+        format: String,
+        encrypt: bool,
+    ) -> Result<(), CliError> {
+        info!("Executing key generate command for {}", algorithm);
+
+        let algorithm: KeyAlgorithm = algorithm
+            .parse()
+            .map_err(|e| CliError::Command(format!("{}", e)))?;
+        let format = parse_key_format(&format)?;
+        let key = EncodedKey::generate(algorithm)
+            .map_err(|e| CliError::Command(format!("Failed to generate key: {}", e)))?;
+
+        self.write_key(&key, output.as_ref(), format, encrypt)?;
+        if let Some(path) = public_output.as_ref() {
+            self.write_key(&key.to_public(), Some(path), format, false)?;
+        }
+
+        eprintln!("✓ Generated {} key pair", algorithm);
+        Ok(())
+    }
+
+    /// Route and execute key convert command
+    pub async fn handle_key_convert(
+        &self,
+        input: PathBuf,
+        output: Option<PathBuf>,
+        format: String,
+        encrypt: bool,
+        public: bool,
+    ) -> Result<(), CliError> {
+        info!("Executing key convert command for {:?}", input);
+
+        let format = parse_key_format(&format)?;
+        let key = self.read_key(&input)?;
+        let key = if public { key.to_public() } else { key };
+
+        self.write_key(&key, output.as_ref(), format, encrypt)?;
+        eprintln!("✓ Converted {} key", key.algorithm());
+        Ok(())
+    }
+
+    /// Route and execute key import command
+    pub async fn handle_key_import(&self, input: PathBuf, label: String) -> Result<(), CliError> {
+        info!("Executing key import command for label: {}", label);
+
+        let key = self.read_key(&input)?;
+
+        let vault_path = self.get_vault_path()?;
+        let master_password = self.prompt_password("Enter master password: ")?;
+
+        use qudag_vault_core::Vault;
+        let mut vault = Vault::open(&vault_path, &master_password)
+            .map_err(|e| CliError::Command(format!("Failed to open vault: {}", e)))?;
+        vault
+            .add_key(&label, &key)
+            .map_err(|e| CliError::Command(format!("Failed to store key: {}", e)))?;
+
+        println!("✓ Imported {} key as {}", key.algorithm(), label);
+        Ok(())
+    }
+
+    /// Route and execute key export command
+    pub async fn handle_key_export(
+        &self,
+        label: String,
+        output: Option<PathBuf>,
+        format: String,
+        encrypt: bool,
+        public: bool,
+    ) -> Result<(), CliError> {
+        info!("Executing key export command for label: {}", label);
+
+        let format = parse_key_format(&format)?;
+        let vault_path = self.get_vault_path()?;
+        let master_password = self.prompt_password("Enter master password: ")?;
+
+        use qudag_vault_core::Vault;
+        let vault = Vault::open(&vault_path, &master_password)
+            .map_err(|e| CliError::Command(format!("Failed to open vault: {}", e)))?;
+        let key = vault
+            .get_key(&label)
+            .map_err(|e| CliError::Command(format!("Failed to get key: {}", e)))?;
+        let key = if public { key.to_public() } else { key };
+
+        self.write_key(&key, output.as_ref(), format, encrypt)?;
+        eprintln!("✓ Exported {} key {}", key.algorithm(), label);
+        Ok(())
+    }
+
     // ===== HELPER METHODS =====
 
+    /// Read a key file in any supported format, prompting for its password if needed
+    fn read_key(&self, input: &PathBuf) -> Result<EncodedKey, CliError> {
+        let data = std::fs::read(input)
+            .map_err(|e| CliError::Command(format!("Failed to read {:?}: {}", input, e)))?;
+        let encrypted = EncodedKey::is_encrypted(&data)
+            .map_err(|e| CliError::Command(format!("Invalid key file: {}", e)))?;
+        let password = if encrypted {
+            Some(self.prompt_password("Enter key password: ")?)
+        } else {
+            None
+        };
+        EncodedKey::decode(&data, password.as_deref())
+            .map_err(|e| CliError::Command(format!("Failed to decode key: {}", e)))
+    }
+
+    /// Encode a key and write it to `output`, or stdout for text formats
+    fn write_key(
+        &self,
+        key: &EncodedKey,
+        output: Option<&PathBuf>,
+        format: KeyFormat,
+        encrypt: bool,
+    ) -> Result<(), CliError> {
+        let password = if encrypt && key.is_secret() {
+            Some(self.prompt_new_password("Enter key password: ")?)
+        } else {
+            None
+        };
+        let encoded = key
+            .encode(format, password.as_deref())
+            .map_err(|e| CliError::Command(format!("Failed to encode key: {}", e)))?;
+
+        match output {
+            Some(path) => std::fs::write(path, encoded)
+                .map_err(|e| CliError::Command(format!("Failed to write {:?}: {}", path, e))),
+            None if format == KeyFormat::Binary => Err(CliError::Command(
+                "Binary keys must be written to a file (use --output)".to_string(),
+            )),
+            None => {
+                println!("{}", String::from_utf8_lossy(&encoded).trim_end());
+                Ok(())
+            }
+        }
+    }
+
     /// Prompt for password (hidden input)
     fn prompt_password(&self, prompt: &str) -> Result<String, CliError> {
         use rpassword::read_password;
@@ -1760,6 +1903,13 @@ impl CommandRouter {
     }
 }
 
+/// Parse a `--format` argument for key commands
+fn parse_key_format(format: &str) -> Result<KeyFormat, CliError> {
+    format
+        .parse()
+        .map_err(|e| CliError::Command(format!("{}", e)))
+}
+
 // Keep existing command implementations below for backward compatibility
 
 pub async fn start_node(
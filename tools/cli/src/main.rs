@@ -132,6 +132,12 @@ enum Commands {
         command: VaultCommands,
     },
 
+    /// Key generation, conversion and vault import/export
+    Key {
+        #[command(subcommand)]
+        command: KeyCommands,
+    },
+
     /// MCP server commands
     Mcp {
         #[command(subcommand)]
@@ -409,6 +415,85 @@ enum VaultConfigCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum KeyCommands {
+    /// Generate a new key pair
+    Generate {
+        /// Key algorithm (ML-DSA-65, ML-KEM-768, HQC-128, HQC-192, HQC-256)
+        #[arg(short, long, default_value = "ML-DSA-65")]
+        algorithm: String,
+
+        /// Output file for the secret key (printed if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Also write the public key to this file
+        #[arg(long)]
+        public_output: Option<PathBuf>,
+
+        /// Output format (binary, armored, json)
+        #[arg(short, long, default_value = "armored")]
+        format: String,
+
+        /// Protect the secret key with a password
+        #[arg(short, long)]
+        encrypt: bool,
+    },
+
+    /// Convert a key file between formats
+    Convert {
+        /// Input key file
+        input: PathBuf,
+
+        /// Output file (printed if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Output format (binary, armored, json)
+        #[arg(short, long, default_value = "armored")]
+        format: String,
+
+        /// Protect the secret key with a password
+        #[arg(short, long)]
+        encrypt: bool,
+
+        /// Write only the public key
+        #[arg(long)]
+        public: bool,
+    },
+
+    /// Import a key file into the vault
+    Import {
+        /// Input key file
+        input: PathBuf,
+
+        /// Vault label for the key (e.g., "keys/identity")
+        label: String,
+    },
+
+    /// Export a key from the vault
+    Export {
+        /// Vault label of the key
+        label: String,
+
+        /// Output file (printed if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Output format (binary, armored, json)
+        #[arg(short, long, default_value = "armored")]
+        format: String,
+
+        /// Protect the secret key with a password
+        #[arg(short, long)]
+        encrypt: bool,
+
+        /// Export only the public key
+        #[arg(long)]
+        public: bool,
+    },
+}
+
 #[derive(Subcommand)]
 enum McpCommands {
     /// Start MCP server
@@ -1352,6 +1437,54 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        Commands::Key { command } => {
+            let router = qudag_cli::CommandRouter::new();
+
+            let result = match command {
+                KeyCommands::Generate {
+                    algorithm,
+                    output,
+                    public_output,
+                    format,
+                    encrypt,
+                } => {
+                    router
+                        .handle_key_generate(algorithm, output, public_output, format, encrypt)
+                        .await
+                }
+                KeyCommands::Convert {
+                    input,
+                    output,
+                    format,
+                    encrypt,
+                    public,
+                } => {
+                    router
+                        .handle_key_convert(input, output, format, encrypt, public)
+                        .await
+                }
+                KeyCommands::Import { input, label } => {
+                    router.handle_key_import(input, label).await
+                }
+                KeyCommands::Export {
+                    label,
+                    output,
+                    format,
+                    encrypt,
+                    public,
+                } => {
+                    router
+                        .handle_key_export(label, output, format, encrypt, public)
+                        .await
+                }
+            };
+
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
         Commands::Exchange { command } => {
             match command {
                 ExchangeCommands::CreateAccount { name } => {
@@ -1686,11 +1819,25 @@ async fn run_node(node_config: NodeConfig) -> Result<(), Box<dyn std::error::Err
         .await
         .map_err(|e| format!("Failed to create data directory: {}", e))?;
 
+    // Load the persistent node identity so the PeerId survives restarts
+    let identity_password = std::env::var("QUDAG_IDENTITY_PASSWORD").ok();
+    let identity_path = node_config
+        .data_dir
+        .join(qudag_network::node_identity::IDENTITY_FILE);
+    let identity = tokio::task::spawn_blocking(move || {
+        qudag_network::NodeIdentity::load_or_create(identity_path, identity_password.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Failed to load node identity: {}", e))?
+    .map_err(|e| format!("Failed to load node identity: {}", e))?;
+
     // Create P2P network configuration
     let p2p_config = qudag_network::p2p::NetworkConfig {
         listen_addrs: vec![format!("/ip4/0.0.0.0/tcp/{}", node_config.network_port)],
         bootstrap_peers: node_config.initial_peers.clone(),
         max_connections: node_config.max_peers,
+        identity: Some(identity),
+        data_dir: Some(node_config.data_dir.clone()),
         ..Default::default()
     };
 